@@ -0,0 +1,181 @@
+use crate::infra::{
+    common_component::{CommonComponent, CommonComponentParts},
+    graphql::DateTimeUtc,
+};
+use anyhow::{Error, Result, bail};
+use chrono::{NaiveDateTime, Utc};
+use graphql_client::GraphQLQuery;
+use std::str::FromStr;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/set_user_expiration_date.graphql",
+    response_derives = "Debug",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct SetUserExpirationDate;
+
+/// The expiration date of a user account, with the controls to change it for admins.
+pub struct AccountExpirationComponent {
+    common: CommonComponentParts<Self>,
+    new_date: Option<NaiveDateTime>,
+}
+
+#[derive(yew::Properties, Clone, PartialEq)]
+pub struct Props {
+    pub username: String,
+    pub expiration_date: Option<DateTimeUtc>,
+    pub is_admin: bool,
+    pub on_expiration_changed: Callback<()>,
+    pub on_error: Callback<Error>,
+}
+
+pub enum Msg {
+    NewDateChanged(String),
+    SubmitSetDate,
+    SubmitRemoveDate,
+    SetUserExpirationDateResponse(Result<set_user_expiration_date::ResponseData>),
+}
+
+impl CommonComponent<AccountExpirationComponent> for AccountExpirationComponent {
+    fn handle_msg(
+        &mut self,
+        ctx: &Context<Self>,
+        msg: <Self as Component>::Message,
+    ) -> Result<bool> {
+        match msg {
+            Msg::NewDateChanged(date) => self.new_date = NaiveDateTime::from_str(&date).ok(),
+            Msg::SubmitSetDate => {
+                let Some(new_date) = self.new_date else {
+                    bail!("Pick an expiration date first");
+                };
+                self.submit_expiration_date(ctx, Some(new_date.and_utc()));
+            }
+            Msg::SubmitRemoveDate => self.submit_expiration_date(ctx, None),
+            Msg::SetUserExpirationDateResponse(response) => {
+                response?;
+                self.new_date = None;
+                ctx.props().on_expiration_changed.emit(());
+            }
+        }
+        Ok(true)
+    }
+
+    fn mut_common(&mut self) -> &mut CommonComponentParts<Self> {
+        &mut self.common
+    }
+}
+
+impl AccountExpirationComponent {
+    fn submit_expiration_date(
+        &mut self,
+        ctx: &Context<Self>,
+        expiration_date: Option<DateTimeUtc>,
+    ) {
+        self.common.call_graphql::<SetUserExpirationDate, _>(
+            ctx,
+            set_user_expiration_date::Variables {
+                user: ctx.props().username.clone(),
+                expiration_date,
+            },
+            Msg::SetUserExpirationDateResponse,
+            "Error trying to update the expiration date",
+        );
+    }
+
+    fn view_current_date(&self, ctx: &Context<Self>) -> Html {
+        match &ctx.props().expiration_date {
+            None => html! {<span>{"This account never expires."}</span>},
+            Some(date) => {
+                let formatted = date.naive_utc().format("%Y-%m-%d %H:%M UTC").to_string();
+                if *date <= Utc::now() {
+                    html! {<span class="text-danger">{"Expired on "}{formatted}</span>}
+                } else {
+                    html! {<span>{"Expires on "}{formatted}</span>}
+                }
+            }
+        }
+    }
+
+    fn view_controls(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
+        if !ctx.props().is_admin {
+            return html! {};
+        }
+        html! {
+          <div class="row mt-2">
+            <div class="col-sm-6">
+              <div class="input-group">
+                <input
+                  type="datetime-local"
+                  class="form-control"
+                  onchange={link.callback(|e: Event| {
+                      Msg::NewDateChanged(
+                          e.target()
+                           .expect("Event should have target")
+                           .unchecked_into::<HtmlInputElement>()
+                           .value()
+                      )
+                  })} />
+                <span class="input-group-text">{"UTC"}</span>
+              </div>
+            </div>
+            <div class="col-sm-6">
+              <button
+                class="btn btn-secondary me-2"
+                disabled={self.common.is_task_running()}
+                onclick={link.callback(|_| Msg::SubmitSetDate)}>
+                <i class="bi-calendar-event me-2"></i>
+                {"Set expiration"}
+              </button>
+              {if ctx.props().expiration_date.is_some() { html! {
+                <button
+                  class="btn btn-outline-secondary"
+                  disabled={self.common.is_task_running()}
+                  onclick={link.callback(|_| Msg::SubmitRemoveDate)}>
+                  <i class="bi-calendar-x me-2"></i>
+                  {"Never expire"}
+                </button>
+              } } else { html! {} } }
+            </div>
+          </div>
+        }
+    }
+}
+
+impl Component for AccountExpirationComponent {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_: &Context<Self>) -> Self {
+        Self {
+            common: CommonComponentParts::<Self>::create(),
+            new_date: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        CommonComponentParts::<Self>::update_and_report_error(
+            self,
+            ctx,
+            msg,
+            ctx.props().on_error.clone(),
+        )
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+          <>
+            <h5 class="row m-3 fw-bold">{"Account expiration"}</h5>
+            <div class="mx-3">
+              {self.view_current_date(ctx)}
+              {self.view_controls(ctx)}
+            </div>
+          </>
+        }
+    }
+}
@@ -4,6 +4,8 @@ use crate::{
         change_password::ChangePasswordForm,
         create_group::CreateGroupForm,
         create_group_attribute::CreateGroupAttributeForm,
+        create_service_account::CreateServiceAccountForm,
+        create_sudo_role::CreateSudoRoleForm,
         create_user::CreateUserForm,
         create_user_attribute::CreateUserAttributeForm,
         group_details::GroupDetails,
@@ -13,11 +15,16 @@ use crate::{
         reset_password_step1::ResetPasswordStep1Form,
         reset_password_step2::ResetPasswordStep2Form,
         router::{AppRoute, Link, Redirect},
+        service_account_table::ServiceAccountTable,
+        sudo_role_table::SudoRoleTable,
         user_details::UserDetails,
         user_schema_table::ListUserSchema,
         user_table::UserTable,
     },
-    infra::{api::HostService, cookies::get_cookie},
+    infra::{
+        api::{HostService, LoginInfo},
+        cookies::get_cookie,
+    },
 };
 
 use gloo_console::error;
@@ -46,10 +53,11 @@ pub struct App {
     user_info: Option<(String, bool)>,
     redirect_to: Option<AppRoute>,
     password_reset_enabled: Option<bool>,
+    webauthn_enabled: bool,
 }
 
 pub enum Msg {
-    Login((String, bool)),
+    Login(LoginInfo),
     Logout,
     SettingsReceived(anyhow::Result<Options>),
 }
@@ -75,6 +83,7 @@ impl Component for App {
                 }),
             redirect_to: Self::get_redirect_route(ctx),
             password_reset_enabled: None,
+            webauthn_enabled: false,
         };
         ctx.link()
             .send_future(async move { Msg::SettingsReceived(HostService::get_settings().await) });
@@ -85,8 +94,27 @@ impl Component for App {
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         let history = ctx.link().history().unwrap();
         match msg {
-            Msg::Login((user_name, is_admin)) => {
+            Msg::Login(LoginInfo {
+                user_id: user_name,
+                is_admin,
+                must_change_password,
+                must_enroll_mfa,
+            }) => {
                 self.user_info = Some((user_name.clone(), is_admin));
+                if must_change_password {
+                    // The password has expired or was reset by an admin.
+                    history.push(AppRoute::ChangePassword {
+                        user_id: user_name.clone(),
+                    });
+                    return true;
+                }
+                if must_enroll_mfa {
+                    // The second factor is set up from the user details page.
+                    history.push(AppRoute::UserDetails {
+                        user_id: user_name.clone(),
+                    });
+                    return true;
+                }
                 history.push(self.redirect_to.take().unwrap_or_else(|| {
                     if is_admin {
                         AppRoute::ListUsers
@@ -104,6 +132,7 @@ impl Component for App {
             }
             Msg::SettingsReceived(Ok(settings)) => {
                 self.password_reset_enabled = Some(settings.password_reset_enabled);
+                self.webauthn_enabled = settings.webauthn_enabled;
             }
             Msg::SettingsReceived(Err(err)) => {
                 error!(err.to_string());
@@ -117,6 +146,7 @@ impl Component for App {
         let is_admin = self.is_admin();
         let username = self.user_info.clone().map(|(username, _)| username);
         let password_reset_enabled = self.password_reset_enabled;
+        let webauthn_enabled = self.webauthn_enabled;
         html! {
           <div>
             <Banner is_admin={is_admin} username={username} on_logged_out={link.callback(|_| Msg::Logout)} />
@@ -124,7 +154,7 @@ impl Component for App {
               <div class="row justify-content-center" style="padding-bottom: 80px;">
                 <main class="py-3">
                   <Switch<AppRoute>
-                    render={Switch::render(move |routes| Self::dispatch_route(routes, &link, is_admin, password_reset_enabled))}
+                    render={Switch::render(move |routes| Self::dispatch_route(routes, &link, is_admin, password_reset_enabled, webauthn_enabled))}
                   />
                 </main>
               </div>
@@ -188,10 +218,11 @@ impl App {
         link: &Scope<Self>,
         is_admin: bool,
         password_reset_enabled: Option<bool>,
+        webauthn_enabled: bool,
     ) -> Html {
         match switch {
             AppRoute::Login => html! {
-                <LoginForm on_logged_in={link.callback(Msg::Login)} password_reset_enabled={password_reset_enabled.unwrap_or(false)}/>
+                <LoginForm on_logged_in={link.callback(Msg::Login)} password_reset_enabled={password_reset_enabled.unwrap_or(false)} webauthn_enabled={webauthn_enabled}/>
             },
             AppRoute::CreateUser => html! {
                 <CreateUserForm/>
@@ -237,6 +268,42 @@ impl App {
                   </div>
                 }
             }
+            AppRoute::CreateSudoRole => html! {
+                <CreateSudoRoleForm/>
+            },
+            AppRoute::ListSudoRoles => {
+                let sudo_role_button = html! {
+                  <Link classes="btn btn-primary" to={AppRoute::CreateSudoRole}>
+                    <i class="bi-plus-circle me-2"></i>
+                    {"Create a sudo rule"}
+                  </Link>
+                };
+                html! {
+                  <div>
+                    { sudo_role_button.clone() }
+                    <SudoRoleTable />
+                    { sudo_role_button }
+                  </div>
+                }
+            }
+            AppRoute::CreateServiceAccount => html! {
+                <CreateServiceAccountForm/>
+            },
+            AppRoute::ListServiceAccounts => {
+                let service_account_button = html! {
+                  <Link classes="btn btn-primary" to={AppRoute::CreateServiceAccount}>
+                    <i class="bi-plus-circle me-2"></i>
+                    {"Create a service account"}
+                  </Link>
+                };
+                html! {
+                  <div>
+                    { service_account_button.clone() }
+                    <ServiceAccountTable />
+                    { service_account_button }
+                  </div>
+                }
+            }
             AppRoute::ListUserSchema => html! {
                 <ListUserSchema />
             },
@@ -247,7 +314,7 @@ impl App {
                 <GroupDetails group_id={*group_id} is_admin={is_admin} />
             },
             AppRoute::UserDetails { user_id } => html! {
-                <UserDetails username={user_id.clone()} is_admin={is_admin} />
+                <UserDetails username={user_id.clone()} is_admin={is_admin} webauthn_enabled={webauthn_enabled} />
             },
             AppRoute::ChangePassword { user_id } => html! {
                 <ChangePasswordForm username={user_id.clone()} is_admin={is_admin} />
@@ -0,0 +1,343 @@
+use crate::{
+    components::{
+        select::{Select, SelectOption, SelectOptionProps},
+        user_details::{AppPassword, Group},
+    },
+    infra::{
+        common_component::{CommonComponent, CommonComponentParts},
+        cookies::get_cookie,
+    },
+};
+use anyhow::{Error, Result, bail};
+use graphql_client::GraphQLQuery;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/create_app_password.graphql",
+    response_derives = "Debug",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct CreateAppPassword;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/delete_app_password.graphql",
+    response_derives = "Debug",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct DeleteAppPassword;
+
+/// The passwords of a user for the applications binding over LDAP (mail clients, media
+/// servers...). Only the user themselves can generate new ones, since they are shown to them.
+pub struct AppPasswordsComponent {
+    common: CommonComponentParts<Self>,
+    new_name: String,
+    /// Separated by commas or spaces.
+    new_allowed_sources: String,
+    new_required_group: Option<i64>,
+    /// Only shown once, right after the creation.
+    created_password: Option<String>,
+}
+
+#[derive(yew::Properties, Clone, PartialEq)]
+pub struct Props {
+    pub username: String,
+    pub app_passwords: Vec<AppPassword>,
+    /// The groups of the user, to restrict a password to one of them.
+    pub groups: Vec<Group>,
+    pub on_app_passwords_changed: Callback<()>,
+    pub on_error: Callback<Error>,
+}
+
+pub enum Msg {
+    NameChanged(String),
+    AllowedSourcesChanged(String),
+    RequiredGroupChanged(Option<SelectOptionProps>),
+    SubmitCreate,
+    CreateAppPasswordResponse(Result<create_app_password::ResponseData>),
+    PasswordSaved,
+    SubmitDelete(i64),
+    DeleteAppPasswordResponse(Result<delete_app_password::ResponseData>),
+}
+
+impl CommonComponent<AppPasswordsComponent> for AppPasswordsComponent {
+    fn handle_msg(
+        &mut self,
+        ctx: &Context<Self>,
+        msg: <Self as Component>::Message,
+    ) -> Result<bool> {
+        match msg {
+            Msg::NameChanged(name) => self.new_name = name,
+            Msg::AllowedSourcesChanged(sources) => self.new_allowed_sources = sources,
+            Msg::RequiredGroupChanged(selection) => {
+                self.new_required_group = selection.and_then(|option| option.value.parse().ok());
+            }
+            Msg::SubmitCreate => {
+                let name = self.new_name.trim().to_owned();
+                if name.is_empty() {
+                    bail!("Give the app password a name first, e.g. \"Thunderbird\"");
+                }
+                let allowed_sources = self
+                    .new_allowed_sources
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|source| !source.is_empty())
+                    .map(str::to_owned)
+                    .collect();
+                self.common.call_graphql::<CreateAppPassword, _>(
+                    ctx,
+                    create_app_password::Variables {
+                        name,
+                        allowed_sources: Some(allowed_sources),
+                        required_group_id: self.new_required_group,
+                    },
+                    Msg::CreateAppPasswordResponse,
+                    "Error trying to create the app password",
+                );
+            }
+            Msg::CreateAppPasswordResponse(response) => {
+                self.created_password = Some(response?.create_app_password);
+                self.new_name.clear();
+                self.new_allowed_sources.clear();
+            }
+            Msg::PasswordSaved => {
+                self.created_password = None;
+                ctx.props().on_app_passwords_changed.emit(());
+            }
+            Msg::SubmitDelete(id) => {
+                self.common.call_graphql::<DeleteAppPassword, _>(
+                    ctx,
+                    delete_app_password::Variables {
+                        user: ctx.props().username.clone(),
+                        id,
+                    },
+                    Msg::DeleteAppPasswordResponse,
+                    "Error trying to revoke the app password",
+                );
+            }
+            Msg::DeleteAppPasswordResponse(response) => {
+                response?;
+                ctx.props().on_app_passwords_changed.emit(());
+            }
+        }
+        Ok(true)
+    }
+
+    fn mut_common(&mut self) -> &mut CommonComponentParts<Self> {
+        &mut self.common
+    }
+}
+
+fn get_input_value(e: InputEvent) -> String {
+    e.target()
+        .expect("Event should have target")
+        .unchecked_into::<HtmlInputElement>()
+        .value()
+}
+
+impl AppPasswordsComponent {
+    fn is_current_user(ctx: &Context<Self>) -> bool {
+        get_cookie("user_id")
+            .ok()
+            .flatten()
+            .is_some_and(|user_id| user_id == ctx.props().username)
+    }
+
+    fn view_restrictions(ctx: &Context<Self>, app_password: &AppPassword) -> Html {
+        let group = app_password.required_group_id.map(|group_id| {
+            ctx.props()
+                .groups
+                .iter()
+                .find(|g| g.id == group_id)
+                .map(|g| g.display_name.clone())
+                .unwrap_or_else(|| format!("group {group_id}"))
+        });
+        let mut restrictions = Vec::new();
+        if !app_password.allowed_sources.is_empty() {
+            restrictions.push(format!("From {}", app_password.allowed_sources.join(", ")));
+        }
+        if let Some(group) = group {
+            restrictions.push(format!("Members of {group}"));
+        }
+        if restrictions.is_empty() {
+            html! {{"None"}}
+        } else {
+            html! {<>{restrictions.join("; ")}</>}
+        }
+    }
+
+    fn view_app_password(&self, ctx: &Context<Self>, app_password: &AppPassword) -> Html {
+        let link = ctx.link();
+        let id = app_password.id;
+        html! {
+          <tr key={id.to_string()}>
+            <td>{&app_password.name}</td>
+            <td>{Self::view_restrictions(ctx, app_password)}</td>
+            <td>{&app_password.creation_date.naive_local().date()}</td>
+            <td>
+              {match &app_password.last_used_date {
+                  Some(date) => html! {<>{date.naive_local().date()}</>},
+                  None => html! {{"Never"}},
+              }}
+            </td>
+            <td>
+              <button
+                class="btn btn-danger"
+                disabled={self.common.is_task_running()}
+                onclick={link.callback(move |_| Msg::SubmitDelete(id))}>
+                <i class="bi-x-circle-fill" aria-label="Revoke" />
+              </button>
+            </td>
+          </tr>
+        }
+    }
+
+    fn view_created_password(&self, ctx: &Context<Self>, password: &str) -> Html {
+        let link = ctx.link();
+        html! {
+          <div class="mx-3">
+            <p>
+              {"Enter this password in the application instead of your main password. \
+                It will not be shown again."}
+            </p>
+            <p class="font-monospace fs-5">{password}</p>
+            <button
+              class="btn btn-primary"
+              onclick={link.callback(|_| Msg::PasswordSaved)}>
+              <i class="bi-check2 me-2"></i>
+              {"Done"}
+            </button>
+          </div>
+        }
+    }
+
+    fn view_create(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
+        if !Self::is_current_user(ctx) {
+            return html! {};
+        }
+        if let Some(password) = &self.created_password {
+            return self.view_created_password(ctx, password);
+        }
+        #[allow(unused_braces)]
+        let make_select_option = |group: &Group| {
+            html_nested! {
+                <SelectOption
+                  value={group.id.to_string()}
+                  text={format!("Only for {}", group.display_name)}
+                  key={group.id} />
+            }
+        };
+        html! {
+          <div class="row">
+            <div class="col-sm-3">
+              <input
+                type="text"
+                class="form-control"
+                placeholder="Name of the application"
+                value={self.new_name.clone()}
+                oninput={link.callback(|e: InputEvent| Msg::NameChanged(get_input_value(e)))} />
+            </div>
+            <div class="col-sm-3">
+              <input
+                type="text"
+                class="form-control"
+                placeholder="Allowed networks (optional)"
+                title="e.g. 192.168.1.0/24, 10.0.0.12"
+                value={self.new_allowed_sources.clone()}
+                oninput={link.callback(|e: InputEvent| {
+                    Msg::AllowedSourcesChanged(get_input_value(e))
+                })} />
+            </div>
+            <div class="col-sm-3">
+              <Select on_selection_change={link.callback(Msg::RequiredGroupChanged)}>
+                <SelectOption value="" text="Any group" key="any" />
+                {
+                  ctx.props()
+                    .groups
+                    .iter()
+                    .map(make_select_option)
+                    .collect::<Vec<_>>()
+                }
+              </Select>
+            </div>
+            <div class="col-sm-3">
+              <button
+                class="btn btn-secondary"
+                disabled={self.common.is_task_running()}
+                onclick={link.callback(|_| Msg::SubmitCreate)}>
+                <i class="bi-key me-2"></i>
+                {"Create app password"}
+              </button>
+            </div>
+          </div>
+        }
+    }
+}
+
+impl Component for AppPasswordsComponent {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_: &Context<Self>) -> Self {
+        Self {
+            common: CommonComponentParts::<Self>::create(),
+            new_name: String::new(),
+            new_allowed_sources: String::new(),
+            new_required_group: None,
+            created_password: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        CommonComponentParts::<Self>::update_and_report_error(
+            self,
+            ctx,
+            msg,
+            ctx.props().on_error.clone(),
+        )
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let app_passwords = &ctx.props().app_passwords;
+        html! {
+          <>
+            <h5 class="row m-3 fw-bold">{"App passwords"}</h5>
+            <div class="table-responsive">
+              <table class="table table-hover">
+                <thead>
+                  <tr key="headerRow">
+                    <th>{"Name"}</th>
+                    <th>{"Restrictions"}</th>
+                    <th>{"Created"}</th>
+                    <th>{"Last used"}</th>
+                    <th></th>
+                  </tr>
+                </thead>
+                <tbody>
+                  {if app_passwords.is_empty() {
+                    html! {
+                      <tr key="EmptyRow">
+                        <td>{"This user has no app passwords."}</td>
+                      </tr>
+                    }
+                  } else {
+                    html! {<>{
+                      app_passwords
+                        .iter()
+                        .map(|p| self.view_app_password(ctx, p))
+                        .collect::<Vec<_>>()
+                    }</>}
+                  }}
+                </tbody>
+              </table>
+            </div>
+            {self.view_create(ctx)}
+          </>
+        }
+    }
+}
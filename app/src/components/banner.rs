@@ -58,6 +58,22 @@ pub fn banner(props: &Props) -> Html {
                       {"Group schema"}
                     </Link>
                   </li>
+                  <li>
+                    <Link
+                      classes="nav-link px-2 h6"
+                      to={AppRoute::ListSudoRoles}>
+                      <i class="bi-shield-lock me-2"></i>
+                      {"Sudo rules"}
+                    </Link>
+                  </li>
+                  <li>
+                    <Link
+                      classes="nav-link px-2 h6"
+                      to={AppRoute::ListServiceAccounts}>
+                      <i class="bi-robot me-2"></i>
+                      {"Service accounts"}
+                    </Link>
+                  </li>
                 </>
               } } else { html!{} } }
             </ul>
@@ -6,11 +6,17 @@ use crate::{
     infra::{
         api::HostService,
         common_component::{CommonComponent, CommonComponentParts},
+        password_policy::{
+            check_breached_password, check_new_password, get_breached_password_prefix,
+            get_password_fingerprint,
+        },
     },
 };
 use anyhow::{Result, anyhow, bail};
 use gloo_console::error;
 use lldap_auth::*;
+use lldap_frontend_options::Options;
+use lldap_validation::{breached_passwords::BreachedPasswordRange, password::PasswordPolicy};
 use validator_derive::Validate;
 use yew::prelude::*;
 use yew_form::Form;
@@ -39,7 +45,6 @@ pub struct FormModel {
         message = "Password should be longer than 8 characters"
     ))]
     old_password: String,
-    #[validate(length(min = 8, message = "Invalid password. Min length: 8"))]
     password: String,
     #[validate(must_match(other = "password", message = "Passwords must match"))]
     confirm_password: String,
@@ -57,6 +62,8 @@ pub struct ChangePasswordForm {
     common: CommonComponentParts<Self>,
     form: Form<FormModel>,
     opaque_data: OpaqueData,
+    password_policy: PasswordPolicy,
+    breached_password_check_enabled: bool,
 }
 
 #[derive(Clone, PartialEq, Eq, Properties)]
@@ -66,8 +73,11 @@ pub struct Props {
 }
 
 pub enum Msg {
+    SettingsReceived(Result<Options>),
     FormUpdate,
     Submit,
+    BreachedPasswordRangeResponse(Result<BreachedPasswordRange>),
+    PasswordChecked,
     AuthenticationStartResponse(Result<Box<login::ServerLoginStartResponse>>),
     SubmitNewPassword,
     RegistrationStartResponse(Result<Box<registration::ServerRegistrationStartResponse>>),
@@ -82,11 +92,40 @@ impl CommonComponent<ChangePasswordForm> for ChangePasswordForm {
     ) -> Result<bool> {
         use anyhow::Context;
         match msg {
+            Msg::SettingsReceived(settings) => {
+                let settings = settings?;
+                self.password_policy = settings.password_policy;
+                self.breached_password_check_enabled = settings.breached_password_check_enabled;
+                Ok(false)
+            }
             Msg::FormUpdate => Ok(true),
             Msg::Submit => {
                 if !self.form.validate() {
                     bail!("Check the form for errors");
                 }
+                check_new_password(
+                    &self.password_policy,
+                    &ctx.props().username,
+                    &self.form.model().password,
+                )?;
+                if self.breached_password_check_enabled {
+                    self.common.call_backend(
+                        ctx,
+                        HostService::get_breached_password_range(get_breached_password_prefix(
+                            &self.form.model().password,
+                        )),
+                        Msg::BreachedPasswordRangeResponse,
+                    );
+                    Ok(true)
+                } else {
+                    self.handle_msg(ctx, Msg::PasswordChecked)
+                }
+            }
+            Msg::BreachedPasswordRangeResponse(range) => {
+                check_breached_password(&range?, &self.form.model().password)?;
+                self.handle_msg(ctx, Msg::PasswordChecked)
+            }
+            Msg::PasswordChecked => {
                 if ctx.props().is_admin {
                     self.handle_msg(ctx, Msg::SubmitNewPassword)
                 } else {
@@ -163,6 +202,10 @@ impl CommonComponent<ChangePasswordForm> for ChangePasswordForm {
                         let req = registration::ClientRegistrationFinishRequest {
                             server_data: res.server_data,
                             registration_upload: registration_finish.message,
+                            password_fingerprint: get_password_fingerprint(
+                                res.password_history_salt.as_deref(),
+                                &self.form.model().password,
+                            )?,
                         };
                         self.common.call_backend(
                             ctx,
@@ -195,11 +238,15 @@ impl Component for ChangePasswordForm {
     type Message = Msg;
     type Properties = Props;
 
-    fn create(_: &Context<Self>) -> Self {
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link()
+            .send_future(async move { Msg::SettingsReceived(HostService::get_settings().await) });
         ChangePasswordForm {
             common: CommonComponentParts::<Self>::create(),
             form: yew_form::Form::<FormModel>::new(FormModel::default()),
             opaque_data: OpaqueData::None,
+            password_policy: PasswordPolicy::default(),
+            breached_password_check_enabled: false,
         }
     }
 
@@ -5,7 +5,7 @@ use crate::{
     },
     infra::{
         common_component::{CommonComponent, CommonComponentParts},
-        schema::{AttributeType, validate_attribute_type},
+        schema::{AttributeType, MatchingRule, validate_attribute_type},
     },
 };
 use anyhow::{Result, bail};
@@ -23,7 +23,7 @@ use yew_router::{prelude::History, scope_ext::RouterScopeExt};
     query_path = "queries/create_group_attribute.graphql",
     response_derives = "Debug",
     custom_scalars_module = "crate::infra::graphql",
-    extern_enums("AttributeType")
+    extern_enums("AttributeType", "MatchingRule")
 )]
 pub struct CreateGroupAttribute;
 
@@ -40,6 +40,7 @@ pub struct CreateGroupAttributeModel {
     attribute_type: String,
     is_list: bool,
     is_visible: bool, // remove when backend doesn't return group attributes for normal users
+    is_case_sensitive: bool,
 }
 
 pub enum Msg {
@@ -70,11 +71,17 @@ impl CommonComponent<CreateGroupAttributeForm> for CreateGroupAttributeForm {
                 })?;
                 let attribute_type =
                     AttributeType::try_from(model.attribute_type.as_str()).unwrap();
+                if model.is_case_sensitive && attribute_type != AttributeType::String {
+                    bail!("Only string attributes can be case-sensitive");
+                }
                 let req = create_group_attribute::Variables {
                     name: model.attribute_name,
                     attribute_type,
                     is_list: model.is_list,
                     is_visible: model.is_visible,
+                    matching_rule: model
+                        .is_case_sensitive
+                        .then_some(MatchingRule::CaseExactMatch),
                 };
                 self.common.call_graphql::<CreateGroupAttribute, _>(
                     ctx,
@@ -157,6 +164,11 @@ impl Component for CreateGroupAttributeForm {
                 form={&self.form}
                 field_name="is_visible"
                 ontoggle={link.callback(|_| Msg::Update)} />
+              <CheckBox<CreateGroupAttributeModel>
+                label="Case-sensitive"
+                form={&self.form}
+                field_name="is_case_sensitive"
+                ontoggle={link.callback(|_| Msg::Update)} />
               <Submit
                 disabled={self.common.is_task_running()}
                 onclick={link.callback(|e: MouseEvent| {e.prevent_default(); Msg::SubmitForm})}/>
@@ -0,0 +1,225 @@
+use crate::{
+    components::{
+        create_user::{CreateUser, create_user},
+        form::{field::Field, submit::Submit},
+        router::AppRoute,
+    },
+    infra::{
+        api::HostService,
+        common_component::{CommonComponent, CommonComponentParts},
+        password_policy::get_password_fingerprint,
+    },
+};
+use anyhow::{Result, ensure};
+use gloo_console::log;
+use lldap_auth::{opaque, registration};
+use validator_derive::Validate;
+use yew::prelude::*;
+use yew_form_derive::Model;
+use yew_router::{prelude::History, scope_ext::RouterScopeExt};
+
+pub struct CreateServiceAccountForm {
+    common: CommonComponentParts<Self>,
+    form: yew_form::Form<CreateServiceAccountModel>,
+}
+
+#[derive(Model, Validate, PartialEq, Eq, Clone, Default)]
+pub struct CreateServiceAccountModel {
+    #[validate(length(min = 1, message = "Account name is required"))]
+    username: String,
+    display_name: String,
+    allowed_sources: String,
+    // Service accounts can only bind over LDAP, so they need a password.
+    #[validate(length(min = 8, message = "Password should be longer than 8 characters"))]
+    password: String,
+    #[validate(must_match(other = "password", message = "Passwords must match"))]
+    confirm_password: String,
+}
+
+pub enum Msg {
+    Update,
+    SubmitForm,
+    CreateUserResponse(Result<create_user::ResponseData>),
+    RegistrationStartResponse(
+        (
+            opaque::client::registration::ClientRegistration,
+            Result<Box<registration::ServerRegistrationStartResponse>>,
+        ),
+    ),
+    RegistrationFinishResponse(Result<()>),
+}
+
+impl CommonComponent<CreateServiceAccountForm> for CreateServiceAccountForm {
+    fn handle_msg(
+        &mut self,
+        ctx: &Context<Self>,
+        msg: <Self as Component>::Message,
+    ) -> Result<bool> {
+        match msg {
+            Msg::Update => Ok(true),
+            Msg::SubmitForm => {
+                ensure!(self.form.validate(), "Check the form for errors");
+                let model = self.form.model();
+                let allowed_sources = model
+                    .allowed_sources
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>();
+                let req = create_user::Variables {
+                    user: create_user::CreateUserInput {
+                        id: model.username,
+                        email: None,
+                        displayName: Some(model.display_name).filter(|n| !n.is_empty()),
+                        firstName: None,
+                        lastName: None,
+                        avatar: None,
+                        attributes: None,
+                        legacyPasswordHash: None,
+                        isServiceAccount: Some(true),
+                        allowedSources: Some(allowed_sources),
+                    },
+                };
+                self.common.call_graphql::<CreateUser, _>(
+                    ctx,
+                    req,
+                    Msg::CreateUserResponse,
+                    "Error trying to create service account",
+                );
+                Ok(true)
+            }
+            Msg::CreateUserResponse(r) => {
+                log!(&format!("Created service account '{}'", &r?.create_user.id));
+                let model = self.form.model();
+                let mut rng = rand::rngs::OsRng;
+                let opaque::client::registration::ClientRegistrationStartResult { state, message } =
+                    opaque::client::registration::start_registration(
+                        model.password.as_bytes(),
+                        &mut rng,
+                    )?;
+                let req = registration::ClientRegistrationStartRequest {
+                    username: model.username.into(),
+                    registration_start_request: message,
+                };
+                self.common
+                    .call_backend(ctx, HostService::register_start(req), move |r| {
+                        Msg::RegistrationStartResponse((state, r))
+                    });
+                Ok(false)
+            }
+            Msg::RegistrationStartResponse((registration_start, response)) => {
+                let response = response?;
+                let mut rng = rand::rngs::OsRng;
+                let registration_upload = opaque::client::registration::finish_registration(
+                    registration_start,
+                    response.registration_response,
+                    &mut rng,
+                )?;
+                let req = registration::ClientRegistrationFinishRequest {
+                    server_data: response.server_data,
+                    registration_upload: registration_upload.message,
+                    password_fingerprint: get_password_fingerprint(
+                        response.password_history_salt.as_deref(),
+                        &self.form.model().password,
+                    )?,
+                };
+                self.common.call_backend(
+                    ctx,
+                    HostService::register_finish(req),
+                    Msg::RegistrationFinishResponse,
+                );
+                Ok(false)
+            }
+            Msg::RegistrationFinishResponse(response) => {
+                response?;
+                ctx.link()
+                    .history()
+                    .unwrap()
+                    .push(AppRoute::ListServiceAccounts);
+                Ok(true)
+            }
+        }
+    }
+
+    fn mut_common(&mut self) -> &mut CommonComponentParts<Self> {
+        &mut self.common
+    }
+}
+
+impl Component for CreateServiceAccountForm {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_: &Context<Self>) -> Self {
+        Self {
+            common: CommonComponentParts::<Self>::create(),
+            form: yew_form::Form::<CreateServiceAccountModel>::new(
+                CreateServiceAccountModel::default(),
+            ),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        CommonComponentParts::<Self>::update(self, ctx, msg)
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
+        html! {
+          <div class="row justify-content-center">
+            <form class="form py-3" style="max-width: 636px">
+              <div class="row mb-3">
+                <h5 class="fw-bold">{"Create a service account"}</h5>
+                <small class="text-muted">
+                  {"Service accounts can only bind over LDAP. Give them permissions by adding them to the lldap_strict_readonly, lldap_password_manager or lldap_admin groups. Allowed networks are comma-separated, e.g. \"10.0.0.0/8, 192.168.1.5\"; leave empty to allow any."}
+                </small>
+              </div>
+              <Field<CreateServiceAccountModel>
+                form={&self.form}
+                required=true
+                label="Account name"
+                field_name="username"
+                oninput={link.callback(|_| Msg::Update)} />
+              <Field<CreateServiceAccountModel>
+                form={&self.form}
+                label="Display name"
+                field_name="display_name"
+                oninput={link.callback(|_| Msg::Update)} />
+              <Field<CreateServiceAccountModel>
+                form={&self.form}
+                label="Allowed networks"
+                field_name="allowed_sources"
+                oninput={link.callback(|_| Msg::Update)} />
+              <Field<CreateServiceAccountModel>
+                form={&self.form}
+                required=true
+                label="Password"
+                field_name="password"
+                input_type="password"
+                autocomplete="new-password"
+                oninput={link.callback(|_| Msg::Update)} />
+              <Field<CreateServiceAccountModel>
+                form={&self.form}
+                required=true
+                label="Confirm password"
+                field_name="confirm_password"
+                input_type="password"
+                autocomplete="new-password"
+                oninput={link.callback(|_| Msg::Update)} />
+              <Submit
+                disabled={self.common.is_task_running()}
+                onclick={link.callback(|e: MouseEvent| {e.prevent_default(); Msg::SubmitForm})} />
+            </form>
+            { if let Some(e) = &self.common.error {
+                html! {
+                  <div class="alert alert-danger">
+                    {e.to_string() }
+                  </div>
+                }
+              } else { html! {} }
+            }
+          </div>
+        }
+    }
+}
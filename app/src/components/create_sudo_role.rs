@@ -0,0 +1,182 @@
+use crate::{
+    components::{
+        form::{field::Field, submit::Submit},
+        router::AppRoute,
+    },
+    infra::common_component::{CommonComponent, CommonComponentParts},
+};
+use anyhow::{Result, anyhow, ensure};
+use gloo_console::log;
+use graphql_client::GraphQLQuery;
+use validator_derive::Validate;
+use yew::prelude::*;
+use yew_form_derive::Model;
+use yew_router::{prelude::History, scope_ext::RouterScopeExt};
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/create_sudo_role.graphql",
+    response_derives = "Debug",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct CreateSudoRole;
+
+pub struct CreateSudoRoleForm {
+    common: CommonComponentParts<Self>,
+    form: yew_form::Form<CreateSudoRoleModel>,
+}
+
+#[derive(Model, Validate, PartialEq, Eq, Clone, Default)]
+pub struct CreateSudoRoleModel {
+    #[validate(length(min = 1, message = "Name is required"))]
+    name: String,
+    description: String,
+    #[validate(length(min = 1, message = "At least one user is required"))]
+    users: String,
+    #[validate(length(min = 1, message = "At least one host is required"))]
+    hosts: String,
+    commands: String,
+    run_as_users: String,
+    run_as_groups: String,
+    options: String,
+    order: String,
+}
+
+/// The values are entered as a comma-separated list.
+fn split_values(values: &str) -> Option<Vec<String>> {
+    let values: Vec<String> = values
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_owned)
+        .collect();
+    (!values.is_empty()).then_some(values)
+}
+
+pub enum Msg {
+    Update,
+    SubmitForm,
+    CreateSudoRoleResponse(Result<create_sudo_role::ResponseData>),
+}
+
+impl CommonComponent<CreateSudoRoleForm> for CreateSudoRoleForm {
+    fn handle_msg(
+        &mut self,
+        ctx: &Context<Self>,
+        msg: <Self as Component>::Message,
+    ) -> Result<bool> {
+        match msg {
+            Msg::Update => Ok(true),
+            Msg::SubmitForm => {
+                ensure!(self.form.validate(), "Check the form for errors");
+                let model = self.form.model();
+                let order = match model.order.trim() {
+                    "" => None,
+                    order => Some(
+                        order
+                            .parse::<i64>()
+                            .map_err(|_| anyhow!("The order must be an integer"))?,
+                    ),
+                };
+                let req = create_sudo_role::Variables {
+                    role: create_sudo_role::CreateSudoRoleInput {
+                        name: model.name,
+                        description: Some(model.description).filter(|d| !d.is_empty()),
+                        users: split_values(&model.users),
+                        hosts: split_values(&model.hosts),
+                        commands: split_values(&model.commands),
+                        runAsUsers: split_values(&model.run_as_users),
+                        runAsGroups: split_values(&model.run_as_groups),
+                        options: split_values(&model.options),
+                        order,
+                    },
+                };
+                self.common.call_graphql::<CreateSudoRole, _>(
+                    ctx,
+                    req,
+                    Msg::CreateSudoRoleResponse,
+                    "Error trying to create sudo rule",
+                );
+                Ok(true)
+            }
+            Msg::CreateSudoRoleResponse(response) => {
+                log!(&format!(
+                    "Created sudo rule '{}'",
+                    &response?.create_sudo_role.name
+                ));
+                ctx.link().history().unwrap().push(AppRoute::ListSudoRoles);
+                Ok(true)
+            }
+        }
+    }
+
+    fn mut_common(&mut self) -> &mut CommonComponentParts<Self> {
+        &mut self.common
+    }
+}
+
+impl Component for CreateSudoRoleForm {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_: &Context<Self>) -> Self {
+        Self {
+            common: CommonComponentParts::<Self>::create(),
+            form: yew_form::Form::<CreateSudoRoleModel>::new(CreateSudoRoleModel {
+                hosts: "ALL".to_owned(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        CommonComponentParts::<Self>::update(self, ctx, msg)
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
+        let field = |label: &'static str, field_name: &'static str, required: bool| {
+            html! {
+              <Field<CreateSudoRoleModel>
+                form={&self.form}
+                required={required}
+                label={label}
+                field_name={field_name}
+                oninput={link.callback(|_| Msg::Update)} />
+            }
+        };
+        html! {
+          <div class="row justify-content-center">
+            <form class="form py-3" style="max-width: 636px">
+              <div class="row mb-3">
+                <h5 class="fw-bold">{"Create a sudo rule"}</h5>
+                <small class="text-muted">
+                  {"Lists are comma-separated. Users can be \"%group\" or \"ALL\"."}
+                </small>
+              </div>
+              {field("Name", "name", true)}
+              {field("Description", "description", false)}
+              {field("Users", "users", true)}
+              {field("Hosts", "hosts", true)}
+              {field("Commands", "commands", false)}
+              {field("Run as users", "run_as_users", false)}
+              {field("Run as groups", "run_as_groups", false)}
+              {field("Options", "options", false)}
+              {field("Order", "order", false)}
+              <Submit
+                disabled={self.common.is_task_running()}
+                onclick={link.callback(|e: MouseEvent| {e.prevent_default(); Msg::SubmitForm})} />
+            </form>
+            { if let Some(e) = &self.common.error {
+                html! {
+                  <div class="alert alert-danger">
+                    {e.to_string() }
+                  </div>
+                }
+              } else { html! {} }
+            }
+          </div>
+        }
+    }
+}
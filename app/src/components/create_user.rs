@@ -14,6 +14,7 @@ use crate::{
             AttributeValue, EmailIsRequired, GraphQlAttributeSchema, IsAdmin,
             read_all_form_attributes,
         },
+        password_policy::get_password_fingerprint,
         schema::AttributeType,
     },
 };
@@ -148,6 +149,9 @@ impl CommonComponent<CreateUserForm> for CreateUserForm {
                         lastName: None,
                         avatar: None,
                         attributes,
+                        legacyPasswordHash: None,
+                        isServiceAccount: None,
+                        allowedSources: None,
                     },
                 };
                 self.common.call_graphql::<CreateUser, _>(
@@ -203,6 +207,10 @@ impl CommonComponent<CreateUserForm> for CreateUserForm {
                 let req = registration::ClientRegistrationFinishRequest {
                     server_data: response.server_data,
                     registration_upload: registration_upload.message,
+                    password_fingerprint: get_password_fingerprint(
+                        response.password_history_salt.as_deref(),
+                        &self.form.model().password,
+                    )?,
                 };
                 self.common.call_backend(
                     ctx,
@@ -5,7 +5,7 @@ use crate::{
     },
     infra::{
         common_component::{CommonComponent, CommonComponentParts},
-        schema::{AttributeType, validate_attribute_type},
+        schema::{AttributeType, MatchingRule, validate_attribute_type},
     },
 };
 use anyhow::{Result, bail};
@@ -23,7 +23,7 @@ use yew_router::{prelude::History, scope_ext::RouterScopeExt};
     query_path = "queries/create_user_attribute.graphql",
     response_derives = "Debug",
     custom_scalars_module = "crate::infra::graphql",
-    extern_enums("AttributeType")
+    extern_enums("AttributeType", "MatchingRule")
 )]
 pub struct CreateUserAttribute;
 
@@ -41,6 +41,7 @@ pub struct CreateUserAttributeModel {
     is_editable: bool,
     is_list: bool,
     is_visible: bool,
+    is_case_sensitive: bool,
 }
 
 pub enum Msg {
@@ -74,12 +75,18 @@ impl CommonComponent<CreateUserAttributeForm> for CreateUserAttributeForm {
                 })?;
                 let attribute_type =
                     AttributeType::try_from(model.attribute_type.as_str()).unwrap();
+                if model.is_case_sensitive && attribute_type != AttributeType::String {
+                    bail!("Only string attributes can be case-sensitive");
+                }
                 let req = create_user_attribute::Variables {
                     name: model.attribute_name,
                     attribute_type,
                     is_editable: model.is_editable,
                     is_list: model.is_list,
                     is_visible: model.is_visible,
+                    matching_rule: model
+                        .is_case_sensitive
+                        .then_some(MatchingRule::CaseExactMatch),
                 };
                 self.common.call_graphql::<CreateUserAttribute, _>(
                     ctx,
@@ -148,6 +155,7 @@ impl Component for CreateUserAttributeForm {
                 <option value="Integer">{"Integer"}</option>
                 <option value="JpegPhoto">{"Jpeg"}</option>
                 <option value="DateTime">{"DateTime"}</option>
+                <option value="SshPublicKey">{"SSH public key"}</option>
               </Select<CreateUserAttributeModel>>
               <CheckBox<CreateUserAttributeModel>
                 label="Multiple values"
@@ -164,6 +172,11 @@ impl Component for CreateUserAttributeForm {
                 form={&self.form}
                 field_name="is_editable"
                 ontoggle={link.callback(|_| Msg::Update)} />
+              <CheckBox<CreateUserAttributeModel>
+                label="Case-sensitive"
+                form={&self.form}
+                field_name="is_case_sensitive"
+                ontoggle={link.callback(|_| Msg::Update)} />
               <Submit
                 disabled={self.common.is_task_running()}
                 onclick={link.callback(|e: MouseEvent| {e.prevent_default(); Msg::SubmitForm})}/>
@@ -19,7 +19,7 @@ struct AttributeInputProps {
 #[function_component(AttributeInput)]
 fn attribute_input(props: &AttributeInputProps) -> Html {
     let input_type = match props.attribute_type {
-        AttributeType::String => "text",
+        AttributeType::String | AttributeType::SshPublicKey => "text",
         AttributeType::Integer => "number",
         AttributeType::DateTime => {
             return html! {
@@ -2,6 +2,7 @@ use crate::{
     components::{
         add_group_member::{self, AddGroupMemberComponent},
         group_details_form::GroupDetailsForm,
+        group_member_filter::GroupMemberFilterComponent,
         remove_user_from_group::RemoveUserFromGroupComponent,
         router::{AppRoute, Link},
     },
@@ -58,6 +59,7 @@ pub enum Msg {
     OnUserAddedToGroup(AddGroupMemberUser),
     OnUserRemovedFromGroup((String, i64)),
     DisplayNameUpdated,
+    MemberFilterUpdated,
 }
 
 #[derive(yew::Properties, Clone, PartialEq, Eq)]
@@ -106,6 +108,8 @@ impl GroupDetails {
 
     fn view_user_list(&self, ctx: &Context<Self>, g: &Group) -> Html {
         let link = ctx.link();
+        // The members of a dynamic group come from its filter.
+        let is_dynamic = g.member_filter.is_some();
         let make_user_row = |user: &User| {
             let user_id = user.id.clone();
             let display_name = user.display_name.clone();
@@ -118,11 +122,17 @@ impl GroupDetails {
                 </td>
                 <td>{display_name}</td>
                 <td>
-                  <RemoveUserFromGroupComponent
-                    username={user_id}
-                    group_id={g.id}
-                    on_user_removed_from_group={link.callback(Msg::OnUserRemovedFromGroup)}
-                    on_error={link.callback(Msg::OnError)}/>
+                  {if is_dynamic {
+                    html! {}
+                  } else {
+                    html! {
+                      <RemoveUserFromGroupComponent
+                        username={user_id}
+                        group_id={g.id}
+                        on_user_removed_from_group={link.callback(Msg::OnUserRemovedFromGroup)}
+                        on_error={link.callback(Msg::OnError)}/>
+                    }
+                  }}
                 </td>
               </tr>
             }
@@ -157,7 +167,25 @@ impl GroupDetails {
         }
     }
 
+    fn view_member_filter(&self, ctx: &Context<Self>, g: &Group) -> Html {
+        if !ctx.props().is_admin {
+            return html! {};
+        }
+        let link = ctx.link();
+        html! {
+            <GroupMemberFilterComponent
+                key={g.member_filter.clone().unwrap_or_default()}
+                group_id={g.id}
+                member_filter={g.member_filter.clone()}
+                on_error={link.callback(Msg::OnError)}
+                on_member_filter_updated={link.callback(|_| Msg::MemberFilterUpdated)}/>
+        }
+    }
+
     fn view_add_user_button(&self, ctx: &Context<Self>, g: &Group) -> Html {
+        if g.member_filter.is_some() {
+            return html! {};
+        }
         let link = ctx.link();
         let users: Vec<_> = g
             .users
@@ -209,7 +237,7 @@ impl CommonComponent<GroupDetails> for GroupDetails {
                     .users
                     .retain(|u| u.id != user_id);
             }
-            Msg::DisplayNameUpdated => self.get_group_details(ctx),
+            Msg::DisplayNameUpdated | Msg::MemberFilterUpdated => self.get_group_details(ctx),
         }
         Ok(true)
     }
@@ -244,6 +272,7 @@ impl Component for GroupDetails {
                 html! {
                     <div>
                       {self.view_details(ctx, group, schema.clone())}
+                      {self.view_member_filter(ctx, group)}
                       {self.view_user_list(ctx, group)}
                       {self.view_add_user_button(ctx, group)}
                       {self.view_messages(error)}
@@ -0,0 +1,186 @@
+use crate::{
+    components::router::{AppRoute, Link},
+    infra::common_component::{CommonComponent, CommonComponentParts},
+};
+use anyhow::{Error, Result};
+use graphql_client::GraphQLQuery;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/set_group_member_filter.graphql",
+    response_derives = "Debug",
+    variables_derives = "Clone",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct SetGroupMemberFilter;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/preview_member_filter.graphql",
+    response_derives = "Debug, Clone, PartialEq, Eq",
+    variables_derives = "Clone",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct PreviewMemberFilter;
+
+pub type PreviewUser = preview_member_filter::PreviewMemberFilterPreviewMemberFilter;
+
+/// Editor for the LDAP filter defining the members of a dynamic group.
+pub struct GroupMemberFilterComponent {
+    common: CommonComponentParts<Self>,
+    filter: String,
+    /// The users matching the filter, once previewed.
+    preview: Option<Vec<PreviewUser>>,
+}
+
+#[derive(yew::Properties, Clone, PartialEq, Eq)]
+pub struct Props {
+    pub group_id: i64,
+    pub member_filter: Option<String>,
+    pub on_member_filter_updated: Callback<()>,
+    pub on_error: Callback<Error>,
+}
+
+pub enum Msg {
+    FilterUpdated(String),
+    SubmitPreview,
+    PreviewResponse(Result<preview_member_filter::ResponseData>),
+    SubmitMemberFilter,
+    MemberFilterResponse(Result<set_group_member_filter::ResponseData>),
+}
+
+impl CommonComponent<GroupMemberFilterComponent> for GroupMemberFilterComponent {
+    fn handle_msg(
+        &mut self,
+        ctx: &Context<Self>,
+        msg: <Self as Component>::Message,
+    ) -> Result<bool> {
+        match msg {
+            Msg::FilterUpdated(filter) => {
+                self.filter = filter;
+                self.preview = None;
+            }
+            Msg::SubmitPreview => self.common.call_graphql::<PreviewMemberFilter, _>(
+                ctx,
+                preview_member_filter::Variables {
+                    filter: self.filter.clone(),
+                },
+                Msg::PreviewResponse,
+                "Error trying to preview the member filter",
+            ),
+            Msg::PreviewResponse(response) => {
+                self.preview = Some(response?.preview_member_filter);
+            }
+            Msg::SubmitMemberFilter => self.common.call_graphql::<SetGroupMemberFilter, _>(
+                ctx,
+                set_group_member_filter::Variables {
+                    group: ctx.props().group_id,
+                    filter: Some(self.filter.clone()),
+                },
+                Msg::MemberFilterResponse,
+                "Error trying to update the member filter",
+            ),
+            Msg::MemberFilterResponse(response) => {
+                response?;
+                self.preview = None;
+                ctx.props().on_member_filter_updated.emit(());
+            }
+        }
+        Ok(true)
+    }
+
+    fn mut_common(&mut self) -> &mut CommonComponentParts<Self> {
+        &mut self.common
+    }
+}
+
+impl GroupMemberFilterComponent {
+    fn view_preview(&self) -> Html {
+        match &self.preview {
+            None => html! {},
+            Some(users) if users.is_empty() => html! {
+              <p class="text-muted">{"No user matches this filter."}</p>
+            },
+            Some(users) => html! {
+              <ul>
+                {users.iter().map(|user| html! {
+                  <li>
+                    <Link to={AppRoute::UserDetails{user_id: user.id.clone()}}>
+                      {&user.id}
+                    </Link>
+                    {" ("}{&user.display_name}{")"}
+                  </li>
+                }).collect::<Html>()}
+              </ul>
+            },
+        }
+    }
+}
+
+impl Component for GroupMemberFilterComponent {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        Self {
+            common: CommonComponentParts::<Self>::create(),
+            filter: ctx.props().member_filter.clone().unwrap_or_default(),
+            preview: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        CommonComponentParts::<Self>::update_and_report_error(
+            self,
+            ctx,
+            msg,
+            ctx.props().on_error.clone(),
+        )
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let link = &ctx.link();
+        let is_unchanged =
+            self.filter.trim() == ctx.props().member_filter.as_deref().unwrap_or_default();
+        html! {
+          <div class="mb-3">
+            <h5 class="fw-bold">{"Dynamic membership"}</h5>
+            <p class="text-muted">
+              {"The members of a dynamic group are the users matching an LDAP filter, e.g. "}
+              <code>{"(&(mail=*@example.com)(memberOf=cn=staff,ou=groups,dc=example,dc=com))"}</code>
+              {". Leave it empty for a regular group."}
+            </p>
+            <div class="input-group">
+              <input
+                type="text"
+                class="form-control"
+                placeholder="LDAP filter"
+                value={self.filter.clone()}
+                oninput={link.callback(|e: InputEvent| {
+                    let input: HtmlInputElement = e.target_unchecked_into();
+                    Msg::FilterUpdated(input.value())
+                })} />
+              <button
+                class="btn btn-secondary"
+                disabled={self.common.is_task_running() || self.filter.trim().is_empty()}
+                onclick={link.callback(|_| Msg::SubmitPreview)}>
+                <i class="bi-eye me-2"></i>
+                {"Preview"}
+              </button>
+              <button
+                class="btn btn-primary"
+                disabled={self.common.is_task_running() || is_unchanged}
+                onclick={link.callback(|_| Msg::SubmitMemberFilter)}>
+                <i class="bi-save me-2"></i>
+                {"Save"}
+              </button>
+            </div>
+            {self.view_preview()}
+          </div>
+        }
+    }
+}
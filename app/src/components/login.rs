@@ -4,8 +4,9 @@ use crate::{
         router::{AppRoute, Link},
     },
     infra::{
-        api::HostService,
+        api::{HostService, LoginInfo, LoginResult},
         common_component::{CommonComponent, CommonComponentParts},
+        webauthn,
     },
 };
 use anyhow::{Result, anyhow, bail};
@@ -19,6 +20,9 @@ use yew_form_derive::Model;
 pub struct LoginForm {
     common: CommonComponentParts<Self>,
     form: Form<FormModel>,
+    mfa_form: Form<MfaFormModel>,
+    /// Set once the password is checked, if the user has a second factor.
+    mfa_required: Option<login::ServerMfaRequiredResponse>,
     refreshing: bool,
 }
 
@@ -31,23 +35,36 @@ pub struct FormModel {
     password: String,
 }
 
+#[derive(Model, Validate, PartialEq, Eq, Clone, Default)]
+pub struct MfaFormModel {
+    #[validate(length(min = 1, message = "Missing code"))]
+    code: String,
+}
+
 #[derive(Clone, PartialEq, Properties)]
 pub struct Props {
-    pub on_logged_in: Callback<(String, bool)>,
+    pub on_logged_in: Callback<LoginInfo>,
     pub password_reset_enabled: bool,
+    pub webauthn_enabled: bool,
 }
 
 pub enum Msg {
     Update,
     Submit,
-    AuthenticationRefreshResponse(Result<(String, bool)>),
+    AuthenticationRefreshResponse(Result<LoginInfo>),
     AuthenticationStartResponse(
         (
             opaque::client::login::ClientLogin,
             Result<Box<login::ServerLoginStartResponse>>,
         ),
     ),
-    AuthenticationFinishResponse(Result<(String, bool)>),
+    AuthenticationFinishResponse(Result<LoginResult>),
+    SubmitMfaCode,
+    MfaVerifyResponse(Result<LoginInfo>),
+    /// Log in with a security key: as the second factor if the password was checked, or with a
+    /// passkey otherwise.
+    SubmitWebauthnLogin,
+    WebauthnLoginResponse(Result<LoginInfo>),
 }
 
 impl CommonComponent<LoginForm> for LoginForm {
@@ -103,10 +120,47 @@ impl CommonComponent<LoginForm> for LoginForm {
                 );
                 Ok(false)
             }
-            Msg::AuthenticationFinishResponse(user_info) => {
+            Msg::AuthenticationFinishResponse(result) => {
+                match result.context("Could not log in")? {
+                    LoginResult::LoggedIn(user_info) => ctx.props().on_logged_in.emit(user_info),
+                    LoginResult::MfaRequired(mfa_required) => {
+                        self.mfa_required = Some(mfa_required)
+                    }
+                }
+                Ok(true)
+            }
+            Msg::SubmitMfaCode => {
+                if !self.mfa_form.validate() {
+                    bail!("Check the form for errors");
+                }
+                let req = login::ClientMfaVerifyRequest {
+                    mfa_token: self.mfa_required.as_ref().unwrap().mfa_token.clone(),
+                    code: self.mfa_form.model().code,
+                };
+                self.common
+                    .call_backend(ctx, HostService::mfa_verify(req), Msg::MfaVerifyResponse);
+                Ok(true)
+            }
+            Msg::MfaVerifyResponse(user_info) => {
+                self.mfa_form = Form::new(MfaFormModel::default());
                 ctx.props()
                     .on_logged_in
-                    .emit(user_info.context("Could not log in")?);
+                    .emit(user_info.context("Could not verify the code")?);
+                Ok(true)
+            }
+            Msg::SubmitWebauthnLogin => {
+                let mfa_token = self.mfa_required.as_ref().map(|m| m.mfa_token.clone());
+                self.common.call_backend(
+                    ctx,
+                    webauthn::login(mfa_token),
+                    Msg::WebauthnLoginResponse,
+                );
+                Ok(true)
+            }
+            Msg::WebauthnLoginResponse(user_info) => {
+                ctx.props()
+                    .on_logged_in
+                    .emit(user_info.context("Could not log in with the security key")?);
                 Ok(true)
             }
             Msg::AuthenticationRefreshResponse(user_info) => {
@@ -124,6 +178,74 @@ impl CommonComponent<LoginForm> for LoginForm {
     }
 }
 
+impl LoginForm {
+    fn view_webauthn_button(&self, ctx: &Context<Self>, text: &'static str) -> Html {
+        let link = &ctx.link();
+        html! {
+          <button
+            type="button"
+            class="btn btn-secondary mt-2"
+            disabled={self.common.is_task_running()}
+            onclick={link.callback(|_| Msg::SubmitWebauthnLogin)}>
+            <i class="bi-usb-drive me-2"></i>
+            {text}
+          </button>
+        }
+    }
+
+    fn view_mfa_form(
+        &self,
+        ctx: &Context<Self>,
+        mfa_required: &login::ServerMfaRequiredResponse,
+    ) -> Html {
+        type Field = yew_form::Field<MfaFormModel>;
+        let link = &ctx.link();
+        let use_webauthn = mfa_required.webauthn && ctx.props().webauthn_enabled;
+        html! {
+          <form class="form center-block col-sm-4 col-offset-4">
+            {if mfa_required.totp { html! {
+            <>
+            <div class="form-group mb-2">
+              {"Enter the code from your authenticator app, or one of your recovery codes."}
+            </div>
+            <div class="input-group">
+              <div class="input-group-prepend">
+                <span class="input-group-text">
+                  <i class="bi-shield-lock-fill"/>
+                </span>
+              </div>
+              <Field
+                class="form-control"
+                class_invalid="is-invalid has-error"
+                class_valid="has-success"
+                form={&self.mfa_form}
+                field_name="code"
+                placeholder="Code"
+                autocomplete="one-time-code"
+                oninput={link.callback(|_| Msg::Update)} />
+            </div>
+            <Submit
+              text="Verify"
+              disabled={self.common.is_task_running()}
+              onclick={link.callback(|e: MouseEvent| {e.prevent_default(); Msg::SubmitMfaCode})} />
+            </>
+            } } else { html! {} } }
+            {if use_webauthn { html! {
+              <div class="form-group">
+                {self.view_webauthn_button(ctx, "Use a security key")}
+              </div>
+            } } else { html! {} } }
+            <div class="form-group">
+            { if let Some(e) = &self.common.error {
+                html! { e.to_string() }
+              } else { html! {} }
+            }
+            </div>
+          </form>
+        }
+    }
+}
+
 impl Component for LoginForm {
     type Message = Msg;
     type Properties = Props;
@@ -132,6 +254,8 @@ impl Component for LoginForm {
         let mut app = LoginForm {
             common: CommonComponentParts::<Self>::create(),
             form: Form::<FormModel>::new(FormModel::default()),
+            mfa_form: Form::<MfaFormModel>::new(MfaFormModel::default()),
+            mfa_required: None,
             refreshing: true,
         };
         app.common.call_backend(
@@ -156,6 +280,8 @@ impl Component for LoginForm {
                 <img src={"spinner.gif"} alt={"Loading"} />
               </div>
             }
+        } else if let Some(mfa_required) = &self.mfa_required {
+            self.view_mfa_form(ctx, mfa_required)
         } else {
             html! {
               <form class="form center-block col-sm-4 col-offset-4">
@@ -208,6 +334,12 @@ impl Component for LoginForm {
                     html!{}
                   }}
                 </Submit>
+                { if ctx.props().webauthn_enabled {
+                    self.view_webauthn_button(ctx, "Log in with a passkey")
+                  } else {
+                    html! {}
+                  }
+                }
                 <div class="form-group">
                 { if let Some(e) = &self.common.error {
                     html! { e.to_string() }
@@ -1,21 +1,27 @@
+pub mod account_expiration;
 pub mod add_group_member;
 pub mod add_user_to_group;
 pub mod app;
+pub mod app_passwords;
 pub mod avatar;
 pub mod banner;
 pub mod change_password;
 pub mod create_group;
 pub mod create_group_attribute;
+pub mod create_service_account;
+pub mod create_sudo_role;
 pub mod create_user;
 pub mod create_user_attribute;
 pub mod delete_group;
 pub mod delete_group_attribute;
+pub mod delete_sudo_role;
 pub mod delete_user;
 pub mod delete_user_attribute;
 pub mod form;
 pub mod fragments;
 pub mod group_details;
 pub mod group_details_form;
+pub mod group_member_filter;
 pub mod group_schema_table;
 pub mod group_table;
 pub mod login;
@@ -25,7 +31,12 @@ pub mod reset_password_step1;
 pub mod reset_password_step2;
 pub mod router;
 pub mod select;
+pub mod service_account_table;
+pub mod ssh_public_keys;
+pub mod sudo_role_table;
+pub mod two_factor_auth;
 pub mod user_details;
 pub mod user_details_form;
 pub mod user_schema_table;
 pub mod user_table;
+pub mod webauthn_credentials;
@@ -6,6 +6,10 @@ use crate::{
     infra::{
         api::HostService,
         common_component::{CommonComponent, CommonComponentParts},
+        password_policy::{
+            check_breached_password, check_new_password, get_breached_password_prefix,
+            get_password_fingerprint,
+        },
     },
 };
 use anyhow::{Result, bail};
@@ -13,6 +17,8 @@ use lldap_auth::{
     opaque::client::registration as opaque_registration,
     password_reset::ServerPasswordResetResponse, registration,
 };
+use lldap_frontend_options::Options;
+use lldap_validation::{breached_passwords::BreachedPasswordRange, password::PasswordPolicy};
 use validator_derive::Validate;
 use yew::prelude::*;
 use yew_form::Form;
@@ -22,7 +28,6 @@ use yew_router::{prelude::History, scope_ext::RouterScopeExt};
 /// The fields of the form, with the constraints.
 #[derive(Model, Validate, PartialEq, Eq, Clone, Default)]
 pub struct FormModel {
-    #[validate(length(min = 8, message = "Invalid password. Min length: 8"))]
     password: String,
     #[validate(must_match(other = "password", message = "Passwords must match"))]
     confirm_password: String,
@@ -33,6 +38,8 @@ pub struct ResetPasswordStep2Form {
     form: Form<FormModel>,
     username: Option<String>,
     opaque_data: Option<opaque_registration::ClientRegistration>,
+    password_policy: PasswordPolicy,
+    breached_password_check_enabled: bool,
 }
 
 #[derive(Clone, PartialEq, Eq, Properties)]
@@ -41,9 +48,12 @@ pub struct Props {
 }
 
 pub enum Msg {
+    SettingsReceived(Result<Options>),
     ValidateTokenResponse(Result<ServerPasswordResetResponse>),
     FormUpdate,
     Submit,
+    BreachedPasswordRangeResponse(Result<BreachedPasswordRange>),
+    PasswordChecked,
     RegistrationStartResponse(Result<Box<registration::ServerRegistrationStartResponse>>),
     RegistrationFinishResponse(Result<()>),
 }
@@ -56,6 +66,12 @@ impl CommonComponent<ResetPasswordStep2Form> for ResetPasswordStep2Form {
     ) -> Result<bool> {
         use anyhow::Context;
         match msg {
+            Msg::SettingsReceived(settings) => {
+                let settings = settings?;
+                self.password_policy = settings.password_policy;
+                self.breached_password_check_enabled = settings.breached_password_check_enabled;
+                Ok(false)
+            }
             Msg::ValidateTokenResponse(response) => {
                 self.username = Some(response?.user_id);
                 Ok(true)
@@ -65,6 +81,30 @@ impl CommonComponent<ResetPasswordStep2Form> for ResetPasswordStep2Form {
                 if !self.form.validate() {
                     bail!("Check the form for errors");
                 }
+                let new_password = self.form.model().password;
+                check_new_password(
+                    &self.password_policy,
+                    self.username.as_ref().unwrap(),
+                    &new_password,
+                )?;
+                if self.breached_password_check_enabled {
+                    self.common.call_backend(
+                        ctx,
+                        HostService::get_breached_password_range(get_breached_password_prefix(
+                            &new_password,
+                        )),
+                        Msg::BreachedPasswordRangeResponse,
+                    );
+                    Ok(true)
+                } else {
+                    self.handle_msg(ctx, Msg::PasswordChecked)
+                }
+            }
+            Msg::BreachedPasswordRangeResponse(range) => {
+                check_breached_password(&range?, &self.form.model().password)?;
+                self.handle_msg(ctx, Msg::PasswordChecked)
+            }
+            Msg::PasswordChecked => {
                 let mut rng = rand::rngs::OsRng;
                 let new_password = self.form.model().password;
                 let registration_start_request =
@@ -95,6 +135,10 @@ impl CommonComponent<ResetPasswordStep2Form> for ResetPasswordStep2Form {
                 let req = registration::ClientRegistrationFinishRequest {
                     server_data: res.server_data,
                     registration_upload: registration_finish.message,
+                    password_fingerprint: get_password_fingerprint(
+                        res.password_history_salt.as_deref(),
+                        &self.form.model().password,
+                    )?,
                 };
                 self.common.call_backend(
                     ctx,
@@ -128,7 +172,11 @@ impl Component for ResetPasswordStep2Form {
             form: yew_form::Form::<FormModel>::new(FormModel::default()),
             opaque_data: None,
             username: None,
+            password_policy: PasswordPolicy::default(),
+            breached_password_check_enabled: false,
         };
+        ctx.link()
+            .send_future(async move { Msg::SettingsReceived(HostService::get_settings().await) });
         let token = ctx.props().token.clone();
         component.common.call_backend(
             ctx,
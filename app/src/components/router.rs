@@ -22,6 +22,14 @@ pub enum AppRoute {
     ListGroups,
     #[at("/group/:group_id")]
     GroupDetails { group_id: i64 },
+    #[at("/sudo-roles/create")]
+    CreateSudoRole,
+    #[at("/sudo-roles")]
+    ListSudoRoles,
+    #[at("/service-accounts/create")]
+    CreateServiceAccount,
+    #[at("/service-accounts")]
+    ListServiceAccounts,
     #[at("/user-attributes")]
     ListUserSchema,
     #[at("/user-attributes/create")]
@@ -0,0 +1,152 @@
+use crate::{
+    components::{
+        delete_user::DeleteUser,
+        router::{AppRoute, Link},
+    },
+    infra::common_component::{CommonComponent, CommonComponentParts},
+};
+use anyhow::{Error, Result};
+use graphql_client::GraphQLQuery;
+use yew::prelude::*;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/list_service_accounts.graphql",
+    response_derives = "Debug",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct ListServiceAccounts;
+
+use list_service_accounts::ResponseData;
+
+type ServiceAccount = list_service_accounts::ListServiceAccountsServiceAccounts;
+
+pub struct ServiceAccountTable {
+    common: CommonComponentParts<Self>,
+    service_accounts: Option<Vec<ServiceAccount>>,
+}
+
+pub enum Msg {
+    ListServiceAccountsResponse(Result<ResponseData>),
+    OnServiceAccountDeleted(String),
+    OnError(Error),
+}
+
+impl CommonComponent<ServiceAccountTable> for ServiceAccountTable {
+    fn handle_msg(&mut self, _: &Context<Self>, msg: <Self as Component>::Message) -> Result<bool> {
+        match msg {
+            Msg::ListServiceAccountsResponse(service_accounts) => {
+                self.service_accounts = Some(service_accounts?.service_accounts);
+                Ok(true)
+            }
+            Msg::OnError(e) => Err(e),
+            Msg::OnServiceAccountDeleted(user_id) => {
+                debug_assert!(self.service_accounts.is_some());
+                self.service_accounts
+                    .as_mut()
+                    .unwrap()
+                    .retain(|a| a.id != user_id);
+                Ok(true)
+            }
+        }
+    }
+
+    fn mut_common(&mut self) -> &mut CommonComponentParts<Self> {
+        &mut self.common
+    }
+}
+
+impl Component for ServiceAccountTable {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let mut table = ServiceAccountTable {
+            common: CommonComponentParts::<Self>::create(),
+            service_accounts: None,
+        };
+        table.common.call_graphql::<ListServiceAccounts, _>(
+            ctx,
+            list_service_accounts::Variables {},
+            Msg::ListServiceAccountsResponse,
+            "Error trying to fetch service accounts",
+        );
+        table
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        CommonComponentParts::<Self>::update(self, ctx, msg)
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div>
+              {self.view_service_accounts(ctx)}
+              {self.view_errors()}
+            </div>
+        }
+    }
+}
+
+impl ServiceAccountTable {
+    fn view_service_accounts(&self, ctx: &Context<Self>) -> Html {
+        let make_table = |service_accounts: &Vec<ServiceAccount>| {
+            html! {
+                <div class="table-responsive">
+                  <table class="table table-hover">
+                    <thead>
+                      <tr>
+                        <th>{"Account ID"}</th>
+                        <th>{"Display name"}</th>
+                        <th>{"Allowed networks"}</th>
+                        <th>{"Creation date"}</th>
+                        <th>{"Delete"}</th>
+                      </tr>
+                    </thead>
+                    <tbody>
+                      {service_accounts.iter().map(|a| self.view_service_account(ctx, a)).collect::<Vec<_>>()}
+                    </tbody>
+                  </table>
+                </div>
+            }
+        };
+        match &self.service_accounts {
+            None => html! {{"Loading..."}},
+            Some(service_accounts) => make_table(service_accounts),
+        }
+    }
+
+    fn view_service_account(&self, ctx: &Context<Self>, account: &ServiceAccount) -> Html {
+        let link = ctx.link();
+        html! {
+          <tr key={account.id.clone()}>
+              <td><Link to={AppRoute::UserDetails{user_id: account.id.clone()}}>{&account.id}</Link></td>
+              <td>{&account.display_name}</td>
+              <td>
+                {
+                  if account.allowed_sources.is_empty() {
+                    html! {<span class="text-muted">{"Any"}</span>}
+                  } else {
+                    account.allowed_sources.iter().map(|s| html! {<div><code>{s}</code></div>}).collect::<Html>()
+                  }
+                }
+              </td>
+              <td>{&account.creation_date.naive_local().date()}</td>
+              <td>
+                <DeleteUser
+                  username={account.id.clone()}
+                  on_user_deleted={link.callback(Msg::OnServiceAccountDeleted)}
+                  on_error={link.callback(Msg::OnError)}/>
+              </td>
+          </tr>
+        }
+    }
+
+    fn view_errors(&self) -> Html {
+        match &self.common.error {
+            None => html! {},
+            Some(e) => html! {<div>{"Error: "}{e.to_string()}</div>},
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{
+    components::user_details::SshPublicKey,
+    infra::common_component::{CommonComponent, CommonComponentParts},
+};
+use anyhow::{Error, Result, bail};
+use graphql_client::GraphQLQuery;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/update_user.graphql",
+    response_derives = "Debug",
+    variables_derives = "Clone",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct UpdateUser;
+
+/// The list of SSH public keys of a user, with buttons to add and remove keys.
+pub struct SshPublicKeysComponent {
+    common: CommonComponentParts<Self>,
+    new_key: String,
+}
+
+#[derive(yew::Properties, Clone, PartialEq)]
+pub struct Props {
+    pub username: String,
+    pub keys: Vec<SshPublicKey>,
+    /// The SSH public key attributes that the current user can modify. New keys are added to the
+    /// first one.
+    pub editable_attributes: Vec<String>,
+    pub on_keys_changed: Callback<()>,
+    pub on_error: Callback<Error>,
+}
+
+pub enum Msg {
+    NewKeyChanged(String),
+    SubmitAddKey,
+    SubmitRemoveKey(usize),
+    UpdateUserResponse(Result<update_user::ResponseData>),
+}
+
+impl CommonComponent<SshPublicKeysComponent> for SshPublicKeysComponent {
+    fn handle_msg(
+        &mut self,
+        ctx: &Context<Self>,
+        msg: <Self as Component>::Message,
+    ) -> Result<bool> {
+        match msg {
+            Msg::NewKeyChanged(key) => self.new_key = key,
+            Msg::SubmitAddKey => {
+                let new_key = self.new_key.trim().to_owned();
+                if new_key.is_empty() {
+                    bail!("Paste a public key to add, e.g. the content of ~/.ssh/id_ed25519.pub");
+                }
+                let Some(attribute) = ctx.props().editable_attributes.first().cloned() else {
+                    bail!("No SSH public key attribute can be edited");
+                };
+                let mut values = self.get_attribute_keys(ctx, &attribute, None);
+                values.push(new_key);
+                self.submit_attribute_update(ctx, attribute, values);
+            }
+            Msg::SubmitRemoveKey(index) => {
+                let attribute = ctx.props().keys[index].attribute_name.clone();
+                let values = self.get_attribute_keys(ctx, &attribute, Some(index));
+                self.submit_attribute_update(ctx, attribute, values);
+            }
+            Msg::UpdateUserResponse(response) => {
+                response?;
+                self.new_key.clear();
+                ctx.props().on_keys_changed.emit(());
+            }
+        }
+        Ok(true)
+    }
+
+    fn mut_common(&mut self) -> &mut CommonComponentParts<Self> {
+        &mut self.common
+    }
+}
+
+impl SshPublicKeysComponent {
+    /// The keys stored in the given attribute, except for the one at index `skipped`.
+    fn get_attribute_keys(
+        &self,
+        ctx: &Context<Self>,
+        attribute: &str,
+        skipped: Option<usize>,
+    ) -> Vec<String> {
+        ctx.props()
+            .keys
+            .iter()
+            .enumerate()
+            .filter(|(i, k)| k.attribute_name == attribute && Some(*i) != skipped)
+            .map(|(_, k)| k.key.clone())
+            .collect()
+    }
+
+    fn submit_attribute_update(
+        &mut self,
+        ctx: &Context<Self>,
+        attribute: String,
+        values: Vec<String>,
+    ) {
+        let insert_attributes = (!values.is_empty()).then(|| {
+            vec![update_user::AttributeValueInput {
+                name: attribute.clone(),
+                value: values,
+            }]
+        });
+        self.common.call_graphql::<UpdateUser, _>(
+            ctx,
+            update_user::Variables {
+                user: update_user::UpdateUserInput {
+                    id: ctx.props().username.clone(),
+                    email: None,
+                    displayName: None,
+                    firstName: None,
+                    lastName: None,
+                    avatar: None,
+                    removeAttributes: Some(vec![attribute]),
+                    insertAttributes: insert_attributes,
+                    allowedSources: None,
+                },
+            },
+            Msg::UpdateUserResponse,
+            "Error trying to update the SSH public keys",
+        );
+    }
+
+    fn view_key(&self, ctx: &Context<Self>, index: usize, key: &SshPublicKey) -> Html {
+        let link = ctx.link();
+        let can_edit = ctx
+            .props()
+            .editable_attributes
+            .contains(&key.attribute_name);
+        html! {
+          <tr key={key.key.clone()}>
+            <td>{&key.key_type}</td>
+            <td><code>{&key.fingerprint}</code></td>
+            <td>{key.comment.as_deref().unwrap_or_default()}</td>
+            <td>
+              {if can_edit { html! {
+                <button
+                  class="btn btn-danger"
+                  disabled={self.common.is_task_running()}
+                  onclick={link.callback(move |_| Msg::SubmitRemoveKey(index))}>
+                  <i class="bi-x-circle-fill" aria-label="Remove key" />
+                </button>
+              } } else { html! {} } }
+            </td>
+          </tr>
+        }
+    }
+
+    fn view_add_key(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
+        if ctx.props().editable_attributes.is_empty() {
+            return html! {};
+        }
+        html! {
+          <div class="row">
+            <div class="col-sm-9">
+              <input
+                type="text"
+                class="form-control"
+                placeholder="ssh-ed25519 AAAA... user@host"
+                value={self.new_key.clone()}
+                oninput={link.callback(|e: InputEvent| {
+                    Msg::NewKeyChanged(
+                        e.target()
+                         .expect("Event should have target")
+                         .unchecked_into::<HtmlInputElement>()
+                         .value()
+                    )
+                })} />
+            </div>
+            <div class="col-sm-3">
+              <button
+                class="btn btn-secondary"
+                disabled={self.common.is_task_running()}
+                onclick={link.callback(|_| Msg::SubmitAddKey)}>
+                <i class="bi-key me-2"></i>
+                {"Add key"}
+              </button>
+            </div>
+          </div>
+        }
+    }
+}
+
+impl Component for SshPublicKeysComponent {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_: &Context<Self>) -> Self {
+        Self {
+            common: CommonComponentParts::<Self>::create(),
+            new_key: String::new(),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        CommonComponentParts::<Self>::update_and_report_error(
+            self,
+            ctx,
+            msg,
+            ctx.props().on_error.clone(),
+        )
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let keys = &ctx.props().keys;
+        html! {
+          <>
+            <h5 class="row m-3 fw-bold">{"SSH public keys"}</h5>
+            <div class="table-responsive">
+              <table class="table table-hover">
+                <thead>
+                  <tr key="headerRow">
+                    <th>{"Type"}</th>
+                    <th>{"Fingerprint"}</th>
+                    <th>{"Comment"}</th>
+                    <th></th>
+                  </tr>
+                </thead>
+                <tbody>
+                  {if keys.is_empty() {
+                    html! {
+                      <tr key="EmptyRow">
+                        <td>{"This user has no SSH public keys."}</td>
+                      </tr>
+                    }
+                  } else {
+                    html! {<>{
+                      keys
+                        .iter()
+                        .enumerate()
+                        .map(|(i, k)| self.view_key(ctx, i, k))
+                        .collect::<Vec<_>>()
+                    }</>}
+                  }}
+                </tbody>
+              </table>
+            </div>
+            {self.view_add_key(ctx)}
+          </>
+        }
+    }
+}
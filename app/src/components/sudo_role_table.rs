@@ -0,0 +1,161 @@
+use crate::{
+    components::delete_sudo_role::DeleteSudoRole,
+    infra::common_component::{CommonComponent, CommonComponentParts},
+};
+use anyhow::{Error, Result};
+use graphql_client::GraphQLQuery;
+use yew::prelude::*;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/get_sudo_role_list.graphql",
+    response_derives = "Debug,Clone,PartialEq,Eq",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct GetSudoRoleList;
+
+use get_sudo_role_list::ResponseData;
+
+pub type SudoRole = get_sudo_role_list::GetSudoRoleListSudoRoles;
+
+pub struct SudoRoleTable {
+    common: CommonComponentParts<Self>,
+    sudo_roles: Option<Vec<SudoRole>>,
+}
+
+pub enum Msg {
+    ListSudoRolesResponse(Result<ResponseData>),
+    OnSudoRoleDeleted(i64),
+    OnError(Error),
+}
+
+impl CommonComponent<SudoRoleTable> for SudoRoleTable {
+    fn handle_msg(&mut self, _: &Context<Self>, msg: <Self as Component>::Message) -> Result<bool> {
+        match msg {
+            Msg::ListSudoRolesResponse(sudo_roles) => {
+                self.sudo_roles = Some(sudo_roles?.sudo_roles);
+                Ok(true)
+            }
+            Msg::OnError(e) => Err(e),
+            Msg::OnSudoRoleDeleted(id) => {
+                debug_assert!(self.sudo_roles.is_some());
+                self.sudo_roles.as_mut().unwrap().retain(|r| r.id != id);
+                Ok(true)
+            }
+        }
+    }
+
+    fn mut_common(&mut self) -> &mut CommonComponentParts<Self> {
+        &mut self.common
+    }
+}
+
+impl Component for SudoRoleTable {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let mut table = SudoRoleTable {
+            common: CommonComponentParts::<Self>::create(),
+            sudo_roles: None,
+        };
+        table.common.call_graphql::<GetSudoRoleList, _>(
+            ctx,
+            get_sudo_role_list::Variables {},
+            Msg::ListSudoRolesResponse,
+            "Error trying to fetch sudo rules",
+        );
+        table
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        CommonComponentParts::<Self>::update(self, ctx, msg)
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div>
+              {self.view_sudo_roles(ctx)}
+              {self.view_errors()}
+            </div>
+        }
+    }
+}
+
+fn view_values(values: &[String]) -> Html {
+    html! {
+      <>
+        {values.iter().map(|v| html! {<div><code>{v}</code></div>}).collect::<Vec<_>>()}
+      </>
+    }
+}
+
+impl SudoRoleTable {
+    fn view_sudo_roles(&self, ctx: &Context<Self>) -> Html {
+        let make_table = |sudo_roles: &Vec<SudoRole>| {
+            html! {
+                <div class="table-responsive">
+                  <table class="table table-hover">
+                    <thead>
+                      <tr>
+                        <th>{"Name"}</th>
+                        <th>{"Users"}</th>
+                        <th>{"Hosts"}</th>
+                        <th>{"Commands"}</th>
+                        <th>{"Run as"}</th>
+                        <th>{"Options"}</th>
+                        <th>{"Order"}</th>
+                        <th>{"Delete"}</th>
+                      </tr>
+                    </thead>
+                    <tbody>
+                      {sudo_roles.iter().map(|r| self.view_sudo_role(ctx, r)).collect::<Vec<_>>()}
+                    </tbody>
+                  </table>
+                </div>
+            }
+        };
+        match &self.sudo_roles {
+            None => html! {{"Loading..."}},
+            Some(sudo_roles) => make_table(sudo_roles),
+        }
+    }
+
+    fn view_sudo_role(&self, ctx: &Context<Self>, sudo_role: &SudoRole) -> Html {
+        let link = ctx.link();
+        let run_as = sudo_role
+            .run_as_users
+            .iter()
+            .cloned()
+            .chain(sudo_role.run_as_groups.iter().map(|g| format!(":{g}")))
+            .collect::<Vec<_>>();
+        html! {
+          <tr key={sudo_role.id}>
+              <td>
+                <div>{&sudo_role.name}</div>
+                <small class="text-muted">{sudo_role.description.as_deref().unwrap_or_default()}</small>
+              </td>
+              <td>{view_values(&sudo_role.users)}</td>
+              <td>{view_values(&sudo_role.hosts)}</td>
+              <td>{view_values(&sudo_role.commands)}</td>
+              <td>{view_values(&run_as)}</td>
+              <td>{view_values(&sudo_role.options)}</td>
+              <td>{sudo_role.order}</td>
+              <td>
+                <DeleteSudoRole
+                  sudo_role={sudo_role.clone()}
+                  on_sudo_role_deleted={link.callback(Msg::OnSudoRoleDeleted)}
+                  on_error={link.callback(Msg::OnError)}/>
+              </td>
+          </tr>
+        }
+    }
+
+    fn view_errors(&self) -> Html {
+        match &self.common.error {
+            None => html! {},
+            Some(e) => html! {<div>{"Error: "}{e.to_string()}</div>},
+        }
+    }
+}
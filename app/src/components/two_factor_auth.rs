@@ -0,0 +1,297 @@
+use crate::infra::{
+    common_component::{CommonComponent, CommonComponentParts},
+    cookies::get_cookie,
+};
+use anyhow::{Context as _, Error, Result, bail};
+use graphql_client::GraphQLQuery;
+use qrcode::{QrCode, render::svg};
+use wasm_bindgen::JsCast;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/start_totp_enrollment.graphql",
+    response_derives = "Debug",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct StartTotpEnrollment;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/confirm_totp_enrollment.graphql",
+    response_derives = "Debug",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct ConfirmTotpEnrollment;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/reset_user_mfa.graphql",
+    response_derives = "Debug",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct ResetUserMfa;
+
+type TotpEnrollment = start_totp_enrollment::StartTotpEnrollmentStartTotpEnrollment;
+
+/// The second factor of a user: the TOTP setup for the user themselves, and the reset for admins.
+pub struct TwoFactorAuthComponent {
+    common: CommonComponentParts<Self>,
+    /// Set while the user scans the QR code, until they confirm it with a code.
+    enrollment: Option<TotpEnrollment>,
+    code: String,
+    /// Only shown once, right after the enrollment.
+    recovery_codes: Option<Vec<String>>,
+}
+
+#[derive(yew::Properties, Clone, PartialEq)]
+pub struct Props {
+    pub username: String,
+    pub mfa_enabled: bool,
+    pub is_admin: bool,
+    pub on_mfa_changed: Callback<()>,
+    pub on_error: Callback<Error>,
+}
+
+pub enum Msg {
+    SubmitStartEnrollment,
+    StartTotpEnrollmentResponse(Result<start_totp_enrollment::ResponseData>),
+    CodeChanged(String),
+    SubmitConfirmEnrollment,
+    ConfirmTotpEnrollmentResponse(Result<confirm_totp_enrollment::ResponseData>),
+    RecoveryCodesSaved,
+    SubmitReset,
+    ResetUserMfaResponse(Result<reset_user_mfa::ResponseData>),
+}
+
+impl CommonComponent<TwoFactorAuthComponent> for TwoFactorAuthComponent {
+    fn handle_msg(
+        &mut self,
+        ctx: &Context<Self>,
+        msg: <Self as Component>::Message,
+    ) -> Result<bool> {
+        match msg {
+            Msg::SubmitStartEnrollment => {
+                self.common.call_graphql::<StartTotpEnrollment, _>(
+                    ctx,
+                    start_totp_enrollment::Variables {},
+                    Msg::StartTotpEnrollmentResponse,
+                    "Error trying to set up the second factor",
+                );
+            }
+            Msg::StartTotpEnrollmentResponse(response) => {
+                self.enrollment = Some(response?.start_totp_enrollment);
+                self.code.clear();
+            }
+            Msg::CodeChanged(code) => self.code = code,
+            Msg::SubmitConfirmEnrollment => {
+                if self.code.trim().is_empty() {
+                    bail!("Enter the code from your authenticator app first");
+                }
+                self.common.call_graphql::<ConfirmTotpEnrollment, _>(
+                    ctx,
+                    confirm_totp_enrollment::Variables {
+                        code: self.code.trim().to_owned(),
+                    },
+                    Msg::ConfirmTotpEnrollmentResponse,
+                    "Error trying to confirm the second factor",
+                );
+            }
+            Msg::ConfirmTotpEnrollmentResponse(response) => {
+                self.recovery_codes = Some(response?.confirm_totp_enrollment);
+                self.enrollment = None;
+                self.code.clear();
+            }
+            Msg::RecoveryCodesSaved => {
+                self.recovery_codes = None;
+                ctx.props().on_mfa_changed.emit(());
+            }
+            Msg::SubmitReset => {
+                self.common.call_graphql::<ResetUserMfa, _>(
+                    ctx,
+                    reset_user_mfa::Variables {
+                        user: ctx.props().username.clone(),
+                    },
+                    Msg::ResetUserMfaResponse,
+                    "Error trying to reset the second factor",
+                );
+            }
+            Msg::ResetUserMfaResponse(response) => {
+                response?;
+                ctx.props().on_mfa_changed.emit(());
+            }
+        }
+        Ok(true)
+    }
+
+    fn mut_common(&mut self) -> &mut CommonComponentParts<Self> {
+        &mut self.common
+    }
+}
+
+fn get_qr_code_data_uri(uri: &str) -> Result<String> {
+    let svg = QrCode::new(uri.as_bytes())
+        .context("Could not generate the QR code")?
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build();
+    Ok(format!("data:image/svg+xml;base64,{}", base64::encode(svg)))
+}
+
+impl TwoFactorAuthComponent {
+    fn is_current_user(ctx: &Context<Self>) -> bool {
+        get_cookie("user_id")
+            .ok()
+            .flatten()
+            .is_some_and(|user_id| user_id == ctx.props().username)
+    }
+
+    fn view_recovery_codes(&self, ctx: &Context<Self>, recovery_codes: &[String]) -> Html {
+        let link = ctx.link();
+        html! {
+          <div>
+            <p>
+              {"Two-factor authentication is enabled. Save these recovery codes somewhere safe: \
+                each of them can be used once instead of a code, if you lose your device. \
+                They will not be shown again."}
+            </p>
+            <ul class="list-unstyled font-monospace">
+              {for recovery_codes.iter().map(|code| html! {<li>{code}</li>})}
+            </ul>
+            <p>
+              {"If you were asked to set up a second factor, log out and log in again to get \
+                your permissions."}
+            </p>
+            <button
+              class="btn btn-primary"
+              onclick={link.callback(|_| Msg::RecoveryCodesSaved)}>
+              <i class="bi-check2 me-2"></i>
+              {"I saved the recovery codes"}
+            </button>
+          </div>
+        }
+    }
+
+    fn view_enrollment(&self, ctx: &Context<Self>, enrollment: &TotpEnrollment) -> Html {
+        let link = ctx.link();
+        let qr_code = match get_qr_code_data_uri(&enrollment.uri) {
+            Ok(data_uri) => html! {<img src={data_uri} alt="TOTP QR code" class="mb-2" />},
+            Err(e) => html! {<span class="text-danger">{e.to_string()}</span>},
+        };
+        html! {
+          <div>
+            <p>
+              {"Scan this QR code with your authenticator app, then enter the code it shows."}
+            </p>
+            {qr_code}
+            <p>
+              {"If you cannot scan it, enter this secret instead: "}
+              <code>{&enrollment.secret}</code>
+            </p>
+            <div class="row">
+              <div class="col-sm-4">
+                <input
+                  type="text"
+                  class="form-control"
+                  placeholder="Code"
+                  autocomplete="one-time-code"
+                  value={self.code.clone()}
+                  oninput={link.callback(|e: InputEvent| {
+                      Msg::CodeChanged(
+                          e.target()
+                           .expect("Event should have target")
+                           .unchecked_into::<HtmlInputElement>()
+                           .value()
+                      )
+                  })} />
+              </div>
+              <div class="col-sm-4">
+                <button
+                  class="btn btn-primary"
+                  disabled={self.common.is_task_running()}
+                  onclick={link.callback(|_| Msg::SubmitConfirmEnrollment)}>
+                  <i class="bi-shield-check me-2"></i>
+                  {"Confirm"}
+                </button>
+              </div>
+            </div>
+          </div>
+        }
+    }
+
+    fn view_status(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
+        let can_enroll = !ctx.props().mfa_enabled && Self::is_current_user(ctx);
+        html! {
+          <div>
+            {if ctx.props().mfa_enabled { html! {
+              <span>{"Enabled: a code is asked after the password when logging in."}</span>
+            } } else { html! {
+              <span>{"Not enabled."}</span>
+            } } }
+            <div class="mt-2">
+              {if can_enroll { html! {
+                <button
+                  class="btn btn-secondary me-2"
+                  disabled={self.common.is_task_running()}
+                  onclick={link.callback(|_| Msg::SubmitStartEnrollment)}>
+                  <i class="bi-shield-lock me-2"></i>
+                  {"Set up two-factor authentication"}
+                </button>
+              } } else { html! {} } }
+              {if ctx.props().mfa_enabled && ctx.props().is_admin { html! {
+                <button
+                  class="btn btn-outline-danger"
+                  disabled={self.common.is_task_running()}
+                  onclick={link.callback(|_| Msg::SubmitReset)}>
+                  <i class="bi-shield-x me-2"></i>
+                  {"Reset second factor"}
+                </button>
+              } } else { html! {} } }
+            </div>
+          </div>
+        }
+    }
+}
+
+impl Component for TwoFactorAuthComponent {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_: &Context<Self>) -> Self {
+        Self {
+            common: CommonComponentParts::<Self>::create(),
+            enrollment: None,
+            code: String::new(),
+            recovery_codes: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        CommonComponentParts::<Self>::update_and_report_error(
+            self,
+            ctx,
+            msg,
+            ctx.props().on_error.clone(),
+        )
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+          <>
+            <h5 class="row m-3 fw-bold">{"Two-factor authentication"}</h5>
+            <div class="mx-3">
+              {match (&self.recovery_codes, &self.enrollment) {
+                  (Some(recovery_codes), _) => self.view_recovery_codes(ctx, recovery_codes),
+                  (None, Some(enrollment)) => self.view_enrollment(ctx, enrollment),
+                  (None, None) => self.view_status(ctx),
+              }}
+            </div>
+          </>
+        }
+    }
+}
@@ -1,9 +1,14 @@
 use crate::{
     components::{
+        account_expiration::AccountExpirationComponent,
         add_user_to_group::AddUserToGroupComponent,
+        app_passwords::AppPasswordsComponent,
         remove_user_from_group::RemoveUserFromGroupComponent,
         router::{AppRoute, Link},
+        ssh_public_keys::SshPublicKeysComponent,
+        two_factor_auth::TwoFactorAuthComponent,
         user_details_form::UserDetailsForm,
+        webauthn_credentials::WebauthnCredentialsComponent,
     },
     infra::{
         common_component::{CommonComponent, CommonComponentParts},
@@ -25,10 +30,22 @@ use yew::prelude::*;
 )]
 pub struct GetUserDetails;
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/set_user_disabled.graphql",
+    response_derives = "Debug",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct SetUserDisabled;
+
 pub type User = get_user_details::GetUserDetailsUser;
 pub type Group = get_user_details::GetUserDetailsUserGroups;
 pub type Attribute = get_user_details::GetUserDetailsUserAttributes;
 pub type AttributeSchema = get_user_details::GetUserDetailsSchemaUserSchemaAttributes;
+pub type SshPublicKey = get_user_details::GetUserDetailsUserSshPublicKeys;
+pub type WebauthnCredential = get_user_details::GetUserDetailsUserWebauthnCredentials;
+pub type AppPassword = get_user_details::GetUserDetailsUserAppPasswords;
 
 impl From<&AttributeSchema> for GraphQlAttributeSchema {
     fn from(attr: &AttributeSchema) -> Self {
@@ -62,16 +79,28 @@ pub enum Msg {
     OnError(Error),
     OnUserAddedToGroup(Group),
     OnUserRemovedFromGroup((String, i64)),
+    OnSshPublicKeysChanged,
+    OnExpirationDateChanged,
+    OnMfaChanged,
+    OnWebauthnCredentialsChanged,
+    OnAppPasswordsChanged,
+    SubmitToggleDisabled,
+    SetUserDisabledResponse(Result<set_user_disabled::ResponseData>),
 }
 
 #[derive(yew::Properties, Clone, PartialEq, Eq)]
 pub struct Props {
     pub username: String,
     pub is_admin: bool,
+    pub webauthn_enabled: bool,
 }
 
 impl CommonComponent<UserDetails> for UserDetails {
-    fn handle_msg(&mut self, _: &Context<Self>, msg: <Self as Component>::Message) -> Result<bool> {
+    fn handle_msg(
+        &mut self,
+        ctx: &Context<Self>,
+        msg: <Self as Component>::Message,
+    ) -> Result<bool> {
         match msg {
             Msg::UserDetailsResponse(response) => match response {
                 Ok(user) => {
@@ -89,6 +118,29 @@ impl CommonComponent<UserDetails> for UserDetails {
             Msg::OnUserRemovedFromGroup((_, group_id)) => {
                 self.mut_groups().retain(|g| g.id != group_id);
             }
+            Msg::OnSshPublicKeysChanged
+            | Msg::OnExpirationDateChanged
+            | Msg::OnMfaChanged
+            | Msg::OnWebauthnCredentialsChanged
+            | Msg::OnAppPasswordsChanged => {
+                self.get_user_details(ctx);
+            }
+            Msg::SubmitToggleDisabled => {
+                let disabled = !self.user_and_schema.as_ref().unwrap().0.disabled;
+                self.common.call_graphql::<SetUserDisabled, _>(
+                    ctx,
+                    set_user_disabled::Variables {
+                        user: ctx.props().username.clone(),
+                        disabled,
+                    },
+                    Msg::SetUserDisabledResponse,
+                    "Error trying to update the user",
+                );
+            }
+            Msg::SetUserDisabledResponse(response) => {
+                response?;
+                self.get_user_details(ctx);
+            }
         }
         Ok(true)
     }
@@ -177,6 +229,105 @@ impl UserDetails {
         }
     }
 
+    fn view_ssh_public_keys(
+        &self,
+        ctx: &Context<Self>,
+        u: &User,
+        schema: &[AttributeSchema],
+    ) -> Html {
+        let link = &ctx.link();
+        let ssh_attributes = schema
+            .iter()
+            .filter(|a| a.attribute_type == AttributeType::SshPublicKey)
+            .collect::<Vec<_>>();
+        if ssh_attributes.is_empty() {
+            return html! {};
+        }
+        let editable_attributes = ssh_attributes
+            .iter()
+            .filter(|a| (ctx.props().is_admin || a.is_editable) && !a.is_readonly)
+            .map(|a| a.name.clone())
+            .collect::<Vec<_>>();
+        html! {
+            <SshPublicKeysComponent
+                username={u.id.clone()}
+                keys={u.ssh_public_keys.clone()}
+                editable_attributes={editable_attributes}
+                on_keys_changed={link.callback(|_| Msg::OnSshPublicKeysChanged)}
+                on_error={link.callback(Msg::OnError)}/>
+        }
+    }
+
+    fn view_account_expiration(&self, ctx: &Context<Self>, u: &User) -> Html {
+        let link = &ctx.link();
+        if !ctx.props().is_admin && u.expiration_date.is_none() {
+            return html! {};
+        }
+        html! {
+            <AccountExpirationComponent
+                username={u.id.clone()}
+                expiration_date={u.expiration_date}
+                is_admin={ctx.props().is_admin}
+                on_expiration_changed={link.callback(|_| Msg::OnExpirationDateChanged)}
+                on_error={link.callback(Msg::OnError)}/>
+        }
+    }
+
+    fn view_two_factor_auth(&self, ctx: &Context<Self>, u: &User) -> Html {
+        let link = &ctx.link();
+        html! {
+            <TwoFactorAuthComponent
+                username={u.id.clone()}
+                mfa_enabled={u.mfa_enabled}
+                is_admin={ctx.props().is_admin}
+                on_mfa_changed={link.callback(|_| Msg::OnMfaChanged)}
+                on_error={link.callback(Msg::OnError)}/>
+        }
+    }
+
+    fn view_webauthn_credentials(&self, ctx: &Context<Self>, u: &User) -> Html {
+        let link = &ctx.link();
+        html! {
+            <WebauthnCredentialsComponent
+                username={u.id.clone()}
+                credentials={u.webauthn_credentials.clone()}
+                webauthn_enabled={ctx.props().webauthn_enabled}
+                on_credentials_changed={link.callback(|_| Msg::OnWebauthnCredentialsChanged)}
+                on_error={link.callback(Msg::OnError)}/>
+        }
+    }
+
+    fn view_app_passwords(&self, ctx: &Context<Self>, u: &User) -> Html {
+        let link = &ctx.link();
+        html! {
+            <AppPasswordsComponent
+                username={u.id.clone()}
+                app_passwords={u.app_passwords.clone()}
+                groups={u.groups.clone()}
+                on_app_passwords_changed={link.callback(|_| Msg::OnAppPasswordsChanged)}
+                on_error={link.callback(Msg::OnError)}/>
+        }
+    }
+
+    fn view_toggle_disabled_button(&self, ctx: &Context<Self>, u: &User) -> Html {
+        let link = &ctx.link();
+        if !ctx.props().is_admin {
+            return html! {};
+        }
+        html! {
+          <button
+            class={if u.disabled { "btn btn-success me-2" } else { "btn btn-warning me-2" }}
+            disabled={self.common.is_task_running()}
+            onclick={link.callback(|_| Msg::SubmitToggleDisabled)}>
+            {if u.disabled { html! {
+              <><i class="bi-unlock me-2"></i>{"Enable account"}</>
+            } } else { html! {
+              <><i class="bi-lock me-2"></i>{"Disable account"}</>
+            } } }
+          </button>
+        }
+    }
+
     fn view_add_group_button(&self, ctx: &Context<Self>, u: &User) -> Html {
         let link = &ctx.link();
         if ctx.props().is_admin {
@@ -215,7 +366,15 @@ impl Component for UserDetails {
             (Some((u, schema)), error) => {
                 html! {
                   <>
-                    <h3>{u.id.to_string()}</h3>
+                    <h3>
+                      {u.id.to_string()}
+                      {if u.disabled { html! {
+                        <span class="badge bg-secondary ms-2">{"Disabled"}</span>
+                      } } else { html! {} } }
+                      {if u.expiration_date.is_some_and(|d| d <= chrono::Utc::now()) { html! {
+                        <span class="badge bg-secondary ms-2">{"Expired"}</span>
+                      } } else { html! {} } }
+                    </h3>
                     <div class="d-flex flex-row-reverse">
                       <Link
                         to={AppRoute::ChangePassword{user_id: u.id.clone()}}
@@ -223,6 +382,7 @@ impl Component for UserDetails {
                         <i class="bi-key me-2"></i>
                         {"Modify password"}
                       </Link>
+                      {self.view_toggle_disabled_button(ctx, u)}
                     </div>
                     <div>
                       <h5 class="row m-3 fw-bold">{"User details"}</h5>
@@ -235,6 +395,11 @@ impl Component for UserDetails {
                     />
                     {self.view_group_memberships(ctx, u)}
                     {self.view_add_group_button(ctx, u)}
+                    {self.view_ssh_public_keys(ctx, u, schema)}
+                    {self.view_account_expiration(ctx, u)}
+                    {self.view_two_factor_auth(ctx, u)}
+                    {self.view_webauthn_credentials(ctx, u)}
+                    {self.view_app_passwords(ctx, u)}
                     {self.view_messages(error)}
                   </>
                 }
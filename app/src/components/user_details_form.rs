@@ -122,6 +122,7 @@ impl Component for UserDetailsForm {
                       .user_attributes_schema
                       .iter()
                       .filter(|a| a.is_hardcoded && a.name != "user_id")
+                      .filter(|a| is_form_attribute(a))
                       .map(display_field)
                       .collect::<Vec<_>>()
               }
@@ -131,6 +132,7 @@ impl Component for UserDetailsForm {
                       .user_attributes_schema
                       .iter()
                       .filter(|a| !a.is_hardcoded)
+                      .filter(|a| is_form_attribute(a))
                       .map(display_field)
                       .collect::<Vec<_>>()
               }
@@ -156,6 +158,11 @@ impl Component for UserDetailsForm {
     }
 }
 
+/// SSH public keys are managed separately, in the user's key list.
+fn is_form_attribute(attribute_schema: &AttributeSchema) -> bool {
+    attribute_schema.attribute_type != AttributeType::SshPublicKey
+}
+
 fn get_custom_attribute_input(
     attribute_schema: &AttributeSchema,
     user_attributes: &[Attribute],
@@ -194,7 +201,9 @@ fn get_custom_attribute_static(
         .map(|attribute| attribute.value.clone())
         .unwrap_or_default();
     let value_to_str = match attribute_schema.attribute_type {
-        AttributeType::String | AttributeType::Integer => |v: String| v,
+        AttributeType::String | AttributeType::Integer | AttributeType::SshPublicKey => {
+            |v: String| v
+        }
         AttributeType::DateTime => |v: String| {
             console!(format!("Parsing date: {}", &v));
             chrono::DateTime::parse_from_rfc3339(&v)
@@ -221,7 +230,10 @@ impl UserDetailsForm {
         //     bail!("Image file hasn't finished loading, try again");
         // }
         let mut all_values = read_all_form_attributes(
-            ctx.props().user_attributes_schema.iter(),
+            ctx.props()
+                .user_attributes_schema
+                .iter()
+                .filter(|a| is_form_attribute(a)),
             &self.form_ref,
             IsAdmin(ctx.props().is_admin),
             EmailIsRequired(!ctx.props().is_edited_user_admin),
@@ -266,6 +278,7 @@ impl UserDetailsForm {
             avatar: None,
             removeAttributes: None,
             insertAttributes: None,
+            allowedSources: None,
         };
         let default_user_input = user_input.clone();
         user_input.removeAttributes = remove_attributes;
@@ -0,0 +1,308 @@
+use crate::{
+    components::user_details::WebauthnCredential,
+    infra::{
+        common_component::{CommonComponent, CommonComponentParts},
+        cookies::get_cookie,
+        webauthn,
+    },
+};
+use anyhow::{Error, Result, bail};
+use graphql_client::GraphQLQuery;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/rename_webauthn_credential.graphql",
+    response_derives = "Debug",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct RenameWebauthnCredential;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../schema.graphql",
+    query_path = "queries/delete_webauthn_credential.graphql",
+    response_derives = "Debug",
+    custom_scalars_module = "crate::infra::graphql"
+)]
+pub struct DeleteWebauthnCredential;
+
+/// The passkeys and security keys of a user, that can be renamed and revoked. Only the user
+/// themselves can register new ones, since the browser talks to their authenticator.
+pub struct WebauthnCredentialsComponent {
+    common: CommonComponentParts<Self>,
+    new_name: String,
+    /// The credential being renamed, with the new name.
+    renaming: Option<(i64, String)>,
+}
+
+#[derive(yew::Properties, Clone, PartialEq)]
+pub struct Props {
+    pub username: String,
+    pub credentials: Vec<WebauthnCredential>,
+    pub webauthn_enabled: bool,
+    pub on_credentials_changed: Callback<()>,
+    pub on_error: Callback<Error>,
+}
+
+pub enum Msg {
+    NewNameChanged(String),
+    SubmitRegister,
+    RegisterResponse(Result<()>),
+    StartRename(i64),
+    RenameChanged(String),
+    CancelRename,
+    SubmitRename,
+    RenameWebauthnCredentialResponse(Result<rename_webauthn_credential::ResponseData>),
+    SubmitDelete(i64),
+    DeleteWebauthnCredentialResponse(Result<delete_webauthn_credential::ResponseData>),
+}
+
+impl CommonComponent<WebauthnCredentialsComponent> for WebauthnCredentialsComponent {
+    fn handle_msg(
+        &mut self,
+        ctx: &Context<Self>,
+        msg: <Self as Component>::Message,
+    ) -> Result<bool> {
+        match msg {
+            Msg::NewNameChanged(name) => self.new_name = name,
+            Msg::SubmitRegister => {
+                let name = self.new_name.trim().to_owned();
+                if name.is_empty() {
+                    bail!("Give the security key a name first, e.g. \"YubiKey\"");
+                }
+                self.common
+                    .call_backend(ctx, webauthn::register(name), Msg::RegisterResponse);
+            }
+            Msg::RegisterResponse(response) => {
+                response?;
+                self.new_name.clear();
+                ctx.props().on_credentials_changed.emit(());
+            }
+            Msg::StartRename(id) => {
+                let name = ctx
+                    .props()
+                    .credentials
+                    .iter()
+                    .find(|c| c.id == id)
+                    .map(|c| c.name.clone())
+                    .unwrap_or_default();
+                self.renaming = Some((id, name));
+            }
+            Msg::RenameChanged(name) => {
+                if let Some((_, new_name)) = &mut self.renaming {
+                    *new_name = name;
+                }
+            }
+            Msg::CancelRename => self.renaming = None,
+            Msg::SubmitRename => {
+                let Some((id, name)) = self.renaming.clone() else {
+                    return Ok(false);
+                };
+                if name.trim().is_empty() {
+                    bail!("The name of the security key cannot be empty");
+                }
+                self.common.call_graphql::<RenameWebauthnCredential, _>(
+                    ctx,
+                    rename_webauthn_credential::Variables {
+                        user: ctx.props().username.clone(),
+                        id,
+                        name: name.trim().to_owned(),
+                    },
+                    Msg::RenameWebauthnCredentialResponse,
+                    "Error trying to rename the security key",
+                );
+            }
+            Msg::RenameWebauthnCredentialResponse(response) => {
+                response?;
+                self.renaming = None;
+                ctx.props().on_credentials_changed.emit(());
+            }
+            Msg::SubmitDelete(id) => {
+                self.common.call_graphql::<DeleteWebauthnCredential, _>(
+                    ctx,
+                    delete_webauthn_credential::Variables {
+                        user: ctx.props().username.clone(),
+                        id,
+                    },
+                    Msg::DeleteWebauthnCredentialResponse,
+                    "Error trying to revoke the security key",
+                );
+            }
+            Msg::DeleteWebauthnCredentialResponse(response) => {
+                response?;
+                ctx.props().on_credentials_changed.emit(());
+            }
+        }
+        Ok(true)
+    }
+
+    fn mut_common(&mut self) -> &mut CommonComponentParts<Self> {
+        &mut self.common
+    }
+}
+
+fn get_input_value(e: InputEvent) -> String {
+    e.target()
+        .expect("Event should have target")
+        .unchecked_into::<HtmlInputElement>()
+        .value()
+}
+
+impl WebauthnCredentialsComponent {
+    fn is_current_user(ctx: &Context<Self>) -> bool {
+        get_cookie("user_id")
+            .ok()
+            .flatten()
+            .is_some_and(|user_id| user_id == ctx.props().username)
+    }
+
+    fn view_name(&self, ctx: &Context<Self>, credential: &WebauthnCredential) -> Html {
+        let link = ctx.link();
+        match &self.renaming {
+            Some((id, name)) if *id == credential.id => html! {
+              <div class="input-group">
+                <input
+                  type="text"
+                  class="form-control"
+                  value={name.clone()}
+                  oninput={link.callback(|e: InputEvent| Msg::RenameChanged(get_input_value(e)))} />
+                <button
+                  class="btn btn-primary"
+                  disabled={self.common.is_task_running()}
+                  onclick={link.callback(|_| Msg::SubmitRename)}>
+                  <i class="bi-check2" aria-label="Save" />
+                </button>
+                <button
+                  class="btn btn-secondary"
+                  onclick={link.callback(|_| Msg::CancelRename)}>
+                  <i class="bi-x" aria-label="Cancel" />
+                </button>
+              </div>
+            },
+            _ => html! {<>{&credential.name}</>},
+        }
+    }
+
+    fn view_credential(&self, ctx: &Context<Self>, credential: &WebauthnCredential) -> Html {
+        let link = ctx.link();
+        let id = credential.id;
+        html! {
+          <tr key={id.to_string()}>
+            <td>{self.view_name(ctx, credential)}</td>
+            <td>{&credential.creation_date.naive_local().date()}</td>
+            <td>
+              {match &credential.last_used_date {
+                  Some(date) => html! {<>{date.naive_local().date()}</>},
+                  None => html! {{"Never"}},
+              }}
+            </td>
+            <td>
+              <button
+                class="btn btn-secondary me-2"
+                disabled={self.common.is_task_running()}
+                onclick={link.callback(move |_| Msg::StartRename(id))}>
+                <i class="bi-pencil" aria-label="Rename" />
+              </button>
+              <button
+                class="btn btn-danger"
+                disabled={self.common.is_task_running()}
+                onclick={link.callback(move |_| Msg::SubmitDelete(id))}>
+                <i class="bi-x-circle-fill" aria-label="Revoke" />
+              </button>
+            </td>
+          </tr>
+        }
+    }
+
+    fn view_register(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
+        if !ctx.props().webauthn_enabled || !Self::is_current_user(ctx) {
+            return html! {};
+        }
+        html! {
+          <div class="row">
+            <div class="col-sm-9">
+              <input
+                type="text"
+                class="form-control"
+                placeholder="Name of the new security key"
+                value={self.new_name.clone()}
+                oninput={link.callback(|e: InputEvent| Msg::NewNameChanged(get_input_value(e)))} />
+            </div>
+            <div class="col-sm-3">
+              <button
+                class="btn btn-secondary"
+                disabled={self.common.is_task_running()}
+                onclick={link.callback(|_| Msg::SubmitRegister)}>
+                <i class="bi-usb-drive me-2"></i>
+                {"Add security key"}
+              </button>
+            </div>
+          </div>
+        }
+    }
+}
+
+impl Component for WebauthnCredentialsComponent {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_: &Context<Self>) -> Self {
+        Self {
+            common: CommonComponentParts::<Self>::create(),
+            new_name: String::new(),
+            renaming: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        CommonComponentParts::<Self>::update_and_report_error(
+            self,
+            ctx,
+            msg,
+            ctx.props().on_error.clone(),
+        )
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let credentials = &ctx.props().credentials;
+        html! {
+          <>
+            <h5 class="row m-3 fw-bold">{"Passkeys and security keys"}</h5>
+            <div class="table-responsive">
+              <table class="table table-hover">
+                <thead>
+                  <tr key="headerRow">
+                    <th>{"Name"}</th>
+                    <th>{"Added"}</th>
+                    <th>{"Last used"}</th>
+                    <th></th>
+                  </tr>
+                </thead>
+                <tbody>
+                  {if credentials.is_empty() {
+                    html! {
+                      <tr key="EmptyRow">
+                        <td>{"This user has no passkeys or security keys."}</td>
+                      </tr>
+                    }
+                  } else {
+                    html! {<>{
+                      credentials
+                        .iter()
+                        .map(|c| self.view_credential(ctx, c))
+                        .collect::<Vec<_>>()
+                    }</>}
+                  }}
+                </tbody>
+              </table>
+            </div>
+            {self.view_register(ctx)}
+          </>
+        }
+    }
+}
@@ -2,9 +2,10 @@ use super::cookies::set_cookie;
 use anyhow::{Context, Result, anyhow};
 use gloo_net::http::{Method, RequestBuilder};
 use graphql_client::GraphQLQuery;
-use lldap_auth::{JWTClaims, login, registration};
+use lldap_auth::{JWTClaims, login, registration, webauthn};
 
 use lldap_frontend_options::Options;
+use lldap_validation::breached_passwords::BreachedPasswordRange;
 use serde::{Serialize, de::DeserializeOwned};
 use web_sys::RequestCredentials;
 
@@ -77,12 +78,34 @@ async fn call_server_empty_response_with_error_message<Body: Serialize>(
     call_server(url, request, error_message).await.map(|_| ())
 }
 
-fn set_cookies_from_jwt(response: login::ServerLoginResponse) -> Result<(String, bool)> {
+/// The logged in user, and what they have to do before anything else.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LoginInfo {
+    pub user_id: String,
+    pub is_admin: bool,
+    pub must_change_password: bool,
+    pub must_enroll_mfa: bool,
+}
+
+/// The outcome of a correct password.
+pub enum LoginResult {
+    LoggedIn(LoginInfo),
+    /// The user has a second factor: the token has to be sent back with the code or the
+    /// security key.
+    MfaRequired(login::ServerMfaRequiredResponse),
+}
+
+fn set_cookies_from_jwt(response: login::ServerLoginResponse) -> Result<LoginInfo> {
     let jwt_claims = get_claims_from_jwt(response.token.as_str()).context("Could not parse JWT")?;
     let is_admin = jwt_claims.groups.contains("lldap_admin");
     set_cookie("user_id", &jwt_claims.user, &jwt_claims.exp)
         .map(|_| set_cookie("is_admin", &is_admin.to_string(), &jwt_claims.exp))
-        .map(|_| (jwt_claims.user.clone(), is_admin))
+        .map(|_| LoginInfo {
+            user_id: jwt_claims.user.clone(),
+            is_admin,
+            must_change_password: response.must_change_password,
+            must_enroll_mfa: response.must_enroll_mfa,
+        })
         .context("Error setting cookie")
 }
 
@@ -128,16 +151,76 @@ impl HostService {
         .await
     }
 
-    pub async fn login_finish(request: login::ClientLoginFinishRequest) -> Result<(String, bool)> {
-        call_server_json_with_error_message::<login::ServerLoginResponse, _>(
+    pub async fn login_finish(request: login::ClientLoginFinishRequest) -> Result<LoginResult> {
+        match call_server_json_with_error_message::<login::ServerLoginResult, _>(
             &(base_url() + "/auth/opaque/login/finish"),
             RequestType::Post(request),
             "Could not finish authentication",
         )
+        .await?
+        {
+            login::ServerLoginResult::Success(response) => {
+                set_cookies_from_jwt(response).map(LoginResult::LoggedIn)
+            }
+            login::ServerLoginResult::MfaRequired(response) => {
+                Ok(LoginResult::MfaRequired(response))
+            }
+        }
+    }
+
+    pub async fn mfa_verify(request: login::ClientMfaVerifyRequest) -> Result<LoginInfo> {
+        call_server_json_with_error_message::<login::ServerLoginResponse, _>(
+            &(base_url() + "/auth/mfa/verify"),
+            RequestType::Post(request),
+            "Could not verify the code",
+        )
         .await
         .and_then(set_cookies_from_jwt)
     }
 
+    pub async fn webauthn_login_start(
+        request: webauthn::ClientLoginStartRequest,
+    ) -> Result<webauthn::ServerLoginStartResponse> {
+        call_server_json_with_error_message(
+            &(base_url() + "/auth/webauthn/login/start"),
+            RequestType::Post(request),
+            "Could not start the security key login: ",
+        )
+        .await
+    }
+
+    pub async fn webauthn_login_finish(
+        request: webauthn::ClientLoginFinishRequest,
+    ) -> Result<LoginInfo> {
+        call_server_json_with_error_message::<login::ServerLoginResponse, _>(
+            &(base_url() + "/auth/webauthn/login/finish"),
+            RequestType::Post(request),
+            "Could not log in with the security key",
+        )
+        .await
+        .and_then(set_cookies_from_jwt)
+    }
+
+    pub async fn webauthn_register_start() -> Result<webauthn::ServerRegistrationStartResponse> {
+        call_server_json_with_error_message(
+            &(base_url() + "/auth/webauthn/register/start"),
+            RequestType::Post(""),
+            "Could not start the security key registration: ",
+        )
+        .await
+    }
+
+    pub async fn webauthn_register_finish(
+        request: webauthn::ClientRegistrationFinishRequest,
+    ) -> Result<()> {
+        call_server_empty_response_with_error_message(
+            &(base_url() + "/auth/webauthn/register/finish"),
+            RequestType::Post(request),
+            "Could not register the security key",
+        )
+        .await
+    }
+
     pub async fn get_settings() -> Result<Options> {
         call_server_json_with_error_message::<Options, _>(
             &(base_url() + "/settings"),
@@ -147,6 +230,15 @@ impl HostService {
         .await
     }
 
+    pub async fn get_breached_password_range(prefix: String) -> Result<BreachedPasswordRange> {
+        call_server_json_with_error_message::<BreachedPasswordRange, _>(
+            &format!("{}/auth/breached_passwords/{}", base_url(), prefix),
+            GET_REQUEST,
+            "Could not check the password against the breached passwords: ",
+        )
+        .await
+    }
+
     pub async fn register_start(
         request: registration::ClientRegistrationStartRequest,
     ) -> Result<Box<registration::ServerRegistrationStartResponse>> {
@@ -169,7 +261,7 @@ impl HostService {
         .await
     }
 
-    pub async fn refresh() -> Result<(String, bool)> {
+    pub async fn refresh() -> Result<LoginInfo> {
         call_server_json_with_error_message::<login::ServerLoginResponse, _>(
             &(base_url() + "/auth/refresh"),
             GET_REQUEST,
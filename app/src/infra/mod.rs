@@ -6,5 +6,7 @@ pub mod form_utils;
 pub mod functional;
 pub mod graphql;
 pub mod modal;
+pub mod password_policy;
 pub mod schema;
 pub mod tooltip;
+pub mod webauthn;
@@ -0,0 +1,37 @@
+use anyhow::{Result, anyhow, bail};
+use lldap_auth::opaque;
+use lldap_validation::{
+    breached_passwords::{
+        BREACHED_PASSWORD_ERROR, BreachedPasswordRange, get_hash_prefix, get_password_hash,
+    },
+    password::{PasswordPolicy, validate_password},
+};
+
+/// Checks a new password against the policy of the server. With OPAQUE, the server never sees the
+/// password, so this is the only place where it can be checked.
+pub fn check_new_password(policy: &PasswordPolicy, user_id: &str, password: &str) -> Result<()> {
+    validate_password(policy, password, &[user_id])
+        .map_err(|errors| anyhow!("Invalid password: {}", errors.join(", ")))
+}
+
+/// The prefix of the password hash, to get the matching breached passwords from the server. The
+/// server never learns which password of the range is being checked.
+pub fn get_breached_password_prefix(password: &str) -> String {
+    get_hash_prefix(&get_password_hash(password))
+}
+
+/// Checks that the password is not in the range of breached passwords returned by the server.
+pub fn check_breached_password(range: &BreachedPasswordRange, password: &str) -> Result<()> {
+    if range.contains(&get_password_hash(password)) {
+        bail!(BREACHED_PASSWORD_ERROR);
+    }
+    Ok(())
+}
+
+/// The fingerprint to send along with a new password, if the server keeps a password history,
+/// i.e. if it sent a salt at the start of the registration.
+pub fn get_password_fingerprint(salt: Option<&[u8]>, password: &str) -> Result<Option<Vec<u8>>> {
+    Ok(salt
+        .map(|salt| opaque::password_fingerprint(salt, password.as_bytes()))
+        .transpose()?)
+}
@@ -13,6 +13,18 @@ pub(crate) enum AttributeType {
     DateTime,
     #[strum(serialize = "JPEG_PHOTO", serialize = "JPEGPHOTO")]
     JpegPhoto,
+    #[strum(serialize = "SSH_PUBLIC_KEY", serialize = "SSHPUBLICKEY")]
+    SshPublicKey,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub(crate) enum MatchingRule {
+    CaseIgnoreMatch,
+    CaseExactMatch,
+    IntegerMatch,
+    GeneralizedTimeMatch,
+    OctetStringMatch,
 }
 
 pub fn validate_attribute_type(attribute_type: &str) -> Result<(), ValidationError> {
@@ -38,5 +50,8 @@ mod tests {
 
         let attr_type: AttributeType = "JpegPhoto".try_into().unwrap();
         assert_eq!(attr_type, AttributeType::JpegPhoto);
+
+        let attr_type: AttributeType = "SSH_PUBLIC_KEY".try_into().unwrap();
+        assert_eq!(attr_type, AttributeType::SshPublicKey);
     }
 }
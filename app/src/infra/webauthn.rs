@@ -0,0 +1,69 @@
+use super::api::{HostService, LoginInfo};
+use anyhow::{Result, anyhow};
+use lldap_auth::webauthn::{
+    ClientLoginFinishRequest, ClientLoginStartRequest, ClientRegistrationFinishRequest,
+    CreationChallengeResponse, PublicKeyCredential, RegisterPublicKeyCredential,
+    RequestChallengeResponse,
+};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+fn credentials() -> Result<web_sys::CredentialsContainer> {
+    Ok(web_sys::window()
+        .ok_or_else(|| anyhow!("Could not access the browser window"))?
+        .navigator()
+        .credentials())
+}
+
+/// The browser rejects the promise when the user cancels or the key times out.
+fn to_error(e: JsValue) -> anyhow::Error {
+    anyhow!("The security key did not answer: {:?}", e)
+}
+
+async fn create_credential(
+    options: CreationChallengeResponse,
+) -> Result<RegisterPublicKeyCredential> {
+    let options = web_sys::CredentialCreationOptions::from(options);
+    let promise = credentials()?
+        .create_with_options(&options)
+        .map_err(to_error)?;
+    let credential = JsFuture::from(promise).await.map_err(to_error)?;
+    Ok(credential
+        .unchecked_into::<web_sys::PublicKeyCredential>()
+        .into())
+}
+
+async fn get_credential(options: RequestChallengeResponse) -> Result<PublicKeyCredential> {
+    let options = web_sys::CredentialRequestOptions::from(options);
+    let promise = credentials()?
+        .get_with_options(&options)
+        .map_err(to_error)?;
+    let credential = JsFuture::from(promise).await.map_err(to_error)?;
+    Ok(credential
+        .unchecked_into::<web_sys::PublicKeyCredential>()
+        .into())
+}
+
+/// Logs in with a security key: as a second factor with the token of the password step, or with
+/// any passkey of the user without it.
+pub async fn login(mfa_token: Option<String>) -> Result<LoginInfo> {
+    let start = HostService::webauthn_login_start(ClientLoginStartRequest { mfa_token }).await?;
+    let credential = get_credential(start.options).await?;
+    HostService::webauthn_login_finish(ClientLoginFinishRequest {
+        state_token: start.state_token,
+        credential,
+    })
+    .await
+}
+
+/// Registers a new security key for the logged in user.
+pub async fn register(name: String) -> Result<()> {
+    let start = HostService::webauthn_register_start().await?;
+    let credential = create_credential(start.options).await?;
+    HostService::webauthn_register_finish(ClientRegistrationFinishRequest {
+        state_token: start.state_token,
+        name,
+        credential,
+    })
+    .await
+}
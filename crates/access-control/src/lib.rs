@@ -1,21 +1,27 @@
 use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use futures_util::stream::BoxStream;
 use lldap_auth::access_control::{Permission, ValidationResults};
 use lldap_domain::{
     public_schema::PublicSchema,
     requests::{
-        CreateAttributeRequest, CreateGroupRequest, CreateUserRequest, UpdateGroupRequest,
+        CreateAppPasswordRequest, CreateAttributeRequest, CreateGroupRequest,
+        CreateSudoRoleRequest, CreateUserRequest, UpdateGroupRequest, UpdateSudoRoleRequest,
         UpdateUserRequest,
     },
     schema::{AttributeSchema, Schema},
     types::{
-        AttributeName, Group, GroupDetails, GroupId, GroupName, LdapObjectClass, User,
-        UserAndGroups, UserId,
+        AppPassword, AppPasswordId, AttributeName, Group, GroupDetails, GroupId, GroupName,
+        LdapObjectClass, SudoRole, SudoRoleId, User, UserAndGroups, UserId, WebauthnCredential,
+        WebauthnCredentialId,
     },
 };
 use lldap_domain_handlers::handler::{
-    BackendHandler, GroupBackendHandler, GroupListerBackendHandler, GroupRequestFilter,
-    ReadSchemaBackendHandler, SchemaBackendHandler, UserBackendHandler, UserListerBackendHandler,
-    UserRequestFilter,
+    AppPasswordBackendHandler, BackendHandler, GroupBackendHandler, GroupListerBackendHandler,
+    GroupMemberFilter, GroupRequestFilter, MfaBackendHandler, ReadSchemaBackendHandler,
+    SchemaBackendHandler, SudoRoleBackendHandler, SudoRoleListerBackendHandler, TotpEnrollment,
+    UserBackendHandler, UserListerBackendHandler, UserRequestFilter, UserSortOrder,
+    WebauthnBackendHandler,
 };
 use lldap_domain_model::error::Result;
 use std::collections::HashSet;
@@ -26,6 +32,8 @@ pub trait UserReadableBackendHandler: ReadSchemaBackendHandler {
     async fn get_user_details(&self, user_id: &UserId) -> Result<User>;
     async fn get_user_groups(&self, user_id: &UserId) -> Result<HashSet<GroupDetails>>;
     async fn get_schema(&self) -> Result<PublicSchema>;
+    async fn list_webauthn_credentials(&self, user_id: &UserId) -> Result<Vec<WebauthnCredential>>;
+    async fn list_app_passwords(&self, user_id: &UserId) -> Result<Vec<AppPassword>>;
 }
 
 #[async_trait]
@@ -37,11 +45,28 @@ pub trait ReadonlyBackendHandler: UserReadableBackendHandler {
     ) -> Result<Vec<UserAndGroups>>;
     async fn list_groups(&self, filters: Option<GroupRequestFilter>) -> Result<Vec<Group>>;
     async fn get_group_details(&self, group_id: GroupId) -> Result<GroupDetails>;
+    async fn list_sudo_roles(&self) -> Result<Vec<SudoRole>>;
+    async fn get_sudo_role(&self, id: SudoRoleId) -> Result<SudoRole>;
 }
 
 #[async_trait]
 pub trait UserWriteableBackendHandler: UserReadableBackendHandler {
     async fn update_user(&self, request: UpdateUserRequest) -> Result<()>;
+    async fn start_totp_enrollment(&self, user_id: &UserId) -> Result<TotpEnrollment>;
+    async fn confirm_totp_enrollment(&self, user_id: &UserId, code: &str) -> Result<Vec<String>>;
+    async fn rename_webauthn_credential(
+        &self,
+        user_id: &UserId,
+        id: WebauthnCredentialId,
+        name: &str,
+    ) -> Result<()>;
+    async fn delete_webauthn_credential(
+        &self,
+        user_id: &UserId,
+        id: WebauthnCredentialId,
+    ) -> Result<()>;
+    async fn create_app_password(&self, request: CreateAppPasswordRequest) -> Result<String>;
+    async fn delete_app_password(&self, user_id: &UserId, id: AppPasswordId) -> Result<()>;
 }
 
 #[async_trait]
@@ -55,9 +80,21 @@ pub trait AdminBackendHandler:
     async fn delete_user(&self, user_id: &UserId) -> Result<()>;
     async fn add_user_to_group(&self, user_id: &UserId, group_id: GroupId) -> Result<()>;
     async fn remove_user_from_group(&self, user_id: &UserId, group_id: GroupId) -> Result<()>;
+    async fn set_user_disabled(&self, user_id: &UserId, disabled: bool) -> Result<()>;
+    async fn set_user_expiration_date(
+        &self,
+        user_id: &UserId,
+        expiration_date: Option<NaiveDateTime>,
+    ) -> Result<()>;
+    async fn reset_mfa(&self, user_id: &UserId) -> Result<()>;
     async fn update_group(&self, request: UpdateGroupRequest) -> Result<()>;
     async fn create_group(&self, request: CreateGroupRequest) -> Result<GroupId>;
     async fn delete_group(&self, group_id: GroupId) -> Result<()>;
+    async fn set_group_member_filter(
+        &self,
+        group_id: GroupId,
+        member_filter: Option<GroupMemberFilter>,
+    ) -> Result<()>;
     async fn add_user_attribute(&self, request: CreateAttributeRequest) -> Result<()>;
     async fn add_group_attribute(&self, request: CreateAttributeRequest) -> Result<()>;
     async fn delete_user_attribute(&self, name: &AttributeName) -> Result<()>;
@@ -66,6 +103,9 @@ pub trait AdminBackendHandler:
     async fn add_group_object_class(&self, name: &LdapObjectClass) -> Result<()>;
     async fn delete_user_object_class(&self, name: &LdapObjectClass) -> Result<()>;
     async fn delete_group_object_class(&self, name: &LdapObjectClass) -> Result<()>;
+    async fn create_sudo_role(&self, request: CreateSudoRoleRequest) -> Result<SudoRoleId>;
+    async fn update_sudo_role(&self, request: UpdateSudoRoleRequest) -> Result<()>;
+    async fn delete_sudo_role(&self, id: SudoRoleId) -> Result<()>;
 }
 
 #[async_trait]
@@ -81,6 +121,12 @@ impl<Handler: BackendHandler> UserReadableBackendHandler for Handler {
             <Handler as ReadSchemaBackendHandler>::get_schema(self).await?,
         ))
     }
+    async fn list_webauthn_credentials(&self, user_id: &UserId) -> Result<Vec<WebauthnCredential>> {
+        <Handler as WebauthnBackendHandler>::list_webauthn_credentials(self, user_id).await
+    }
+    async fn list_app_passwords(&self, user_id: &UserId) -> Result<Vec<AppPassword>> {
+        <Handler as AppPasswordBackendHandler>::list_app_passwords(self, user_id).await
+    }
 }
 
 #[async_trait]
@@ -98,6 +144,12 @@ impl<Handler: BackendHandler> ReadonlyBackendHandler for Handler {
     async fn get_group_details(&self, group_id: GroupId) -> Result<GroupDetails> {
         <Handler as GroupBackendHandler>::get_group_details(self, group_id).await
     }
+    async fn list_sudo_roles(&self) -> Result<Vec<SudoRole>> {
+        <Handler as SudoRoleListerBackendHandler>::list_sudo_roles(self).await
+    }
+    async fn get_sudo_role(&self, id: SudoRoleId) -> Result<SudoRole> {
+        <Handler as SudoRoleBackendHandler>::get_sudo_role(self, id).await
+    }
 }
 
 #[async_trait]
@@ -105,6 +157,34 @@ impl<Handler: BackendHandler> UserWriteableBackendHandler for Handler {
     async fn update_user(&self, request: UpdateUserRequest) -> Result<()> {
         <Handler as UserBackendHandler>::update_user(self, request).await
     }
+    async fn start_totp_enrollment(&self, user_id: &UserId) -> Result<TotpEnrollment> {
+        <Handler as MfaBackendHandler>::start_totp_enrollment(self, user_id).await
+    }
+    async fn confirm_totp_enrollment(&self, user_id: &UserId, code: &str) -> Result<Vec<String>> {
+        <Handler as MfaBackendHandler>::confirm_totp_enrollment(self, user_id, code).await
+    }
+    async fn rename_webauthn_credential(
+        &self,
+        user_id: &UserId,
+        id: WebauthnCredentialId,
+        name: &str,
+    ) -> Result<()> {
+        <Handler as WebauthnBackendHandler>::rename_webauthn_credential(self, user_id, id, name)
+            .await
+    }
+    async fn delete_webauthn_credential(
+        &self,
+        user_id: &UserId,
+        id: WebauthnCredentialId,
+    ) -> Result<()> {
+        <Handler as WebauthnBackendHandler>::delete_webauthn_credential(self, user_id, id).await
+    }
+    async fn create_app_password(&self, request: CreateAppPasswordRequest) -> Result<String> {
+        <Handler as AppPasswordBackendHandler>::create_app_password(self, request).await
+    }
+    async fn delete_app_password(&self, user_id: &UserId, id: AppPasswordId) -> Result<()> {
+        <Handler as AppPasswordBackendHandler>::delete_app_password(self, user_id, id).await
+    }
 }
 #[async_trait]
 impl<Handler: BackendHandler> AdminBackendHandler for Handler {
@@ -120,6 +200,20 @@ impl<Handler: BackendHandler> AdminBackendHandler for Handler {
     async fn remove_user_from_group(&self, user_id: &UserId, group_id: GroupId) -> Result<()> {
         <Handler as UserBackendHandler>::remove_user_from_group(self, user_id, group_id).await
     }
+    async fn set_user_disabled(&self, user_id: &UserId, disabled: bool) -> Result<()> {
+        <Handler as UserBackendHandler>::set_user_disabled(self, user_id, disabled).await
+    }
+    async fn set_user_expiration_date(
+        &self,
+        user_id: &UserId,
+        expiration_date: Option<NaiveDateTime>,
+    ) -> Result<()> {
+        <Handler as UserBackendHandler>::set_user_expiration_date(self, user_id, expiration_date)
+            .await
+    }
+    async fn reset_mfa(&self, user_id: &UserId) -> Result<()> {
+        <Handler as MfaBackendHandler>::reset_mfa(self, user_id).await
+    }
     async fn update_group(&self, request: UpdateGroupRequest) -> Result<()> {
         <Handler as GroupBackendHandler>::update_group(self, request).await
     }
@@ -129,6 +223,14 @@ impl<Handler: BackendHandler> AdminBackendHandler for Handler {
     async fn delete_group(&self, group_id: GroupId) -> Result<()> {
         <Handler as GroupBackendHandler>::delete_group(self, group_id).await
     }
+    async fn set_group_member_filter(
+        &self,
+        group_id: GroupId,
+        member_filter: Option<GroupMemberFilter>,
+    ) -> Result<()> {
+        <Handler as GroupBackendHandler>::set_group_member_filter(self, group_id, member_filter)
+            .await
+    }
     async fn add_user_attribute(&self, request: CreateAttributeRequest) -> Result<()> {
         <Handler as SchemaBackendHandler>::add_user_attribute(self, request).await
     }
@@ -153,6 +255,15 @@ impl<Handler: BackendHandler> AdminBackendHandler for Handler {
     async fn delete_group_object_class(&self, name: &LdapObjectClass) -> Result<()> {
         <Handler as SchemaBackendHandler>::delete_group_object_class(self, name).await
     }
+    async fn create_sudo_role(&self, request: CreateSudoRoleRequest) -> Result<SudoRoleId> {
+        <Handler as SudoRoleBackendHandler>::create_sudo_role(self, request).await
+    }
+    async fn update_sudo_role(&self, request: UpdateSudoRoleRequest) -> Result<()> {
+        <Handler as SudoRoleBackendHandler>::update_sudo_role(self, request).await
+    }
+    async fn delete_sudo_role(&self, id: SudoRoleId) -> Result<()> {
+        <Handler as SudoRoleBackendHandler>::delete_sudo_role(self, id).await
+    }
 }
 
 pub struct AccessControlledBackendHandler<Handler> {
@@ -284,58 +395,125 @@ impl<Handler: ReadSchemaBackendHandler + Sync> ReadSchemaBackendHandler
     }
 }
 
-#[async_trait]
-impl<Handler: UserListerBackendHandler + Sync> UserListerBackendHandler
-    for UserRestrictedListerBackendHandler<'_, Handler>
-{
-    async fn list_users(
+impl<Handler> UserRestrictedListerBackendHandler<'_, Handler> {
+    fn restrict_user_filters(
         &self,
         filters: Option<UserRequestFilter>,
-        get_groups: bool,
-    ) -> Result<Vec<UserAndGroups>> {
+    ) -> Option<UserRequestFilter> {
         let user_filter = self
             .user_filter
             .as_ref()
             .map(|u| UserRequestFilter::UserId(u.clone()));
-        let filters = match (filters, user_filter) {
+        match (filters, user_filter) {
             (None, None) => None,
             (None, u) => u,
             (f, None) => f,
             (Some(f), Some(u)) => Some(UserRequestFilter::And(vec![f, u])),
-        };
-        self.handler.list_users(filters, get_groups).await
+        }
     }
-}
 
-#[async_trait]
-impl<Handler: GroupListerBackendHandler + Sync> GroupListerBackendHandler
-    for UserRestrictedListerBackendHandler<'_, Handler>
-{
-    async fn list_groups(&self, filters: Option<GroupRequestFilter>) -> Result<Vec<Group>> {
+    fn restrict_group_filters(
+        &self,
+        filters: Option<GroupRequestFilter>,
+    ) -> Option<GroupRequestFilter> {
         let group_filter = self
             .user_filter
             .as_ref()
             .map(|u| GroupRequestFilter::Member(u.clone()));
-        let filters = match (filters, group_filter) {
+        match (filters, group_filter) {
             (None, None) => None,
             (None, u) => u,
             (f, None) => f,
             (Some(f), Some(u)) => Some(GroupRequestFilter::And(vec![f, u])),
-        };
-        self.handler.list_groups(filters).await
+        }
+    }
+}
+
+#[async_trait]
+impl<Handler: UserListerBackendHandler + Sync> UserListerBackendHandler
+    for UserRestrictedListerBackendHandler<'_, Handler>
+{
+    async fn list_users(
+        &self,
+        filters: Option<UserRequestFilter>,
+        get_groups: bool,
+    ) -> Result<Vec<UserAndGroups>> {
+        self.handler
+            .list_users(self.restrict_user_filters(filters), get_groups)
+            .await
+    }
+    async fn count_users(&self, filters: Option<UserRequestFilter>) -> Result<u64> {
+        self.handler
+            .count_users(self.restrict_user_filters(filters))
+            .await
+    }
+    fn stream_users(
+        &self,
+        filters: Option<UserRequestFilter>,
+        get_groups: bool,
+    ) -> BoxStream<'_, Result<UserAndGroups>> {
+        self.handler
+            .stream_users(self.restrict_user_filters(filters), get_groups)
+    }
+    async fn list_users_sorted(
+        &self,
+        filters: Option<UserRequestFilter>,
+        get_groups: bool,
+        order: UserSortOrder,
+        offset: u64,
+        limit: Option<u64>,
+    ) -> Result<Vec<UserAndGroups>> {
+        self.handler
+            .list_users_sorted(
+                self.restrict_user_filters(filters),
+                get_groups,
+                order,
+                offset,
+                limit,
+            )
+            .await
+    }
+}
+
+#[async_trait]
+impl<Handler: GroupListerBackendHandler + Sync> GroupListerBackendHandler
+    for UserRestrictedListerBackendHandler<'_, Handler>
+{
+    async fn list_groups(&self, filters: Option<GroupRequestFilter>) -> Result<Vec<Group>> {
+        self.handler
+            .list_groups(self.restrict_group_filters(filters))
+            .await
+    }
+    fn stream_groups(&self, filters: Option<GroupRequestFilter>) -> BoxStream<'_, Result<Group>> {
+        self.handler
+            .stream_groups(self.restrict_group_filters(filters))
+    }
+}
+
+/// The sudo rules are only visible to the users who can read everything.
+#[async_trait]
+impl<Handler: SudoRoleListerBackendHandler + Sync> SudoRoleListerBackendHandler
+    for UserRestrictedListerBackendHandler<'_, Handler>
+{
+    async fn list_sudo_roles(&self) -> Result<Vec<SudoRole>> {
+        if self.user_filter.is_some() {
+            return Ok(Vec::new());
+        }
+        self.handler.list_sudo_roles().await
     }
 }
 
 #[async_trait]
 pub trait UserAndGroupListerBackendHandler:
-    UserListerBackendHandler + GroupListerBackendHandler
+    UserListerBackendHandler + GroupListerBackendHandler + SudoRoleListerBackendHandler
 {
     fn user_filter(&self) -> &Option<UserId>;
 }
 
 #[async_trait]
-impl<Handler: GroupListerBackendHandler + UserListerBackendHandler + Sync>
-    UserAndGroupListerBackendHandler for UserRestrictedListerBackendHandler<'_, Handler>
+impl<
+    Handler: GroupListerBackendHandler + UserListerBackendHandler + SudoRoleListerBackendHandler + Sync,
+> UserAndGroupListerBackendHandler for UserRestrictedListerBackendHandler<'_, Handler>
 {
     fn user_filter(&self) -> &Option<UserId> {
         &self.user_filter
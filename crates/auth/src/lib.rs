@@ -58,6 +58,53 @@ pub mod login {
         pub token: String,
         #[serde(rename = "refreshToken", skip_serializing_if = "Option::is_none")]
         pub refresh_token: Option<String>,
+        /// The password was reset by an administrator or is too old: the user should change it
+        /// before doing anything else.
+        #[serde(rename = "mustChangePassword", default)]
+        pub must_change_password: bool,
+        /// The user is a member of a group requiring a second factor, but hasn't enrolled one:
+        /// the token doesn't grant the group permissions until they do.
+        #[serde(rename = "mustEnrollMfa", default)]
+        pub must_enroll_mfa: bool,
+    }
+
+    /// Sent instead of the [`ServerLoginResponse`] when the user has enabled a second factor.
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct ServerMfaRequiredResponse {
+        /// To be sent back with the code, see [`ClientMfaVerifyRequest`].
+        #[serde(rename = "mfaToken")]
+        pub mfa_token: String,
+        /// The user can answer with a TOTP or recovery code.
+        #[serde(default)]
+        pub totp: bool,
+        /// The user can answer with one of their security keys, see [`crate::webauthn`].
+        #[serde(default)]
+        pub webauthn: bool,
+    }
+
+    /// What the server answers to a successful password check.
+    #[derive(Serialize, Deserialize, Clone)]
+    #[serde(untagged)]
+    pub enum ServerLoginResult {
+        Success(ServerLoginResponse),
+        MfaRequired(ServerMfaRequiredResponse),
+    }
+
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct ClientMfaVerifyRequest {
+        #[serde(rename = "mfaToken")]
+        pub mfa_token: String,
+        /// A TOTP code or a recovery code.
+        pub code: String,
+    }
+
+    impl fmt::Debug for ClientMfaVerifyRequest {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("ClientMfaVerifyRequest")
+                .field("mfa_token", &"***********")
+                .field("code", &"***********")
+                .finish()
+        }
     }
 }
 
@@ -82,6 +129,10 @@ pub mod registration {
         /// Base64, encrypted ServerData to be passed back to the server.
         pub server_data: String,
         pub registration_response: opaque::client::registration::RegistrationResponse,
+        /// Salt for [`opaque::password_fingerprint`], only sent when the password history is
+        /// enabled. The client must then send the fingerprint of the new password.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub password_history_salt: Option<Vec<u8>>,
     }
 
     #[derive(Serialize, Deserialize, Clone)]
@@ -89,6 +140,62 @@ pub mod registration {
         /// Encrypted ServerData from the previous step.
         pub server_data: String,
         pub registration_upload: opaque::server::registration::RegistrationUpload,
+        /// Fingerprint of the new password, see [`opaque::password_fingerprint`]. Required when
+        /// the server sent a `password_history_salt`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub password_fingerprint: Option<Vec<u8>>,
+    }
+}
+
+/// The messages for the WebAuthn ceremonies, used to register passkeys and security keys and to
+/// log in with them.
+pub mod webauthn {
+    use super::*;
+    pub use webauthn_rs_proto::{
+        CreationChallengeResponse, PublicKeyCredential, RegisterPublicKeyCredential,
+        RequestChallengeResponse,
+    };
+
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct ServerRegistrationStartResponse {
+        /// To be passed back to the server with the new credential.
+        #[serde(rename = "stateToken")]
+        pub state_token: String,
+        /// To be given to `navigator.credentials.create()`.
+        pub options: CreationChallengeResponse,
+    }
+
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct ClientRegistrationFinishRequest {
+        #[serde(rename = "stateToken")]
+        pub state_token: String,
+        /// A name for the user to recognize the credential, e.g. "YubiKey".
+        pub name: String,
+        pub credential: RegisterPublicKeyCredential,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, Default)]
+    pub struct ClientLoginStartRequest {
+        /// The token of a login waiting for a second factor, see
+        /// [`crate::login::ServerMfaRequiredResponse`]. Without it, the login is passwordless
+        /// and any passkey is accepted.
+        #[serde(rename = "mfaToken", default, skip_serializing_if = "Option::is_none")]
+        pub mfa_token: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct ServerLoginStartResponse {
+        #[serde(rename = "stateToken")]
+        pub state_token: String,
+        /// To be given to `navigator.credentials.get()`.
+        pub options: RequestChallengeResponse,
+    }
+
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct ClientLoginFinishRequest {
+        #[serde(rename = "stateToken")]
+        pub state_token: String,
+        pub credential: PublicKeyCredential,
     }
 }
 
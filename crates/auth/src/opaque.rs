@@ -6,6 +6,8 @@ use rand::{CryptoRng, RngCore};
 pub enum AuthenticationError {
     #[error("Protocol error: `{0}`")]
     ProtocolError(#[from] opaque_ke::errors::ProtocolError),
+    #[error("Hashing error: `{0}`")]
+    HashingError(#[from] argon2::Error),
 }
 
 pub type AuthenticationResult<T> = std::result::Result<T, AuthenticationError>;
@@ -47,6 +49,26 @@ impl<D: opaque_ke::hash::Hash> opaque_ke::slow_hash::SlowHash<D> for ArgonHasher
     }
 }
 
+/// Computes a fingerprint of a password, to detect reused passwords.
+///
+/// With OPAQUE, the server never sees the password, so the client computes the fingerprint and
+/// sends it along with the registration. The server only stores keyed hashes of it. The salt is
+/// sent by the server at the start of the registration: it is derived from the user ID and the
+/// server key, so the fingerprint can't be precomputed or bruteforced without the server key.
+pub fn password_fingerprint(salt: &[u8], password: &[u8]) -> AuthenticationResult<Vec<u8>> {
+    const CONFIG: &argon2::Config<'static> = &argon2::Config {
+        ad: &[],
+        hash_length: 32,
+        lanes: 1,
+        mem_cost: 19 * 1024, // 19 MB, in KB
+        secret: &[],
+        time_cost: 2,
+        variant: argon2::Variant::Argon2id,
+        version: argon2::Version::Version13,
+    };
+    Ok(argon2::hash_raw(password, salt, CONFIG)?)
+}
+
 /// The ciphersuite trait allows to specify the underlying primitives
 /// that will be used in the OPAQUE protocol
 #[allow(dead_code)]
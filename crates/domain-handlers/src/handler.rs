@@ -1,24 +1,68 @@
 use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use futures_util::{
+    StreamExt, TryFutureExt,
+    stream::{self, BoxStream},
+};
 use ldap3_proto::proto::LdapSubstringFilter;
 use lldap_domain::{
     requests::{
-        CreateAttributeRequest, CreateGroupRequest, CreateUserRequest, UpdateGroupRequest,
-        UpdateUserRequest,
+        AddWebauthnCredentialRequest, CreateAppPasswordRequest, CreateAttributeRequest,
+        CreateGroupRequest, CreateSudoRoleRequest, CreateUserRequest, UpdateGroupRequest,
+        UpdateSudoRoleRequest, UpdateUserRequest,
     },
     schema::Schema,
     types::{
-        AttributeName, AttributeValue, Group, GroupDetails, GroupId, GroupName, LdapObjectClass,
-        User, UserAndGroups, UserId, Uuid,
+        AppPassword, AppPasswordId, AttributeName, AttributeValue, Group, GroupDetails, GroupId,
+        GroupName, LdapObjectClass, SudoRole, SudoRoleId, User, UserAndGroups, UserId, Uuid,
+        WebauthnCredential, WebauthnCredentialId,
     },
 };
 use lldap_domain_model::{error::Result, model::UserColumn};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::{collections::HashSet, net::IpAddr};
+
+/// Where a bind comes from: the application passwords are only accepted from LDAP clients and
+/// from the non-interactive HTTP clients.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub enum BindSource {
+    /// The web UI or the HTTP API.
+    #[default]
+    Http,
+    /// An LDAP client, with its address when known.
+    Ldap(Option<IpAddr>),
+    /// An HTTP client that can't do a second factor, e.g. sshd fetching the authorized keys.
+    /// Only the application passwords are accepted, except for the service accounts which have
+    /// no second factor and use their own password, from their allowed networks.
+    HttpAppPassword(Option<IpAddr>),
+}
 
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone)]
 pub struct BindRequest {
     pub name: UserId,
     pub password: String,
+    #[serde(default)]
+    pub source: BindSource,
+}
+
+/// Whether the password of a user can still be used, or has to be changed first.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub enum PasswordState {
+    #[default]
+    Valid,
+    /// The password is older than the configured maximum age.
+    Expired,
+    /// The password was set by an administrator, and the user has to choose a new one.
+    MustChange,
+}
+
+/// The outcome of a successful bind.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct BindResult {
+    pub password_state: PasswordState,
+    /// The credentials were an app password rather than the user's password. App passwords are
+    /// meant for the clients that can't do a second factor, so they don't need one.
+    pub app_password: bool,
 }
 
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone)]
@@ -79,12 +123,25 @@ pub enum UserRequestFilter {
     UserIdSubString(SubStringFilter),
     Equality(UserColumn, String),
     AttributeEquality(AttributeName, AttributeValue),
+    // Case-insensitive match on one of the values of a custom string attribute.
+    AttributeEqualityIgnoreCase(AttributeName, String),
     SubString(UserColumn, SubStringFilter),
+    // Case-insensitive substring match on one of the values of a custom string attribute.
+    AttributeSubString(AttributeName, SubStringFilter),
+    // Case-insensitive ordering comparisons, consistent with `UserSortOrder`.
+    GreaterOrEqual(UserColumn, String),
+    LessOrEqual(UserColumn, String),
     // Check if a user belongs to a group identified by name.
     MemberOf(GroupName),
     // Same, by id.
     MemberOfId(GroupId),
     CustomAttributePresent(AttributeName),
+    // Check if the user account is disabled.
+    Disabled(bool),
+    // Check if the user account has an expiration date.
+    Expires(bool),
+    // Check if the user is a service account rather than a person.
+    ServiceAccount(bool),
 }
 
 impl From<bool> for UserRequestFilter {
@@ -93,6 +150,21 @@ impl From<bool> for UserRequestFilter {
     }
 }
 
+/// The membership rule of a dynamic group: the users matching the filter are its members.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone)]
+pub struct GroupMemberFilter {
+    /// The LDAP filter, as entered by the user.
+    pub ldap_filter: String,
+    pub filter: UserRequestFilter,
+}
+
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct UserSortOrder {
+    /// One of `UserId`, `Email` or `DisplayName`. Ties are broken by user id.
+    pub column: UserColumn,
+    pub descending: bool,
+}
+
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone)]
 pub enum GroupRequestFilter {
     True,
@@ -107,6 +179,10 @@ pub enum GroupRequestFilter {
     // Check if the group contains a user identified by uid.
     Member(UserId),
     AttributeEquality(AttributeName, AttributeValue),
+    // Case-insensitive match on one of the values of a custom string attribute.
+    AttributeEqualityIgnoreCase(AttributeName, String),
+    // Case-insensitive substring match on one of the values of a custom string attribute.
+    AttributeSubString(AttributeName, SubStringFilter),
     CustomAttributePresent(AttributeName),
 }
 
@@ -118,12 +194,27 @@ impl From<bool> for GroupRequestFilter {
 
 #[async_trait]
 pub trait LoginHandler: Send + Sync {
-    async fn bind(&self, request: BindRequest) -> Result<()>;
+    /// Checks the credentials, and returns whether the password has to be changed.
+    async fn bind(&self, request: BindRequest) -> Result<BindResult>;
+    async fn get_password_state(&self, user_id: &UserId) -> Result<PasswordState>;
+    /// Forces the user to change their password at the next login.
+    async fn require_password_change(&self, user_id: &UserId) -> Result<()>;
+    /// Checks a new password against the password policy. Only possible when the server sees the
+    /// password in clear, e.g. over LDAP.
+    async fn check_password_policy(&self, user_id: &UserId, password: &str) -> Result<()>;
 }
 
 #[async_trait]
 pub trait GroupListerBackendHandler: ReadSchemaBackendHandler {
     async fn list_groups(&self, filters: Option<GroupRequestFilter>) -> Result<Vec<Group>>;
+    /// Same as `list_groups`, but the groups can be fetched in batches to keep the memory usage
+    /// bounded. The default implementation fetches them all at once.
+    fn stream_groups(&self, filters: Option<GroupRequestFilter>) -> BoxStream<'_, Result<Group>> {
+        self.list_groups(filters)
+            .map_ok(|groups| stream::iter(groups.into_iter().map(Ok)))
+            .try_flatten_stream()
+            .boxed()
+    }
 }
 
 #[async_trait]
@@ -132,6 +223,12 @@ pub trait GroupBackendHandler: ReadSchemaBackendHandler {
     async fn update_group(&self, request: UpdateGroupRequest) -> Result<()>;
     async fn create_group(&self, request: CreateGroupRequest) -> Result<GroupId>;
     async fn delete_group(&self, group_id: GroupId) -> Result<()>;
+    /// Turns the group into a dynamic group, or back into a regular group with `None`.
+    async fn set_group_member_filter(
+        &self,
+        group_id: GroupId,
+        member_filter: Option<GroupMemberFilter>,
+    ) -> Result<()>;
 }
 
 #[async_trait]
@@ -141,6 +238,29 @@ pub trait UserListerBackendHandler: ReadSchemaBackendHandler {
         filters: Option<UserRequestFilter>,
         get_groups: bool,
     ) -> Result<Vec<UserAndGroups>>;
+    async fn count_users(&self, filters: Option<UserRequestFilter>) -> Result<u64>;
+    /// Same as `list_users`, but the users can be fetched in batches to keep the memory usage
+    /// bounded. The default implementation fetches them all at once.
+    fn stream_users(
+        &self,
+        filters: Option<UserRequestFilter>,
+        get_groups: bool,
+    ) -> BoxStream<'_, Result<UserAndGroups>> {
+        self.list_users(filters, get_groups)
+            .map_ok(|users| stream::iter(users.into_iter().map(Ok)))
+            .try_flatten_stream()
+            .boxed()
+    }
+    /// Lists a window of the matching users, in the given order. Without a limit, all the users
+    /// after the offset are returned.
+    async fn list_users_sorted(
+        &self,
+        filters: Option<UserRequestFilter>,
+        get_groups: bool,
+        order: UserSortOrder,
+        offset: u64,
+        limit: Option<u64>,
+    ) -> Result<Vec<UserAndGroups>>;
 }
 
 #[async_trait]
@@ -152,6 +272,29 @@ pub trait UserBackendHandler: ReadSchemaBackendHandler {
     async fn add_user_to_group(&self, user_id: &UserId, group_id: GroupId) -> Result<()>;
     async fn remove_user_from_group(&self, user_id: &UserId, group_id: GroupId) -> Result<()>;
     async fn get_user_groups(&self, user_id: &UserId) -> Result<HashSet<GroupDetails>>;
+    /// Disabling a user also revokes their sessions: refresh tokens, JWTs and password reset
+    /// tokens.
+    async fn set_user_disabled(&self, user_id: &UserId, disabled: bool) -> Result<()>;
+    /// Past the expiration date, the user cannot log in anymore. `None` removes the expiration.
+    async fn set_user_expiration_date(
+        &self,
+        user_id: &UserId,
+        expiration_date: Option<NaiveDateTime>,
+    ) -> Result<()>;
+}
+
+#[async_trait]
+pub trait SudoRoleListerBackendHandler {
+    /// Lists all the sudo roles, sorted by name.
+    async fn list_sudo_roles(&self) -> Result<Vec<SudoRole>>;
+}
+
+#[async_trait]
+pub trait SudoRoleBackendHandler: SudoRoleListerBackendHandler {
+    async fn get_sudo_role(&self, id: SudoRoleId) -> Result<SudoRole>;
+    async fn create_sudo_role(&self, request: CreateSudoRoleRequest) -> Result<SudoRoleId>;
+    async fn update_sudo_role(&self, request: UpdateSudoRoleRequest) -> Result<()>;
+    async fn delete_sudo_role(&self, id: SudoRoleId) -> Result<()>;
 }
 
 #[async_trait]
@@ -173,6 +316,62 @@ pub trait SchemaBackendHandler: ReadSchemaBackendHandler {
     async fn delete_group_object_class(&self, name: &LdapObjectClass) -> Result<()>;
 }
 
+/// What the user needs to add the TOTP secret to their authenticator app.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone)]
+pub struct TotpEnrollment {
+    /// The secret in base32, for the apps that cannot scan the QR code.
+    pub secret: String,
+    /// The `otpauth://` URI to encode in the QR code.
+    pub uri: String,
+}
+
+#[async_trait]
+pub trait MfaBackendHandler: Send + Sync {
+    /// Generates a new TOTP secret for the user. It is only enabled once confirmed.
+    async fn start_totp_enrollment(&self, user_id: &UserId) -> Result<TotpEnrollment>;
+    /// Enables TOTP if the code matches the new secret, and returns new recovery codes.
+    async fn confirm_totp_enrollment(&self, user_id: &UserId, code: &str) -> Result<Vec<String>>;
+    /// Checks a TOTP code, or a recovery code. Both can only be used once, and the second factor
+    /// is locked for a while after too many wrong codes.
+    async fn verify_mfa_code(&self, user_id: &UserId, code: &str) -> Result<()>;
+    /// Removes the second factor and the recovery codes of the user.
+    async fn reset_mfa(&self, user_id: &UserId) -> Result<()>;
+}
+
+#[async_trait]
+pub trait WebauthnBackendHandler: Send + Sync {
+    async fn list_webauthn_credentials(&self, user_id: &UserId) -> Result<Vec<WebauthnCredential>>;
+    /// Finds a credential from the ID sent by the authenticator, for the passwordless logins.
+    async fn get_webauthn_credential(&self, credential_id: &[u8]) -> Result<WebauthnCredential>;
+    async fn add_webauthn_credential(&self, request: AddWebauthnCredentialRequest) -> Result<()>;
+    /// Records a login with the credential, and stores the updated passkey if its signature
+    /// counter changed. Fails if the user is disabled or expired.
+    async fn use_webauthn_credential(
+        &self,
+        credential_id: &[u8],
+        updated_passkey: Option<String>,
+    ) -> Result<()>;
+    async fn rename_webauthn_credential(
+        &self,
+        user_id: &UserId,
+        id: WebauthnCredentialId,
+        name: &str,
+    ) -> Result<()>;
+    async fn delete_webauthn_credential(
+        &self,
+        user_id: &UserId,
+        id: WebauthnCredentialId,
+    ) -> Result<()>;
+}
+
+#[async_trait]
+pub trait AppPasswordBackendHandler: Send + Sync {
+    async fn list_app_passwords(&self, user_id: &UserId) -> Result<Vec<AppPassword>>;
+    /// Returns the generated password: it cannot be retrieved later.
+    async fn create_app_password(&self, request: CreateAppPasswordRequest) -> Result<String>;
+    async fn delete_app_password(&self, user_id: &UserId, id: AppPasswordId) -> Result<()>;
+}
+
 #[async_trait]
 pub trait BackendHandler:
     Send
@@ -183,6 +382,10 @@ pub trait BackendHandler:
     + GroupListerBackendHandler
     + ReadSchemaBackendHandler
     + SchemaBackendHandler
+    + SudoRoleBackendHandler
+    + MfaBackendHandler
+    + WebauthnBackendHandler
+    + AppPasswordBackendHandler
 {
 }
 
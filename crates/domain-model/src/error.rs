@@ -21,6 +21,8 @@ pub enum DomainError {
     EntityNotFound(String),
     #[error("Internal error: `{0}`")]
     InternalError(String),
+    #[error("The password doesn't follow the password policy: {0}")]
+    PasswordPolicyError(String),
 }
 
 impl From<sea_orm::TransactionError<DomainError>> for DomainError {
@@ -0,0 +1,71 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use lldap_domain::types::{AppPasswordId, GroupId, UserId};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "app_passwords")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub app_password_id: AppPasswordId,
+    pub user_id: UserId,
+    pub name: String,
+    pub password_hash: Vec<u8>,
+    /// Separated by spaces.
+    pub allowed_sources: String,
+    pub required_group_id: Option<GroupId>,
+    pub creation_date: chrono::NaiveDateTime,
+    pub last_used_date: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::UserId",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Users,
+    #[sea_orm(
+        belongs_to = "super::groups::Entity",
+        from = "Column::RequiredGroupId",
+        to = "super::groups::Column::GroupId",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Groups,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+impl Related<super::groups::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Groups.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl From<Model> for lldap_domain::types::AppPassword {
+    fn from(app_password: Model) -> Self {
+        Self {
+            id: app_password.app_password_id,
+            user_id: app_password.user_id,
+            name: app_password.name,
+            allowed_sources: app_password
+                .allowed_sources
+                .split_whitespace()
+                .map(str::to_owned)
+                .collect(),
+            required_group: app_password.required_group_id,
+            creation_date: app_password.creation_date,
+            last_used_date: app_password.last_used_date,
+        }
+    }
+}
@@ -36,6 +36,12 @@ pub fn deserialize_attribute_value(
         (AttributeType::JpegPhoto, true) => {
             AttributeValue::JpegPhoto(Cardinality::Unbounded(value.unwrap()))
         }
+        (AttributeType::SshPublicKey, false) => {
+            AttributeValue::SshPublicKey(Cardinality::Singleton(value.unwrap()))
+        }
+        (AttributeType::SshPublicKey, true) => {
+            AttributeValue::SshPublicKey(Cardinality::Unbounded(value.unwrap()))
+        }
     }
 }
 
@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 
 use lldap_domain::{
     schema::AttributeSchema,
-    types::{AttributeName, AttributeType},
+    types::{AttributeName, AttributeType, MatchingRule},
 };
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
@@ -25,6 +25,8 @@ pub struct Model {
     pub is_group_editable: bool,
     #[sea_orm(column_name = "group_attribute_schema_is_hardcoded")]
     pub is_hardcoded: bool,
+    #[sea_orm(column_name = "group_attribute_schema_matching_rule")]
+    pub matching_rule: MatchingRule,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -51,6 +53,7 @@ impl From<Model> for AttributeSchema {
             is_editable: value.is_group_editable,
             is_hardcoded: value.is_hardcoded,
             is_readonly: false,
+            matching_rule: value.matching_rule,
         }
     }
 }
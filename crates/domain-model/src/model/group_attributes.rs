@@ -20,6 +20,10 @@ pub struct Model {
     pub attribute_name: AttributeName,
     #[sea_orm(column_name = "group_attribute_value")]
     pub value: Serialized,
+    /// For string attributes, the lowercase values separated (and surrounded) by newlines, to
+    /// support substring filters.
+    #[sea_orm(column_name = "group_attribute_lowercase_value")]
+    pub lowercase_value: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -14,6 +14,10 @@ pub struct Model {
     pub lowercase_display_name: String,
     pub creation_date: chrono::NaiveDateTime,
     pub uuid: Uuid,
+    /// The LDAP filter defining the members of a dynamic group, as entered by the user.
+    pub member_filter: Option<String>,
+    /// The serialized `UserRequestFilter` matching the members of a dynamic group.
+    pub member_filter_query: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -39,6 +43,7 @@ impl From<Model> for lldap_domain::types::Group {
             uuid: group.uuid,
             users: vec![],
             attributes: Vec::new(),
+            member_filter: group.member_filter,
         }
     }
 }
@@ -51,6 +56,7 @@ impl From<Model> for lldap_domain::types::GroupDetails {
             creation_date: group.creation_date,
             uuid: group.uuid,
             attributes: Vec::new(),
+            member_filter: group.member_filter,
         }
     }
 }
@@ -1,12 +1,19 @@
 pub mod prelude;
 
+pub mod app_passwords;
 pub mod deserialize;
 pub mod groups;
 pub mod jwt_refresh_storage;
 pub mod jwt_storage;
 pub mod memberships;
+pub mod mfa_recovery_codes;
+pub mod password_history;
 pub mod password_reset_tokens;
 pub mod users;
+pub mod webauthn_credentials;
+
+pub mod sudo_role_values;
+pub mod sudo_roles;
 
 pub mod user_attribute_schema;
 pub mod user_attributes;
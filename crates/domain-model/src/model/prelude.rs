@@ -1,5 +1,7 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.3
 
+pub use super::app_passwords::Column as AppPasswordsColumn;
+pub use super::app_passwords::Entity as AppPasswords;
 pub use super::group_attribute_schema::Column as GroupAttributeSchemaColumn;
 pub use super::group_attribute_schema::Entity as GroupAttributeSchema;
 pub use super::group_attributes::Column as GroupAttributesColumn;
@@ -14,8 +16,16 @@ pub use super::jwt_storage::Column as JwtStorageColumn;
 pub use super::jwt_storage::Entity as JwtStorage;
 pub use super::memberships::Column as MembershipColumn;
 pub use super::memberships::Entity as Membership;
+pub use super::mfa_recovery_codes::Column as MfaRecoveryCodesColumn;
+pub use super::mfa_recovery_codes::Entity as MfaRecoveryCodes;
+pub use super::password_history::Column as PasswordHistoryColumn;
+pub use super::password_history::Entity as PasswordHistory;
 pub use super::password_reset_tokens::Column as PasswordResetTokensColumn;
 pub use super::password_reset_tokens::Entity as PasswordResetTokens;
+pub use super::sudo_role_values::Column as SudoRoleValuesColumn;
+pub use super::sudo_role_values::Entity as SudoRoleValues;
+pub use super::sudo_roles::Column as SudoRoleColumn;
+pub use super::sudo_roles::Entity as SudoRole;
 pub use super::user_attribute_schema::Column as UserAttributeSchemaColumn;
 pub use super::user_attribute_schema::Entity as UserAttributeSchema;
 pub use super::user_attributes::Column as UserAttributesColumn;
@@ -24,3 +34,5 @@ pub use super::user_object_classes::Column as UserObjectClassesColumn;
 pub use super::user_object_classes::Entity as UserObjectClasses;
 pub use super::users::Column as UserColumn;
 pub use super::users::Entity as User;
+pub use super::webauthn_credentials::Column as WebauthnCredentialsColumn;
+pub use super::webauthn_credentials::Entity as WebauthnCredentials;
@@ -0,0 +1,38 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use lldap_domain::types::SudoRoleId;
+
+/// The values of the multi-valued attributes of the sudo roles, in order.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "sudo_role_values")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub sudo_role_id: SudoRoleId,
+    /// The LDAP name of the attribute, e.g. "sudoUser".
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub attribute_name: String,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub position: i32,
+    pub value: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::sudo_roles::Entity",
+        from = "Column::SudoRoleId",
+        to = "super::sudo_roles::Column::SudoRoleId",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    SudoRoles,
+}
+
+impl Related<super::sudo_roles::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SudoRoles.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
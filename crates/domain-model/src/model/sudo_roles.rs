@@ -0,0 +1,50 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use lldap_domain::types::{SudoRoleId, Uuid};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "sudo_roles")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub sudo_role_id: SudoRoleId,
+    pub name: String,
+    pub lowercase_name: String,
+    pub description: Option<String>,
+    pub sudo_order: i64,
+    pub creation_date: chrono::NaiveDateTime,
+    pub uuid: Uuid,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::sudo_role_values::Entity")]
+    SudoRoleValues,
+}
+
+impl Related<super::sudo_role_values::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SudoRoleValues.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl From<Model> for lldap_domain::types::SudoRole {
+    fn from(role: Model) -> Self {
+        Self {
+            id: role.sudo_role_id,
+            name: role.name,
+            description: role.description,
+            users: Vec::new(),
+            hosts: Vec::new(),
+            commands: Vec::new(),
+            run_as_users: Vec::new(),
+            run_as_groups: Vec::new(),
+            options: Vec::new(),
+            order: role.sudo_order,
+            creation_date: role.creation_date,
+            uuid: role.uuid,
+        }
+    }
+}
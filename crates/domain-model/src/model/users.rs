@@ -3,7 +3,7 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use lldap_domain::types::{Email, UserId, Uuid};
+use lldap_domain::types::{Email, LegacyPasswordHash, UserId, Uuid};
 
 #[derive(Copy, Clone, Default, Debug, DeriveEntity)]
 pub struct Entity;
@@ -14,13 +14,31 @@ pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub user_id: UserId,
     pub email: Email,
-    pub lowercase_email: String,
+    /// `None` for the users without an email, like the service accounts.
+    pub lowercase_email: Option<String>,
     pub display_name: Option<String>,
     pub creation_date: chrono::NaiveDateTime,
     pub password_hash: Option<Vec<u8>>,
+    pub legacy_password_hash: Option<LegacyPasswordHash>,
+    /// Plain text TOTP secret from before they were encrypted, only until the next startup.
     pub totp_secret: Option<String>,
+    /// TOTP secret, encrypted with the server key.
+    pub encrypted_totp_secret: Option<Vec<u8>>,
     pub mfa_type: Option<String>,
+    /// The time step of the last accepted TOTP code, so that it can't be used again.
+    pub totp_last_step: Option<i64>,
+    /// Wrong second factor codes since the last successful one or the last lockout.
+    pub mfa_failed_attempts: i32,
+    pub mfa_locked_until: Option<chrono::NaiveDateTime>,
     pub uuid: Uuid,
+    pub disabled: bool,
+    pub expiration_date: Option<chrono::NaiveDateTime>,
+    pub expiration_warning_sent: bool,
+    pub password_modified_date: Option<chrono::NaiveDateTime>,
+    pub must_change_password: bool,
+    pub is_service_account: bool,
+    /// Space-separated networks, only for service accounts.
+    pub allowed_sources: Option<String>,
 }
 
 impl EntityName for Entity {
@@ -37,9 +55,21 @@ pub enum Column {
     DisplayName,
     CreationDate,
     PasswordHash,
+    LegacyPasswordHash,
     TotpSecret,
+    EncryptedTotpSecret,
     MfaType,
+    TotpLastStep,
+    MfaFailedAttempts,
+    MfaLockedUntil,
     Uuid,
+    Disabled,
+    ExpirationDate,
+    ExpirationWarningSent,
+    PasswordModifiedDate,
+    MustChangePassword,
+    IsServiceAccount,
+    AllowedSources,
 }
 
 impl ColumnTrait for Column {
@@ -53,9 +83,21 @@ impl ColumnTrait for Column {
             Column::DisplayName => ColumnType::String(StringLen::N(255)),
             Column::CreationDate => ColumnType::DateTime,
             Column::PasswordHash => ColumnType::Blob,
+            Column::LegacyPasswordHash => ColumnType::Text,
             Column::TotpSecret => ColumnType::String(StringLen::N(64)),
+            Column::EncryptedTotpSecret => ColumnType::Blob,
             Column::MfaType => ColumnType::String(StringLen::N(64)),
+            Column::TotpLastStep => ColumnType::BigInteger,
+            Column::MfaFailedAttempts => ColumnType::Integer,
+            Column::MfaLockedUntil => ColumnType::DateTime,
             Column::Uuid => ColumnType::String(StringLen::N(36)),
+            Column::Disabled => ColumnType::Boolean,
+            Column::ExpirationDate => ColumnType::DateTime,
+            Column::ExpirationWarningSent => ColumnType::Boolean,
+            Column::PasswordModifiedDate => ColumnType::DateTime,
+            Column::MustChangePassword => ColumnType::Boolean,
+            Column::IsServiceAccount => ColumnType::Boolean,
+            Column::AllowedSources => ColumnType::Text,
         }
         .def()
     }
@@ -71,6 +113,14 @@ pub enum Relation {
     JwtStorage,
     #[sea_orm(has_many = "super::password_reset_tokens::Entity")]
     PasswordResetTokens,
+    #[sea_orm(has_many = "super::password_history::Entity")]
+    PasswordHistory,
+    #[sea_orm(has_many = "super::mfa_recovery_codes::Entity")]
+    MfaRecoveryCodes,
+    #[sea_orm(has_many = "super::webauthn_credentials::Entity")]
+    WebauthnCredentials,
+    #[sea_orm(has_many = "super::app_passwords::Entity")]
+    AppPasswords,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DerivePrimaryKey)]
@@ -110,6 +160,30 @@ impl Related<super::password_reset_tokens::Entity> for Entity {
     }
 }
 
+impl Related<super::password_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PasswordHistory.def()
+    }
+}
+
+impl Related<super::mfa_recovery_codes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MfaRecoveryCodes.def()
+    }
+}
+
+impl Related<super::webauthn_credentials::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WebauthnCredentials.def()
+    }
+}
+
+impl Related<super::app_passwords::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AppPasswords.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
 
 impl From<Model> for lldap_domain::types::User {
@@ -121,6 +195,16 @@ impl From<Model> for lldap_domain::types::User {
             creation_date: user.creation_date,
             uuid: user.uuid,
             attributes: Vec::new(),
+            disabled: user.disabled,
+            expiration_date: user.expiration_date,
+            mfa_enabled: user.mfa_type.is_some(),
+            is_service_account: user.is_service_account,
+            allowed_sources: user
+                .allowed_sources
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(str::to_owned)
+                .collect(),
         }
     }
 }
@@ -0,0 +1,51 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use lldap_domain::types::{UserId, WebauthnCredentialId};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "webauthn_credentials")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub webauthn_credential_id: WebauthnCredentialId,
+    pub user_id: UserId,
+    pub credential_id: Vec<u8>,
+    pub name: String,
+    pub passkey: String,
+    pub creation_date: chrono::NaiveDateTime,
+    pub last_used_date: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::UserId",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Users,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl From<Model> for lldap_domain::types::WebauthnCredential {
+    fn from(credential: Model) -> Self {
+        Self {
+            id: credential.webauthn_credential_id,
+            user_id: credential.user_id,
+            credential_id: credential.credential_id,
+            name: credential.name,
+            passkey: credential.passkey,
+            creation_date: credential.creation_date,
+            last_used_date: credential.last_used_date,
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::types::{AttributeType, AttributeValue, JpegPhoto};
+use crate::types::{AttributeType, AttributeValue, JpegPhoto, SshPublicKey};
 use anyhow::{Context as AnyhowContext, Result, bail};
 
 pub fn deserialize_attribute_value(
@@ -22,6 +22,10 @@ pub fn deserialize_attribute_value(
     let parse_photo = |value: &String| -> Result<JpegPhoto> {
         JpegPhoto::try_from(value.as_str()).context("Provided image is not a valid JPEG")
     };
+    let parse_ssh_key = |value: &String| -> Result<SshPublicKey> {
+        SshPublicKey::try_from(value.as_str())
+            .with_context(|| format!("Invalid SSH public key {value}"))
+    };
     Ok(match (typ, is_list) {
         (AttributeType::String, false) => value[0].clone().into(),
         (AttributeType::String, true) => value.to_vec().into(),
@@ -37,5 +41,11 @@ pub fn deserialize_attribute_value(
         (AttributeType::JpegPhoto, true) => {
             (value.iter().map(parse_photo).collect::<Result<Vec<_>>>()?).into()
         }
+        (AttributeType::SshPublicKey, false) => (parse_ssh_key(&value[0])?).into(),
+        (AttributeType::SshPublicKey, true) => (value
+            .iter()
+            .map(parse_ssh_key)
+            .collect::<Result<Vec<_>>>()?)
+        .into(),
     })
 }
@@ -1,6 +1,6 @@
 use crate::{
     schema::{AttributeSchema, Schema},
-    types::AttributeType,
+    types::{AttributeType, MatchingRule},
 };
 use serde::{Deserialize, Serialize};
 
@@ -24,6 +24,7 @@ impl From<Schema> for PublicSchema {
                 is_editable: false,
                 is_hardcoded: true,
                 is_readonly: true,
+                matching_rule: MatchingRule::CaseIgnoreMatch,
             },
             AttributeSchema {
                 name: "creation_date".into(),
@@ -33,6 +34,7 @@ impl From<Schema> for PublicSchema {
                 is_editable: false,
                 is_hardcoded: true,
                 is_readonly: true,
+                matching_rule: MatchingRule::GeneralizedTimeMatch,
             },
             AttributeSchema {
                 name: "mail".into(),
@@ -42,6 +44,7 @@ impl From<Schema> for PublicSchema {
                 is_editable: true,
                 is_hardcoded: true,
                 is_readonly: false,
+                matching_rule: MatchingRule::CaseIgnoreMatch,
             },
             AttributeSchema {
                 name: "uuid".into(),
@@ -51,6 +54,7 @@ impl From<Schema> for PublicSchema {
                 is_editable: false,
                 is_hardcoded: true,
                 is_readonly: true,
+                matching_rule: MatchingRule::CaseIgnoreMatch,
             },
             AttributeSchema {
                 name: "display_name".into(),
@@ -60,6 +64,7 @@ impl From<Schema> for PublicSchema {
                 is_editable: true,
                 is_hardcoded: true,
                 is_readonly: false,
+                matching_rule: MatchingRule::CaseIgnoreMatch,
             },
         ]);
         schema
@@ -75,6 +80,7 @@ impl From<Schema> for PublicSchema {
                 is_editable: false,
                 is_hardcoded: true,
                 is_readonly: true,
+                matching_rule: MatchingRule::IntegerMatch,
             },
             AttributeSchema {
                 name: "creation_date".into(),
@@ -84,6 +90,7 @@ impl From<Schema> for PublicSchema {
                 is_editable: false,
                 is_hardcoded: true,
                 is_readonly: true,
+                matching_rule: MatchingRule::GeneralizedTimeMatch,
             },
             AttributeSchema {
                 name: "uuid".into(),
@@ -93,6 +100,7 @@ impl From<Schema> for PublicSchema {
                 is_editable: false,
                 is_hardcoded: true,
                 is_readonly: true,
+                matching_rule: MatchingRule::CaseIgnoreMatch,
             },
             AttributeSchema {
                 name: "display_name".into(),
@@ -102,6 +110,7 @@ impl From<Schema> for PublicSchema {
                 is_editable: true,
                 is_hardcoded: true,
                 is_readonly: false,
+                matching_rule: MatchingRule::CaseIgnoreMatch,
             },
         ]);
         schema
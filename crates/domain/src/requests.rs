@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-use crate::types::{Attribute, AttributeName, AttributeType, Email, GroupId, GroupName, UserId};
+use crate::types::{
+    Attribute, AttributeName, AttributeType, Email, GroupId, GroupName, LegacyPasswordHash,
+    MatchingRule, SudoRoleId, UserId,
+};
 
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Default)]
 pub struct CreateUserRequest {
@@ -9,6 +12,13 @@ pub struct CreateUserRequest {
     pub email: Email,
     pub display_name: Option<String>,
     pub attributes: Vec<Attribute>,
+    /// A password hash imported from another directory, replaced by an OPAQUE registration on
+    /// the first successful login.
+    pub legacy_password_hash: Option<LegacyPasswordHash>,
+    /// Service accounts don't need an email, and can only bind over LDAP.
+    pub is_service_account: bool,
+    /// The networks a service account can bind from. Empty means anywhere.
+    pub allowed_sources: Vec<String>,
 }
 
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Default)]
@@ -19,6 +29,8 @@ pub struct UpdateUserRequest {
     pub display_name: Option<String>,
     pub delete_attributes: Vec<AttributeName>,
     pub insert_attributes: Vec<Attribute>,
+    /// Only for service accounts.
+    pub allowed_sources: Option<Vec<String>>,
 }
 
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Default)]
@@ -42,4 +54,51 @@ pub struct CreateAttributeRequest {
     pub is_list: bool,
     pub is_visible: bool,
     pub is_editable: bool,
+    /// Defaults to the usual rule for the attribute type.
+    pub matching_rule: Option<MatchingRule>,
+}
+
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CreateSudoRoleRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub users: Vec<String>,
+    pub hosts: Vec<String>,
+    pub commands: Vec<String>,
+    pub run_as_users: Vec<String>,
+    pub run_as_groups: Vec<String>,
+    pub options: Vec<String>,
+    pub order: i64,
+}
+
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateSudoRoleRequest {
+    // Same fields as CreateSudoRoleRequest, but with an extra layer of Option: the fields that are
+    // None are left unchanged. An empty description removes it.
+    pub id: SudoRoleId,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub users: Option<Vec<String>>,
+    pub hosts: Option<Vec<String>>,
+    pub commands: Option<Vec<String>>,
+    pub run_as_users: Option<Vec<String>>,
+    pub run_as_groups: Option<Vec<String>>,
+    pub options: Option<Vec<String>>,
+    pub order: Option<i64>,
+}
+
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone)]
+pub struct AddWebauthnCredentialRequest {
+    pub user_id: UserId,
+    pub credential_id: Vec<u8>,
+    pub name: String,
+    pub passkey: String,
+}
+
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone)]
+pub struct CreateAppPasswordRequest {
+    pub user_id: UserId,
+    pub name: String,
+    pub allowed_sources: Vec<String>,
+    pub required_group: Option<GroupId>,
 }
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use crate::types::{AttributeName, AttributeType, LdapObjectClass};
+use crate::types::{AttributeName, AttributeType, LdapObjectClass, MatchingRule};
 
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone)]
 pub struct Schema {
@@ -20,6 +20,7 @@ pub struct AttributeSchema {
     pub is_editable: bool,
     pub is_hardcoded: bool,
     pub is_readonly: bool,
+    pub matching_rule: MatchingRule,
 }
 
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone)]
@@ -37,6 +38,10 @@ impl AttributeList {
             .map(|a| (a.attribute_type, a.is_list))
     }
 
+    pub fn get_matching_rule(&self, name: &AttributeName) -> Option<MatchingRule> {
+        self.get_attribute_schema(name).map(|a| a.matching_rule)
+    }
+
     pub fn format_for_ldap_schema_description(&self) -> String {
         self.attributes
             .iter()
@@ -147,6 +147,8 @@ impl From<AttributeValue> for Serialized {
             AttributeValue::JpegPhoto(Cardinality::Unbounded(l)) => Serialized::from(l),
             AttributeValue::DateTime(Cardinality::Singleton(dt)) => Serialized::from(dt),
             AttributeValue::DateTime(Cardinality::Unbounded(l)) => Serialized::from(l),
+            AttributeValue::SshPublicKey(Cardinality::Singleton(k)) => Serialized::from(k),
+            AttributeValue::SshPublicKey(Cardinality::Unbounded(l)) => Serialized::from(l),
         }
     }
 }
@@ -431,6 +433,214 @@ impl IntoActiveValue<Serialized> for JpegPhoto {
     }
 }
 
+/// The key types accepted in an SSH public key, as written in an authorized_keys file.
+const SSH_PUBLIC_KEY_TYPES: &[&str] = &[
+    "ssh-ed25519",
+    "ssh-rsa",
+    "ssh-dss",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "sk-ssh-ed25519@openssh.com",
+    "sk-ecdsa-sha2-nistp256@openssh.com",
+];
+
+/// An OpenSSH public key, in the authorized_keys format: "<type> <base64 key> [comment]".
+///
+/// The key is validated when parsed, and stored normalized (single spaces, no trailing
+/// whitespace).
+#[derive(
+    PartialEq, Eq, Clone, Serialize, Deserialize, Hash, derive_more::Debug, derive_more::Display,
+)]
+#[serde(transparent)]
+#[debug(r#""{_0}""#)]
+#[display("{_0}")]
+pub struct SshPublicKey(String);
+
+impl TryFrom<&str> for SshPublicKey {
+    type Error = anyhow::Error;
+    fn try_from(string: &str) -> anyhow::Result<Self> {
+        let mut parts = string.split_whitespace();
+        let key_type = parts
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("SSH public key is empty"))?;
+        if !SSH_PUBLIC_KEY_TYPES.contains(&key_type) {
+            anyhow::bail!("Unsupported SSH public key type: {key_type}");
+        }
+        let encoded_key = parts
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("SSH public key is missing the key data"))?;
+        let blob = base64::engine::general_purpose::STANDARD
+            .decode(encoded_key)
+            .map_err(|e| anyhow::anyhow!("SSH public key data is not valid base64: {e}"))?;
+        // The blob starts with the key type, as a length-prefixed string.
+        let blob_key_type = blob
+            .get(..4)
+            .map(|len| u32::from_be_bytes(len.try_into().unwrap()) as usize)
+            .and_then(|len| blob[4..].get(..len));
+        if blob_key_type != Some(key_type.as_bytes()) {
+            anyhow::bail!("SSH public key data doesn't match the key type {key_type}");
+        }
+        let comment = parts.collect::<Vec<_>>().join(" ");
+        Ok(SshPublicKey(if comment.is_empty() {
+            format!("{key_type} {encoded_key}")
+        } else {
+            format!("{key_type} {encoded_key} {comment}")
+        }))
+    }
+}
+
+impl SshPublicKey {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+
+    /// The key type, e.g. "ssh-ed25519".
+    pub fn key_type(&self) -> &str {
+        self.0.split(' ').next().unwrap_or_default()
+    }
+
+    /// The free-form comment after the key, usually identifying its owner or device.
+    pub fn comment(&self) -> Option<&str> {
+        self.0.splitn(3, ' ').nth(2)
+    }
+
+    /// The SHA256 fingerprint of the key, formatted like `ssh-keygen -l`: "SHA256:<base64>".
+    pub fn fingerprint(&self) -> String {
+        use sha2::Digest;
+        let blob = self
+            .0
+            .split(' ')
+            .nth(1)
+            .and_then(|key| base64::engine::general_purpose::STANDARD.decode(key).ok())
+            .unwrap_or_default();
+        format!(
+            "SHA256:{}",
+            base64::engine::general_purpose::STANDARD_NO_PAD.encode(sha2::Sha256::digest(blob))
+        )
+    }
+}
+
+/// The password hashing schemes that can be imported from other directories.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LegacyPasswordScheme {
+    /// `{SSHA}`: salted SHA-1, as used by OpenLDAP.
+    Ssha,
+    /// `{SSHA512}`: salted SHA-512.
+    Ssha512,
+    /// `$6$`: sha512-crypt, usually prefixed by `{CRYPT}` in LDAP.
+    Sha512Crypt,
+    /// `$2a$`, `$2b$` or `$2y$`.
+    Bcrypt,
+    /// `$argon2i$`, `$argon2d$` or `$argon2id$`, usually prefixed by `{ARGON2}` in LDAP.
+    Argon2,
+}
+
+fn parse_crypt_scheme(hash: &str) -> anyhow::Result<LegacyPasswordScheme> {
+    if hash.starts_with("$6$") {
+        Ok(LegacyPasswordScheme::Sha512Crypt)
+    } else if ["$2a$", "$2b$", "$2y$"].iter().any(|p| hash.starts_with(p)) {
+        if hash.len() != 60 {
+            anyhow::bail!("Invalid bcrypt hash");
+        }
+        Ok(LegacyPasswordScheme::Bcrypt)
+    } else if ["$argon2i$", "$argon2d$", "$argon2id$"]
+        .iter()
+        .any(|p| hash.starts_with(p))
+    {
+        Ok(LegacyPasswordScheme::Argon2)
+    } else {
+        anyhow::bail!("Unsupported crypt hash format")
+    }
+}
+
+fn parse_salted_sha(hash: &str, digest_len: usize) -> anyhow::Result<()> {
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(hash)
+        .map_err(|e| anyhow::anyhow!("Salted hash is not valid base64: {e}"))?;
+    if decoded.len() <= digest_len {
+        anyhow::bail!("Salted hash is too short");
+    }
+    Ok(())
+}
+
+/// Splits a hash in its scheme and the part of the hash that follows the optional `{SCHEME}`
+/// prefix.
+fn parse_legacy_password_hash(hash: &str) -> anyhow::Result<(LegacyPasswordScheme, &str)> {
+    let Some(rest) = hash.strip_prefix('{') else {
+        return Ok((parse_crypt_scheme(hash)?, hash));
+    };
+    let (prefix, hash) = rest
+        .split_once('}')
+        .ok_or_else(|| anyhow::anyhow!("Unterminated password scheme"))?;
+    let scheme = match prefix.to_ascii_uppercase().as_str() {
+        "SSHA" => {
+            parse_salted_sha(hash, 20)?;
+            LegacyPasswordScheme::Ssha
+        }
+        "SSHA512" => {
+            parse_salted_sha(hash, 64)?;
+            LegacyPasswordScheme::Ssha512
+        }
+        "CRYPT" => parse_crypt_scheme(hash)?,
+        "ARGON2" => match parse_crypt_scheme(hash)? {
+            LegacyPasswordScheme::Argon2 => LegacyPasswordScheme::Argon2,
+            _ => anyhow::bail!("Invalid argon2 hash"),
+        },
+        _ => anyhow::bail!("Unsupported password scheme: {{{prefix}}}"),
+    };
+    Ok((scheme, hash))
+}
+
+/// A password hash imported from another directory, e.g. the `userPassword` of an OpenLDAP
+/// user. It is only kept until the user first logs in, when the password is registered with
+/// OPAQUE instead.
+#[derive(PartialEq, Eq, Clone, Serialize, Deserialize, DeriveValueType)]
+#[serde(try_from = "&str")]
+#[sea_orm(column_type = "Text")]
+pub struct LegacyPasswordHash(String);
+
+impl TryFrom<&str> for LegacyPasswordHash {
+    type Error = anyhow::Error;
+    fn try_from(hash: &str) -> anyhow::Result<Self> {
+        let hash = hash.trim();
+        parse_legacy_password_hash(hash)?;
+        Ok(LegacyPasswordHash(hash.to_owned()))
+    }
+}
+
+impl std::fmt::Debug for LegacyPasswordHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Don't leak the hash in the logs.
+        f.debug_tuple("LegacyPasswordHash")
+            .field(&self.scheme())
+            .finish()
+    }
+}
+
+impl LegacyPasswordHash {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn scheme(&self) -> LegacyPasswordScheme {
+        parse_legacy_password_hash(&self.0)
+            .expect("Legacy password hash was validated")
+            .0
+    }
+
+    /// The hash without the `{SCHEME}` prefix, if any.
+    pub fn hash(&self) -> &str {
+        parse_legacy_password_hash(&self.0)
+            .expect("Legacy password hash was validated")
+            .1
+    }
+}
+
 // Represents values that can be either a singleton or a list of a specific type
 // Used by AttributeValue to model attributes with types that might be a list.
 #[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize, Hash)]
@@ -454,6 +664,7 @@ pub enum AttributeValue {
     Integer(Cardinality<i64>),
     JpegPhoto(Cardinality<JpegPhoto>),
     DateTime(Cardinality<NaiveDateTime>),
+    SshPublicKey(Cardinality<SshPublicKey>),
 }
 
 impl AttributeValue {
@@ -463,6 +674,7 @@ impl AttributeValue {
             Self::Integer(_) => AttributeType::Integer,
             Self::JpegPhoto(_) => AttributeType::JpegPhoto,
             Self::DateTime(_) => AttributeType::DateTime,
+            Self::SshPublicKey(_) => AttributeType::SshPublicKey,
         }
     }
     pub fn as_str(&self) -> Option<&str> {
@@ -486,6 +698,14 @@ impl AttributeValue {
             None
         }
     }
+    /// The SSH public keys held by the value, if it is of the SSH public key type.
+    pub fn as_ssh_public_keys(&self) -> &[SshPublicKey] {
+        match self {
+            AttributeValue::SshPublicKey(Cardinality::Singleton(k)) => std::slice::from_ref(k),
+            AttributeValue::SshPublicKey(Cardinality::Unbounded(l)) => l,
+            _ => &[],
+        }
+    }
 }
 
 impl From<String> for AttributeValue {
@@ -532,6 +752,17 @@ impl From<Vec<NaiveDateTime>> for AttributeValue {
     }
 }
 
+impl From<SshPublicKey> for AttributeValue {
+    fn from(k: SshPublicKey) -> Self {
+        AttributeValue::SshPublicKey(Cardinality::Singleton(k))
+    }
+}
+impl From<Vec<SshPublicKey>> for AttributeValue {
+    fn from(l: Vec<SshPublicKey>) -> Self {
+        AttributeValue::SshPublicKey(Cardinality::Unbounded(l))
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize, Hash)]
 pub struct Attribute {
     pub name: AttributeName,
@@ -546,6 +777,17 @@ pub struct User {
     pub creation_date: NaiveDateTime,
     pub uuid: Uuid,
     pub attributes: Vec<Attribute>,
+    /// Disabled users keep their data and memberships, but cannot log in.
+    pub disabled: bool,
+    /// Past this date, the user cannot log in anymore.
+    pub expiration_date: Option<NaiveDateTime>,
+    /// The user has enrolled a second factor, asked when logging in to the web UI.
+    pub mfa_enabled: bool,
+    /// An account for an integration rather than a person: it has no email, cannot log in to the
+    /// web UI, and lives under `ou=services` in LDAP.
+    pub is_service_account: bool,
+    /// For service accounts, the networks they can bind from. Empty means anywhere.
+    pub allowed_sources: Vec<String>,
 }
 
 #[cfg(feature = "test")]
@@ -559,6 +801,11 @@ impl Default for User {
             creation_date: epoch,
             uuid: Uuid::from_name_and_date("", &epoch),
             attributes: Vec::new(),
+            disabled: false,
+            expiration_date: None,
+            mfa_enabled: false,
+            is_service_account: false,
+            allowed_sources: Vec::new(),
         }
     }
 }
@@ -609,6 +856,7 @@ pub enum AttributeType {
     Integer,
     JpegPhoto,
     DateTime,
+    SshPublicKey,
 }
 
 impl From<AttributeType> for Value {
@@ -646,6 +894,126 @@ impl ValueType for AttributeType {
     }
 }
 
+/// How the values of an attribute are compared, named after the LDAP matching rules (RFC 4517).
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    EnumString,
+    IntoStaticStr,
+    juniper::GraphQLEnum,
+)]
+pub enum MatchingRule {
+    CaseIgnoreMatch,
+    CaseExactMatch,
+    IntegerMatch,
+    GeneralizedTimeMatch,
+    OctetStringMatch,
+}
+
+impl MatchingRule {
+    /// The rule used for an attribute of the given type when none is specified.
+    pub fn default_for(attribute_type: AttributeType) -> Self {
+        match attribute_type {
+            AttributeType::String => Self::CaseIgnoreMatch,
+            AttributeType::Integer => Self::IntegerMatch,
+            AttributeType::DateTime => Self::GeneralizedTimeMatch,
+            AttributeType::JpegPhoto | AttributeType::SshPublicKey => Self::OctetStringMatch,
+        }
+    }
+
+    /// Whether the rule can compare values of the given type: only strings have a choice.
+    pub fn is_compatible_with(self, attribute_type: AttributeType) -> bool {
+        self == Self::default_for(attribute_type)
+            || (attribute_type == AttributeType::String && self == Self::CaseExactMatch)
+    }
+
+    /// The name of the rule in the LDAP schema, e.g. "caseIgnoreMatch".
+    pub fn ldap_name(self) -> &'static str {
+        match self {
+            Self::CaseIgnoreMatch => "caseIgnoreMatch",
+            Self::CaseExactMatch => "caseExactMatch",
+            Self::IntegerMatch => "integerMatch",
+            Self::GeneralizedTimeMatch => "generalizedTimeMatch",
+            Self::OctetStringMatch => "octetStringMatch",
+        }
+    }
+
+    /// The rule with the given LDAP name, ignoring case.
+    pub fn from_ldap_name(name: &str) -> Option<Self> {
+        [
+            Self::CaseIgnoreMatch,
+            Self::CaseExactMatch,
+            Self::IntegerMatch,
+            Self::GeneralizedTimeMatch,
+            Self::OctetStringMatch,
+        ]
+        .into_iter()
+        .find(|rule| rule.ldap_name().eq_ignore_ascii_case(name))
+    }
+
+    /// Whether the two raw values are equal according to the rule.
+    pub fn values_match(self, value1: &[u8], value2: &[u8]) -> bool {
+        fn as_str(value: &[u8]) -> Option<&str> {
+            std::str::from_utf8(value).ok()
+        }
+        fn as_int(value: &[u8]) -> Option<i64> {
+            as_str(value).and_then(|s| s.trim().parse().ok())
+        }
+        match self {
+            Self::CaseIgnoreMatch => match (as_str(value1), as_str(value2)) {
+                (Some(s1), Some(s2)) => s1.to_lowercase() == s2.to_lowercase(),
+                _ => value1 == value2,
+            },
+            Self::IntegerMatch => match (as_int(value1), as_int(value2)) {
+                (Some(i1), Some(i2)) => i1 == i2,
+                _ => value1 == value2,
+            },
+            Self::CaseExactMatch | Self::GeneralizedTimeMatch | Self::OctetStringMatch => {
+                value1 == value2
+            }
+        }
+    }
+}
+
+impl From<MatchingRule> for Value {
+    fn from(matching_rule: MatchingRule) -> Self {
+        Into::<&'static str>::into(matching_rule).into()
+    }
+}
+
+impl TryGetable for MatchingRule {
+    fn try_get_by<I: sea_orm::ColIdx>(res: &QueryResult, index: I) -> Result<Self, TryGetError> {
+        use std::str::FromStr;
+        Ok(MatchingRule::from_str(&String::try_get_by(res, index)?).expect("Invalid enum value"))
+    }
+}
+
+impl ValueType for MatchingRule {
+    fn try_from(v: Value) -> Result<Self, ValueTypeErr> {
+        use std::str::FromStr;
+        Ok(MatchingRule::from_str(&<String as ValueType>::try_from(v)?)
+            .expect("Invalid enum value"))
+    }
+
+    fn type_name() -> String {
+        "MatchingRule".to_owned()
+    }
+
+    fn array_type() -> ArrayType {
+        ArrayType::String
+    }
+
+    fn column_type() -> ColumnType {
+        ColumnType::String(StringLen::N(64))
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct Group {
     pub id: GroupId,
@@ -654,6 +1022,8 @@ pub struct Group {
     pub uuid: Uuid,
     pub users: Vec<UserId>,
     pub attributes: Vec<Attribute>,
+    /// For dynamic groups, the LDAP filter defining the members.
+    pub member_filter: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -663,6 +1033,7 @@ pub struct GroupDetails {
     pub creation_date: NaiveDateTime,
     pub uuid: Uuid,
     pub attributes: Vec<Attribute>,
+    pub member_filter: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -671,6 +1042,139 @@ pub struct UserAndGroups {
     pub groups: Option<Vec<GroupDetails>>,
 }
 
+#[derive(
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    DeriveValueType,
+    derive_more::Debug,
+)]
+#[debug("{_0}")]
+pub struct SudoRoleId(pub i32);
+
+impl TryFromU64 for SudoRoleId {
+    fn try_from_u64(n: u64) -> Result<Self, DbErr> {
+        Ok(SudoRoleId(i32::try_from_u64(n)?))
+    }
+}
+
+impl From<&SudoRoleId> for Value {
+    fn from(id: &SudoRoleId) -> Self {
+        (*id).into()
+    }
+}
+
+/// A sudo rule, served over LDAP with the "sudoRole" object class of the sudo schema.
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub struct SudoRole {
+    pub id: SudoRoleId,
+    /// The "cn" of the rule. The rule named "defaults" holds the global sudo options.
+    pub name: String,
+    pub description: Option<String>,
+    /// The users, "%group"s or "+netgroup"s the rule applies to.
+    pub users: Vec<String>,
+    pub hosts: Vec<String>,
+    pub commands: Vec<String>,
+    pub run_as_users: Vec<String>,
+    pub run_as_groups: Vec<String>,
+    pub options: Vec<String>,
+    /// When several rules match, the one with the highest order wins.
+    pub order: i64,
+    pub creation_date: NaiveDateTime,
+    pub uuid: Uuid,
+}
+
+#[derive(
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    DeriveValueType,
+    derive_more::Debug,
+)]
+#[debug("{_0}")]
+pub struct WebauthnCredentialId(pub i32);
+
+impl TryFromU64 for WebauthnCredentialId {
+    fn try_from_u64(n: u64) -> Result<Self, DbErr> {
+        Ok(WebauthnCredentialId(i32::try_from_u64(n)?))
+    }
+}
+
+impl From<&WebauthnCredentialId> for Value {
+    fn from(id: &WebauthnCredentialId) -> Self {
+        (*id).into()
+    }
+}
+
+/// A passkey or security key registered by a user to log in to the web UI.
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub struct WebauthnCredential {
+    pub id: WebauthnCredentialId,
+    pub user_id: UserId,
+    /// The ID chosen by the authenticator, sent back when the credential is used.
+    pub credential_id: Vec<u8>,
+    pub name: String,
+    /// The public key and the signature counter, serialized by the WebAuthn library.
+    pub passkey: String,
+    pub creation_date: NaiveDateTime,
+    pub last_used_date: Option<NaiveDateTime>,
+}
+
+#[derive(
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    DeriveValueType,
+    derive_more::Debug,
+)]
+#[debug("{_0}")]
+pub struct AppPasswordId(pub i32);
+
+impl TryFromU64 for AppPasswordId {
+    fn try_from_u64(n: u64) -> Result<Self, DbErr> {
+        Ok(AppPasswordId(i32::try_from_u64(n)?))
+    }
+}
+
+impl From<&AppPasswordId> for Value {
+    fn from(id: &AppPasswordId) -> Self {
+        (*id).into()
+    }
+}
+
+/// A generated password for an application binding over LDAP, accepted in addition to the main
+/// password of the user. Only its keyed hash is stored.
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub struct AppPassword {
+    pub id: AppPasswordId,
+    pub user_id: UserId,
+    pub name: String,
+    /// The networks (e.g. `192.168.1.0/24`) the binds can come from. Empty for anywhere.
+    pub allowed_sources: Vec<String>,
+    /// The password only works while the user is a member of this group.
+    pub required_group: Option<GroupId>,
+    pub creation_date: NaiveDateTime,
+    pub last_used_date: Option<NaiveDateTime>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -699,4 +1203,65 @@ mod tests {
         assert_eq!(SERIALIZED_I64_LEN, Serialized::from(&i64::MIN).0.len());
         assert_eq!(SERIALIZED_I64_LEN, Serialized::from(&-1000i64).0.len());
     }
+
+    #[test]
+    fn test_ssh_public_key_parsing() {
+        const KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIAABAgMEBQYHCAkKCwwNDg8QERITFBUWFxgZGhscHR4f";
+        let key =
+            SshPublicKey::try_from(format!("  ssh-ed25519 {KEY}  alice@laptop  home\n").as_str())
+                .unwrap();
+        assert_eq!(key.as_str(), format!("ssh-ed25519 {KEY} alice@laptop home"));
+        assert_eq!(key.key_type(), "ssh-ed25519");
+        assert_eq!(key.comment(), Some("alice@laptop home"));
+        assert_eq!(
+            key.fingerprint(),
+            "SHA256:ZkAslGjFiUHdGf/WUL8rQvkib4PTvQatUV0OUQSncCA"
+        );
+        let key = SshPublicKey::try_from(format!("ssh-ed25519 {KEY}").as_str()).unwrap();
+        assert_eq!(key.comment(), None);
+
+        assert!(SshPublicKey::try_from("").is_err());
+        assert!(SshPublicKey::try_from("ssh-ed25519").is_err());
+        assert!(SshPublicKey::try_from(format!("ssh-foo {KEY}").as_str()).is_err());
+        assert!(SshPublicKey::try_from("ssh-ed25519 not-base64!").is_err());
+        // The key data is for a different key type.
+        assert!(SshPublicKey::try_from(format!("ssh-rsa {KEY}").as_str()).is_err());
+        assert!(SshPublicKey::try_from("ssh-ed25519 AAAAB3NzaC1yc2E=").is_err());
+    }
+
+    #[test]
+    fn test_legacy_password_hash_parsing() {
+        let parse = |hash: &str| LegacyPasswordHash::try_from(hash).map(|h| h.scheme());
+        let salted_sha1 = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+        assert_eq!(
+            parse(&format!("{{SSHA}}{salted_sha1}")).unwrap(),
+            LegacyPasswordScheme::Ssha
+        );
+        assert_eq!(
+            parse(&format!("{{ssha}}{salted_sha1}")).unwrap(),
+            LegacyPasswordScheme::Ssha
+        );
+        // Too short for a salted SHA-512.
+        assert!(parse(&format!("{{SSHA512}}{salted_sha1}")).is_err());
+        assert_eq!(
+            parse("{CRYPT}$6$salt$hash").unwrap(),
+            LegacyPasswordScheme::Sha512Crypt
+        );
+        assert_eq!(
+            parse("$2b$04$AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA").unwrap(),
+            LegacyPasswordScheme::Bcrypt
+        );
+        assert!(parse("$2b$04$tooshort").is_err());
+        assert_eq!(
+            parse("{ARGON2}$argon2id$v=19$m=65536,t=2,p=1$salt$hash").unwrap(),
+            LegacyPasswordScheme::Argon2
+        );
+        assert!(parse("{ARGON2}$6$salt$hash").is_err());
+        assert!(parse("{MD5}AAAA").is_err());
+        assert!(parse("plaintext").is_err());
+
+        let hash = LegacyPasswordHash::try_from("{CRYPT}$6$salt$hash").unwrap();
+        assert_eq!(hash.hash(), "$6$salt$hash");
+        assert_eq!(format!("{hash:?}"), "LegacyPasswordHash(Sha512Crypt)");
+    }
 }
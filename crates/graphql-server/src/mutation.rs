@@ -9,16 +9,19 @@ use lldap_domain::{
     deserialize::deserialize_attribute_value,
     public_schema::PublicSchema,
     requests::{
-        CreateAttributeRequest, CreateGroupRequest, CreateUserRequest, UpdateGroupRequest,
+        CreateAppPasswordRequest, CreateAttributeRequest, CreateGroupRequest,
+        CreateSudoRoleRequest, CreateUserRequest, UpdateGroupRequest, UpdateSudoRoleRequest,
         UpdateUserRequest,
     },
     schema::AttributeList,
     types::{
-        Attribute as DomainAttribute, AttributeName, AttributeType, Email, GroupId,
-        LdapObjectClass, UserId,
+        AppPasswordId, Attribute as DomainAttribute, AttributeName, AttributeType, Email, GroupId,
+        LdapObjectClass, LegacyPasswordHash, MatchingRule, SudoRoleId, UserId,
+        WebauthnCredentialId,
     },
 };
-use lldap_domain_handlers::handler::BackendHandler;
+use lldap_domain_handlers::handler::{BackendHandler, GroupMemberFilter};
+use lldap_ldap::parse_member_filter;
 use lldap_validation::attributes::{ALLOWED_CHARACTERS_DESCRIPTION, validate_attribute_name};
 use std::{collections::BTreeMap, sync::Arc};
 use tracing::{Instrument, Span, debug, debug_span};
@@ -55,6 +58,7 @@ struct AttributeValue {
     /// Integers (signed 64 bits) are represented as strings.
     /// Dates are represented as strings in RFC3339 format, e.g. "2019-10-12T07:20:50.52Z".
     /// JpegPhotos are represented as base64 encoded strings. They must be valid JPEGs.
+    /// SSH public keys are in the authorized_keys format, e.g. "ssh-ed25519 AAAA... comment".
     value: Vec<String>,
 }
 
@@ -76,6 +80,14 @@ pub struct CreateUserInput {
     avatar: Option<String>,
     /// Attributes.
     attributes: Option<Vec<AttributeValue>>,
+    /// A password hash imported from another directory, e.g. "{SSHA}...", "{CRYPT}$6$...",
+    /// bcrypt or argon2. It is replaced by an OPAQUE registration on the first successful login.
+    legacy_password_hash: Option<String>,
+    /// Creates a service account for an integration: no email required, no login to the web UI,
+    /// only LDAP binds.
+    is_service_account: Option<bool>,
+    /// For service accounts, the networks they can bind from, e.g. "192.168.1.0/24".
+    allowed_sources: Option<Vec<String>>,
 }
 
 #[derive(PartialEq, Eq, Debug, GraphQLInputObject)]
@@ -107,6 +119,9 @@ pub struct UpdateUserInput {
     /// Inserts or updates the given attributes.
     /// For lists, the entire list must be provided.
     insert_attributes: Option<Vec<AttributeValue>>,
+    /// For service accounts, the networks they can bind from. An empty list removes the
+    /// restriction. Only for admins.
+    allowed_sources: Option<Vec<String>>,
 }
 
 #[derive(PartialEq, Eq, Debug, GraphQLInputObject)]
@@ -124,11 +139,66 @@ pub struct UpdateGroupInput {
     insert_attributes: Option<Vec<AttributeValue>>,
 }
 
+#[derive(PartialEq, Eq, Debug, GraphQLInputObject)]
+/// The details required to create a sudo rule.
+pub struct CreateSudoRoleInput {
+    name: String,
+    description: Option<String>,
+    /// The users, "%group"s or "+netgroup"s the rule applies to.
+    users: Option<Vec<String>>,
+    hosts: Option<Vec<String>>,
+    commands: Option<Vec<String>>,
+    run_as_users: Option<Vec<String>>,
+    run_as_groups: Option<Vec<String>>,
+    options: Option<Vec<String>>,
+    /// When several rules match, the one with the highest order wins. Defaults to 0.
+    order: Option<i32>,
+}
+
+#[derive(PartialEq, Eq, Debug, GraphQLInputObject)]
+/// The fields that can be updated for a sudo rule. For lists, the entire list must be provided.
+pub struct UpdateSudoRoleInput {
+    id: i32,
+    name: Option<String>,
+    /// An empty description removes it.
+    description: Option<String>,
+    users: Option<Vec<String>>,
+    hosts: Option<Vec<String>>,
+    commands: Option<Vec<String>>,
+    run_as_users: Option<Vec<String>>,
+    run_as_groups: Option<Vec<String>>,
+    options: Option<Vec<String>>,
+    order: Option<i32>,
+}
+
+fn validate_sudo_role_name(name: &str) -> FieldResult<()> {
+    if name.trim().is_empty() || name.trim() != name {
+        return Err(anyhow!("Invalid sudo role name: {name:?}").into());
+    }
+    Ok(())
+}
+
+fn validate_sudo_role_values(values: &[String]) -> FieldResult<()> {
+    if let Some(value) = values.iter().find(|v| v.trim().is_empty()) {
+        return Err(anyhow!("Invalid sudo role value: {value:?}").into());
+    }
+    Ok(())
+}
+
 #[derive(PartialEq, Eq, Debug, GraphQLObject)]
 pub struct Success {
     ok: bool,
 }
 
+/// What the authenticator app needs to generate the TOTP codes.
+#[derive(PartialEq, Eq, Debug, GraphQLObject)]
+pub struct TotpEnrollment {
+    /// The secret in base32, to type in the app if it cannot scan the QR code.
+    secret: String,
+    /// The `otpauth://` URI to show as a QR code.
+    uri: String,
+}
+
 impl Success {
     fn new() -> Self {
         Self { ok: true }
@@ -233,6 +303,12 @@ impl<Handler: BackendHandler> Mutation<Handler> {
             .get_admin_handler()
             .ok_or_else(field_error_callback(&span, "Unauthorized user creation"))?;
         let user_id = UserId::new(&user.id);
+        let legacy_password_hash = user
+            .legacy_password_hash
+            .as_deref()
+            .map(LegacyPasswordHash::try_from)
+            .transpose()
+            .context("Invalid legacy password hash")?;
         let schema = handler.get_schema().await?;
         let consolidated_attributes = consolidate_attributes(
             user.attributes.unwrap_or_default(),
@@ -245,16 +321,23 @@ impl<Handler: BackendHandler> Mutation<Handler> {
             display_name,
             attributes,
         } = unpack_attributes(consolidated_attributes, &schema, true)?;
+        let is_service_account = user.is_service_account.unwrap_or(false);
+        let email = user.email.map(Email::from).or(email);
+        // Service accounts don't need an email.
+        let email = if is_service_account {
+            email.unwrap_or_default()
+        } else {
+            email.ok_or_else(|| anyhow!("Email is required when creating a new user"))?
+        };
         handler
             .create_user(CreateUserRequest {
                 user_id: user_id.clone(),
-                email: user
-                    .email
-                    .map(Email::from)
-                    .or(email)
-                    .ok_or_else(|| anyhow!("Email is required when creating a new user"))?,
+                email,
                 display_name: user.display_name.or(display_name),
                 attributes,
+                legacy_password_hash,
+                is_service_account,
+                allowed_sources: user.allowed_sources.unwrap_or_default(),
             })
             .instrument(span.clone())
             .await?;
@@ -304,6 +387,10 @@ impl<Handler: BackendHandler> Mutation<Handler> {
             .get_writeable_handler(&user_id)
             .ok_or_else(field_error_callback(&span, "Unauthorized user update"))?;
         let is_admin = context.validation_result.is_admin();
+        if user.allowed_sources.is_some() && !is_admin {
+            span.in_scope(|| debug!("Unauthorized change of the allowed sources"));
+            return Err("Only admins can change the allowed sources of a service account".into());
+        }
         let schema = handler.get_schema().await?;
         // Consolidate attributes and fields into a combined attribute list
         let consolidated_attributes = consolidate_attributes(
@@ -344,6 +431,7 @@ impl<Handler: BackendHandler> Mutation<Handler> {
                     .map(Into::into)
                     .collect(),
                 insert_attributes,
+                allowed_sources: user.allowed_sources,
             })
             .instrument(span)
             .await?;
@@ -447,6 +535,45 @@ impl<Handler: BackendHandler> Mutation<Handler> {
         Ok(Success::new())
     }
 
+    /// Turns the group into a dynamic group whose members are the users matching the LDAP filter.
+    /// An empty filter turns it back into a regular group, without members.
+    async fn set_group_member_filter(
+        context: &Context<Handler>,
+        group_id: i32,
+        filter: Option<String>,
+    ) -> FieldResult<Success> {
+        let span = debug_span!("[GraphQL mutation] set_group_member_filter");
+        span.in_scope(|| {
+            debug!(?group_id, ?filter);
+        });
+        let handler = context
+            .get_admin_handler()
+            .ok_or_else(field_error_callback(
+                &span,
+                "Unauthorized group membership modification",
+            ))?;
+        let member_filter = match filter.as_deref().map(str::trim).unwrap_or_default() {
+            "" => None,
+            ldap_filter => {
+                let group = handler.get_group_details(GroupId(group_id)).await?;
+                if group.display_name.as_str().starts_with("lldap_") {
+                    span.in_scope(|| debug!("Cannot turn a built-in group into a dynamic group"));
+                    return Err("Cannot turn a built-in group into a dynamic group".into());
+                }
+                let schema = handler.get_schema().await?;
+                Some(GroupMemberFilter {
+                    ldap_filter: ldap_filter.to_owned(),
+                    filter: parse_member_filter(ldap_filter, &schema)?,
+                })
+            }
+        };
+        handler
+            .set_group_member_filter(GroupId(group_id), member_filter)
+            .instrument(span)
+            .await?;
+        Ok(Success::new())
+    }
+
     async fn delete_user(context: &Context<Handler>, user_id: String) -> FieldResult<Success> {
         let span = debug_span!("[GraphQL mutation] delete_user");
         span.in_scope(|| {
@@ -464,6 +591,218 @@ impl<Handler: BackendHandler> Mutation<Handler> {
         Ok(Success::new())
     }
 
+    /// Disabling a user prevents them from logging in and revokes their sessions, while keeping
+    /// their data and group memberships.
+    async fn set_user_disabled(
+        context: &Context<Handler>,
+        user_id: String,
+        disabled: bool,
+    ) -> FieldResult<Success> {
+        let span = debug_span!("[GraphQL mutation] set_user_disabled");
+        span.in_scope(|| {
+            debug!(?user_id, ?disabled);
+        });
+        let user_id = UserId::new(&user_id);
+        let handler = context
+            .get_admin_handler()
+            .ok_or_else(field_error_callback(&span, "Unauthorized user update"))?;
+        if disabled && context.validation_result.user == user_id {
+            span.in_scope(|| debug!("Cannot disable current user"));
+            return Err("Cannot disable current user".into());
+        }
+        handler
+            .set_user_disabled(&user_id, disabled)
+            .instrument(span)
+            .await?;
+        Ok(Success::new())
+    }
+
+    /// Sets the date past which the user cannot log in anymore. A null date removes the
+    /// expiration.
+    async fn set_user_expiration_date(
+        context: &Context<Handler>,
+        user_id: String,
+        expiration_date: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> FieldResult<Success> {
+        let span = debug_span!("[GraphQL mutation] set_user_expiration_date");
+        span.in_scope(|| {
+            debug!(?user_id, ?expiration_date);
+        });
+        let user_id = UserId::new(&user_id);
+        let handler = context
+            .get_admin_handler()
+            .ok_or_else(field_error_callback(&span, "Unauthorized user update"))?;
+        if expiration_date.is_some_and(|d| d <= chrono::Utc::now())
+            && context.validation_result.user == user_id
+        {
+            span.in_scope(|| debug!("Cannot expire current user"));
+            return Err("Cannot expire current user".into());
+        }
+        handler
+            .set_user_expiration_date(&user_id, expiration_date.map(|d| d.naive_utc()))
+            .instrument(span)
+            .await?;
+        Ok(Success::new())
+    }
+
+    /// Starts setting up TOTP for the current user. The second factor is only enabled once
+    /// confirmed with a code.
+    async fn start_totp_enrollment(context: &Context<Handler>) -> FieldResult<TotpEnrollment> {
+        let span = debug_span!("[GraphQL mutation] start_totp_enrollment");
+        let user_id = context.validation_result.user.clone();
+        let handler = context
+            .get_writeable_handler(&user_id)
+            .ok_or_else(field_error_callback(&span, "Unauthorized TOTP enrollment"))?;
+        let enrollment = handler
+            .start_totp_enrollment(&user_id)
+            .instrument(span)
+            .await?;
+        Ok(TotpEnrollment {
+            secret: enrollment.secret,
+            uri: enrollment.uri,
+        })
+    }
+
+    /// Enables TOTP for the current user if the code is valid, and returns the recovery codes.
+    /// They can each be used once instead of a TOTP code.
+    async fn confirm_totp_enrollment(
+        context: &Context<Handler>,
+        code: String,
+    ) -> FieldResult<Vec<String>> {
+        let span = debug_span!("[GraphQL mutation] confirm_totp_enrollment");
+        let user_id = context.validation_result.user.clone();
+        let handler = context
+            .get_writeable_handler(&user_id)
+            .ok_or_else(field_error_callback(&span, "Unauthorized TOTP enrollment"))?;
+        Ok(handler
+            .confirm_totp_enrollment(&user_id, &code)
+            .instrument(span)
+            .await?)
+    }
+
+    /// Removes the second factor and the recovery codes of a user who lost them. They can then
+    /// log in with their password only, and set up a new second factor.
+    async fn reset_user_mfa(context: &Context<Handler>, user_id: String) -> FieldResult<Success> {
+        let span = debug_span!("[GraphQL mutation] reset_user_mfa");
+        span.in_scope(|| {
+            debug!(?user_id);
+        });
+        let user_id = UserId::new(&user_id);
+        let handler = context
+            .get_admin_handler()
+            .ok_or_else(field_error_callback(&span, "Unauthorized MFA reset"))?;
+        handler.reset_mfa(&user_id).instrument(span).await?;
+        Ok(Success::new())
+    }
+
+    async fn rename_webauthn_credential(
+        context: &Context<Handler>,
+        user_id: String,
+        id: i32,
+        name: String,
+    ) -> FieldResult<Success> {
+        let span = debug_span!("[GraphQL mutation] rename_webauthn_credential");
+        span.in_scope(|| {
+            debug!(?user_id, ?id, ?name);
+        });
+        if name.trim().is_empty() {
+            return Err(anyhow!("The name cannot be empty").into());
+        }
+        let user_id = UserId::new(&user_id);
+        let handler = context
+            .get_writeable_handler(&user_id)
+            .ok_or_else(field_error_callback(
+                &span,
+                "Unauthorized WebAuthn credential update",
+            ))?;
+        handler
+            .rename_webauthn_credential(&user_id, WebauthnCredentialId(id), name.trim())
+            .instrument(span)
+            .await?;
+        Ok(Success::new())
+    }
+
+    /// Revokes a passkey or security key, e.g. when it is lost.
+    async fn delete_webauthn_credential(
+        context: &Context<Handler>,
+        user_id: String,
+        id: i32,
+    ) -> FieldResult<Success> {
+        let span = debug_span!("[GraphQL mutation] delete_webauthn_credential");
+        span.in_scope(|| {
+            debug!(?user_id, ?id);
+        });
+        let user_id = UserId::new(&user_id);
+        let handler = context
+            .get_writeable_handler(&user_id)
+            .ok_or_else(field_error_callback(
+                &span,
+                "Unauthorized WebAuthn credential deletion",
+            ))?;
+        handler
+            .delete_webauthn_credential(&user_id, WebauthnCredentialId(id))
+            .instrument(span)
+            .await?;
+        Ok(Success::new())
+    }
+
+    /// Generates a password for an application of the current user, accepted for the LDAP binds.
+    /// It is only returned once. The binds can be restricted to some networks (e.g.
+    /// "192.168.1.0/24"), and to the members of a group.
+    async fn create_app_password(
+        context: &Context<Handler>,
+        name: String,
+        allowed_sources: Option<Vec<String>>,
+        required_group_id: Option<i32>,
+    ) -> FieldResult<String> {
+        let span = debug_span!("[GraphQL mutation] create_app_password");
+        span.in_scope(|| {
+            debug!(?name, ?allowed_sources, ?required_group_id);
+        });
+        if name.trim().is_empty() {
+            return Err(anyhow!("The name cannot be empty").into());
+        }
+        let user_id = context.validation_result.user.clone();
+        let handler = context
+            .get_writeable_handler(&user_id)
+            .ok_or_else(field_error_callback(
+                &span,
+                "Unauthorized app password creation",
+            ))?;
+        Ok(handler
+            .create_app_password(CreateAppPasswordRequest {
+                user_id,
+                name: name.trim().to_owned(),
+                allowed_sources: allowed_sources.unwrap_or_default(),
+                required_group: required_group_id.map(GroupId),
+            })
+            .instrument(span)
+            .await?)
+    }
+
+    async fn delete_app_password(
+        context: &Context<Handler>,
+        user_id: String,
+        id: i32,
+    ) -> FieldResult<Success> {
+        let span = debug_span!("[GraphQL mutation] delete_app_password");
+        span.in_scope(|| {
+            debug!(?user_id, ?id);
+        });
+        let user_id = UserId::new(&user_id);
+        let handler = context
+            .get_writeable_handler(&user_id)
+            .ok_or_else(field_error_callback(
+                &span,
+                "Unauthorized app password deletion",
+            ))?;
+        handler
+            .delete_app_password(&user_id, AppPasswordId(id))
+            .instrument(span)
+            .await?;
+        Ok(Success::new())
+    }
+
     async fn delete_group(context: &Context<Handler>, group_id: i32) -> FieldResult<Success> {
         let span = debug_span!("[GraphQL mutation] delete_group");
         span.in_scope(|| {
@@ -490,10 +829,18 @@ impl<Handler: BackendHandler> Mutation<Handler> {
         is_list: bool,
         is_visible: bool,
         is_editable: bool,
+        matching_rule: Option<MatchingRule>,
     ) -> FieldResult<Success> {
         let span = debug_span!("[GraphQL mutation] add_user_attribute");
         span.in_scope(|| {
-            debug!(?name, ?attribute_type, is_list, is_visible, is_editable);
+            debug!(
+                ?name,
+                ?attribute_type,
+                is_list,
+                is_visible,
+                is_editable,
+                ?matching_rule
+            );
         });
         validate_attribute_name(&name).map_err(|invalid_chars: Vec<char>| -> FieldError {
             let chars = String::from_iter(invalid_chars);
@@ -524,6 +871,7 @@ impl<Handler: BackendHandler> Mutation<Handler> {
                 is_list,
                 is_visible,
                 is_editable,
+                matching_rule,
             })
             .instrument(span)
             .await?;
@@ -537,10 +885,18 @@ impl<Handler: BackendHandler> Mutation<Handler> {
         is_list: bool,
         is_visible: bool,
         is_editable: bool,
+        matching_rule: Option<MatchingRule>,
     ) -> FieldResult<Success> {
         let span = debug_span!("[GraphQL mutation] add_group_attribute");
         span.in_scope(|| {
-            debug!(?name, ?attribute_type, is_list, is_visible, is_editable);
+            debug!(
+                ?name,
+                ?attribute_type,
+                is_list,
+                is_visible,
+                is_editable,
+                ?matching_rule
+            );
         });
         validate_attribute_name(&name).map_err(|invalid_chars: Vec<char>| -> FieldError {
             let chars = String::from_iter(invalid_chars);
@@ -569,6 +925,7 @@ impl<Handler: BackendHandler> Mutation<Handler> {
                 is_list,
                 is_visible,
                 is_editable,
+                matching_rule,
             })
             .instrument(span)
             .await?;
@@ -720,6 +1077,110 @@ impl<Handler: BackendHandler> Mutation<Handler> {
             .await?;
         Ok(Success::new())
     }
+
+    async fn create_sudo_role(
+        context: &Context<Handler>,
+        role: CreateSudoRoleInput,
+    ) -> FieldResult<super::query::SudoRole> {
+        let span = debug_span!("[GraphQL mutation] create_sudo_role");
+        span.in_scope(|| {
+            debug!(?role.name);
+        });
+        let handler = context
+            .get_admin_handler()
+            .ok_or_else(field_error_callback(
+                &span,
+                "Unauthorized sudo role creation",
+            ))?;
+        validate_sudo_role_name(&role.name)?;
+        let request = CreateSudoRoleRequest {
+            name: role.name,
+            description: role.description,
+            users: role.users.unwrap_or_default(),
+            hosts: role.hosts.unwrap_or_default(),
+            commands: role.commands.unwrap_or_default(),
+            run_as_users: role.run_as_users.unwrap_or_default(),
+            run_as_groups: role.run_as_groups.unwrap_or_default(),
+            options: role.options.unwrap_or_default(),
+            order: role.order.unwrap_or_default().into(),
+        };
+        for values in [
+            &request.users,
+            &request.hosts,
+            &request.commands,
+            &request.run_as_users,
+            &request.run_as_groups,
+            &request.options,
+        ] {
+            validate_sudo_role_values(values)?;
+        }
+        let id = handler
+            .create_sudo_role(request)
+            .instrument(span.clone())
+            .await?;
+        Ok(handler.get_sudo_role(id).instrument(span).await?.into())
+    }
+
+    async fn update_sudo_role(
+        context: &Context<Handler>,
+        role: UpdateSudoRoleInput,
+    ) -> FieldResult<Success> {
+        let span = debug_span!("[GraphQL mutation] update_sudo_role");
+        span.in_scope(|| {
+            debug!(?role.id);
+        });
+        let handler = context
+            .get_admin_handler()
+            .ok_or_else(field_error_callback(&span, "Unauthorized sudo role update"))?;
+        if let Some(name) = &role.name {
+            validate_sudo_role_name(name)?;
+        }
+        let request = UpdateSudoRoleRequest {
+            id: SudoRoleId(role.id),
+            name: role.name,
+            description: role.description,
+            users: role.users,
+            hosts: role.hosts,
+            commands: role.commands,
+            run_as_users: role.run_as_users,
+            run_as_groups: role.run_as_groups,
+            options: role.options,
+            order: role.order.map(Into::into),
+        };
+        for values in [
+            &request.users,
+            &request.hosts,
+            &request.commands,
+            &request.run_as_users,
+            &request.run_as_groups,
+            &request.options,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            validate_sudo_role_values(values)?;
+        }
+        handler.update_sudo_role(request).instrument(span).await?;
+        Ok(Success::new())
+    }
+
+    async fn delete_sudo_role(context: &Context<Handler>, id: i32) -> FieldResult<Success> {
+        let span = debug_span!("[GraphQL mutation] delete_sudo_role");
+        span.in_scope(|| {
+            debug!(?id);
+        });
+        let handler = context
+            .get_admin_handler()
+            .ok_or_else(field_error_callback(
+                &span,
+                "Unauthorized sudo role deletion",
+            ))?;
+        handler
+            .delete_sudo_role(SudoRoleId(id))
+            .instrument(span)
+            .await?;
+        Ok(Success::new())
+    }
 }
 
 async fn create_group_with_details<Handler: BackendHandler>(
@@ -823,6 +1284,7 @@ mod tests {
                 is_list: false,
                 is_visible: false,
                 is_editable: false,
+                matching_rule: None,
             }))
             .return_once(|_| Ok(()));
         let context = Context::<MockTestBackendHandler>::new_for_tests(
@@ -928,6 +1390,7 @@ mod tests {
                 is_list: false,
                 is_visible: false,
                 is_editable: false,
+                matching_rule: None,
             }))
             .return_once(|_| Ok(()));
         let context = Context::<MockTestBackendHandler>::new_for_tests(
@@ -1115,4 +1578,319 @@ mod tests {
             ]
         );
     }
+
+    #[tokio::test]
+    async fn test_update_sudo_role() {
+        const QUERY: &str = r#"
+            mutation {
+                updateSudoRole(role: {id: 3, users: ["%admins"], order: 5}) {
+                    ok
+                }
+            }
+        "#;
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_update_sudo_role()
+            .with(eq(UpdateSudoRoleRequest {
+                id: SudoRoleId(3),
+                name: None,
+                description: None,
+                users: Some(vec!["%admins".to_owned()]),
+                hosts: None,
+                commands: None,
+                run_as_users: None,
+                run_as_groups: None,
+                options: None,
+                order: Some(5),
+            }))
+            .return_once(|_| Ok(()));
+        let context = Context::<MockTestBackendHandler>::new_for_tests(
+            mock,
+            ValidationResults {
+                user: UserId::new("bob"),
+                permission: Permission::Admin,
+            },
+        );
+        let schema = mutation_schema(
+            Query::<MockTestBackendHandler>::new(),
+            Mutation::<MockTestBackendHandler>::new(),
+        );
+        assert_eq!(
+            execute(QUERY, None, &schema, &Variables::new(), &context).await,
+            Ok((graphql_value!({"updateSudoRole": {"ok": true}}), vec![]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_sudo_role_unauthorized() {
+        const QUERY: &str = r#"
+            mutation {
+                deleteSudoRole(id: 3) {
+                    ok
+                }
+            }
+        "#;
+        let context = Context::<MockTestBackendHandler>::new_for_tests(
+            MockTestBackendHandler::new(),
+            ValidationResults {
+                user: UserId::new("bob"),
+                permission: Permission::Readonly,
+            },
+        );
+        let schema = mutation_schema(
+            Query::<MockTestBackendHandler>::new(),
+            Mutation::<MockTestBackendHandler>::new(),
+        );
+        let (_, errors) = execute(QUERY, None, &schema, &Variables::new(), &context)
+            .await
+            .unwrap();
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_set_user_disabled() {
+        const QUERY: &str = r#"
+            mutation {
+                setUserDisabled(userId: "john", disabled: true) {
+                    ok
+                }
+            }
+        "#;
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_set_user_disabled()
+            .with(eq(UserId::new("john")), eq(true))
+            .return_once(|_, _| Ok(()));
+        let context = Context::<MockTestBackendHandler>::new_for_tests(
+            mock,
+            ValidationResults {
+                user: UserId::new("bob"),
+                permission: Permission::Admin,
+            },
+        );
+        let schema = mutation_schema(
+            Query::<MockTestBackendHandler>::new(),
+            Mutation::<MockTestBackendHandler>::new(),
+        );
+        assert_eq!(
+            execute(QUERY, None, &schema, &Variables::new(), &context).await,
+            Ok((graphql_value!({"setUserDisabled": {"ok": true}}), vec![]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_user_disabled_current_user() {
+        const QUERY: &str = r#"
+            mutation {
+                setUserDisabled(userId: "bob", disabled: true) {
+                    ok
+                }
+            }
+        "#;
+        let context = Context::<MockTestBackendHandler>::new_for_tests(
+            MockTestBackendHandler::new(),
+            ValidationResults {
+                user: UserId::new("bob"),
+                permission: Permission::Admin,
+            },
+        );
+        let schema = mutation_schema(
+            Query::<MockTestBackendHandler>::new(),
+            Mutation::<MockTestBackendHandler>::new(),
+        );
+        let (_, errors) = execute(QUERY, None, &schema, &Variables::new(), &context)
+            .await
+            .unwrap();
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_set_user_expiration_date() {
+        const QUERY: &str = r#"
+            mutation {
+                setUserExpirationDate(userId: "john", expirationDate: "2100-01-01T00:00:00Z") {
+                    ok
+                }
+            }
+        "#;
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_set_user_expiration_date()
+            .with(
+                eq(UserId::new("john")),
+                eq(Some(
+                    chrono::NaiveDate::from_ymd_opt(2100, 1, 1)
+                        .unwrap()
+                        .and_hms_opt(0, 0, 0)
+                        .unwrap(),
+                )),
+            )
+            .return_once(|_, _| Ok(()));
+        let context = Context::<MockTestBackendHandler>::new_for_tests(
+            mock,
+            ValidationResults {
+                user: UserId::new("bob"),
+                permission: Permission::Admin,
+            },
+        );
+        let schema = mutation_schema(
+            Query::<MockTestBackendHandler>::new(),
+            Mutation::<MockTestBackendHandler>::new(),
+        );
+        assert_eq!(
+            execute(QUERY, None, &schema, &Variables::new(), &context).await,
+            Ok((
+                graphql_value!({"setUserExpirationDate": {"ok": true}}),
+                vec![]
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reset_user_mfa() {
+        const QUERY: &str = r#"
+            mutation {
+                resetUserMfa(userId: "john") {
+                    ok
+                }
+            }
+        "#;
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_reset_mfa()
+            .with(eq(UserId::new("john")))
+            .return_once(|_| Ok(()));
+        let context = Context::<MockTestBackendHandler>::new_for_tests(
+            mock,
+            ValidationResults {
+                user: UserId::new("bob"),
+                permission: Permission::Admin,
+            },
+        );
+        let schema = mutation_schema(
+            Query::<MockTestBackendHandler>::new(),
+            Mutation::<MockTestBackendHandler>::new(),
+        );
+        assert_eq!(
+            execute(QUERY, None, &schema, &Variables::new(), &context).await,
+            Ok((graphql_value!({"resetUserMfa": {"ok": true}}), vec![]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reset_user_mfa_unauthorized() {
+        const QUERY: &str = r#"
+            mutation {
+                resetUserMfa(userId: "john") {
+                    ok
+                }
+            }
+        "#;
+        let context = Context::<MockTestBackendHandler>::new_for_tests(
+            MockTestBackendHandler::new(),
+            ValidationResults {
+                user: UserId::new("bob"),
+                permission: Permission::Regular,
+            },
+        );
+        let schema = mutation_schema(
+            Query::<MockTestBackendHandler>::new(),
+            Mutation::<MockTestBackendHandler>::new(),
+        );
+        let (_, errors) = execute(QUERY, None, &schema, &Variables::new(), &context)
+            .await
+            .unwrap();
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_delete_webauthn_credential() {
+        const QUERY: &str = r#"
+            mutation {
+                deleteWebauthnCredential(userId: "bob", id: 3) {
+                    ok
+                }
+            }
+        "#;
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_delete_webauthn_credential()
+            .with(eq(UserId::new("bob")), eq(WebauthnCredentialId(3)))
+            .return_once(|_, _| Ok(()));
+        let context = Context::<MockTestBackendHandler>::new_for_tests(
+            mock,
+            ValidationResults {
+                user: UserId::new("bob"),
+                permission: Permission::Regular,
+            },
+        );
+        let schema = mutation_schema(
+            Query::<MockTestBackendHandler>::new(),
+            Mutation::<MockTestBackendHandler>::new(),
+        );
+        assert_eq!(
+            execute(QUERY, None, &schema, &Variables::new(), &context).await,
+            Ok((
+                graphql_value!({"deleteWebauthnCredential": {"ok": true}}),
+                vec![]
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_webauthn_credential_other_user() {
+        const QUERY: &str = r#"
+            mutation {
+                deleteWebauthnCredential(userId: "john", id: 3) {
+                    ok
+                }
+            }
+        "#;
+        let context = Context::<MockTestBackendHandler>::new_for_tests(
+            MockTestBackendHandler::new(),
+            ValidationResults {
+                user: UserId::new("bob"),
+                permission: Permission::Regular,
+            },
+        );
+        let schema = mutation_schema(
+            Query::<MockTestBackendHandler>::new(),
+            Mutation::<MockTestBackendHandler>::new(),
+        );
+        let (_, errors) = execute(QUERY, None, &schema, &Variables::new(), &context)
+            .await
+            .unwrap();
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_create_app_password() {
+        const QUERY: &str = r#"
+            mutation {
+                createAppPassword(name: " Thunderbird ", allowedSources: ["192.168.1.0/24"], requiredGroupId: 3)
+            }
+        "#;
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_create_app_password()
+            .with(eq(CreateAppPasswordRequest {
+                user_id: UserId::new("bob"),
+                name: "Thunderbird".to_owned(),
+                allowed_sources: vec!["192.168.1.0/24".to_owned()],
+                required_group: Some(GroupId(3)),
+            }))
+            .return_once(|_| Ok("abcde-fghjk-mnpqr-stuvw".to_owned()));
+        let context = Context::<MockTestBackendHandler>::new_for_tests(
+            mock,
+            ValidationResults {
+                user: UserId::new("bob"),
+                permission: Permission::Regular,
+            },
+        );
+        let schema = mutation_schema(
+            Query::<MockTestBackendHandler>::new(),
+            Mutation::<MockTestBackendHandler>::new(),
+        );
+        assert_eq!(
+            execute(QUERY, None, &schema, &Variables::new(), &context).await,
+            Ok((
+                graphql_value!({"createAppPassword": "abcde-fghjk-mnpqr-stuvw"}),
+                vec![]
+            ))
+        );
+    }
 }
@@ -6,13 +6,16 @@ use lldap_access_control::{ReadonlyBackendHandler, UserReadableBackendHandler};
 use lldap_domain::{
     deserialize::deserialize_attribute_value,
     public_schema::PublicSchema,
-    types::{AttributeType, Cardinality, GroupDetails, GroupId, LdapObjectClass, UserId},
+    types::{
+        AttributeType, Cardinality, GroupDetails, GroupId, LdapObjectClass, MatchingRule,
+        SudoRoleId, UserId,
+    },
 };
-use lldap_domain_handlers::handler::{BackendHandler, ReadSchemaBackendHandler};
+use lldap_domain_handlers::handler::{BackendHandler, ReadSchemaBackendHandler, SubStringFilter};
 use lldap_domain_model::model::UserColumn;
 use lldap_ldap::{
     UserFieldType, get_default_group_object_classes, get_default_user_object_classes,
-    map_user_field,
+    map_user_field, parse_member_filter,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -22,10 +25,14 @@ type DomainRequestFilter = lldap_domain_handlers::handler::UserRequestFilter;
 type DomainUser = lldap_domain::types::User;
 type DomainGroup = lldap_domain::types::Group;
 type DomainUserAndGroups = lldap_domain::types::UserAndGroups;
+type DomainWebauthnCredential = lldap_domain::types::WebauthnCredential;
+type DomainAppPassword = lldap_domain::types::AppPassword;
 type DomainAttributeList = lldap_domain::schema::AttributeList;
 type DomainAttributeSchema = lldap_domain::schema::AttributeSchema;
 type DomainAttribute = lldap_domain::types::Attribute;
 type DomainAttributeValue = lldap_domain::types::AttributeValue;
+type DomainSudoRole = lldap_domain::types::SudoRole;
+type DomainSshPublicKey = lldap_domain::types::SshPublicKey;
 
 #[derive(PartialEq, Eq, Debug, GraphQLInputObject)]
 /// A filter for requests, specifying a boolean expression based on field constraints. Only one of
@@ -37,6 +44,7 @@ pub struct RequestFilter {
     eq: Option<EqualityConstraint>,
     member_of: Option<String>,
     member_of_id: Option<i32>,
+    substring: Option<SubstringConstraint>,
 }
 
 impl RequestFilter {
@@ -48,9 +56,10 @@ impl RequestFilter {
             self.not,
             self.member_of,
             self.member_of_id,
+            self.substring,
         ) {
-            (Some(eq), None, None, None, None, None) => {
-                match map_user_field(&eq.field.as_str().into(), schema) {
+            (Some(eq), None, None, None, None, None, None) => {
+                match map_user_field(&eq.field.as_str().into(), schema, false) {
                     UserFieldType::NoMatch => {
                         Err(format!("Unknown request filter: {}", &eq.field).into())
                     }
@@ -60,6 +69,18 @@ impl RequestFilter {
                     UserFieldType::PrimaryField(column) => {
                         Ok(DomainRequestFilter::Equality(column, eq.value))
                     }
+                    UserFieldType::Attribute(name, typ, false)
+                        if schema
+                            .get_schema()
+                            .user_attributes
+                            .get_matching_rule(&name)
+                            .unwrap_or_else(|| MatchingRule::default_for(typ))
+                            == MatchingRule::CaseIgnoreMatch =>
+                    {
+                        Ok(DomainRequestFilter::AttributeEqualityIgnoreCase(
+                            name, eq.value,
+                        ))
+                    }
                     UserFieldType::Attribute(name, typ, false) => {
                         let value = deserialize_attribute_value(&[eq.value], typ, false)
                             .context(format!("While deserializing attribute {}", &name))?;
@@ -69,31 +90,43 @@ impl RequestFilter {
                         Err("Equality not supported for list fields".into())
                     }
                     UserFieldType::MemberOf => Ok(DomainRequestFilter::MemberOf(eq.value.into())),
-                    UserFieldType::ObjectClass | UserFieldType::Dn | UserFieldType::EntryDn => {
+                    UserFieldType::ObjectClass
+                    | UserFieldType::Dn
+                    | UserFieldType::EntryDn
+                    | UserFieldType::UserPrincipalName
+                    | UserFieldType::ObjectGuid
+                    | UserFieldType::UserAccountControl
+                    | UserFieldType::AccountLock
+                    | UserFieldType::AccountLockedTime
+                    | UserFieldType::ShadowExpire
+                    | UserFieldType::AccountExpires => {
                         Err("Ldap fields not supported in request filter".into())
                     }
                 }
             }
-            (None, Some(any), None, None, None, None) => Ok(DomainRequestFilter::Or(
+            (None, Some(any), None, None, None, None, None) => Ok(DomainRequestFilter::Or(
                 any.into_iter()
                     .map(|f| f.try_into_domain_filter(schema))
                     .collect::<FieldResult<Vec<_>>>()?,
             )),
-            (None, None, Some(all), None, None, None) => Ok(DomainRequestFilter::And(
+            (None, None, Some(all), None, None, None, None) => Ok(DomainRequestFilter::And(
                 all.into_iter()
                     .map(|f| f.try_into_domain_filter(schema))
                     .collect::<FieldResult<Vec<_>>>()?,
             )),
-            (None, None, None, Some(not), None, None) => Ok(DomainRequestFilter::Not(Box::new(
-                (*not).try_into_domain_filter(schema)?,
-            ))),
-            (None, None, None, None, Some(group), None) => {
+            (None, None, None, Some(not), None, None, None) => Ok(DomainRequestFilter::Not(
+                Box::new((*not).try_into_domain_filter(schema)?),
+            )),
+            (None, None, None, None, Some(group), None, None) => {
                 Ok(DomainRequestFilter::MemberOf(group.into()))
             }
-            (None, None, None, None, None, Some(group_id)) => {
+            (None, None, None, None, None, Some(group_id), None) => {
                 Ok(DomainRequestFilter::MemberOfId(GroupId(group_id)))
             }
-            (None, None, None, None, None, None) => {
+            (None, None, None, None, None, None, Some(substring)) => {
+                substring.try_into_domain_filter(schema)
+            }
+            (None, None, None, None, None, None, None) => {
                 Err("No field specified in request filter".into())
             }
             _ => Err("Multiple fields specified in request filter".into()),
@@ -107,6 +140,62 @@ pub struct EqualityConstraint {
     value: String,
 }
 
+#[derive(PartialEq, Eq, Debug, GraphQLInputObject)]
+/// Matches the values of a string field starting with `initial`, then containing each of `any` in
+/// order, and ending with `final`. The comparison is case-insensitive.
+pub struct SubstringConstraint {
+    field: String,
+    initial: Option<String>,
+    any: Option<Vec<String>>,
+    #[graphql(name = "final")]
+    final_: Option<String>,
+}
+
+impl SubstringConstraint {
+    fn try_into_domain_filter(self, schema: &PublicSchema) -> FieldResult<DomainRequestFilter> {
+        let filter = SubStringFilter {
+            initial: self.initial,
+            any: self.any.unwrap_or_default(),
+            final_: self.final_,
+        };
+        match map_user_field(&self.field.as_str().into(), schema, false) {
+            UserFieldType::NoMatch => {
+                Err(format!("Unknown request filter: {}", &self.field).into())
+            }
+            UserFieldType::PrimaryField(UserColumn::UserId) => {
+                Ok(DomainRequestFilter::UserIdSubString(filter))
+            }
+            UserFieldType::PrimaryField(UserColumn::Email) => Ok(DomainRequestFilter::SubString(
+                UserColumn::LowercaseEmail,
+                filter,
+            )),
+            UserFieldType::Attribute(name, AttributeType::String, _) => {
+                Ok(DomainRequestFilter::AttributeSubString(name, filter))
+            }
+            UserFieldType::PrimaryField(UserColumn::CreationDate | UserColumn::Uuid)
+            | UserFieldType::Attribute(_, _, _) => {
+                Err("Substring filters are only supported for string fields".into())
+            }
+            UserFieldType::PrimaryField(column) => {
+                Ok(DomainRequestFilter::SubString(column, filter))
+            }
+            UserFieldType::MemberOf
+            | UserFieldType::ObjectClass
+            | UserFieldType::Dn
+            | UserFieldType::EntryDn
+            | UserFieldType::UserPrincipalName
+            | UserFieldType::ObjectGuid
+            | UserFieldType::UserAccountControl
+            | UserFieldType::AccountLock
+            | UserFieldType::AccountLockedTime
+            | UserFieldType::ShadowExpire
+            | UserFieldType::AccountExpires => {
+                Err("Ldap fields not supported in request filter".into())
+            }
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Debug)]
 /// The top-level GraphQL query type.
 pub struct Query<Handler: BackendHandler> {
@@ -152,6 +241,7 @@ impl<Handler: BackendHandler> Query<Handler> {
         User::<Handler>::from_user(user, schema)
     }
 
+    /// The people, without the service accounts.
     async fn users(
         context: &Context<Handler>,
         #[graphql(name = "where")] filters: Option<RequestFilter>,
@@ -160,26 +250,19 @@ impl<Handler: BackendHandler> Query<Handler> {
         span.in_scope(|| {
             debug!(?filters);
         });
-        let handler = context
-            .get_readonly_handler()
-            .ok_or_else(field_error_callback(
-                &span,
-                "Unauthorized access to user list",
-            ))?;
-        let schema = Arc::new(self.get_schema(context, span.clone()).await?);
-        let users = handler
-            .list_users(
-                filters
-                    .map(|f| f.try_into_domain_filter(&schema))
-                    .transpose()?,
-                false,
-            )
-            .instrument(span)
-            .await?;
-        users
-            .into_iter()
-            .map(|u| User::<Handler>::from_user_and_groups(u, schema.clone()))
-            .collect()
+        self.list_users_of_kind(context, filters, false, span).await
+    }
+
+    /// The accounts of the integrations, that can only bind over LDAP.
+    async fn service_accounts(
+        context: &Context<Handler>,
+        #[graphql(name = "where")] filters: Option<RequestFilter>,
+    ) -> FieldResult<Vec<User<Handler>>> {
+        let span = debug_span!("[GraphQL query] service_accounts");
+        span.in_scope(|| {
+            debug!(?filters);
+        });
+        self.list_users_of_kind(context, filters, true, span).await
     }
 
     async fn groups(context: &Context<Handler>) -> FieldResult<Vec<Group<Handler>>> {
@@ -217,10 +300,72 @@ impl<Handler: BackendHandler> Query<Handler> {
         Group::<Handler>::from_group_details(group_details, schema.clone())
     }
 
+    /// Lists the users that would be members of a dynamic group with the given LDAP filter.
+    async fn preview_member_filter(
+        context: &Context<Handler>,
+        filter: String,
+    ) -> FieldResult<Vec<User<Handler>>> {
+        let span = debug_span!("[GraphQL query] preview_member_filter");
+        span.in_scope(|| {
+            debug!(?filter);
+        });
+        let handler = context
+            .get_readonly_handler()
+            .ok_or_else(field_error_callback(
+                &span,
+                "Unauthorized access to user list",
+            ))?;
+        let schema = Arc::new(self.get_schema(context, span.clone()).await?);
+        let filter = parse_member_filter(&filter, &schema)?;
+        let users = handler
+            .list_users(Some(filter), false)
+            .instrument(span)
+            .await?;
+        users
+            .into_iter()
+            .map(|u| User::<Handler>::from_user_and_groups(u, schema.clone()))
+            .collect()
+    }
+
     async fn schema(context: &Context<Handler>) -> FieldResult<Schema<Handler>> {
         let span = debug_span!("[GraphQL query] get_schema");
         self.get_schema(context, span).await.map(Into::into)
     }
+
+    async fn sudo_roles(context: &Context<Handler>) -> FieldResult<Vec<SudoRole>> {
+        let span = debug_span!("[GraphQL query] sudo_roles");
+        let handler = context
+            .get_readonly_handler()
+            .ok_or_else(field_error_callback(
+                &span,
+                "Unauthorized access to sudo role list",
+            ))?;
+        Ok(handler
+            .list_sudo_roles()
+            .instrument(span)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    async fn sudo_role(context: &Context<Handler>, id: i32) -> FieldResult<SudoRole> {
+        let span = debug_span!("[GraphQL query] sudo_role");
+        span.in_scope(|| {
+            debug!(?id);
+        });
+        let handler = context
+            .get_readonly_handler()
+            .ok_or_else(field_error_callback(
+                &span,
+                "Unauthorized access to sudo role data",
+            ))?;
+        Ok(handler
+            .get_sudo_role(SudoRoleId(id))
+            .instrument(span)
+            .await?
+            .into())
+    }
 }
 
 impl<Handler: BackendHandler> Query<Handler> {
@@ -238,6 +383,37 @@ impl<Handler: BackendHandler> Query<Handler> {
             .await
             .map(Into::<PublicSchema>::into)?)
     }
+
+    async fn list_users_of_kind(
+        &self,
+        context: &Context<Handler>,
+        filters: Option<RequestFilter>,
+        is_service_account: bool,
+        span: Span,
+    ) -> FieldResult<Vec<User<Handler>>> {
+        let handler = context
+            .get_readonly_handler()
+            .ok_or_else(field_error_callback(
+                &span,
+                "Unauthorized access to user list",
+            ))?;
+        let schema = Arc::new(self.get_schema(context, span.clone()).await?);
+        let kind = DomainRequestFilter::ServiceAccount(is_service_account);
+        let filters = match filters {
+            Some(filters) => {
+                DomainRequestFilter::And(vec![filters.try_into_domain_filter(&schema)?, kind])
+            }
+            None => kind,
+        };
+        let users = handler
+            .list_users(Some(filters), false)
+            .instrument(span)
+            .await?;
+        users
+            .into_iter()
+            .map(|u| User::<Handler>::from_user_and_groups(u, schema.clone()))
+            .collect()
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
@@ -333,11 +509,60 @@ impl<Handler: BackendHandler> User<Handler> {
         self.user.uuid.as_str()
     }
 
+    /// Disabled users cannot log in.
+    fn disabled(&self) -> bool {
+        self.user.disabled
+    }
+
+    /// Past this date, the user cannot log in anymore.
+    fn expiration_date(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.user
+            .expiration_date
+            .map(|d| chrono::Utc.from_utc_datetime(&d))
+    }
+
+    /// The user has to enter a TOTP code or a recovery code when logging in to the web UI.
+    fn mfa_enabled(&self) -> bool {
+        self.user.mfa_enabled
+    }
+
+    /// Service accounts cannot log in to the web UI, they only bind over LDAP.
+    fn is_service_account(&self) -> bool {
+        self.user.is_service_account
+    }
+
+    /// The networks a service account can bind from. Empty if not restricted.
+    fn allowed_sources(&self) -> &[String] {
+        &self.user.allowed_sources
+    }
+
     /// User-defined attributes.
     fn attributes(&self) -> &[AttributeValue<Handler>] {
         &self.attributes
     }
 
+    /// The SSH public keys of the user, from all the attributes holding SSH public keys.
+    fn ssh_public_keys(&self) -> Vec<SshPublicKey> {
+        self.attributes
+            .iter()
+            .flat_map(|a| {
+                a.attribute
+                    .value
+                    .as_ssh_public_keys()
+                    .iter()
+                    .map(|key| SshPublicKey {
+                        attribute_name: a.attribute.name.to_string(),
+                        key: key.clone(),
+                    })
+            })
+            .collect()
+    }
+
+    /// For dynamic groups, the LDAP filter defining the members.
+    fn member_filter(&self) -> Option<String> {
+        self.member_filter.clone()
+    }
+
     /// The groups to which this user belongs.
     async fn groups(&self, context: &Context<Handler>) -> FieldResult<Vec<Group<Handler>>> {
         if let Some(groups) = &self.groups {
@@ -361,6 +586,45 @@ impl<Handler: BackendHandler> User<Handler> {
         groups.sort_by(|g1, g2| g1.display_name.cmp(&g2.display_name));
         Ok(groups)
     }
+
+    /// The passkeys and security keys registered to log in to the web UI.
+    async fn webauthn_credentials(
+        &self,
+        context: &Context<Handler>,
+    ) -> FieldResult<Vec<WebauthnCredential>> {
+        let span = debug_span!("[GraphQL query] user::webauthn_credentials");
+        span.in_scope(|| {
+            debug!(user_id = ?self.user.user_id);
+        });
+        let handler = context
+            .get_readable_handler(&self.user.user_id)
+            .expect("We shouldn't be able to get there without readable permission");
+        Ok(handler
+            .list_webauthn_credentials(&self.user.user_id)
+            .instrument(span)
+            .await?
+            .into_iter()
+            .map(WebauthnCredential)
+            .collect())
+    }
+
+    /// The passwords generated for the applications binding over LDAP.
+    async fn app_passwords(&self, context: &Context<Handler>) -> FieldResult<Vec<AppPassword>> {
+        let span = debug_span!("[GraphQL query] user::app_passwords");
+        span.in_scope(|| {
+            debug!(user_id = ?self.user.user_id);
+        });
+        let handler = context
+            .get_readable_handler(&self.user.user_id)
+            .expect("We shouldn't be able to get there without readable permission");
+        Ok(handler
+            .list_app_passwords(&self.user.user_id)
+            .instrument(span)
+            .await?
+            .into_iter()
+            .map(AppPassword)
+            .collect())
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
@@ -371,6 +635,7 @@ pub struct Group<Handler: BackendHandler> {
     creation_date: chrono::NaiveDateTime,
     uuid: String,
     attributes: Vec<AttributeValue<Handler>>,
+    member_filter: Option<String>,
     schema: Arc<PublicSchema>,
     _phantom: std::marker::PhantomData<Box<Handler>>,
 }
@@ -388,6 +653,7 @@ impl<Handler: BackendHandler> Group<Handler> {
             creation_date: group.creation_date,
             uuid: group.uuid.into_string(),
             attributes,
+            member_filter: group.member_filter,
             schema,
             _phantom: std::marker::PhantomData,
         })
@@ -407,6 +673,7 @@ impl<Handler: BackendHandler> Group<Handler> {
             creation_date: group_details.creation_date,
             uuid: group_details.uuid.into_string(),
             attributes,
+            member_filter: group_details.member_filter,
             schema,
             _phantom: std::marker::PhantomData,
         })
@@ -421,6 +688,7 @@ impl<Handler: BackendHandler> Clone for Group<Handler> {
             creation_date: self.creation_date,
             uuid: self.uuid.clone(),
             attributes: self.attributes.clone(),
+            member_filter: self.member_filter.clone(),
             schema: self.schema.clone(),
             _phantom: std::marker::PhantomData,
         }
@@ -502,6 +770,9 @@ impl<Handler: BackendHandler> AttributeSchema<Handler> {
     fn is_readonly(&self) -> bool {
         self.schema.is_readonly
     }
+    fn matching_rule(&self) -> MatchingRule {
+        self.schema.matching_rule
+    }
 }
 
 impl<Handler: BackendHandler> Clone for AttributeSchema<Handler> {
@@ -530,6 +801,146 @@ pub struct AttributeList<Handler: BackendHandler> {
     _phantom: std::marker::PhantomData<Box<Handler>>,
 }
 
+#[derive(PartialEq, Eq, Debug, Clone)]
+/// A sudo rule, served over LDAP under "ou=sudoers".
+pub struct SudoRole {
+    role: DomainSudoRole,
+}
+
+impl From<DomainSudoRole> for SudoRole {
+    fn from(role: DomainSudoRole) -> Self {
+        Self { role }
+    }
+}
+
+#[graphql_object]
+impl SudoRole {
+    fn id(&self) -> i32 {
+        self.role.id.0
+    }
+    fn name(&self) -> &str {
+        &self.role.name
+    }
+    fn description(&self) -> Option<&str> {
+        self.role.description.as_deref()
+    }
+    /// The users, "%group"s or "+netgroup"s the rule applies to.
+    fn users(&self) -> &[String] {
+        &self.role.users
+    }
+    fn hosts(&self) -> &[String] {
+        &self.role.hosts
+    }
+    fn commands(&self) -> &[String] {
+        &self.role.commands
+    }
+    fn run_as_users(&self) -> &[String] {
+        &self.role.run_as_users
+    }
+    fn run_as_groups(&self) -> &[String] {
+        &self.role.run_as_groups
+    }
+    fn options(&self) -> &[String] {
+        &self.role.options
+    }
+    /// When several rules match, the one with the highest order wins.
+    fn order(&self) -> i32 {
+        self.role
+            .order
+            .clamp(i32::MIN.into(), i32::MAX.into())
+            .try_into()
+            .unwrap_or_default()
+    }
+    fn creation_date(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc.from_utc_datetime(&self.role.creation_date)
+    }
+    fn uuid(&self) -> &str {
+        self.role.uuid.as_str()
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+/// An SSH public key of a user.
+pub struct SshPublicKey {
+    attribute_name: String,
+    key: DomainSshPublicKey,
+}
+
+#[graphql_object]
+impl SshPublicKey {
+    /// The attribute holding the key.
+    fn attribute_name(&self) -> &str {
+        &self.attribute_name
+    }
+    /// The full key, in the authorized_keys format.
+    fn key(&self) -> &str {
+        self.key.as_str()
+    }
+    fn key_type(&self) -> &str {
+        self.key.key_type()
+    }
+    /// The SHA256 fingerprint, as displayed by `ssh-keygen -l`.
+    fn fingerprint(&self) -> String {
+        self.key.fingerprint()
+    }
+    fn comment(&self) -> Option<&str> {
+        self.key.comment()
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+/// A passkey or security key of a user.
+pub struct WebauthnCredential(DomainWebauthnCredential);
+
+#[graphql_object]
+impl WebauthnCredential {
+    fn id(&self) -> i32 {
+        self.0.id.0
+    }
+    /// Chosen by the user when registering it.
+    fn name(&self) -> &str {
+        &self.0.name
+    }
+    fn creation_date(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc.from_utc_datetime(&self.0.creation_date)
+    }
+    fn last_used_date(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.0
+            .last_used_date
+            .map(|d| chrono::Utc.from_utc_datetime(&d))
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+/// A password generated for an application binding over LDAP.
+pub struct AppPassword(DomainAppPassword);
+
+#[graphql_object]
+impl AppPassword {
+    fn id(&self) -> i32 {
+        self.0.id.0
+    }
+    fn name(&self) -> &str {
+        &self.0.name
+    }
+    /// The networks the binds can come from. Empty for anywhere.
+    fn allowed_sources(&self) -> &[String] {
+        &self.0.allowed_sources
+    }
+    /// The password only works while the user is a member of this group.
+    fn required_group_id(&self) -> Option<i32> {
+        self.0.required_group.map(|id| id.0)
+    }
+    fn creation_date(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc.from_utc_datetime(&self.0.creation_date)
+    }
+    fn last_used_date(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.0
+            .last_used_date
+            .map(|d| chrono::Utc.from_utc_datetime(&d))
+    }
+}
+
 #[derive(Clone)]
 pub struct ObjectClassInfo {
     object_class: String,
@@ -691,6 +1102,10 @@ pub fn serialize_attribute_to_graphql(attribute_value: &DomainAttributeValue) ->
         DomainAttributeValue::JpegPhoto(Cardinality::Unbounded(l)) => {
             l.iter().map(String::from).collect()
         }
+        DomainAttributeValue::SshPublicKey(Cardinality::Singleton(k)) => vec![k.to_string()],
+        DomainAttributeValue::SshPublicKey(Cardinality::Unbounded(l)) => {
+            l.iter().map(ToString::to_string).collect()
+        }
     }
 }
 
@@ -839,7 +1254,7 @@ mod tests {
     use lldap_auth::access_control::{Permission, ValidationResults};
     use lldap_domain::{
         schema::{AttributeList, Schema},
-        types::{AttributeName, AttributeType, LdapObjectClass},
+        types::{AttributeName, AttributeType, LdapObjectClass, MatchingRule},
     };
     use lldap_test_utils::{MockTestBackendHandler, setup_default_schema};
     use mockall::predicate::eq;
@@ -897,6 +1312,7 @@ mod tests {
                             is_editable: true,
                             is_hardcoded: true,
                             is_readonly: false,
+                            matching_rule: MatchingRule::CaseIgnoreMatch,
                         },
                         DomainAttributeSchema {
                             name: "last_name".into(),
@@ -906,6 +1322,7 @@ mod tests {
                             is_editable: true,
                             is_hardcoded: true,
                             is_readonly: false,
+                            matching_rule: MatchingRule::CaseIgnoreMatch,
                         },
                     ],
                 },
@@ -918,6 +1335,7 @@ mod tests {
                         is_editable: true,
                         is_hardcoded: false,
                         is_readonly: false,
+                        matching_rule: MatchingRule::CaseIgnoreMatch,
                     }],
                 },
                 extra_user_object_classes: vec![
@@ -958,6 +1376,7 @@ mod tests {
                 name: "club_name".into(),
                 value: "Gang of Four".to_string().into(),
             }],
+            member_filter: None,
         });
         groups.insert(GroupDetails {
             group_id: GroupId(7),
@@ -965,6 +1384,7 @@ mod tests {
             creation_date: chrono::Utc.timestamp_nanos(12).naive_utc(),
             uuid: lldap_domain::uuid!("b1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d8"),
             attributes: Vec::new(),
+            member_filter: None,
         });
         mock.expect_get_user_groups()
             .with(eq(UserId::new("bob")))
@@ -1067,6 +1487,83 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn get_user_ssh_public_keys() {
+        const QUERY: &str = r#"{
+          user(userId: "bob") {
+            sshPublicKeys {
+              attributeName
+              keyType
+              fingerprint
+              comment
+            }
+          }
+        }"#;
+
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_get_schema().returning(|| {
+            Ok(Schema {
+                user_attributes: DomainAttributeList {
+                    attributes: vec![DomainAttributeSchema {
+                        name: "sshPublicKey".into(),
+                        attribute_type: AttributeType::SshPublicKey,
+                        is_list: true,
+                        is_visible: true,
+                        is_editable: true,
+                        is_hardcoded: false,
+                        is_readonly: false,
+                        matching_rule: MatchingRule::OctetStringMatch,
+                    }],
+                },
+                group_attributes: DomainAttributeList {
+                    attributes: Vec::new(),
+                },
+                extra_user_object_classes: Vec::new(),
+                extra_group_object_classes: Vec::new(),
+            })
+        });
+        mock.expect_get_user_details()
+            .with(eq(UserId::new("bob")))
+            .return_once(|_| {
+                Ok(DomainUser {
+                    user_id: UserId::new("bob"),
+                    attributes: vec![DomainAttribute {
+                        name: "sshPublicKey".into(),
+                        value: vec![
+                            DomainSshPublicKey::try_from(
+                                "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAABAgMEBQYHCAkKCwwNDg8QERITFBUWFxgZGhscHR4f bob@laptop",
+                            )
+                            .unwrap(),
+                        ]
+                        .into(),
+                    }],
+                    ..Default::default()
+                })
+            });
+
+        let context =
+            Context::<MockTestBackendHandler>::new_for_tests(mock, ValidationResults::admin());
+
+        let schema = schema(Query::<MockTestBackendHandler>::new());
+        assert_eq!(
+            execute(QUERY, None, &schema, &Variables::new(), &context).await,
+            Ok((
+                graphql_value!(
+                {
+                    "user": {
+                        "sshPublicKeys": [{
+                            "attributeName": "sshPublicKey",
+                            "keyType": "ssh-ed25519",
+                            "fingerprint": "SHA256:ZkAslGjFiUHdGf/WUL8rQvkib4PTvQatUV0OUQSncCA",
+                            "comment": "bob@laptop",
+                        }]
+                    }
+                }),
+                vec![]
+            ))
+        );
+    }
+
     #[tokio::test]
     async fn list_users() {
         const QUERY: &str = r#"{
@@ -1094,16 +1591,19 @@ mod tests {
         setup_default_schema(&mut mock);
         mock.expect_list_users()
             .with(
-                eq(Some(DomainRequestFilter::Or(vec![
-                    DomainRequestFilter::UserId(UserId::new("bob")),
-                    DomainRequestFilter::Equality(
-                        UserColumn::Email,
-                        "robert@bobbers.on".to_owned(),
-                    ),
-                    DomainRequestFilter::AttributeEquality(
-                        AttributeName::from("first_name"),
-                        "robert".to_string().into(),
-                    ),
+                eq(Some(DomainRequestFilter::And(vec![
+                    DomainRequestFilter::Or(vec![
+                        DomainRequestFilter::UserId(UserId::new("bob")),
+                        DomainRequestFilter::Equality(
+                            UserColumn::Email,
+                            "robert@bobbers.on".to_owned(),
+                        ),
+                        DomainRequestFilter::AttributeEqualityIgnoreCase(
+                            AttributeName::from("first_name"),
+                            "robert".to_string(),
+                        ),
+                    ]),
+                    DomainRequestFilter::ServiceAccount(false),
                 ]))),
                 eq(false),
             )
@@ -1153,6 +1653,119 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn list_users_substring() {
+        const QUERY: &str = r#"{
+          users(filters: {
+            all: [
+              {substring: {
+                field: "email"
+                final: "@BOBBERS.ON"
+              }},
+              {substring: {
+                field: "firstName"
+                initial: "rob"
+                any: ["e"]
+              }}
+            ]}) {
+            id
+          }
+        }"#;
+
+        let mut mock = MockTestBackendHandler::new();
+        setup_default_schema(&mut mock);
+        mock.expect_list_users()
+            .with(
+                eq(Some(DomainRequestFilter::And(vec![
+                    DomainRequestFilter::And(vec![
+                        DomainRequestFilter::SubString(
+                            UserColumn::LowercaseEmail,
+                            SubStringFilter {
+                                initial: None,
+                                any: Vec::new(),
+                                final_: Some("@BOBBERS.ON".to_owned()),
+                            },
+                        ),
+                        DomainRequestFilter::AttributeSubString(
+                            AttributeName::from("first_name"),
+                            SubStringFilter {
+                                initial: Some("rob".to_owned()),
+                                any: vec!["e".to_owned()],
+                                final_: None,
+                            },
+                        ),
+                    ]),
+                    DomainRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(false),
+            )
+            .return_once(|_, _| {
+                Ok(vec![DomainUserAndGroups {
+                    user: DomainUser {
+                        user_id: UserId::new("robert"),
+                        email: "robert@bobbers.on".into(),
+                        ..Default::default()
+                    },
+                    groups: None,
+                }])
+            });
+
+        let context =
+            Context::<MockTestBackendHandler>::new_for_tests(mock, ValidationResults::admin());
+
+        let schema = schema(Query::<MockTestBackendHandler>::new());
+        assert_eq!(
+            execute(QUERY, None, &schema, &Variables::new(), &context).await,
+            Ok((graphql_value!({"users": [{"id": "robert"}]}), vec![]))
+        );
+    }
+
+    #[tokio::test]
+    async fn list_service_accounts() {
+        const QUERY: &str = r#"{
+          serviceAccounts {
+            id
+            isServiceAccount
+            allowedSources
+          }
+        }"#;
+
+        let mut mock = MockTestBackendHandler::new();
+        setup_default_schema(&mut mock);
+        mock.expect_list_users()
+            .with(
+                eq(Some(DomainRequestFilter::ServiceAccount(true))),
+                eq(false),
+            )
+            .return_once(|_, _| {
+                Ok(vec![DomainUserAndGroups {
+                    user: DomainUser {
+                        user_id: UserId::new("backup"),
+                        is_service_account: true,
+                        allowed_sources: vec!["10.0.0.0/8".to_owned()],
+                        ..Default::default()
+                    },
+                    groups: None,
+                }])
+            });
+
+        let context =
+            Context::<MockTestBackendHandler>::new_for_tests(mock, ValidationResults::admin());
+
+        let schema = schema(Query::<MockTestBackendHandler>::new());
+        assert_eq!(
+            execute(QUERY, None, &schema, &Variables::new(), &context).await,
+            Ok((
+                graphql_value!({"serviceAccounts": [{
+                    "id": "backup",
+                    "isServiceAccount": true,
+                    "allowedSources": ["10.0.0.0/8"],
+                }]}),
+                vec![]
+            ))
+        );
+    }
+
     #[tokio::test]
     async fn get_schema() {
         const QUERY: &str = r#"{
@@ -1335,6 +1948,7 @@ mod tests {
                         is_editable: true,
                         is_hardcoded: true,
                         is_readonly: false,
+                        matching_rule: MatchingRule::OctetStringMatch,
                     }],
                 },
                 group_attributes: AttributeList {
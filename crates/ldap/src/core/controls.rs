@@ -0,0 +1,443 @@
+use crate::core::error::{LdapError, LdapResult};
+use ldap3_proto::{control::LdapControl, proto::LdapResultCode};
+
+/// Tree Delete control: delete an entry along with all its subordinates.
+pub const TREE_DELETE_OID: &str = "1.2.840.113556.1.4.805";
+/// Server side sorting, RFC 2891.
+pub const SORT_REQUEST_OID: &str = "1.2.840.113556.1.4.473";
+pub const SORT_RESPONSE_OID: &str = "1.2.840.113556.1.4.474";
+/// Virtual List View, draft-ietf-ldapext-ldapv3-vlv.
+pub const VLV_REQUEST_OID: &str = "2.16.840.1.113730.3.4.9";
+pub const VLV_RESPONSE_OID: &str = "2.16.840.1.113730.3.4.10";
+/// Password policy, draft-behera-ldap-password-policy. The same OID is used for the request and
+/// the response.
+pub const PASSWORD_POLICY_OID: &str = "1.3.6.1.4.1.42.2.27.8.5.1";
+
+/// Controls advertised in the root DSE.
+pub const SUPPORTED_CONTROLS: &[&str] = &[
+    TREE_DELETE_OID,
+    SORT_REQUEST_OID,
+    VLV_REQUEST_OID,
+    PASSWORD_POLICY_OID,
+];
+
+/// Result codes used in the sort and VLV response controls.
+pub const CONTROL_RESULT_SUCCESS: u8 = 0;
+pub const CONTROL_RESULT_UNWILLING_TO_PERFORM: u8 = 53;
+pub const CONTROL_RESULT_SORT_CONTROL_MISSING: u8 = 60;
+
+/// Errors reported in the password policy response control.
+pub const PASSWORD_POLICY_ERROR_PASSWORD_EXPIRED: u8 = 0;
+pub const PASSWORD_POLICY_ERROR_CHANGE_AFTER_RESET: u8 = 2;
+
+pub fn find_control<'a>(controls: &'a [LdapControl], control_oid: &str) -> Option<&'a LdapControl> {
+    controls.iter().find(|c| match c {
+        LdapControl::Unknown { oid, .. } => oid == control_oid,
+        _ => false,
+    })
+}
+
+pub fn has_control(controls: &[LdapControl], control_oid: &str) -> bool {
+    find_control(controls, control_oid).is_some()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub attribute: String,
+    pub reverse: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortRequest {
+    pub keys: Vec<SortKey>,
+    pub critical: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VlvTarget {
+    ByOffset { offset: u64, content_count: u64 },
+    GreaterThanOrEqual(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VlvRequest {
+    pub before_count: u64,
+    pub after_count: u64,
+    pub target: VlvTarget,
+    pub context_id: Option<Vec<u8>>,
+    pub critical: bool,
+}
+
+// Minimal BER support for the control values, which ldap3_proto leaves opaque.
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_ENUMERATED: u8 = 0x0a;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_CONTEXT_0: u8 = 0x80;
+const TAG_CONTEXT_1: u8 = 0x81;
+const TAG_CONTEXT_CONSTRUCTED_0: u8 = 0xa0;
+
+fn make_decoding_error(control: &str) -> LdapError {
+    LdapError {
+        code: LdapResultCode::ProtocolError,
+        message: format!("Invalid value for the {control} control"),
+    }
+}
+
+/// Reads one BER element, returning its tag, its value and the remaining input.
+fn read_element(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, input) = input.split_first()?;
+    let (&first_length_byte, mut input) = input.split_first()?;
+    let length = if first_length_byte & 0x80 == 0 {
+        usize::from(first_length_byte)
+    } else {
+        let num_bytes = usize::from(first_length_byte & 0x7f);
+        if num_bytes == 0 || num_bytes > 4 || input.len() < num_bytes {
+            return None;
+        }
+        let (length_bytes, rest) = input.split_at(num_bytes);
+        input = rest;
+        length_bytes
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | usize::from(*b))
+    };
+    if input.len() < length {
+        return None;
+    }
+    let (value, rest) = input.split_at(length);
+    Some((tag, value, rest))
+}
+
+fn read_elements(mut input: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut elements = Vec::new();
+    while !input.is_empty() {
+        let (tag, value, rest) = read_element(input)?;
+        elements.push((tag, value));
+        input = rest;
+    }
+    Some(elements)
+}
+
+fn read_unsigned(value: &[u8]) -> Option<u64> {
+    if value.is_empty() || value.len() > 8 || value[0] & 0x80 != 0 {
+        return None;
+    }
+    Some(value.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b)))
+}
+
+fn write_element(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut result = vec![tag];
+    if value.len() < 0x80 {
+        result.push(value.len() as u8);
+    } else {
+        let length_bytes: Vec<u8> = value
+            .len()
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|b| *b == 0)
+            .collect();
+        result.push(0x80 | length_bytes.len() as u8);
+        result.extend(length_bytes);
+    }
+    result.extend_from_slice(value);
+    result
+}
+
+fn write_unsigned(tag: u8, value: u64) -> Vec<u8> {
+    let mut bytes: Vec<u8> = value
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|b| *b == 0)
+        .collect();
+    if bytes.first().is_none_or(|b| b & 0x80 != 0) {
+        bytes.insert(0, 0);
+    }
+    write_element(tag, &bytes)
+}
+
+fn get_control_value<'a>(controls: &'a [LdapControl], oid: &str) -> Option<(bool, &'a [u8])> {
+    match find_control(controls, oid)? {
+        LdapControl::Unknown {
+            criticality, value, ..
+        } => Some((*criticality, value.as_deref().unwrap_or_default())),
+        _ => None,
+    }
+}
+
+/// Parses the server side sort request control, if present.
+pub fn parse_sort_request(controls: &[LdapControl]) -> LdapResult<Option<SortRequest>> {
+    let Some((critical, value)) = get_control_value(controls, SORT_REQUEST_OID) else {
+        return Ok(None);
+    };
+    let parse = || -> Option<Vec<SortKey>> {
+        let (tag, keys, rest) = read_element(value)?;
+        if tag != TAG_SEQUENCE || !rest.is_empty() {
+            return None;
+        }
+        read_elements(keys)?
+            .into_iter()
+            .map(|(tag, key)| {
+                if tag != TAG_SEQUENCE {
+                    return None;
+                }
+                let fields = read_elements(key)?;
+                let (&(TAG_OCTET_STRING, attribute), options) = fields.split_first()? else {
+                    return None;
+                };
+                let mut reverse = false;
+                for (tag, value) in options {
+                    match *tag {
+                        // The ordering rule is ignored: we only support the default one.
+                        TAG_CONTEXT_0 => {}
+                        TAG_CONTEXT_1 => reverse = value.first().is_some_and(|b| *b != 0),
+                        _ => return None,
+                    }
+                }
+                Some(SortKey {
+                    attribute: String::from_utf8(attribute.to_vec()).ok()?,
+                    reverse,
+                })
+            })
+            .collect()
+    };
+    let keys = parse()
+        .filter(|keys| !keys.is_empty())
+        .ok_or_else(|| make_decoding_error("sort"))?;
+    Ok(Some(SortRequest { keys, critical }))
+}
+
+/// Parses the virtual list view request control, if present.
+pub fn parse_vlv_request(controls: &[LdapControl]) -> LdapResult<Option<VlvRequest>> {
+    let Some((critical, value)) = get_control_value(controls, VLV_REQUEST_OID) else {
+        return Ok(None);
+    };
+    let parse = || -> Option<VlvRequest> {
+        let (tag, fields, rest) = read_element(value)?;
+        if tag != TAG_SEQUENCE || !rest.is_empty() {
+            return None;
+        }
+        let fields = read_elements(fields)?;
+        let [
+            (TAG_INTEGER, before_count),
+            (TAG_INTEGER, after_count),
+            (target_tag, target),
+            context_id @ ..,
+        ] = fields.as_slice()
+        else {
+            return None;
+        };
+        let target = match *target_tag {
+            TAG_CONTEXT_CONSTRUCTED_0 => match read_elements(target)?.as_slice() {
+                [(TAG_INTEGER, offset), (TAG_INTEGER, content_count)] => VlvTarget::ByOffset {
+                    offset: read_unsigned(offset)?,
+                    content_count: read_unsigned(content_count)?,
+                },
+                _ => return None,
+            },
+            TAG_CONTEXT_1 => {
+                VlvTarget::GreaterThanOrEqual(String::from_utf8(target.to_vec()).ok()?)
+            }
+            _ => return None,
+        };
+        let context_id = match context_id {
+            [] => None,
+            [(TAG_OCTET_STRING, context_id)] => Some(context_id.to_vec()),
+            _ => return None,
+        };
+        Some(VlvRequest {
+            before_count: read_unsigned(before_count)?,
+            after_count: read_unsigned(after_count)?,
+            target,
+            context_id,
+            critical,
+        })
+    };
+    parse()
+        .map(Some)
+        .ok_or_else(|| make_decoding_error("virtual list view"))
+}
+
+/// Builds the server side sort response control. `attribute` is the sort key that caused an
+/// error, if any.
+pub fn make_sort_response(result: u8, attribute: Option<&str>) -> LdapControl {
+    let mut fields = write_unsigned(TAG_ENUMERATED, result.into());
+    if let Some(attribute) = attribute {
+        fields.extend(write_element(TAG_CONTEXT_0, attribute.as_bytes()));
+    }
+    LdapControl::Unknown {
+        oid: SORT_RESPONSE_OID.to_string(),
+        criticality: false,
+        value: Some(write_element(TAG_SEQUENCE, &fields)),
+    }
+}
+
+/// Builds the virtual list view response control.
+pub fn make_vlv_response(
+    target_position: u64,
+    content_count: u64,
+    result: u8,
+    context_id: Option<&[u8]>,
+) -> LdapControl {
+    let mut fields = write_unsigned(TAG_INTEGER, target_position);
+    fields.extend(write_unsigned(TAG_INTEGER, content_count));
+    fields.extend(write_unsigned(TAG_ENUMERATED, result.into()));
+    if let Some(context_id) = context_id {
+        fields.extend(write_element(TAG_OCTET_STRING, context_id));
+    }
+    LdapControl::Unknown {
+        oid: VLV_RESPONSE_OID.to_string(),
+        criticality: false,
+        value: Some(write_element(TAG_SEQUENCE, &fields)),
+    }
+}
+
+/// Builds the password policy response control, with an error and no warning.
+pub fn make_password_policy_response(error: u8) -> LdapControl {
+    LdapControl::Unknown {
+        oid: PASSWORD_POLICY_OID.to_string(),
+        criticality: false,
+        value: Some(write_element(
+            TAG_SEQUENCE,
+            &write_unsigned(TAG_CONTEXT_1, error.into()),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn make_control(oid: &str, value: Vec<u8>) -> LdapControl {
+        LdapControl::Unknown {
+            oid: oid.to_string(),
+            criticality: true,
+            value: Some(value),
+        }
+    }
+
+    #[test]
+    fn test_parse_sort_request() {
+        // SEQUENCE { SEQUENCE { "cn" }, SEQUENCE { "mail", [0] "2.5.13.3", [1] TRUE } }
+        let value = vec![
+            0x30, 0x1b, 0x30, 0x04, 0x04, 0x02, b'c', b'n', 0x30, 0x13, 0x04, 0x04, b'm', b'a',
+            b'i', b'l', 0x80, 0x08, b'2', b'.', b'5', b'.', b'1', b'3', b'.', b'3', 0x81, 0x01,
+            0xff,
+        ];
+        assert_eq!(
+            parse_sort_request(&[make_control(SORT_REQUEST_OID, value)]),
+            Ok(Some(SortRequest {
+                keys: vec![
+                    SortKey {
+                        attribute: "cn".to_string(),
+                        reverse: false,
+                    },
+                    SortKey {
+                        attribute: "mail".to_string(),
+                        reverse: true,
+                    },
+                ],
+                critical: true,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_sort_request_invalid() {
+        assert_eq!(
+            parse_sort_request(&[make_control(SORT_REQUEST_OID, vec![0x30, 0x05, 0x04])]),
+            Err(make_decoding_error("sort"))
+        );
+        assert_eq!(parse_sort_request(&[]), Ok(None));
+    }
+
+    #[test]
+    fn test_parse_vlv_request_by_offset() {
+        // SEQUENCE { 2, 10, [0] { 300, 0 } }
+        let value = vec![
+            0x30, 0x0f, 0x02, 0x01, 0x02, 0x02, 0x01, 0x0a, 0xa0, 0x07, 0x02, 0x02, 0x01, 0x2c,
+            0x02, 0x01, 0x00,
+        ];
+        assert_eq!(
+            parse_vlv_request(&[make_control(VLV_REQUEST_OID, value)]),
+            Ok(Some(VlvRequest {
+                before_count: 2,
+                after_count: 10,
+                target: VlvTarget::ByOffset {
+                    offset: 300,
+                    content_count: 0,
+                },
+                context_id: None,
+                critical: true,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_vlv_request_assertion_value() {
+        // SEQUENCE { 0, 5, [1] "Jo", "ctx" }
+        let value = vec![
+            0x30, 0x0f, 0x02, 0x01, 0x00, 0x02, 0x01, 0x05, 0x81, 0x02, b'J', b'o', 0x04, 0x03,
+            b'c', b't', b'x',
+        ];
+        assert_eq!(
+            parse_vlv_request(&[make_control(VLV_REQUEST_OID, value)]),
+            Ok(Some(VlvRequest {
+                before_count: 0,
+                after_count: 5,
+                target: VlvTarget::GreaterThanOrEqual("Jo".to_string()),
+                context_id: Some(b"ctx".to_vec()),
+                critical: true,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_make_vlv_response() {
+        assert_eq!(
+            make_vlv_response(1, 200, 0, None),
+            LdapControl::Unknown {
+                oid: VLV_RESPONSE_OID.to_string(),
+                criticality: false,
+                value: Some(vec![
+                    0x30, 0x0a, 0x02, 0x01, 0x01, 0x02, 0x02, 0x00, 0xc8, 0x0a, 0x01, 0x00
+                ]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_make_sort_response() {
+        assert_eq!(
+            make_sort_response(53, Some("sn")),
+            LdapControl::Unknown {
+                oid: SORT_RESPONSE_OID.to_string(),
+                criticality: false,
+                value: Some(vec![0x30, 0x07, 0x0a, 0x01, 0x35, 0x80, 0x02, b's', b'n']),
+            }
+        );
+    }
+
+    #[test]
+    fn test_make_password_policy_response() {
+        assert_eq!(
+            make_password_policy_response(PASSWORD_POLICY_ERROR_CHANGE_AFTER_RESET),
+            LdapControl::Unknown {
+                oid: PASSWORD_POLICY_OID.to_string(),
+                criticality: false,
+                value: Some(vec![0x30, 0x03, 0x81, 0x01, 0x02]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_long_form_length() {
+        let value = vec![b'a'; 200];
+        let element = write_element(TAG_OCTET_STRING, &value);
+        assert_eq!(&element[..3], &[TAG_OCTET_STRING, 0x81, 200]);
+        assert_eq!(
+            read_element(&element),
+            Some((TAG_OCTET_STRING, value.as_slice(), [].as_slice()))
+        );
+    }
+}
@@ -0,0 +1,484 @@
+//! Parsing and formatting of distinguished names, following RFC 4514.
+
+use itertools::join;
+use std::fmt;
+
+/// The attribute types that can appear in a DN under another name: their numeric OID and long
+/// name, and the short name we normalize them to.
+const ATTRIBUTE_TYPE_ALIASES: &[(&str, &str, &str)] = &[
+    ("2.5.4.3", "commonname", "cn"),
+    ("2.5.4.6", "countryname", "c"),
+    ("2.5.4.7", "localityname", "l"),
+    ("2.5.4.8", "stateorprovincename", "st"),
+    ("2.5.4.9", "streetaddress", "street"),
+    ("2.5.4.10", "organizationname", "o"),
+    ("2.5.4.11", "organizationalunitname", "ou"),
+    ("0.9.2342.19200300.100.1.1", "userid", "uid"),
+    ("0.9.2342.19200300.100.1.25", "domaincomponent", "dc"),
+];
+
+/// A relative distinguished name: one or more attribute type and value pairs, joined with "+" in
+/// the string form.
+///
+/// The attribute types are lowercase short names (or numeric OIDs if there is no short name), and
+/// the pairs are kept sorted so that the comparison doesn't depend on their order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rdn(Vec<(String, String)>);
+
+impl Rdn {
+    pub fn new(attribute_type: &str, value: &str) -> Self {
+        Self(vec![(
+            normalize_attribute_type(attribute_type),
+            value.to_string(),
+        )])
+    }
+
+    fn from_pairs(mut pairs: Vec<(String, String)>) -> Self {
+        pairs.sort();
+        Self(pairs)
+    }
+
+    pub fn pairs(&self) -> &[(String, String)] {
+        &self.0
+    }
+
+    /// The attribute type and value, if the RDN is single-valued.
+    pub fn single(&self) -> Option<(&str, &str)> {
+        match self.0.as_slice() {
+            [(attribute_type, value)] => Some((attribute_type, value)),
+            _ => None,
+        }
+    }
+
+    pub fn to_ascii_lowercase(&self) -> Self {
+        Self::from_pairs(
+            self.0
+                .iter()
+                .map(|(t, v)| (t.clone(), v.to_ascii_lowercase()))
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for Rdn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&join(
+            self.0
+                .iter()
+                .map(|(t, v)| format!("{t}={}", escape_dn_value(v))),
+            "+",
+        ))
+    }
+}
+
+/// Formats a DN from its RDNs, escaping the values.
+pub fn format_distinguished_name(rdns: &[Rdn]) -> String {
+    join(rdns, ",")
+}
+
+/// Escapes an attribute value to be used in a DN, as per RFC 4514 section 2.4.
+pub fn escape_dn_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    let last = value.chars().count().saturating_sub(1);
+    for (i, c) in value.chars().enumerate() {
+        match c {
+            '"' | '+' | ',' | ';' | '<' | '>' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '#' if i == 0 => escaped.push_str("\\#"),
+            ' ' if i == 0 || i == last => escaped.push_str("\\ "),
+            '\0' => escaped.push_str("\\00"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn normalize_attribute_type(attribute_type: &str) -> String {
+    let lowercase = attribute_type.to_ascii_lowercase();
+    let lowercase = lowercase
+        .strip_prefix("oid.")
+        .filter(|oid| oid.starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or(&lowercase);
+    ATTRIBUTE_TYPE_ALIASES
+        .iter()
+        .find(|(oid, long_name, _)| *oid == lowercase || *long_name == lowercase)
+        .map(|(_, _, short_name)| short_name.to_string())
+        .unwrap_or_else(|| lowercase.to_string())
+}
+
+/// Parses the string representation of a DN, as per RFC 4514 section 3.
+///
+/// The values are unescaped, and their case is preserved. Whitespace around the separators is
+/// ignored, unless escaped.
+pub fn parse_dn(dn: &str) -> Result<Vec<Rdn>, String> {
+    if dn.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut parser = DnParser {
+        input: dn.as_bytes(),
+        pos: 0,
+    };
+    let mut rdns = Vec::new();
+    loop {
+        let mut pairs = Vec::new();
+        loop {
+            let attribute_type = parser.parse_attribute_type()?;
+            parser.skip_spaces();
+            if parser.next_byte() != Some(b'=') {
+                return Err(format!(
+                    r#"Missing "=" after attribute type "{attribute_type}""#
+                ));
+            }
+            parser.skip_spaces();
+            pairs.push((attribute_type, parser.parse_value()?));
+            match parser.peek() {
+                Some(b'+') => parser.pos += 1,
+                _ => break,
+            }
+        }
+        rdns.push(Rdn::from_pairs(pairs));
+        match parser.next_byte() {
+            None => return Ok(rdns),
+            Some(b',') => {}
+            Some(c) => return Err(format!(r#"Unexpected character "{}""#, c as char)),
+        }
+    }
+}
+
+struct DnParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl DnParser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(b' ') {
+            self.pos += 1;
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(u8) -> bool) -> &[u8] {
+        let start = self.pos;
+        while self.peek().is_some_and(&predicate) {
+            self.pos += 1;
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn parse_hex_pair(&mut self) -> Option<u8> {
+        let pair = self.input.get(self.pos..self.pos + 2)?;
+        if !pair.iter().all(u8::is_ascii_hexdigit) {
+            return None;
+        }
+        let byte = u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap();
+        self.pos += 2;
+        Some(byte)
+    }
+
+    /// attributeType = descr / numericoid
+    fn parse_attribute_type(&mut self) -> Result<String, String> {
+        self.skip_spaces();
+        let attribute_type =
+            self.take_while(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'.');
+        let attribute_type = std::str::from_utf8(attribute_type).unwrap();
+        let normalized = normalize_attribute_type(attribute_type);
+        let is_valid = if normalized.starts_with(|c: char| c.is_ascii_digit()) {
+            normalized.split('.').all(|number| {
+                !number.is_empty()
+                    && number.bytes().all(|c| c.is_ascii_digit())
+                    && (number == "0" || !number.starts_with('0'))
+            })
+        } else {
+            normalized.starts_with(|c: char| c.is_ascii_alphabetic()) && !normalized.contains('.')
+        };
+        if is_valid {
+            Ok(normalized)
+        } else if attribute_type.is_empty() {
+            Err("Empty attribute type".to_string())
+        } else {
+            Err(format!(r#"Invalid attribute type "{attribute_type}""#))
+        }
+    }
+
+    /// attributeValue = string / hexstring
+    fn parse_value(&mut self) -> Result<String, String> {
+        if self.peek() == Some(b'#') {
+            self.pos += 1;
+            let mut ber = Vec::new();
+            while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                ber.push(
+                    self.parse_hex_pair()
+                        .ok_or_else(|| "Odd number of hex digits in value".to_string())?,
+                );
+            }
+            self.skip_spaces();
+            return decode_ber_string(&ber);
+        }
+        let mut value = Vec::new();
+        // The length of the value without the unescaped trailing spaces.
+        let mut len = 0;
+        while let Some(c) = self.peek() {
+            match c {
+                b',' | b'+' => break,
+                b'\\' => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(
+                            c @ (b' ' | b'"' | b'#' | b'+' | b',' | b';' | b'<' | b'=' | b'>'
+                            | b'\\'),
+                        ) => {
+                            self.pos += 1;
+                            value.push(c);
+                        }
+                        _ => value.push(
+                            self.parse_hex_pair()
+                                .ok_or_else(|| "Invalid escape sequence in value".to_string())?,
+                        ),
+                    }
+                    len = value.len();
+                }
+                b'"' | b';' | b'<' | b'>' | b'\0' => {
+                    return Err(format!(
+                        r#"Character "{}" must be escaped in value"#,
+                        (c as char).escape_default()
+                    ));
+                }
+                _ => {
+                    self.pos += 1;
+                    value.push(c);
+                    if c != b' ' {
+                        len = value.len();
+                    }
+                }
+            }
+        }
+        value.truncate(len);
+        String::from_utf8(value).map_err(|_| "Value is not valid UTF-8".to_string())
+    }
+}
+
+/// Decodes the BER encoding of a string value, as found in the "#hexstring" form of values.
+fn decode_ber_string(ber: &[u8]) -> Result<String, String> {
+    let invalid = || "Invalid BER-encoded value".to_string();
+    let (&tag, rest) = ber.split_first().ok_or_else(invalid)?;
+    // OCTET STRING, UTF8String, PrintableString, IA5String.
+    if ![0x04, 0x0c, 0x13, 0x16].contains(&tag) {
+        return Err(format!("Unsupported BER value tag {tag:#04x}"));
+    }
+    let (&length, rest) = rest.split_first().ok_or_else(invalid)?;
+    let (length, content) = if length < 0x80 {
+        (length as usize, rest)
+    } else {
+        let num_bytes = (length & 0x7f) as usize;
+        if num_bytes == 0 || num_bytes > 4 || rest.len() < num_bytes {
+            return Err(invalid());
+        }
+        let (length_bytes, content) = rest.split_at(num_bytes);
+        (
+            length_bytes
+                .iter()
+                .fold(0, |acc, &b| (acc << 8) | b as usize),
+            content,
+        )
+    };
+    if content.len() != length {
+        return Err(invalid());
+    }
+    String::from_utf8(content.to_vec()).map_err(|_| "Value is not valid UTF-8".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn rdn(pairs: &[(&str, &str)]) -> Rdn {
+        Rdn::from_pairs(
+            pairs
+                .iter()
+                .map(|(t, v)| (t.to_string(), v.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_parse_dn() {
+        let cases: &[(&str, Vec<Rdn>)] = &[
+            ("", vec![]),
+            (
+                "uid=bob,ou=people,dc=example,dc=com",
+                vec![
+                    rdn(&[("uid", "bob")]),
+                    rdn(&[("ou", "people")]),
+                    rdn(&[("dc", "example")]),
+                    rdn(&[("dc", "com")]),
+                ],
+            ),
+            (
+                " UID = bob , OU=people",
+                vec![rdn(&[("uid", "bob")]), rdn(&[("ou", "people")])],
+            ),
+            (
+                r"cn=Doe\, John,ou=people",
+                vec![rdn(&[("cn", "Doe, John")]), rdn(&[("ou", "people")])],
+            ),
+            (
+                r#"cn=a\+b\;c\<d\>e\=f\\g\",dc=com"#,
+                vec![rdn(&[("cn", r#"a+b;c<d>e=f\g""#)]), rdn(&[("dc", "com")])],
+            ),
+            (r"cn=\ spaces\ ", vec![rdn(&[("cn", " spaces ")])]),
+            (r"cn=\#hash", vec![rdn(&[("cn", "#hash")])]),
+            (r"cn=trailing   ", vec![rdn(&[("cn", "trailing")])]),
+            (r"cn=Lu\C4\8Di\C4\87", vec![rdn(&[("cn", "Lučić")])]),
+            (r"cn=Lučić", vec![rdn(&[("cn", "Lučić")])]),
+            (r"cn=null\00byte", vec![rdn(&[("cn", "null\0byte")])]),
+            ("cn=", vec![rdn(&[("cn", "")])]),
+            ("cn=a=b", vec![rdn(&[("cn", "a=b")])]),
+            ("cn=#0C03626F62", vec![rdn(&[("cn", "bob")])]),
+            (
+                "cn=#04026869 ,dc=com",
+                vec![rdn(&[("cn", "hi")]), rdn(&[("dc", "com")])],
+            ),
+            (
+                "cn=bob+mail=bob@example.com,dc=com",
+                vec![
+                    rdn(&[("cn", "bob"), ("mail", "bob@example.com")]),
+                    rdn(&[("dc", "com")]),
+                ],
+            ),
+            (
+                "mail=bob@example.com + cn=bob",
+                vec![rdn(&[("cn", "bob"), ("mail", "bob@example.com")])],
+            ),
+            (
+                "0.9.2342.19200300.100.1.1=bob,2.5.4.11=people,OID.0.9.2342.19200300.100.1.25=com",
+                vec![
+                    rdn(&[("uid", "bob")]),
+                    rdn(&[("ou", "people")]),
+                    rdn(&[("dc", "com")]),
+                ],
+            ),
+            ("1.2.3.4=value", vec![rdn(&[("1.2.3.4", "value")])]),
+            (
+                "commonName=bob,domainComponent=com",
+                vec![rdn(&[("cn", "bob")]), rdn(&[("dc", "com")])],
+            ),
+        ];
+        for (dn, expected) in cases {
+            assert_eq!(&parse_dn(dn).expect(dn), expected, "{dn}");
+        }
+    }
+
+    #[test]
+    fn test_parse_dn_errors() {
+        for dn in [
+            "uid",
+            "uid=bob,",
+            ",uid=bob",
+            "=bob",
+            "uid=bob+",
+            "1.02.3=bob",
+            "1..3=bob",
+            "u.id=bob",
+            "_uid=bob",
+            r#"cn="quoted""#,
+            "cn=a;b",
+            "cn=a<b",
+            r"cn=bad\escape",
+            r"cn=trailing\",
+            r"cn=\C4",
+            "cn=#0C0362",
+            "cn=#0C03626F6",
+            "cn=#0203010001",
+            "cn=#",
+        ] {
+            assert!(parse_dn(dn).is_err(), "{dn}");
+        }
+    }
+
+    #[test]
+    fn test_escape_dn_value() {
+        let cases = [
+            ("bob", "bob"),
+            ("Doe, John", r"Doe\, John"),
+            (r#"a+b;c<d>e=f\g""#, r#"a\+b\;c\<d\>e=f\\g\""#),
+            (" spaces ", r"\ spaces\ "),
+            (" ", r"\ "),
+            ("#hash#", r"\#hash#"),
+            ("null\0byte", r"null\00byte"),
+            ("Lučić", "Lučić"),
+            ("", ""),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(escape_dn_value(value), expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn test_dn_round_trip() {
+        for value in [
+            "bob",
+            "Doe, John",
+            "a+b",
+            r#"quote"d"#,
+            r"back\slash",
+            "semi;colon",
+            "<angle>",
+            "equal=sign",
+            " leading space",
+            "trailing space ",
+            "  ",
+            "#hash",
+            "in#side",
+            "null\0byte",
+            "Lučić",
+            "😀 emoji",
+            "",
+        ] {
+            let rdns = vec![
+                Rdn::new("cn", value),
+                rdn(&[("cn", value), ("uid", value)]),
+                Rdn::new("dc", "com"),
+            ];
+            let dn = format_distinguished_name(&rdns);
+            assert_eq!(parse_dn(&dn).expect(&dn), rdns, "{dn}");
+        }
+        for dn in [
+            "uid=bob,ou=people,dc=example,dc=com",
+            r"cn=Doe\, John+uid=jdoe,ou=people,dc=example,dc=com",
+            r"cn=\ a\+b\ ,ou=groups,dc=example,dc=com",
+            r"cn=\#1\\2\<3\>4\;5\00,dc=com",
+        ] {
+            assert_eq!(
+                format_distinguished_name(&parse_dn(dn).expect(dn)),
+                dn,
+                "{dn}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rdn_equality_ignores_order() {
+        assert_eq!(
+            parse_dn("cn=bob+uid=bob").unwrap(),
+            parse_dn("uid=bob+cn=bob").unwrap()
+        );
+        assert_eq!(Rdn::new("CN", "x"), Rdn::new("2.5.4.3", "x"));
+        assert_eq!(rdn(&[("cn", "x")]).single(), Some(("cn", "x")));
+        assert_eq!(rdn(&[("cn", "x"), ("uid", "y")]).single(), None);
+    }
+}
@@ -0,0 +1,389 @@
+use crate::core::{
+    error::{LdapError, LdapResult},
+    user::convert_user_filter,
+    utils::{BindMfaPolicy, LdapInfo, UserFieldType, map_user_field},
+};
+use ldap3_proto::{LdapFilter, LdapResultCode, proto::LdapSubstringFilter};
+use lldap_domain::{public_schema::PublicSchema, types::AttributeName};
+use lldap_domain_handlers::handler::UserRequestFilter;
+
+fn syntax_error(filter: &str, message: &str) -> LdapError {
+    LdapError {
+        code: LdapResultCode::ProtocolError,
+        message: format!(r#"Invalid filter "{filter}": {message}"#),
+    }
+}
+
+/// Parser for the string representation of search filters, as defined in RFC 4515.
+struct FilterParser<'a> {
+    input: &'a str,
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> FilterParser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            bytes: input.as_bytes(),
+            position: 0,
+        }
+    }
+
+    fn error(&self, message: &str) -> LdapError {
+        syntax_error(
+            self.input,
+            &format!("{message} at position {}", self.position),
+        )
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn expect(&mut self, expected: u8) -> LdapResult<()> {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected as char)))
+        }
+    }
+
+    fn parse_filter(&mut self) -> LdapResult<LdapFilter> {
+        self.expect(b'(')?;
+        let filter = match self.peek() {
+            Some(b'&') => {
+                self.position += 1;
+                LdapFilter::And(self.parse_filter_list()?)
+            }
+            Some(b'|') => {
+                self.position += 1;
+                LdapFilter::Or(self.parse_filter_list()?)
+            }
+            Some(b'!') => {
+                self.position += 1;
+                LdapFilter::Not(Box::new(self.parse_filter()?))
+            }
+            _ => self.parse_item()?,
+        };
+        self.expect(b')')?;
+        Ok(filter)
+    }
+
+    fn parse_filter_list(&mut self) -> LdapResult<Vec<LdapFilter>> {
+        let mut filters = Vec::new();
+        while self.peek() == Some(b'(') {
+            filters.push(self.parse_filter()?);
+        }
+        if filters.is_empty() {
+            return Err(self.error("expected a filter"));
+        }
+        Ok(filters)
+    }
+
+    fn parse_attribute(&mut self) -> LdapResult<String> {
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == b'-' || c == b'.' || c == b';' || c == b'_' {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+        if start == self.position {
+            return Err(self.error("expected an attribute name"));
+        }
+        Ok(self.input[start..self.position].to_owned())
+    }
+
+    /// Parses an assertion value up to the closing parenthesis, split on the unescaped '*'.
+    fn parse_value(&mut self) -> LdapResult<Vec<String>> {
+        let mut parts = Vec::new();
+        let mut current = Vec::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated filter")),
+                Some(b')') => break,
+                Some(b'(') => return Err(self.error("unescaped '('")),
+                Some(b'*') => {
+                    parts.push(std::mem::take(&mut current));
+                    self.position += 1;
+                }
+                Some(b'\\') => {
+                    let byte = self
+                        .input
+                        .get(self.position + 1..self.position + 3)
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                        .ok_or_else(|| self.error("invalid escape sequence"))?;
+                    current.push(byte);
+                    self.position += 3;
+                }
+                Some(c) => {
+                    current.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+        parts.push(current);
+        parts
+            .into_iter()
+            .map(|part| String::from_utf8(part).map_err(|_| self.error("invalid UTF-8 value")))
+            .collect()
+    }
+
+    fn parse_item(&mut self) -> LdapResult<LdapFilter> {
+        let attribute = self.parse_attribute()?;
+        let operator = match self.peek() {
+            Some(b'=') => {
+                self.position += 1;
+                b'='
+            }
+            Some(c @ (b'~' | b'>' | b'<')) => {
+                self.position += 1;
+                self.expect(b'=')?;
+                c
+            }
+            Some(b':') => return Err(self.error("extensible match filters are not supported")),
+            _ => return Err(self.error("expected a filter type")),
+        };
+        let mut value = self.parse_value()?;
+        if operator != b'=' {
+            if value.len() != 1 {
+                return Err(self.error("unexpected '*'"));
+            }
+            let value = value.remove(0);
+            return Ok(match operator {
+                b'~' => LdapFilter::Approx(attribute, value),
+                b'>' => LdapFilter::GreaterOrEqual(attribute, value),
+                _ => LdapFilter::LessOrEqual(attribute, value),
+            });
+        }
+        match value.len() {
+            1 => Ok(LdapFilter::Equality(attribute, value.remove(0))),
+            2 if value.iter().all(String::is_empty) => Ok(LdapFilter::Present(attribute)),
+            _ => {
+                let final_ = value.pop().filter(|s| !s.is_empty());
+                let initial = Some(value.remove(0)).filter(|s| !s.is_empty());
+                if value.iter().any(String::is_empty) {
+                    return Err(self.error("empty substring"));
+                }
+                Ok(LdapFilter::Substring(
+                    attribute,
+                    LdapSubstringFilter {
+                        initial,
+                        any: value,
+                        final_,
+                    },
+                ))
+            }
+        }
+    }
+}
+
+/// Parses the string representation of a search filter, e.g. "(&(objectClass=person)(uid=bob))".
+///
+/// The outer parentheses can be omitted for a single item.
+pub fn parse_filter(filter: &str) -> LdapResult<LdapFilter> {
+    let trimmed = filter.trim();
+    let wrapped;
+    let input = if trimmed.starts_with('(') {
+        trimmed
+    } else {
+        wrapped = format!("({trimmed})");
+        &wrapped
+    };
+    let mut parser = FilterParser::new(input);
+    let result = parser.parse_filter()?;
+    if parser.position != input.len() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+    Ok(result)
+}
+
+/// Rejects the unknown attributes, and trims the DNs down to the entry name: member filters are
+/// independent of the base DN.
+fn prepare_member_filter(filter: LdapFilter, schema: &PublicSchema) -> LdapResult<LdapFilter> {
+    let check_attribute =
+        |attribute: &str| match map_user_field(&AttributeName::from(attribute), schema, false) {
+            UserFieldType::NoMatch => Err(LdapError {
+                code: LdapResultCode::UndefinedAttributeType,
+                message: format!("Unknown user attribute in member filter: {attribute}"),
+            }),
+            field_type => Ok(field_type),
+        };
+    let rec = |filters: Vec<LdapFilter>| {
+        filters
+            .into_iter()
+            .map(|f| prepare_member_filter(f, schema))
+            .collect::<LdapResult<Vec<_>>>()
+    };
+    Ok(match filter {
+        LdapFilter::And(filters) => LdapFilter::And(rec(filters)?),
+        LdapFilter::Or(filters) => LdapFilter::Or(rec(filters)?),
+        LdapFilter::Not(filter) => {
+            LdapFilter::Not(Box::new(prepare_member_filter(*filter, schema)?))
+        }
+        LdapFilter::Equality(attribute, value) => match check_attribute(&attribute)? {
+            UserFieldType::MemberOf | UserFieldType::Dn | UserFieldType::EntryDn => {
+                let value = value.split(',').take(2).collect::<Vec<_>>().join(",");
+                LdapFilter::Equality(attribute, value)
+            }
+            _ => LdapFilter::Equality(attribute, value),
+        },
+        LdapFilter::Present(attribute) => {
+            check_attribute(&attribute)?;
+            LdapFilter::Present(attribute)
+        }
+        LdapFilter::Substring(attribute, substring) => {
+            check_attribute(&attribute)?;
+            LdapFilter::Substring(attribute, substring)
+        }
+        filter => {
+            return Err(LdapError {
+                code: LdapResultCode::UnwillingToPerform,
+                message: format!("Unsupported member filter: {filter:?}"),
+            });
+        }
+    })
+}
+
+/// Compiles the LDAP filter defining the members of a dynamic group.
+pub fn parse_member_filter(filter: &str, schema: &PublicSchema) -> LdapResult<UserRequestFilter> {
+    let ldap_filter = prepare_member_filter(parse_filter(filter)?, schema)?;
+    let ldap_info = LdapInfo {
+        base_dn: Vec::new(),
+        base_dn_str: String::new(),
+        ignored_user_attributes: Vec::new(),
+        ignored_group_attributes: Vec::new(),
+        ad_compatibility: false,
+        bind_mfa_policy: BindMfaPolicy::default(),
+    };
+    convert_user_filter(&ldap_info, &ldap_filter, schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lldap_domain::types::{GroupName, UserId};
+    use lldap_domain_handlers::handler::ReadSchemaBackendHandler;
+    use lldap_test_utils::{MockTestBackendHandler, setup_default_schema};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(
+            parse_filter("(&(objectClass=person)(|(uid=bob)(!(mail=*))))").unwrap(),
+            LdapFilter::And(vec![
+                LdapFilter::Equality("objectClass".to_owned(), "person".to_owned()),
+                LdapFilter::Or(vec![
+                    LdapFilter::Equality("uid".to_owned(), "bob".to_owned()),
+                    LdapFilter::Not(Box::new(LdapFilter::Present("mail".to_owned()))),
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_without_parentheses() {
+        assert_eq!(
+            parse_filter(" uid=bob ").unwrap(),
+            LdapFilter::Equality("uid".to_owned(), "bob".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_substring() {
+        assert_eq!(
+            parse_filter("(cn=ab*c\\2a*d)").unwrap(),
+            LdapFilter::Substring(
+                "cn".to_owned(),
+                LdapSubstringFilter {
+                    initial: Some("ab".to_owned()),
+                    any: vec!["c*".to_owned()],
+                    final_: Some("d".to_owned()),
+                }
+            )
+        );
+        assert_eq!(
+            parse_filter("(cn=*b*)").unwrap(),
+            LdapFilter::Substring(
+                "cn".to_owned(),
+                LdapSubstringFilter {
+                    initial: None,
+                    any: vec!["b".to_owned()],
+                    final_: None,
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_escapes() {
+        assert_eq!(
+            parse_filter("(cn=a\\28b\\29\\5c\\c3\\a9)").unwrap(),
+            LdapFilter::Equality("cn".to_owned(), "a(b)\\é".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_comparisons() {
+        assert_eq!(
+            parse_filter("(|(uid>=a)(uid<=b)(uid~=c))").unwrap(),
+            LdapFilter::Or(vec![
+                LdapFilter::GreaterOrEqual("uid".to_owned(), "a".to_owned()),
+                LdapFilter::LessOrEqual("uid".to_owned(), "b".to_owned()),
+                LdapFilter::Approx("uid".to_owned(), "c".to_owned()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_errors() {
+        for filter in [
+            "",
+            "(uid=bob",
+            "(uid=bob))",
+            "(&)",
+            "(uid=a(b)",
+            "(uid=\\zz)",
+            "(uid:dn:=bob)",
+            "(=bob)",
+            "(cn=a**b)",
+            "(uid>=a*)",
+        ] {
+            parse_filter(filter).unwrap_err();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parse_member_filter() {
+        let mut mock = MockTestBackendHandler::new();
+        setup_default_schema(&mut mock);
+        let schema = PublicSchema::from(ReadSchemaBackendHandler::get_schema(&mock).await.unwrap());
+        assert_eq!(
+            parse_member_filter(
+                "(|(memberOf=cn=Admins,ou=groups,dc=example,dc=com)(uid=Bob))",
+                &schema
+            )
+            .unwrap(),
+            UserRequestFilter::Or(vec![
+                UserRequestFilter::MemberOf(GroupName::from("admins")),
+                UserRequestFilter::UserId(UserId::new("bob")),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parse_member_filter_unknown_attribute() {
+        let mut mock = MockTestBackendHandler::new();
+        setup_default_schema(&mut mock);
+        let schema = PublicSchema::from(ReadSchemaBackendHandler::get_schema(&mock).await.unwrap());
+        assert_eq!(
+            parse_member_filter("(nonexistent=1)", &schema)
+                .unwrap_err()
+                .code,
+            LdapResultCode::UndefinedAttributeType
+        );
+    }
+}
@@ -1,27 +1,40 @@
 use crate::core::{
+    dn::escape_dn_value,
     error::{LdapError, LdapResult},
+    user::get_user_dn,
     utils::{
-        ExpandedAttributes, GroupFieldType, LdapInfo, expand_attribute_wildcards,
-        get_custom_attribute, get_group_id_from_distinguished_name_or_plain_name,
-        get_user_id_from_distinguished_name_or_plain_name, map_group_field,
+        AD_MATCHING_RULE_IN_CHAIN, ExpandedAttributes, GroupFieldType, LdapInfo, ad_guid_to_uuid,
+        expand_attribute_wildcards, get_custom_attribute,
+        get_group_id_from_distinguished_name_or_plain_name,
+        get_user_id_from_distinguished_name_or_plain_name, map_group_field, uuid_to_ad_guid,
     },
 };
 use chrono::TimeZone;
+use futures_util::{
+    Stream, StreamExt, TryStreamExt,
+    stream::{self, BoxStream},
+};
 use ldap3_proto::{
     LdapFilter, LdapPartialAttribute, LdapResultCode, LdapSearchResultEntry, proto::LdapOp,
 };
 use lldap_domain::{
     deserialize::deserialize_attribute_value,
     public_schema::PublicSchema,
-    types::{AttributeName, AttributeType, Group, GroupId, LdapObjectClass, UserId, Uuid},
+    types::{
+        AttributeName, AttributeType, Group, GroupId, LdapObjectClass, MatchingRule, UserId, Uuid,
+    },
 };
 use lldap_domain_handlers::handler::{GroupListerBackendHandler, GroupRequestFilter};
+use std::collections::HashSet;
 use tracing::{debug, instrument, warn};
 
 pub const REQUIRED_GROUP_ATTRIBUTES: &[&str] = &["display_name"];
 
 const DEFAULT_GROUP_OBJECT_CLASSES: &[&str] = &["groupOfUniqueNames", "groupOfNames"];
 
+/// Extra object classes advertised in Active Directory compatibility mode.
+pub const AD_GROUP_OBJECT_CLASSES: &[&str] = &["group"];
+
 fn get_default_group_object_classes_as_bytes() -> Vec<Vec<u8>> {
     DEFAULT_GROUP_OBJECT_CLASSES
         .iter()
@@ -38,13 +51,14 @@ pub fn get_default_group_object_classes() -> Vec<LdapObjectClass> {
 
 pub fn get_group_attribute(
     group: &Group,
-    base_dn_str: &str,
     attribute: &AttributeName,
     user_filter: &Option<UserId>,
-    ignored_group_attributes: &[AttributeName],
+    service_accounts: &HashSet<UserId>,
+    ldap_info: &LdapInfo,
     schema: &PublicSchema,
 ) -> Option<Vec<Vec<u8>>> {
-    let attribute_values = match map_group_field(attribute, schema) {
+    let base_dn_str = &ldap_info.base_dn_str;
+    let attribute_values = match map_group_field(attribute, schema, ldap_info.ad_compatibility) {
         GroupFieldType::ObjectClass => {
             let mut classes: Vec<Vec<u8>> = get_default_group_object_classes_as_bytes();
 
@@ -55,12 +69,26 @@ pub fn get_group_attribute(
                     .iter()
                     .map(|c| c.as_str().as_bytes().to_vec()),
             );
+            if ldap_info.ad_compatibility {
+                classes.extend(
+                    AD_GROUP_OBJECT_CLASSES
+                        .iter()
+                        .map(|c| c.as_bytes().to_vec()),
+                );
+            }
             classes
         }
         // Always returned as part of the base response.
         GroupFieldType::Dn => return None,
         GroupFieldType::EntryDn => {
-            vec![format!("uid={},ou=groups,{}", group.display_name, base_dn_str).into_bytes()]
+            vec![
+                format!(
+                    "uid={},ou=groups,{}",
+                    escape_dn_value(group.display_name.as_str()),
+                    base_dn_str
+                )
+                .into_bytes(),
+            ]
         }
         GroupFieldType::GroupId => {
             vec![group.id.0.to_string().into_bytes()]
@@ -76,9 +104,17 @@ pub fn get_group_attribute(
             .users
             .iter()
             .filter(|u| user_filter.as_ref().map(|f| *u == f).unwrap_or(true))
-            .map(|u| format!("uid={u},ou=people,{base_dn_str}").into_bytes())
+            .map(|u| get_user_dn(u, service_accounts.contains(u), base_dn_str).into_bytes())
             .collect(),
+        GroupFieldType::MemberUrl => vec![
+            format!(
+                "ldap:///ou=people,{base_dn_str}??sub?{}",
+                group.member_filter.as_ref()?
+            )
+            .into_bytes(),
+        ],
         GroupFieldType::Uuid => vec![group.uuid.to_string().into_bytes()],
+        GroupFieldType::ObjectGuid => vec![uuid_to_ad_guid(&group.uuid)],
         GroupFieldType::Attribute(attr, _, _) => get_custom_attribute(&group.attributes, &attr)?,
         GroupFieldType::NoMatch => match attribute.as_str() {
             "1.1" => return None,
@@ -90,7 +126,7 @@ pub fn get_group_attribute(
                 )
             }
             _ => {
-                if ignored_group_attributes.contains(attribute) {
+                if ldap_info.ignored_group_attributes.contains(attribute) {
                     return None;
                 }
                 get_custom_attribute(
@@ -125,10 +161,10 @@ fn expand_group_attribute_wildcards(attributes: &[String]) -> ExpandedAttributes
 
 fn make_ldap_search_group_result_entry(
     group: Group,
-    base_dn_str: &str,
     mut expanded_attributes: ExpandedAttributes,
     user_filter: &Option<UserId>,
-    ignored_group_attributes: &[AttributeName],
+    service_accounts: &HashSet<UserId>,
+    ldap_info: &LdapInfo,
     schema: &PublicSchema,
 ) -> LdapSearchResultEntry {
     if expanded_attributes.include_custom_attributes {
@@ -140,17 +176,21 @@ fn make_ldap_search_group_result_entry(
         );
     }
     LdapSearchResultEntry {
-        dn: format!("cn={},ou=groups,{}", group.display_name, base_dn_str),
+        dn: format!(
+            "cn={},ou=groups,{}",
+            escape_dn_value(group.display_name.as_str()),
+            ldap_info.base_dn_str
+        ),
         attributes: expanded_attributes
             .attribute_keys
             .into_iter()
             .filter_map(|(attribute, name)| {
                 let values = get_group_attribute(
                     &group,
-                    base_dn_str,
                     &attribute,
                     user_filter,
-                    ignored_group_attributes,
+                    service_accounts,
+                    ldap_info,
                     schema,
                 )?;
                 Some(LdapPartialAttribute {
@@ -166,21 +206,15 @@ fn get_group_attribute_equality_filter(
     field: &AttributeName,
     typ: AttributeType,
     is_list: bool,
+    matching_rule: MatchingRule,
     value: &str,
 ) -> GroupRequestFilter {
-    let value_lc = value.to_ascii_lowercase();
-    let serialized_value = deserialize_attribute_value(&[value.to_owned()], typ, is_list);
-    let serialized_value_lc = deserialize_attribute_value(&[value_lc.to_owned()], typ, is_list);
-    match (serialized_value, serialized_value_lc) {
-        (Ok(v), Ok(v_lc)) => GroupRequestFilter::Or(vec![
-            GroupRequestFilter::AttributeEquality(field.clone(), v),
-            GroupRequestFilter::AttributeEquality(field.clone(), v_lc),
-        ]),
-        (Ok(_), Err(e)) => {
-            warn!("Invalid value for attribute {} (lowercased): {}", field, e);
-            GroupRequestFilter::from(false)
-        }
-        (Err(e), _) => {
+    if matching_rule == MatchingRule::CaseIgnoreMatch {
+        return GroupRequestFilter::AttributeEqualityIgnoreCase(field.clone(), value.to_owned());
+    }
+    match deserialize_attribute_value(&[value.to_owned()], typ, is_list) {
+        Ok(v) => GroupRequestFilter::AttributeEquality(field.clone(), v),
+        Err(e) => {
             warn!("Invalid value for attribute {}: {}", field, e);
             GroupRequestFilter::from(false)
         }
@@ -197,7 +231,7 @@ fn convert_group_filter(
         LdapFilter::Equality(field, value) => {
             let field = AttributeName::from(field.as_str());
             let value_lc = value.to_ascii_lowercase();
-            match map_group_field(&field, schema) {
+            match map_group_field(&field, schema, ldap_info.ad_compatibility) {
                 GroupFieldType::GroupId => Ok(value_lc
                     .parse::<i32>()
                     .map(|id| GroupRequestFilter::GroupId(GroupId(id)))
@@ -212,6 +246,12 @@ fn convert_group_filter(
                         code: LdapResultCode::Other,
                         message: format!("Invalid UUID: {e:#}"),
                     }),
+                GroupFieldType::ObjectGuid => Ok(ad_guid_to_uuid(value)
+                    .map(GroupRequestFilter::Uuid)
+                    .unwrap_or_else(|| {
+                        warn!("Invalid objectGUID filter: {:?}", value);
+                        GroupRequestFilter::from(false)
+                    })),
                 GroupFieldType::Member => Ok(get_user_id_from_distinguished_name_or_plain_name(
                     &value_lc,
                     &ldap_info.base_dn,
@@ -226,6 +266,10 @@ fn convert_group_filter(
                     get_default_group_object_classes()
                         .iter()
                         .any(|class| class.as_str().eq_ignore_ascii_case(value_lc.as_str()))
+                        || (ldap_info.ad_compatibility
+                            && AD_GROUP_OBJECT_CLASSES
+                                .iter()
+                                .any(|class| class.eq_ignore_ascii_case(value_lc.as_str())))
                         || schema
                             .get_schema()
                             .extra_group_object_classes
@@ -253,13 +297,28 @@ fn convert_group_filter(
                     }
                     Ok(GroupRequestFilter::from(false))
                 }
-                GroupFieldType::Attribute(field, typ, is_list) => Ok(
-                    get_group_attribute_equality_filter(&field, typ, is_list, value),
-                ),
+                GroupFieldType::Attribute(field, typ, is_list) => {
+                    let matching_rule = schema
+                        .get_schema()
+                        .group_attributes
+                        .get_matching_rule(&field)
+                        .unwrap_or_else(|| MatchingRule::default_for(typ));
+                    Ok(get_group_attribute_equality_filter(
+                        &field,
+                        typ,
+                        is_list,
+                        matching_rule,
+                        value,
+                    ))
+                }
                 GroupFieldType::CreationDate => Err(LdapError {
                     code: LdapResultCode::UnwillingToPerform,
                     message: "Creation date filter for groups not supported".to_owned(),
                 }),
+                GroupFieldType::MemberUrl => Err(LdapError {
+                    code: LdapResultCode::UnwillingToPerform,
+                    message: "Member URL filter for groups not supported".to_owned(),
+                }),
             }
         }
         LdapFilter::And(filters) => Ok(GroupRequestFilter::And(
@@ -271,20 +330,25 @@ fn convert_group_filter(
         LdapFilter::Not(filter) => Ok(GroupRequestFilter::Not(Box::new(rec(filter)?))),
         LdapFilter::Present(field) => {
             let field = AttributeName::from(field.as_str());
-            Ok(match map_group_field(&field, schema) {
-                GroupFieldType::Attribute(name, _, _) => {
-                    GroupRequestFilter::CustomAttributePresent(name)
-                }
-                GroupFieldType::NoMatch => GroupRequestFilter::from(false),
-                _ => GroupRequestFilter::from(true),
-            })
+            Ok(
+                match map_group_field(&field, schema, ldap_info.ad_compatibility) {
+                    GroupFieldType::Attribute(name, _, _) => {
+                        GroupRequestFilter::CustomAttributePresent(name)
+                    }
+                    GroupFieldType::NoMatch => GroupRequestFilter::from(false),
+                    _ => GroupRequestFilter::from(true),
+                },
+            )
         }
         LdapFilter::Substring(field, substring_filter) => {
             let field = AttributeName::from(field.as_str());
-            match map_group_field(&field, schema) {
+            match map_group_field(&field, schema, ldap_info.ad_compatibility) {
                 GroupFieldType::DisplayName => Ok(GroupRequestFilter::DisplayNameSubString(
                     substring_filter.clone().into(),
                 )),
+                GroupFieldType::Attribute(name, AttributeType::String, _) => Ok(
+                    GroupRequestFilter::AttributeSubString(name, substring_filter.clone().into()),
+                ),
                 GroupFieldType::NoMatch => Ok(GroupRequestFilter::from(false)),
                 _ => Err(LdapError {
                     code: LdapResultCode::UnwillingToPerform,
@@ -294,6 +358,22 @@ fn convert_group_filter(
                 }),
             }
         }
+        LdapFilter::Extensible(assertion)
+            if ldap_info.ad_compatibility
+                && assertion.matching_rule.as_deref() == Some(AD_MATCHING_RULE_IN_CHAIN) =>
+        {
+            let field = AttributeName::from(assertion.type_.as_deref().unwrap_or_default());
+            match map_group_field(&field, schema, true) {
+                GroupFieldType::Member => rec(&LdapFilter::Equality(
+                    field.to_string(),
+                    assertion.match_value.clone(),
+                )),
+                _ => Err(LdapError {
+                    code: LdapResultCode::UnwillingToPerform,
+                    message: format!("Unsupported group filter: {filter:?}"),
+                }),
+            }
+        }
         _ => Err(LdapError {
             code: LdapResultCode::UnwillingToPerform,
             message: format!("Unsupported group filter: {filter:?}"),
@@ -301,45 +381,46 @@ fn convert_group_filter(
     }
 }
 
+/// Lists the groups matching the filter, as a stream. Like for the users, the first batch is
+/// fetched before the stream is returned.
 #[instrument(skip_all, level = "debug", fields(ldap_filter))]
-pub async fn get_groups_list<Backend: GroupListerBackendHandler>(
+pub fn get_groups_list<'a, Backend: GroupListerBackendHandler>(
     ldap_info: &LdapInfo,
     ldap_filter: &LdapFilter,
-    base: &str,
-    backend: &Backend,
+    base: &'a str,
+    backend: &'a Backend,
     schema: &PublicSchema,
-) -> LdapResult<Vec<Group>> {
-    let filters = convert_group_filter(ldap_info, ldap_filter, schema)?;
+) -> impl Future<Output = LdapResult<BoxStream<'a, LdapResult<Group>>>> + use<'a, Backend> {
+    let filters = convert_group_filter(ldap_info, ldap_filter, schema);
     debug!(?filters);
-    backend
-        .list_groups(Some(filters))
-        .await
-        .map_err(|e| LdapError {
-            code: LdapResultCode::Other,
-            message: format!(r#"Error while listing groups "{base}": {e:#}"#),
-        })
+    async move {
+        let mut groups = backend
+            .stream_groups(Some(filters?))
+            .map_err(move |e| LdapError {
+                code: LdapResultCode::Other,
+                message: format!(r#"Error while listing groups "{base}": {e:#}"#),
+            });
+        let first = groups.try_next().await?;
+        Ok(stream::iter(first.map(Ok)).chain(groups).boxed())
+    }
 }
 
 pub fn convert_groups_to_ldap_op<'a>(
-    groups: Vec<Group>,
+    groups: impl Stream<Item = LdapResult<Group>> + Send + 'a,
     attributes: &'a [String],
     ldap_info: &'a LdapInfo,
     user_filter: &'a Option<UserId>,
+    service_accounts: HashSet<UserId>,
     schema: &'a PublicSchema,
-) -> impl Iterator<Item = LdapOp> + 'a {
-    let expanded_attributes = if groups.is_empty() {
-        None
-    } else {
-        Some(expand_group_attribute_wildcards(attributes))
-    };
-
-    groups.into_iter().map(move |g| {
+) -> impl Stream<Item = LdapResult<LdapOp>> + Send + 'a {
+    let expanded_attributes = expand_group_attribute_wildcards(attributes);
+    groups.map_ok(move |g| {
         LdapOp::SearchResultEntry(make_ldap_search_group_result_entry(
             g,
-            &ldap_info.base_dn_str,
-            expanded_attributes.clone().unwrap(),
+            expanded_attributes.clone(),
             user_filter,
-            &ldap_info.ignored_group_attributes,
+            &service_accounts,
+            ldap_info,
             schema,
         ))
     })
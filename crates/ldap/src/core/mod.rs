@@ -1,4 +1,8 @@
+pub mod controls;
+pub mod dn;
 pub mod error;
+pub mod filter;
 pub mod group;
+pub mod sudo;
 pub mod user;
 pub mod utils;
@@ -0,0 +1,376 @@
+use crate::core::{
+    dn::escape_dn_value,
+    error::{LdapError, LdapResult},
+    utils::{ExpandedAttributes, LdapInfo, expand_attribute_wildcards},
+};
+use chrono::TimeZone;
+use ldap3_proto::{
+    LdapFilter, LdapPartialAttribute, LdapResultCode, LdapSearchResultEntry,
+    proto::{LdapOp, LdapSubstringFilter},
+};
+use lldap_domain::types::{AttributeName, SudoRole};
+use lldap_domain_handlers::handler::SudoRoleListerBackendHandler;
+use tracing::{debug, instrument, warn};
+
+/// The name of the organizational unit containing the sudo rules: "ou=sudoers".
+pub const SUDOERS_OU: &str = "sudoers";
+
+const SUDO_ROLE_OBJECT_CLASSES: &[&str] = &["top", "sudoRole"];
+
+/// The attribute types of the standard sudo schema, as published in the subschema.
+pub const SUDO_SCHEMA_ATTRIBUTE_TYPES: &[&str] = &[
+    "( 1.3.6.1.4.1.15953.9.1.1 NAME 'sudoUser' DESC 'User(s) who may run sudo' EQUALITY caseExactIA5Match SUBSTR caseExactIA5SubstringsMatch SYNTAX 1.3.6.1.4.1.1466.115.121.1.26 )",
+    "( 1.3.6.1.4.1.15953.9.1.2 NAME 'sudoHost' DESC 'Host(s) who may run sudo' EQUALITY caseExactIA5Match SUBSTR caseExactIA5SubstringsMatch SYNTAX 1.3.6.1.4.1.1466.115.121.1.26 )",
+    "( 1.3.6.1.4.1.15953.9.1.3 NAME 'sudoCommand' DESC 'Command(s) to be executed by sudo' EQUALITY caseExactIA5Match SYNTAX 1.3.6.1.4.1.1466.115.121.1.26 )",
+    "( 1.3.6.1.4.1.15953.9.1.5 NAME 'sudoOption' DESC 'Options(s) followed by sudo' EQUALITY caseExactIA5Match SYNTAX 1.3.6.1.4.1.1466.115.121.1.26 )",
+    "( 1.3.6.1.4.1.15953.9.1.6 NAME 'sudoRunAsUser' DESC 'User(s) impersonated by sudo' EQUALITY caseExactIA5Match SYNTAX 1.3.6.1.4.1.1466.115.121.1.26 )",
+    "( 1.3.6.1.4.1.15953.9.1.7 NAME 'sudoRunAsGroup' DESC 'Group(s) impersonated by sudo' EQUALITY caseExactIA5Match SYNTAX 1.3.6.1.4.1.1466.115.121.1.26 )",
+    "( 1.3.6.1.4.1.15953.9.1.10 NAME 'sudoOrder' DESC 'an integer to order the sudoRole entries' EQUALITY integerMatch ORDERING integerOrderingMatch SYNTAX 1.3.6.1.4.1.1466.115.121.1.27 )",
+];
+
+/// The object class of the standard sudo schema, as published in the subschema.
+pub const SUDO_SCHEMA_OBJECT_CLASS: &str = "( 1.3.6.1.4.1.15953.9.2.1 NAME 'sudoRole' SUP top STRUCTURAL DESC 'Sudoer Entries' MUST ( cn ) MAY ( sudoUser $ sudoHost $ sudoCommand $ sudoRunAsUser $ sudoRunAsGroup $ sudoOption $ sudoOrder $ description ) )";
+
+const ALL_SUDO_ROLE_ATTRIBUTE_KEYS: &[&str] = &[
+    "objectClass",
+    "cn",
+    "description",
+    "sudoUser",
+    "sudoHost",
+    "sudoCommand",
+    "sudoRunAsUser",
+    "sudoRunAsGroup",
+    "sudoOption",
+    "sudoOrder",
+    "entryUUID",
+];
+
+/// How the values of an attribute are compared in filters.
+enum SudoRoleAttribute<'a> {
+    /// Matched with caseIgnoreMatch.
+    CaseIgnore(Vec<&'a str>),
+    /// Matched with caseExactIA5Match, like in the sudo schema.
+    CaseExact(&'a [String]),
+    Order(i64),
+    /// Only returned, not used in filters.
+    CreationDate,
+    NoMatch,
+}
+
+fn get_sudo_role_field<'a>(role: &'a SudoRole, attribute: &AttributeName) -> SudoRoleAttribute<'a> {
+    match attribute.as_str() {
+        "objectclass" => SudoRoleAttribute::CaseIgnore(SUDO_ROLE_OBJECT_CLASSES.to_vec()),
+        "cn" | "commonname" => SudoRoleAttribute::CaseIgnore(vec![role.name.as_str()]),
+        "description" => {
+            SudoRoleAttribute::CaseIgnore(role.description.as_deref().into_iter().collect())
+        }
+        "entryuuid" | "uuid" => SudoRoleAttribute::CaseIgnore(vec![role.uuid.as_str()]),
+        "sudouser" => SudoRoleAttribute::CaseExact(&role.users),
+        "sudohost" => SudoRoleAttribute::CaseExact(&role.hosts),
+        "sudocommand" => SudoRoleAttribute::CaseExact(&role.commands),
+        "sudorunasuser" | "sudorunas" => SudoRoleAttribute::CaseExact(&role.run_as_users),
+        "sudorunasgroup" => SudoRoleAttribute::CaseExact(&role.run_as_groups),
+        "sudooption" => SudoRoleAttribute::CaseExact(&role.options),
+        "sudoorder" => SudoRoleAttribute::Order(role.order),
+        "createtimestamp" | "creationdate" => SudoRoleAttribute::CreationDate,
+        _ => SudoRoleAttribute::NoMatch,
+    }
+}
+
+fn get_sudo_role_attribute(
+    role: &SudoRole,
+    attribute: &AttributeName,
+    ldap_info: &LdapInfo,
+) -> Option<Vec<Vec<u8>>> {
+    let values: Vec<Vec<u8>> = match get_sudo_role_field(role, attribute) {
+        SudoRoleAttribute::CaseIgnore(values) => {
+            values.into_iter().map(|v| v.as_bytes().to_vec()).collect()
+        }
+        SudoRoleAttribute::CaseExact(values) => {
+            values.iter().map(|v| v.as_bytes().to_vec()).collect()
+        }
+        SudoRoleAttribute::Order(order) => vec![order.to_string().into_bytes()],
+        SudoRoleAttribute::CreationDate => vec![
+            chrono::Utc
+                .from_utc_datetime(&role.creation_date)
+                .to_rfc3339()
+                .into_bytes(),
+        ],
+        SudoRoleAttribute::NoMatch => match attribute.as_str() {
+            "1.1" | "+" => return None,
+            "entrydn" => vec![make_sudo_role_dn(role, ldap_info).into_bytes()],
+            _ => {
+                warn!("Ignoring unrecognized sudo role attribute: {}", attribute);
+                return None;
+            }
+        },
+    };
+    if values.is_empty() {
+        None
+    } else {
+        Some(values)
+    }
+}
+
+fn make_sudo_role_dn(role: &SudoRole, ldap_info: &LdapInfo) -> String {
+    format!(
+        "cn={},ou={SUDOERS_OU},{}",
+        escape_dn_value(&role.name),
+        ldap_info.base_dn_str
+    )
+}
+
+fn substring_matches(value: &str, filter: &LdapSubstringFilter) -> bool {
+    let mut rest = value;
+    if let Some(initial) = &filter.initial {
+        let Some(stripped) = rest.strip_prefix(initial.as_str()) else {
+            return false;
+        };
+        rest = stripped;
+    }
+    for any in &filter.any {
+        let Some(position) = rest.find(any.as_str()) else {
+            return false;
+        };
+        rest = &rest[position + any.len()..];
+    }
+    filter
+        .final_
+        .as_ref()
+        .is_none_or(|final_| rest.ends_with(final_.as_str()))
+}
+
+fn lowercase_substring_filter(filter: &LdapSubstringFilter) -> LdapSubstringFilter {
+    LdapSubstringFilter {
+        initial: filter.initial.as_ref().map(|s| s.to_lowercase()),
+        any: filter.any.iter().map(|s| s.to_lowercase()).collect(),
+        final_: filter.final_.as_ref().map(|s| s.to_lowercase()),
+    }
+}
+
+/// Evaluates the filter on the sudo role. The sudo rules are few, so they are filtered in memory.
+fn sudo_role_matches_filter(role: &SudoRole, filter: &LdapFilter) -> bool {
+    match filter {
+        LdapFilter::And(filters) => filters.iter().all(|f| sudo_role_matches_filter(role, f)),
+        LdapFilter::Or(filters) => filters.iter().any(|f| sudo_role_matches_filter(role, f)),
+        LdapFilter::Not(filter) => !sudo_role_matches_filter(role, filter),
+        LdapFilter::Present(field) => {
+            match get_sudo_role_field(role, &AttributeName::from(field.as_str())) {
+                SudoRoleAttribute::CaseIgnore(values) => !values.is_empty(),
+                SudoRoleAttribute::CaseExact(values) => !values.is_empty(),
+                SudoRoleAttribute::Order(_) | SudoRoleAttribute::CreationDate => true,
+                SudoRoleAttribute::NoMatch => false,
+            }
+        }
+        LdapFilter::Equality(field, value) => {
+            match get_sudo_role_field(role, &AttributeName::from(field.as_str())) {
+                SudoRoleAttribute::CaseIgnore(values) => {
+                    values.iter().any(|v| v.eq_ignore_ascii_case(value))
+                }
+                SudoRoleAttribute::CaseExact(values) => values.iter().any(|v| v == value),
+                SudoRoleAttribute::Order(order) => value.parse::<i64>() == Ok(order),
+                SudoRoleAttribute::CreationDate | SudoRoleAttribute::NoMatch => false,
+            }
+        }
+        LdapFilter::Substring(field, substring_filter) => {
+            match get_sudo_role_field(role, &AttributeName::from(field.as_str())) {
+                SudoRoleAttribute::CaseIgnore(values) => {
+                    let substring_filter = lowercase_substring_filter(substring_filter);
+                    values
+                        .iter()
+                        .any(|v| substring_matches(&v.to_lowercase(), &substring_filter))
+                }
+                SudoRoleAttribute::CaseExact(values) => values
+                    .iter()
+                    .any(|v| substring_matches(v, substring_filter)),
+                _ => false,
+            }
+        }
+        LdapFilter::GreaterOrEqual(field, value) | LdapFilter::LessOrEqual(field, value) => {
+            let SudoRoleAttribute::Order(order) =
+                get_sudo_role_field(role, &AttributeName::from(field.as_str()))
+            else {
+                return false;
+            };
+            match value.parse::<i64>() {
+                Ok(value) if matches!(filter, LdapFilter::GreaterOrEqual(..)) => order >= value,
+                Ok(value) => order <= value,
+                Err(_) => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Whether the filter can only match sudo roles, i.e. it requires "objectClass=sudoRole".
+pub fn is_sudo_role_filter(filter: &LdapFilter) -> bool {
+    match filter {
+        LdapFilter::Equality(field, value) => {
+            field.eq_ignore_ascii_case("objectclass") && value.eq_ignore_ascii_case("sudorole")
+        }
+        LdapFilter::And(filters) => filters.iter().any(is_sudo_role_filter),
+        _ => false,
+    }
+}
+
+#[instrument(skip_all, level = "debug", fields(ldap_filter))]
+pub async fn get_sudo_roles_list<Backend: SudoRoleListerBackendHandler>(
+    ldap_filter: &LdapFilter,
+    base: &str,
+    backend: &Backend,
+) -> LdapResult<Vec<SudoRole>> {
+    debug!(?ldap_filter);
+    Ok(backend
+        .list_sudo_roles()
+        .await
+        .map_err(|e| LdapError {
+            code: LdapResultCode::Other,
+            message: format!(r#"Error while listing sudo roles "{base}": {e:#}"#),
+        })?
+        .into_iter()
+        .filter(|role| sudo_role_matches_filter(role, ldap_filter))
+        .collect())
+}
+
+fn make_ldap_search_sudo_role_result_entry(
+    role: SudoRole,
+    expanded_attributes: &ExpandedAttributes,
+    ldap_info: &LdapInfo,
+) -> LdapSearchResultEntry {
+    LdapSearchResultEntry {
+        dn: make_sudo_role_dn(&role, ldap_info),
+        attributes: expanded_attributes
+            .attribute_keys
+            .iter()
+            .filter_map(|(attribute, name)| {
+                Some(LdapPartialAttribute {
+                    atype: name.clone(),
+                    vals: get_sudo_role_attribute(&role, attribute, ldap_info)?,
+                })
+            })
+            .collect(),
+    }
+}
+
+pub fn convert_sudo_roles_to_ldap_op(
+    roles: Vec<SudoRole>,
+    attributes: &[String],
+    ldap_info: &LdapInfo,
+) -> Vec<LdapOp> {
+    let expanded_attributes = expand_attribute_wildcards(attributes, ALL_SUDO_ROLE_ATTRIBUTE_KEYS);
+    roles
+        .into_iter()
+        .map(|role| {
+            LdapOp::SearchResultEntry(make_ldap_search_sudo_role_result_entry(
+                role,
+                &expanded_attributes,
+                ldap_info,
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use lldap_domain::types::{SudoRoleId, Uuid};
+
+    fn make_role() -> SudoRole {
+        SudoRole {
+            id: SudoRoleId(1),
+            name: "Admins".to_owned(),
+            description: None,
+            users: vec!["%admins".to_owned(), "bob".to_owned()],
+            hosts: vec!["ALL".to_owned()],
+            commands: vec!["/usr/bin/systemctl restart *".to_owned()],
+            run_as_users: vec![],
+            run_as_groups: vec![],
+            options: vec!["!authenticate".to_owned()],
+            order: 10,
+            creation_date: NaiveDateTime::default(),
+            uuid: Uuid::from_name_and_date("Admins", &NaiveDateTime::default()),
+        }
+    }
+
+    fn eq(field: &str, value: &str) -> LdapFilter {
+        LdapFilter::Equality(field.to_owned(), value.to_owned())
+    }
+
+    #[test]
+    fn test_sudo_role_matches_filter() {
+        let role = make_role();
+        let matches = |filter: LdapFilter| sudo_role_matches_filter(&role, &filter);
+        assert!(matches(eq("objectClass", "SUDOROLE")));
+        assert!(matches(eq("cn", "admins")));
+        assert!(matches(eq("sudoUser", "bob")));
+        assert!(!matches(eq("sudoUser", "Bob")));
+        assert!(matches(eq("sudoOrder", "10")));
+        assert!(matches(LdapFilter::Present("sudoOption".to_owned())));
+        assert!(!matches(LdapFilter::Present("sudoRunAsUser".to_owned())));
+        assert!(!matches(LdapFilter::Present("description".to_owned())));
+        assert!(!matches(eq("mail", "bob")));
+        assert!(matches(LdapFilter::Substring(
+            "sudoHost".to_owned(),
+            LdapSubstringFilter {
+                initial: None,
+                any: vec!["L".to_owned()],
+                final_: None,
+            }
+        )));
+        assert!(matches(LdapFilter::Substring(
+            "cn".to_owned(),
+            LdapSubstringFilter {
+                initial: Some("ad".to_owned()),
+                any: vec![],
+                final_: Some("S".to_owned()),
+            }
+        )));
+        assert!(!matches(LdapFilter::Substring(
+            "sudoUser".to_owned(),
+            LdapSubstringFilter {
+                initial: Some("+".to_owned()),
+                any: vec![],
+                final_: None,
+            }
+        )));
+        // The filter used by SSSD.
+        assert!(matches(LdapFilter::And(vec![
+            eq("objectClass", "sudoRole"),
+            LdapFilter::Or(vec![
+                eq("sudoUser", "ALL"),
+                eq("sudoUser", "%admins"),
+                LdapFilter::Substring(
+                    "sudoUser".to_owned(),
+                    LdapSubstringFilter {
+                        initial: Some("+".to_owned()),
+                        any: vec![],
+                        final_: None,
+                    }
+                ),
+            ]),
+        ])));
+        assert!(matches(LdapFilter::GreaterOrEqual(
+            "sudoOrder".to_owned(),
+            "5".to_owned()
+        )));
+        assert!(!matches(LdapFilter::LessOrEqual(
+            "sudoOrder".to_owned(),
+            "5".to_owned()
+        )));
+    }
+
+    #[test]
+    fn test_is_sudo_role_filter() {
+        assert!(is_sudo_role_filter(&eq("objectclass", "sudoRole")));
+        assert!(is_sudo_role_filter(&LdapFilter::And(vec![
+            eq("sudoUser", "bob"),
+            eq("objectClass", "sudorole"),
+        ])));
+        assert!(!is_sudo_role_filter(&LdapFilter::Or(vec![eq(
+            "objectClass",
+            "sudoRole"
+        )])));
+        assert!(!is_sudo_role_filter(&eq("objectClass", "person")));
+    }
+}
@@ -1,12 +1,19 @@
 use crate::core::{
+    dn::escape_dn_value,
     error::{LdapError, LdapResult},
     utils::{
-        ExpandedAttributes, LdapInfo, UserFieldType, expand_attribute_wildcards,
-        get_custom_attribute, get_group_id_from_distinguished_name_or_plain_name,
-        get_user_id_from_distinguished_name_or_plain_name, map_user_field,
+        AD_ACCOUNT_DISABLE, AD_ACCOUNT_NEVER_EXPIRES, AD_MATCHING_RULE_IN_CHAIN, AD_NORMAL_ACCOUNT,
+        ExpandedAttributes, LdapInfo, UserFieldType, ad_bitwise_match, ad_guid_to_uuid,
+        expand_attribute_wildcards, get_ad_domain, get_custom_attribute,
+        get_group_id_from_distinguished_name_or_plain_name,
+        get_user_id_from_distinguished_name_or_plain_name, map_user_field, uuid_to_ad_guid,
     },
 };
-use chrono::TimeZone;
+use chrono::{NaiveDateTime, TimeZone};
+use futures_util::{
+    Stream, StreamExt, TryStreamExt,
+    stream::{self, BoxStream},
+};
 use ldap3_proto::{
     LdapFilter, LdapPartialAttribute, LdapResultCode, LdapSearchResultEntry, proto::LdapOp,
 };
@@ -14,7 +21,8 @@ use lldap_domain::{
     deserialize::deserialize_attribute_value,
     public_schema::PublicSchema,
     types::{
-        AttributeName, AttributeType, GroupDetails, LdapObjectClass, User, UserAndGroups, UserId,
+        AttributeName, AttributeType, GroupDetails, LdapObjectClass, MatchingRule, User,
+        UserAndGroups, UserId,
     },
 };
 use lldap_domain_handlers::handler::{UserListerBackendHandler, UserRequestFilter};
@@ -23,9 +31,96 @@ use tracing::{debug, instrument, warn};
 
 pub const REQUIRED_USER_ATTRIBUTES: &[&str] = &["user_id", "mail"];
 
+/// The subtree of the service accounts, next to the people in `ou=people`.
+pub const SERVICES_OU: &str = "services";
+
+/// The DN of a user, in the subtree matching its kind.
+pub fn get_user_dn(user_id: &UserId, is_service_account: bool, base_dn_str: &str) -> String {
+    format!(
+        "uid={},ou={},{base_dn_str}",
+        escape_dn_value(user_id.as_str()),
+        if is_service_account {
+            SERVICES_OU
+        } else {
+            "people"
+        }
+    )
+}
+
 const DEFAULT_USER_OBJECT_CLASSES: &[&str] =
     &["inetOrgPerson", "posixAccount", "mailAccount", "person"];
 
+/// The additional object classes of users in Active Directory compatibility mode.
+pub const AD_USER_OBJECT_CLASSES: &[&str] = &["user", "organizationalPerson"];
+
+/// The auxiliary object class of the OpenSSH LDAP public key schema, added to all users when the
+/// schema has an attribute holding SSH public keys.
+pub const SSH_PUBLIC_KEY_OBJECT_CLASS: &str = "ldapPublicKey";
+
+/// The user attributes holding SSH public keys.
+pub fn get_ssh_public_key_attributes(schema: &PublicSchema) -> Vec<&AttributeName> {
+    schema
+        .get_schema()
+        .user_attributes
+        .attributes
+        .iter()
+        .filter(|a| a.attribute_type == AttributeType::SshPublicKey)
+        .map(|a| &a.name)
+        .collect()
+}
+
+/// The value of pwdAccountLockedTime for the accounts locked by an administrator, which are not
+/// unlocked automatically.
+const PERMANENTLY_LOCKED_TIME: &str = "000001010000Z";
+
+/// The attributes describing the account state, as published in the subschema.
+pub const ACCOUNT_LOCK_ATTRIBUTE_TYPES: &[&str] = &[
+    "( 2.16.840.1.113730.3.1.610 NAME 'nsAccountLock' DESC 'Operational attribute for Account Inactivation' SYNTAX 1.3.6.1.4.1.1466.115.121.1.15 SINGLE-VALUE USAGE directoryOperation )",
+    "( 1.3.6.1.4.1.42.2.27.8.1.17 NAME 'pwdAccountLockedTime' DESC 'The time an user account was locked' EQUALITY generalizedTimeMatch ORDERING generalizedTimeOrderingMatch SYNTAX 1.3.6.1.4.1.1466.115.121.1.24 SINGLE-VALUE NO-USER-MODIFICATION USAGE directoryOperation )",
+    "( 1.3.6.1.1.1.1.10 NAME 'shadowExpire' EQUALITY integerMatch SYNTAX 1.3.6.1.4.1.1466.115.121.1.27 SINGLE-VALUE )",
+];
+
+fn get_user_account_control(user: &User) -> u32 {
+    if user.disabled {
+        AD_NORMAL_ACCOUNT | AD_ACCOUNT_DISABLE
+    } else {
+        AD_NORMAL_ACCOUNT
+    }
+}
+
+/// The number of days since the epoch, rounded down.
+fn get_shadow_expire(expiration_date: &NaiveDateTime) -> i64 {
+    expiration_date
+        .and_utc()
+        .timestamp()
+        .div_euclid(24 * 60 * 60)
+}
+
+/// The number of 100-nanosecond intervals since January 1, 1601 (UTC).
+fn get_windows_file_time(date: &NaiveDateTime) -> i64 {
+    const SECONDS_FROM_1601_TO_1970: i64 = 11_644_473_600;
+    let date = date.and_utc();
+    (date.timestamp() + SECONDS_FROM_1601_TO_1970) * 10_000_000
+        + i64::from(date.timestamp_subsec_nanos() / 100)
+}
+
+/// Converts a condition on the userAccountControl flags into a filter on the account state.
+fn user_account_control_filter(
+    matches: impl Fn(u32) -> LdapResult<bool>,
+) -> LdapResult<UserRequestFilter> {
+    let enabled = matches(AD_NORMAL_ACCOUNT)?;
+    let disabled = matches(AD_NORMAL_ACCOUNT | AD_ACCOUNT_DISABLE)?;
+    Ok(if enabled == disabled {
+        UserRequestFilter::from(enabled)
+    } else {
+        UserRequestFilter::Disabled(disabled)
+    })
+}
+
+fn has_ssh_public_key_attribute(schema: &PublicSchema) -> bool {
+    !get_ssh_public_key_attributes(schema).is_empty()
+}
+
 fn get_default_user_object_classes_vec_u8() -> Vec<Vec<u8>> {
     DEFAULT_USER_OBJECT_CLASSES
         .iter()
@@ -43,12 +138,12 @@ pub fn get_default_user_object_classes() -> Vec<LdapObjectClass> {
 pub fn get_user_attribute(
     user: &User,
     attribute: &AttributeName,
-    base_dn_str: &str,
     groups: Option<&[GroupDetails]>,
-    ignored_user_attributes: &[AttributeName],
+    ldap_info: &LdapInfo,
     schema: &PublicSchema,
 ) -> Option<Vec<Vec<u8>>> {
-    let attribute_values = match map_user_field(attribute, schema) {
+    let base_dn_str = &ldap_info.base_dn_str;
+    let attribute_values = match map_user_field(attribute, schema, ldap_info.ad_compatibility) {
         UserFieldType::ObjectClass => {
             let mut classes: Vec<Vec<u8>> = get_default_user_object_classes_vec_u8();
 
@@ -59,29 +154,58 @@ pub fn get_user_attribute(
                     .iter()
                     .map(|c| c.as_str().as_bytes().to_vec()),
             );
+            if ldap_info.ad_compatibility {
+                classes.extend(AD_USER_OBJECT_CLASSES.iter().map(|c| c.as_bytes().to_vec()));
+            }
+            if has_ssh_public_key_attribute(schema) {
+                classes.push(SSH_PUBLIC_KEY_OBJECT_CLASS.as_bytes().to_vec());
+            }
             classes
         }
         // dn is always returned as part of the base response.
         UserFieldType::Dn => return None,
         UserFieldType::EntryDn => {
-            vec![format!("uid={},ou=people,{}", &user.user_id, base_dn_str).into_bytes()]
+            vec![get_user_dn(&user.user_id, user.is_service_account, base_dn_str).into_bytes()]
         }
         UserFieldType::MemberOf => groups
             .into_iter()
             .flatten()
             .map(|id_and_name| {
-                format!("cn={},ou=groups,{}", &id_and_name.display_name, base_dn_str).into_bytes()
+                format!(
+                    "cn={},ou=groups,{}",
+                    escape_dn_value(id_and_name.display_name.as_str()),
+                    base_dn_str
+                )
+                .into_bytes()
             })
             .collect(),
         UserFieldType::PrimaryField(UserColumn::UserId) => {
             vec![user.user_id.to_string().into_bytes()]
         }
-        UserFieldType::PrimaryField(UserColumn::Email) => vec![user.email.to_string().into_bytes()],
+        UserFieldType::PrimaryField(UserColumn::Email) => {
+            // Service accounts have no email.
+            if user.is_service_account {
+                return None;
+            }
+            vec![user.email.to_string().into_bytes()]
+        }
         UserFieldType::PrimaryField(
             UserColumn::LowercaseEmail
             | UserColumn::PasswordHash
+            | UserColumn::LegacyPasswordHash
             | UserColumn::TotpSecret
-            | UserColumn::MfaType,
+            | UserColumn::EncryptedTotpSecret
+            | UserColumn::MfaType
+            | UserColumn::TotpLastStep
+            | UserColumn::MfaFailedAttempts
+            | UserColumn::MfaLockedUntil
+            | UserColumn::Disabled
+            | UserColumn::ExpirationDate
+            | UserColumn::ExpirationWarningSent
+            | UserColumn::PasswordModifiedDate
+            | UserColumn::MustChangePassword
+            | UserColumn::IsServiceAccount
+            | UserColumn::AllowedSources,
         ) => panic!("Should not get here"),
         UserFieldType::PrimaryField(UserColumn::Uuid) => vec![user.uuid.to_string().into_bytes()],
         UserFieldType::PrimaryField(UserColumn::DisplayName) => {
@@ -94,6 +218,36 @@ pub fn get_user_attribute(
                 .into_bytes(),
         ],
         UserFieldType::Attribute(attr, _, _) => get_custom_attribute(&user.attributes, &attr)?,
+        UserFieldType::UserPrincipalName => {
+            vec![format!("{}@{}", user.user_id, get_ad_domain(&ldap_info.base_dn)).into_bytes()]
+        }
+        UserFieldType::ObjectGuid => vec![uuid_to_ad_guid(&user.uuid)],
+        UserFieldType::UserAccountControl => {
+            vec![get_user_account_control(user).to_string().into_bytes()]
+        }
+        UserFieldType::AccountLock => {
+            vec![if user.disabled { "TRUE" } else { "FALSE" }.into()]
+        }
+        UserFieldType::AccountLockedTime => {
+            if !user.disabled {
+                return None;
+            }
+            vec![PERMANENTLY_LOCKED_TIME.into()]
+        }
+        UserFieldType::ShadowExpire => {
+            vec![
+                get_shadow_expire(user.expiration_date.as_ref()?)
+                    .to_string()
+                    .into_bytes(),
+            ]
+        }
+        UserFieldType::AccountExpires => vec![
+            user.expiration_date
+                .as_ref()
+                .map_or(AD_ACCOUNT_NEVER_EXPIRES, get_windows_file_time)
+                .to_string()
+                .into_bytes(),
+        ],
         UserFieldType::NoMatch => match attribute.as_str() {
             "1.1" => return None,
             // We ignore the operational attribute wildcard.
@@ -104,7 +258,7 @@ pub fn get_user_attribute(
                 )
             }
             _ => {
-                if ignored_user_attributes.contains(attribute) {
+                if ldap_info.ignored_user_attributes.contains(attribute) {
                     return None;
                 }
                 get_custom_attribute(&user.attributes, attribute).or_else(|| {
@@ -138,10 +292,9 @@ const ALL_USER_ATTRIBUTE_KEYS: &[&str] = &[
 
 fn make_ldap_search_user_result_entry(
     user: User,
-    base_dn_str: &str,
     mut expanded_attributes: ExpandedAttributes,
     groups: Option<&[GroupDetails]>,
-    ignored_user_attributes: &[AttributeName],
+    ldap_info: &LdapInfo,
     schema: &PublicSchema,
 ) -> LdapSearchResultEntry {
     if expanded_attributes.include_custom_attributes {
@@ -152,19 +305,16 @@ fn make_ldap_search_user_result_entry(
         );
     }
     LdapSearchResultEntry {
-        dn: format!("uid={},ou=people,{}", user.user_id.as_str(), base_dn_str),
+        dn: get_user_dn(
+            &user.user_id,
+            user.is_service_account,
+            &ldap_info.base_dn_str,
+        ),
         attributes: expanded_attributes
             .attribute_keys
             .into_iter()
             .filter_map(|(attribute, name)| {
-                let values = get_user_attribute(
-                    &user,
-                    &attribute,
-                    base_dn_str,
-                    groups,
-                    ignored_user_attributes,
-                    schema,
-                )?;
+                let values = get_user_attribute(&user, &attribute, groups, ldap_info, schema)?;
                 Some(LdapPartialAttribute {
                     atype: name,
                     vals: values,
@@ -178,28 +328,22 @@ fn get_user_attribute_equality_filter(
     field: &AttributeName,
     typ: AttributeType,
     is_list: bool,
+    matching_rule: MatchingRule,
     value: &str,
 ) -> UserRequestFilter {
-    let value_lc = value.to_ascii_lowercase();
-    let serialized_value = deserialize_attribute_value(&[value.to_owned()], typ, is_list);
-    let serialized_value_lc = deserialize_attribute_value(&[value_lc.to_owned()], typ, is_list);
-    match (serialized_value, serialized_value_lc) {
-        (Ok(v), Ok(v_lc)) => UserRequestFilter::Or(vec![
-            UserRequestFilter::AttributeEquality(field.clone(), v),
-            UserRequestFilter::AttributeEquality(field.clone(), v_lc),
-        ]),
-        (Ok(_), Err(e)) => {
-            warn!("Invalid value for attribute {} (lowercased): {}", field, e);
-            UserRequestFilter::from(false)
-        }
-        (Err(e), _) => {
+    if matching_rule == MatchingRule::CaseIgnoreMatch {
+        return UserRequestFilter::AttributeEqualityIgnoreCase(field.clone(), value.to_owned());
+    }
+    match deserialize_attribute_value(&[value.to_owned()], typ, is_list) {
+        Ok(v) => UserRequestFilter::AttributeEquality(field.clone(), v),
+        Err(e) => {
             warn!("Invalid value for attribute {}: {}", field, e);
             UserRequestFilter::from(false)
         }
     }
 }
 
-fn convert_user_filter(
+pub fn convert_user_filter(
     ldap_info: &LdapInfo,
     filter: &LdapFilter,
     schema: &PublicSchema,
@@ -216,7 +360,7 @@ fn convert_user_filter(
         LdapFilter::Equality(field, value) => {
             let field = AttributeName::from(field.as_str());
             let value_lc = value.to_ascii_lowercase();
-            match map_user_field(&field, schema) {
+            match map_user_field(&field, schema, ldap_info.ad_compatibility) {
                 UserFieldType::PrimaryField(UserColumn::UserId) => {
                     Ok(UserRequestFilter::UserId(UserId::new(&value_lc)))
                 }
@@ -227,9 +371,20 @@ fn convert_user_filter(
                 UserFieldType::PrimaryField(field) => {
                     Ok(UserRequestFilter::Equality(field, value_lc))
                 }
-                UserFieldType::Attribute(field, typ, is_list) => Ok(
-                    get_user_attribute_equality_filter(&field, typ, is_list, value),
-                ),
+                UserFieldType::Attribute(field, typ, is_list) => {
+                    let matching_rule = schema
+                        .get_schema()
+                        .user_attributes
+                        .get_matching_rule(&field)
+                        .unwrap_or_else(|| MatchingRule::default_for(typ));
+                    Ok(get_user_attribute_equality_filter(
+                        &field,
+                        typ,
+                        is_list,
+                        matching_rule,
+                        value,
+                    ))
+                }
                 UserFieldType::NoMatch => {
                     if !ldap_info.ignored_user_attributes.contains(&field) {
                         warn!(
@@ -244,6 +399,12 @@ fn convert_user_filter(
                     get_default_user_object_classes()
                         .iter()
                         .any(|class| class.as_str().eq_ignore_ascii_case(value_lc.as_str()))
+                        || (ldap_info.ad_compatibility
+                            && AD_USER_OBJECT_CLASSES
+                                .iter()
+                                .any(|class| class.eq_ignore_ascii_case(value_lc.as_str())))
+                        || (SSH_PUBLIC_KEY_OBJECT_CLASS.eq_ignore_ascii_case(value_lc.as_str())
+                            && has_ssh_public_key_attribute(schema))
                         || schema
                             .get_schema()
                             .extra_user_object_classes
@@ -259,6 +420,40 @@ fn convert_user_filter(
                     warn!("Invalid memberOf filter: {}", e);
                     UserRequestFilter::from(false)
                 })),
+                UserFieldType::UserPrincipalName => Ok(value_lc
+                    .split_once('@')
+                    .map(|(user_id, _)| UserRequestFilter::UserId(UserId::new(user_id)))
+                    .unwrap_or_else(|| UserRequestFilter::from(false))),
+                UserFieldType::ObjectGuid => Ok(ad_guid_to_uuid(value)
+                    .map(|uuid| UserRequestFilter::Equality(UserColumn::Uuid, uuid.into_string()))
+                    .unwrap_or_else(|| {
+                        warn!("Invalid objectGUID filter: {:?}", value);
+                        UserRequestFilter::from(false)
+                    })),
+                UserFieldType::UserAccountControl => {
+                    user_account_control_filter(|flags| Ok(value.parse::<u32>() == Ok(flags)))
+                }
+                UserFieldType::AccountLock => Ok(match value_lc.as_str() {
+                    "true" => UserRequestFilter::Disabled(true),
+                    "false" => UserRequestFilter::Disabled(false),
+                    _ => UserRequestFilter::from(false),
+                }),
+                UserFieldType::AccountLockedTime => Ok(if value == PERMANENTLY_LOCKED_TIME {
+                    UserRequestFilter::Disabled(true)
+                } else {
+                    UserRequestFilter::from(false)
+                }),
+                UserFieldType::ShadowExpire => {
+                    warn!("Equality filters on shadowExpire are not supported");
+                    Ok(UserRequestFilter::from(false))
+                }
+                UserFieldType::AccountExpires => Ok(match value.parse::<i64>() {
+                    Ok(0 | AD_ACCOUNT_NEVER_EXPIRES) => UserRequestFilter::Expires(false),
+                    _ => {
+                        warn!("Equality filters on accountExpires dates are not supported");
+                        UserRequestFilter::from(false)
+                    }
+                }),
                 UserFieldType::EntryDn | UserFieldType::Dn => {
                     Ok(get_user_id_from_distinguished_name_or_plain_name(
                         value_lc.as_str(),
@@ -275,25 +470,39 @@ fn convert_user_filter(
         }
         LdapFilter::Present(field) => {
             let field = AttributeName::from(field.as_str());
-            Ok(match map_user_field(&field, schema) {
-                UserFieldType::Attribute(name, _, _) => {
-                    UserRequestFilter::CustomAttributePresent(name)
-                }
-                UserFieldType::NoMatch => UserRequestFilter::from(false),
-                _ => UserRequestFilter::from(true),
-            })
+            Ok(
+                match map_user_field(&field, schema, ldap_info.ad_compatibility) {
+                    UserFieldType::Attribute(name, _, _) => {
+                        UserRequestFilter::CustomAttributePresent(name)
+                    }
+                    UserFieldType::NoMatch => UserRequestFilter::from(false),
+                    UserFieldType::AccountLockedTime => UserRequestFilter::Disabled(true),
+                    UserFieldType::ShadowExpire => UserRequestFilter::Expires(true),
+                    _ => UserRequestFilter::from(true),
+                },
+            )
         }
         LdapFilter::Substring(field, substring_filter) => {
             let field = AttributeName::from(field.as_str());
-            match map_user_field(&field, schema) {
+            match map_user_field(&field, schema, ldap_info.ad_compatibility) {
                 UserFieldType::PrimaryField(UserColumn::UserId) => Ok(
                     UserRequestFilter::UserIdSubString(substring_filter.clone().into()),
                 ),
+                UserFieldType::Attribute(name, AttributeType::String, _) => Ok(
+                    UserRequestFilter::AttributeSubString(name, substring_filter.clone().into()),
+                ),
                 UserFieldType::Attribute(_, _, _)
                 | UserFieldType::ObjectClass
                 | UserFieldType::MemberOf
                 | UserFieldType::Dn
                 | UserFieldType::EntryDn
+                | UserFieldType::UserPrincipalName
+                | UserFieldType::ObjectGuid
+                | UserFieldType::UserAccountControl
+                | UserFieldType::AccountLock
+                | UserFieldType::AccountLockedTime
+                | UserFieldType::ShadowExpire
+                | UserFieldType::AccountExpires
                 | UserFieldType::PrimaryField(UserColumn::CreationDate)
                 | UserFieldType::PrimaryField(UserColumn::Uuid) => Err(LdapError {
                     code: LdapResultCode::UnwillingToPerform,
@@ -310,6 +519,26 @@ fn convert_user_filter(
                 )),
             }
         }
+        LdapFilter::Extensible(assertion) if ldap_info.ad_compatibility => {
+            let field = AttributeName::from(assertion.type_.as_deref().unwrap_or_default());
+            match (
+                assertion.matching_rule.as_deref(),
+                map_user_field(&field, schema, true),
+            ) {
+                (Some(AD_MATCHING_RULE_IN_CHAIN), UserFieldType::MemberOf) => rec(
+                    &LdapFilter::Equality(field.to_string(), assertion.match_value.clone()),
+                ),
+                (Some(rule), UserFieldType::UserAccountControl) => {
+                    user_account_control_filter(|flags| {
+                        ad_bitwise_match(rule, flags, &assertion.match_value)
+                    })
+                }
+                _ => Err(LdapError {
+                    code: LdapResultCode::UnwillingToPerform,
+                    message: format!("Unsupported user filter: {filter:?}"),
+                }),
+            }
+        }
         _ => Err(LdapError {
             code: LdapResultCode::UnwillingToPerform,
             message: format!("Unsupported user filter: {filter:?}"),
@@ -317,48 +546,72 @@ fn convert_user_filter(
     }
 }
 
+/// Adds the condition on the kind of user, for searches in the people or service accounts subtree.
+pub fn restrict_to_user_kind(
+    filters: UserRequestFilter,
+    service_accounts: Option<bool>,
+) -> UserRequestFilter {
+    let Some(is_service_account) = service_accounts else {
+        return filters;
+    };
+    let kind = UserRequestFilter::ServiceAccount(is_service_account);
+    match filters {
+        UserRequestFilter::And(mut filters) => {
+            filters.push(kind);
+            UserRequestFilter::And(filters)
+        }
+        filters => UserRequestFilter::And(vec![filters, kind]),
+    }
+}
+
 fn expand_user_attribute_wildcards(attributes: &[String]) -> ExpandedAttributes {
     expand_attribute_wildcards(attributes, ALL_USER_ATTRIBUTE_KEYS)
 }
 
+/// Lists the users matching the filter, as a stream. The filter is converted right away, and the
+/// first batch of users is fetched before the stream is returned so that backend errors are
+/// reported before any entry is sent.
+///
+/// `service_accounts` restricts the list to the service accounts or to the people, for searches
+/// in their subtree.
 #[instrument(skip_all, level = "debug", fields(ldap_filter, request_groups))]
-pub async fn get_user_list<Backend: UserListerBackendHandler>(
+pub fn get_user_list<'a, Backend: UserListerBackendHandler>(
     ldap_info: &LdapInfo,
     ldap_filter: &LdapFilter,
+    service_accounts: Option<bool>,
     request_groups: bool,
-    base: &str,
-    backend: &Backend,
+    base: &'a str,
+    backend: &'a Backend,
     schema: &PublicSchema,
-) -> LdapResult<Vec<UserAndGroups>> {
-    let filters = convert_user_filter(ldap_info, ldap_filter, schema)?;
+) -> impl Future<Output = LdapResult<BoxStream<'a, LdapResult<UserAndGroups>>>> + use<'a, Backend> {
+    let filters = convert_user_filter(ldap_info, ldap_filter, schema)
+        .map(|filters| restrict_to_user_kind(filters, service_accounts));
     debug!(?filters);
-    backend
-        .list_users(Some(filters), request_groups)
-        .await
-        .map_err(|e| LdapError {
-            code: LdapResultCode::Other,
-            message: format!(r#"Error while searching user "{base}": {e:#}"#),
-        })
+    async move {
+        let mut users = backend
+            .stream_users(Some(filters?), request_groups)
+            .map_err(move |e| LdapError {
+                code: LdapResultCode::Other,
+                message: format!(r#"Error while searching user "{base}": {e:#}"#),
+            });
+        let first = users.try_next().await?;
+        Ok(stream::iter(first.map(Ok)).chain(users).boxed())
+    }
 }
 
 pub fn convert_users_to_ldap_op<'a>(
-    users: Vec<UserAndGroups>,
+    users: impl Stream<Item = LdapResult<UserAndGroups>> + Send + 'a,
     attributes: &'a [String],
     ldap_info: &'a LdapInfo,
     schema: &'a PublicSchema,
-) -> impl Iterator<Item = LdapOp> + 'a {
-    let expanded_attributes = if users.is_empty() {
-        None
-    } else {
-        Some(expand_user_attribute_wildcards(attributes))
-    };
-    users.into_iter().map(move |u| {
+) -> impl Stream<Item = LdapResult<LdapOp>> + Send + 'a {
+    let expanded_attributes = expand_user_attribute_wildcards(attributes);
+    users.map_ok(move |u| {
         LdapOp::SearchResultEntry(make_ldap_search_user_result_entry(
             u.user,
-            &ldap_info.base_dn_str,
-            expanded_attributes.clone().unwrap(),
+            expanded_attributes.clone(),
             u.groups.as_deref(),
-            &ldap_info.ignored_user_attributes,
+            ldap_info,
             schema,
         ))
     })
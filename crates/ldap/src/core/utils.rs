@@ -1,7 +1,11 @@
 use crate::core::{
+    dn::{Rdn, parse_dn},
     error::{LdapError, LdapResult},
     group::{REQUIRED_GROUP_ATTRIBUTES, get_default_group_object_classes},
-    user::{REQUIRED_USER_ATTRIBUTES, get_default_user_object_classes},
+    user::{
+        REQUIRED_USER_ATTRIBUTES, SERVICES_OU, SSH_PUBLIC_KEY_OBJECT_CLASS,
+        get_default_user_object_classes, get_ssh_public_key_attributes,
+    },
 };
 use chrono::TimeZone;
 use itertools::join;
@@ -10,47 +14,28 @@ use lldap_domain::{
     public_schema::PublicSchema,
     schema::{AttributeList, Schema},
     types::{
-        Attribute, AttributeName, AttributeType, AttributeValue, Cardinality, GroupName,
-        LdapObjectClass, UserId,
+        Attribute, AttributeName, AttributeType, AttributeValue, Cardinality, GroupDetails,
+        GroupName, LdapObjectClass, UserId, Uuid,
     },
 };
 use lldap_domain_model::model::UserColumn;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use tracing::{debug, instrument, warn};
 
-fn make_dn_pair<I>(mut iter: I) -> LdapResult<(String, String)>
-where
-    I: Iterator<Item = String>,
-{
-    (|| {
-        let pair = (
-            iter.next().ok_or_else(|| "Empty DN element".to_string())?,
-            iter.next().ok_or_else(|| "Missing DN value".to_string())?,
-        );
-        if let Some(e) = iter.next() {
-            Err(format!(
-                r#"Too many elements in distinguished name: "{}", "{}", "{}""#,
-                pair.0, pair.1, e
-            ))
-        } else {
-            Ok(pair)
-        }
-    })()
-    .map_err(|e| LdapError {
-        code: LdapResultCode::InvalidDNSyntax,
-        message: e,
-    })
-}
-
-pub fn parse_distinguished_name(dn: &str) -> LdapResult<Vec<(String, String)>> {
-    assert!(dn == dn.to_ascii_lowercase());
-    dn.split(',')
-        .map(|s| make_dn_pair(s.split('=').map(str::trim).map(String::from)))
-        .collect()
+/// Parses a DN, normalized to lowercase for comparisons.
+pub fn parse_distinguished_name(dn: &str) -> LdapResult<Vec<Rdn>> {
+    parse_dn(dn)
+        .map(|rdns| rdns.iter().map(Rdn::to_ascii_lowercase).collect())
+        .map_err(|e| LdapError {
+            code: LdapResultCode::InvalidDNSyntax,
+            message: format!(r#"Invalid DN "{dn}": {e}"#),
+        })
 }
 
 pub enum UserOrGroupName {
     User(UserId),
+    // A user under the service accounts subtree.
+    ServiceAccount(UserId),
     Group(GroupName),
     BadSubStree,
     UnexpectedFormat,
@@ -66,6 +51,7 @@ impl UserOrGroupName {
                 UserOrGroupName::InvalidSyntax(err) => return err,
                 UserOrGroupName::UnexpectedFormat
                 | UserOrGroupName::User(_)
+                | UserOrGroupName::ServiceAccount(_)
                 | UserOrGroupName::Group(_) => {
                     format!(r#"Unexpected DN format. Got "{input}", expected: {expected_format}"#)
                 }
@@ -76,7 +62,7 @@ impl UserOrGroupName {
 
 pub fn get_user_or_group_id_from_distinguished_name(
     dn: &str,
-    base_tree: &[(String, String)],
+    base_tree: &[Rdn],
 ) -> UserOrGroupName {
     let parts = match parse_distinguished_name(dn) {
         Ok(p) => p,
@@ -84,14 +70,18 @@ pub fn get_user_or_group_id_from_distinguished_name(
     };
     if !is_subtree(&parts, base_tree) {
         return UserOrGroupName::BadSubStree;
-    } else if parts.len() == base_tree.len() + 2
-        && parts[1].0 == "ou"
-        && (parts[0].0 == "cn" || parts[0].0 == "uid")
-    {
-        if parts[1].1 == "groups" {
-            return UserOrGroupName::Group(GroupName::from(parts[0].1.clone()));
-        } else if parts[1].1 == "people" {
-            return UserOrGroupName::User(UserId::from(parts[0].1.clone()));
+    } else if parts.len() == base_tree.len() + 2 {
+        match (parts[0].single(), parts[1].single()) {
+            (Some(("cn" | "uid", name)), Some(("ou", "groups"))) => {
+                return UserOrGroupName::Group(GroupName::from(name));
+            }
+            (Some(("cn" | "uid", name)), Some(("ou", "people"))) => {
+                return UserOrGroupName::User(UserId::from(name));
+            }
+            (Some(("cn" | "uid", name)), Some(("ou", SERVICES_OU))) => {
+                return UserOrGroupName::ServiceAccount(UserId::from(name));
+            }
+            _ => {}
         }
     }
     UserOrGroupName::UnexpectedFormat
@@ -99,18 +89,18 @@ pub fn get_user_or_group_id_from_distinguished_name(
 
 pub fn get_user_id_from_distinguished_name(
     dn: &str,
-    base_tree: &[(String, String)],
+    base_tree: &[Rdn],
     base_dn_str: &str,
 ) -> LdapResult<UserId> {
     match get_user_or_group_id_from_distinguished_name(dn, base_tree) {
-        UserOrGroupName::User(user_id) => Ok(user_id),
+        UserOrGroupName::User(user_id) | UserOrGroupName::ServiceAccount(user_id) => Ok(user_id),
         err => Err(err.into_ldap_error(dn, format!(r#""uid=id,ou=people,{base_dn_str}""#))),
     }
 }
 
 pub fn get_group_id_from_distinguished_name(
     dn: &str,
-    base_tree: &[(String, String)],
+    base_tree: &[Rdn],
     base_dn_str: &str,
 ) -> LdapResult<GroupName> {
     match get_user_or_group_id_from_distinguished_name(dn, base_tree) {
@@ -125,7 +115,7 @@ fn looks_like_distinguished_name(dn: &str) -> bool {
 
 pub fn get_user_id_from_distinguished_name_or_plain_name(
     dn: &str,
-    base_tree: &[(String, String)],
+    base_tree: &[Rdn],
     base_dn_str: &str,
 ) -> LdapResult<UserId> {
     if !looks_like_distinguished_name(dn) {
@@ -137,7 +127,7 @@ pub fn get_user_id_from_distinguished_name_or_plain_name(
 
 pub fn get_group_id_from_distinguished_name_or_plain_name(
     dn: &str,
-    base_tree: &[(String, String)],
+    base_tree: &[Rdn],
     base_dn_str: &str,
 ) -> LdapResult<GroupName> {
     if !looks_like_distinguished_name(dn) {
@@ -182,15 +172,7 @@ pub fn expand_attribute_wildcards(
     }
 }
 
-pub fn is_subtree(subtree: &[(String, String)], base_tree: &[(String, String)]) -> bool {
-    for (k, v) in subtree {
-        assert!(k == &k.to_ascii_lowercase());
-        assert!(v == &v.to_ascii_lowercase());
-    }
-    for (k, v) in base_tree {
-        assert!(k == &k.to_ascii_lowercase());
-        assert!(v == &v.to_ascii_lowercase());
-    }
+pub fn is_subtree(subtree: &[Rdn], base_tree: &[Rdn]) -> bool {
     if subtree.len() < base_tree.len() {
         return false;
     }
@@ -211,10 +193,36 @@ pub enum UserFieldType {
     EntryDn,
     PrimaryField(UserColumn),
     Attribute(AttributeName, AttributeType, bool),
+    // Whether the account is disabled, "TRUE" or "FALSE", like in 389 Directory Server.
+    AccountLock,
+    // When the account was locked, from the password policy schema: only present for disabled
+    // accounts.
+    AccountLockedTime,
+    // The expiration date of the account, in days since the epoch, like in shadowAccount.
+    ShadowExpire,
+    // The following are only mapped in Active Directory compatibility mode.
+    // "user@domain", with the domain taken from the base DN.
+    UserPrincipalName,
+    // The UUID, in the binary GUID format.
+    ObjectGuid,
+    // The account flags.
+    UserAccountControl,
+    // The expiration date of the account, as a Windows file time.
+    AccountExpires,
 }
 
-pub fn map_user_field(field: &AttributeName, schema: &PublicSchema) -> UserFieldType {
+pub fn map_user_field(
+    field: &AttributeName,
+    schema: &PublicSchema,
+    ad_compatibility: bool,
+) -> UserFieldType {
     match field.as_str() {
+        "samaccountname" if ad_compatibility => UserFieldType::PrimaryField(UserColumn::UserId),
+        "userprincipalname" if ad_compatibility => UserFieldType::UserPrincipalName,
+        "objectguid" if ad_compatibility => UserFieldType::ObjectGuid,
+        "useraccountcontrol" if ad_compatibility => UserFieldType::UserAccountControl,
+        "accountexpires" if ad_compatibility => UserFieldType::AccountExpires,
+        "objectcategory" if ad_compatibility => UserFieldType::ObjectClass,
         "memberof" | "ismemberof" => UserFieldType::MemberOf,
         "objectclass" => UserFieldType::ObjectClass,
         "dn" | "distinguishedname" => UserFieldType::Dn,
@@ -243,6 +251,9 @@ pub fn map_user_field(field: &AttributeName, schema: &PublicSchema) -> UserField
             UserFieldType::PrimaryField(UserColumn::CreationDate)
         }
         "entryuuid" | "uuid" => UserFieldType::PrimaryField(UserColumn::Uuid),
+        "nsaccountlock" => UserFieldType::AccountLock,
+        "pwdaccountlockedtime" => UserFieldType::AccountLockedTime,
+        "shadowexpire" => UserFieldType::ShadowExpire,
         _ => schema
             .get_schema()
             .user_attributes
@@ -262,12 +273,23 @@ pub enum GroupFieldType {
     // Like Dn, but returned as part of the attributes.
     EntryDn,
     Member,
+    // The filter defining the members of a dynamic group, as an LDAP URL.
+    MemberUrl,
     Uuid,
     Attribute(AttributeName, AttributeType, bool),
+    // Only mapped in Active Directory compatibility mode: the UUID, in the binary GUID format.
+    ObjectGuid,
 }
 
-pub fn map_group_field(field: &AttributeName, schema: &PublicSchema) -> GroupFieldType {
+pub fn map_group_field(
+    field: &AttributeName,
+    schema: &PublicSchema,
+    ad_compatibility: bool,
+) -> GroupFieldType {
     match field.as_str() {
+        "samaccountname" if ad_compatibility => GroupFieldType::DisplayName,
+        "objectguid" if ad_compatibility => GroupFieldType::ObjectGuid,
+        "objectcategory" if ad_compatibility => GroupFieldType::ObjectClass,
         "dn" | "distinguishedname" => GroupFieldType::Dn,
         "entrydn" => GroupFieldType::EntryDn,
         "objectclass" => GroupFieldType::ObjectClass,
@@ -276,6 +298,7 @@ pub fn map_group_field(field: &AttributeName, schema: &PublicSchema) -> GroupFie
             GroupFieldType::CreationDate
         }
         "member" | "uniquemember" => GroupFieldType::Member,
+        "memberurl" => GroupFieldType::MemberUrl,
         "entryuuid" | "uuid" => GroupFieldType::Uuid,
         "group_id" | "groupid" => GroupFieldType::GroupId,
         _ => schema
@@ -288,10 +311,100 @@ pub fn map_group_field(field: &AttributeName, schema: &PublicSchema) -> GroupFie
 }
 
 pub struct LdapInfo {
-    pub base_dn: Vec<(String, String)>,
+    pub base_dn: Vec<Rdn>,
     pub base_dn_str: String,
     pub ignored_user_attributes: Vec<AttributeName>,
     pub ignored_group_attributes: Vec<AttributeName>,
+    /// Emulate the attributes and behaviors of Active Directory, for clients that only support it.
+    pub ad_compatibility: bool,
+    pub bind_mfa_policy: BindMfaPolicy,
+}
+
+/// Which users have to append a TOTP code to their password when binding, since the LDAP clients
+/// (VPNs, SSH jump hosts...) cannot ask for it separately.
+#[derive(Clone, Debug, Default)]
+pub struct BindMfaPolicy {
+    /// The members of these groups have to bind with `<password><6-digit TOTP code>`.
+    pub required_groups: HashSet<GroupName>,
+    /// The members of these groups keep binding with only the password, even if they are also in
+    /// a required group, e.g. the service accounts.
+    pub exempt_groups: HashSet<GroupName>,
+}
+
+impl BindMfaPolicy {
+    pub fn is_enabled(&self) -> bool {
+        !self.required_groups.is_empty()
+    }
+
+    pub fn is_required_for(&self, groups: &HashSet<GroupDetails>) -> bool {
+        let is_member_of_any = |names: &HashSet<GroupName>| {
+            groups
+                .iter()
+                .any(|group| names.contains(&group.display_name))
+        };
+        is_member_of_any(&self.required_groups) && !is_member_of_any(&self.exempt_groups)
+    }
+}
+
+/// The value of userAccountControl for a normal, enabled account.
+pub const AD_NORMAL_ACCOUNT: u32 = 0x200;
+/// The userAccountControl flag of disabled accounts.
+pub const AD_ACCOUNT_DISABLE: u32 = 0x2;
+/// The accountExpires value of the accounts that never expire. 0 means the same.
+pub const AD_ACCOUNT_NEVER_EXPIRES: i64 = i64::MAX;
+
+/// Active Directory extensible match rules.
+pub const AD_MATCHING_RULE_BIT_AND: &str = "1.2.840.113556.1.4.803";
+pub const AD_MATCHING_RULE_BIT_OR: &str = "1.2.840.113556.1.4.804";
+/// Matches through nested groups. Since groups cannot be nested, it's the same as equality.
+pub const AD_MATCHING_RULE_IN_CHAIN: &str = "1.2.840.113556.1.4.1941";
+
+/// Evaluates a bitwise matching rule on a set of flags, e.g. for
+/// "(userAccountControl:1.2.840.113556.1.4.803:=2)".
+pub fn ad_bitwise_match(rule: &str, flags: u32, value: &str) -> LdapResult<bool> {
+    let mask = value.parse::<u32>().map_err(|_| LdapError {
+        code: LdapResultCode::InvalidAttributeSyntax,
+        message: format!("Invalid value for a bitwise filter: {value}"),
+    })?;
+    match rule {
+        AD_MATCHING_RULE_BIT_AND => Ok(flags & mask == mask),
+        AD_MATCHING_RULE_BIT_OR => Ok(flags & mask != 0),
+        _ => Err(LdapError {
+            code: LdapResultCode::InappropriateMatching,
+            message: format!("Unsupported matching rule: {rule}"),
+        }),
+    }
+}
+
+/// The Active Directory domain corresponding to the base DN, e.g. "example.com" for
+/// "dc=example,dc=com".
+pub fn get_ad_domain(base_dn: &[Rdn]) -> String {
+    base_dn
+        .iter()
+        .filter_map(Rdn::single)
+        .filter(|(key, _)| *key == "dc")
+        .map(|(_, value)| value)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Converts a UUID to the binary format of Active Directory GUIDs, where the first 3 fields are
+/// little-endian.
+pub fn uuid_to_ad_guid(uuid: &Uuid) -> Vec<u8> {
+    uuid::Uuid::parse_str(uuid.as_str())
+        .map(|u| u.to_bytes_le().to_vec())
+        .unwrap_or_default()
+}
+
+/// Parses a GUID from a filter, either in the binary format or as a string.
+pub fn ad_guid_to_uuid(value: &str) -> Option<Uuid> {
+    if value.len() == 16 {
+        uuid::Uuid::from_slice_le(value.as_bytes())
+            .ok()
+            .and_then(|u| Uuid::try_from(u.to_string().as_str()).ok())
+    } else {
+        Uuid::try_from(value).ok()
+    }
 }
 
 pub fn get_custom_attribute(
@@ -333,6 +446,12 @@ pub fn get_custom_attribute(
             AttributeValue::DateTime(Cardinality::Unbounded(l)) => {
                 l.iter().map(convert_date).collect()
             }
+            AttributeValue::SshPublicKey(Cardinality::Singleton(k)) => {
+                vec![k.as_str().as_bytes().to_vec()]
+            }
+            AttributeValue::SshPublicKey(Cardinality::Unbounded(l)) => {
+                l.iter().map(|k| k.as_str().as_bytes().to_vec()).collect()
+            }
         })
 }
 
@@ -442,7 +561,7 @@ impl LdapSchemaDescription {
         for (index, attribute) in self.all_attributes().attributes.into_iter().enumerate() {
             formatted_list.push(
                 format!(
-                    "( 2.{} NAME '{}' DESC 'LLDAP: {}' SUP {:?} )",
+                    "( 2.{} NAME '{}' DESC 'LLDAP: {}' SUP {:?} EQUALITY {} )",
                     (index + index_offset),
                     attribute.name,
                     if attribute.is_hardcoded {
@@ -450,7 +569,8 @@ impl LdapSchemaDescription {
                     } else {
                         "custom attribute"
                     },
-                    attribute.attribute_type
+                    attribute.attribute_type,
+                    attribute.matching_rule.ldap_name()
                 )
                 .into_bytes()
                 .to_vec(),
@@ -460,6 +580,23 @@ impl LdapSchemaDescription {
         formatted_list
     }
 
+    // The auxiliary object class of the OpenSSH LDAP public key schema, only present when some
+    // user attributes hold SSH public keys.
+    pub fn formatted_ssh_public_key_object_class(&self) -> Option<Vec<u8>> {
+        let attributes = get_ssh_public_key_attributes(&self.base);
+        if attributes.is_empty() {
+            return None;
+        }
+        Some(
+            format!(
+                "( 1.3.6.1.4.1.24552.500.1.1.2.0 NAME '{}' DESC 'OpenSSH LPK objectclass' SUP top AUXILIARY MAY ( {} $ uid ) )",
+                SSH_PUBLIC_KEY_OBJECT_CLASS,
+                join(attributes, " $ "),
+            )
+            .into_bytes(),
+        )
+    }
+
     pub fn all_attributes(&self) -> AttributeList {
         let mut combined_attributes = self.schema().user_attributes.attributes.clone();
         combined_attributes.extend_from_slice(&self.schema().group_attributes.attributes);
@@ -476,14 +613,11 @@ mod tests {
     #[test]
     fn test_is_subtree() {
         let subtree1 = &[
-            ("ou".to_string(), "people".to_string()),
-            ("dc".to_string(), "example".to_string()),
-            ("dc".to_string(), "com".to_string()),
-        ];
-        let root = &[
-            ("dc".to_string(), "example".to_string()),
-            ("dc".to_string(), "com".to_string()),
+            Rdn::new("ou", "people"),
+            Rdn::new("dc", "example"),
+            Rdn::new("dc", "com"),
         ];
+        let root = &[Rdn::new("dc", "example"), Rdn::new("dc", "com")];
         assert!(is_subtree(subtree1, root));
         assert!(!is_subtree(&[], root));
     }
@@ -491,9 +625,9 @@ mod tests {
     #[test]
     fn test_parse_distinguished_name() {
         let parsed_dn = &[
-            ("ou".to_string(), "people".to_string()),
-            ("dc".to_string(), "example".to_string()),
-            ("dc".to_string(), "com".to_string()),
+            Rdn::new("ou", "people"),
+            Rdn::new("dc", "example"),
+            Rdn::new("dc", "com"),
         ];
         assert_eq!(
             parse_distinguished_name("ou=people,dc=example,dc=com").expect("parsing failed"),
@@ -504,5 +638,54 @@ mod tests {
                 .expect("parsing failed"),
             parsed_dn
         );
+        assert_eq!(
+            parse_distinguished_name("OU=People,DC=Example,0.9.2342.19200300.100.1.25=COM")
+                .expect("parsing failed"),
+            parsed_dn
+        );
+        assert_eq!(
+            parse_distinguished_name("ou=people,dc=example,").unwrap_err(),
+            LdapError {
+                code: LdapResultCode::InvalidDNSyntax,
+                message: r#"Invalid DN "ou=people,dc=example,": Empty attribute type"#.to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_get_user_or_group_id_from_distinguished_name() {
+        let base_dn = parse_distinguished_name("dc=example,dc=com").unwrap();
+        assert!(matches!(
+            get_user_or_group_id_from_distinguished_name(
+                r"uid=Doe\, John,ou=people,dc=example,dc=com",
+                &base_dn
+            ),
+            UserOrGroupName::User(user_id) if user_id.as_str() == "doe, john"
+        ));
+        assert!(matches!(
+            get_user_or_group_id_from_distinguished_name(
+                "uid=backup,ou=services,dc=example,dc=com",
+                &base_dn
+            ),
+            UserOrGroupName::ServiceAccount(user_id) if user_id.as_str() == "backup"
+        ));
+        assert!(matches!(
+            get_user_or_group_id_from_distinguished_name(
+                r"cn=\#1 B fans\ ,ou=groups,dc=example,dc=com",
+                &base_dn
+            ),
+            UserOrGroupName::Group(group_name) if group_name.as_str() == "#1 + fans "
+        ));
+        assert!(matches!(
+            get_user_or_group_id_from_distinguished_name(
+                "uid=bob+cn=bob,ou=people,dc=example,dc=com",
+                &base_dn
+            ),
+            UserOrGroupName::UnexpectedFormat
+        ));
+        assert!(matches!(
+            get_user_or_group_id_from_distinguished_name("uid=bob,ou=people,dc=other", &base_dn),
+            UserOrGroupName::BadSubStree
+        ));
     }
 }
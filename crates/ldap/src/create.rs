@@ -1,35 +1,68 @@
 use crate::{
     core::{
         error::{LdapError, LdapResult},
-        utils::{LdapInfo, UserOrGroupName, get_user_or_group_id_from_distinguished_name},
+        group::{AD_GROUP_OBJECT_CLASSES, get_default_group_object_classes},
+        user::{AD_USER_OBJECT_CLASSES, get_default_user_object_classes},
+        utils::{
+            GroupFieldType, LdapInfo, UserFieldType, UserOrGroupName,
+            get_user_id_from_distinguished_name_or_plain_name,
+            get_user_or_group_id_from_distinguished_name, map_group_field, map_user_field,
+        },
     },
     handler::make_add_response,
+    password,
 };
 use ldap3_proto::proto::{
     LdapAddRequest, LdapAttribute, LdapOp, LdapPartialAttribute, LdapResultCode,
 };
-use lldap_access_control::AdminBackendHandler;
+use lldap_access_control::{AdminBackendHandler, UserReadableBackendHandler};
 use lldap_domain::{
     deserialize,
+    public_schema::PublicSchema,
     requests::{CreateGroupRequest, CreateUserRequest},
-    types::{Attribute, AttributeName, AttributeType, Email, GroupName, UserId},
+    types::{
+        Attribute, AttributeName, AttributeType, Email, GroupName, JpegPhoto, LdapObjectClass,
+        LegacyPasswordHash, UserId,
+    },
 };
-use std::collections::HashMap;
-use tracing::instrument;
+use lldap_domain_handlers::handler::UserRequestFilter;
+use lldap_domain_model::model::UserColumn;
+use lldap_opaque_handler::OpaqueHandler;
+use tracing::{debug, instrument, warn};
 
 #[instrument(skip_all, level = "debug")]
 pub(crate) async fn create_user_or_group(
     backend_handler: &impl AdminBackendHandler,
+    opaque_handler: &impl OpaqueHandler,
     ldap_info: &LdapInfo,
     request: LdapAddRequest,
 ) -> LdapResult<Vec<LdapOp>> {
     let base_dn_str = &ldap_info.base_dn_str;
     match get_user_or_group_id_from_distinguished_name(&request.dn, &ldap_info.base_dn) {
         UserOrGroupName::User(user_id) => {
-            create_user(backend_handler, user_id, request.attributes).await
+            create_user(
+                backend_handler,
+                opaque_handler,
+                ldap_info,
+                user_id,
+                false,
+                request.attributes,
+            )
+            .await
+        }
+        UserOrGroupName::ServiceAccount(user_id) => {
+            create_user(
+                backend_handler,
+                opaque_handler,
+                ldap_info,
+                user_id,
+                true,
+                request.attributes,
+            )
+            .await
         }
         UserOrGroupName::Group(group_name) => {
-            create_group(backend_handler, group_name, request.attributes).await
+            create_group(backend_handler, ldap_info, group_name, request.attributes).await
         }
         err => Err(err.into_ldap_error(
             &request.dn,
@@ -38,98 +71,219 @@ pub(crate) async fn create_user_or_group(
     }
 }
 
+fn decode_attribute_value(val: &[u8]) -> LdapResult<String> {
+    std::str::from_utf8(val)
+        .map_err(|e| LdapError {
+            code: LdapResultCode::ConstraintViolation,
+            message: format!("Attribute value is invalid UTF-8: {e:#?} (value {val:?})"),
+        })
+        .map(str::to_owned)
+}
+
+fn get_single_value(mut attr: LdapPartialAttribute) -> LdapResult<Vec<u8>> {
+    if attr.vals.len() > 1 {
+        return Err(LdapError {
+            code: LdapResultCode::ConstraintViolation,
+            message: format!("Expected a single value for attribute {}", attr.atype),
+        });
+    }
+    attr.vals.pop().ok_or_else(|| LdapError {
+        code: LdapResultCode::ConstraintViolation,
+        message: format!("Missing value for attribute {}", attr.atype),
+    })
+}
+
+fn get_single_string_value(attr: LdapPartialAttribute) -> LdapResult<String> {
+    decode_attribute_value(&get_single_value(attr)?)
+}
+
+/// Converts the raw LDAP values to the string representation expected by
+/// `deserialize_attribute_value`.
+fn to_string_value(typ: AttributeType, val: Vec<u8>) -> LdapResult<String> {
+    if typ == AttributeType::JpegPhoto && std::str::from_utf8(&val).is_err() {
+        // Most LDAP clients send the raw JPEG bytes, while the domain expects base64.
+        return JpegPhoto::try_from(val)
+            .map(|photo| String::from(&photo))
+            .map_err(|e| LdapError {
+                code: LdapResultCode::InvalidAttributeSyntax,
+                message: format!("Provided image is not a valid JPEG: {e:#}"),
+            });
+    }
+    decode_attribute_value(&val)
+}
+
+fn make_attribute(
+    name: AttributeName,
+    typ: AttributeType,
+    is_list: bool,
+    attr: LdapPartialAttribute,
+) -> LdapResult<Attribute> {
+    let values = attr
+        .vals
+        .into_iter()
+        .map(|val| to_string_value(typ, val))
+        .collect::<LdapResult<Vec<_>>>()?;
+    if values.is_empty() {
+        return Err(LdapError {
+            code: LdapResultCode::ConstraintViolation,
+            message: format!("Missing value for attribute {}", attr.atype),
+        });
+    }
+    let value =
+        deserialize::deserialize_attribute_value(&values, typ, is_list).map_err(|e| LdapError {
+            code: LdapResultCode::ConstraintViolation,
+            message: format!("Invalid value for attribute {}: {e:#}", attr.atype),
+        })?;
+    Ok(Attribute { name, value })
+}
+
+fn check_object_classes(
+    attr: &LdapPartialAttribute,
+    mut allowed_classes: Vec<LdapObjectClass>,
+) -> LdapResult<()> {
+    allowed_classes.push(LdapObjectClass::from("top"));
+    for val in &attr.vals {
+        let class = LdapObjectClass::from(decode_attribute_value(val)?);
+        if !allowed_classes.contains(&class) {
+            return Err(LdapError {
+                code: LdapResultCode::ObjectClassViolation,
+                message: format!("Unsupported object class: {class}"),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn make_read_only_error(attr: &LdapPartialAttribute) -> LdapError {
+    LdapError {
+        code: LdapResultCode::ConstraintViolation,
+        message: format!("Attribute {} cannot be set", attr.atype),
+    }
+}
+
+fn make_unknown_attribute_error(attr: &LdapPartialAttribute) -> LdapError {
+    LdapError {
+        code: LdapResultCode::UndefinedAttributeType,
+        message: format!("Unknown attribute: {}", attr.atype),
+    }
+}
+
+async fn get_schema(backend_handler: &impl AdminBackendHandler) -> LdapResult<PublicSchema> {
+    // Both `UserReadableBackendHandler` and `ReadSchemaBackendHandler` provide `get_schema`.
+    UserReadableBackendHandler::get_schema(backend_handler)
+        .await
+        .map_err(|e| LdapError {
+            code: LdapResultCode::OperationsError,
+            message: format!("Unable to get schema: {e:#}"),
+        })
+}
+
 #[instrument(skip_all, level = "debug")]
 async fn create_user(
     backend_handler: &impl AdminBackendHandler,
+    opaque_handler: &impl OpaqueHandler,
+    ldap_info: &LdapInfo,
     user_id: UserId,
+    is_service_account: bool,
     attributes: Vec<LdapAttribute>,
 ) -> LdapResult<Vec<LdapOp>> {
-    fn parse_attribute(mut attr: LdapPartialAttribute) -> LdapResult<(String, Vec<u8>)> {
-        if attr.vals.len() > 1 {
-            Err(LdapError {
-                code: LdapResultCode::ConstraintViolation,
-                message: format!("Expected a single value for attribute {}", attr.atype),
-            })
-        } else {
-            attr.atype.make_ascii_lowercase();
-            match attr.vals.pop() {
-                Some(val) => Ok((attr.atype, val)),
-                None => Err(LdapError {
-                    code: LdapResultCode::ConstraintViolation,
-                    message: format!("Missing value for attribute {}", attr.atype),
-                }),
-            }
+    let schema = get_schema(backend_handler).await?;
+    let mut email = None;
+    let mut display_name = None;
+    let mut password = None;
+    let mut new_user_attributes: Vec<Attribute> = Vec::new();
+    for attr in attributes {
+        let name = AttributeName::from(attr.atype.as_str());
+        if name.as_str() == "userpassword" {
+            password = Some(get_single_value(attr)?);
+            continue;
         }
-    }
-    let attributes: HashMap<String, Vec<u8>> = attributes
-        .into_iter()
-        .filter(|a| !a.atype.eq_ignore_ascii_case("objectclass"))
-        .map(parse_attribute)
-        .collect::<LdapResult<_>>()?;
-    fn decode_attribute_value(val: &[u8]) -> LdapResult<String> {
-        std::str::from_utf8(val)
-            .map_err(|e| LdapError {
-                code: LdapResultCode::ConstraintViolation,
-                message: format!("Attribute value is invalid UTF-8: {e:#?} (value {val:?})"),
-            })
-            .map(str::to_owned)
-    }
-    let get_attribute = |name| {
-        attributes
-            .get(name)
-            .map(Vec::as_slice)
-            .map(decode_attribute_value)
-    };
-    let make_encoded_attribute = |name: &str, typ: AttributeType, value: String| {
-        Ok(Attribute {
-            name: AttributeName::from(name),
-            value: deserialize::deserialize_attribute_value(&[value], typ, false).map_err(|e| {
-                LdapError {
-                    code: LdapResultCode::ConstraintViolation,
-                    message: format!("Invalid attribute value: {e}"),
+        match map_user_field(&name, &schema, ldap_info.ad_compatibility) {
+            UserFieldType::ObjectClass => {
+                let mut classes = get_default_user_object_classes();
+                classes.extend(schema.get_schema().extra_user_object_classes.clone());
+                if ldap_info.ad_compatibility {
+                    classes.extend(AD_USER_OBJECT_CLASSES.iter().map(|&c| c.into()));
                 }
-            })?,
-        })
-    };
-    let mut new_user_attributes: Vec<Attribute> = Vec::new();
-    if let Some(first_name) = get_attribute("givenname").transpose()? {
-        new_user_attributes.push(make_encoded_attribute(
-            "first_name",
-            AttributeType::String,
-            first_name,
-        )?);
-    }
-    if let Some(last_name) = get_attribute("sn").transpose()? {
-        new_user_attributes.push(make_encoded_attribute(
-            "last_name",
-            AttributeType::String,
-            last_name,
-        )?);
+                check_object_classes(&attr, classes)?;
+            }
+            // The user id is taken from the DN.
+            UserFieldType::PrimaryField(UserColumn::UserId) => {}
+            UserFieldType::PrimaryField(UserColumn::Email) => {
+                email = Some(Email::from(get_single_string_value(attr)?));
+            }
+            UserFieldType::PrimaryField(UserColumn::DisplayName) => {
+                display_name = Some(get_single_string_value(attr)?);
+            }
+            UserFieldType::Attribute(name, typ, is_list) => {
+                if schema
+                    .get_schema()
+                    .user_attributes
+                    .get_attribute_schema(&name)
+                    .is_some_and(|a| a.is_readonly)
+                {
+                    return Err(make_read_only_error(&attr));
+                }
+                new_user_attributes.push(make_attribute(name, typ, is_list, attr)?);
+            }
+            UserFieldType::NoMatch => {
+                if !ldap_info.ignored_user_attributes.contains(&name) {
+                    return Err(make_unknown_attribute_error(&attr));
+                }
+            }
+            UserFieldType::PrimaryField(_)
+            | UserFieldType::MemberOf
+            | UserFieldType::Dn
+            | UserFieldType::EntryDn
+            | UserFieldType::UserPrincipalName
+            | UserFieldType::ObjectGuid
+            | UserFieldType::UserAccountControl
+            | UserFieldType::AccountLock
+            | UserFieldType::AccountLockedTime
+            | UserFieldType::ShadowExpire
+            | UserFieldType::AccountExpires => return Err(make_read_only_error(&attr)),
+        }
     }
-    if let Some(avatar) = get_attribute("avatar").transpose()? {
-        new_user_attributes.push(make_encoded_attribute(
-            "avatar",
-            AttributeType::JpegPhoto,
-            avatar,
-        )?);
+    // Hashed passwords, e.g. from an export of another directory, are checked on the first login.
+    let legacy_password_hash = password
+        .as_deref()
+        .and_then(|p| std::str::from_utf8(p).ok())
+        .and_then(|p| LegacyPasswordHash::try_from(p).ok());
+    if legacy_password_hash.is_some() {
+        debug!("Importing the password hash");
+        password = None;
     }
     backend_handler
         .create_user(CreateUserRequest {
-            user_id,
-            email: Email::from(
-                get_attribute("mail")
-                    .or_else(|| get_attribute("email"))
-                    .transpose()?
-                    .unwrap_or_default(),
-            ),
-            display_name: get_attribute("cn").transpose()?,
+            user_id: user_id.clone(),
+            email: email.unwrap_or_default(),
+            display_name,
             attributes: new_user_attributes,
+            legacy_password_hash,
+            is_service_account,
+            allowed_sources: Vec::new(),
         })
         .await
         .map_err(|e| LdapError {
             code: LdapResultCode::OperationsError,
             message: format!("Could not create user: {e:#?}"),
         })?;
+    if let Some(password) = password {
+        debug!("Setting the initial password");
+        if let Err(e) = password::change_password(opaque_handler, user_id.clone(), &password).await
+        {
+            // Remove the user so that the client can retry the whole operation.
+            if let Err(delete_error) = backend_handler.delete_user(&user_id).await {
+                warn!(
+                    "Could not remove the user {user_id} after a failed creation: {delete_error:#?}"
+                );
+            }
+            return Err(LdapError {
+                code: LdapResultCode::Other,
+                message: format!("Could not set the password: {e:#?}"),
+            });
+        }
+    }
     Ok(vec![make_add_response(
         LdapResultCode::Success,
         String::new(),
@@ -139,19 +293,115 @@ async fn create_user(
 #[instrument(skip_all, level = "debug")]
 async fn create_group(
     backend_handler: &impl AdminBackendHandler,
+    ldap_info: &LdapInfo,
     group_name: GroupName,
-    _attributes: Vec<LdapAttribute>,
+    attributes: Vec<LdapAttribute>,
 ) -> LdapResult<Vec<LdapOp>> {
-    backend_handler
+    let schema = get_schema(backend_handler).await?;
+    let mut members: Vec<UserId> = Vec::new();
+    let mut new_group_attributes: Vec<Attribute> = Vec::new();
+    for attr in attributes {
+        let name = AttributeName::from(attr.atype.as_str());
+        match map_group_field(&name, &schema, ldap_info.ad_compatibility) {
+            GroupFieldType::ObjectClass => {
+                let mut classes = get_default_group_object_classes();
+                classes.extend(schema.get_schema().extra_group_object_classes.clone());
+                if ldap_info.ad_compatibility {
+                    classes.extend(AD_GROUP_OBJECT_CLASSES.iter().map(|&c| c.into()));
+                }
+                check_object_classes(&attr, classes)?;
+            }
+            // The group name is taken from the DN.
+            GroupFieldType::DisplayName => {}
+            GroupFieldType::Member => {
+                for val in &attr.vals {
+                    let user_id = get_user_id_from_distinguished_name_or_plain_name(
+                        &decode_attribute_value(val)?.to_ascii_lowercase(),
+                        &ldap_info.base_dn,
+                        &ldap_info.base_dn_str,
+                    )?;
+                    if !members.contains(&user_id) {
+                        members.push(user_id);
+                    }
+                }
+            }
+            GroupFieldType::Attribute(name, typ, is_list) => {
+                if schema
+                    .get_schema()
+                    .group_attributes
+                    .get_attribute_schema(&name)
+                    .is_some_and(|a| a.is_readonly)
+                {
+                    return Err(make_read_only_error(&attr));
+                }
+                new_group_attributes.push(make_attribute(name, typ, is_list, attr)?);
+            }
+            GroupFieldType::NoMatch => {
+                if !ldap_info.ignored_group_attributes.contains(&name) {
+                    return Err(make_unknown_attribute_error(&attr));
+                }
+            }
+            GroupFieldType::GroupId
+            | GroupFieldType::CreationDate
+            | GroupFieldType::MemberUrl
+            | GroupFieldType::ObjectGuid
+            | GroupFieldType::Uuid
+            | GroupFieldType::Dn
+            | GroupFieldType::EntryDn => return Err(make_read_only_error(&attr)),
+        }
+    }
+    if !members.is_empty() {
+        // Check the members before creating anything, to avoid leaving a half-created group.
+        let existing_users = backend_handler
+            .list_users(
+                Some(UserRequestFilter::Or(
+                    members
+                        .iter()
+                        .cloned()
+                        .map(UserRequestFilter::UserId)
+                        .collect(),
+                )),
+                false,
+            )
+            .await
+            .map_err(|e| LdapError {
+                code: LdapResultCode::OperationsError,
+                message: format!("Error while finding the group members: {e:#?}"),
+            })?;
+        if let Some(missing) = members
+            .iter()
+            .find(|m| !existing_users.iter().any(|u| &u.user.user_id == *m))
+        {
+            return Err(LdapError {
+                code: LdapResultCode::ConstraintViolation,
+                message: format!("No such user: {missing}"),
+            });
+        }
+    }
+    let group_id = backend_handler
         .create_group(CreateGroupRequest {
             display_name: group_name,
-            attributes: Vec::new(),
+            attributes: new_group_attributes,
         })
         .await
         .map_err(|e| LdapError {
             code: LdapResultCode::OperationsError,
             message: format!("Could not create group: {e:#?}"),
         })?;
+    for user_id in members {
+        if let Err(e) = backend_handler.add_user_to_group(&user_id, group_id).await {
+            // Remove the group so that the client can retry the whole operation.
+            if let Err(delete_error) = backend_handler.delete_group(group_id).await {
+                warn!(
+                    "Could not remove the group {group_id:?} after a failed creation: {delete_error:#?}"
+                );
+            }
+            return Err(LdapError {
+                code: LdapResultCode::OperationsError,
+                message: format!("Could not add member {user_id}: {e:#?}"),
+            });
+        }
+    }
     Ok(vec![make_add_response(
         LdapResultCode::Success,
         String::new(),
@@ -161,8 +411,14 @@ async fn create_group(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::handler::tests::setup_bound_admin_handler;
-    use lldap_domain::types::*;
+    use crate::{
+        handler::tests::setup_bound_admin_handler, password::tests::expect_password_registration,
+    };
+    use lldap_domain::{
+        schema::{AttributeList, AttributeSchema, Schema},
+        types::*,
+    };
+    use lldap_domain_model::error::DomainError;
     use lldap_test_utils::MockTestBackendHandler;
     use mockall::predicate::eq;
     use pretty_assertions::assert_eq;
@@ -196,6 +452,35 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_create_service_account() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_create_user()
+            .with(eq(CreateUserRequest {
+                user_id: UserId::new("backup"),
+                email: "".into(),
+                is_service_account: true,
+                ..Default::default()
+            }))
+            .times(1)
+            .return_once(|_| Ok(()));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapAddRequest {
+            dn: "uid=backup,ou=services,dc=example,dc=com".to_owned(),
+            attributes: vec![LdapPartialAttribute {
+                atype: "objectClass".to_owned(),
+                vals: vec![b"inetOrgPerson".to_vec()],
+            }],
+        };
+        assert_eq!(
+            ldap_handler.create_user_or_group(request).await,
+            Ok(vec![make_add_response(
+                LdapResultCode::Success,
+                String::new()
+            )])
+        );
+    }
+
     #[tokio::test]
     async fn test_create_group() {
         let mut mock = MockTestBackendHandler::new();
@@ -261,4 +546,400 @@ mod tests {
             )])
         );
     }
+
+    fn make_schema_with_custom_attributes() -> Schema {
+        let make_attribute = |name: &str, attribute_type, is_list| AttributeSchema {
+            name: name.into(),
+            attribute_type,
+            is_list,
+            is_visible: true,
+            is_editable: true,
+            is_hardcoded: false,
+            is_readonly: false,
+            matching_rule: MatchingRule::default_for(attribute_type),
+        };
+        Schema {
+            user_attributes: AttributeList {
+                attributes: vec![
+                    make_attribute("avatar", AttributeType::JpegPhoto, false),
+                    make_attribute("nicknames", AttributeType::String, true),
+                    make_attribute("uidnumber", AttributeType::Integer, false),
+                ],
+            },
+            group_attributes: AttributeList {
+                attributes: vec![make_attribute("gidnumber", AttributeType::Integer, false)],
+            },
+            extra_user_object_classes: vec![LdapObjectClass::from("posixAccountExtra")],
+            extra_group_object_classes: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_user_with_custom_attributes() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_get_schema()
+            .returning(|| Ok(make_schema_with_custom_attributes()));
+        let photo = JpegPhoto::for_tests();
+        mock.expect_create_user()
+            .with(eq(CreateUserRequest {
+                user_id: UserId::new("bob"),
+                email: "bob@example.com".into(),
+                display_name: Some("Bob".to_string()),
+                attributes: vec![
+                    Attribute {
+                        name: "nicknames".into(),
+                        value: vec!["bobby".to_string(), "rob".to_string()].into(),
+                    },
+                    Attribute {
+                        name: "uidnumber".into(),
+                        value: 1000i64.into(),
+                    },
+                    Attribute {
+                        name: "avatar".into(),
+                        value: photo.clone().into(),
+                    },
+                ],
+                ..Default::default()
+            }))
+            .times(1)
+            .return_once(|_| Ok(()));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapAddRequest {
+            dn: "uid=bob,ou=people,dc=example,dc=com".to_owned(),
+            attributes: vec![
+                LdapPartialAttribute {
+                    atype: "objectClass".to_owned(),
+                    vals: vec![b"inetOrgPerson".to_vec(), b"posixAccountExtra".to_vec()],
+                },
+                LdapPartialAttribute {
+                    atype: "uid".to_owned(),
+                    vals: vec![b"bob".to_vec()],
+                },
+                LdapPartialAttribute {
+                    atype: "cn".to_owned(),
+                    vals: vec![b"Bob".to_vec()],
+                },
+                LdapPartialAttribute {
+                    atype: "mail".to_owned(),
+                    vals: vec![b"bob@example.com".to_vec()],
+                },
+                LdapPartialAttribute {
+                    atype: "nickNames".to_owned(),
+                    vals: vec![b"bobby".to_vec(), b"rob".to_vec()],
+                },
+                LdapPartialAttribute {
+                    atype: "uidNumber".to_owned(),
+                    vals: vec![b"1000".to_vec()],
+                },
+                LdapPartialAttribute {
+                    atype: "jpegPhoto".to_owned(),
+                    vals: vec![photo.into_bytes()],
+                },
+            ],
+        };
+        assert_eq!(
+            ldap_handler.create_user_or_group(request).await,
+            Ok(vec![make_add_response(
+                LdapResultCode::Success,
+                String::new()
+            )])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_user_with_password() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_create_user()
+            .with(eq(CreateUserRequest {
+                user_id: UserId::new("bob"),
+                email: "".into(),
+                display_name: None,
+                ..Default::default()
+            }))
+            .times(1)
+            .return_once(|_| Ok(()));
+        expect_password_registration(&mut mock, "bob");
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapAddRequest {
+            dn: "uid=bob,ou=people,dc=example,dc=com".to_owned(),
+            attributes: vec![LdapPartialAttribute {
+                atype: "userPassword".to_owned(),
+                vals: vec![b"password".to_vec()],
+            }],
+        };
+        assert_eq!(
+            ldap_handler.create_user_or_group(request).await,
+            Ok(vec![make_add_response(
+                LdapResultCode::Success,
+                String::new()
+            )])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_user_password_failure_removes_user() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_create_user().times(1).return_once(|_| Ok(()));
+        mock.expect_registration_start()
+            .times(1)
+            .return_once(|_| Err(DomainError::InternalError("boom".to_string())));
+        mock.expect_delete_user()
+            .with(eq(UserId::new("bob")))
+            .times(1)
+            .return_once(|_| Ok(()));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapAddRequest {
+            dn: "uid=bob,ou=people,dc=example,dc=com".to_owned(),
+            attributes: vec![LdapPartialAttribute {
+                atype: "userPassword".to_owned(),
+                vals: vec![b"password".to_vec()],
+            }],
+        };
+        assert_eq!(
+            ldap_handler
+                .create_user_or_group(request)
+                .await
+                .unwrap_err()
+                .code,
+            LdapResultCode::Other
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_user_with_password_hash() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_create_user()
+            .with(eq(CreateUserRequest {
+                user_id: UserId::new("bob"),
+                email: "".into(),
+                legacy_password_hash: Some(
+                    LegacyPasswordHash::try_from("{SSHA}UC5tunSkhtN5xi9e7qcCsXKJNjBzYWx0").unwrap(),
+                ),
+                ..Default::default()
+            }))
+            .times(1)
+            .return_once(|_| Ok(()));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapAddRequest {
+            dn: "uid=bob,ou=people,dc=example,dc=com".to_owned(),
+            attributes: vec![LdapPartialAttribute {
+                atype: "userPassword".to_owned(),
+                vals: vec![b"{SSHA}UC5tunSkhtN5xi9e7qcCsXKJNjBzYWx0".to_vec()],
+            }],
+        };
+        assert_eq!(
+            ldap_handler.create_user_or_group(request).await,
+            Ok(vec![make_add_response(
+                LdapResultCode::Success,
+                String::new()
+            )])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_user_unknown_object_class() {
+        let mock = MockTestBackendHandler::new();
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapAddRequest {
+            dn: "uid=bob,ou=people,dc=example,dc=com".to_owned(),
+            attributes: vec![LdapPartialAttribute {
+                atype: "objectClass".to_owned(),
+                vals: vec![b"inetOrgPerson".to_vec(), b"shadowAccount".to_vec()],
+            }],
+        };
+        assert_eq!(
+            ldap_handler.create_user_or_group(request).await,
+            Err(LdapError {
+                code: LdapResultCode::ObjectClassViolation,
+                message: "Unsupported object class: shadowAccount".to_string(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_user_unknown_attribute() {
+        let mock = MockTestBackendHandler::new();
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapAddRequest {
+            dn: "uid=bob,ou=people,dc=example,dc=com".to_owned(),
+            attributes: vec![LdapPartialAttribute {
+                atype: "homeDirectory".to_owned(),
+                vals: vec![b"/home/bob".to_vec()],
+            }],
+        };
+        assert_eq!(
+            ldap_handler.create_user_or_group(request).await,
+            Err(LdapError {
+                code: LdapResultCode::UndefinedAttributeType,
+                message: "Unknown attribute: homeDirectory".to_string(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_user_invalid_attribute_value() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_get_schema()
+            .returning(|| Ok(make_schema_with_custom_attributes()));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapAddRequest {
+            dn: "uid=bob,ou=people,dc=example,dc=com".to_owned(),
+            attributes: vec![LdapPartialAttribute {
+                atype: "uidNumber".to_owned(),
+                vals: vec![b"abc".to_vec()],
+            }],
+        };
+        assert_eq!(
+            ldap_handler
+                .create_user_or_group(request)
+                .await
+                .unwrap_err()
+                .code,
+            LdapResultCode::ConstraintViolation
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_group_with_members_and_attributes() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_get_schema()
+            .returning(|| Ok(make_schema_with_custom_attributes()));
+        mock.expect_list_users()
+            .with(
+                eq(Some(UserRequestFilter::Or(vec![
+                    UserRequestFilter::UserId(UserId::new("bob")),
+                    UserRequestFilter::UserId(UserId::new("john")),
+                ]))),
+                eq(false),
+            )
+            .times(1)
+            .return_once(|_, _| {
+                Ok(vec![
+                    UserAndGroups {
+                        user: User {
+                            user_id: UserId::new("bob"),
+                            ..Default::default()
+                        },
+                        groups: None,
+                    },
+                    UserAndGroups {
+                        user: User {
+                            user_id: UserId::new("john"),
+                            ..Default::default()
+                        },
+                        groups: None,
+                    },
+                ])
+            });
+        mock.expect_create_group()
+            .with(eq(CreateGroupRequest {
+                display_name: GroupName::new("admins"),
+                attributes: vec![Attribute {
+                    name: "gidnumber".into(),
+                    value: 500i64.into(),
+                }],
+            }))
+            .times(1)
+            .return_once(|_| Ok(GroupId(5)));
+        mock.expect_add_user_to_group()
+            .with(eq(UserId::new("bob")), eq(GroupId(5)))
+            .times(1)
+            .return_once(|_, _| Ok(()));
+        mock.expect_add_user_to_group()
+            .with(eq(UserId::new("john")), eq(GroupId(5)))
+            .times(1)
+            .return_once(|_, _| Ok(()));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapAddRequest {
+            dn: "cn=admins,ou=groups,dc=example,dc=com".to_owned(),
+            attributes: vec![
+                LdapPartialAttribute {
+                    atype: "objectClass".to_owned(),
+                    vals: vec![b"groupOfNames".to_vec()],
+                },
+                LdapPartialAttribute {
+                    atype: "member".to_owned(),
+                    vals: vec![
+                        b"uid=bob,ou=people,dc=example,dc=com".to_vec(),
+                        b"UID=John,OU=People,DC=Example,DC=com".to_vec(),
+                    ],
+                },
+                LdapPartialAttribute {
+                    atype: "gidNumber".to_owned(),
+                    vals: vec![b"500".to_vec()],
+                },
+            ],
+        };
+        assert_eq!(
+            ldap_handler.create_user_or_group(request).await,
+            Ok(vec![make_add_response(
+                LdapResultCode::Success,
+                String::new()
+            )])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_group_member_failure_removes_group() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users().times(1).return_once(|_, _| {
+            Ok(vec![UserAndGroups {
+                user: User {
+                    user_id: UserId::new("bob"),
+                    ..Default::default()
+                },
+                groups: None,
+            }])
+        });
+        mock.expect_create_group()
+            .times(1)
+            .return_once(|_| Ok(GroupId(5)));
+        mock.expect_add_user_to_group()
+            .with(eq(UserId::new("bob")), eq(GroupId(5)))
+            .times(1)
+            .return_once(|_, _| Err(DomainError::InternalError("boom".to_string())));
+        mock.expect_delete_group()
+            .with(eq(GroupId(5)))
+            .times(1)
+            .return_once(|_| Ok(()));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapAddRequest {
+            dn: "cn=admins,ou=groups,dc=example,dc=com".to_owned(),
+            attributes: vec![LdapPartialAttribute {
+                atype: "member".to_owned(),
+                vals: vec![b"uid=bob,ou=people,dc=example,dc=com".to_vec()],
+            }],
+        };
+        assert_eq!(
+            ldap_handler
+                .create_user_or_group(request)
+                .await
+                .unwrap_err()
+                .code,
+            LdapResultCode::OperationsError
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_group_unknown_member() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users()
+            .times(1)
+            .return_once(|_, _| Ok(vec![]));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapAddRequest {
+            dn: "cn=admins,ou=groups,dc=example,dc=com".to_owned(),
+            attributes: vec![LdapPartialAttribute {
+                atype: "member".to_owned(),
+                vals: vec![b"uid=bob,ou=people,dc=example,dc=com".to_vec()],
+            }],
+        };
+        assert_eq!(
+            ldap_handler.create_user_or_group(request).await,
+            Err(LdapError {
+                code: LdapResultCode::ConstraintViolation,
+                message: "No such user: bob".to_string(),
+            })
+        );
+    }
 }
@@ -1,8 +1,16 @@
 use crate::core::{
+    controls::{TREE_DELETE_OID, has_control},
     error::{LdapError, LdapResult},
-    utils::{LdapInfo, UserOrGroupName, get_user_or_group_id_from_distinguished_name},
+    user::SERVICES_OU,
+    utils::{
+        LdapInfo, UserOrGroupName, get_user_or_group_id_from_distinguished_name,
+        parse_distinguished_name,
+    },
+};
+use ldap3_proto::{
+    control::LdapControl,
+    proto::{LdapOp, LdapResult as LdapResultOp, LdapResultCode},
 };
-use ldap3_proto::proto::{LdapOp, LdapResult as LdapResultOp, LdapResultCode};
 use lldap_access_control::AdminBackendHandler;
 use lldap_domain::types::{GroupName, UserId};
 use lldap_domain_handlers::handler::GroupRequestFilter;
@@ -18,16 +26,86 @@ pub(crate) fn make_del_response(code: LdapResultCode, message: String) -> LdapOp
     })
 }
 
+fn make_no_such_object_response(matched_dn: String, message: String) -> Vec<LdapOp> {
+    vec![LdapOp::DelResponse(LdapResultOp {
+        code: LdapResultCode::NoSuchObject,
+        matcheddn: matched_dn,
+        message,
+        referral: vec![],
+    })]
+}
+
+/// The entries of the DIT that are not users or groups. They always exist and cannot be
+/// deleted themselves, but they have children.
+enum ContainerEntry {
+    Base,
+    People,
+    Services,
+    Groups,
+}
+
+fn get_container_entry(dn: &str, ldap_info: &LdapInfo) -> Option<ContainerEntry> {
+    let parts = parse_distinguished_name(dn).ok()?;
+    if parts == ldap_info.base_dn {
+        return Some(ContainerEntry::Base);
+    }
+    if parts.len() != ldap_info.base_dn.len() + 1 || parts[1..] != ldap_info.base_dn[..] {
+        return None;
+    }
+    match parts[0].single()? {
+        ("ou", "people") => Some(ContainerEntry::People),
+        ("ou", SERVICES_OU) => Some(ContainerEntry::Services),
+        ("ou", "groups") => Some(ContainerEntry::Groups),
+        _ => None,
+    }
+}
+
+/// Returns the deepest existing ancestor of a DN that doesn't exist, to be sent back as the
+/// matchedDN.
+fn get_matched_dn(dn: &str, ldap_info: &LdapInfo) -> String {
+    let Ok(parts) = parse_distinguished_name(dn) else {
+        return String::new();
+    };
+    let base_len = ldap_info.base_dn.len();
+    if parts.len() <= base_len || parts[parts.len() - base_len..] != ldap_info.base_dn[..] {
+        return String::new();
+    }
+    match parts[parts.len() - base_len - 1].single() {
+        Some(("ou", value @ ("people" | SERVICES_OU | "groups"))) => {
+            format!("ou={value},{}", ldap_info.base_dn_str)
+        }
+        _ => ldap_info.base_dn_str.clone(),
+    }
+}
+
 #[instrument(skip_all, level = "debug")]
 pub(crate) async fn delete_user_or_group(
     backend_handler: &impl AdminBackendHandler,
     ldap_info: &LdapInfo,
     request: String,
+    controls: &[LdapControl],
 ) -> LdapResult<Vec<LdapOp>> {
     let base_dn_str = &ldap_info.base_dn_str;
+    let tree_delete = has_control(controls, TREE_DELETE_OID);
+    let matched_dn = get_matched_dn(&request, ldap_info);
     match get_user_or_group_id_from_distinguished_name(&request, &ldap_info.base_dn) {
-        UserOrGroupName::User(user_id) => delete_user(backend_handler, user_id).await,
-        UserOrGroupName::Group(group_name) => delete_group(backend_handler, group_name).await,
+        UserOrGroupName::User(user_id) | UserOrGroupName::ServiceAccount(user_id) => {
+            delete_user(backend_handler, user_id, matched_dn).await
+        }
+        UserOrGroupName::Group(group_name) => {
+            delete_group(backend_handler, group_name, matched_dn).await
+        }
+        UserOrGroupName::UnexpectedFormat => match get_container_entry(&request, ldap_info) {
+            Some(container) => delete_container(ldap_info, &request, container, tree_delete),
+            None => Ok(make_no_such_object_response(
+                matched_dn,
+                format!(r#"No such object: "{request}""#),
+            )),
+        },
+        UserOrGroupName::BadSubStree => Ok(make_no_such_object_response(
+            String::new(),
+            format!(r#"No such object: "{request}" is not under "{base_dn_str}""#),
+        )),
         err => Err(err.into_ldap_error(
             &request,
             format!(r#""uid=id,ou=people,{base_dn_str}" or "uid=id,ou=groups,{base_dn_str}""#),
@@ -35,41 +113,77 @@ pub(crate) async fn delete_user_or_group(
     }
 }
 
+fn delete_container(
+    ldap_info: &LdapInfo,
+    dn: &str,
+    container: ContainerEntry,
+    tree_delete: bool,
+) -> LdapResult<Vec<LdapOp>> {
+    if !tree_delete {
+        return Err(LdapError {
+            code: LdapResultCode::NotAllowedOnNonLeaf,
+            message: format!(
+                "Entry has children, use the Tree Delete control ({TREE_DELETE_OID}) to delete them"
+            ),
+        });
+    }
+    // Deleting all the users or groups at once would also remove the admins and the built-in
+    // groups, locking everyone out: the entries have to be deleted one by one.
+    Err(LdapError {
+        code: LdapResultCode::UnwillingToPerform,
+        message: match container {
+            ContainerEntry::Base => {
+                format!("Refusing to delete the base DN {}", ldap_info.base_dn_str)
+            }
+            ContainerEntry::People | ContainerEntry::Services | ContainerEntry::Groups => {
+                format!("Refusing to delete all the entries under {dn}")
+            }
+        },
+    })
+}
+
 #[instrument(skip_all, level = "debug")]
 async fn delete_user(
     backend_handler: &impl AdminBackendHandler,
     user_id: UserId,
+    matched_dn: String,
 ) -> LdapResult<Vec<LdapOp>> {
-    backend_handler
-        .get_user_details(&user_id)
-        .await
-        .map_err(|err| match err {
-            DomainError::EntityNotFound(_) => LdapError {
-                code: LdapResultCode::NoSuchObject,
-                message: "Could not find user".to_string(),
-            },
-            e => LdapError {
+    match backend_handler.get_user_details(&user_id).await {
+        Ok(_) => {}
+        Err(DomainError::EntityNotFound(_)) => {
+            return Ok(make_no_such_object_response(
+                matched_dn,
+                "Could not find user".to_string(),
+            ));
+        }
+        Err(e) => {
+            return Err(LdapError {
                 code: LdapResultCode::OperationsError,
                 message: format!("Error while finding user: {e:?}"),
-            },
-        })?;
-    backend_handler
-        .delete_user(&user_id)
-        .await
-        .map_err(|e| LdapError {
+            });
+        }
+    }
+    match backend_handler.delete_user(&user_id).await {
+        Ok(()) => Ok(vec![make_del_response(
+            LdapResultCode::Success,
+            String::new(),
+        )]),
+        Err(DomainError::EntityNotFound(_)) => Ok(make_no_such_object_response(
+            matched_dn,
+            "Could not find user".to_string(),
+        )),
+        Err(e) => Err(LdapError {
             code: LdapResultCode::OperationsError,
             message: format!("Error while deleting user: {e:?}"),
-        })?;
-    Ok(vec![make_del_response(
-        LdapResultCode::Success,
-        String::new(),
-    )])
+        }),
+    }
 }
 
 #[instrument(skip_all, level = "debug")]
 async fn delete_group(
     backend_handler: &impl AdminBackendHandler,
     group_name: GroupName,
+    matched_dn: String,
 ) -> LdapResult<Vec<LdapOp>> {
     let groups = backend_handler
         .list_groups(Some(GroupRequestFilter::DisplayName(group_name.clone())))
@@ -78,25 +192,30 @@ async fn delete_group(
             code: LdapResultCode::OperationsError,
             message: format!("Error while finding group: {e:?}"),
         })?;
-    let group_id = groups
+    let Some(group_id) = groups
         .iter()
         .find(|g| g.display_name == group_name)
         .map(|g| g.id)
-        .ok_or_else(|| LdapError {
-            code: LdapResultCode::NoSuchObject,
-            message: "Could not find group".to_string(),
-        })?;
-    backend_handler
-        .delete_group(group_id)
-        .await
-        .map_err(|e| LdapError {
+    else {
+        return Ok(make_no_such_object_response(
+            matched_dn,
+            "Could not find group".to_string(),
+        ));
+    };
+    match backend_handler.delete_group(group_id).await {
+        Ok(()) => Ok(vec![make_del_response(
+            LdapResultCode::Success,
+            String::new(),
+        )]),
+        Err(DomainError::EntityNotFound(_)) => Ok(make_no_such_object_response(
+            matched_dn,
+            "Could not find group".to_string(),
+        )),
+        Err(e) => Err(LdapError {
             code: LdapResultCode::OperationsError,
             message: format!("Error while deleting group: {e:?}"),
-        })?;
-    Ok(vec![make_del_response(
-        LdapResultCode::Success,
-        String::new(),
-    )])
+        }),
+    }
 }
 
 #[cfg(test)]
@@ -139,6 +258,33 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_delete_service_account() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_get_user_details()
+            .with(eq(UserId::new("backup")))
+            .return_once(|_| {
+                Ok(User {
+                    user_id: UserId::new("backup"),
+                    is_service_account: true,
+                    ..Default::default()
+                })
+            });
+        mock.expect_delete_user()
+            .with(eq(UserId::new("backup")))
+            .times(1)
+            .return_once(|_| Ok(()));
+        let mut ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapOp::DelRequest("uid=backup,ou=services,dc=example,dc=com".to_owned());
+        assert_eq!(
+            ldap_handler.handle_ldap_message(request).await,
+            Some(vec![make_del_response(
+                LdapResultCode::Success,
+                String::new()
+            )])
+        );
+    }
+
     #[tokio::test]
     async fn test_delete_group() {
         let mut mock = MockTestBackendHandler::new();
@@ -154,6 +300,7 @@ mod tests {
                     uuid: uuid!("a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d8"),
                     users: Vec::new(),
                     attributes: Vec::new(),
+                    member_filter: None,
                 }])
             });
         mock.expect_delete_group()
@@ -181,10 +328,10 @@ mod tests {
         let request = LdapOp::DelRequest("uid=bob,ou=people,dc=example,dc=com".to_owned());
         assert_eq!(
             ldap_handler.handle_ldap_message(request).await,
-            Some(vec![make_del_response(
-                LdapResultCode::NoSuchObject,
+            Some(make_no_such_object_response(
+                "ou=people,dc=example,dc=com".to_string(),
                 "Could not find user".to_string()
-            )])
+            ))
         );
     }
 
@@ -243,10 +390,10 @@ mod tests {
         let request = LdapOp::DelRequest("uid=bob,ou=groups,dc=example,dc=com".to_owned());
         assert_eq!(
             ldap_handler.handle_ldap_message(request).await,
-            Some(vec![make_del_response(
-                LdapResultCode::NoSuchObject,
+            Some(make_no_such_object_response(
+                "ou=groups,dc=example,dc=com".to_string(),
                 "Could not find group".to_string()
-            )])
+            ))
         );
     }
 
@@ -284,6 +431,7 @@ mod tests {
                     uuid: uuid!("a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d8"),
                     users: Vec::new(),
                     attributes: Vec::new(),
+                    member_filter: None,
                 }])
             });
         mock.expect_delete_group()
@@ -300,4 +448,161 @@ mod tests {
             )])
         );
     }
+
+    fn make_tree_delete_control() -> LdapControl {
+        LdapControl::Unknown {
+            oid: TREE_DELETE_OID.to_string(),
+            criticality: true,
+            value: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_user_deleted_concurrently() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_get_user_details()
+            .with(eq(UserId::new("bob")))
+            .return_once(|_| {
+                Ok(User {
+                    user_id: UserId::new("bob"),
+                    ..Default::default()
+                })
+            });
+        mock.expect_delete_user()
+            .with(eq(UserId::new("bob")))
+            .times(1)
+            .return_once(|_| Err(DomainError::EntityNotFound("No such user".to_string())));
+        let mut ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapOp::DelRequest("uid=bob,ou=people,dc=example,dc=com".to_owned());
+        assert_eq!(
+            ldap_handler.handle_ldap_message(request).await,
+            Some(make_no_such_object_response(
+                "ou=people,dc=example,dc=com".to_string(),
+                "Could not find user".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_unknown_entry() {
+        let mock = MockTestBackendHandler::new();
+        let mut ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapOp::DelRequest("ou=robots,dc=example,dc=com".to_owned());
+        assert_eq!(
+            ldap_handler.handle_ldap_message(request).await,
+            Some(make_no_such_object_response(
+                "dc=example,dc=com".to_string(),
+                r#"No such object: "ou=robots,dc=example,dc=com""#.to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_outside_of_base_dn() {
+        let mock = MockTestBackendHandler::new();
+        let mut ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapOp::DelRequest("uid=bob,ou=people,dc=example,dc=org".to_owned());
+        assert_eq!(
+            ldap_handler.handle_ldap_message(request).await,
+            Some(make_no_such_object_response(
+                String::new(),
+                r#"No such object: "uid=bob,ou=people,dc=example,dc=org" is not under "dc=example,dc=com""#
+                    .to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_non_leaf_without_control() {
+        let mock = MockTestBackendHandler::new();
+        let mut ldap_handler = setup_bound_admin_handler(mock).await;
+        for dn in [
+            "ou=people,dc=example,dc=com",
+            "ou=groups,dc=example,dc=com",
+            "dc=example,dc=com",
+        ] {
+            let request = LdapOp::DelRequest(dn.to_owned());
+            assert_eq!(
+                ldap_handler.handle_ldap_message(request).await,
+                Some(vec![make_del_response(
+                    LdapResultCode::NotAllowedOnNonLeaf,
+                    "Entry has children, use the Tree Delete control (1.2.840.113556.1.4.805) to delete them"
+                        .to_string()
+                )])
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tree_delete_organizational_units() {
+        // No backend call: nothing gets deleted.
+        let mock = MockTestBackendHandler::new();
+        let mut ldap_handler = setup_bound_admin_handler(mock).await;
+        for dn in [
+            "ou=people,dc=example,dc=com",
+            "ou=services,dc=example,dc=com",
+            "ou=groups,dc=example,dc=com",
+        ] {
+            let request = LdapOp::DelRequest(dn.to_owned());
+            assert_eq!(
+                ldap_handler
+                    .handle_ldap_message_with_controls(request, &[make_tree_delete_control()])
+                    .await,
+                Some((
+                    vec![make_del_response(
+                        LdapResultCode::UnwillingToPerform,
+                        format!("Refusing to delete all the entries under {dn}")
+                    )],
+                    vec![]
+                ))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tree_delete_base_dn() {
+        let mock = MockTestBackendHandler::new();
+        let mut ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapOp::DelRequest("dc=example,dc=com".to_owned());
+        assert_eq!(
+            ldap_handler
+                .handle_ldap_message_with_controls(request, &[make_tree_delete_control()])
+                .await,
+            Some((
+                vec![make_del_response(
+                    LdapResultCode::UnwillingToPerform,
+                    "Refusing to delete the base DN dc=example,dc=com".to_string()
+                )],
+                vec![]
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tree_delete_leaf() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_get_user_details()
+            .with(eq(UserId::new("bob")))
+            .return_once(|_| {
+                Ok(User {
+                    user_id: UserId::new("bob"),
+                    ..Default::default()
+                })
+            });
+        mock.expect_delete_user()
+            .with(eq(UserId::new("bob")))
+            .times(1)
+            .return_once(|_| Ok(()));
+        let mut ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapOp::DelRequest("uid=bob,ou=people,dc=example,dc=com".to_owned());
+        assert_eq!(
+            ldap_handler
+                .handle_ldap_message_with_controls(request, &[make_tree_delete_control()])
+                .await,
+            Some((
+                vec![make_del_response(LdapResultCode::Success, String::new())],
+                vec![]
+            ))
+        );
+    }
 }
@@ -1,8 +1,13 @@
 use crate::{
     compare,
     core::{
+        controls::{
+            PASSWORD_POLICY_ERROR_CHANGE_AFTER_RESET, PASSWORD_POLICY_ERROR_PASSWORD_EXPIRED,
+            PASSWORD_POLICY_OID, has_control, make_password_policy_response,
+        },
+        dn::escape_dn_value,
         error::{LdapError, LdapResult},
-        utils::{LdapInfo, parse_distinguished_name},
+        utils::{BindMfaPolicy, LdapInfo, parse_distinguished_name},
     },
     create, delete, modify,
     password::{self, do_password_modification},
@@ -10,17 +15,26 @@ use crate::{
         self, is_root_dse_request, is_subschema_entry_request, make_ldap_subschema_entry,
         make_search_error, make_search_request, make_search_success, root_dse_response,
     },
+    subschema,
 };
-use ldap3_proto::proto::{
-    LdapAddRequest, LdapBindRequest, LdapBindResponse, LdapCompareRequest, LdapExtendedRequest,
-    LdapExtendedResponse, LdapFilter, LdapModifyRequest, LdapOp, LdapPasswordModifyRequest,
-    LdapResult as LdapResultOp, LdapResultCode, LdapSearchRequest, OID_PASSWORD_MODIFY, OID_WHOAMI,
+use futures_util::{Sink, SinkExt, Stream, StreamExt, stream};
+use ldap3_proto::{
+    control::LdapControl,
+    proto::{
+        LdapAddRequest, LdapBindRequest, LdapBindResponse, LdapCompareRequest, LdapExtendedRequest,
+        LdapExtendedResponse, LdapFilter, LdapModifyRequest, LdapOp, LdapPasswordModifyRequest,
+        LdapResult as LdapResultOp, LdapResultCode, LdapSearchRequest, OID_PASSWORD_MODIFY,
+        OID_WHOAMI,
+    },
 };
-use lldap_access_control::AccessControlledBackendHandler;
+use lldap_access_control::{AccessControlledBackendHandler, UserRestrictedListerBackendHandler};
 use lldap_auth::access_control::ValidationResults;
 use lldap_domain::{public_schema::PublicSchema, types::AttributeName};
-use lldap_domain_handlers::handler::{BackendHandler, LoginHandler, ReadSchemaBackendHandler};
+use lldap_domain_handlers::handler::{
+    BackendHandler, LoginHandler, PasswordState, ReadSchemaBackendHandler,
+};
 use lldap_opaque_handler::OpaqueHandler;
+use std::net::IpAddr;
 use tracing::{debug, instrument};
 
 use super::delete::make_del_response;
@@ -58,9 +72,13 @@ pub(crate) fn make_modify_response(code: LdapResultCode, message: String) -> Lda
 
 pub struct LdapHandler<Backend> {
     user_info: Option<ValidationResults>,
+    /// The state of the bound user's password: until it is changed, nothing else is allowed.
+    password_state: PasswordState,
     backend_handler: AccessControlledBackendHandler<Backend>,
     ldap_info: LdapInfo,
     session_uuid: uuid::Uuid,
+    /// The address of the LDAP client, to check the sources allowed for its app passwords.
+    client_address: Option<IpAddr>,
 }
 
 impl<Backend> LdapHandler<Backend> {
@@ -75,8 +93,8 @@ impl<Backend: LoginHandler> LdapHandler<Backend> {
     }
 }
 
-impl<Backend: OpaqueHandler> LdapHandler<Backend> {
-    pub fn get_opaque_handler(&self) -> &(impl OpaqueHandler + use<Backend>) {
+impl<Backend: OpaqueHandler + LoginHandler> LdapHandler<Backend> {
+    pub fn get_opaque_handler(&self) -> &(impl OpaqueHandler + LoginHandler + use<Backend>) {
         self.backend_handler.unsafe_get_handler()
     }
 }
@@ -92,11 +110,14 @@ impl<Backend: BackendHandler + LoginHandler + OpaqueHandler> LdapHandler<Backend
         mut ldap_base_dn: String,
         ignored_user_attributes: Vec<AttributeName>,
         ignored_group_attributes: Vec<AttributeName>,
+        ad_compatibility: bool,
+        bind_mfa_policy: BindMfaPolicy,
         session_uuid: uuid::Uuid,
     ) -> Self {
         ldap_base_dn.make_ascii_lowercase();
         Self {
             user_info: None,
+            password_state: PasswordState::Valid,
             backend_handler,
             ldap_info: LdapInfo {
                 base_dn: parse_distinguished_name(&ldap_base_dn).unwrap_or_else(|_| {
@@ -105,11 +126,19 @@ impl<Backend: BackendHandler + LoginHandler + OpaqueHandler> LdapHandler<Backend
                 base_dn_str: ldap_base_dn,
                 ignored_user_attributes,
                 ignored_group_attributes,
+                ad_compatibility,
+                bind_mfa_policy,
             },
             session_uuid,
+            client_address: None,
         }
     }
 
+    pub fn with_client_address(mut self, client_address: Option<IpAddr>) -> Self {
+        self.client_address = client_address;
+        self
+    }
+
     #[cfg(test)]
     pub fn new_for_tests(backend_handler: Backend, ldap_base_dn: &str) -> Self {
         Self::new(
@@ -117,10 +146,24 @@ impl<Backend: BackendHandler + LoginHandler + OpaqueHandler> LdapHandler<Backend
             ldap_base_dn.to_string(),
             vec![],
             vec![],
+            false,
+            BindMfaPolicy::default(),
             uuid::Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap(),
         )
     }
 
+    #[cfg(test)]
+    pub fn with_ad_compatibility(mut self) -> Self {
+        self.ldap_info.ad_compatibility = true;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_bind_mfa_policy(mut self, bind_mfa_policy: BindMfaPolicy) -> Self {
+        self.ldap_info.bind_mfa_policy = bind_mfa_policy;
+        self
+    }
+
     fn get_credentials(&self) -> Credentials<'_> {
         match self.user_info.as_ref() {
             Some(user_info) => Credentials::Bound(user_info),
@@ -132,12 +175,66 @@ impl<Backend: BackendHandler + LoginHandler + OpaqueHandler> LdapHandler<Backend
     }
 
     pub async fn do_search_or_dse(&self, request: &LdapSearchRequest) -> LdapResult<Vec<LdapOp>> {
+        Ok(self.do_search_or_dse_with_controls(request, &[]).await?.0)
+    }
+
+    pub async fn do_search_or_dse_with_controls(
+        &self,
+        request: &LdapSearchRequest,
+        controls: &[LdapControl],
+    ) -> LdapResult<(Vec<LdapOp>, Vec<LdapControl>)> {
+        if let Some(results) = self.do_dse(request).await? {
+            return Ok((results, Vec::new()));
+        }
+        self.do_search(request, controls).await
+    }
+
+    /// Same as `do_search_or_dse_with_controls`, but the results are sent to `sink` as they come
+    /// from the backend instead of being buffered. The response controls are sent along with the
+    /// `SearchResultDone`.
+    pub async fn stream_search_or_dse<S>(
+        &self,
+        request: &LdapSearchRequest,
+        controls: &[LdapControl],
+        sink: &mut S,
+    ) -> Result<(), S::Error>
+    where
+        S: Sink<(LdapOp, Vec<LdapControl>)> + Unpin,
+    {
+        let result = async {
+            if let Some(results) = self.do_dse(request).await? {
+                return Ok(send_responses(sink, stream::iter(results), Vec::new()).await);
+            }
+            let backend_handler = self.get_lister_handler()?;
+            let schema = search::get_search_schema(&backend_handler).await?;
+            let (results, response_controls) = search::do_search_with_controls(
+                &backend_handler,
+                &self.ldap_info,
+                &schema,
+                request,
+                controls,
+            )
+            .await?;
+            Ok::<_, LdapError>(send_responses(sink, results, response_controls).await)
+        }
+        .await;
+        match result {
+            Ok(sent) => sent,
+            Err(e) => {
+                sink.feed((make_search_error(e.code, e.message), Vec::new()))
+                    .await
+            }
+        }
+    }
+
+    /// Handles the root DSE and the subschema requests.
+    async fn do_dse(&self, request: &LdapSearchRequest) -> LdapResult<Option<Vec<LdapOp>>> {
         if is_root_dse_request(request) {
             debug!("rootDSE request");
-            return Ok(vec![
-                root_dse_response(&self.ldap_info.base_dn_str),
+            return Ok(Some(vec![
+                root_dse_response(&self.ldap_info.base_dn_str, self.ldap_info.ad_compatibility),
                 make_search_success(),
-            ]);
+            ]));
         } else if is_subschema_entry_request(request) {
             // See RFC4512 section 4.4 "Subschema discovery"
             debug!("Schema request");
@@ -154,41 +251,65 @@ impl<Backend: BackendHandler + LoginHandler + OpaqueHandler> LdapHandler<Backend
                 code: LdapResultCode::OperationsError,
                 message: format!("Unable to get schema: {e:#}"),
             })?;
-            return Ok(vec![
+            return Ok(Some(vec![
                 make_ldap_subschema_entry(PublicSchema::from(schema)),
                 make_search_success(),
-            ]);
+            ]));
         }
-        self.do_search(request).await
+        Ok(None)
     }
 
-    #[instrument(skip_all, level = "debug")]
-    async fn do_search(&self, request: &LdapSearchRequest) -> LdapResult<Vec<LdapOp>> {
+    fn get_lister_handler(&self) -> LdapResult<UserRestrictedListerBackendHandler<'_, Backend>> {
         let user_info = self.user_info.as_ref().ok_or_else(|| LdapError {
             code: LdapResultCode::InsufficentAccessRights,
             message: "No user currently bound".to_string(),
         })?;
-        let backend_handler = self
+        Ok(self
             .backend_handler
-            .get_user_restricted_lister_handler(user_info);
-        search::do_search(&backend_handler, &self.ldap_info, request).await
+            .get_user_restricted_lister_handler(user_info))
+    }
+
+    #[instrument(skip_all, level = "debug")]
+    async fn do_search(
+        &self,
+        request: &LdapSearchRequest,
+        controls: &[LdapControl],
+    ) -> LdapResult<(Vec<LdapOp>, Vec<LdapControl>)> {
+        let backend_handler = self.get_lister_handler()?;
+        let schema = search::get_search_schema(&backend_handler).await?;
+        let (results, response_controls) = search::do_search_with_controls(
+            &backend_handler,
+            &self.ldap_info,
+            &schema,
+            request,
+            controls,
+        )
+        .await?;
+        Ok((results.collect().await, response_controls))
     }
 
     #[instrument(skip_all, level = "debug", fields(dn = %request.dn))]
     pub async fn do_bind(&mut self, request: &LdapBindRequest) -> Vec<LdapOp> {
-        let (code, message) =
-            match password::do_bind(&self.ldap_info, request, self.get_login_handler()).await {
-                Ok(user_id) => {
-                    self.user_info = self
-                        .backend_handler
-                        .get_permissions_for_user(user_id)
-                        .await
-                        .ok();
-                    debug!("Success!");
-                    (LdapResultCode::Success, "".to_string())
-                }
-                Err(err) => (err.code, err.message),
-            };
+        let (code, message) = match password::do_bind(
+            &self.ldap_info,
+            request,
+            self.client_address,
+            self.backend_handler.unsafe_get_handler(),
+        )
+        .await
+        {
+            Ok((user_id, password_state)) => {
+                self.user_info = self
+                    .backend_handler
+                    .get_permissions_for_user(user_id)
+                    .await
+                    .ok();
+                self.password_state = password_state;
+                debug!("Success!");
+                (LdapResultCode::Success, "".to_string())
+            }
+            Err(err) => (err.code, err.message),
+        };
         vec![LdapOp::BindResponse(LdapBindResponse {
             res: LdapResultOp {
                 code,
@@ -231,7 +352,7 @@ impl<Backend: BackendHandler + LoginHandler + OpaqueHandler> LdapHandler<Backend
                     .map(|user_info| {
                         format!(
                             "dn:uid={},ou=people,{}",
-                            user_info.user.as_str(),
+                            escape_dn_value(user_info.user.as_str()),
                             self.ldap_info.base_dn_str
                         )
                     })
@@ -245,12 +366,33 @@ impl<Backend: BackendHandler + LoginHandler + OpaqueHandler> LdapHandler<Backend
         }
     }
 
+    /// Checks whether the bound user changed their password.
+    async fn refresh_password_state(&mut self) {
+        let Some(user_info) = &self.user_info else {
+            return;
+        };
+        match self
+            .get_login_handler()
+            .get_password_state(&user_info.user)
+            .await
+        {
+            Ok(password_state) => self.password_state = password_state,
+            Err(e) => debug!("Could not get the password state: {e:#}"),
+        }
+    }
+
     #[instrument(skip_all, level = "debug", fields(dn = %request.dn))]
     pub async fn do_modify_request(&self, request: &LdapModifyRequest) -> Vec<LdapOp> {
         let credentials = match self.get_credentials() {
             Credentials::Bound(cred) => cred,
             Credentials::Unbound(err) => return err,
         };
+        if subschema::is_subschema_dn(&request.dn) {
+            return self
+                .modify_subschema(request)
+                .await
+                .unwrap_or_else(|e: LdapError| vec![make_modify_response(e.code, e.message)]);
+        }
         modify::handle_modify_request(
             self.get_opaque_handler(),
             |credentials, user_id| {
@@ -265,6 +407,19 @@ impl<Backend: BackendHandler + LoginHandler + OpaqueHandler> LdapHandler<Backend
         .unwrap_or_else(|e: LdapError| vec![make_modify_response(e.code, e.message)])
     }
 
+    #[instrument(skip_all, level = "debug")]
+    async fn modify_subschema(&self, request: &LdapModifyRequest) -> LdapResult<Vec<LdapOp>> {
+        let backend_handler = self
+            .user_info
+            .as_ref()
+            .and_then(|u| self.backend_handler.get_admin_handler(u))
+            .ok_or_else(|| LdapError {
+                code: LdapResultCode::InsufficentAccessRights,
+                message: "Unauthorized schema modification".to_string(),
+            })?;
+        subschema::modify_subschema(backend_handler, request).await
+    }
+
     #[instrument(skip_all, level = "debug")]
     pub async fn create_user_or_group(&self, request: LdapAddRequest) -> LdapResult<Vec<LdapOp>> {
         let backend_handler = self
@@ -275,11 +430,21 @@ impl<Backend: BackendHandler + LoginHandler + OpaqueHandler> LdapHandler<Backend
                 code: LdapResultCode::InsufficentAccessRights,
                 message: "Unauthorized write".to_string(),
             })?;
-        create::create_user_or_group(backend_handler, &self.ldap_info, request).await
+        create::create_user_or_group(
+            backend_handler,
+            self.get_opaque_handler(),
+            &self.ldap_info,
+            request,
+        )
+        .await
     }
 
     #[instrument(skip_all, level = "debug")]
-    pub async fn delete_user_or_group(&self, request: String) -> LdapResult<Vec<LdapOp>> {
+    pub async fn delete_user_or_group(
+        &self,
+        request: String,
+        controls: &[LdapControl],
+    ) -> LdapResult<Vec<LdapOp>> {
         let backend_handler = self
             .user_info
             .as_ref()
@@ -288,7 +453,7 @@ impl<Backend: BackendHandler + LoginHandler + OpaqueHandler> LdapHandler<Backend
                 code: LdapResultCode::InsufficentAccessRights,
                 message: "Unauthorized write".to_string(),
             })?;
-        delete::delete_user_or_group(backend_handler, &self.ldap_info, request).await
+        delete::delete_user_or_group(backend_handler, &self.ldap_info, request, controls).await
     }
 
     #[instrument(skip_all, level = "debug")]
@@ -300,18 +465,90 @@ impl<Backend: BackendHandler + LoginHandler + OpaqueHandler> LdapHandler<Backend
         );
         compare::compare(
             request,
-            self.do_search(&req).await?,
+            self.do_search(&req, &[]).await?.0,
             &self.ldap_info.base_dn_str,
         )
     }
 
+    fn make_password_policy_controls(&self, controls: &[LdapControl]) -> Vec<LdapControl> {
+        let error = match self.password_state {
+            PasswordState::Valid => return Vec::new(),
+            PasswordState::Expired => PASSWORD_POLICY_ERROR_PASSWORD_EXPIRED,
+            PasswordState::MustChange => PASSWORD_POLICY_ERROR_CHANGE_AFTER_RESET,
+        };
+        // The response control is only sent to the clients asking for it.
+        if has_control(controls, PASSWORD_POLICY_OID) {
+            vec![make_password_policy_response(error)]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Until the bound user changes their expired or reset password, the only allowed operations
+    /// are binding and changing the password.
+    fn refuse_until_password_changed(
+        &self,
+        ldap_op: &LdapOp,
+        controls: &[LdapControl],
+    ) -> Option<(Vec<LdapOp>, Vec<LdapControl>)> {
+        if self.password_state == PasswordState::Valid || self.user_info.is_none() {
+            return None;
+        }
+        let code = LdapResultCode::InsufficentAccessRights;
+        let message = "The password must be changed first".to_string();
+        let response = match ldap_op {
+            LdapOp::BindRequest(_) | LdapOp::UnbindRequest => return None,
+            LdapOp::ExtendedRequest(request)
+                if request.name == OID_PASSWORD_MODIFY || request.name == OID_WHOAMI =>
+            {
+                return None;
+            }
+            LdapOp::SearchRequest(_) | LdapOp::CompareRequest(_) => {
+                make_search_error(code, message)
+            }
+            LdapOp::ModifyRequest(_) => make_modify_response(code, message),
+            LdapOp::AddRequest(_) => make_add_response(code, message),
+            LdapOp::DelRequest(_) => make_del_response(code, message),
+            _ => make_extended_response(code, message),
+        };
+        Some((vec![response], self.make_password_policy_controls(controls)))
+    }
+
     pub async fn handle_ldap_message(&mut self, ldap_op: LdapOp) -> Option<Vec<LdapOp>> {
-        Some(match ldap_op {
-            LdapOp::BindRequest(request) => self.do_bind(&request).await,
-            LdapOp::SearchRequest(request) => self
-                .do_search_or_dse(&request)
-                .await
-                .unwrap_or_else(|e: LdapError| vec![make_search_error(e.code, e.message)]),
+        self.handle_ldap_message_with_controls(ldap_op, &[])
+            .await
+            .map(|(responses, _)| responses)
+    }
+
+    /// Handles a message with its request controls. The returned controls are meant to be
+    /// attached to the final response.
+    pub async fn handle_ldap_message_with_controls(
+        &mut self,
+        ldap_op: LdapOp,
+        controls: &[LdapControl],
+    ) -> Option<(Vec<LdapOp>, Vec<LdapControl>)> {
+        if let Some(refusal) = self.refuse_until_password_changed(&ldap_op, controls) {
+            return Some(refusal);
+        }
+        let mut response_controls = Vec::new();
+        let responses = match ldap_op {
+            LdapOp::BindRequest(request) => {
+                let responses = self.do_bind(&request).await;
+                response_controls = self.make_password_policy_controls(controls);
+                responses
+            }
+            LdapOp::SearchRequest(request) => {
+                match self
+                    .do_search_or_dse_with_controls(&request, controls)
+                    .await
+                {
+                    Ok((responses, search_controls)) => {
+                        response_controls = search_controls;
+                        responses
+                    }
+                    Err(e) => vec![make_search_error(e.code, e.message)],
+                }
+            }
             LdapOp::UnbindRequest => {
                 debug!(
                     "Unbind request for {}",
@@ -325,13 +562,19 @@ impl<Backend: BackendHandler + LoginHandler + OpaqueHandler> LdapHandler<Backend
                 return None;
             }
             LdapOp::ModifyRequest(request) => self.do_modify_request(&request).await,
-            LdapOp::ExtendedRequest(request) => self.do_extended_request(&request).await,
+            LdapOp::ExtendedRequest(request) => {
+                let responses = self.do_extended_request(&request).await;
+                if self.password_state != PasswordState::Valid {
+                    self.refresh_password_state().await;
+                }
+                responses
+            }
             LdapOp::AddRequest(request) => self
                 .create_user_or_group(request)
                 .await
                 .unwrap_or_else(|e: LdapError| vec![make_add_response(e.code, e.message)]),
             LdapOp::DelRequest(request) => self
-                .delete_user_or_group(request)
+                .delete_user_or_group(request, controls)
                 .await
                 .unwrap_or_else(|e: LdapError| vec![make_del_response(e.code, e.message)]),
             LdapOp::CompareRequest(request) => self
@@ -342,16 +585,73 @@ impl<Backend: BackendHandler + LoginHandler + OpaqueHandler> LdapHandler<Backend
                 LdapResultCode::UnwillingToPerform,
                 format!("Unsupported operation: {op:#?}"),
             )],
-        })
+        };
+        Some((responses, response_controls))
+    }
+
+    /// Same as `handle_ldap_message_with_controls`, but the responses are sent to `sink` with
+    /// their controls as soon as they are available: search results are streamed from the
+    /// backend rather than buffered. Returns false if the session should end.
+    pub async fn handle_ldap_message_into<S>(
+        &mut self,
+        ldap_op: LdapOp,
+        controls: &[LdapControl],
+        sink: &mut S,
+    ) -> Result<bool, S::Error>
+    where
+        S: Sink<(LdapOp, Vec<LdapControl>)> + Unpin,
+    {
+        // The refused searches are answered by `handle_ldap_message_with_controls`.
+        let is_refused = self
+            .refuse_until_password_changed(&ldap_op, controls)
+            .is_some();
+        match ldap_op {
+            LdapOp::SearchRequest(request) if !is_refused => {
+                self.stream_search_or_dse(&request, controls, sink).await?;
+                Ok(true)
+            }
+            ldap_op => match self
+                .handle_ldap_message_with_controls(ldap_op, controls)
+                .await
+            {
+                None => Ok(false),
+                Some((responses, response_controls)) => {
+                    send_responses(sink, stream::iter(responses), response_controls).await?;
+                    Ok(true)
+                }
+            },
+        }
+    }
+}
+
+/// Sends the responses to `sink`, attaching the response controls to the final response: the
+/// `SearchResultDone` for searches.
+async fn send_responses<S>(
+    sink: &mut S,
+    responses: impl Stream<Item = LdapOp>,
+    mut response_controls: Vec<LdapControl>,
+) -> Result<(), S::Error>
+where
+    S: Sink<(LdapOp, Vec<LdapControl>)> + Unpin,
+{
+    let mut responses = std::pin::pin!(responses);
+    while let Some(response) = responses.next().await {
+        let controls = if !matches!(response, LdapOp::SearchResultEntry(_)) {
+            std::mem::take(&mut response_controls)
+        } else {
+            Vec::new()
+        };
+        sink.feed((response, controls)).await?;
     }
+    Ok(())
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use crate::password::tests::make_bind_success;
+    use crate::password::tests::{expect_password_change, make_bind_success};
     use chrono::TimeZone;
-    use ldap3_proto::proto::{LdapBindCred, LdapWhoamiRequest};
+    use ldap3_proto::proto::{LdapBindCred, LdapSearchScope, LdapWhoamiRequest};
     use lldap_domain::{
         types::{GroupDetails, GroupId, UserId},
         uuid,
@@ -385,8 +685,9 @@ pub mod tests {
             .with(eq(BindRequest {
                 name: UserId::new("test"),
                 password: "pass".to_string(),
+                source: BindSource::Ldap(None),
             }))
-            .return_once(|_| Ok(()));
+            .return_once(|_| Ok(BindResult::default()));
         let group = group.to_string();
         mock.expect_get_user_groups()
             .with(eq(UserId::new("test")))
@@ -398,6 +699,7 @@ pub mod tests {
                     creation_date: chrono::Utc.timestamp_opt(42, 42).unwrap().naive_utc(),
                     uuid: uuid!("a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d8"),
                     attributes: Vec::new(),
+                    member_filter: None,
                 });
                 Ok(set)
             });
@@ -456,4 +758,93 @@ pub mod tests {
             )])
         );
     }
+
+    #[tokio::test]
+    async fn test_password_must_be_changed_first() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_bind()
+            .with(eq(BindRequest {
+                name: UserId::new("test"),
+                password: "pass".to_string(),
+                source: BindSource::Ldap(None),
+            }))
+            .return_once(|_| {
+                Ok(BindResult {
+                    password_state: PasswordState::MustChange,
+                    ..Default::default()
+                })
+            });
+        mock.expect_get_user_groups()
+            .with(eq(UserId::new("test")))
+            .returning(|_| Ok(HashSet::new()));
+        expect_password_change(&mut mock, "test");
+        mock.expect_get_password_state()
+            .with(eq(UserId::new("test")))
+            .return_once(|_| Ok(PasswordState::Valid));
+        let mut ldap_handler = LdapHandler::new_for_tests(mock, "dc=example,dc=com");
+        let password_policy_request = [LdapControl::Unknown {
+            oid: PASSWORD_POLICY_OID.to_string(),
+            criticality: false,
+            value: None,
+        }];
+        let password_policy_response = vec![make_password_policy_response(
+            PASSWORD_POLICY_ERROR_CHANGE_AFTER_RESET,
+        )];
+        let bind_request = LdapOp::BindRequest(LdapBindRequest {
+            dn: "uid=test,ou=people,dc=example,dc=com".to_string(),
+            cred: LdapBindCred::Simple("pass".to_string()),
+        });
+        assert_eq!(
+            ldap_handler
+                .handle_ldap_message_with_controls(bind_request, &password_policy_request)
+                .await,
+            Some((make_bind_success(), password_policy_response.clone()))
+        );
+        let root_dse_request = || {
+            LdapOp::SearchRequest(LdapSearchRequest {
+                scope: LdapSearchScope::Base,
+                ..make_search_request(
+                    "",
+                    LdapFilter::Present("objectClass".to_string()),
+                    vec!["vendorName"],
+                )
+            })
+        };
+        assert_eq!(
+            ldap_handler
+                .handle_ldap_message_with_controls(root_dse_request(), &password_policy_request)
+                .await,
+            Some((
+                vec![make_search_error(
+                    LdapResultCode::InsufficentAccessRights,
+                    "The password must be changed first".to_string()
+                )],
+                password_policy_response
+            ))
+        );
+        let password_modify_request = LdapOp::ExtendedRequest(
+            LdapPasswordModifyRequest {
+                user_identity: Some("uid=test,ou=people,dc=example,dc=com".to_string()),
+                old_password: None,
+                new_password: Some("password".to_string()),
+            }
+            .into(),
+        );
+        assert_eq!(
+            ldap_handler
+                .handle_ldap_message(password_modify_request)
+                .await,
+            Some(vec![make_extended_response(
+                LdapResultCode::Success,
+                "".to_string(),
+            )])
+        );
+        assert_eq!(
+            ldap_handler.handle_ldap_message(root_dse_request()).await,
+            Some(vec![
+                root_dse_response("dc=example,dc=com", false),
+                make_search_success()
+            ])
+        );
+    }
 }
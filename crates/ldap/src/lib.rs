@@ -6,9 +6,16 @@ pub(crate) mod handler;
 pub(crate) mod modify;
 pub(crate) mod password;
 pub(crate) mod search;
+pub(crate) mod subschema;
 
-pub use core::utils::{UserFieldType, map_group_field, map_user_field};
+pub use core::controls::SUPPORTED_CONTROLS;
+pub use core::dn::{Rdn, escape_dn_value, parse_dn};
+pub use core::filter::{parse_filter, parse_member_filter};
+pub use core::utils::{
+    BindMfaPolicy, GroupFieldType, UserFieldType, get_custom_attribute, map_group_field,
+    map_user_field,
+};
 pub use handler::LdapHandler;
 
 pub use core::group::get_default_group_object_classes;
-pub use core::user::get_default_user_object_classes;
+pub use core::user::{SERVICES_OU, get_default_user_object_classes, get_user_dn};
@@ -10,10 +10,11 @@ use ldap3_proto::proto::{LdapModify, LdapModifyRequest, LdapModifyType, LdapOp,
 use lldap_access_control::UserReadableBackendHandler;
 use lldap_auth::access_control::ValidationResults;
 use lldap_domain::types::UserId;
+use lldap_domain_handlers::handler::LoginHandler;
 use lldap_opaque_handler::OpaqueHandler;
 
 async fn handle_modify_change(
-    opaque_handler: &impl OpaqueHandler,
+    opaque_handler: &(impl OpaqueHandler + LoginHandler),
     user_id: UserId,
     credentials: &ValidationResults,
     user_is_admin: bool,
@@ -43,12 +44,7 @@ async fn handle_modify_change(
         });
     }
     if let [value] = &change.modification.vals.as_slice() {
-        password::change_password(opaque_handler, user_id, value)
-            .await
-            .map_err(|e| LdapError {
-                code: LdapResultCode::Other,
-                message: format!("Error while changing the password: {e:#?}"),
-            })?;
+        password::change_password_as(opaque_handler, credentials, user_id, value).await?;
     } else {
         return Err(LdapError {
             code: LdapResultCode::InvalidAttributeSyntax,
@@ -62,7 +58,7 @@ async fn handle_modify_change(
 }
 
 pub(crate) async fn handle_modify_request<'cred, UserBackendHandler>(
-    opaque_handler: &impl OpaqueHandler,
+    opaque_handler: &(impl OpaqueHandler + LoginHandler),
     get_readable_handler: impl FnOnce(
         &'cred ValidationResults,
         UserId,
@@ -128,7 +124,7 @@ mod tests {
             setup_bound_admin_handler, setup_bound_handler_with_group,
             setup_bound_password_manager_handler,
         },
-        password::tests::expect_password_change,
+        password::tests::{expect_password_change, expect_password_reset},
     };
     use chrono::TimeZone;
     use ldap3_proto::proto::LdapResult as LdapResultOp;
@@ -158,6 +154,7 @@ mod tests {
                         creation_date: chrono::Utc.timestamp_opt(42, 42).unwrap().naive_utc(),
                         uuid: uuid!("a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d8"),
                         attributes: Vec::new(),
+                        member_filter: None,
                     });
                 }
                 Ok(g)
@@ -199,7 +196,7 @@ mod tests {
     async fn test_modify_password_of_regular_as_admin() {
         let mut mock = MockTestBackendHandler::new();
         setup_target_user_groups(&mut mock, "bob", Vec::new());
-        expect_password_change(&mut mock, "bob");
+        expect_password_reset(&mut mock, "bob");
         let ldap_handler = setup_bound_admin_handler(mock).await;
         let request = make_password_modify_request("bob");
         assert_eq!(
@@ -225,7 +222,7 @@ mod tests {
     async fn test_modify_password_of_regular_as_password_manager() {
         let mut mock = MockTestBackendHandler::new();
         setup_target_user_groups(&mut mock, "bob", Vec::new());
-        expect_password_change(&mut mock, "bob");
+        expect_password_reset(&mut mock, "bob");
         let ldap_handler = setup_bound_password_manager_handler(mock).await;
         let request = make_password_modify_request("bob");
         assert_eq!(
@@ -12,32 +12,76 @@ use ldap3_proto::proto::{
 use lldap_access_control::{AccessControlledBackendHandler, UserReadableBackendHandler};
 use lldap_auth::access_control::ValidationResults;
 use lldap_domain::types::UserId;
-use lldap_domain_handlers::handler::{BackendHandler, BindRequest, LoginHandler};
+use lldap_domain_handlers::handler::{
+    BackendHandler, BindRequest, BindSource, LoginHandler, MfaBackendHandler, PasswordState,
+    UserBackendHandler,
+};
+use lldap_domain_model::error::DomainError;
 use lldap_opaque_handler::OpaqueHandler;
+use std::net::IpAddr;
+use tracing::debug;
+
+/// Parses the bind names accepted by Active Directory besides DNs: the user principal name
+/// ("user@example.com") and the down-level logon name ("EXAMPLE\user").
+fn get_user_id_from_ad_bind_name(name: &str) -> Option<UserId> {
+    if name.contains('=') {
+        return None;
+    }
+    name.split_once('@')
+        .map(|(user, _)| user)
+        .or_else(|| name.split_once('\\').map(|(_, user)| user))
+        .filter(|user| !user.is_empty())
+        .map(UserId::new)
+}
+
+/// The number of digits of the TOTP code appended to the password, see
+/// [`crate::core::utils::BindMfaPolicy`].
+const BIND_TOTP_CODE_LENGTH: usize = 6;
+
+/// Splits `<password><6-digit code>`. `None` if the password doesn't end with a code.
+fn split_totp_code(password: &str) -> Option<(&str, &str)> {
+    let split = password.len().checked_sub(BIND_TOTP_CODE_LENGTH)?;
+    let code = password.get(split..)?;
+    if !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((&password[..split], code))
+}
+
+fn invalid_credentials() -> LdapError {
+    LdapError {
+        code: LdapResultCode::InvalidCredentials,
+        message: "".to_string(),
+    }
+}
 
 pub(crate) async fn do_bind(
     ldap_info: &LdapInfo,
     request: &LdapBindRequest,
-    login_handler: &impl LoginHandler,
-) -> LdapResult<UserId> {
+    client_address: Option<IpAddr>,
+    backend_handler: &(impl LoginHandler + UserBackendHandler + MfaBackendHandler),
+) -> LdapResult<(UserId, PasswordState)> {
     if request.dn.is_empty() {
         return Err(LdapError {
             code: LdapResultCode::InappropriateAuthentication,
             message: "Anonymous bind not allowed".to_string(),
         });
     }
-    let user_id = match get_user_id_from_distinguished_name(
-        &request.dn.to_ascii_lowercase(),
-        &ldap_info.base_dn,
-        &ldap_info.base_dn_str,
-    ) {
-        Ok(s) => s,
-        Err(e) => {
-            return Err(LdapError {
-                code: LdapResultCode::NamingViolation,
-                message: e.to_string(),
-            });
-        }
+    let ad_user_id = ldap_info
+        .ad_compatibility
+        .then(|| get_user_id_from_ad_bind_name(&request.dn))
+        .flatten();
+    let user_id = match ad_user_id {
+        Some(user_id) => user_id,
+        None => get_user_id_from_distinguished_name(
+            &request.dn.to_ascii_lowercase(),
+            &ldap_info.base_dn,
+            &ldap_info.base_dn_str,
+        )
+        .map_err(|e| LdapError {
+            code: LdapResultCode::NamingViolation,
+            message: e.to_string(),
+        })?,
     };
     let password = if let LdapBindCred::Simple(password) = &request.cred {
         password
@@ -47,19 +91,41 @@ pub(crate) async fn do_bind(
             message: "SASL not supported".to_string(),
         });
     };
-    match login_handler
+    let requires_totp = ldap_info.bind_mfa_policy.is_enabled()
+        && ldap_info.bind_mfa_policy.is_required_for(
+            &backend_handler
+                .get_user_groups(&user_id)
+                .await
+                .map_err(|_| invalid_credentials())?,
+        );
+    // App passwords never end with a code: they are used as is.
+    let (password, totp_code) = match split_totp_code(password) {
+        Some((password, code)) if requires_totp => (password, Some(code)),
+        _ => (password.as_str(), None),
+    };
+    let bind_result = backend_handler
         .bind(BindRequest {
             name: user_id.clone(),
-            password: password.clone(),
+            password: password.to_owned(),
+            source: BindSource::Ldap(client_address),
         })
         .await
-    {
-        Ok(()) => Ok(user_id),
-        Err(_) => Err(LdapError {
-            code: LdapResultCode::InvalidCredentials,
-            message: "".to_string(),
-        }),
+        .map_err(|_| invalid_credentials())?;
+    if requires_totp && !bind_result.app_password {
+        let Some(code) = totp_code else {
+            debug!(
+                r#"Missing TOTP code in the bind password of "{}""#,
+                &user_id
+            );
+            return Err(invalid_credentials());
+        };
+        // The recovery codes are longer, they cannot be used here.
+        backend_handler
+            .verify_mfa_code(&user_id, code)
+            .await
+            .map_err(|_| invalid_credentials())?;
     }
+    Ok((user_id, bind_result.password_state))
 }
 
 pub(crate) async fn change_password<B: OpaqueHandler>(
@@ -84,16 +150,53 @@ pub(crate) async fn change_password<B: OpaqueHandler>(
     let req = registration::ClientRegistrationFinishRequest {
         server_data: registration_start_response.server_data,
         registration_upload: registration_finish.message,
+        password_fingerprint: registration_start_response
+            .password_history_salt
+            .map(|salt| opaque::password_fingerprint(&salt, password))
+            .transpose()?,
     };
     backend_handler.registration_finish(req).await?;
     Ok(())
 }
 
+/// Changes the password of `user` on behalf of the bound user, after checking it against the
+/// password policy. A password set by someone else, e.g. reset by an admin, has to be changed by
+/// the user at the next login.
+pub(crate) async fn change_password_as<B: OpaqueHandler + LoginHandler>(
+    backend_handler: &B,
+    credentials: &ValidationResults,
+    user: UserId,
+    password: &[u8],
+) -> LdapResult<()> {
+    let is_reset = credentials.user != user;
+    let result: Result<()> = async {
+        backend_handler
+            .check_password_policy(&user, &String::from_utf8_lossy(password))
+            .await?;
+        change_password(backend_handler, user.clone(), password).await?;
+        if is_reset {
+            backend_handler.require_password_change(&user).await?;
+        }
+        Ok(())
+    }
+    .await;
+    result.map_err(|e| match e.downcast_ref::<DomainError>() {
+        Some(policy_error @ DomainError::PasswordPolicyError(_)) => LdapError {
+            code: LdapResultCode::ConstraintViolation,
+            message: policy_error.to_string(),
+        },
+        _ => LdapError {
+            code: LdapResultCode::Other,
+            message: format!("Error while changing the password: {e:#?}"),
+        },
+    })
+}
+
 pub(crate) async fn do_password_modification<Handler: BackendHandler>(
     credentials: &ValidationResults,
     ldap_info: &LdapInfo,
     backend_handler: &AccessControlledBackendHandler<Handler>,
-    opaque_handler: &impl OpaqueHandler,
+    opaque_handler: &(impl OpaqueHandler + LoginHandler),
     request: &LdapPasswordModifyRequest,
 ) -> LdapResult<Vec<LdapOp>> {
     match (&request.user_identity, &request.new_password) {
@@ -126,12 +229,10 @@ pub(crate) async fn do_password_modification<Handler: BackendHandler>(
                             ),
                         })
                     } else if let Err(e) =
-                        change_password(opaque_handler, uid, password.as_bytes()).await
+                        change_password_as(opaque_handler, credentials, uid, password.as_bytes())
+                            .await
                     {
-                        Err(LdapError {
-                            code: LdapResultCode::Other,
-                            message: format!("Error while changing the password: {e:#?}"),
-                        })
+                        Err(e)
                     } else {
                         Ok(vec![make_extended_response(
                             LdapResultCode::Success,
@@ -155,6 +256,7 @@ pub(crate) async fn do_password_modification<Handler: BackendHandler>(
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::core::utils::BindMfaPolicy;
     use crate::handler::{
         LdapHandler, make_modify_response,
         tests::{
@@ -169,8 +271,9 @@ pub mod tests {
     };
     use ldap3_proto::{LdapPartialAttribute, proto::LdapExtendedRequest};
     use lldap_domain::{types::*, uuid};
+    use lldap_domain_handlers::handler::BindResult;
     use lldap_test_utils::MockTestBackendHandler;
-    use mockall::predicate::eq;
+    use mockall::predicate::{always, eq};
     use pretty_assertions::assert_eq;
     use std::collections::HashSet;
     use tokio;
@@ -191,7 +294,8 @@ pub mod tests {
         make_bind_result(LdapResultCode::Success, "")
     }
 
-    pub fn expect_password_change(mock: &mut MockTestBackendHandler, user: &str) {
+    /// Expects a new password to be registered for `user`, without checking the password policy.
+    pub fn expect_password_registration(mock: &mut MockTestBackendHandler, user: &str) {
         use lldap_auth::{opaque, registration};
         let mut rng = rand::rngs::OsRng;
         let registration_start_request =
@@ -211,9 +315,29 @@ pub mod tests {
             Ok(registration::ServerRegistrationStartResponse {
                 server_data: "".to_string(),
                 registration_response: start_response.message,
+                password_history_salt: None,
             })
         });
+        let user_id = UserId::new(user);
         mock.expect_registration_finish()
+            .times(1)
+            .return_once(|_| Ok(user_id));
+    }
+
+    pub fn expect_password_change(mock: &mut MockTestBackendHandler, user: &str) {
+        mock.expect_check_password_policy()
+            .with(eq(UserId::new(user)), always())
+            .times(1)
+            .return_once(|_, _| Ok(()));
+        expect_password_registration(mock, user);
+    }
+
+    /// Expects the password of `user` to be changed by someone else, which forces `user` to
+    /// change it at the next login.
+    pub fn expect_password_reset(mock: &mut MockTestBackendHandler, user: &str) {
+        expect_password_change(mock, user);
+        mock.expect_require_password_change()
+            .with(eq(UserId::new(user)))
             .times(1)
             .return_once(|_| Ok(()));
     }
@@ -225,9 +349,10 @@ pub mod tests {
             .with(eq(lldap_domain_handlers::handler::BindRequest {
                 name: UserId::new("bob"),
                 password: "pass".to_string(),
+                source: BindSource::Ldap(None),
             }))
             .times(1)
-            .return_once(|_| Ok(()));
+            .return_once(|_| Ok(BindResult::default()));
         mock.expect_get_user_groups()
             .with(eq(UserId::new("bob")))
             .return_once(|_| Ok(HashSet::new()));
@@ -243,6 +368,159 @@ pub mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_bind_ad_compatibility() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_bind()
+            .with(eq(lldap_domain_handlers::handler::BindRequest {
+                name: UserId::new("bob"),
+                password: "pass".to_string(),
+                source: BindSource::Ldap(None),
+            }))
+            .times(2)
+            .returning(|_| Ok(BindResult::default()));
+        mock.expect_get_user_groups()
+            .with(eq(UserId::new("bob")))
+            .returning(|_| Ok(HashSet::new()));
+        let mut ldap_handler =
+            LdapHandler::new_for_tests(mock, "dc=example,dc=com").with_ad_compatibility();
+
+        for dn in ["bob@example.com", "EXAMPLE\\bob"] {
+            let request = LdapBindRequest {
+                dn: dn.to_string(),
+                cred: LdapBindCred::Simple("pass".to_string()),
+            };
+            assert_eq!(ldap_handler.do_bind(&request).await, make_bind_success());
+        }
+    }
+
+    fn make_group(name: &str) -> GroupDetails {
+        GroupDetails {
+            group_id: GroupId(42),
+            display_name: name.into(),
+            creation_date: chrono::Utc.timestamp_opt(42, 42).unwrap().naive_utc(),
+            uuid: uuid!("a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d8"),
+            attributes: Vec::new(),
+            member_filter: None,
+        }
+    }
+
+    fn make_vpn_handler(mock: MockTestBackendHandler) -> LdapHandler<MockTestBackendHandler> {
+        LdapHandler::new_for_tests(mock, "dc=example,dc=com").with_bind_mfa_policy(BindMfaPolicy {
+            required_groups: HashSet::from([GroupName::from("vpn")]),
+            exempt_groups: HashSet::from([GroupName::from("service")]),
+        })
+    }
+
+    #[test]
+    fn test_split_totp_code() {
+        assert_eq!(split_totp_code("pass123456"), Some(("pass", "123456")));
+        assert_eq!(split_totp_code("123456"), Some(("", "123456")));
+        assert_eq!(split_totp_code("pass12345a"), None);
+        assert_eq!(split_totp_code("12345"), None);
+        assert_eq!(split_totp_code("passé12345"), None);
+    }
+
+    #[tokio::test]
+    async fn test_bind_with_totp_code() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_get_user_groups()
+            .with(eq(UserId::new("bob")))
+            .returning(|_| Ok(HashSet::from([make_group("vpn")])));
+        mock.expect_bind()
+            .with(eq(lldap_domain_handlers::handler::BindRequest {
+                name: UserId::new("bob"),
+                password: "pass".to_string(),
+                source: BindSource::Ldap(None),
+            }))
+            .times(1)
+            .return_once(|_| Ok(BindResult::default()));
+        mock.expect_verify_mfa_code()
+            .with(eq(UserId::new("bob")), eq("123456"))
+            .times(1)
+            .return_once(|_, _| Ok(()));
+        let mut ldap_handler = make_vpn_handler(mock);
+        let request = LdapBindRequest {
+            dn: "uid=bob,ou=people,dc=example,dc=com".to_string(),
+            cred: LdapBindCred::Simple("pass123456".to_string()),
+        };
+        assert_eq!(ldap_handler.do_bind(&request).await, make_bind_success());
+    }
+
+    #[tokio::test]
+    async fn test_bind_with_wrong_or_missing_totp_code() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_get_user_groups()
+            .with(eq(UserId::new("bob")))
+            .returning(|_| Ok(HashSet::from([make_group("vpn")])));
+        mock.expect_bind()
+            .times(2)
+            .returning(|_| Ok(BindResult::default()));
+        mock.expect_verify_mfa_code()
+            .times(1)
+            .return_once(|_, _| Err(DomainError::AuthenticationError("wrong code".to_string())));
+        let mut ldap_handler = make_vpn_handler(mock);
+        for password in ["pass654321", "password"] {
+            let request = LdapBindRequest {
+                dn: "uid=bob,ou=people,dc=example,dc=com".to_string(),
+                cred: LdapBindCred::Simple(password.to_string()),
+            };
+            assert_eq!(
+                ldap_handler.do_bind(&request).await,
+                make_bind_result(LdapResultCode::InvalidCredentials, "")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bind_with_app_password_without_totp_code() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_get_user_groups()
+            .with(eq(UserId::new("bob")))
+            .returning(|_| Ok(HashSet::from([make_group("vpn")])));
+        mock.expect_bind()
+            .with(eq(lldap_domain_handlers::handler::BindRequest {
+                name: UserId::new("bob"),
+                password: "abcde-fghjk-mnpqr-stuvw".to_string(),
+                source: BindSource::Ldap(None),
+            }))
+            .times(1)
+            .return_once(|_| {
+                Ok(BindResult {
+                    app_password: true,
+                    ..Default::default()
+                })
+            });
+        let mut ldap_handler = make_vpn_handler(mock);
+        let request = LdapBindRequest {
+            dn: "uid=bob,ou=people,dc=example,dc=com".to_string(),
+            cred: LdapBindCred::Simple("abcde-fghjk-mnpqr-stuvw".to_string()),
+        };
+        assert_eq!(ldap_handler.do_bind(&request).await, make_bind_success());
+    }
+
+    #[tokio::test]
+    async fn test_bind_exempt_from_totp_code() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_get_user_groups()
+            .with(eq(UserId::new("bob")))
+            .returning(|_| Ok(HashSet::from([make_group("vpn"), make_group("service")])));
+        mock.expect_bind()
+            .with(eq(lldap_domain_handlers::handler::BindRequest {
+                name: UserId::new("bob"),
+                password: "pass123456".to_string(),
+                source: BindSource::Ldap(None),
+            }))
+            .times(1)
+            .return_once(|_| Ok(BindResult::default()));
+        let mut ldap_handler = make_vpn_handler(mock);
+        let request = LdapBindRequest {
+            dn: "uid=bob,ou=people,dc=example,dc=com".to_string(),
+            cred: LdapBindCred::Simple("pass123456".to_string()),
+        };
+        assert_eq!(ldap_handler.do_bind(&request).await, make_bind_success());
+    }
+
     #[tokio::test]
     async fn test_admin_bind() {
         let mut mock = MockTestBackendHandler::new();
@@ -250,9 +528,10 @@ pub mod tests {
             .with(eq(lldap_domain_handlers::handler::BindRequest {
                 name: UserId::new("test"),
                 password: "pass".to_string(),
+                source: BindSource::Ldap(None),
             }))
             .times(1)
-            .return_once(|_| Ok(()));
+            .return_once(|_| Ok(BindResult::default()));
         mock.expect_get_user_groups()
             .with(eq(UserId::new("test")))
             .return_once(|_| {
@@ -263,6 +542,7 @@ pub mod tests {
                     creation_date: chrono::Utc.timestamp_opt(42, 42).unwrap().naive_utc(),
                     uuid: uuid!("a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d8"),
                     attributes: Vec::new(),
+                    member_filter: None,
                 });
                 Ok(set)
             });
@@ -325,14 +605,14 @@ pub mod tests {
             ),
         );
         let request = LdapBindRequest {
-            dn: "uid=bob=test,ou=people,dc=example,dc=com".to_string(),
+            dn: "uid=bob;test,ou=people,dc=example,dc=com".to_string(),
             cred: LdapBindCred::Simple("pass".to_string()),
         };
         assert_eq!(
             ldap_handler.do_bind(&request).await,
             make_bind_result(
                 LdapResultCode::NamingViolation,
-                r#"Too many elements in distinguished name: "uid", "bob", "test""#
+                r#"Invalid DN "uid=bob;test,ou=people,dc=example,dc=com": Character ";" must be escaped in value"#
             ),
         );
     }
@@ -343,7 +623,7 @@ pub mod tests {
         mock.expect_get_user_groups()
             .with(eq(UserId::new("bob")))
             .returning(|_| Ok(HashSet::new()));
-        expect_password_change(&mut mock, "bob");
+        expect_password_reset(&mut mock, "bob");
         let mut ldap_handler = setup_bound_admin_handler(mock).await;
         let request = LdapOp::ExtendedRequest(
             LdapPasswordModifyRequest {
@@ -368,6 +648,9 @@ pub mod tests {
         mock.expect_get_user_groups()
             .with(eq(UserId::new("bob")))
             .returning(|_| Ok(HashSet::new()));
+        mock.expect_check_password_policy()
+            .times(1)
+            .return_once(|_, _| Ok(()));
         use lldap_auth::*;
         let mut rng = rand::rngs::OsRng;
         let registration_start_request =
@@ -387,9 +670,14 @@ pub mod tests {
             Ok(registration::ServerRegistrationStartResponse {
                 server_data: "".to_string(),
                 registration_response: start_response.message,
+                password_history_salt: None,
             })
         });
         mock.expect_registration_finish()
+            .times(1)
+            .return_once(|_| Ok(UserId::new("bob")));
+        mock.expect_require_password_change()
+            .with(eq(UserId::new("bob")))
             .times(1)
             .return_once(|_| Ok(()));
         let mut ldap_handler = setup_bound_admin_handler(mock).await;
@@ -418,6 +706,9 @@ pub mod tests {
         mock.expect_get_user_groups()
             .with(eq(UserId::new("bob")))
             .returning(|_| Ok(HashSet::new()));
+        mock.expect_check_password_policy()
+            .times(1)
+            .return_once(|_, _| Ok(()));
         use lldap_auth::*;
         let mut rng = rand::rngs::OsRng;
         let registration_start_request =
@@ -437,9 +728,14 @@ pub mod tests {
             Ok(registration::ServerRegistrationStartResponse {
                 server_data: "".to_string(),
                 registration_response: start_response.message,
+                password_history_salt: None,
             })
         });
         mock.expect_registration_finish()
+            .times(1)
+            .return_once(|_| Ok(UserId::new("bob")));
+        mock.expect_require_password_change()
+            .with(eq(UserId::new("bob")))
             .times(1)
             .return_once(|_| Ok(()));
         let mut ldap_handler = setup_bound_password_manager_handler(mock).await;
@@ -460,6 +756,39 @@ pub mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_password_change_policy_violation() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_get_user_groups()
+            .with(eq(UserId::new("bob")))
+            .returning(|_| Ok(HashSet::new()));
+        mock.expect_check_password_policy()
+            .with(eq(UserId::new("bob")), eq("pass"))
+            .times(1)
+            .return_once(|_, _| {
+                Err(DomainError::PasswordPolicyError(
+                    "The password must be at least 8 characters long".to_string(),
+                ))
+            });
+        mock.expect_registration_start().never();
+        let mut ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = LdapOp::ExtendedRequest(
+            LdapPasswordModifyRequest {
+                user_identity: Some("uid=bob,ou=people,dc=example,dc=com".to_string()),
+                old_password: None,
+                new_password: Some("pass".to_string()),
+            }
+            .into(),
+        );
+        assert_eq!(
+            ldap_handler.handle_ldap_message(request).await,
+            Some(vec![make_extended_response(
+                LdapResultCode::ConstraintViolation,
+                "The password doesn't follow the password policy: The password must be at least 8 characters long".to_string(),
+            )])
+        );
+    }
+
     #[tokio::test]
     async fn test_password_change_errors() {
         let mut mock = MockTestBackendHandler::new();
@@ -520,6 +849,7 @@ pub mod tests {
             creation_date: chrono::Utc.timestamp_opt(42, 42).unwrap().naive_utc(),
             uuid: uuid!("a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d8"),
             attributes: Vec::new(),
+            member_filter: None,
         });
         mock.expect_get_user_groups()
             .with(eq(UserId::new("bob")))
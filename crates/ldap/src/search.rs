@@ -1,12 +1,33 @@
 use crate::core::{
+    controls::{
+        CONTROL_RESULT_SORT_CONTROL_MISSING, CONTROL_RESULT_SUCCESS,
+        CONTROL_RESULT_UNWILLING_TO_PERFORM, SUPPORTED_CONTROLS, SortRequest, VlvRequest,
+        VlvTarget, make_sort_response, make_vlv_response, parse_sort_request, parse_vlv_request,
+    },
+    dn::Rdn,
     error::{LdapError, LdapResult},
     group::{convert_groups_to_ldap_op, get_groups_list},
-    user::{convert_users_to_ldap_op, get_user_list},
-    utils::{LdapInfo, LdapSchemaDescription, is_subtree, parse_distinguished_name},
+    sudo::{
+        SUDO_SCHEMA_ATTRIBUTE_TYPES, SUDO_SCHEMA_OBJECT_CLASS, SUDOERS_OU,
+        convert_sudo_roles_to_ldap_op, get_sudo_roles_list, is_sudo_role_filter,
+    },
+    user::{
+        ACCOUNT_LOCK_ATTRIBUTE_TYPES, SERVICES_OU, convert_user_filter, convert_users_to_ldap_op,
+        get_user_list, restrict_to_user_kind,
+    },
+    utils::{
+        LdapInfo, LdapSchemaDescription, UserFieldType, get_ad_domain, is_subtree, map_user_field,
+        parse_distinguished_name,
+    },
 };
 use chrono::Utc;
+use futures_util::{
+    Stream, StreamExt, TryStreamExt, future,
+    stream::{self, BoxStream},
+};
 use ldap3_proto::{
     LdapFilter, LdapPartialAttribute, LdapResultCode, LdapSearchResultEntry, LdapSearchScope,
+    control::LdapControl,
     proto::{
         LdapDerefAliases, LdapOp, LdapResult as LdapResultOp, LdapSearchRequest,
         OID_PASSWORD_MODIFY, OID_WHOAMI,
@@ -15,69 +36,113 @@ use ldap3_proto::{
 use lldap_access_control::UserAndGroupListerBackendHandler;
 use lldap_domain::{
     public_schema::PublicSchema,
-    types::{Group, UserAndGroups},
+    types::{AttributeName, Group, UserAndGroups, UserId},
 };
+use lldap_domain_handlers::handler::{UserRequestFilter, UserSortOrder};
+use lldap_domain_model::{error::DomainError, model::UserColumn};
+use std::collections::HashSet;
 use tracing::{debug, instrument, warn};
 
 #[derive(Debug)]
 enum SearchScope {
     Global,
-    Users,
+    // The people or the service accounts, depending on the subtree.
+    Users {
+        service_accounts: bool,
+    },
     Groups,
-    User(LdapFilter),
+    User {
+        service_accounts: bool,
+        filter: LdapFilter,
+    },
     Group(LdapFilter),
     UserOuOnly,
     GroupOuOnly,
+    SudoRoles,
+    SudoRole(LdapFilter),
+    SudoersOuOnly,
     Unknown,
     Invalid,
 }
 
-enum InternalSearchResults {
-    UsersAndGroups(Vec<UserAndGroups>, Vec<Group>),
+enum InternalSearchResults<'a> {
+    // With the ids of the service accounts, when the members of the groups are requested.
+    UsersAndGroups(
+        BoxStream<'a, LdapResult<UserAndGroups>>,
+        BoxStream<'a, LdapResult<Group>>,
+        HashSet<UserId>,
+    ),
     Raw(Vec<LdapOp>),
     Empty,
 }
 
+/// The entries returned by a search, followed by its `SearchResultDone`.
+pub type SearchResultStream<'a> = BoxStream<'a, LdapOp>;
+
+/// The filter matching the entries named by the RDN.
+fn rdn_filter(rdn: &Rdn) -> LdapFilter {
+    match rdn.single() {
+        Some((attribute_type, value)) => {
+            LdapFilter::Equality(attribute_type.to_string(), value.to_string())
+        }
+        None => LdapFilter::And(
+            rdn.pairs()
+                .iter()
+                .map(|(attribute_type, value)| {
+                    LdapFilter::Equality(attribute_type.clone(), value.clone())
+                })
+                .collect(),
+        ),
+    }
+}
+
 fn get_search_scope(
-    base_dn: &[(String, String)],
-    dn_parts: &[(String, String)],
+    base_dn: &[Rdn],
+    dn_parts: &[Rdn],
     ldap_scope: &LdapSearchScope,
 ) -> SearchScope {
     let base_dn_len = base_dn.len();
+    let people = Rdn::new("ou", "people");
+    let services = Rdn::new("ou", SERVICES_OU);
+    let groups = Rdn::new("ou", "groups");
+    let sudoers = Rdn::new("ou", SUDOERS_OU);
     if !is_subtree(dn_parts, base_dn) {
         SearchScope::Invalid
     } else if dn_parts.len() == base_dn_len {
         SearchScope::Global
     } else if dn_parts.len() == base_dn_len + 1
-        && dn_parts[0] == ("ou".to_string(), "people".to_string())
+        && (dn_parts[0] == people || dn_parts[0] == services)
     {
         if matches!(ldap_scope, LdapSearchScope::Base) {
             SearchScope::UserOuOnly
         } else {
-            SearchScope::Users
+            SearchScope::Users {
+                service_accounts: dn_parts[0] == services,
+            }
         }
-    } else if dn_parts.len() == base_dn_len + 1
-        && dn_parts[0] == ("ou".to_string(), "groups".to_string())
-    {
+    } else if dn_parts.len() == base_dn_len + 1 && dn_parts[0] == groups {
         if matches!(ldap_scope, LdapSearchScope::Base) {
             SearchScope::GroupOuOnly
         } else {
             SearchScope::Groups
         }
+    } else if dn_parts.len() == base_dn_len + 1 && dn_parts[0] == sudoers {
+        if matches!(ldap_scope, LdapSearchScope::Base) {
+            SearchScope::SudoersOuOnly
+        } else {
+            SearchScope::SudoRoles
+        }
     } else if dn_parts.len() == base_dn_len + 2
-        && dn_parts[1] == ("ou".to_string(), "people".to_string())
-    {
-        SearchScope::User(LdapFilter::Equality(
-            dn_parts[0].0.clone(),
-            dn_parts[0].1.clone(),
-        ))
-    } else if dn_parts.len() == base_dn_len + 2
-        && dn_parts[1] == ("ou".to_string(), "groups".to_string())
+        && (dn_parts[1] == people || dn_parts[1] == services)
     {
-        SearchScope::Group(LdapFilter::Equality(
-            dn_parts[0].0.clone(),
-            dn_parts[0].1.clone(),
-        ))
+        SearchScope::User {
+            service_accounts: dn_parts[1] == services,
+            filter: rdn_filter(&dn_parts[0]),
+        }
+    } else if dn_parts.len() == base_dn_len + 2 && dn_parts[1] == groups {
+        SearchScope::Group(rdn_filter(&dn_parts[0]))
+    } else if dn_parts.len() == base_dn_len + 2 && dn_parts[1] == sudoers {
+        SearchScope::SudoRole(rdn_filter(&dn_parts[0]))
     } else {
         SearchScope::Unknown
     }
@@ -113,66 +178,109 @@ pub(crate) fn make_search_error(code: LdapResultCode, message: String) -> LdapOp
     })
 }
 
-pub(crate) fn root_dse_response(base_dn: &str) -> LdapOp {
+pub(crate) fn root_dse_response(base_dn: &str, ad_compatibility: bool) -> LdapOp {
+    let mut attributes = vec![
+        LdapPartialAttribute {
+            atype: "objectClass".to_string(),
+            vals: vec![b"top".to_vec()],
+        },
+        LdapPartialAttribute {
+            atype: "vendorName".to_string(),
+            vals: vec![b"LLDAP".to_vec()],
+        },
+        LdapPartialAttribute {
+            atype: "vendorVersion".to_string(),
+            vals: vec![
+                concat!("lldap_", env!("CARGO_PKG_VERSION"))
+                    .to_string()
+                    .into_bytes(),
+            ],
+        },
+        LdapPartialAttribute {
+            atype: "supportedLDAPVersion".to_string(),
+            vals: vec![b"3".to_vec()],
+        },
+        LdapPartialAttribute {
+            atype: "supportedExtension".to_string(),
+            vals: vec![
+                OID_PASSWORD_MODIFY.as_bytes().to_vec(),
+                OID_WHOAMI.as_bytes().to_vec(),
+            ],
+        },
+        LdapPartialAttribute {
+            atype: "supportedControl".to_string(),
+            vals: SUPPORTED_CONTROLS
+                .iter()
+                .map(|oid| oid.as_bytes().to_vec())
+                .collect(),
+        },
+        LdapPartialAttribute {
+            atype: "supportedFeatures".to_string(),
+            // Attribute "+"
+            vals: vec![b"1.3.6.1.4.1.4203.1.5.1".to_vec()],
+        },
+        LdapPartialAttribute {
+            atype: "defaultNamingContext".to_string(),
+            vals: vec![base_dn.to_string().into_bytes()],
+        },
+        LdapPartialAttribute {
+            atype: "namingContexts".to_string(),
+            vals: vec![base_dn.to_string().into_bytes()],
+        },
+        LdapPartialAttribute {
+            atype: "isGlobalCatalogReady".to_string(),
+            vals: vec![b"false".to_vec()],
+        },
+        LdapPartialAttribute {
+            atype: "subschemaSubentry".to_string(),
+            vals: vec![b"cn=Subschema".to_vec()],
+        },
+    ];
+    if ad_compatibility {
+        attributes.extend(ad_root_dse_attributes(base_dn));
+    }
     LdapOp::SearchResultEntry(LdapSearchResultEntry {
         dn: "".to_string(),
-        attributes: vec![
-            LdapPartialAttribute {
-                atype: "objectClass".to_string(),
-                vals: vec![b"top".to_vec()],
-            },
-            LdapPartialAttribute {
-                atype: "vendorName".to_string(),
-                vals: vec![b"LLDAP".to_vec()],
-            },
-            LdapPartialAttribute {
-                atype: "vendorVersion".to_string(),
-                vals: vec![
-                    concat!("lldap_", env!("CARGO_PKG_VERSION"))
-                        .to_string()
-                        .into_bytes(),
-                ],
-            },
-            LdapPartialAttribute {
-                atype: "supportedLDAPVersion".to_string(),
-                vals: vec![b"3".to_vec()],
-            },
-            LdapPartialAttribute {
-                atype: "supportedExtension".to_string(),
-                vals: vec![
-                    OID_PASSWORD_MODIFY.as_bytes().to_vec(),
-                    OID_WHOAMI.as_bytes().to_vec(),
-                ],
-            },
-            LdapPartialAttribute {
-                atype: "supportedControl".to_string(),
-                vals: vec![],
-            },
-            LdapPartialAttribute {
-                atype: "supportedFeatures".to_string(),
-                // Attribute "+"
-                vals: vec![b"1.3.6.1.4.1.4203.1.5.1".to_vec()],
-            },
-            LdapPartialAttribute {
-                atype: "defaultNamingContext".to_string(),
-                vals: vec![base_dn.to_string().into_bytes()],
-            },
-            LdapPartialAttribute {
-                atype: "namingContexts".to_string(),
-                vals: vec![base_dn.to_string().into_bytes()],
-            },
-            LdapPartialAttribute {
-                atype: "isGlobalCatalogReady".to_string(),
-                vals: vec![b"false".to_vec()],
-            },
-            LdapPartialAttribute {
-                atype: "subschemaSubentry".to_string(),
-                vals: vec![b"cn=Subschema".to_vec()],
-            },
-        ],
+        attributes,
     })
 }
 
+/// The root DSE attributes that Active Directory clients use to discover the domain.
+fn ad_root_dse_attributes(base_dn: &str) -> Vec<LdapPartialAttribute> {
+    let domain = parse_distinguished_name(base_dn)
+        .map(|parts| get_ad_domain(&parts))
+        .unwrap_or_default();
+    let attribute = |atype: &str, value: String| LdapPartialAttribute {
+        atype: atype.to_string(),
+        vals: vec![value.into_bytes()],
+    };
+    vec![
+        attribute("rootDomainNamingContext", base_dn.to_string()),
+        attribute(
+            "configurationNamingContext",
+            format!("cn=configuration,{base_dn}"),
+        ),
+        attribute(
+            "schemaNamingContext",
+            format!("cn=schema,cn=configuration,{base_dn}"),
+        ),
+        attribute("dnsHostName", domain.clone()),
+        attribute(
+            "ldapServiceName",
+            format!("{domain}:lldap@{}", domain.to_ascii_uppercase()),
+        ),
+        // Windows Server 2016 functional level.
+        attribute("domainFunctionality", "7".to_string()),
+        attribute("forestFunctionality", "7".to_string()),
+        attribute("domainControllerFunctionality", "7".to_string()),
+        // LDAP_CAP_ACTIVE_DIRECTORY_OID
+        attribute(
+            "supportedCapabilities",
+            "1.2.840.113556.1.4.800".to_string(),
+        ),
+    ]
+}
+
 pub fn make_ldap_subschema_entry(schema: PublicSchema) -> LdapOp {
     let ldap_schema_description: LdapSchemaDescription = LdapSchemaDescription::from(schema);
     let current_time_utc = Utc::now().format("%Y%m%d%H%M%SZ").to_string().into_bytes();
@@ -204,8 +312,10 @@ pub fn make_ldap_subschema_entry(schema: PublicSchema) -> LdapOp {
             vals: vec![
                 b"( 1.3.6.1.4.1.1466.115.121.1.15 DESC 'Directory String' )".to_vec(),
                 b"( 1.3.6.1.4.1.1466.115.121.1.24 DESC 'Generalized Time' )".to_vec(),
+                b"( 1.3.6.1.4.1.1466.115.121.1.26 DESC 'IA5 String' )".to_vec(),
                 b"( 1.3.6.1.4.1.1466.115.121.1.27 DESC 'Integer' )".to_vec(),
                 b"( 1.3.6.1.4.1.1466.115.121.1.28 DESC 'JPEG' X-NOT-HUMAN-READABLE 'TRUE' )".to_vec(),
+                b"( 1.3.6.1.4.1.1466.115.121.1.40 DESC 'Octet String' )".to_vec(),
                 ],
            },
            LdapPartialAttribute {
@@ -216,17 +326,22 @@ pub fn make_ldap_subschema_entry(schema: PublicSchema) -> LdapOp {
                     b"( 2.1 NAME 'Integer' SYNTAX 1.3.6.1.4.1.1466.115.121.1.27 )".to_vec(),
                     b"( 2.2 NAME 'JpegPhoto' SYNTAX 1.3.6.1.4.1.1466.115.121.1.28 )".to_vec(),
                     b"( 2.3 NAME 'DateTime' SYNTAX 1.3.6.1.4.1.1466.115.121.1.24 )".to_vec(),
+                    b"( 2.4 NAME 'SshPublicKey' SYNTAX 1.3.6.1.4.1.1466.115.121.1.40 )".to_vec(),
                 ];
                 let num_hardcoded_attributes = hardcoded_attributes.len();
                 hardcoded_attributes.into_iter().chain(
                     ldap_schema_description
                         .formatted_attribute_list(num_hardcoded_attributes)
+                ).chain(
+                    SUDO_SCHEMA_ATTRIBUTE_TYPES.iter().map(|a| a.as_bytes().to_vec())
+                ).chain(
+                    ACCOUNT_LOCK_ATTRIBUTE_TYPES.iter().map(|a| a.as_bytes().to_vec())
                 ).collect()
             }
            },
            LdapPartialAttribute {
             atype: "objectClasses".to_string(),
-            vals: vec![
+            vals: [
                     format!(
                         "( 3.0 NAME ( {} ) DESC 'LLDAP builtin: a person' STRUCTURAL MUST ( {} ) MAY ( {} ) )",
                         ldap_schema_description.user_object_classes().format_for_ldap_schema_description(),
@@ -239,7 +354,11 @@ pub fn make_ldap_subschema_entry(schema: PublicSchema) -> LdapOp {
                         ldap_schema_description.required_group_attributes().format_for_ldap_schema_description(),
                         ldap_schema_description.optional_group_attributes().format_for_ldap_schema_description(),
                     ).into_bytes(),
-                ],
+                    SUDO_SCHEMA_OBJECT_CLASS.as_bytes().to_vec(),
+                ]
+                .into_iter()
+                .chain(ldap_schema_description.formatted_ssh_public_key_object_class())
+                .collect(),
            },
            LdapPartialAttribute {
             atype: "subschemaSubentry".to_string(),
@@ -264,77 +383,146 @@ pub(crate) fn is_subschema_entry_request(request: &LdapSearchRequest) -> bool {
     request.base == "cn=Subschema" && request.scope == LdapSearchScope::Base
 }
 
-async fn do_search_internal(
+async fn get_sudo_role_results<'a>(
     ldap_info: &LdapInfo,
     backend_handler: &impl UserAndGroupListerBackendHandler,
     request: &LdapSearchRequest,
-    schema: &PublicSchema,
-) -> LdapResult<InternalSearchResults> {
-    let dn_parts = parse_distinguished_name(&request.base.to_ascii_lowercase())?;
-    let scope = get_search_scope(&ldap_info.base_dn, &dn_parts, &request.scope);
-    debug!(?request.base, ?scope);
-    // Disambiguate the lifetimes.
-    fn cast<'a, T, R>(x: T) -> T
-    where
-        T: Fn(&'a LdapFilter) -> R + 'a,
-    {
-        x
+    filter: &LdapFilter,
+) -> LdapResult<InternalSearchResults<'a>> {
+    let roles = get_sudo_roles_list(filter, &request.base, backend_handler).await?;
+    Ok(InternalSearchResults::Raw(convert_sudo_roles_to_ldap_op(
+        roles,
+        &request.attrs,
+        ldap_info,
+    )))
+}
+
+/// The service accounts, whose DNs are under `ou=services` in the members of the groups. Only
+/// fetched when the members are requested.
+async fn get_service_account_ids(
+    backend_handler: &impl UserAndGroupListerBackendHandler,
+    attributes: &[String],
+) -> LdapResult<HashSet<UserId>> {
+    let needs_members = attributes.is_empty()
+        || attributes.iter().any(|a| {
+            a == "*" || a.eq_ignore_ascii_case("member") || a.eq_ignore_ascii_case("uniquemember")
+        });
+    if !needs_members {
+        return Ok(HashSet::new());
     }
+    Ok(backend_handler
+        .list_users(Some(UserRequestFilter::ServiceAccount(true)), false)
+        .await
+        .map_err(|e| LdapError {
+            code: LdapResultCode::Other,
+            message: format!("Error while listing the service accounts: {e:#}"),
+        })?
+        .into_iter()
+        .map(|u| u.user.user_id)
+        .collect())
+}
 
-    let get_user_list = cast(async |filter: &LdapFilter| {
-        let need_groups = request
-            .attrs
-            .iter()
-            .any(|s| s.eq_ignore_ascii_case("memberof"));
+async fn do_search_internal<'a>(
+    ldap_info: &'a LdapInfo,
+    backend_handler: &'a impl UserAndGroupListerBackendHandler,
+    request: &'a LdapSearchRequest,
+    schema: &'a PublicSchema,
+) -> LdapResult<InternalSearchResults<'a>> {
+    let dn_parts = parse_distinguished_name(&request.base)?;
+    let scope = get_search_scope(&ldap_info.base_dn, &dn_parts, &request.scope);
+    debug!(?request.base, ?scope);
+    let need_groups = request
+        .attrs
+        .iter()
+        .any(|s| s.eq_ignore_ascii_case("memberof"));
+    let get_user_list = |filter: &LdapFilter, service_accounts: Option<bool>| {
         get_user_list(
             ldap_info,
             filter,
+            service_accounts,
             need_groups,
             &request.base,
             backend_handler,
             schema,
         )
-        .await
-    });
-    let get_group_list = cast(|filter: &LdapFilter| async {
-        get_groups_list(ldap_info, filter, &request.base, backend_handler, schema).await
-    });
+    };
+    let get_group_list = |filter: &LdapFilter| {
+        get_groups_list(ldap_info, filter, &request.base, backend_handler, schema)
+    };
     Ok(match scope {
+        // The sudo rules are only looked up by clients asking for them, e.g. SSSD searching
+        // from the base DN.
+        SearchScope::Global if is_sudo_role_filter(&request.filter) => {
+            get_sudo_role_results(ldap_info, backend_handler, request, &request.filter).await?
+        }
         SearchScope::Global => {
-            let users = get_user_list(&request.filter).await;
+            let users = get_user_list(&request.filter, None).await;
             let groups = get_group_list(&request.filter).await;
             match (users, groups) {
                 (Ok(users), Err(e)) => {
                     warn!("Error while getting groups: {:#}", e);
-                    InternalSearchResults::UsersAndGroups(users, Vec::new())
+                    InternalSearchResults::UsersAndGroups(
+                        users,
+                        stream::empty().boxed(),
+                        HashSet::new(),
+                    )
                 }
                 (Err(e), Ok(groups)) => {
                     warn!("Error while getting users: {:#}", e);
-                    InternalSearchResults::UsersAndGroups(Vec::new(), groups)
+                    InternalSearchResults::UsersAndGroups(
+                        stream::empty().boxed(),
+                        groups,
+                        get_service_account_ids(backend_handler, &request.attrs).await?,
+                    )
                 }
                 (Err(user_error), Err(_)) => InternalSearchResults::Raw(vec![make_search_error(
                     user_error.code,
                     user_error.message,
                 )]),
-                (Ok(users), Ok(groups)) => InternalSearchResults::UsersAndGroups(users, groups),
+                (Ok(users), Ok(groups)) => InternalSearchResults::UsersAndGroups(
+                    users,
+                    groups,
+                    get_service_account_ids(backend_handler, &request.attrs).await?,
+                ),
             }
         }
-        SearchScope::Users => {
-            InternalSearchResults::UsersAndGroups(get_user_list(&request.filter).await?, Vec::new())
-        }
+        SearchScope::Users { service_accounts } => InternalSearchResults::UsersAndGroups(
+            get_user_list(&request.filter, Some(service_accounts)).await?,
+            stream::empty().boxed(),
+            HashSet::new(),
+        ),
         SearchScope::Groups => InternalSearchResults::UsersAndGroups(
-            Vec::new(),
+            stream::empty().boxed(),
             get_group_list(&request.filter).await?,
+            get_service_account_ids(backend_handler, &request.attrs).await?,
         ),
-        SearchScope::User(filter) => {
+        SearchScope::User {
+            service_accounts,
+            filter,
+        } => {
             let filter = LdapFilter::And(vec![request.filter.clone(), filter]);
-            InternalSearchResults::UsersAndGroups(get_user_list(&filter).await?, Vec::new())
+            InternalSearchResults::UsersAndGroups(
+                get_user_list(&filter, Some(service_accounts)).await?,
+                stream::empty().boxed(),
+                HashSet::new(),
+            )
         }
         SearchScope::Group(filter) => {
             let filter = LdapFilter::And(vec![request.filter.clone(), filter]);
-            InternalSearchResults::UsersAndGroups(Vec::new(), get_group_list(&filter).await?)
+            InternalSearchResults::UsersAndGroups(
+                stream::empty().boxed(),
+                get_group_list(&filter).await?,
+                get_service_account_ids(backend_handler, &request.attrs).await?,
+            )
         }
-        SearchScope::UserOuOnly | SearchScope::GroupOuOnly => {
+        SearchScope::SudoRoles => {
+            get_sudo_role_results(ldap_info, backend_handler, request, &request.filter).await?
+        }
+        SearchScope::SudoRole(filter) => {
+            let filter = LdapFilter::And(vec![request.filter.clone(), filter]);
+            get_sudo_role_results(ldap_info, backend_handler, request, &filter).await?
+        }
+        SearchScope::UserOuOnly | SearchScope::GroupOuOnly | SearchScope::SudoersOuOnly => {
             InternalSearchResults::Raw(vec![LdapOp::SearchResultEntry(LdapSearchResultEntry {
                 dn: request.base.clone(),
                 attributes: vec![LdapPartialAttribute {
@@ -345,8 +533,12 @@ async fn do_search_internal(
         }
         SearchScope::Unknown => {
             warn!(
-                r#"The requested search tree "{}" matches neither the user subtree "ou=people,{}" nor the group subtree "ou=groups,{}""#,
-                &request.base, &ldap_info.base_dn_str, &ldap_info.base_dn_str
+                r#"The requested search tree "{}" matches neither the user subtree "ou=people,{}", the service accounts subtree "ou=services,{}", the group subtree "ou=groups,{}" nor the sudo rules subtree "ou=sudoers,{}""#,
+                &request.base,
+                &ldap_info.base_dn_str,
+                &ldap_info.base_dn_str,
+                &ldap_info.base_dn_str,
+                &ldap_info.base_dn_str
             );
             InternalSearchResults::Empty
         }
@@ -367,35 +559,319 @@ pub async fn do_search(
     ldap_info: &LdapInfo,
     request: &LdapSearchRequest,
 ) -> LdapResult<Vec<LdapOp>> {
-    let schema = PublicSchema::from(backend_handler.get_schema().await.map_err(|e| LdapError {
-        code: LdapResultCode::OperationsError,
-        message: format!("Unable to get schema: {e:#}"),
-    })?);
-    let search_results = do_search_internal(ldap_info, backend_handler, request, &schema).await?;
-    let mut results = match search_results {
-        InternalSearchResults::UsersAndGroups(users, groups) => {
-            convert_users_to_ldap_op(users, &request.attrs, ldap_info, &schema)
-                .chain(convert_groups_to_ldap_op(
-                    groups,
-                    &request.attrs,
-                    ldap_info,
-                    backend_handler.user_filter(),
-                    &schema,
-                ))
-                .collect()
+    let schema = get_search_schema(backend_handler).await?;
+    Ok(
+        do_search_with_controls(backend_handler, ldap_info, &schema, request, &[])
+            .await?
+            .0
+            .collect()
+            .await,
+    )
+}
+
+pub async fn get_search_schema(
+    backend_handler: &impl UserAndGroupListerBackendHandler,
+) -> LdapResult<PublicSchema> {
+    Ok(PublicSchema::from(
+        backend_handler.get_schema().await.map_err(|e| LdapError {
+            code: LdapResultCode::OperationsError,
+            message: format!("Unable to get schema: {e:#}"),
+        })?,
+    ))
+}
+
+/// Ends the stream at the first error, which is reported in the final `SearchResultDone`.
+fn finish_search_results<'a>(
+    entries: impl Stream<Item = LdapResult<LdapOp>> + Send + 'a,
+) -> SearchResultStream<'a> {
+    entries
+        .chain(stream::once(future::ready(Ok(make_search_success()))))
+        .scan(false, |failed, result| {
+            future::ready(match result {
+                _ if *failed => None,
+                Ok(op) => Some(op),
+                Err(e) => {
+                    *failed = true;
+                    Some(make_search_error(e.code, e.message))
+                }
+            })
+        })
+        .boxed()
+}
+
+/// Returns the search results, and the controls to attach to the final `SearchResultDone`.
+///
+/// The results are streamed from the backend: errors that happen after the first entries are
+/// reported in the `SearchResultDone`.
+#[instrument(skip_all, level = "debug")]
+pub async fn do_search_with_controls<'a>(
+    backend_handler: &'a impl UserAndGroupListerBackendHandler,
+    ldap_info: &'a LdapInfo,
+    schema: &'a PublicSchema,
+    request: &'a LdapSearchRequest,
+    controls: &[LdapControl],
+) -> LdapResult<(SearchResultStream<'a>, Vec<LdapControl>)> {
+    let sort = parse_sort_request(controls)?;
+    let vlv = parse_vlv_request(controls)?;
+    if sort.is_some() || vlv.is_some() {
+        if let Some((results, response_controls)) =
+            do_sorted_search(ldap_info, backend_handler, request, schema, sort, vlv).await?
+        {
+            return Ok((stream::iter(results).boxed(), response_controls));
+        }
+    }
+    let results = match do_search_internal(ldap_info, backend_handler, request, schema).await? {
+        InternalSearchResults::UsersAndGroups(users, groups, service_accounts) => {
+            finish_search_results(
+                convert_users_to_ldap_op(users, &request.attrs, ldap_info, schema).chain(
+                    convert_groups_to_ldap_op(
+                        groups,
+                        &request.attrs,
+                        ldap_info,
+                        backend_handler.user_filter(),
+                        service_accounts,
+                        schema,
+                    ),
+                ),
+            )
+        }
+        InternalSearchResults::Raw(mut raw_results) => {
+            if !matches!(raw_results.last(), Some(LdapOp::SearchResultDone(_))) {
+                raw_results.push(make_search_success());
+            }
+            stream::iter(raw_results).boxed()
         }
-        InternalSearchResults::Raw(raw_results) => raw_results,
-        InternalSearchResults::Empty => Vec::new(),
+        InternalSearchResults::Empty => stream::iter([make_search_success()]).boxed(),
     };
-    if !matches!(results.last(), Some(LdapOp::SearchResultDone(_))) {
-        results.push(make_search_success());
+    Ok((results, Vec::new()))
+}
+
+fn get_user_sort_order(
+    sort_request: &SortRequest,
+    ldap_info: &LdapInfo,
+    schema: &PublicSchema,
+) -> Option<UserSortOrder> {
+    // Only the primary sort key is taken into account.
+    let key = sort_request.keys.first()?;
+    match map_user_field(
+        &AttributeName::from(key.attribute.as_str()),
+        schema,
+        ldap_info.ad_compatibility,
+    ) {
+        UserFieldType::PrimaryField(
+            column @ (UserColumn::UserId | UserColumn::Email | UserColumn::DisplayName),
+        ) => Some(UserSortOrder {
+            column,
+            descending: key.reverse,
+        }),
+        _ => None,
     }
-    Ok(results)
+}
+
+/// The maximum number of entries returned in a single virtual list view window.
+const MAX_VLV_WINDOW_SIZE: u64 = 1000;
+
+/// Handles searches with the server side sort and/or virtual list view controls.
+///
+/// Sorting is done by the database, and only user searches are supported: a virtual list view
+/// of the whole tree only contains the users. Returns `None` if the controls should be ignored
+/// and the search performed normally.
+async fn do_sorted_search(
+    ldap_info: &LdapInfo,
+    backend_handler: &impl UserAndGroupListerBackendHandler,
+    request: &LdapSearchRequest,
+    schema: &PublicSchema,
+    sort: Option<SortRequest>,
+    vlv: Option<VlvRequest>,
+) -> LdapResult<Option<(Vec<LdapOp>, Vec<LdapControl>)>> {
+    let critical =
+        sort.as_ref().is_some_and(|s| s.critical) || vlv.as_ref().is_some_and(|v| v.critical);
+    let dn_parts = parse_distinguished_name(&request.base)?;
+    let (user_filter, service_accounts) =
+        match get_search_scope(&ldap_info.base_dn, &dn_parts, &request.scope) {
+            SearchScope::Users { service_accounts } => {
+                (request.filter.clone(), Some(service_accounts))
+            }
+            SearchScope::User {
+                service_accounts,
+                filter,
+            } => (
+                LdapFilter::And(vec![request.filter.clone(), filter]),
+                Some(service_accounts),
+            ),
+            SearchScope::Global if vlv.is_some() => (request.filter.clone(), None),
+            _ if critical => {
+                return Err(LdapError {
+                    code: LdapResultCode::UnavailableCriticalExtension,
+                    message: "Sorting is only supported for user searches".to_string(),
+                });
+            }
+            _ => return Ok(None),
+        };
+    let Some(sort) = sort else {
+        // A virtual list view needs a sort order.
+        let vlv = vlv.expect("sort or vlv should be present");
+        return Ok(Some((
+            vec![make_search_error(
+                LdapResultCode::UnwillingToPerform,
+                "The virtual list view control requires the server side sort control".to_string(),
+            )],
+            vec![make_vlv_response(
+                0,
+                0,
+                CONTROL_RESULT_SORT_CONTROL_MISSING,
+                vlv.context_id.as_deref(),
+            )],
+        )));
+    };
+    let (order, sort_response) = match get_user_sort_order(&sort, ldap_info, schema) {
+        Some(order) => (order, make_sort_response(CONTROL_RESULT_SUCCESS, None)),
+        None if sort.critical => {
+            return Err(LdapError {
+                code: LdapResultCode::UnavailableCriticalExtension,
+                message: format!("Unsupported sort key: {}", sort.keys[0].attribute),
+            });
+        }
+        // Fall back to the user id, results still need a stable order for the list view.
+        None => (
+            UserSortOrder {
+                column: UserColumn::UserId,
+                descending: false,
+            },
+            make_sort_response(
+                CONTROL_RESULT_UNWILLING_TO_PERFORM,
+                Some(&sort.keys[0].attribute),
+            ),
+        ),
+    };
+    let filters = restrict_to_user_kind(
+        convert_user_filter(ldap_info, &user_filter, schema)?,
+        service_accounts,
+    );
+    debug!(?filters, ?order, ?vlv);
+    let need_groups = request
+        .attrs
+        .iter()
+        .any(|s| s.eq_ignore_ascii_case("memberof"));
+    let backend_error = |e: DomainError| LdapError {
+        code: LdapResultCode::Other,
+        message: format!(r#"Error while searching user "{}": {e:#}"#, request.base),
+    };
+    let mut response_controls = vec![sort_response];
+    let mut size_limit_exceeded = false;
+    let users = match vlv {
+        None => {
+            // Only the client's size limit applies, the sorting is done by the database.
+            let size_limit = u64::try_from(request.sizelimit)
+                .ok()
+                .filter(|limit| *limit > 0);
+            // Fetch one more entry to know whether the limit was reached.
+            let mut users = backend_handler
+                .list_users_sorted(
+                    Some(filters),
+                    need_groups,
+                    order,
+                    0,
+                    size_limit.map(|limit| limit + 1),
+                )
+                .await
+                .map_err(backend_error)?;
+            if let Some(limit) = size_limit.filter(|limit| users.len() as u64 > *limit) {
+                users.truncate(limit as usize);
+                size_limit_exceeded = true;
+            }
+            users
+        }
+        Some(vlv) => {
+            let content_count = backend_handler
+                .count_users(Some(filters.clone()))
+                .await
+                .map_err(backend_error)?;
+            let target_position = match &vlv.target {
+                VlvTarget::ByOffset {
+                    offset,
+                    content_count: client_count,
+                } => get_vlv_target_from_offset(*offset, *client_count, content_count),
+                VlvTarget::GreaterThanOrEqual(value) => {
+                    // Count the entries that sort strictly before the assertion value.
+                    let at_or_after = if order.descending {
+                        UserRequestFilter::LessOrEqual(order.column, value.clone())
+                    } else {
+                        UserRequestFilter::GreaterOrEqual(order.column, value.clone())
+                    };
+                    backend_handler
+                        .count_users(Some(UserRequestFilter::And(vec![
+                            filters.clone(),
+                            UserRequestFilter::Not(Box::new(at_or_after)),
+                        ])))
+                        .await
+                        .map_err(backend_error)?
+                        + 1
+                }
+            };
+            response_controls.push(make_vlv_response(
+                target_position,
+                content_count,
+                CONTROL_RESULT_SUCCESS,
+                vlv.context_id.as_deref(),
+            ));
+            let first = target_position.saturating_sub(vlv.before_count).max(1);
+            let last = target_position
+                .saturating_add(vlv.after_count)
+                .min(content_count)
+                .min(first.saturating_add(MAX_VLV_WINDOW_SIZE - 1));
+            if first > last {
+                Vec::new()
+            } else {
+                backend_handler
+                    .list_users_sorted(
+                        Some(filters),
+                        need_groups,
+                        order,
+                        first - 1,
+                        Some(last - first + 1),
+                    )
+                    .await
+                    .map_err(backend_error)?
+            }
+        }
+    };
+    let mut results: Vec<_> = convert_users_to_ldap_op(
+        stream::iter(users.into_iter().map(Ok)),
+        &request.attrs,
+        ldap_info,
+        schema,
+    )
+    .try_collect()
+    .await?;
+    results.push(if size_limit_exceeded {
+        make_search_error(
+            LdapResultCode::SizeLimitExceeded,
+            "Size limit exceeded".to_string(),
+        )
+    } else {
+        make_search_success()
+    });
+    Ok(Some((results, response_controls)))
+}
+
+/// Converts a (1-based) VLV offset to a position in the list, scaling it if the client's estimate
+/// of the content count is out of date.
+fn get_vlv_target_from_offset(offset: u64, client_count: u64, content_count: u64) -> u64 {
+    let target = if client_count == 0 || client_count == content_count {
+        offset
+    } else if offset >= client_count {
+        content_count
+    } else {
+        ((offset as f64) * (content_count as f64) / (client_count as f64)).round() as u64
+    };
+    target.clamp(1, content_count.max(1))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::controls::{SORT_REQUEST_OID, VLV_REQUEST_OID};
     use crate::{
         core::error::LdapError,
         handler::tests::{
@@ -404,19 +880,21 @@ mod tests {
         },
     };
     use chrono::{DateTime, Duration, NaiveDateTime, TimeZone};
-    use ldap3_proto::proto::{LdapDerefAliases, LdapSearchScope, LdapSubstringFilter};
+    use ldap3_proto::proto::{
+        LdapDerefAliases, LdapMatchingRuleAssertion, LdapSearchScope, LdapSubstringFilter,
+    };
     use lldap_domain::{
         schema::{AttributeList, AttributeSchema, Schema},
         types::{
             Attribute, AttributeName, AttributeType, GroupDetails, GroupId, JpegPhoto,
-            LdapObjectClass, User, UserId,
+            LdapObjectClass, MatchingRule, SshPublicKey, SudoRole, SudoRoleId, User, UserId,
         },
         uuid,
     };
     use lldap_domain_handlers::handler::*;
     use lldap_domain_model::model::UserColumn;
     use lldap_test_utils::MockTestBackendHandler;
-    use mockall::predicate::eq;
+    use mockall::predicate::{always, eq};
     use pretty_assertions::assert_eq;
 
     #[tokio::test]
@@ -435,7 +913,7 @@ mod tests {
         assert_eq!(
             ldap_handler.do_search_or_dse(&request).await,
             Ok(vec![
-                root_dse_response("dc=example,dc=com"),
+                root_dse_response("dc=example,dc=com", false),
                 make_search_success()
             ])
         );
@@ -532,9 +1010,11 @@ mod tests {
                 vals: vec![
                     b"( 1.3.6.1.4.1.1466.115.121.1.15 DESC 'Directory String' )".to_vec(),
                     b"( 1.3.6.1.4.1.1466.115.121.1.24 DESC 'Generalized Time' )".to_vec(),
+                    b"( 1.3.6.1.4.1.1466.115.121.1.26 DESC 'IA5 String' )".to_vec(),
                     b"( 1.3.6.1.4.1.1466.115.121.1.27 DESC 'Integer' )".to_vec(),
                     b"( 1.3.6.1.4.1.1466.115.121.1.28 DESC 'JPEG' X-NOT-HUMAN-READABLE 'TRUE' )"
-                        .to_vec()
+                        .to_vec(),
+                    b"( 1.3.6.1.4.1.1466.115.121.1.40 DESC 'Octet String' )".to_vec(),
                 ]
             }
         );
@@ -548,24 +1028,35 @@ mod tests {
                     b"( 2.1 NAME 'Integer' SYNTAX 1.3.6.1.4.1.1466.115.121.1.27 )".to_vec(),
                     b"( 2.2 NAME 'JpegPhoto' SYNTAX 1.3.6.1.4.1.1466.115.121.1.28 )".to_vec(),
                     b"( 2.3 NAME 'DateTime' SYNTAX 1.3.6.1.4.1.1466.115.121.1.24 )".to_vec(),
-                    b"( 2.4 NAME 'avatar' DESC 'LLDAP: builtin attribute' SUP JpegPhoto )".to_vec(),
-                    b"( 2.5 NAME 'creation_date' DESC 'LLDAP: builtin attribute' SUP DateTime )"
+                    b"( 2.4 NAME 'SshPublicKey' SYNTAX 1.3.6.1.4.1.1466.115.121.1.40 )".to_vec(),
+                    b"( 2.5 NAME 'avatar' DESC 'LLDAP: builtin attribute' SUP JpegPhoto EQUALITY octetStringMatch )".to_vec(),
+                    b"( 2.6 NAME 'creation_date' DESC 'LLDAP: builtin attribute' SUP DateTime EQUALITY generalizedTimeMatch )"
                         .to_vec(),
-                    b"( 2.6 NAME 'display_name' DESC 'LLDAP: builtin attribute' SUP String )"
+                    b"( 2.7 NAME 'display_name' DESC 'LLDAP: builtin attribute' SUP String EQUALITY caseIgnoreMatch )"
                         .to_vec(),
-                    b"( 2.7 NAME 'first_name' DESC 'LLDAP: builtin attribute' SUP String )"
+                    b"( 2.8 NAME 'first_name' DESC 'LLDAP: builtin attribute' SUP String EQUALITY caseIgnoreMatch )"
                         .to_vec(),
-                    b"( 2.8 NAME 'last_name' DESC 'LLDAP: builtin attribute' SUP String )".to_vec(),
-                    b"( 2.9 NAME 'mail' DESC 'LLDAP: builtin attribute' SUP String )".to_vec(),
-                    b"( 2.10 NAME 'user_id' DESC 'LLDAP: builtin attribute' SUP String )".to_vec(),
-                    b"( 2.11 NAME 'uuid' DESC 'LLDAP: builtin attribute' SUP String )".to_vec(),
-                    b"( 2.12 NAME 'creation_date' DESC 'LLDAP: builtin attribute' SUP DateTime )"
+                    b"( 2.9 NAME 'last_name' DESC 'LLDAP: builtin attribute' SUP String EQUALITY caseIgnoreMatch )".to_vec(),
+                    b"( 2.10 NAME 'mail' DESC 'LLDAP: builtin attribute' SUP String EQUALITY caseIgnoreMatch )".to_vec(),
+                    b"( 2.11 NAME 'user_id' DESC 'LLDAP: builtin attribute' SUP String EQUALITY caseIgnoreMatch )".to_vec(),
+                    b"( 2.12 NAME 'uuid' DESC 'LLDAP: builtin attribute' SUP String EQUALITY caseIgnoreMatch )".to_vec(),
+                    b"( 2.13 NAME 'creation_date' DESC 'LLDAP: builtin attribute' SUP DateTime EQUALITY generalizedTimeMatch )"
                         .to_vec(),
-                    b"( 2.13 NAME 'display_name' DESC 'LLDAP: builtin attribute' SUP String )"
+                    b"( 2.14 NAME 'display_name' DESC 'LLDAP: builtin attribute' SUP String EQUALITY caseIgnoreMatch )"
                         .to_vec(),
-                    b"( 2.14 NAME 'group_id' DESC 'LLDAP: builtin attribute' SUP Integer )"
+                    b"( 2.15 NAME 'group_id' DESC 'LLDAP: builtin attribute' SUP Integer EQUALITY integerMatch )"
                         .to_vec(),
-                    b"( 2.15 NAME 'uuid' DESC 'LLDAP: builtin attribute' SUP String )".to_vec()
+                    b"( 2.16 NAME 'uuid' DESC 'LLDAP: builtin attribute' SUP String EQUALITY caseIgnoreMatch )".to_vec(),
+                    b"( 1.3.6.1.4.1.15953.9.1.1 NAME 'sudoUser' DESC 'User(s) who may run sudo' EQUALITY caseExactIA5Match SUBSTR caseExactIA5SubstringsMatch SYNTAX 1.3.6.1.4.1.1466.115.121.1.26 )".to_vec(),
+                    b"( 1.3.6.1.4.1.15953.9.1.2 NAME 'sudoHost' DESC 'Host(s) who may run sudo' EQUALITY caseExactIA5Match SUBSTR caseExactIA5SubstringsMatch SYNTAX 1.3.6.1.4.1.1466.115.121.1.26 )".to_vec(),
+                    b"( 1.3.6.1.4.1.15953.9.1.3 NAME 'sudoCommand' DESC 'Command(s) to be executed by sudo' EQUALITY caseExactIA5Match SYNTAX 1.3.6.1.4.1.1466.115.121.1.26 )".to_vec(),
+                    b"( 1.3.6.1.4.1.15953.9.1.5 NAME 'sudoOption' DESC 'Options(s) followed by sudo' EQUALITY caseExactIA5Match SYNTAX 1.3.6.1.4.1.1466.115.121.1.26 )".to_vec(),
+                    b"( 1.3.6.1.4.1.15953.9.1.6 NAME 'sudoRunAsUser' DESC 'User(s) impersonated by sudo' EQUALITY caseExactIA5Match SYNTAX 1.3.6.1.4.1.1466.115.121.1.26 )".to_vec(),
+                    b"( 1.3.6.1.4.1.15953.9.1.7 NAME 'sudoRunAsGroup' DESC 'Group(s) impersonated by sudo' EQUALITY caseExactIA5Match SYNTAX 1.3.6.1.4.1.1466.115.121.1.26 )".to_vec(),
+                    b"( 1.3.6.1.4.1.15953.9.1.10 NAME 'sudoOrder' DESC 'an integer to order the sudoRole entries' EQUALITY integerMatch ORDERING integerOrderingMatch SYNTAX 1.3.6.1.4.1.1466.115.121.1.27 )".to_vec(),
+                    b"( 2.16.840.1.113730.3.1.610 NAME 'nsAccountLock' DESC 'Operational attribute for Account Inactivation' SYNTAX 1.3.6.1.4.1.1466.115.121.1.15 SINGLE-VALUE USAGE directoryOperation )".to_vec(),
+                    b"( 1.3.6.1.4.1.42.2.27.8.1.17 NAME 'pwdAccountLockedTime' DESC 'The time an user account was locked' EQUALITY generalizedTimeMatch ORDERING generalizedTimeOrderingMatch SYNTAX 1.3.6.1.4.1.1466.115.121.1.24 SINGLE-VALUE NO-USER-MODIFICATION USAGE directoryOperation )".to_vec(),
+                    b"( 1.3.6.1.1.1.1.10 NAME 'shadowExpire' EQUALITY integerMatch SYNTAX 1.3.6.1.4.1.1466.115.121.1.27 SINGLE-VALUE )".to_vec(),
                 ]
             }
         );
@@ -576,6 +1067,7 @@ mod tests {
                 vals: vec![
                     b"( 3.0 NAME ( 'inetOrgPerson' 'posixAccount' 'mailAccount' 'person' 'customUserClass' ) DESC 'LLDAP builtin: a person' STRUCTURAL MUST ( mail $ user_id ) MAY ( avatar $ creation_date $ display_name $ first_name $ last_name $ uuid ) )".to_vec(),
                     b"( 3.1 NAME ( 'groupOfUniqueNames' 'groupOfNames' ) DESC 'LLDAP builtin: a group' STRUCTURAL MUST ( display_name ) MAY ( creation_date $ group_id $ uuid ) )".to_vec(),
+                    b"( 1.3.6.1.4.1.15953.9.2.1 NAME 'sudoRole' SUP top STRUCTURAL DESC 'Sudoer Entries' MUST ( cn ) MAY ( sudoUser $ sudoHost $ sudoCommand $ sudoRunAsUser $ sudoRunAsGroup $ sudoOption $ sudoOrder $ description ) )".to_vec(),
                 ]
             }
         );
@@ -597,7 +1089,7 @@ mod tests {
         mock.expect_list_users()
             .with(
                 eq(Some(UserRequestFilter::And(vec![
-                    UserRequestFilter::And(Vec::new()),
+                    UserRequestFilter::And(vec![UserRequestFilter::ServiceAccount(false)]),
                     UserRequestFilter::UserId(UserId::new("test")),
                 ]))),
                 eq(false),
@@ -617,14 +1109,298 @@ mod tests {
         let request =
             make_user_search_request::<String>(LdapFilter::And(vec![]), vec!["1.1".to_string()]);
         assert_eq!(
-            ldap_handler.do_search_or_dse(&request).await,
-            Ok(vec![
-                LdapOp::SearchResultEntry(LdapSearchResultEntry {
-                    dn: "uid=test,ou=people,dc=example,dc=com".to_string(),
-                    attributes: vec![],
-                }),
-                make_search_success()
-            ]),
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(vec![
+                LdapOp::SearchResultEntry(LdapSearchResultEntry {
+                    dn: "uid=test,ou=people,dc=example,dc=com".to_string(),
+                    attributes: vec![],
+                }),
+                make_search_success()
+            ]),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_user_ad_compatibility() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users()
+            .with(
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::UserId(UserId::new("bob")),
+                    UserRequestFilter::Not(Box::new(UserRequestFilter::Disabled(true))),
+                    UserRequestFilter::MemberOf("group_1".into()),
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(false),
+            )
+            .times(1)
+            .return_once(|_, _| {
+                Ok(vec![UserAndGroups {
+                    user: User {
+                        user_id: UserId::new("bob"),
+                        uuid: uuid!("698e1d5f-7a40-3151-8745-b9b8a37839da"),
+                        ..Default::default()
+                    },
+                    groups: None,
+                }])
+            });
+        let ldap_handler = setup_bound_admin_handler(mock)
+            .await
+            .with_ad_compatibility();
+        let request = make_user_search_request(
+            LdapFilter::And(vec![
+                LdapFilter::Equality("sAMAccountName".to_string(), "bob".to_string()),
+                LdapFilter::Not(Box::new(LdapFilter::Extensible(
+                    LdapMatchingRuleAssertion {
+                        matching_rule: Some("1.2.840.113556.1.4.803".to_string()),
+                        type_: Some("userAccountControl".to_string()),
+                        match_value: "2".to_string(),
+                        dn_attributes: false,
+                    },
+                ))),
+                LdapFilter::Extensible(LdapMatchingRuleAssertion {
+                    matching_rule: Some("1.2.840.113556.1.4.1941".to_string()),
+                    type_: Some("memberOf".to_string()),
+                    match_value: "cn=group_1,ou=groups,dc=example,dc=com".to_string(),
+                    dn_attributes: false,
+                }),
+            ]),
+            vec![
+                "sAMAccountName",
+                "userPrincipalName",
+                "objectGUID",
+                "userAccountControl",
+                "objectClass",
+            ],
+        );
+        assert_eq!(
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(vec![
+                LdapOp::SearchResultEntry(LdapSearchResultEntry {
+                    dn: "uid=bob,ou=people,dc=example,dc=com".to_string(),
+                    attributes: vec![
+                        LdapPartialAttribute {
+                            atype: "sAMAccountName".to_string(),
+                            vals: vec![b"bob".to_vec()]
+                        },
+                        LdapPartialAttribute {
+                            atype: "userPrincipalName".to_string(),
+                            vals: vec![b"bob@example.com".to_vec()]
+                        },
+                        LdapPartialAttribute {
+                            atype: "objectGUID".to_string(),
+                            vals: vec![vec![
+                                0x5f, 0x1d, 0x8e, 0x69, 0x40, 0x7a, 0x51, 0x31, 0x87, 0x45, 0xb9,
+                                0xb8, 0xa3, 0x78, 0x39, 0xda
+                            ]]
+                        },
+                        LdapPartialAttribute {
+                            atype: "userAccountControl".to_string(),
+                            vals: vec![b"512".to_vec()]
+                        },
+                        LdapPartialAttribute {
+                            atype: "objectClass".to_string(),
+                            vals: vec![
+                                b"inetOrgPerson".to_vec(),
+                                b"posixAccount".to_vec(),
+                                b"mailAccount".to_vec(),
+                                b"person".to_vec(),
+                                b"customUserClass".to_vec(),
+                                b"user".to_vec(),
+                                b"organizationalPerson".to_vec(),
+                            ]
+                        },
+                    ],
+                }),
+                make_search_success()
+            ]),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_user_account_lock() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users()
+            .with(
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::Or(vec![
+                        UserRequestFilter::Disabled(true),
+                        UserRequestFilter::Disabled(true),
+                    ]),
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(false),
+            )
+            .times(1)
+            .return_once(|_, _| {
+                Ok(vec![
+                    UserAndGroups {
+                        user: User {
+                            user_id: UserId::new("bob"),
+                            disabled: true,
+                            ..Default::default()
+                        },
+                        groups: None,
+                    },
+                    UserAndGroups {
+                        user: User {
+                            user_id: UserId::new("jim"),
+                            ..Default::default()
+                        },
+                        groups: None,
+                    },
+                ])
+            });
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_user_search_request(
+            LdapFilter::Or(vec![
+                LdapFilter::Equality("nsAccountLock".to_string(), "true".to_string()),
+                LdapFilter::Present("pwdAccountLockedTime".to_string()),
+            ]),
+            vec!["nsAccountLock", "pwdAccountLockedTime"],
+        );
+        assert_eq!(
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(vec![
+                LdapOp::SearchResultEntry(LdapSearchResultEntry {
+                    dn: "uid=bob,ou=people,dc=example,dc=com".to_string(),
+                    attributes: vec![
+                        LdapPartialAttribute {
+                            atype: "nsAccountLock".to_string(),
+                            vals: vec![b"TRUE".to_vec()]
+                        },
+                        LdapPartialAttribute {
+                            atype: "pwdAccountLockedTime".to_string(),
+                            vals: vec![b"000001010000Z".to_vec()]
+                        },
+                    ],
+                }),
+                LdapOp::SearchResultEntry(LdapSearchResultEntry {
+                    dn: "uid=jim,ou=people,dc=example,dc=com".to_string(),
+                    attributes: vec![LdapPartialAttribute {
+                        atype: "nsAccountLock".to_string(),
+                        vals: vec![b"FALSE".to_vec()]
+                    }],
+                }),
+                make_search_success()
+            ]),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_user_account_expiration() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users()
+            .with(
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::Or(vec![
+                        UserRequestFilter::Expires(true),
+                        UserRequestFilter::Expires(false),
+                    ]),
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(false),
+            )
+            .times(1)
+            .return_once(|_, _| {
+                Ok(vec![
+                    UserAndGroups {
+                        user: User {
+                            user_id: UserId::new("bob"),
+                            expiration_date: Some(
+                                chrono::Utc
+                                    .timestamp_opt(1704153600, 0)
+                                    .unwrap()
+                                    .naive_utc(),
+                            ),
+                            ..Default::default()
+                        },
+                        groups: None,
+                    },
+                    UserAndGroups {
+                        user: User {
+                            user_id: UserId::new("jim"),
+                            ..Default::default()
+                        },
+                        groups: None,
+                    },
+                ])
+            });
+        let ldap_handler = setup_bound_admin_handler(mock)
+            .await
+            .with_ad_compatibility();
+        let request = make_user_search_request(
+            LdapFilter::Or(vec![
+                LdapFilter::Present("shadowExpire".to_string()),
+                LdapFilter::Equality(
+                    "accountExpires".to_string(),
+                    "9223372036854775807".to_string(),
+                ),
+            ]),
+            vec!["shadowExpire", "accountExpires"],
+        );
+        assert_eq!(
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(vec![
+                LdapOp::SearchResultEntry(LdapSearchResultEntry {
+                    dn: "uid=bob,ou=people,dc=example,dc=com".to_string(),
+                    attributes: vec![
+                        LdapPartialAttribute {
+                            atype: "shadowExpire".to_string(),
+                            vals: vec![b"19724".to_vec()]
+                        },
+                        LdapPartialAttribute {
+                            atype: "accountExpires".to_string(),
+                            vals: vec![b"133486272000000000".to_vec()]
+                        },
+                    ],
+                }),
+                LdapOp::SearchResultEntry(LdapSearchResultEntry {
+                    dn: "uid=jim,ou=people,dc=example,dc=com".to_string(),
+                    attributes: vec![LdapPartialAttribute {
+                        atype: "accountExpires".to_string(),
+                        vals: vec![b"9223372036854775807".to_vec()]
+                    }],
+                }),
+                make_search_success()
+            ]),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_root_dse_ad_compatibility() {
+        let ldap_handler = setup_bound_admin_handler(MockTestBackendHandler::new())
+            .await
+            .with_ad_compatibility();
+        let request = LdapSearchRequest {
+            base: "".to_string(),
+            scope: LdapSearchScope::Base,
+            aliases: LdapDerefAliases::Never,
+            sizelimit: 0,
+            timelimit: 0,
+            typesonly: false,
+            filter: LdapFilter::Present("objectClass".to_string()),
+            attrs: vec![],
+        };
+        let response = ldap_handler.do_search_or_dse(&request).await.unwrap();
+        let LdapOp::SearchResultEntry(entry) = &response[0] else {
+            panic!("Unexpected response: {response:?}");
+        };
+        let get = |atype: &str| {
+            entry
+                .attributes
+                .iter()
+                .find(|a| a.atype == atype)
+                .map(|a| a.vals.clone())
+        };
+        assert_eq!(
+            get("rootDomainNamingContext"),
+            Some(vec![b"dc=example,dc=com".to_vec()])
+        );
+        assert_eq!(get("dnsHostName"), Some(vec![b"example.com".to_vec()]));
+        assert_eq!(
+            get("supportedCapabilities"),
+            Some(vec![b"1.2.840.113556.1.4.800".to_vec()])
         );
     }
 
@@ -632,7 +1408,12 @@ mod tests {
     async fn test_search_readonly_user() {
         let mut mock = MockTestBackendHandler::new();
         mock.expect_list_users()
-            .with(eq(Some(UserRequestFilter::And(Vec::new()))), eq(false))
+            .with(
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(false),
+            )
             .times(1)
             .return_once(|_, _| Ok(vec![]));
         let ldap_handler = setup_bound_readonly_handler(mock).await;
@@ -649,7 +1430,12 @@ mod tests {
     async fn test_search_member_of() {
         let mut mock = MockTestBackendHandler::new();
         mock.expect_list_users()
-            .with(eq(Some(UserRequestFilter::And(Vec::new()))), eq(true))
+            .with(
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(true),
+            )
             .times(1)
             .return_once(|_, _| {
                 Ok(vec![UserAndGroups {
@@ -663,6 +1449,7 @@ mod tests {
                         creation_date: chrono::Utc.timestamp_opt(42, 42).unwrap().naive_utc(),
                         uuid: uuid!("a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d8"),
                         attributes: Vec::new(),
+                        member_filter: None,
                     }]),
                 }])
             });
@@ -695,6 +1482,7 @@ mod tests {
                 eq(Some(UserRequestFilter::And(vec![
                     UserRequestFilter::And(Vec::new()),
                     UserRequestFilter::UserId(UserId::new("bob")),
+                    UserRequestFilter::ServiceAccount(false),
                 ]))),
                 eq(false),
             )
@@ -768,6 +1556,11 @@ mod tests {
                             .with_ymd_and_hms(2014, 7, 8, 9, 10, 11)
                             .unwrap()
                             .naive_utc(),
+                        disabled: false,
+                        expiration_date: None,
+                        mfa_enabled: false,
+                        is_service_account: false,
+                        allowed_sources: Vec::new(),
                     },
                     groups: None,
                 },
@@ -887,9 +1680,47 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_search_service_accounts_without_mail() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users().times(1).return_once(|_, _| {
+            Ok(vec![UserAndGroups {
+                user: User {
+                    user_id: UserId::new("backup"),
+                    is_service_account: true,
+                    ..Default::default()
+                },
+                groups: None,
+            }])
+        });
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_search_request(
+            "ou=services,dc=example,dc=com",
+            LdapFilter::And(vec![]),
+            vec!["uid", "mail"],
+        );
+        assert_eq!(
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(vec![
+                LdapOp::SearchResultEntry(LdapSearchResultEntry {
+                    dn: "uid=backup,ou=services,dc=example,dc=com".to_string(),
+                    attributes: vec![LdapPartialAttribute {
+                        atype: "uid".to_string(),
+                        vals: vec![b"backup".to_vec()]
+                    }],
+                }),
+                make_search_success(),
+            ])
+        );
+    }
+
     #[tokio::test]
     async fn test_search_groups() {
         let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users()
+            .with(eq(Some(UserRequestFilter::ServiceAccount(true))), eq(false))
+            .times(1)
+            .return_once(|_, _| Ok(vec![]));
         mock.expect_list_groups()
             .with(eq(Some(GroupRequestFilter::And(Vec::new()))))
             .times(1)
@@ -902,6 +1733,7 @@ mod tests {
                         users: vec![UserId::new("bob"), UserId::new("john")],
                         uuid: uuid!("04ac75e0-2900-3e21-926c-2f732c26b3fc"),
                         attributes: Vec::new(),
+                        member_filter: None,
                     },
                     Group {
                         id: GroupId(3),
@@ -910,6 +1742,7 @@ mod tests {
                         users: vec![UserId::new("john")],
                         uuid: uuid!("04ac75e0-2900-3e21-926c-2f732c26b3fc"),
                         attributes: Vec::new(),
+                        member_filter: None,
                     },
                 ])
             });
@@ -1000,6 +1833,7 @@ mod tests {
                     users: vec![UserId::new("bob"), UserId::new("john")],
                     uuid: uuid!("04ac75e0-2900-3e21-926c-2f732c26b3fc"),
                     attributes: Vec::new(),
+                    member_filter: None,
                 }])
             });
         let ldap_handler = setup_bound_admin_handler(mock).await;
@@ -1019,6 +1853,105 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_search_dynamic_group_member_url() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users()
+            .with(eq(Some(UserRequestFilter::ServiceAccount(true))), eq(false))
+            .times(1)
+            .return_once(|_, _| Ok(vec![]));
+        mock.expect_list_groups()
+            .with(eq(Some(GroupRequestFilter::GroupId(GroupId(1)))))
+            .times(1)
+            .return_once(|_| {
+                Ok(vec![Group {
+                    id: GroupId(1),
+                    display_name: "group_1".into(),
+                    creation_date: chrono::Utc.timestamp_opt(42, 42).unwrap().naive_utc(),
+                    users: vec![UserId::new("bob")],
+                    uuid: uuid!("04ac75e0-2900-3e21-926c-2f732c26b3fc"),
+                    attributes: Vec::new(),
+                    member_filter: Some("(uid=bob)".to_owned()),
+                }])
+            });
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_group_search_request(
+            LdapFilter::Equality("groupid".to_string(), "1".to_string()),
+            vec!["member", "memberURL"],
+        );
+        assert_eq!(
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(vec![
+                LdapOp::SearchResultEntry(LdapSearchResultEntry {
+                    dn: "cn=group_1,ou=groups,dc=example,dc=com".to_string(),
+                    attributes: vec![
+                        LdapPartialAttribute {
+                            atype: "member".to_string(),
+                            vals: vec![b"uid=bob,ou=people,dc=example,dc=com".to_vec()],
+                        },
+                        LdapPartialAttribute {
+                            atype: "memberURL".to_string(),
+                            vals: vec![
+                                b"ldap:///ou=people,dc=example,dc=com??sub?(uid=bob)".to_vec()
+                            ],
+                        },
+                    ],
+                }),
+                make_search_success(),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_group_service_account_member() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users()
+            .with(eq(Some(UserRequestFilter::ServiceAccount(true))), eq(false))
+            .times(1)
+            .return_once(|_, _| {
+                Ok(vec![UserAndGroups {
+                    user: User {
+                        user_id: UserId::new("backup"),
+                        is_service_account: true,
+                        ..Default::default()
+                    },
+                    groups: None,
+                }])
+            });
+        mock.expect_list_groups()
+            .with(eq(Some(GroupRequestFilter::And(Vec::new()))))
+            .times(1)
+            .return_once(|_| {
+                Ok(vec![Group {
+                    id: GroupId(1),
+                    display_name: "lldap_strict_readonly".into(),
+                    creation_date: chrono::Utc.timestamp_opt(42, 42).unwrap().naive_utc(),
+                    users: vec![UserId::new("backup"), UserId::new("bob")],
+                    uuid: uuid!("04ac75e0-2900-3e21-926c-2f732c26b3fc"),
+                    attributes: Vec::new(),
+                    member_filter: None,
+                }])
+            });
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_group_search_request(LdapFilter::And(vec![]), vec!["member"]);
+        assert_eq!(
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(vec![
+                LdapOp::SearchResultEntry(LdapSearchResultEntry {
+                    dn: "cn=lldap_strict_readonly,ou=groups,dc=example,dc=com".to_string(),
+                    attributes: vec![LdapPartialAttribute {
+                        atype: "member".to_string(),
+                        vals: vec![
+                            b"uid=backup,ou=services,dc=example,dc=com".to_vec(),
+                            b"uid=bob,ou=people,dc=example,dc=com".to_vec(),
+                        ],
+                    }],
+                }),
+                make_search_success(),
+            ])
+        );
+    }
+
     #[tokio::test]
     async fn test_search_groups_filter() {
         let mut mock = MockTestBackendHandler::new();
@@ -1050,6 +1983,7 @@ mod tests {
                     users: vec![],
                     uuid: uuid!("04ac75e0-2900-3e21-926c-2f732c26b3fc"),
                     attributes: Vec::new(),
+                    member_filter: None,
                 }])
             });
         let ldap_handler = setup_bound_admin_handler(mock).await;
@@ -1121,6 +2055,7 @@ mod tests {
                     users: vec![],
                     uuid: uuid!("04ac75e0-2900-3e21-926c-2f732c26b3fc"),
                     attributes: Vec::new(),
+                    member_filter: None,
                 }])
             });
         let ldap_handler = setup_bound_admin_handler(mock).await;
@@ -1150,16 +2085,10 @@ mod tests {
     async fn test_search_groups_filter_3() {
         let mut mock = MockTestBackendHandler::new();
         mock.expect_list_groups()
-            .with(eq(Some(GroupRequestFilter::Or(vec![
-                GroupRequestFilter::AttributeEquality(
-                    AttributeName::from("attr"),
-                    "TEST".to_string().into(),
-                ),
-                GroupRequestFilter::AttributeEquality(
-                    AttributeName::from("attr"),
-                    "test".to_string().into(),
-                ),
-            ]))))
+            .with(eq(Some(GroupRequestFilter::AttributeEqualityIgnoreCase(
+                AttributeName::from("attr"),
+                "TEST".to_string(),
+            ))))
             .times(1)
             .return_once(|_| {
                 Ok(vec![Group {
@@ -1172,6 +2101,7 @@ mod tests {
                         name: "Attr".into(),
                         value: "TEST".to_string().into(),
                     }],
+                    member_filter: None,
                 }])
             });
         mock.expect_get_schema().returning(|| {
@@ -1188,6 +2118,7 @@ mod tests {
                         is_editable: true,
                         is_hardcoded: false,
                         is_readonly: false,
+                        matching_rule: MatchingRule::CaseIgnoreMatch,
                     }],
                 },
                 extra_user_object_classes: Vec::new(),
@@ -1214,6 +2145,127 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_search_groups_filter_case_exact() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_groups()
+            .with(eq(Some(GroupRequestFilter::AttributeEquality(
+                AttributeName::from("attr"),
+                "TEST".to_string().into(),
+            ))))
+            .times(1)
+            .return_once(|_| Ok(vec![]));
+        mock.expect_get_schema().returning(|| {
+            Ok(Schema {
+                user_attributes: AttributeList {
+                    attributes: Vec::new(),
+                },
+                group_attributes: AttributeList {
+                    attributes: vec![AttributeSchema {
+                        name: "Attr".into(),
+                        attribute_type: AttributeType::String,
+                        is_list: false,
+                        is_visible: true,
+                        is_editable: true,
+                        is_hardcoded: false,
+                        is_readonly: false,
+                        matching_rule: MatchingRule::CaseExactMatch,
+                    }],
+                },
+                extra_user_object_classes: Vec::new(),
+                extra_group_object_classes: Vec::new(),
+            })
+        });
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_group_search_request(
+            LdapFilter::Equality("Attr".to_string(), "TEST".to_string()),
+            vec!["cn"],
+        );
+        assert_eq!(
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(vec![make_search_success()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_user_ssh_public_keys() {
+        const KEY: &str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAABAgMEBQYHCAkKCwwNDg8QERITFBUWFxgZGhscHR4f bob@laptop";
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users()
+            .with(
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::True,
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(false),
+            )
+            .times(1)
+            .return_once(|_, _| {
+                Ok(vec![UserAndGroups {
+                    user: User {
+                        user_id: UserId::new("bob"),
+                        attributes: vec![Attribute {
+                            name: "sshPublicKey".into(),
+                            value: vec![SshPublicKey::try_from(KEY).unwrap()].into(),
+                        }],
+                        ..Default::default()
+                    },
+                    groups: None,
+                }])
+            });
+        mock.expect_get_schema().returning(|| {
+            Ok(Schema {
+                user_attributes: AttributeList {
+                    attributes: vec![AttributeSchema {
+                        name: "sshPublicKey".into(),
+                        attribute_type: AttributeType::SshPublicKey,
+                        is_list: true,
+                        is_visible: true,
+                        is_editable: true,
+                        is_hardcoded: false,
+                        is_readonly: false,
+                        matching_rule: MatchingRule::OctetStringMatch,
+                    }],
+                },
+                group_attributes: AttributeList {
+                    attributes: Vec::new(),
+                },
+                extra_user_object_classes: Vec::new(),
+                extra_group_object_classes: Vec::new(),
+            })
+        });
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_user_search_request(
+            LdapFilter::Equality("objectClass".to_string(), "ldapPublicKey".to_string()),
+            vec!["objectClass", "sshPublicKey"],
+        );
+        assert_eq!(
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(vec![
+                LdapOp::SearchResultEntry(LdapSearchResultEntry {
+                    dn: "uid=bob,ou=people,dc=example,dc=com".to_string(),
+                    attributes: vec![
+                        LdapPartialAttribute {
+                            atype: "objectClass".to_string(),
+                            vals: vec![
+                                b"inetOrgPerson".to_vec(),
+                                b"posixAccount".to_vec(),
+                                b"mailAccount".to_vec(),
+                                b"person".to_vec(),
+                                b"ldapPublicKey".to_vec(),
+                            ]
+                        },
+                        LdapPartialAttribute {
+                            atype: "sshPublicKey".to_string(),
+                            vals: vec![KEY.as_bytes().to_vec()]
+                        },
+                    ],
+                }),
+                make_search_success(),
+            ])
+        );
+    }
+
     #[tokio::test]
     async fn test_search_group_as_scope() {
         let mut mock = MockTestBackendHandler::new();
@@ -1332,8 +2384,8 @@ mod tests {
         let mut mock = MockTestBackendHandler::new();
         mock.expect_list_users()
             .with(
-                eq(Some(UserRequestFilter::And(vec![UserRequestFilter::Or(
-                    vec![
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::Or(vec![
                         UserRequestFilter::Not(Box::new(UserRequestFilter::UserId(UserId::new(
                             "bob",
                         )))),
@@ -1344,16 +2396,10 @@ mod tests {
                         true.into(),
                         true.into(),
                         false.into(),
-                        UserRequestFilter::Or(vec![
-                            UserRequestFilter::AttributeEquality(
-                                AttributeName::from("first_name"),
-                                "FirstName".to_string().into(),
-                            ),
-                            UserRequestFilter::AttributeEquality(
-                                AttributeName::from("first_name"),
-                                "firstname".to_string().into(),
-                            ),
-                        ]),
+                        UserRequestFilter::AttributeEqualityIgnoreCase(
+                            AttributeName::from("first_name"),
+                            "FirstName".to_string(),
+                        ),
                         false.into(),
                         UserRequestFilter::UserIdSubString(SubStringFilter {
                             initial: Some("iNIt".to_owned()),
@@ -1368,8 +2414,9 @@ mod tests {
                                 final_: Some("finAl".to_owned()),
                             },
                         ),
-                    ],
-                )]))),
+                    ]),
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
                 eq(false),
             )
             .times(1)
@@ -1436,18 +2483,56 @@ mod tests {
             vec!["objectClass"],
         );
         ldap_handler.do_search_or_dse(&request).await.unwrap_err();
+        let request = make_user_search_request(
+            LdapFilter::Substring(
+                "jpegphoto".to_owned(),
+                LdapSubstringFilter {
+                    initial: Some("iNIt".to_owned()),
+                    any: vec!["1".to_owned(), "2aA".to_owned()],
+                    final_: Some("finAl".to_owned()),
+                },
+            ),
+            vec!["objectClass"],
+        );
+        ldap_handler.do_search_or_dse(&request).await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_search_custom_attribute_substring_filter() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users()
+            .with(
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::AttributeSubString(
+                        AttributeName::from("first_name"),
+                        SubStringFilter {
+                            initial: Some("jo".to_owned()),
+                            any: vec![],
+                            final_: None,
+                        },
+                    ),
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(false),
+            )
+            .times(1)
+            .return_once(|_, _| Ok(make_users(&["john"])));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
         let request = make_user_search_request(
             LdapFilter::Substring(
                 "givenname".to_owned(),
                 LdapSubstringFilter {
-                    initial: Some("iNIt".to_owned()),
-                    any: vec!["1".to_owned(), "2aA".to_owned()],
-                    final_: Some("finAl".to_owned()),
+                    initial: Some("jo".to_owned()),
+                    any: vec![],
+                    final_: None,
                 },
             ),
-            vec!["objectClass"],
+            vec!["1.1"],
+        );
+        assert_eq!(
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(make_user_entries(&["john"]))
         );
-        ldap_handler.do_search_or_dse(&request).await.unwrap_err();
     }
 
     #[tokio::test]
@@ -1455,7 +2540,10 @@ mod tests {
         let mut mock = MockTestBackendHandler::new();
         mock.expect_list_users()
             .with(
-                eq(Some(UserRequestFilter::MemberOf("group_1".into()))),
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::MemberOf("group_1".into()),
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
                 eq(false),
             )
             .times(2)
@@ -1485,7 +2573,13 @@ mod tests {
     async fn test_search_member_of_filter_error() {
         let mut mock = MockTestBackendHandler::new();
         mock.expect_list_users()
-            .with(eq(Some(UserRequestFilter::from(false))), eq(false))
+            .with(
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::from(false),
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(false),
+            )
             .times(1)
             .returning(|_, _| Ok(vec![]));
         let ldap_handler = setup_bound_admin_handler(mock).await;
@@ -1508,11 +2602,12 @@ mod tests {
         let mut mock = MockTestBackendHandler::new();
         mock.expect_list_users()
             .with(
-                eq(Some(UserRequestFilter::And(vec![UserRequestFilter::Or(
-                    vec![UserRequestFilter::Not(Box::new(
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::Or(vec![UserRequestFilter::Not(Box::new(
                         UserRequestFilter::Equality(UserColumn::DisplayName, "bob".to_string()),
-                    ))],
-                )]))),
+                    ))]),
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
                 eq(false),
             )
             .times(1)
@@ -1557,7 +2652,13 @@ mod tests {
     async fn test_search_filters_custom_object_class() {
         let mut mock = MockTestBackendHandler::new();
         mock.expect_list_users()
-            .with(eq(Some(UserRequestFilter::from(true))), eq(false))
+            .with(
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::from(true),
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(false),
+            )
             .times(1)
             .return_once(|_, _| {
                 Ok(vec![UserAndGroups {
@@ -1629,6 +2730,7 @@ mod tests {
                     users: vec![UserId::new("bob"), UserId::new("john")],
                     uuid: uuid!("04ac75e0-2900-3e21-926c-2f732c26b3fc"),
                     attributes: Vec::new(),
+                    member_filter: None,
                 }])
             });
         let ldap_handler = setup_bound_admin_handler(mock).await;
@@ -1713,6 +2815,7 @@ mod tests {
                     users: vec![UserId::new("bob"), UserId::new("john")],
                     uuid: uuid!("04ac75e0-2900-3e21-926c-2f732c26b3fc"),
                     attributes: Vec::new(),
+                    member_filter: None,
                 }])
             });
         let ldap_handler = setup_bound_admin_handler(mock).await;
@@ -1904,7 +3007,13 @@ mod tests {
     async fn test_search_filter_non_attribute() {
         let mut mock = MockTestBackendHandler::new();
         mock.expect_list_users()
-            .with(eq(Some(true.into())), eq(false))
+            .with(
+                eq(Some(UserRequestFilter::And(vec![
+                    true.into(),
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(false),
+            )
             .times(1)
             .return_once(|_, _| Ok(vec![]));
         let ldap_handler = setup_bound_admin_handler(mock).await;
@@ -1973,6 +3082,7 @@ mod tests {
                     name: "club_name".into(),
                     value: "Breakfast Club".to_string().into(),
                 }],
+                member_filter: None,
             }])
         });
         mock.expect_get_schema().returning(|| {
@@ -1986,6 +3096,7 @@ mod tests {
                         is_editable: true,
                         is_hardcoded: false,
                         is_readonly: false,
+                        matching_rule: MatchingRule::CaseIgnoreMatch,
                     }],
                 },
                 group_attributes: AttributeList {
@@ -1997,6 +3108,7 @@ mod tests {
                         is_editable: true,
                         is_hardcoded: false,
                         is_readonly: false,
+                        matching_rule: MatchingRule::CaseIgnoreMatch,
                     }],
                 },
                 extra_user_object_classes: vec![
@@ -2046,4 +3158,528 @@ mod tests {
             ]),
         );
     }
+
+    fn make_sort_control(attribute: &str, reverse: bool, criticality: bool) -> LdapControl {
+        let mut key = vec![0x04, attribute.len() as u8];
+        key.extend_from_slice(attribute.as_bytes());
+        if reverse {
+            key.extend([0x81, 0x01, 0xff]);
+        }
+        let mut value = vec![0x30, key.len() as u8 + 2, 0x30, key.len() as u8];
+        value.extend(key);
+        LdapControl::Unknown {
+            oid: SORT_REQUEST_OID.to_string(),
+            criticality,
+            value: Some(value),
+        }
+    }
+
+    fn make_vlv_control(before_count: u8, after_count: u8, target: &[u8]) -> LdapControl {
+        let mut fields = vec![0x02, 0x01, before_count, 0x02, 0x01, after_count];
+        fields.extend_from_slice(target);
+        let mut value = vec![0x30, fields.len() as u8];
+        value.extend(fields);
+        LdapControl::Unknown {
+            oid: VLV_REQUEST_OID.to_string(),
+            criticality: true,
+            value: Some(value),
+        }
+    }
+
+    fn make_users(names: &[&str]) -> Vec<UserAndGroups> {
+        names
+            .iter()
+            .map(|name| UserAndGroups {
+                user: User {
+                    user_id: UserId::new(name),
+                    ..Default::default()
+                },
+                groups: None,
+            })
+            .collect()
+    }
+
+    fn make_user_entries(names: &[&str]) -> Vec<LdapOp> {
+        names
+            .iter()
+            .map(|name| {
+                LdapOp::SearchResultEntry(LdapSearchResultEntry {
+                    dn: format!("uid={name},ou=people,dc=example,dc=com"),
+                    attributes: vec![],
+                })
+            })
+            .chain(std::iter::once(make_search_success()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_search_vlv_by_offset() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_count_users()
+            .with(eq(Some(UserRequestFilter::And(vec![
+                UserRequestFilter::ServiceAccount(false),
+            ]))))
+            .times(1)
+            .return_once(|_| Ok(10));
+        mock.expect_list_users_sorted()
+            .with(
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(false),
+                eq(UserSortOrder {
+                    column: UserColumn::DisplayName,
+                    descending: false,
+                }),
+                eq(3),
+                eq(Some(3)),
+            )
+            .times(1)
+            .return_once(|_, _, _, _, _| Ok(make_users(&["bob", "carol", "dave"])));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_user_search_request(LdapFilter::And(vec![]), vec!["1.1"]);
+        let controls = vec![
+            make_sort_control("cn", false, true),
+            // Target the 5th entry, with one entry before and after.
+            make_vlv_control(1, 1, &[0xa0, 0x06, 0x02, 0x01, 0x05, 0x02, 0x01, 0x00]),
+        ];
+        assert_eq!(
+            ldap_handler
+                .do_search_or_dse_with_controls(&request, &controls)
+                .await,
+            Ok((
+                make_user_entries(&["bob", "carol", "dave"]),
+                vec![
+                    make_sort_response(CONTROL_RESULT_SUCCESS, None),
+                    make_vlv_response(5, 10, CONTROL_RESULT_SUCCESS, None),
+                ]
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_vlv_greater_than_or_equal() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_count_users()
+            .with(eq(Some(UserRequestFilter::And(vec![
+                UserRequestFilter::ServiceAccount(false),
+            ]))))
+            .times(1)
+            .return_once(|_| Ok(10));
+        mock.expect_count_users()
+            .with(eq(Some(UserRequestFilter::And(vec![
+                UserRequestFilter::And(vec![UserRequestFilter::ServiceAccount(false)]),
+                UserRequestFilter::Not(Box::new(UserRequestFilter::LessOrEqual(
+                    UserColumn::Email,
+                    "m".to_string(),
+                ))),
+            ]))))
+            .times(1)
+            .return_once(|_| Ok(4));
+        mock.expect_list_users_sorted()
+            .with(
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(false),
+                eq(UserSortOrder {
+                    column: UserColumn::Email,
+                    descending: true,
+                }),
+                eq(4),
+                eq(Some(2)),
+            )
+            .times(1)
+            .return_once(|_, _, _, _, _| Ok(make_users(&["mallory", "john"])));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_user_search_request(LdapFilter::And(vec![]), vec!["1.1"]);
+        let controls = vec![
+            make_sort_control("mail", true, false),
+            make_vlv_control(0, 1, &[0x81, 0x01, b'm']),
+        ];
+        assert_eq!(
+            ldap_handler
+                .do_search_or_dse_with_controls(&request, &controls)
+                .await,
+            Ok((
+                make_user_entries(&["mallory", "john"]),
+                vec![
+                    make_sort_response(CONTROL_RESULT_SUCCESS, None),
+                    make_vlv_response(5, 10, CONTROL_RESULT_SUCCESS, None),
+                ]
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_vlv_without_sort() {
+        let ldap_handler = setup_bound_admin_handler(MockTestBackendHandler::new()).await;
+        let request = make_user_search_request(LdapFilter::And(vec![]), vec!["1.1"]);
+        let controls = vec![make_vlv_control(
+            0,
+            1,
+            &[0xa0, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x00],
+        )];
+        assert_eq!(
+            ldap_handler
+                .do_search_or_dse_with_controls(&request, &controls)
+                .await,
+            Ok((
+                vec![make_search_error(
+                    LdapResultCode::UnwillingToPerform,
+                    "The virtual list view control requires the server side sort control"
+                        .to_string()
+                )],
+                vec![make_vlv_response(
+                    0,
+                    0,
+                    CONTROL_RESULT_SORT_CONTROL_MISSING,
+                    None
+                )]
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_sort_unsupported_key() {
+        let ldap_handler = setup_bound_admin_handler(MockTestBackendHandler::new()).await;
+        let request = make_user_search_request(LdapFilter::And(vec![]), vec!["1.1"]);
+        assert_eq!(
+            ldap_handler
+                .do_search_or_dse_with_controls(&request, &[make_sort_control("sn", false, true)])
+                .await,
+            Err(LdapError {
+                code: LdapResultCode::UnavailableCriticalExtension,
+                message: "Unsupported sort key: sn".to_string(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_sort_unsupported_key_not_critical() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users_sorted()
+            .with(
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(false),
+                eq(UserSortOrder {
+                    column: UserColumn::UserId,
+                    descending: false,
+                }),
+                eq(0),
+                eq(None),
+            )
+            .times(1)
+            .return_once(|_, _, _, _, _| Ok(make_users(&["bob", "john"])));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_user_search_request(LdapFilter::And(vec![]), vec!["1.1"]);
+        assert_eq!(
+            ldap_handler
+                .do_search_or_dse_with_controls(&request, &[make_sort_control("sn", false, false)])
+                .await,
+            Ok((
+                make_user_entries(&["bob", "john"]),
+                vec![make_sort_response(
+                    CONTROL_RESULT_UNWILLING_TO_PERFORM,
+                    Some("sn")
+                )]
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_sort_size_limit() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users_sorted()
+            .with(always(), eq(false), always(), eq(0), eq(Some(3)))
+            .times(1)
+            .return_once(|_, _, _, _, _| Ok(make_users(&["bob", "carol", "dave"])));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let mut request = make_user_search_request(LdapFilter::And(vec![]), vec!["1.1"]);
+        request.sizelimit = 2;
+        let mut expected = make_user_entries(&["bob", "carol"]);
+        *expected.last_mut().unwrap() = make_search_error(
+            LdapResultCode::SizeLimitExceeded,
+            "Size limit exceeded".to_string(),
+        );
+        assert_eq!(
+            ldap_handler
+                .do_search_or_dse_with_controls(&request, &[make_sort_control("uid", false, true)])
+                .await,
+            Ok((
+                expected,
+                vec![make_sort_response(CONTROL_RESULT_SUCCESS, None)]
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_search_users() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users()
+            .with(
+                eq(Some(UserRequestFilter::And(vec![
+                    UserRequestFilter::ServiceAccount(false),
+                ]))),
+                eq(false),
+            )
+            .times(1)
+            .return_once(|_, _| Ok(make_users(&["bob", "john"])));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_user_search_request(LdapFilter::And(vec![]), vec!["1.1"]);
+        let mut sink = Vec::new();
+        ldap_handler
+            .stream_search_or_dse(&request, &[], &mut sink)
+            .await
+            .unwrap();
+        assert_eq!(
+            sink,
+            make_user_entries(&["bob", "john"])
+                .into_iter()
+                .map(|op| (op, Vec::new()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_search_response_controls() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_users_sorted()
+            .times(1)
+            .return_once(|_, _, _, _, _| Ok(make_users(&["bob"])));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_user_search_request(LdapFilter::And(vec![]), vec!["1.1"]);
+        let mut sink = Vec::new();
+        ldap_handler
+            .stream_search_or_dse(
+                &request,
+                &[make_sort_control("sn", false, false)],
+                &mut sink,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            sink,
+            vec![
+                (make_user_entries(&["bob"])[0].clone(), Vec::new()),
+                (
+                    make_search_success(),
+                    vec![make_sort_response(
+                        CONTROL_RESULT_UNWILLING_TO_PERFORM,
+                        Some("sn")
+                    )]
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_search_error() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_groups().times(1).return_once(|_| {
+            Err(lldap_domain_model::error::DomainError::InternalError(
+                "Error getting groups".to_string(),
+            ))
+        });
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_group_search_request(LdapFilter::And(vec![]), vec!["cn"]);
+        let mut sink = Vec::new();
+        ldap_handler
+            .stream_search_or_dse(&request, &[], &mut sink)
+            .await
+            .unwrap();
+        assert_eq!(
+            sink,
+            vec![(
+                make_search_error(
+                    LdapResultCode::Other,
+                    r#"Error while listing groups "ou=groups,dc=example,dc=com": Internal error: `Error getting groups`"#.to_string()
+                ),
+                Vec::new()
+            )]
+        );
+    }
+
+    fn make_sudo_role() -> SudoRole {
+        SudoRole {
+            id: SudoRoleId(1),
+            name: "Admins".to_owned(),
+            description: Some("Administrators".to_owned()),
+            users: vec!["%admins".to_owned()],
+            hosts: vec!["ALL".to_owned()],
+            commands: vec!["ALL".to_owned()],
+            run_as_users: Vec::new(),
+            run_as_groups: Vec::new(),
+            options: vec!["!authenticate".to_owned()],
+            order: 2,
+            creation_date: chrono::Utc.timestamp_opt(42, 42).unwrap().naive_utc(),
+            uuid: uuid!("04ac75e0-2900-3e21-926c-2f732c26b3fc"),
+        }
+    }
+
+    fn make_sudo_role_entry() -> LdapOp {
+        LdapOp::SearchResultEntry(LdapSearchResultEntry {
+            dn: "cn=Admins,ou=sudoers,dc=example,dc=com".to_string(),
+            attributes: vec![
+                LdapPartialAttribute {
+                    atype: "cn".to_string(),
+                    vals: vec![b"Admins".to_vec()],
+                },
+                LdapPartialAttribute {
+                    atype: "sudoCommand".to_string(),
+                    vals: vec![b"ALL".to_vec()],
+                },
+                LdapPartialAttribute {
+                    atype: "sudoOrder".to_string(),
+                    vals: vec![b"2".to_vec()],
+                },
+                LdapPartialAttribute {
+                    atype: "sudoUser".to_string(),
+                    vals: vec![b"%admins".to_vec()],
+                },
+            ],
+        })
+    }
+
+    /// The filter used by SSSD to fetch the rules of a user.
+    fn make_sssd_sudo_filter() -> LdapFilter {
+        LdapFilter::And(vec![
+            LdapFilter::Equality("objectClass".to_string(), "sudoRole".to_string()),
+            LdapFilter::Or(vec![
+                LdapFilter::Equality("sudoUser".to_string(), "ALL".to_string()),
+                LdapFilter::Equality("sudoUser".to_string(), "bob".to_string()),
+                LdapFilter::Equality("sudoUser".to_string(), "%admins".to_string()),
+            ]),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_search_sudo_roles() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_sudo_roles().times(1).return_once(|| {
+            Ok(vec![make_sudo_role(), {
+                let mut role = make_sudo_role();
+                role.name = "Backup".to_owned();
+                role.users = vec!["backup".to_owned()];
+                role
+            }])
+        });
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_search_request(
+            "ou=sudoers,dc=example,dc=com",
+            make_sssd_sudo_filter(),
+            vec!["cn", "sudoUser", "sudoCommand", "sudoOrder"],
+        );
+        assert_eq!(
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(vec![make_sudo_role_entry(), make_search_success()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_sudo_roles_from_base() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_sudo_roles()
+            .times(1)
+            .return_once(|| Ok(vec![make_sudo_role()]));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_search_request(
+            "dc=example,dc=com",
+            make_sssd_sudo_filter(),
+            vec!["cn", "sudoUser", "sudoCommand", "sudoOrder"],
+        );
+        assert_eq!(
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(vec![make_sudo_role_entry(), make_search_success()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_sudo_role_by_dn() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_list_sudo_roles()
+            .times(1)
+            .return_once(|| Ok(vec![make_sudo_role()]));
+        let ldap_handler = setup_bound_readonly_handler(mock).await;
+        let request = make_search_request(
+            "cn=admins,ou=sudoers,dc=example,dc=com",
+            LdapFilter::Present("objectClass".to_string()),
+            vec!["objectClass", "description", "createTimestamp"],
+        );
+        assert_eq!(
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(vec![
+                LdapOp::SearchResultEntry(LdapSearchResultEntry {
+                    dn: "cn=Admins,ou=sudoers,dc=example,dc=com".to_string(),
+                    attributes: vec![
+                        LdapPartialAttribute {
+                            atype: "createTimestamp".to_string(),
+                            vals: vec![b"1970-01-01T00:00:42.000000042+00:00".to_vec()],
+                        },
+                        LdapPartialAttribute {
+                            atype: "description".to_string(),
+                            vals: vec![b"Administrators".to_vec()],
+                        },
+                        LdapPartialAttribute {
+                            atype: "objectClass".to_string(),
+                            vals: vec![b"top".to_vec(), b"sudoRole".to_vec()],
+                        },
+                    ],
+                }),
+                make_search_success()
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_sudo_roles_regular_user() {
+        let ldap_handler =
+            setup_bound_handler_with_group(MockTestBackendHandler::new(), "regular").await;
+        let request = make_search_request(
+            "ou=sudoers,dc=example,dc=com",
+            make_sssd_sudo_filter(),
+            vec!["cn"],
+        );
+        assert_eq!(
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(vec![make_search_success()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_sudoers_ou() {
+        let ldap_handler = setup_bound_admin_handler(MockTestBackendHandler::new()).await;
+        let request = LdapSearchRequest {
+            scope: LdapSearchScope::Base,
+            ..make_search_request(
+                "ou=sudoers,dc=example,dc=com",
+                LdapFilter::Present("objectClass".to_string()),
+                vec!["objectClass"],
+            )
+        };
+        assert_eq!(
+            ldap_handler.do_search_or_dse(&request).await,
+            Ok(vec![
+                LdapOp::SearchResultEntry(LdapSearchResultEntry {
+                    dn: "ou=sudoers,dc=example,dc=com".to_string(),
+                    attributes: vec![LdapPartialAttribute {
+                        atype: "objectClass".to_string(),
+                        vals: vec![b"top".to_vec(), b"organizationalUnit".to_vec()],
+                    }],
+                }),
+                make_search_success()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_vlv_target_from_offset() {
+        assert_eq!(get_vlv_target_from_offset(5, 0, 10), 5);
+        assert_eq!(get_vlv_target_from_offset(50, 100, 10), 5);
+        assert_eq!(get_vlv_target_from_offset(100, 100, 10), 10);
+        assert_eq!(get_vlv_target_from_offset(20, 0, 10), 10);
+        assert_eq!(get_vlv_target_from_offset(0, 0, 10), 1);
+    }
 }
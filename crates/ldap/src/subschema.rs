@@ -0,0 +1,833 @@
+use crate::{
+    core::{
+        dn::Rdn,
+        error::{LdapError, LdapResult},
+        group::get_default_group_object_classes,
+        user::get_default_user_object_classes,
+        utils::parse_distinguished_name,
+    },
+    handler::make_modify_response,
+};
+use ldap3_proto::proto::{LdapModify, LdapModifyRequest, LdapModifyType, LdapOp, LdapResultCode};
+use lldap_access_control::AdminBackendHandler;
+use lldap_domain::{
+    public_schema::PublicSchema,
+    requests::CreateAttributeRequest,
+    types::{AttributeName, AttributeType, LdapObjectClass, MatchingRule},
+};
+use lldap_domain_model::error::DomainError;
+use lldap_validation::attributes::validate_attribute_name;
+use tracing::{debug, instrument};
+
+pub(crate) fn is_subschema_dn(dn: &str) -> bool {
+    parse_distinguished_name(dn).is_ok_and(|parts| parts == [Rdn::new("cn", "subschema")])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SchemaKind {
+    User,
+    Group,
+}
+
+#[derive(Debug)]
+enum SchemaChange {
+    AddAttribute(SchemaKind, CreateAttributeRequest),
+    DeleteAttribute(Option<SchemaKind>, AttributeName),
+    AddObjectClass(SchemaKind, LdapObjectClass),
+    DeleteObjectClass(Option<SchemaKind>, LdapObjectClass),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Dollar,
+    Quoted(String),
+    Word(String),
+}
+
+fn tokenize(description: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = description.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '$' => tokens.push(Token::Dollar),
+            '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err("unterminated quoted string".to_owned()),
+                        Some('\'') => break,
+                        Some('\\') => {
+                            let escape = chars.by_ref().take(2).collect::<String>();
+                            match escape.to_ascii_lowercase().as_str() {
+                                "27" => value.push('\''),
+                                "5c" => value.push('\\'),
+                                _ => return Err(format!(r#"invalid escape sequence "\{escape}""#)),
+                            }
+                        }
+                        Some(c) => value.push(c),
+                    }
+                }
+                tokens.push(Token::Quoted(value));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '$' | '\'') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn is_numeric_oid(oid: &str) -> bool {
+    let parts = oid.split('.').collect::<Vec<_>>();
+    parts.len() >= 2
+        && parts.iter().all(|part| {
+            !part.is_empty()
+                && part.bytes().all(|b| b.is_ascii_digit())
+                && (*part == "0" || !part.starts_with('0'))
+        })
+}
+
+// A "keystring" in RFC 4512: a letter followed by letters, digits and hyphens.
+fn is_keystring(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+// The keywords of RFC 4512 that are not followed by a value.
+const FLAG_KEYWORDS: &[&str] = &[
+    "OBSOLETE",
+    "SINGLE-VALUE",
+    "COLLECTIVE",
+    "NO-USER-MODIFICATION",
+    "ABSTRACT",
+    "STRUCTURAL",
+    "AUXILIARY",
+];
+
+/// An attribute type or object class description, as defined in RFC 4512 section 4.1.
+#[derive(Debug)]
+struct SchemaDescription {
+    fields: Vec<(String, Vec<String>)>,
+}
+
+impl SchemaDescription {
+    fn parse(description: &str) -> Result<Self, String> {
+        let mut tokens = tokenize(description)?.into_iter();
+        if tokens.next() != Some(Token::Open) {
+            return Err(r#"expected "(""#.to_owned());
+        }
+        match tokens.next() {
+            // The OID is only validated: the schema identifies attributes and classes by name.
+            Some(Token::Word(oid)) if is_numeric_oid(&oid) => (),
+            Some(Token::Word(oid)) => return Err(format!("invalid OID {oid}")),
+            _ => return Err("missing OID".to_owned()),
+        }
+        let mut fields: Vec<(String, Vec<String>)> = Vec::new();
+        loop {
+            let keyword = match tokens.next() {
+                Some(Token::Close) => break,
+                Some(Token::Word(keyword)) => keyword.to_ascii_uppercase(),
+                _ => return Err(r#"expected a keyword or ")""#.to_owned()),
+            };
+            if fields.iter().any(|(k, _)| k == &keyword) {
+                return Err(format!("duplicate {keyword}"));
+            }
+            let values = if FLAG_KEYWORDS.contains(&keyword.as_str()) {
+                Vec::new()
+            } else {
+                match tokens.next() {
+                    Some(Token::Quoted(value) | Token::Word(value)) => vec![value],
+                    Some(Token::Open) => {
+                        let mut values = Vec::new();
+                        loop {
+                            match tokens.next() {
+                                Some(Token::Close) => break,
+                                Some(Token::Dollar) => (),
+                                Some(Token::Quoted(value) | Token::Word(value)) => {
+                                    values.push(value)
+                                }
+                                _ => return Err(format!("invalid list of values for {keyword}")),
+                            }
+                        }
+                        values
+                    }
+                    _ => return Err(format!("missing value for {keyword}")),
+                }
+            };
+            fields.push((keyword, values));
+        }
+        if tokens.next().is_some() {
+            return Err(r#"unexpected content after ")""#.to_owned());
+        }
+        Ok(Self { fields })
+    }
+
+    fn get(&self, keyword: &str) -> Option<&[String]> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == keyword)
+            .map(|(_, values)| values.as_slice())
+    }
+
+    fn get_single(&self, keyword: &str) -> Result<Option<&str>, String> {
+        match self.get(keyword) {
+            None => Ok(None),
+            Some([value]) => Ok(Some(value.as_str())),
+            Some(_) => Err(format!("expected a single value for {keyword}")),
+        }
+    }
+
+    fn has_flag(&self, keyword: &str) -> bool {
+        self.get(keyword).is_some()
+    }
+
+    /// Rejects the keywords that are not in the list. Extensions are always allowed.
+    fn check_keywords(&self, supported: &[&str]) -> Result<(), String> {
+        match self
+            .fields
+            .iter()
+            .find(|(k, _)| !k.starts_with("X-") && !supported.contains(&k.as_str()))
+        {
+            Some((keyword, _)) => Err(format!("{keyword} is not supported")),
+            None => Ok(()),
+        }
+    }
+
+    fn get_name(&self) -> Result<&str, String> {
+        self.get_single("NAME")?
+            .ok_or_else(|| "missing NAME".to_owned())
+    }
+
+    fn get_bool_extension(&self, keyword: &str) -> Result<bool, String> {
+        match self.get_single(keyword)? {
+            None => Ok(false),
+            Some(value) if value.eq_ignore_ascii_case("TRUE") => Ok(true),
+            Some(value) if value.eq_ignore_ascii_case("FALSE") => Ok(false),
+            Some(value) => Err(format!("invalid value {value} for {keyword}")),
+        }
+    }
+
+    /// The "X-LLDAP-KIND" extension tells whether the definition applies to users or groups.
+    fn get_kind(&self) -> Result<Option<SchemaKind>, String> {
+        match self.get_single("X-LLDAP-KIND")? {
+            None => Ok(None),
+            Some(kind) if kind.eq_ignore_ascii_case("user") => Ok(Some(SchemaKind::User)),
+            Some(kind) if kind.eq_ignore_ascii_case("group") => Ok(Some(SchemaKind::Group)),
+            Some(kind) => Err(format!(
+                "invalid value {kind} for X-LLDAP-KIND, expected user or group"
+            )),
+        }
+    }
+}
+
+// The syntaxes published in the subschema entry.
+fn get_attribute_type_from_syntax(syntax: &str) -> Option<AttributeType> {
+    // The syntax can have a length bound, e.g. "1.3.6.1.4.1.1466.115.121.1.15{256}".
+    match syntax.split('{').next().unwrap_or_default() {
+        "1.3.6.1.4.1.1466.115.121.1.15" => Some(AttributeType::String),
+        "1.3.6.1.4.1.1466.115.121.1.24" => Some(AttributeType::DateTime),
+        "1.3.6.1.4.1.1466.115.121.1.27" => Some(AttributeType::Integer),
+        "1.3.6.1.4.1.1466.115.121.1.28" => Some(AttributeType::JpegPhoto),
+        "1.3.6.1.4.1.1466.115.121.1.40" => Some(AttributeType::SshPublicKey),
+        _ => None,
+    }
+}
+
+// The attribute types published in the subschema entry, used as superior types.
+fn get_attribute_type_from_superior(superior: &str) -> Option<AttributeType> {
+    match superior.to_ascii_lowercase().as_str() {
+        "string" => Some(AttributeType::String),
+        "datetime" => Some(AttributeType::DateTime),
+        "integer" => Some(AttributeType::Integer),
+        "jpegphoto" => Some(AttributeType::JpegPhoto),
+        "sshpublickey" => Some(AttributeType::SshPublicKey),
+        _ => None,
+    }
+}
+
+fn parse_attribute_type(
+    description: &SchemaDescription,
+) -> Result<(Option<SchemaKind>, CreateAttributeRequest), String> {
+    description.check_keywords(&[
+        "NAME",
+        "DESC",
+        "SUP",
+        "EQUALITY",
+        "ORDERING",
+        "SUBSTR",
+        "SYNTAX",
+        "SINGLE-VALUE",
+        "USAGE",
+    ])?;
+    if let Some(usage) = description.get_single("USAGE")? {
+        if !usage.eq_ignore_ascii_case("userApplications") {
+            return Err(format!("USAGE {usage} is not supported"));
+        }
+    }
+    let name = description.get_name()?;
+    validate_attribute_name(name).map_err(|invalid_chars| {
+        format!(
+            "invalid characters in NAME: {}",
+            String::from_iter(invalid_chars)
+        )
+    })?;
+    let syntax_type = description
+        .get_single("SYNTAX")?
+        .map(|syntax| {
+            get_attribute_type_from_syntax(syntax)
+                .ok_or_else(|| format!("SYNTAX {syntax} is not supported"))
+        })
+        .transpose()?;
+    let superior_type = description
+        .get_single("SUP")?
+        .map(|superior| {
+            get_attribute_type_from_superior(superior)
+                .ok_or_else(|| format!("SUP {superior} is not supported"))
+        })
+        .transpose()?;
+    let attribute_type = match (syntax_type, superior_type) {
+        (Some(syntax_type), Some(superior_type)) if syntax_type != superior_type => {
+            return Err("SYNTAX and SUP have different types".to_owned());
+        }
+        (Some(attribute_type), _) | (None, Some(attribute_type)) => attribute_type,
+        (None, None) => return Err("missing SYNTAX".to_owned()),
+    };
+    let matching_rule = description
+        .get_single("EQUALITY")?
+        .map(|equality| {
+            MatchingRule::from_ldap_name(equality)
+                .filter(|rule| rule.is_compatible_with(attribute_type))
+                .ok_or_else(|| {
+                    format!("EQUALITY {equality} is not supported for {attribute_type:?}")
+                })
+        })
+        .transpose()?;
+    let is_visible = description.get_bool_extension("X-LLDAP-VISIBLE")?;
+    let is_editable = description.get_bool_extension("X-LLDAP-EDITABLE")?;
+    if is_editable && !is_visible {
+        return Err("editable attributes must also be visible".to_owned());
+    }
+    Ok((
+        description.get_kind()?,
+        CreateAttributeRequest {
+            name: name.into(),
+            attribute_type,
+            is_list: !description.has_flag("SINGLE-VALUE"),
+            is_visible,
+            is_editable,
+            matching_rule,
+        },
+    ))
+}
+
+/// The kind of an object class, given explicitly or deduced from a builtin superior class.
+fn get_object_class_kind(description: &SchemaDescription) -> Result<Option<SchemaKind>, String> {
+    if let Some(kind) = description.get_kind()? {
+        return Ok(Some(kind));
+    }
+    Ok(description
+        .get("SUP")
+        .unwrap_or_default()
+        .iter()
+        .map(|superior| LdapObjectClass::from(superior.as_str()))
+        .find_map(|superior| {
+            if get_default_user_object_classes().contains(&superior) {
+                Some(SchemaKind::User)
+            } else if get_default_group_object_classes().contains(&superior) {
+                Some(SchemaKind::Group)
+            } else {
+                None
+            }
+        }))
+}
+
+fn parse_object_class(
+    description: &SchemaDescription,
+) -> Result<(Option<SchemaKind>, LdapObjectClass), String> {
+    // Any attribute can be set on any user or group, so "MAY" has no effect, but "MUST" cannot
+    // be enforced.
+    description.check_keywords(&[
+        "NAME",
+        "DESC",
+        "SUP",
+        "ABSTRACT",
+        "STRUCTURAL",
+        "AUXILIARY",
+        "MAY",
+    ])?;
+    let name = description.get_name()?;
+    if !is_keystring(name) {
+        return Err(format!("invalid NAME {name}"));
+    }
+    Ok((get_object_class_kind(description)?, name.into()))
+}
+
+fn parse_change_value(
+    atype: &str,
+    operation: &LdapModifyType,
+    value: &[u8],
+) -> LdapResult<SchemaChange> {
+    let make_error = |message: String| LdapError {
+        code: LdapResultCode::InvalidAttributeSyntax,
+        message: format!(
+            "Invalid {atype} value `{}`: {message}",
+            String::from_utf8_lossy(value)
+        ),
+    };
+    let description = std::str::from_utf8(value)
+        .map_err(|_| "invalid UTF-8".to_owned())
+        .and_then(SchemaDescription::parse)
+        .map_err(make_error)?;
+    let missing_kind = || make_error("missing X-LLDAP-KIND".to_owned());
+    let is_add = *operation == LdapModifyType::Add;
+    if atype.eq_ignore_ascii_case("attributeTypes") {
+        if is_add {
+            let (kind, request) = parse_attribute_type(&description).map_err(make_error)?;
+            Ok(SchemaChange::AddAttribute(
+                kind.ok_or_else(missing_kind)?,
+                request,
+            ))
+        } else {
+            Ok(SchemaChange::DeleteAttribute(
+                description.get_kind().map_err(make_error)?,
+                description.get_name().map_err(make_error)?.into(),
+            ))
+        }
+    } else if is_add {
+        let (kind, object_class) = parse_object_class(&description).map_err(make_error)?;
+        Ok(SchemaChange::AddObjectClass(
+            kind.ok_or_else(missing_kind)?,
+            object_class,
+        ))
+    } else {
+        Ok(SchemaChange::DeleteObjectClass(
+            get_object_class_kind(&description).map_err(make_error)?,
+            description.get_name().map_err(make_error)?.into(),
+        ))
+    }
+}
+
+fn parse_change(change: &LdapModify) -> LdapResult<Vec<SchemaChange>> {
+    let atype = change.modification.atype.as_str();
+    if !atype.eq_ignore_ascii_case("attributeTypes") && !atype.eq_ignore_ascii_case("objectClasses")
+    {
+        return Err(LdapError {
+            code: LdapResultCode::UnwillingToPerform,
+            message: format!("Unsupported modification of `{atype}` in the subschema"),
+        });
+    }
+    match change.operation {
+        LdapModifyType::Add => (),
+        LdapModifyType::Delete if !change.modification.vals.is_empty() => (),
+        _ => {
+            return Err(LdapError {
+                code: LdapResultCode::UnwillingToPerform,
+                message: format!(
+                    "Unsupported operation `{:?}` for `{atype}`: only adding or deleting values is supported",
+                    change.operation
+                ),
+            });
+        }
+    }
+    change
+        .modification
+        .vals
+        .iter()
+        .map(|value| parse_change_value(atype, &change.operation, value))
+        .collect()
+}
+
+fn resolve_kind(
+    kind: Option<SchemaKind>,
+    is_user: bool,
+    is_group: bool,
+    description: &str,
+) -> LdapResult<SchemaKind> {
+    match (kind, is_user, is_group) {
+        (Some(kind), _, _) => Ok(kind),
+        (None, true, false) => Ok(SchemaKind::User),
+        (None, false, true) => Ok(SchemaKind::Group),
+        (None, true, true) => Err(LdapError {
+            code: LdapResultCode::UnwillingToPerform,
+            message: format!(
+                "{description} is defined for both users and groups, specify X-LLDAP-KIND"
+            ),
+        }),
+        (None, false, false) => Err(LdapError {
+            code: LdapResultCode::NoSuchAttribute,
+            message: format!("{description} is not defined in the schema"),
+        }),
+    }
+}
+
+fn make_backend_error(action: &str, e: DomainError) -> LdapError {
+    LdapError {
+        code: LdapResultCode::OperationsError,
+        message: format!("Error while {action}: {e:#?}"),
+    }
+}
+
+async fn apply_change(
+    backend_handler: &impl AdminBackendHandler,
+    change: SchemaChange,
+) -> LdapResult<()> {
+    let schema = PublicSchema::from(
+        backend_handler
+            .get_schema()
+            .await
+            .map_err(|e| make_backend_error("reading the schema", e))?,
+    );
+    let schema = schema.get_schema();
+    let get_attributes = |kind: SchemaKind| match kind {
+        SchemaKind::User => &schema.user_attributes,
+        SchemaKind::Group => &schema.group_attributes,
+    };
+    let get_object_classes = |kind: SchemaKind| match kind {
+        SchemaKind::User => &schema.extra_user_object_classes,
+        SchemaKind::Group => &schema.extra_group_object_classes,
+    };
+    let is_default_object_class = |object_class: &LdapObjectClass| {
+        get_default_user_object_classes().contains(object_class)
+            || get_default_group_object_classes().contains(object_class)
+    };
+    match change {
+        SchemaChange::AddAttribute(kind, request) => {
+            if get_attributes(kind)
+                .get_attribute_schema(&request.name)
+                .is_some()
+            {
+                return Err(LdapError {
+                    code: LdapResultCode::AttributeOrValueExists,
+                    message: format!("Attribute {} is already defined", request.name),
+                });
+            }
+            match kind {
+                SchemaKind::User => backend_handler.add_user_attribute(request).await,
+                SchemaKind::Group => backend_handler.add_group_attribute(request).await,
+            }
+            .map_err(|e| make_backend_error("adding the attribute", e))
+        }
+        SchemaChange::DeleteAttribute(kind, name) => {
+            let description = format!("Attribute {name}");
+            let kind = resolve_kind(
+                kind,
+                schema.user_attributes.get_attribute_schema(&name).is_some(),
+                schema
+                    .group_attributes
+                    .get_attribute_schema(&name)
+                    .is_some(),
+                &description,
+            )?;
+            match get_attributes(kind).get_attribute_schema(&name) {
+                None => {
+                    return Err(LdapError {
+                        code: LdapResultCode::NoSuchAttribute,
+                        message: format!("{description} is not defined in the schema"),
+                    });
+                }
+                Some(attribute) if attribute.is_hardcoded => {
+                    return Err(LdapError {
+                        code: LdapResultCode::UnwillingToPerform,
+                        message: format!("{description} cannot be deleted"),
+                    });
+                }
+                Some(_) => (),
+            }
+            match kind {
+                SchemaKind::User => backend_handler.delete_user_attribute(&name).await,
+                SchemaKind::Group => backend_handler.delete_group_attribute(&name).await,
+            }
+            .map_err(|e| make_backend_error("deleting the attribute", e))
+        }
+        SchemaChange::AddObjectClass(kind, object_class) => {
+            if is_default_object_class(&object_class)
+                || get_object_classes(kind).contains(&object_class)
+            {
+                return Err(LdapError {
+                    code: LdapResultCode::AttributeOrValueExists,
+                    message: format!("Object class {object_class} is already defined"),
+                });
+            }
+            match kind {
+                SchemaKind::User => backend_handler.add_user_object_class(&object_class).await,
+                SchemaKind::Group => backend_handler.add_group_object_class(&object_class).await,
+            }
+            .map_err(|e| make_backend_error("adding the object class", e))
+        }
+        SchemaChange::DeleteObjectClass(kind, object_class) => {
+            let description = format!("Object class {object_class}");
+            if is_default_object_class(&object_class) {
+                return Err(LdapError {
+                    code: LdapResultCode::UnwillingToPerform,
+                    message: format!("{description} cannot be deleted"),
+                });
+            }
+            let kind = resolve_kind(
+                kind,
+                schema.extra_user_object_classes.contains(&object_class),
+                schema.extra_group_object_classes.contains(&object_class),
+                &description,
+            )?;
+            if !get_object_classes(kind).contains(&object_class) {
+                return Err(LdapError {
+                    code: LdapResultCode::NoSuchAttribute,
+                    message: format!("{description} is not defined in the schema"),
+                });
+            }
+            match kind {
+                SchemaKind::User => {
+                    backend_handler
+                        .delete_user_object_class(&object_class)
+                        .await
+                }
+                SchemaKind::Group => {
+                    backend_handler
+                        .delete_group_object_class(&object_class)
+                        .await
+                }
+            }
+            .map_err(|e| make_backend_error("deleting the object class", e))
+        }
+    }
+}
+
+/// Handles a modification of the subschema entry: values added to or deleted from its
+/// `attributeTypes` and `objectClasses` define or remove custom attributes and object classes.
+#[instrument(skip_all, level = "debug")]
+pub(crate) async fn modify_subschema(
+    backend_handler: &impl AdminBackendHandler,
+    request: &LdapModifyRequest,
+) -> LdapResult<Vec<LdapOp>> {
+    // Validate all the changes before applying any of them.
+    let changes = request
+        .changes
+        .iter()
+        .map(parse_change)
+        .collect::<LdapResult<Vec<_>>>()?;
+    for change in changes.into_iter().flatten() {
+        debug!(?change);
+        apply_change(backend_handler, change).await?;
+    }
+    Ok(vec![make_modify_response(
+        LdapResultCode::Success,
+        String::new(),
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::tests::{setup_bound_admin_handler, setup_bound_readonly_handler};
+    use ldap3_proto::proto::{LdapPartialAttribute, LdapResult as LdapResultOp};
+    use lldap_test_utils::MockTestBackendHandler;
+    use mockall::predicate::eq;
+    use pretty_assertions::assert_eq;
+
+    fn make_subschema_change(
+        operation: LdapModifyType,
+        atype: &str,
+        values: &[&str],
+    ) -> LdapModify {
+        LdapModify {
+            operation,
+            modification: LdapPartialAttribute {
+                atype: atype.to_owned(),
+                vals: values.iter().map(|v| v.as_bytes().to_vec()).collect(),
+            },
+        }
+    }
+
+    fn make_subschema_request(changes: Vec<LdapModify>) -> LdapModifyRequest {
+        LdapModifyRequest {
+            dn: "cn=Subschema".to_owned(),
+            changes,
+        }
+    }
+
+    fn get_result_code(response: Vec<LdapOp>) -> LdapResultCode {
+        match response.as_slice() {
+            [LdapOp::ModifyResponse(LdapResultOp { code, .. })] => code.clone(),
+            _ => panic!("Unexpected response: {response:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_user_attribute() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_add_user_attribute()
+            .with(eq(CreateAttributeRequest {
+                name: "employeeNumber".into(),
+                attribute_type: AttributeType::String,
+                is_list: false,
+                is_visible: true,
+                is_editable: false,
+                matching_rule: Some(MatchingRule::CaseExactMatch),
+            }))
+            .times(1)
+            .return_once(|_| Ok(()));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_subschema_request(vec![make_subschema_change(
+            LdapModifyType::Add,
+            "attributeTypes",
+            &[
+                "( 1.3.6.1.4.1.99999.1 NAME 'employeeNumber' DESC 'The employee\\27s number' \
+               SYNTAX 1.3.6.1.4.1.1466.115.121.1.15{64} EQUALITY caseExactMatch SINGLE-VALUE \
+               X-LLDAP-KIND 'user' X-LLDAP-VISIBLE 'TRUE' )",
+            ],
+        )]);
+        assert_eq!(
+            ldap_handler.do_modify_request(&request).await,
+            vec![make_modify_response(LdapResultCode::Success, String::new())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_and_delete_object_classes() {
+        let mut mock = MockTestBackendHandler::new();
+        mock.expect_add_group_object_class()
+            .with(eq(LdapObjectClass::from("myGroupClass")))
+            .times(1)
+            .return_once(|_| Ok(()));
+        mock.expect_delete_user_object_class()
+            .with(eq(LdapObjectClass::from("customUserClass")))
+            .times(1)
+            .return_once(|_| Ok(()));
+        let ldap_handler = setup_bound_admin_handler(mock).await;
+        let request = make_subschema_request(vec![
+            make_subschema_change(
+                LdapModifyType::Add,
+                "objectClasses",
+                &[
+                    "( 1.2.3 NAME 'myGroupClass' SUP groupOfNames AUXILIARY MAY ( description $ o ) )",
+                ],
+            ),
+            make_subschema_change(
+                LdapModifyType::Delete,
+                "objectclasses",
+                &["( 3.2 NAME 'customUserClass' )"],
+            ),
+        ]);
+        assert_eq!(
+            get_result_code(ldap_handler.do_modify_request(&request).await),
+            LdapResultCode::Success
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_hardcoded_attribute() {
+        let ldap_handler = setup_bound_admin_handler(MockTestBackendHandler::new()).await;
+        let request = make_subschema_request(vec![make_subschema_change(
+            LdapModifyType::Delete,
+            "attributeTypes",
+            &[
+                "( 2.7 NAME 'first_name' DESC 'LLDAP: builtin attribute' SUP String EQUALITY caseIgnoreMatch )",
+            ],
+        )]);
+        assert_eq!(
+            ldap_handler.do_modify_request(&request).await,
+            vec![make_modify_response(
+                LdapResultCode::UnwillingToPerform,
+                "Attribute first_name cannot be deleted".to_owned()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_descriptions() {
+        let ldap_handler = setup_bound_admin_handler(MockTestBackendHandler::new()).await;
+        for (atype, value) in [
+            (
+                "attributeTypes",
+                "( 1.02 NAME 'attr' SUP String X-LLDAP-KIND 'user' )",
+            ),
+            ("attributeTypes", "( 1.2 NAME 'attr' SUP String )"),
+            (
+                "attributeTypes",
+                "( 1.2 NAME 'attr' SYNTAX 1.3.6.1.4.1.1466.115.121.1.7 X-LLDAP-KIND 'user' )",
+            ),
+            (
+                "attributeTypes",
+                "( 1.2 NAME 'attr' SUP Integer EQUALITY caseExactMatch X-LLDAP-KIND 'user' )",
+            ),
+            (
+                "attributeTypes",
+                "( 1.2 NAME 'attr_1' SUP String X-LLDAP-KIND 'user' )",
+            ),
+            (
+                "attributeTypes",
+                "( 1.2 NAME 'attr' SUP String X-LLDAP-KIND 'user' X-LLDAP-EDITABLE 'TRUE' )",
+            ),
+            (
+                "attributeTypes",
+                "( 1.2 NAME 'attr' SUP String X-LLDAP-KIND 'user'",
+            ),
+            (
+                "objectClasses",
+                "( 1.2 NAME 'myClass' MUST cn X-LLDAP-KIND 'user' )",
+            ),
+            (
+                "objectClasses",
+                "( 1.2 NAME ( 'myClass' 'otherClass' ) X-LLDAP-KIND 'user' )",
+            ),
+        ] {
+            let request = make_subschema_request(vec![make_subschema_change(
+                LdapModifyType::Add,
+                atype,
+                &[value],
+            )]);
+            assert_eq!(
+                get_result_code(ldap_handler.do_modify_request(&request).await),
+                LdapResultCode::InvalidAttributeSyntax,
+                "{value}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_modifications() {
+        let ldap_handler = setup_bound_admin_handler(MockTestBackendHandler::new()).await;
+        for change in [
+            make_subschema_change(
+                LdapModifyType::Replace,
+                "attributeTypes",
+                &["( 1.2 NAME 'attr' SUP String X-LLDAP-KIND 'user' )"],
+            ),
+            make_subschema_change(LdapModifyType::Delete, "objectClasses", &[]),
+            make_subschema_change(LdapModifyType::Add, "ldapSyntaxes", &["( 1.2.3 )"]),
+        ] {
+            let request = make_subschema_request(vec![change]);
+            assert_eq!(
+                get_result_code(ldap_handler.do_modify_request(&request).await),
+                LdapResultCode::UnwillingToPerform
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_modify_subschema_requires_admin() {
+        let ldap_handler = setup_bound_readonly_handler(MockTestBackendHandler::new()).await;
+        let request = make_subschema_request(vec![make_subschema_change(
+            LdapModifyType::Add,
+            "objectClasses",
+            &["( 1.2.3 NAME 'myClass' X-LLDAP-KIND 'user' )"],
+        )]);
+        assert_eq!(
+            get_result_code(ldap_handler.do_modify_request(&request).await),
+            LdapResultCode::InsufficentAccessRights
+        );
+    }
+}
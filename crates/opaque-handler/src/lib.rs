@@ -15,10 +15,11 @@ pub trait OpaqueHandler: Send + Sync {
         &self,
         request: registration::ClientRegistrationStartRequest,
     ) -> Result<registration::ServerRegistrationStartResponse>;
+    /// Sets the new password, and returns the user it belongs to.
     async fn registration_finish(
         &self,
         request: registration::ClientRegistrationFinishRequest,
-    ) -> Result<()>;
+    ) -> Result<UserId>;
 }
 
 #[cfg(test)]
@@ -41,6 +42,6 @@ mockall::mock! {
         async fn registration_finish(
             &self,
             request: registration::ClientRegistrationFinishRequest
-        ) -> Result<()>;
+        ) -> Result<UserId>;
     }
 }
@@ -0,0 +1,64 @@
+use ipnet::IpNet;
+use lldap_domain_model::error::{DomainError, Result};
+use std::net::IpAddr;
+
+/// Accepts a network ("192.168.1.0/24") or a single address.
+fn parse_source(source: &str) -> Result<IpNet> {
+    source
+        .parse::<IpNet>()
+        .or_else(|_| source.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| DomainError::InternalError(format!("Invalid network: {source:?}")))
+}
+
+/// Validates the networks entered by the user, and stores them in their canonical form.
+pub(crate) fn normalize_allowed_sources(allowed_sources: &[String]) -> Result<Vec<String>> {
+    allowed_sources
+        .iter()
+        .map(|source| parse_source(source.trim()).map(|network| network.to_string()))
+        .collect()
+}
+
+/// Whether a client can bind from its address. No restriction means any address.
+pub(crate) fn is_source_allowed(
+    allowed_sources: &[String],
+    client_address: Option<IpAddr>,
+) -> bool {
+    if allowed_sources.is_empty() {
+        return true;
+    }
+    // IPv4 clients show up as IPv6 addresses on a dual-stack socket.
+    let Some(address) = client_address.map(|address| address.to_canonical()) else {
+        return false;
+    };
+    allowed_sources
+        .iter()
+        .filter_map(|source| parse_source(source).ok())
+        .any(|network| network.contains(&address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_allowed_sources() {
+        assert_eq!(
+            normalize_allowed_sources(&[" 192.168.1.0/24".to_owned(), "10.0.0.1".to_owned()])
+                .unwrap(),
+            vec!["192.168.1.0/24".to_owned(), "10.0.0.1/32".to_owned()]
+        );
+        assert!(normalize_allowed_sources(&["not a network".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn test_is_source_allowed() {
+        let sources = vec!["192.168.1.0/24".to_owned(), "fd00::1".to_owned()];
+        let address = |a: &str| Some(a.parse().unwrap());
+        assert!(is_source_allowed(&[], None));
+        assert!(is_source_allowed(&sources, address("192.168.1.12")));
+        assert!(is_source_allowed(&sources, address("::ffff:192.168.1.12")));
+        assert!(is_source_allowed(&sources, address("fd00::1")));
+        assert!(!is_source_allowed(&sources, address("192.168.2.12")));
+        assert!(!is_source_allowed(&sources, None));
+    }
+}
@@ -0,0 +1,113 @@
+use lldap_domain::types::UserId;
+use orion::auth::{SecretKey, Tag};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Beyond this many entries, new successful binds are not cached until older ones expire.
+const MAX_CACHED_BINDS: usize = 10_000;
+
+struct CachedBind {
+    tag: Tag,
+    expiry: Instant,
+}
+
+/// A memory-only cache of the credentials that were recently verified with a simple bind, to
+/// avoid running the (expensive) OPAQUE exchange for every bind of applications that bind on each
+/// request.
+///
+/// The entries only contain a keyed hash of the user ID, the password file and the password: the
+/// key is random and never leaves the process, and an entry stops matching as soon as the password
+/// file changes, even if it was changed by another instance.
+pub(crate) struct BindCache {
+    key: SecretKey,
+    ttl: Duration,
+    entries: Mutex<HashMap<UserId, CachedBind>>,
+}
+
+impl BindCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            key: SecretKey::default(),
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn compute_tag(&self, user_id: &UserId, password_file: &[u8], password: &str) -> Option<Tag> {
+        let mut data = Vec::with_capacity(16 + user_id.as_str().len() + password_file.len());
+        for part in [user_id.as_str().as_bytes(), password_file] {
+            data.extend_from_slice(&(part.len() as u64).to_be_bytes());
+            data.extend_from_slice(part);
+        }
+        data.extend_from_slice(password.as_bytes());
+        orion::auth::authenticate(&self.key, &data).ok()
+    }
+
+    /// Whether the credentials were successfully verified less than `ttl` ago.
+    pub(crate) fn contains(&self, user_id: &UserId, password_file: &[u8], password: &str) -> bool {
+        let Some(tag) = self.compute_tag(user_id, password_file, password) else {
+            return false;
+        };
+        let entries = self.entries.lock().unwrap();
+        // The comparison of tags is constant-time.
+        entries
+            .get(user_id)
+            .is_some_and(|entry| entry.expiry > Instant::now() && entry.tag == tag)
+    }
+
+    pub(crate) fn insert(&self, user_id: &UserId, password_file: &[u8], password: &str) {
+        let Some(tag) = self.compute_tag(user_id, password_file, password) else {
+            return;
+        };
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= MAX_CACHED_BINDS && !entries.contains_key(user_id) {
+            entries.retain(|_, entry| entry.expiry > now);
+            if entries.len() >= MAX_CACHED_BINDS {
+                return;
+            }
+        }
+        entries.insert(
+            user_id.clone(),
+            CachedBind {
+                tag,
+                expiry: now + self.ttl,
+            },
+        );
+    }
+
+    /// Forgets the credentials of the user, e.g. when their password changes.
+    pub(crate) fn invalidate(&self, user_id: &UserId) {
+        self.entries.lock().unwrap().remove(user_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_cache() {
+        let cache = BindCache::new(Duration::from_secs(60));
+        let bob = UserId::new("bob");
+        assert!(!cache.contains(&bob, b"file", "pass"));
+        cache.insert(&bob, b"file", "pass");
+        assert!(cache.contains(&bob, b"file", "pass"));
+        assert!(!cache.contains(&bob, b"file", "wrong"));
+        assert!(!cache.contains(&bob, b"other file", "pass"));
+        assert!(!cache.contains(&UserId::new("john"), b"file", "pass"));
+        cache.invalidate(&bob);
+        assert!(!cache.contains(&bob, b"file", "pass"));
+    }
+
+    #[test]
+    fn test_bind_cache_expiry() {
+        let cache = BindCache::new(Duration::ZERO);
+        let bob = UserId::new("bob");
+        cache.insert(&bob, b"file", "pass");
+        assert!(!cache.contains(&bob, b"file", "pass"));
+    }
+}
@@ -0,0 +1,80 @@
+//! Verification of the password hashes imported from other directories.
+use base64::Engine;
+use lldap_domain::types::{LegacyPasswordHash, LegacyPasswordScheme};
+use sha2::Digest;
+
+/// Checks a salted hash in the format used by OpenLDAP: base64(digest(password + salt) + salt).
+fn salted_digest_matches<D: Digest>(hash: &str, password: &str) -> bool {
+    let Ok(decoded) = base64::engine::general_purpose::STANDARD.decode(hash) else {
+        return false;
+    };
+    let digest_len = <D as Digest>::output_size();
+    if decoded.len() <= digest_len {
+        return false;
+    }
+    let (expected_digest, salt) = decoded.split_at(digest_len);
+    let digest = D::new()
+        .chain_update(password.as_bytes())
+        .chain_update(salt)
+        .finalize();
+    orion::util::secure_cmp(&digest, expected_digest).is_ok()
+}
+
+/// Checks the password against the imported hash. This is slow for most schemes, so it should
+/// run on the password hashing pool.
+pub(crate) fn legacy_password_matches(hash: &LegacyPasswordHash, password: &str) -> bool {
+    match hash.scheme() {
+        LegacyPasswordScheme::Ssha => salted_digest_matches::<sha1::Sha1>(hash.hash(), password),
+        LegacyPasswordScheme::Ssha512 => {
+            salted_digest_matches::<sha2::Sha512>(hash.hash(), password)
+        }
+        LegacyPasswordScheme::Sha512Crypt => sha_crypt::sha512_check(password, hash.hash()).is_ok(),
+        LegacyPasswordScheme::Bcrypt => bcrypt::verify(password, hash.hash()).unwrap_or(false),
+        LegacyPasswordScheme::Argon2 => {
+            argon2::verify_encoded(hash.hash(), password.as_bytes()).unwrap_or(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(hash: &str, password: &str) -> bool {
+        legacy_password_matches(&LegacyPasswordHash::try_from(hash).unwrap(), password)
+    }
+
+    #[test]
+    fn test_salted_sha() {
+        const SSHA: &str = "{SSHA}UC5tunSkhtN5xi9e7qcCsXKJNjBzYWx0";
+        assert!(matches(SSHA, "bob00"));
+        assert!(!matches(SSHA, "bob01"));
+        const SSHA512: &str = "{SSHA512}GxxVyJ7xzxIij1uGFSuSkJbHRhAXS+uzITi84PcCKg0met5NN2fmhFklUT1qXAZsoL1k4K8gBUP4AM++EZXTPXNhbHQ=";
+        assert!(matches(SSHA512, "bob00"));
+        assert!(!matches(SSHA512, "bob01"));
+    }
+
+    #[test]
+    fn test_sha512_crypt() {
+        const HASH: &str = "{CRYPT}$6$saltsalt$CPrFaapMIWoMb1g6gNdODZtUZQeJ.gUN3QwwmThZvg8AKa8faGpx6LRbtKkDmJQHJ.POJLDTZchU21lka/1O6/";
+        assert!(matches(HASH, "bob00"));
+        assert!(!matches(HASH, "bob01"));
+    }
+
+    #[test]
+    fn test_bcrypt() {
+        let hash = bcrypt::hash("bob00", 4).unwrap();
+        assert!(matches(&hash, "bob00"));
+        assert!(!matches(&hash, "bob01"));
+    }
+
+    #[test]
+    fn test_argon2() {
+        let hash = format!(
+            "{{ARGON2}}{}",
+            argon2::hash_encoded(b"bob00", b"saltsalt", &argon2::Config::default()).unwrap()
+        );
+        assert!(matches(&hash, "bob00"));
+        assert!(!matches(&hash, "bob01"));
+    }
+}
@@ -1,11 +1,20 @@
+pub(crate) mod allowed_sources;
+pub(crate) mod bind_cache;
+pub(crate) mod legacy_password;
 pub(crate) mod logging;
+pub(crate) mod sql_app_password_backend_handler;
 pub(crate) mod sql_backend_handler;
 pub(crate) mod sql_group_backend_handler;
+pub(crate) mod sql_mfa_backend_handler;
 pub(crate) mod sql_opaque_handler;
 pub(crate) mod sql_schema_backend_handler;
+pub(crate) mod sql_sudo_backend_handler;
 pub(crate) mod sql_user_backend_handler;
+pub(crate) mod sql_webauthn_backend_handler;
+pub(crate) mod totp;
 
 pub use sql_backend_handler::SqlBackendHandler;
 pub use sql_opaque_handler::register_password;
+pub use sql_user_backend_handler::active_user_condition;
 pub mod sql_migrations;
 pub mod sql_tables;
@@ -0,0 +1,387 @@
+use crate::{
+    allowed_sources::{is_source_allowed, normalize_allowed_sources},
+    sql_backend_handler::SqlBackendHandler,
+};
+use async_trait::async_trait;
+use itertools::Itertools;
+use lldap_domain::{
+    requests::CreateAppPasswordRequest,
+    types::{AppPassword, AppPasswordId, UserId},
+};
+use lldap_domain_handlers::handler::{
+    AppPasswordBackendHandler, BindRequest, BindSource, UserBackendHandler,
+};
+use lldap_domain_model::{
+    error::{DomainError, Result},
+    model::{self, AppPasswordsColumn},
+};
+use rand::seq::SliceRandom;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter, QueryOrder,
+    sea_query::Expr,
+};
+use tracing::{debug, info, instrument};
+
+const APP_PASSWORD_GROUP_COUNT: usize = 4;
+const APP_PASSWORD_GROUP_LENGTH: usize = 5;
+/// Without the characters that are easily confused, like 0 and o.
+const APP_PASSWORD_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+fn generate_app_password(rng: &mut impl rand::Rng) -> String {
+    (0..APP_PASSWORD_GROUP_COUNT)
+        .map(|_| {
+            (0..APP_PASSWORD_GROUP_LENGTH)
+                .map(|_| *APP_PASSWORD_ALPHABET.choose(rng).unwrap() as char)
+                .collect::<String>()
+        })
+        .join("-")
+}
+
+impl SqlBackendHandler {
+    /// Checks the password of an LDAP bind against the app passwords of the user, and records
+    /// the use of the matching one if its restrictions allow it.
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?request.name.as_str()))]
+    pub(crate) async fn bind_with_app_password(&self, request: &BindRequest) -> Result<bool> {
+        let (BindSource::Ldap(client_address) | BindSource::HttpAppPassword(client_address)) =
+            request.source
+        else {
+            return Ok(false);
+        };
+        let Some(app_password) = model::AppPasswords::find()
+            .filter(AppPasswordsColumn::UserId.eq(&request.name))
+            .filter(
+                AppPasswordsColumn::PasswordHash.eq(self.keyed_hash(request.password.as_bytes())?),
+            )
+            .one(&self.sql_pool)
+            .await?
+            .map(AppPassword::from)
+        else {
+            return Ok(false);
+        };
+        if !is_source_allowed(&app_password.allowed_sources, client_address) {
+            info!(
+                r#"App password "{}" of "{}" used from a source that is not allowed: {:?}"#,
+                &app_password.name, &request.name, client_address
+            );
+            return Ok(false);
+        }
+        if let Some(group_id) = app_password.required_group {
+            // Same membership as reported everywhere else, including the dynamic groups.
+            let is_member = self
+                .get_user_groups(&request.name)
+                .await?
+                .iter()
+                .any(|group| group.group_id == group_id);
+            if !is_member {
+                info!(
+                    r#"App password "{}" of "{}" used outside of its required group"#,
+                    &app_password.name, &request.name
+                );
+                return Ok(false);
+            }
+        }
+        model::AppPasswords::update_many()
+            .col_expr(
+                AppPasswordsColumn::LastUsedDate,
+                Expr::value(chrono::Utc::now().naive_utc()),
+            )
+            .filter(AppPasswordsColumn::AppPasswordId.eq(app_password.id))
+            .exec(&self.sql_pool)
+            .await?;
+        debug!(
+            r#"User "{}" bound with the app password "{}""#,
+            &request.name, &app_password.name
+        );
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl AppPasswordBackendHandler for SqlBackendHandler {
+    #[instrument(skip_all, level = "debug", ret, err, fields(user_id = ?user_id.as_str()))]
+    async fn list_app_passwords(&self, user_id: &UserId) -> Result<Vec<AppPassword>> {
+        Ok(model::AppPasswords::find()
+            .filter(AppPasswordsColumn::UserId.eq(user_id))
+            .order_by_asc(AppPasswordsColumn::AppPasswordId)
+            .all(&self.sql_pool)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?request.user_id.as_str(), name = ?request.name))]
+    async fn create_app_password(&self, request: CreateAppPasswordRequest) -> Result<String> {
+        let allowed_sources = normalize_allowed_sources(&request.allowed_sources)?;
+        let password = generate_app_password(&mut rand::rngs::OsRng);
+        model::app_passwords::ActiveModel {
+            user_id: ActiveValue::Set(request.user_id.clone()),
+            name: ActiveValue::Set(request.name),
+            password_hash: ActiveValue::Set(self.keyed_hash(password.as_bytes())?),
+            allowed_sources: ActiveValue::Set(allowed_sources.join(" ")),
+            required_group_id: ActiveValue::Set(request.required_group),
+            creation_date: ActiveValue::Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .insert(&self.sql_pool)
+        .await?;
+        info!(r#"User "{}" created an app password"#, request.user_id);
+        Ok(password)
+    }
+
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str(), id = ?id))]
+    async fn delete_app_password(&self, user_id: &UserId, id: AppPasswordId) -> Result<()> {
+        let res = model::AppPasswords::delete_many()
+            .filter(AppPasswordsColumn::AppPasswordId.eq(id))
+            .filter(AppPasswordsColumn::UserId.eq(user_id))
+            .exec(&self.sql_pool)
+            .await?;
+        if res.rows_affected == 0 {
+            return Err(DomainError::EntityNotFound(format!(
+                "No such app password: {id:?}"
+            )));
+        }
+        info!(r#"App password {:?} of user "{}" revoked"#, id, user_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql_backend_handler::tests::{
+        get_initialized_db, insert_group, insert_membership, insert_user,
+    };
+    use lldap_auth::opaque::server::generate_random_private_key;
+    use lldap_domain::requests::UpdateUserRequest;
+    use lldap_domain::types::GroupId;
+    use lldap_domain_handlers::handler::{
+        GroupBackendHandler, GroupMemberFilter, LoginHandler, UserRequestFilter,
+    };
+    use lldap_domain_model::model::UserColumn;
+
+    async fn setup_handler() -> SqlBackendHandler {
+        let sql_pool = get_initialized_db().await;
+        let handler = SqlBackendHandler::new(generate_random_private_key(), sql_pool);
+        insert_user(&handler, "bob", "bob00").await;
+        insert_user(&handler, "patrick", "pass00").await;
+        handler
+    }
+
+    fn create_request(
+        user_id: &str,
+        allowed_sources: &[&str],
+        required_group: Option<GroupId>,
+    ) -> CreateAppPasswordRequest {
+        CreateAppPasswordRequest {
+            user_id: UserId::new(user_id),
+            name: "Thunderbird".to_owned(),
+            allowed_sources: allowed_sources.iter().map(|s| s.to_string()).collect(),
+            required_group,
+        }
+    }
+
+    fn ldap_bind(user_id: &str, password: &str, client_address: Option<&str>) -> BindRequest {
+        BindRequest {
+            name: UserId::new(user_id),
+            password: password.to_owned(),
+            source: BindSource::Ldap(client_address.map(|address| address.parse().unwrap())),
+        }
+    }
+
+    #[test]
+    fn test_generate_app_password() {
+        let password = generate_app_password(&mut rand::rngs::OsRng);
+        assert_eq!(password.len(), 23);
+        assert_eq!(password.split('-').count(), APP_PASSWORD_GROUP_COUNT);
+    }
+
+    #[tokio::test]
+    async fn test_create_and_bind_with_app_password() {
+        let handler = setup_handler().await;
+        let bob = UserId::new("bob");
+        let password = handler
+            .create_app_password(create_request("bob", &[], None))
+            .await
+            .unwrap();
+        assert!(
+            handler.list_app_passwords(&bob).await.unwrap()[0]
+                .last_used_date
+                .is_none()
+        );
+        assert!(
+            handler
+                .bind(ldap_bind("bob", &password, Some("10.0.0.1")))
+                .await
+                .unwrap()
+                .app_password
+        );
+        assert!(
+            handler.list_app_passwords(&bob).await.unwrap()[0]
+                .last_used_date
+                .is_some()
+        );
+        // The main password still works.
+        assert!(
+            !handler
+                .bind(ldap_bind("bob", "bob00", None))
+                .await
+                .unwrap()
+                .app_password
+        );
+        // Only for LDAP binds, and only for its user.
+        handler
+            .bind(BindRequest {
+                name: bob.clone(),
+                password: password.clone(),
+                source: BindSource::Http,
+            })
+            .await
+            .unwrap_err();
+        // The non-interactive HTTP clients only accept the app passwords.
+        handler
+            .bind(BindRequest {
+                name: bob.clone(),
+                password: password.clone(),
+                source: BindSource::HttpAppPassword(None),
+            })
+            .await
+            .unwrap();
+        handler
+            .bind(BindRequest {
+                name: bob.clone(),
+                password: "bob00".to_owned(),
+                source: BindSource::HttpAppPassword(None),
+            })
+            .await
+            .unwrap_err();
+        handler
+            .bind(ldap_bind("patrick", &password, None))
+            .await
+            .unwrap_err();
+        // Not for disabled users.
+        handler.set_user_disabled(&bob, true).await.unwrap();
+        handler
+            .bind(ldap_bind("bob", &password, None))
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_app_password_allowed_sources() {
+        let handler = setup_handler().await;
+        assert!(
+            handler
+                .create_app_password(create_request("bob", &["not a network"], None))
+                .await
+                .is_err()
+        );
+        let password = handler
+            .create_app_password(create_request("bob", &["192.168.1.0/24"], None))
+            .await
+            .unwrap();
+        handler
+            .bind(ldap_bind("bob", &password, Some("192.168.1.20")))
+            .await
+            .unwrap();
+        handler
+            .bind(ldap_bind("bob", &password, Some("10.0.0.1")))
+            .await
+            .unwrap_err();
+        handler
+            .bind(ldap_bind("bob", &password, None))
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_app_password_required_group() {
+        let handler = setup_handler().await;
+        let group_id = insert_group(&handler, "jellyfin_users").await;
+        let password = handler
+            .create_app_password(create_request("bob", &[], Some(group_id)))
+            .await
+            .unwrap();
+        handler
+            .bind(ldap_bind("bob", &password, None))
+            .await
+            .unwrap_err();
+        insert_membership(&handler, group_id, "bob").await;
+        handler
+            .bind(ldap_bind("bob", &password, None))
+            .await
+            .unwrap();
+        // Deleting the group deletes the app passwords restricted to it.
+        handler.delete_group(group_id).await.unwrap();
+        assert!(
+            handler
+                .list_app_passwords(&UserId::new("bob"))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_app_password_required_dynamic_group() {
+        let handler = setup_handler().await;
+        let group_id = insert_group(&handler, "vpn_users").await;
+        handler
+            .set_group_member_filter(
+                group_id,
+                Some(GroupMemberFilter {
+                    ldap_filter: "(mail=bob@bob.bob)".to_owned(),
+                    filter: UserRequestFilter::Equality(
+                        UserColumn::Email,
+                        "bob@bob.bob".to_owned(),
+                    ),
+                }),
+            )
+            .await
+            .unwrap();
+        let password = handler
+            .create_app_password(create_request("bob", &[], Some(group_id)))
+            .await
+            .unwrap();
+        handler
+            .bind(ldap_bind("bob", &password, None))
+            .await
+            .unwrap();
+        // Leaving the dynamic group forbids the app password.
+        handler
+            .update_user(UpdateUserRequest {
+                user_id: UserId::new("bob"),
+                email: Some("bob@example.com".to_owned().into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        handler
+            .bind(ldap_bind("bob", &password, None))
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_delete_app_password() {
+        let handler = setup_handler().await;
+        let bob = UserId::new("bob");
+        let password = handler
+            .create_app_password(create_request("bob", &[], None))
+            .await
+            .unwrap();
+        let id = handler.list_app_passwords(&bob).await.unwrap()[0].id;
+        // Only the owner's app passwords can be revoked.
+        assert!(
+            handler
+                .delete_app_password(&UserId::new("patrick"), id)
+                .await
+                .is_err()
+        );
+        handler.delete_app_password(&bob, id).await.unwrap();
+        handler
+            .bind(ldap_bind("bob", &password, None))
+            .await
+            .unwrap_err();
+    }
+}
@@ -1,25 +1,145 @@
-use crate::sql_tables::DbConnection;
+use crate::{bind_cache::BindCache, sql_tables::DbConnection};
 use async_trait::async_trait;
 use lldap_auth::opaque::server::ServerSetup;
-use lldap_domain_handlers::handler::BackendHandler;
+use lldap_domain::types::{AttributeValue, Cardinality};
+use lldap_domain_handlers::handler::{BackendHandler, SubStringFilter};
+use lldap_validation::{breached_passwords::BreachedPasswordList, password::PasswordPolicy};
+use sea_orm::sea_query::LikeExpr;
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::sync::Semaphore;
+
+/// How many users or groups are fetched from the database at a time when streaming them.
+pub(crate) const STREAM_BATCH_SIZE: usize = 100;
+
+/// Separates the values in the lowercase value column of the attribute tables.
+pub(crate) const LOWERCASE_VALUE_SEPARATOR: char = '\n';
+
+/// Computes the lowercase value column of a string attribute: each value is surrounded by
+/// separators, so that substring filters can be anchored to the start or end of a value.
+pub(crate) fn get_lowercase_attribute_value(value: &AttributeValue) -> Option<String> {
+    let values = match value {
+        AttributeValue::String(Cardinality::Singleton(s)) => std::slice::from_ref(s),
+        AttributeValue::String(Cardinality::Unbounded(l)) => l.as_slice(),
+        _ => return None,
+    };
+    let mut lowercase_value = String::from(LOWERCASE_VALUE_SEPARATOR);
+    for value in values {
+        lowercase_value.push_str(&value.to_lowercase());
+        lowercase_value.push(LOWERCASE_VALUE_SEPARATOR);
+    }
+    Some(lowercase_value)
+}
+
+/// The LIKE pattern matching the lowercase value column of the attributes with a value matching
+/// the substring filter. For list attributes, the parts between the initial and final substrings
+/// can span several values.
+pub(crate) fn get_attribute_substring_pattern(filter: &SubStringFilter) -> String {
+    let mut pattern = String::from('%');
+    pattern.push(LOWERCASE_VALUE_SEPARATOR);
+    if let Some(initial) = &filter.initial {
+        pattern.push_str(&initial.to_lowercase());
+    }
+    pattern.push('%');
+    for part in filter.any.iter() {
+        pattern.push_str(&part.to_lowercase());
+        pattern.push('%');
+    }
+    if let Some(final_) = &filter.final_ {
+        pattern.push_str(&final_.to_lowercase());
+        pattern.push(LOWERCASE_VALUE_SEPARATOR);
+        pattern.push('%');
+    }
+    pattern
+}
+
+/// The LIKE expression matching the lowercase value column of the attributes with a value equal
+/// to the given one, ignoring case.
+pub(crate) fn get_attribute_ignore_case_pattern(value: &str) -> LikeExpr {
+    let mut pattern = String::from('%');
+    pattern.push(LOWERCASE_VALUE_SEPARATOR);
+    for c in value.to_lowercase().chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push(LOWERCASE_VALUE_SEPARATOR);
+    pattern.push('%');
+    LikeExpr::new(pattern).escape('\\')
+}
 
 #[derive(Clone)]
 pub struct SqlBackendHandler {
     pub(crate) opaque_setup: ServerSetup,
     pub(crate) sql_pool: DbConnection,
+    /// Bounds the number of password hashing operations running at the same time on the blocking
+    /// thread pool. The other ones wait for a permit.
+    pub(crate) password_hashing_permits: Arc<Semaphore>,
+    pub(crate) bind_cache: Option<Arc<BindCache>>,
+    /// Past this age, the passwords have to be changed before using the directory.
+    pub(crate) password_max_age: Option<chrono::Duration>,
+    /// Rules for the new passwords, when the server sees them, and size of the password history.
+    pub(crate) password_policy: PasswordPolicy,
+    pub(crate) breached_passwords: Option<BreachedPasswordList>,
+    /// Hashes of the revoked JWTs, shared by all the clones of the handler so that the tokens can
+    /// be checked without a DB query.
+    pub(crate) jwt_blacklist: Arc<RwLock<HashSet<u64>>>,
 }
 
 impl SqlBackendHandler {
     pub fn new(opaque_setup: ServerSetup, sql_pool: DbConnection) -> Self {
+        let max_concurrent_hashes = std::thread::available_parallelism()
+            .map(usize::from)
+            .unwrap_or(1);
         SqlBackendHandler {
             opaque_setup,
             sql_pool,
+            password_hashing_permits: Arc::new(Semaphore::new(max_concurrent_hashes)),
+            bind_cache: None,
+            password_max_age: None,
+            password_policy: PasswordPolicy::default(),
+            breached_passwords: None,
+            jwt_blacklist: Arc::default(),
         }
     }
 
+    /// Caches the successful simple binds in memory for the given duration, so that binding again
+    /// with the same credentials doesn't require running the OPAQUE exchange.
+    pub fn with_bind_cache(mut self, ttl: Duration) -> Self {
+        self.bind_cache = Some(Arc::new(BindCache::new(ttl)));
+        self
+    }
+
+    /// Reports the passwords older than `max_age` as expired when logging in.
+    pub fn with_password_max_age(mut self, max_age: chrono::Duration) -> Self {
+        self.password_max_age = Some(max_age);
+        self
+    }
+
+    /// Checks the new passwords against the policy, and refuses the recently used ones.
+    pub fn with_password_policy(mut self, password_policy: PasswordPolicy) -> Self {
+        self.password_policy = password_policy;
+        self
+    }
+
+    /// Refuses the new passwords found in the list of breached passwords.
+    pub fn with_breached_passwords(mut self, breached_passwords: BreachedPasswordList) -> Self {
+        self.breached_passwords = Some(breached_passwords);
+        self
+    }
+
     pub fn pool(&self) -> &DbConnection {
         &self.sql_pool
     }
+
+    /// The in-memory set of revoked JWTs, filled from the DB on startup.
+    pub fn jwt_blacklist(&self) -> &RwLock<HashSet<u64>> {
+        &self.jwt_blacklist
+    }
 }
 
 #[async_trait]
@@ -82,6 +202,7 @@ pub mod tests {
             .registration_finish(registration::ClientRegistrationFinishRequest {
                 server_data: response.server_data,
                 registration_upload: registration_upload.message,
+                password_fingerprint: None,
             })
             .await
             .unwrap();
@@ -103,6 +224,7 @@ pub mod tests {
                         value: ("last ".to_string() + name).into(),
                     },
                 ],
+                ..Default::default()
             })
             .await
             .unwrap();
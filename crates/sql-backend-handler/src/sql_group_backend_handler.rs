@@ -1,16 +1,27 @@
-use crate::sql_backend_handler::SqlBackendHandler;
+use crate::{
+    sql_backend_handler::{
+        STREAM_BATCH_SIZE, SqlBackendHandler, get_attribute_ignore_case_pattern,
+        get_attribute_substring_pattern, get_lowercase_attribute_value,
+    },
+    sql_user_backend_handler::get_user_filter_expr,
+};
 use async_trait::async_trait;
+use futures_util::{
+    StreamExt, TryFutureExt, TryStreamExt,
+    stream::{self, BoxStream},
+};
 use lldap_access_control::UserReadableBackendHandler;
 use lldap_domain::{
     requests::{CreateGroupRequest, UpdateGroupRequest},
-    types::{AttributeName, Group, GroupDetails, GroupId, Serialized, Uuid},
+    types::{AttributeName, Group, GroupDetails, GroupId, Serialized, UserId, Uuid},
 };
 use lldap_domain_handlers::handler::{
-    GroupBackendHandler, GroupListerBackendHandler, GroupRequestFilter,
+    GroupBackendHandler, GroupListerBackendHandler, GroupMemberFilter, GroupRequestFilter,
+    UserRequestFilter,
 };
 use lldap_domain_model::{
     error::{DomainError, Result},
-    model::{self, GroupColumn, MembershipColumn, deserialize},
+    model::{self, GroupColumn, MembershipColumn, UserColumn, deserialize},
 };
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, DatabaseTransaction, EntityTrait, QueryFilter, QueryOrder,
@@ -19,92 +30,130 @@ use sea_orm::{
 };
 use tracing::instrument;
 
-fn attribute_condition(name: AttributeName, value: Option<Serialized>) -> Cond {
+/// Matches the entities having the attribute, with a value satisfying `value_condition`.
+fn attribute_condition(name: AttributeName, value_condition: SimpleExpr) -> Cond {
     Expr::in_subquery(
         Expr::col(GroupColumn::GroupId.as_column_ref()),
         model::GroupAttributes::find()
             .select_only()
             .column(model::GroupAttributesColumn::GroupId)
             .filter(model::GroupAttributesColumn::AttributeName.eq(name))
-            .filter(
-                value
-                    .map(|value| model::GroupAttributesColumn::Value.eq(value))
-                    .unwrap_or_else(|| SimpleExpr::Value(true.into())),
-            )
+            .filter(value_condition)
             .into_query(),
     )
     .into_condition()
 }
 
-fn get_group_filter_expr(filter: GroupRequestFilter) -> Cond {
+/// A group whose members are the users matching a filter, rather than a list of memberships.
+pub(crate) struct DynamicGroup {
+    pub details: GroupDetails,
+    pub filter: UserRequestFilter,
+}
+
+fn get_group_filter_expr(filter: GroupRequestFilter, dynamic_groups: &[DynamicGroup]) -> Cond {
     use GroupRequestFilter::*;
     let group_table = Alias::new("groups");
     fn bool_to_expr(b: bool) -> Cond {
         SimpleExpr::Value(b.into()).into_condition()
     }
-    fn get_repeated_filter(
-        fs: Vec<GroupRequestFilter>,
-        condition: Cond,
-        default_value: bool,
-    ) -> Cond {
+    let get_repeated_filter = |fs: Vec<GroupRequestFilter>, condition: Cond, default_value| {
         if fs.is_empty() {
             bool_to_expr(default_value)
         } else {
             fs.into_iter()
-                .map(get_group_filter_expr)
+                .map(|f| get_group_filter_expr(f, dynamic_groups))
                 .fold(condition, Cond::add)
         }
-    }
+    };
     match filter {
         True => bool_to_expr(true),
         False => bool_to_expr(false),
         And(fs) => get_repeated_filter(fs, Cond::all(), true),
         Or(fs) => get_repeated_filter(fs, Cond::any(), false),
-        Not(f) => get_group_filter_expr(*f).not(),
+        Not(f) => get_group_filter_expr(*f, dynamic_groups).not(),
         DisplayName(name) => GroupColumn::LowercaseDisplayName
             .eq(name.as_str().to_lowercase())
             .into_condition(),
         GroupId(id) => GroupColumn::GroupId.eq(id.0).into_condition(),
         Uuid(uuid) => GroupColumn::Uuid.eq(uuid.to_string()).into_condition(),
         // WHERE (group_id in (SELECT group_id FROM memberships WHERE user_id = user))
-        Member(user) => GroupColumn::GroupId
-            .in_subquery(
-                model::Membership::find()
-                    .select_only()
-                    .column(MembershipColumn::GroupId)
-                    .filter(MembershipColumn::UserId.eq(user))
-                    .into_query(),
-            )
-            .into_condition(),
+        // OR (group_id = dynamic_group AND user in (SELECT user_id FROM users WHERE filter))
+        Member(user) => dynamic_groups.iter().fold(
+            Cond::any().add(
+                GroupColumn::GroupId.in_subquery(
+                    model::Membership::find()
+                        .select_only()
+                        .column(MembershipColumn::GroupId)
+                        .filter(MembershipColumn::UserId.eq(user.clone()))
+                        .into_query(),
+                ),
+            ),
+            |condition, group| {
+                condition.add(
+                    Cond::all()
+                        .add(GroupColumn::GroupId.eq(group.details.group_id))
+                        .add(
+                            Expr::val(user.as_str()).in_subquery(
+                                model::User::find()
+                                    .select_only()
+                                    .column(UserColumn::UserId)
+                                    .filter(get_user_filter_expr(group.filter.clone()))
+                                    .into_query(),
+                            ),
+                        ),
+                )
+            },
+        ),
         DisplayNameSubString(filter) => SimpleExpr::FunctionCall(Func::lower(Expr::col((
             group_table,
             GroupColumn::LowercaseDisplayName,
         ))))
         .like(filter.to_sql_filter())
         .into_condition(),
-        AttributeEquality(name, value) => attribute_condition(name, Some(value.into())),
-        CustomAttributePresent(name) => attribute_condition(name, None),
+        AttributeEquality(name, value) => attribute_condition(
+            name,
+            model::GroupAttributesColumn::Value.eq(Serialized::from(value)),
+        ),
+        AttributeEqualityIgnoreCase(name, value) => attribute_condition(
+            name,
+            model::GroupAttributesColumn::LowercaseValue
+                .like(get_attribute_ignore_case_pattern(&value)),
+        ),
+        AttributeSubString(name, filter) => attribute_condition(
+            name,
+            model::GroupAttributesColumn::LowercaseValue
+                .like(get_attribute_substring_pattern(&filter)),
+        ),
+        CustomAttributePresent(name) => attribute_condition(name, SimpleExpr::Value(true.into())),
     }
 }
 
+fn get_group_list_filter_expr(
+    filters: Option<GroupRequestFilter>,
+    dynamic_groups: &[DynamicGroup],
+) -> Cond {
+    filters
+        .map(|f| {
+            GroupColumn::GroupId
+                .in_subquery(
+                    model::Group::find()
+                        .find_also_linked(model::memberships::GroupToUser)
+                        .select_only()
+                        .column(GroupColumn::GroupId)
+                        .filter(get_group_filter_expr(f, dynamic_groups))
+                        .into_query(),
+                )
+                .into_condition()
+        })
+        .unwrap_or_else(|| SimpleExpr::Value(true.into()).into_condition())
+}
+
 #[async_trait]
 impl GroupListerBackendHandler for SqlBackendHandler {
     #[instrument(skip(self), level = "debug", ret, err)]
     async fn list_groups(&self, filters: Option<GroupRequestFilter>) -> Result<Vec<Group>> {
-        let filters = filters
-            .map(|f| {
-                GroupColumn::GroupId
-                    .in_subquery(
-                        model::Group::find()
-                            .find_also_linked(model::memberships::GroupToUser)
-                            .select_only()
-                            .column(GroupColumn::GroupId)
-                            .filter(get_group_filter_expr(f))
-                            .into_query(),
-                    )
-                    .into_condition()
-            })
-            .unwrap_or_else(|| SimpleExpr::Value(true.into()).into_condition());
+        let dynamic_groups = self.get_dynamic_groups().await?;
+        let filters = get_group_list_filter_expr(filters, &dynamic_groups);
         let results = model::Group::find()
             .order_by_asc(GroupColumn::GroupId)
             .find_with_related(model::Membership)
@@ -121,6 +170,14 @@ impl GroupListerBackendHandler for SqlBackendHandler {
                 }
             })
             .collect();
+        for group in groups.iter_mut() {
+            if let Some(dynamic_group) = dynamic_groups
+                .iter()
+                .find(|g| g.details.group_id == group.id)
+            {
+                group.users = self.get_dynamic_group_members(dynamic_group).await?;
+            }
+        }
         // TODO: should be wrapped in a transaction
         let schema = self.get_schema().await?;
         let attributes = model::GroupAttributes::find()
@@ -154,6 +211,40 @@ impl GroupListerBackendHandler for SqlBackendHandler {
         groups.sort_by(|g1, g2| g1.display_name.cmp(&g2.display_name));
         Ok(groups)
     }
+
+    fn stream_groups(&self, filters: Option<GroupRequestFilter>) -> BoxStream<'_, Result<Group>> {
+        async move {
+            // Select the ids up front, in the same order as `list_groups`, then fetch the groups
+            // with their members and attributes one batch at a time.
+            let group_ids: Vec<GroupId> = model::Group::find()
+                .filter(get_group_list_filter_expr(
+                    filters,
+                    &self.get_dynamic_groups().await?,
+                ))
+                .select_only()
+                .column(GroupColumn::GroupId)
+                .order_by_asc(GroupColumn::LowercaseDisplayName)
+                .into_tuple()
+                .all(&self.sql_pool)
+                .await?;
+            let batches: Vec<Vec<GroupId>> = group_ids
+                .chunks(STREAM_BATCH_SIZE)
+                .map(<[GroupId]>::to_vec)
+                .collect();
+            Ok::<_, DomainError>(
+                stream::iter(batches)
+                    .then(move |batch| {
+                        self.list_groups(Some(GroupRequestFilter::Or(
+                            batch.into_iter().map(GroupRequestFilter::GroupId).collect(),
+                        )))
+                    })
+                    .map_ok(|groups| stream::iter(groups.into_iter().map(Ok::<_, DomainError>)))
+                    .try_flatten(),
+            )
+        }
+        .try_flatten_stream()
+        .boxed()
+    }
 }
 
 #[async_trait]
@@ -224,6 +315,9 @@ impl GroupBackendHandler for SqlBackendHandler {
                             new_group_attributes.push(model::group_attributes::ActiveModel {
                                 group_id: Set(group_id),
                                 attribute_name: Set(attribute.name),
+                                lowercase_value: Set(get_lowercase_attribute_value(
+                                    &attribute.value,
+                                )),
                                 value: Set(attribute.value.into()),
                             });
                         } else {
@@ -257,6 +351,99 @@ impl GroupBackendHandler for SqlBackendHandler {
         }
         Ok(())
     }
+
+    #[instrument(skip(self), level = "debug", err)]
+    async fn set_group_member_filter(
+        &self,
+        group_id: GroupId,
+        member_filter: Option<GroupMemberFilter>,
+    ) -> Result<()> {
+        let (member_filter, member_filter_query) = match member_filter {
+            Some(GroupMemberFilter {
+                ldap_filter,
+                filter,
+            }) => (
+                Some(ldap_filter),
+                Some(serde_json::to_string(&filter).map_err(|e| {
+                    DomainError::InternalError(format!("Could not serialize member filter: {e}"))
+                })?),
+            ),
+            None => (None, None),
+        };
+        Ok(self
+            .sql_pool
+            .transaction::<_, (), DomainError>(|transaction| {
+                Box::pin(async move {
+                    if model::Group::find_by_id(group_id)
+                        .one(transaction)
+                        .await?
+                        .is_none()
+                    {
+                        return Err(DomainError::EntityNotFound(format!(
+                            "No such group: '{group_id:?}'"
+                        )));
+                    }
+                    if member_filter.is_some() {
+                        // The members of a dynamic group are only defined by the filter.
+                        model::Membership::delete_many()
+                            .filter(MembershipColumn::GroupId.eq(group_id))
+                            .exec(transaction)
+                            .await?;
+                    }
+                    model::groups::ActiveModel {
+                        group_id: Set(group_id),
+                        member_filter: Set(member_filter),
+                        member_filter_query: Set(member_filter_query),
+                        ..Default::default()
+                    }
+                    .update(transaction)
+                    .await?;
+                    Ok(())
+                })
+            })
+            .await?)
+    }
+}
+
+impl SqlBackendHandler {
+    /// Fetches all the dynamic groups, along with their member filter.
+    pub(crate) async fn get_dynamic_groups(&self) -> Result<Vec<DynamicGroup>> {
+        model::Group::find()
+            .filter(GroupColumn::MemberFilterQuery.is_not_null())
+            .order_by_asc(GroupColumn::GroupId)
+            .all(&self.sql_pool)
+            .await?
+            .into_iter()
+            .map(|group| {
+                let filter =
+                    serde_json::from_str(group.member_filter_query.as_deref().unwrap_or_default())
+                        .map_err(|e| {
+                            DomainError::InternalError(format!(
+                                "Invalid member filter for group {:?}: {e}",
+                                group.group_id
+                            ))
+                        })?;
+                Ok(DynamicGroup {
+                    details: group.into(),
+                    filter,
+                })
+            })
+            .collect()
+    }
+
+    pub(crate) async fn get_dynamic_group_members(
+        &self,
+        group: &DynamicGroup,
+    ) -> Result<Vec<UserId>> {
+        Ok(model::User::find()
+            .select_only()
+            .column(UserColumn::UserId)
+            .filter(get_user_filter_expr(group.filter.clone()))
+            .order_by_asc(UserColumn::UserId)
+            .into_tuple()
+            .all(&self.sql_pool)
+            .await?)
+    }
 }
 
 impl SqlBackendHandler {
@@ -287,6 +474,7 @@ impl SqlBackendHandler {
                 update_group_attributes.push(model::group_attributes::ActiveModel {
                     group_id: Set(request.group_id),
                     attribute_name: Set(attribute.name.to_owned()),
+                    lowercase_value: Set(get_lowercase_attribute_value(&attribute.value)),
                     value: Set(attribute.value.into()),
                 });
             } else {
@@ -323,7 +511,10 @@ impl SqlBackendHandler {
                         model::GroupAttributesColumn::GroupId,
                         model::GroupAttributesColumn::AttributeName,
                     ])
-                    .update_column(model::GroupAttributesColumn::Value)
+                    .update_columns([
+                        model::GroupAttributesColumn::Value,
+                        model::GroupAttributesColumn::LowercaseValue,
+                    ])
                     .to_owned(),
                 )
                 .exec(transaction)
@@ -341,9 +532,29 @@ mod tests {
         requests::CreateAttributeRequest,
         types::{Attribute, AttributeType, GroupName, UserId},
     };
-    use lldap_domain_handlers::handler::{SchemaBackendHandler, SubStringFilter};
+    use lldap_domain_handlers::handler::{
+        SchemaBackendHandler, SubStringFilter, UserBackendHandler, UserListerBackendHandler,
+    };
     use pretty_assertions::assert_eq;
 
+    /// Turns "Empty Group" into a dynamic group containing bob and John.
+    async fn make_dynamic_group(fixture: &TestFixture) {
+        fixture
+            .handler
+            .set_group_member_filter(
+                fixture.groups[2],
+                Some(GroupMemberFilter {
+                    ldap_filter: "(|(uid=bob)(uid=john))".to_owned(),
+                    filter: UserRequestFilter::Or(vec![
+                        UserRequestFilter::UserId(UserId::new("bob")),
+                        UserRequestFilter::UserId(UserId::new("john")),
+                    ]),
+                }),
+            )
+            .await
+            .unwrap();
+    }
+
     async fn get_group_ids(
         handler: &SqlBackendHandler,
         filters: Option<GroupRequestFilter>,
@@ -383,6 +594,21 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_stream_groups() {
+        let fixture = TestFixture::new().await;
+        make_dynamic_group(&fixture).await;
+        for filter in [None, Some(GroupRequestFilter::Member(UserId::new("bob")))] {
+            let streamed: Vec<_> = fixture
+                .handler
+                .stream_groups(filter.clone())
+                .try_collect()
+                .await
+                .unwrap();
+            assert_eq!(streamed, fixture.handler.list_groups(filter).await.unwrap());
+        }
+    }
+
     #[tokio::test]
     async fn test_list_groups_simple_filter() {
         let fixture = TestFixture::new().await;
@@ -459,6 +685,7 @@ mod tests {
                 is_list: false,
                 is_visible: true,
                 is_editable: true,
+                matching_rule: None,
             })
             .await
             .unwrap();
@@ -488,6 +715,62 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_list_groups_attribute_substring_filter() {
+        let fixture = TestFixture::new().await;
+        fixture
+            .handler
+            .add_group_attribute(CreateAttributeRequest {
+                name: "aliases".into(),
+                attribute_type: AttributeType::String,
+                is_list: true,
+                is_visible: true,
+                is_editable: true,
+                matching_rule: None,
+            })
+            .await
+            .unwrap();
+        fixture
+            .handler
+            .update_group(UpdateGroupRequest {
+                group_id: fixture.groups[1],
+                display_name: None,
+                delete_attributes: Vec::new(),
+                insert_attributes: vec![Attribute {
+                    name: "aliases".into(),
+                    value: vec!["Bottom".to_owned(), "Last Team".to_owned()].into(),
+                }],
+            })
+            .await
+            .unwrap();
+        let get_matching_groups = |initial: Option<&str>, final_: Option<&str>| {
+            get_group_ids(
+                &fixture.handler,
+                Some(GroupRequestFilter::AttributeSubString(
+                    AttributeName::from("aliases"),
+                    SubStringFilter {
+                        initial: initial.map(str::to_owned),
+                        any: Vec::new(),
+                        final_: final_.map(str::to_owned),
+                    },
+                )),
+            )
+        };
+        assert_eq!(
+            get_matching_groups(Some("LAST"), None).await,
+            vec![fixture.groups[1]]
+        );
+        assert_eq!(
+            get_matching_groups(None, Some("tom")).await,
+            vec![fixture.groups[1]]
+        );
+        // The anchors apply to each value.
+        assert_eq!(
+            get_matching_groups(Some("team"), None).await,
+            Vec::<GroupId>::new()
+        );
+    }
+
     #[tokio::test]
     async fn test_get_group_details() {
         let fixture = TestFixture::new().await;
@@ -562,6 +845,7 @@ mod tests {
                 is_list: false,
                 is_visible: true,
                 is_editable: true,
+                matching_rule: None,
             })
             .await
             .unwrap();
@@ -602,6 +886,7 @@ mod tests {
                 is_list: false,
                 is_visible: true,
                 is_editable: true,
+                matching_rule: None,
             })
             .await
             .unwrap();
@@ -656,4 +941,106 @@ mod tests {
             .await
             .unwrap_err();
     }
+
+    #[tokio::test]
+    async fn test_dynamic_group_members() {
+        let fixture = TestFixture::new().await;
+        make_dynamic_group(&fixture).await;
+        let groups = fixture.handler.list_groups(None).await.unwrap();
+        let dynamic_group = groups.iter().find(|g| g.id == fixture.groups[2]).unwrap();
+        assert_eq!(
+            dynamic_group.users,
+            vec![UserId::new("bob"), UserId::new("john")]
+        );
+        assert_eq!(
+            dynamic_group.member_filter.as_deref(),
+            Some("(|(uid=bob)(uid=john))")
+        );
+        assert_eq!(
+            get_group_ids(
+                &fixture.handler,
+                Some(GroupRequestFilter::Member(UserId::new("john")))
+            )
+            .await,
+            vec![fixture.groups[2], fixture.groups[1]]
+        );
+        let users = fixture
+            .handler
+            .list_users(Some(UserRequestFilter::MemberOfId(fixture.groups[2])), true)
+            .await
+            .unwrap();
+        assert_eq!(
+            users
+                .iter()
+                .map(|u| u.user.user_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["bob", "john"]
+        );
+        assert_eq!(
+            users[0]
+                .groups
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|g| g.group_id)
+                .collect::<Vec<_>>(),
+            vec![fixture.groups[0], fixture.groups[2]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dynamic_group_membership_cannot_be_edited() {
+        let fixture = TestFixture::new().await;
+        make_dynamic_group(&fixture).await;
+        fixture
+            .handler
+            .add_user_to_group(&UserId::new("patrick"), fixture.groups[2])
+            .await
+            .unwrap_err();
+        fixture
+            .handler
+            .remove_user_from_group(&UserId::new("bob"), fixture.groups[2])
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_remove_member_filter() {
+        let fixture = TestFixture::new().await;
+        make_dynamic_group(&fixture).await;
+        fixture
+            .handler
+            .set_group_member_filter(fixture.groups[2], None)
+            .await
+            .unwrap();
+        let details = fixture
+            .handler
+            .get_group_details(fixture.groups[2])
+            .await
+            .unwrap();
+        assert_eq!(details.member_filter, None);
+        assert_eq!(
+            get_group_ids(
+                &fixture.handler,
+                Some(GroupRequestFilter::Member(UserId::new("bob")))
+            )
+            .await,
+            vec![fixture.groups[0]]
+        );
+        fixture
+            .handler
+            .add_user_to_group(&UserId::new("patrick"), fixture.groups[2])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_set_member_filter_unknown_group() {
+        let fixture = TestFixture::new().await;
+        fixture
+            .handler
+            .set_group_member_filter(GroupId(123), None)
+            .await
+            .unwrap_err();
+    }
 }
@@ -0,0 +1,532 @@
+use crate::{sql_backend_handler::SqlBackendHandler, totp};
+use async_trait::async_trait;
+use lldap_domain::types::UserId;
+use lldap_domain_handlers::handler::{MfaBackendHandler, TotpEnrollment};
+use lldap_domain_model::{
+    error::{DomainError, Result},
+    model::{self, MfaRecoveryCodesColumn, UserColumn},
+};
+use rand::seq::SliceRandom;
+use sea_orm::{
+    ActiveValue, ColumnTrait, EntityTrait, QueryFilter, QuerySelect, TransactionTrait,
+    sea_query::Expr,
+};
+use tracing::{info, instrument, warn};
+
+/// The value of `mfa_type` once TOTP is enabled. While the enrollment is pending, only the secret
+/// is set.
+const MFA_TYPE_TOTP: &str = "totp";
+const TOTP_ISSUER: &str = "LLDAP";
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: usize = 10;
+/// Without the characters that are easily confused, like 0 and o.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+/// How many wrong codes can be entered in a row, across logins, before the second factor is
+/// locked.
+const MAX_MFA_FAILURES: i32 = 5;
+const MFA_LOCKOUT_MINUTES: i64 = 15;
+
+fn generate_recovery_code(rng: &mut impl rand::Rng) -> String {
+    let code = (0..RECOVERY_CODE_LENGTH)
+        .map(|_| *RECOVERY_CODE_ALPHABET.choose(rng).unwrap() as char)
+        .collect::<String>();
+    let (first, second) = code.split_at(RECOVERY_CODE_LENGTH / 2);
+    format!("{first}-{second}")
+}
+
+/// The recovery codes are compared without the dashes and spaces, and ignoring the case.
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn get_unix_time() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
+impl SqlBackendHandler {
+    /// Encrypts the TOTP secrets stored in plain text before the encryption was introduced. To
+    /// be called on startup, after the DB migrations.
+    #[instrument(skip_all, level = "debug", err)]
+    pub async fn encrypt_legacy_totp_secrets(&self) -> Result<()> {
+        let legacy_secrets = model::User::find()
+            .select_only()
+            .column(UserColumn::UserId)
+            .column(UserColumn::TotpSecret)
+            .filter(UserColumn::TotpSecret.is_not_null())
+            .into_tuple::<(UserId, String)>()
+            .all(&self.sql_pool)
+            .await?;
+        for (user_id, secret) in legacy_secrets {
+            model::User::update_many()
+                .col_expr(
+                    UserColumn::EncryptedTotpSecret,
+                    Expr::value(self.encrypt_totp_secret(&secret)?),
+                )
+                .col_expr(UserColumn::TotpSecret, Expr::value(Option::<String>::None))
+                .filter(UserColumn::UserId.eq(&user_id))
+                .exec(&self.sql_pool)
+                .await?;
+            info!(r#"Encrypted the TOTP secret of user "{}""#, user_id);
+        }
+        Ok(())
+    }
+
+    fn encrypt_totp_secret(&self, secret: &str) -> Result<Vec<u8>> {
+        Ok(orion::aead::seal(
+            &self.get_orion_secret_key()?,
+            secret.as_bytes(),
+        )?)
+    }
+
+    fn decrypt_totp_secret(&self, encrypted_secret: &[u8]) -> Result<String> {
+        String::from_utf8(orion::aead::open(
+            &self.get_orion_secret_key()?,
+            encrypted_secret,
+        )?)
+        .map_err(|e| DomainError::InternalError(format!("Invalid TOTP secret: {e}")))
+    }
+
+    /// Returns the encrypted TOTP secret and the MFA type of the user.
+    async fn get_mfa_state(&self, user_id: &UserId) -> Result<(Option<Vec<u8>>, Option<String>)> {
+        model::User::find_by_id(user_id.clone())
+            .select_only()
+            .column(UserColumn::EncryptedTotpSecret)
+            .column(UserColumn::MfaType)
+            .into_tuple::<(Option<Vec<u8>>, Option<String>)>()
+            .one(&self.sql_pool)
+            .await?
+            .ok_or_else(|| DomainError::EntityNotFound(format!("No such user: '{user_id}'")))
+    }
+
+    /// Marks the TOTP time step as used, unless a code of the same or a later step was accepted
+    /// in the meantime.
+    async fn use_totp_step(&self, user_id: &UserId, step: u64) -> Result<bool> {
+        let step = step as i64;
+        let res = model::User::update_many()
+            .col_expr(UserColumn::TotpLastStep, Expr::value(step))
+            .filter(UserColumn::UserId.eq(user_id))
+            .filter(
+                UserColumn::TotpLastStep
+                    .is_null()
+                    .or(UserColumn::TotpLastStep.lt(step)),
+            )
+            .exec(&self.sql_pool)
+            .await?;
+        Ok(res.rows_affected > 0)
+    }
+
+    /// Counts a wrong code, and locks the second factor after too many of them.
+    async fn record_mfa_failure(&self, user_id: &UserId) -> Result<()> {
+        model::User::update_many()
+            .col_expr(
+                UserColumn::MfaFailedAttempts,
+                Expr::col(UserColumn::MfaFailedAttempts.as_column_ref()).add(1),
+            )
+            .filter(UserColumn::UserId.eq(user_id))
+            .exec(&self.sql_pool)
+            .await?;
+        let locked_until =
+            chrono::Utc::now().naive_utc() + chrono::Duration::minutes(MFA_LOCKOUT_MINUTES);
+        let res = model::User::update_many()
+            .col_expr(UserColumn::MfaFailedAttempts, Expr::value(0))
+            .col_expr(UserColumn::MfaLockedUntil, Expr::value(locked_until))
+            .filter(UserColumn::UserId.eq(user_id))
+            .filter(UserColumn::MfaFailedAttempts.gte(MAX_MFA_FAILURES))
+            .exec(&self.sql_pool)
+            .await?;
+        if res.rows_affected > 0 {
+            warn!(
+                r#"Too many wrong second factor codes for user "{}", locked until {}"#,
+                user_id, locked_until
+            );
+        }
+        Ok(())
+    }
+
+    fn hash_recovery_code(&self, code: &str) -> Result<Vec<u8>> {
+        self.keyed_hash(normalize_recovery_code(code).as_bytes())
+    }
+
+    /// Consumes the recovery code if it's one of the user's.
+    async fn use_recovery_code(&self, user_id: &UserId, code: &str) -> Result<bool> {
+        let res = model::MfaRecoveryCodes::delete_many()
+            .filter(MfaRecoveryCodesColumn::UserId.eq(user_id))
+            .filter(MfaRecoveryCodesColumn::CodeHash.eq(self.hash_recovery_code(code)?))
+            .exec(&self.sql_pool)
+            .await?;
+        if res.rows_affected > 0 {
+            info!(r#"User "{}" used a recovery code"#, user_id);
+        }
+        Ok(res.rows_affected > 0)
+    }
+}
+
+#[async_trait]
+impl MfaBackendHandler for SqlBackendHandler {
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str()))]
+    async fn start_totp_enrollment(&self, user_id: &UserId) -> Result<TotpEnrollment> {
+        let (_, mfa_type) = self.get_mfa_state(user_id).await?;
+        if mfa_type.is_some() {
+            return Err(DomainError::InternalError(
+                "A second factor is already enabled, it has to be reset first".to_string(),
+            ));
+        }
+        let secret = totp::generate_secret(&mut rand::rngs::OsRng);
+        model::User::update_many()
+            .col_expr(
+                UserColumn::EncryptedTotpSecret,
+                Expr::value(self.encrypt_totp_secret(&secret)?),
+            )
+            .filter(UserColumn::UserId.eq(user_id))
+            .exec(&self.sql_pool)
+            .await?;
+        Ok(TotpEnrollment {
+            uri: totp::get_otpauth_uri(&secret, user_id.as_str(), TOTP_ISSUER),
+            secret,
+        })
+    }
+
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str()))]
+    async fn confirm_totp_enrollment(&self, user_id: &UserId, code: &str) -> Result<Vec<String>> {
+        let secret = match self.get_mfa_state(user_id).await? {
+            (_, Some(_)) => {
+                return Err(DomainError::InternalError(
+                    "A second factor is already enabled".to_string(),
+                ));
+            }
+            (None, None) => {
+                return Err(DomainError::InternalError(
+                    "The TOTP enrollment was not started".to_string(),
+                ));
+            }
+            (Some(encrypted_secret), None) => self.decrypt_totp_secret(&encrypted_secret)?,
+        };
+        let Some(step) = totp::check_code(&secret, code, get_unix_time(), None) else {
+            return Err(DomainError::AuthenticationError(
+                "Invalid TOTP code".to_string(),
+            ));
+        };
+        let codes = {
+            let mut rng = rand::rngs::OsRng;
+            (0..RECOVERY_CODE_COUNT)
+                .map(|_| generate_recovery_code(&mut rng))
+                .collect::<Vec<_>>()
+        };
+        let code_hashes = codes
+            .iter()
+            .map(|code| self.hash_recovery_code(code))
+            .collect::<Result<Vec<_>>>()?;
+        self.sql_pool
+            .transaction::<_, (), DomainError>(|transaction| {
+                let user_id = user_id.clone();
+                Box::pin(async move {
+                    // The code used to confirm can't be used again to log in.
+                    model::User::update_many()
+                        .col_expr(UserColumn::MfaType, Expr::value(MFA_TYPE_TOTP))
+                        .col_expr(UserColumn::TotpLastStep, Expr::value(step as i64))
+                        .col_expr(UserColumn::MfaFailedAttempts, Expr::value(0))
+                        .col_expr(
+                            UserColumn::MfaLockedUntil,
+                            Expr::value(Option::<chrono::NaiveDateTime>::None),
+                        )
+                        .filter(UserColumn::UserId.eq(&user_id))
+                        .exec(transaction)
+                        .await?;
+                    model::MfaRecoveryCodes::delete_many()
+                        .filter(MfaRecoveryCodesColumn::UserId.eq(&user_id))
+                        .exec(transaction)
+                        .await?;
+                    model::MfaRecoveryCodes::insert_many(code_hashes.into_iter().map(
+                        |code_hash| model::mfa_recovery_codes::ActiveModel {
+                            user_id: ActiveValue::Set(user_id.clone()),
+                            code_hash: ActiveValue::Set(code_hash),
+                            ..Default::default()
+                        },
+                    ))
+                    .exec(transaction)
+                    .await?;
+                    Ok(())
+                })
+            })
+            .await?;
+        info!(r#"User "{}" enabled TOTP"#, user_id);
+        Ok(codes)
+    }
+
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str()))]
+    async fn verify_mfa_code(&self, user_id: &UserId, code: &str) -> Result<()> {
+        let (secret, mfa_type, last_step, locked_until) = model::User::find_by_id(user_id.clone())
+            .select_only()
+            .column(UserColumn::EncryptedTotpSecret)
+            .column(UserColumn::MfaType)
+            .column(UserColumn::TotpLastStep)
+            .column(UserColumn::MfaLockedUntil)
+            .into_tuple::<(
+                Option<Vec<u8>>,
+                Option<String>,
+                Option<i64>,
+                Option<chrono::NaiveDateTime>,
+            )>()
+            .one(&self.sql_pool)
+            .await?
+            .ok_or_else(|| DomainError::EntityNotFound(format!("No such user: '{user_id}'")))?;
+        let encrypted_secret = match (secret, mfa_type) {
+            (Some(secret), Some(mfa_type)) if mfa_type == MFA_TYPE_TOTP => secret,
+            _ => {
+                return Err(DomainError::AuthenticationError(format!(
+                    r#"No second factor enabled for user "{user_id}""#
+                )));
+            }
+        };
+        if locked_until.is_some_and(|until| until > chrono::Utc::now().naive_utc()) {
+            return Err(DomainError::AuthenticationError(format!(
+                r#"Too many wrong second factor codes for user "{user_id}", try again later"#
+            )));
+        }
+        let secret = self.decrypt_totp_secret(&encrypted_secret)?;
+        let is_valid = match totp::check_code(
+            &secret,
+            code,
+            get_unix_time(),
+            last_step.map(|step| step as u64),
+        ) {
+            Some(step) => self.use_totp_step(user_id, step).await?,
+            None => self.use_recovery_code(user_id, code).await?,
+        };
+        if !is_valid {
+            self.record_mfa_failure(user_id).await?;
+            return Err(DomainError::AuthenticationError(format!(
+                r#"Invalid second factor code for user "{user_id}""#
+            )));
+        }
+        model::User::update_many()
+            .col_expr(UserColumn::MfaFailedAttempts, Expr::value(0))
+            .filter(UserColumn::UserId.eq(user_id))
+            .exec(&self.sql_pool)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str()))]
+    async fn reset_mfa(&self, user_id: &UserId) -> Result<()> {
+        self.sql_pool
+            .transaction::<_, (), DomainError>(|transaction| {
+                let user_id = user_id.clone();
+                Box::pin(async move {
+                    let res = model::User::update_many()
+                        .col_expr(UserColumn::TotpSecret, Expr::value(Option::<String>::None))
+                        .col_expr(
+                            UserColumn::EncryptedTotpSecret,
+                            Expr::value(Option::<Vec<u8>>::None),
+                        )
+                        .col_expr(UserColumn::MfaType, Expr::value(Option::<String>::None))
+                        .col_expr(UserColumn::TotpLastStep, Expr::value(Option::<i64>::None))
+                        .col_expr(UserColumn::MfaFailedAttempts, Expr::value(0))
+                        .col_expr(
+                            UserColumn::MfaLockedUntil,
+                            Expr::value(Option::<chrono::NaiveDateTime>::None),
+                        )
+                        .filter(UserColumn::UserId.eq(&user_id))
+                        .exec(transaction)
+                        .await?;
+                    if res.rows_affected == 0 {
+                        return Err(DomainError::EntityNotFound(format!(
+                            "No such user: '{user_id}'"
+                        )));
+                    }
+                    model::MfaRecoveryCodes::delete_many()
+                        .filter(MfaRecoveryCodesColumn::UserId.eq(&user_id))
+                        .exec(transaction)
+                        .await?;
+                    Ok(())
+                })
+            })
+            .await?;
+        info!(r#"Second factor of user "{}" reset"#, user_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql_backend_handler::tests::{get_initialized_db, insert_user_no_password};
+    use lldap_auth::opaque::server::generate_random_private_key;
+    use lldap_domain_handlers::handler::UserBackendHandler;
+
+    async fn setup_handler() -> SqlBackendHandler {
+        let sql_pool = get_initialized_db().await;
+        let handler = SqlBackendHandler::new(generate_random_private_key(), sql_pool);
+        insert_user_no_password(&handler, "bob").await;
+        handler
+    }
+
+    async fn enroll_bob(handler: &SqlBackendHandler) -> (String, Vec<String>) {
+        let bob = UserId::new("bob");
+        let enrollment = handler.start_totp_enrollment(&bob).await.unwrap();
+        assert!(enrollment.uri.starts_with("otpauth://totp/LLDAP:bob?"));
+        let code = totp::generate_code(&enrollment.secret, get_unix_time());
+        let recovery_codes = handler.confirm_totp_enrollment(&bob, &code).await.unwrap();
+        (enrollment.secret, recovery_codes)
+    }
+
+    #[tokio::test]
+    async fn test_totp_enrollment() {
+        let handler = setup_handler().await;
+        let bob = UserId::new("bob");
+        let enrollment = handler.start_totp_enrollment(&bob).await.unwrap();
+        // The second factor is only enabled once confirmed.
+        assert!(!handler.get_user_details(&bob).await.unwrap().mfa_enabled);
+        assert!(handler.verify_mfa_code(&bob, "123456").await.is_err());
+        let wrong_code = totp::generate_code(&enrollment.secret, get_unix_time() + 3600);
+        assert!(
+            handler
+                .confirm_totp_enrollment(&bob, &wrong_code)
+                .await
+                .is_err()
+        );
+        let code = totp::generate_code(&enrollment.secret, get_unix_time());
+        let recovery_codes = handler.confirm_totp_enrollment(&bob, &code).await.unwrap();
+        assert_eq!(recovery_codes.len(), RECOVERY_CODE_COUNT);
+        assert!(handler.get_user_details(&bob).await.unwrap().mfa_enabled);
+        // The code used to confirm cannot be used to log in.
+        assert!(handler.verify_mfa_code(&bob, &code).await.is_err());
+        let code = totp::generate_code(&enrollment.secret, get_unix_time() + 30);
+        handler.verify_mfa_code(&bob, &code).await.unwrap();
+        // It cannot be enrolled again before a reset.
+        assert!(handler.start_totp_enrollment(&bob).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_totp_secret_encrypted() {
+        let handler = setup_handler().await;
+        let (secret, _) = enroll_bob(&handler).await;
+        let user = model::User::find_by_id(UserId::new("bob"))
+            .one(&handler.sql_pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.totp_secret, None);
+        let encrypted_secret = user.encrypted_totp_secret.unwrap();
+        assert!(
+            !encrypted_secret
+                .windows(secret.len())
+                .any(|window| window == secret.as_bytes())
+        );
+        assert_eq!(
+            handler.decrypt_totp_secret(&encrypted_secret).unwrap(),
+            secret
+        );
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_legacy_totp_secrets() {
+        let handler = setup_handler().await;
+        let bob = UserId::new("bob");
+        let secret = totp::generate_secret(&mut rand::rngs::OsRng);
+        model::User::update_many()
+            .col_expr(UserColumn::TotpSecret, Expr::value(secret.clone()))
+            .col_expr(UserColumn::MfaType, Expr::value(MFA_TYPE_TOTP))
+            .filter(UserColumn::UserId.eq(&bob))
+            .exec(&handler.sql_pool)
+            .await
+            .unwrap();
+        handler.encrypt_legacy_totp_secrets().await.unwrap();
+        let user = model::User::find_by_id(bob.clone())
+            .one(&handler.sql_pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.totp_secret, None);
+        assert!(user.encrypted_totp_secret.is_some());
+        let code = totp::generate_code(&secret, get_unix_time());
+        handler.verify_mfa_code(&bob, &code).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_recovery_codes() {
+        let handler = setup_handler().await;
+        let bob = UserId::new("bob");
+        let (_, recovery_codes) = enroll_bob(&handler).await;
+        let code = &recovery_codes[3];
+        handler
+            .verify_mfa_code(&bob, &code.to_uppercase().replace('-', " "))
+            .await
+            .unwrap();
+        // Each code can only be used once.
+        assert!(handler.verify_mfa_code(&bob, code).await.is_err());
+        handler
+            .verify_mfa_code(&bob, &recovery_codes[4])
+            .await
+            .unwrap();
+        assert!(handler.verify_mfa_code(&bob, "abcde-fghjk").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_totp_code_reuse() {
+        let handler = setup_handler().await;
+        let bob = UserId::new("bob");
+        let (secret, _) = enroll_bob(&handler).await;
+        let code = totp::generate_code(&secret, get_unix_time() + 30);
+        handler.verify_mfa_code(&bob, &code).await.unwrap();
+        assert!(handler.verify_mfa_code(&bob, &code).await.is_err());
+        // The codes of the previous steps are not accepted either.
+        let code = totp::generate_code(&secret, get_unix_time());
+        assert!(handler.verify_mfa_code(&bob, &code).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mfa_lockout() {
+        let handler = setup_handler().await;
+        let bob = UserId::new("bob");
+        let (secret, recovery_codes) = enroll_bob(&handler).await;
+        for _ in 0..MAX_MFA_FAILURES - 1 {
+            assert!(handler.verify_mfa_code(&bob, "000000").await.is_err());
+        }
+        // A right code resets the count.
+        handler
+            .verify_mfa_code(&bob, &recovery_codes[0])
+            .await
+            .unwrap();
+        for _ in 0..MAX_MFA_FAILURES {
+            assert!(handler.verify_mfa_code(&bob, "000000").await.is_err());
+        }
+        // Even the right codes are rejected while locked.
+        let code = totp::generate_code(&secret, get_unix_time() + 30);
+        assert!(handler.verify_mfa_code(&bob, &code).await.is_err());
+        assert!(
+            handler
+                .verify_mfa_code(&bob, &recovery_codes[1])
+                .await
+                .is_err()
+        );
+        // Resetting the second factor lifts the lock.
+        handler.reset_mfa(&bob).await.unwrap();
+        let (secret, _) = enroll_bob(&handler).await;
+        let code = totp::generate_code(&secret, get_unix_time() + 30);
+        handler.verify_mfa_code(&bob, &code).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reset_mfa() {
+        let handler = setup_handler().await;
+        let bob = UserId::new("bob");
+        let (secret, recovery_codes) = enroll_bob(&handler).await;
+        handler.reset_mfa(&bob).await.unwrap();
+        assert!(!handler.get_user_details(&bob).await.unwrap().mfa_enabled);
+        let code = totp::generate_code(&secret, get_unix_time());
+        assert!(handler.verify_mfa_code(&bob, &code).await.is_err());
+        assert!(
+            handler
+                .verify_mfa_code(&bob, &recovery_codes[0])
+                .await
+                .is_err()
+        );
+        // A new secret can be enrolled.
+        enroll_bob(&handler).await;
+        assert!(handler.reset_mfa(&UserId::new("alice")).await.is_err());
+    }
+}
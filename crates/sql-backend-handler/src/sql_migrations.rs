@@ -1,6 +1,12 @@
-use crate::sql_tables::{DbConnection, LAST_SCHEMA_VERSION, SchemaVersion};
+use crate::{
+    sql_backend_handler::get_lowercase_attribute_value,
+    sql_tables::{DbConnection, LAST_SCHEMA_VERSION, SchemaVersion},
+};
 use itertools::Itertools;
-use lldap_domain::types::{AttributeType, GroupId, JpegPhoto, Serialized, UserId, Uuid};
+use lldap_domain::types::{
+    AttributeType, GroupId, JpegPhoto, MatchingRule, Serialized, UserId, Uuid,
+};
+use lldap_domain_model::model::deserialize;
 use sea_orm::{
     ConnectionTrait, DatabaseTransaction, DbErr, DeriveIden, FromQueryResult, Iden, Order,
     Statement, TransactionTrait,
@@ -24,9 +30,21 @@ pub enum Users {
     Avatar,
     CreationDate,
     PasswordHash,
+    LegacyPasswordHash,
     TotpSecret,
+    EncryptedTotpSecret,
     MfaType,
     Uuid,
+    Disabled,
+    ExpirationDate,
+    ExpirationWarningSent,
+    PasswordModifiedDate,
+    MustChangePassword,
+    IsServiceAccount,
+    AllowedSources,
+    TotpLastStep,
+    MfaFailedAttempts,
+    MfaLockedUntil,
 }
 
 #[derive(DeriveIden, PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy)]
@@ -37,6 +55,8 @@ pub(crate) enum Groups {
     LowercaseDisplayName,
     CreationDate,
     Uuid,
+    MemberFilter,
+    MemberFilterQuery,
 }
 
 #[derive(DeriveIden, Clone, Copy)]
@@ -56,6 +76,7 @@ pub(crate) enum UserAttributeSchema {
     UserAttributeSchemaIsUserVisible,
     UserAttributeSchemaIsUserEditable,
     UserAttributeSchemaIsHardcoded,
+    UserAttributeSchemaMatchingRule,
 }
 
 #[derive(DeriveIden, PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy)]
@@ -64,6 +85,7 @@ pub(crate) enum UserAttributes {
     UserAttributeUserId,
     UserAttributeName,
     UserAttributeValue,
+    UserAttributeLowercaseValue,
 }
 
 #[allow(clippy::enum_variant_names)] // The table names are generated from the enum.
@@ -76,6 +98,7 @@ pub(crate) enum GroupAttributeSchema {
     GroupAttributeSchemaIsGroupVisible,
     GroupAttributeSchemaIsGroupEditable,
     GroupAttributeSchemaIsHardcoded,
+    GroupAttributeSchemaMatchingRule,
 }
 
 #[derive(DeriveIden, PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy)]
@@ -84,6 +107,7 @@ pub(crate) enum GroupAttributes {
     GroupAttributeGroupId,
     GroupAttributeName,
     GroupAttributeValue,
+    GroupAttributeLowercaseValue,
 }
 
 #[derive(DeriveIden, PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy)]
@@ -100,6 +124,69 @@ pub(crate) enum GroupObjectClasses {
     ObjectClass,
 }
 
+#[derive(DeriveIden, PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy)]
+pub(crate) enum SudoRoles {
+    Table,
+    SudoRoleId,
+    Name,
+    LowercaseName,
+    Description,
+    SudoOrder,
+    CreationDate,
+    Uuid,
+}
+
+#[derive(DeriveIden, PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy)]
+pub(crate) enum SudoRoleValues {
+    Table,
+    SudoRoleId,
+    AttributeName,
+    Position,
+    Value,
+}
+
+#[derive(DeriveIden, PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy)]
+pub(crate) enum PasswordHistory {
+    Table,
+    PasswordHistoryId,
+    UserId,
+    FingerprintHash,
+    CreationDate,
+}
+
+#[derive(DeriveIden, PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy)]
+pub(crate) enum MfaRecoveryCodes {
+    Table,
+    MfaRecoveryCodeId,
+    UserId,
+    CodeHash,
+}
+
+#[derive(DeriveIden, PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy)]
+pub(crate) enum WebauthnCredentials {
+    Table,
+    WebauthnCredentialId,
+    UserId,
+    CredentialId,
+    Name,
+    Passkey,
+    CreationDate,
+    LastUsedDate,
+}
+
+#[derive(DeriveIden, PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy)]
+pub(crate) enum AppPasswords {
+    Table,
+    AppPasswordId,
+    UserId,
+    Name,
+    PasswordHash,
+    AllowedSources,
+    RequiredGroupId,
+    CreationDate,
+    LastUsedDate,
+}
+
 // Metadata about the SQL DB.
 #[derive(DeriveIden)]
 pub(crate) enum Metadata {
@@ -1112,6 +1199,732 @@ async fn migrate_to_v10(transaction: DatabaseTransaction) -> Result<DatabaseTran
     Ok(transaction)
 }
 
+async fn migrate_to_v11(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    transaction
+        .execute(
+            builder.build(
+                Table::alter()
+                    .table(Groups::Table)
+                    .add_column(ColumnDef::new(Groups::MemberFilter).text()),
+            ),
+        )
+        .await?;
+    transaction
+        .execute(
+            builder.build(
+                Table::alter()
+                    .table(Groups::Table)
+                    .add_column(ColumnDef::new(Groups::MemberFilterQuery).text()),
+            ),
+        )
+        .await?;
+    Ok(transaction)
+}
+
+async fn migrate_to_v12(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    transaction
+        .execute(
+            builder.build(
+                Table::alter()
+                    .table(UserAttributes::Table)
+                    .add_column(ColumnDef::new(UserAttributes::UserAttributeLowercaseValue).text()),
+            ),
+        )
+        .await?;
+    transaction
+        .execute(
+            builder.build(
+                Table::alter().table(GroupAttributes::Table).add_column(
+                    ColumnDef::new(GroupAttributes::GroupAttributeLowercaseValue).text(),
+                ),
+            ),
+        )
+        .await?;
+    // Fill in the lowercase values of the existing string attributes.
+    #[derive(FromQueryResult)]
+    struct UserStringAttribute {
+        user_attribute_user_id: UserId,
+        user_attribute_name: String,
+        user_attribute_value: Serialized,
+        user_attribute_schema_is_list: bool,
+    }
+    for attribute in UserStringAttribute::find_by_statement(
+        builder.build(
+            Query::select()
+                .columns([
+                    UserAttributes::UserAttributeUserId,
+                    UserAttributes::UserAttributeName,
+                    UserAttributes::UserAttributeValue,
+                ])
+                .column(UserAttributeSchema::UserAttributeSchemaIsList)
+                .from(UserAttributes::Table)
+                .inner_join(
+                    UserAttributeSchema::Table,
+                    Expr::col(UserAttributes::UserAttributeName)
+                        .equals(UserAttributeSchema::UserAttributeSchemaName),
+                )
+                .and_where(
+                    Expr::col(UserAttributeSchema::UserAttributeSchemaType)
+                        .eq(AttributeType::String),
+                ),
+        ),
+    )
+    .all(&transaction)
+    .await?
+    {
+        let value = deserialize::deserialize_attribute_value(
+            &attribute.user_attribute_value,
+            AttributeType::String,
+            attribute.user_attribute_schema_is_list,
+        );
+        transaction
+            .execute(
+                builder.build(
+                    Query::update()
+                        .table(UserAttributes::Table)
+                        .value(
+                            UserAttributes::UserAttributeLowercaseValue,
+                            get_lowercase_attribute_value(&value),
+                        )
+                        .and_where(
+                            Expr::col(UserAttributes::UserAttributeUserId)
+                                .eq(attribute.user_attribute_user_id),
+                        )
+                        .and_where(
+                            Expr::col(UserAttributes::UserAttributeName)
+                                .eq(attribute.user_attribute_name),
+                        ),
+                ),
+            )
+            .await?;
+    }
+    #[derive(FromQueryResult)]
+    struct GroupStringAttribute {
+        group_attribute_group_id: GroupId,
+        group_attribute_name: String,
+        group_attribute_value: Serialized,
+        group_attribute_schema_is_list: bool,
+    }
+    for attribute in GroupStringAttribute::find_by_statement(
+        builder.build(
+            Query::select()
+                .columns([
+                    GroupAttributes::GroupAttributeGroupId,
+                    GroupAttributes::GroupAttributeName,
+                    GroupAttributes::GroupAttributeValue,
+                ])
+                .column(GroupAttributeSchema::GroupAttributeSchemaIsList)
+                .from(GroupAttributes::Table)
+                .inner_join(
+                    GroupAttributeSchema::Table,
+                    Expr::col(GroupAttributes::GroupAttributeName)
+                        .equals(GroupAttributeSchema::GroupAttributeSchemaName),
+                )
+                .and_where(
+                    Expr::col(GroupAttributeSchema::GroupAttributeSchemaType)
+                        .eq(AttributeType::String),
+                ),
+        ),
+    )
+    .all(&transaction)
+    .await?
+    {
+        let value = deserialize::deserialize_attribute_value(
+            &attribute.group_attribute_value,
+            AttributeType::String,
+            attribute.group_attribute_schema_is_list,
+        );
+        transaction
+            .execute(
+                builder.build(
+                    Query::update()
+                        .table(GroupAttributes::Table)
+                        .value(
+                            GroupAttributes::GroupAttributeLowercaseValue,
+                            get_lowercase_attribute_value(&value),
+                        )
+                        .and_where(
+                            Expr::col(GroupAttributes::GroupAttributeGroupId)
+                                .eq(attribute.group_attribute_group_id),
+                        )
+                        .and_where(
+                            Expr::col(GroupAttributes::GroupAttributeName)
+                                .eq(attribute.group_attribute_name),
+                        ),
+                ),
+            )
+            .await?;
+    }
+    Ok(transaction)
+}
+
+async fn migrate_to_v13(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    transaction
+        .execute(
+            builder.build(
+                Table::alter().table(UserAttributeSchema::Table).add_column(
+                    ColumnDef::new(UserAttributeSchema::UserAttributeSchemaMatchingRule)
+                        .string_len(64)
+                        .not_null()
+                        .default("UNSET"),
+                ),
+            ),
+        )
+        .await?;
+    transaction
+        .execute(
+            builder.build(
+                Table::alter()
+                    .table(GroupAttributeSchema::Table)
+                    .add_column(
+                        ColumnDef::new(GroupAttributeSchema::GroupAttributeSchemaMatchingRule)
+                            .string_len(64)
+                            .not_null()
+                            .default("UNSET"),
+                    ),
+            ),
+        )
+        .await?;
+    // The existing attributes keep the matching rule of their type.
+    for attribute_type in [
+        AttributeType::String,
+        AttributeType::Integer,
+        AttributeType::JpegPhoto,
+        AttributeType::DateTime,
+    ] {
+        let matching_rule = MatchingRule::default_for(attribute_type);
+        transaction
+            .execute(
+                builder.build(
+                    Query::update()
+                        .table(UserAttributeSchema::Table)
+                        .value(
+                            UserAttributeSchema::UserAttributeSchemaMatchingRule,
+                            matching_rule,
+                        )
+                        .and_where(
+                            Expr::col(UserAttributeSchema::UserAttributeSchemaType)
+                                .eq(attribute_type),
+                        ),
+                ),
+            )
+            .await?;
+        transaction
+            .execute(
+                builder.build(
+                    Query::update()
+                        .table(GroupAttributeSchema::Table)
+                        .value(
+                            GroupAttributeSchema::GroupAttributeSchemaMatchingRule,
+                            matching_rule,
+                        )
+                        .and_where(
+                            Expr::col(GroupAttributeSchema::GroupAttributeSchemaType)
+                                .eq(attribute_type),
+                        ),
+                ),
+            )
+            .await?;
+    }
+    Ok(transaction)
+}
+
+async fn migrate_to_v14(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    transaction
+        .execute(
+            builder.build(
+                Table::create()
+                    .table(SudoRoles::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(SudoRoles::SudoRoleId)
+                            .integer()
+                            .auto_increment()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(SudoRoles::Name).string_len(255).not_null())
+                    .col(
+                        ColumnDef::new(SudoRoles::LowercaseName)
+                            .string_len(255)
+                            .unique_key()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(SudoRoles::Description).text())
+                    .col(
+                        ColumnDef::new(SudoRoles::SudoOrder)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(SudoRoles::CreationDate)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(SudoRoles::Uuid).string_len(36).not_null()),
+            ),
+        )
+        .await?;
+    transaction
+        .execute(
+            builder.build(
+                Table::create()
+                    .table(SudoRoleValues::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(SudoRoleValues::SudoRoleId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SudoRoleValues::AttributeName)
+                            .string_len(64)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SudoRoleValues::Position)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(SudoRoleValues::Value).text().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("SudoRoleValuesSudoRoleIdForeignKey")
+                            .from(SudoRoleValues::Table, SudoRoleValues::SudoRoleId)
+                            .to(SudoRoles::Table, SudoRoles::SudoRoleId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(SudoRoleValues::SudoRoleId)
+                            .col(SudoRoleValues::AttributeName)
+                            .col(SudoRoleValues::Position),
+                    ),
+            ),
+        )
+        .await?;
+    Ok(transaction)
+}
+
+async fn migrate_to_v15(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    transaction
+        .execute(
+            builder.build(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(ColumnDef::new(Users::LegacyPasswordHash).text()),
+            ),
+        )
+        .await?;
+    Ok(transaction)
+}
+
+async fn migrate_to_v16(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    transaction
+        .execute(
+            builder.build(
+                Table::alter().table(Users::Table).add_column(
+                    ColumnDef::new(Users::Disabled)
+                        .boolean()
+                        .not_null()
+                        .default(false),
+                ),
+            ),
+        )
+        .await?;
+    Ok(transaction)
+}
+
+async fn migrate_to_v17(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    transaction
+        .execute(
+            builder.build(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(ColumnDef::new(Users::ExpirationDate).date_time()),
+            ),
+        )
+        .await?;
+    transaction
+        .execute(
+            builder.build(
+                Table::alter().table(Users::Table).add_column(
+                    ColumnDef::new(Users::ExpirationWarningSent)
+                        .boolean()
+                        .not_null()
+                        .default(false),
+                ),
+            ),
+        )
+        .await?;
+    Ok(transaction)
+}
+
+async fn migrate_to_v18(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    transaction
+        .execute(
+            builder.build(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(ColumnDef::new(Users::PasswordModifiedDate).date_time()),
+            ),
+        )
+        .await?;
+    transaction
+        .execute(
+            builder.build(
+                Table::alter().table(Users::Table).add_column(
+                    ColumnDef::new(Users::MustChangePassword)
+                        .boolean()
+                        .not_null()
+                        .default(false),
+                ),
+            ),
+        )
+        .await?;
+    // We don't know when the existing passwords were set: the maximum age starts from the
+    // upgrade.
+    transaction
+        .execute(
+            builder.build(
+                Query::update()
+                    .table(Users::Table)
+                    .value(
+                        Users::PasswordModifiedDate,
+                        Value::from(chrono::Utc::now().naive_utc()),
+                    )
+                    .and_where(Expr::col(Users::PasswordHash).is_not_null()),
+            ),
+        )
+        .await?;
+    Ok(transaction)
+}
+
+async fn migrate_to_v19(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    transaction
+        .execute(
+            builder.build(
+                Table::create()
+                    .table(PasswordHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PasswordHistory::PasswordHistoryId)
+                            .integer()
+                            .auto_increment()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PasswordHistory::UserId)
+                            .string_len(255)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PasswordHistory::FingerprintHash)
+                            .blob()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PasswordHistory::CreationDate)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("PasswordHistoryUserForeignKey")
+                            .from(PasswordHistory::Table, PasswordHistory::UserId)
+                            .to(Users::Table, Users::UserId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    ),
+            ),
+        )
+        .await?;
+    Ok(transaction)
+}
+
+async fn migrate_to_v20(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    transaction
+        .execute(
+            builder.build(
+                Table::create()
+                    .table(MfaRecoveryCodes::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MfaRecoveryCodes::MfaRecoveryCodeId)
+                            .integer()
+                            .auto_increment()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(MfaRecoveryCodes::UserId)
+                            .string_len(255)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(MfaRecoveryCodes::CodeHash).blob().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("MfaRecoveryCodesUserForeignKey")
+                            .from(MfaRecoveryCodes::Table, MfaRecoveryCodes::UserId)
+                            .to(Users::Table, Users::UserId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    ),
+            ),
+        )
+        .await?;
+    Ok(transaction)
+}
+
+async fn migrate_to_v21(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    // The credential IDs are not unique in the DB, since MySQL cannot index a blob: it's checked
+    // when adding a credential.
+    transaction
+        .execute(
+            builder.build(
+                Table::create()
+                    .table(WebauthnCredentials::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WebauthnCredentials::WebauthnCredentialId)
+                            .integer()
+                            .auto_increment()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(WebauthnCredentials::UserId)
+                            .string_len(255)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(WebauthnCredentials::CredentialId)
+                            .blob()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(WebauthnCredentials::Name)
+                            .string_len(255)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(WebauthnCredentials::Passkey)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(WebauthnCredentials::CreationDate)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(WebauthnCredentials::LastUsedDate).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("WebauthnCredentialsUserForeignKey")
+                            .from(WebauthnCredentials::Table, WebauthnCredentials::UserId)
+                            .to(Users::Table, Users::UserId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    ),
+            ),
+        )
+        .await?;
+    Ok(transaction)
+}
+
+async fn migrate_to_v22(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    transaction
+        .execute(
+            builder.build(
+                Table::create()
+                    .table(AppPasswords::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AppPasswords::AppPasswordId)
+                            .integer()
+                            .auto_increment()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(AppPasswords::UserId)
+                            .string_len(255)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AppPasswords::Name)
+                            .string_len(255)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(AppPasswords::PasswordHash).blob().not_null())
+                    .col(
+                        ColumnDef::new(AppPasswords::AllowedSources)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(AppPasswords::RequiredGroupId).integer())
+                    .col(
+                        ColumnDef::new(AppPasswords::CreationDate)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(AppPasswords::LastUsedDate).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("AppPasswordsUserForeignKey")
+                            .from(AppPasswords::Table, AppPasswords::UserId)
+                            .to(Users::Table, Users::UserId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    // Deleting the group deletes the passwords restricted to it, rather than
+                    // lifting the restriction.
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("AppPasswordsGroupForeignKey")
+                            .from(AppPasswords::Table, AppPasswords::RequiredGroupId)
+                            .to(Groups::Table, Groups::GroupId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    ),
+            ),
+        )
+        .await?;
+    Ok(transaction)
+}
+
+async fn migrate_to_v23(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    transaction
+        .execute(
+            builder.build(
+                Table::alter().table(Users::Table).add_column(
+                    ColumnDef::new(Users::IsServiceAccount)
+                        .boolean()
+                        .not_null()
+                        .default(false),
+                ),
+            ),
+        )
+        .await?;
+    transaction
+        .execute(
+            builder.build(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(ColumnDef::new(Users::AllowedSources).text()),
+            ),
+        )
+        .await?;
+    Ok(transaction)
+}
+
+async fn migrate_to_v24(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    transaction
+        .execute(
+            builder.build(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(ColumnDef::new(Users::TotpLastStep).big_integer()),
+            ),
+        )
+        .await?;
+    transaction
+        .execute(
+            builder.build(
+                Table::alter().table(Users::Table).add_column(
+                    ColumnDef::new(Users::MfaFailedAttempts)
+                        .integer()
+                        .not_null()
+                        .default(0),
+                ),
+            ),
+        )
+        .await?;
+    transaction
+        .execute(
+            builder.build(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(ColumnDef::new(Users::MfaLockedUntil).date_time()),
+            ),
+        )
+        .await?;
+    Ok(transaction)
+}
+
+async fn migrate_to_v25(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    // The users without an email, like the service accounts, get a NULL lowercase email, which the
+    // unique index ignores. The unique index on the raw email would still make them collide, and
+    // the case-insensitive one already covers it.
+    for index in ["unique-user-email", "unique-user-lower-email"] {
+        transaction
+            .execute(builder.build(Index::drop().name(index).table(Users::Table)))
+            .await?;
+    }
+    let transaction = replace_column(
+        transaction,
+        Users::Table,
+        Users::LowercaseEmail,
+        ColumnDef::new(Users::LowercaseEmail)
+            .string_len(255)
+            .to_owned(),
+        [builder.build(
+            Query::update()
+                .table(Users::Table)
+                .value(Users::LowercaseEmail, Option::<String>::None)
+                .cond_where(Expr::col(Users::Email).eq("")),
+        )],
+    )
+    .await?;
+    transaction
+        .execute(
+            builder.build(
+                Index::create()
+                    .if_not_exists()
+                    .name("unique-user-lower-email")
+                    .table(Users::Table)
+                    .col(Users::LowercaseEmail)
+                    .unique(),
+            ),
+        )
+        .await?;
+    Ok(transaction)
+}
+
+async fn migrate_to_v26(transaction: DatabaseTransaction) -> Result<DatabaseTransaction, DbErr> {
+    let builder = transaction.get_database_backend();
+    // The existing plain text secrets are encrypted on startup, since the migrations don't have
+    // access to the server key.
+    transaction
+        .execute(
+            builder.build(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(ColumnDef::new(Users::EncryptedTotpSecret).blob()),
+            ),
+        )
+        .await?;
+    Ok(transaction)
+}
+
 // This is needed to make an array of async functions.
 macro_rules! to_sync {
     ($l:ident) => {
@@ -1142,6 +1955,22 @@ pub(crate) async fn migrate_from_version(
         to_sync!(migrate_to_v8),
         to_sync!(migrate_to_v9),
         to_sync!(migrate_to_v10),
+        to_sync!(migrate_to_v11),
+        to_sync!(migrate_to_v12),
+        to_sync!(migrate_to_v13),
+        to_sync!(migrate_to_v14),
+        to_sync!(migrate_to_v15),
+        to_sync!(migrate_to_v16),
+        to_sync!(migrate_to_v17),
+        to_sync!(migrate_to_v18),
+        to_sync!(migrate_to_v19),
+        to_sync!(migrate_to_v20),
+        to_sync!(migrate_to_v21),
+        to_sync!(migrate_to_v22),
+        to_sync!(migrate_to_v23),
+        to_sync!(migrate_to_v24),
+        to_sync!(migrate_to_v25),
+        to_sync!(migrate_to_v26),
     ];
     assert_eq!(migrations.len(), (LAST_SCHEMA_VERSION.0 - 1) as usize);
     for migration in 2..=last_version.0 {
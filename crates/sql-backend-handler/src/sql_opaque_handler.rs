@@ -1,15 +1,23 @@
-use crate::SqlBackendHandler;
+use crate::{
+    SqlBackendHandler, allowed_sources::is_source_allowed, legacy_password::legacy_password_matches,
+};
 use async_trait::async_trait;
 use base64::Engine;
 use lldap_auth::opaque;
-use lldap_domain::types::UserId;
-use lldap_domain_handlers::handler::{BindRequest, LoginHandler};
+use lldap_domain::types::{LegacyPasswordHash, UserId};
+use lldap_domain_handlers::handler::{
+    BindRequest, BindResult, BindSource, LoginHandler, PasswordState,
+};
 use lldap_domain_model::{
     error::{DomainError, Result},
-    model::{self, UserColumn},
+    model::{self, PasswordHistoryColumn, UserColumn},
 };
 use lldap_opaque_handler::{OpaqueHandler, login, registration};
-use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait, QuerySelect};
+use lldap_validation::{breached_passwords::BREACHED_PASSWORD_ERROR, password::validate_password};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+    sea_query::Expr,
+};
 use secstr::SecUtf8;
 use tracing::{debug, info, instrument, warn};
 
@@ -43,12 +51,70 @@ fn passwords_match(
 }
 
 impl SqlBackendHandler {
-    fn get_orion_secret_key(&self) -> Result<orion::aead::SecretKey> {
+    pub(crate) fn get_orion_secret_key(&self) -> Result<orion::aead::SecretKey> {
         Ok(orion::aead::SecretKey::from_slice(
             self.opaque_setup.keypair().private(),
         )?)
     }
 
+    /// Keyed hash of a secret (password fingerprint, recovery code), so that the stored hashes
+    /// can't be used to bruteforce the secrets without the server key.
+    pub(crate) fn keyed_hash(&self, secret: &[u8]) -> Result<Vec<u8>> {
+        let key = orion::auth::SecretKey::from_slice(self.opaque_setup.keypair().private())?;
+        Ok(orion::auth::authenticate(&key, secret)?
+            .unprotected_as_bytes()
+            .to_vec())
+    }
+
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str()))]
+    async fn is_password_in_history(
+        &self,
+        user_id: &UserId,
+        fingerprint_hash: &[u8],
+    ) -> Result<bool> {
+        Ok(model::PasswordHistory::find()
+            .filter(PasswordHistoryColumn::UserId.eq(user_id))
+            .order_by_desc(PasswordHistoryColumn::PasswordHistoryId)
+            .limit(self.password_policy.history_size as u64)
+            .all(&self.sql_pool)
+            .await?
+            .into_iter()
+            .any(|entry| entry.fingerprint_hash == fingerprint_hash))
+    }
+
+    /// Adds the new password to the history, and forgets the ones that are too old to matter.
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str()))]
+    async fn add_password_to_history(
+        &self,
+        user_id: &UserId,
+        fingerprint_hash: Vec<u8>,
+    ) -> Result<()> {
+        model::password_history::ActiveModel {
+            user_id: ActiveValue::Set(user_id.clone()),
+            fingerprint_hash: ActiveValue::Set(fingerprint_hash),
+            creation_date: ActiveValue::Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .insert(&self.sql_pool)
+        .await?;
+        let expired_entries = model::PasswordHistory::find()
+            .select_only()
+            .column(PasswordHistoryColumn::PasswordHistoryId)
+            .filter(PasswordHistoryColumn::UserId.eq(user_id))
+            .order_by_desc(PasswordHistoryColumn::PasswordHistoryId)
+            .offset(self.password_policy.history_size as u64)
+            .into_tuple::<i32>()
+            .all(&self.sql_pool)
+            .await?;
+        if !expired_entries.is_empty() {
+            model::PasswordHistory::delete_many()
+                .filter(PasswordHistoryColumn::PasswordHistoryId.is_in(expired_entries))
+                .exec(&self.sql_pool)
+                .await?;
+        }
+        Ok(())
+    }
+
     #[instrument(skip(self), level = "debug", err)]
     async fn get_password_file_for_user(&self, user_id: UserId) -> Result<Option<Vec<u8>>> {
         // Fetch the previously registered password file from the DB.
@@ -60,26 +126,268 @@ impl SqlBackendHandler {
             .await?
             .and_then(|u| u.0))
     }
+
+    #[instrument(skip(self), level = "debug", err)]
+    async fn get_legacy_password_hash_for_user(
+        &self,
+        user_id: UserId,
+    ) -> Result<Option<LegacyPasswordHash>> {
+        Ok(model::User::find_by_id(user_id)
+            .select_only()
+            .column(UserColumn::LegacyPasswordHash)
+            .into_tuple::<(Option<LegacyPasswordHash>,)>()
+            .one(&self.sql_pool)
+            .await?
+            .and_then(|u| u.0))
+    }
+
+    /// Whether the user exists and is either disabled or expired.
+    #[instrument(skip(self), level = "debug", err, ret)]
+    pub(crate) async fn is_user_locked_out(&self, user_id: &UserId) -> Result<bool> {
+        let now = chrono::Utc::now().naive_utc();
+        Ok(model::User::find_by_id(user_id.clone())
+            .select_only()
+            .column(UserColumn::Disabled)
+            .column(UserColumn::ExpirationDate)
+            .into_tuple::<(bool, Option<chrono::NaiveDateTime>)>()
+            .one(&self.sql_pool)
+            .await?
+            .is_some_and(|(disabled, expiration_date)| {
+                disabled || expiration_date.is_some_and(|d| d <= now)
+            }))
+    }
+
+    #[instrument(skip(self), level = "debug", err, ret)]
+    async fn is_service_account(&self, user_id: &UserId) -> Result<bool> {
+        Ok(model::User::find_by_id(user_id.clone())
+            .select_only()
+            .column(UserColumn::IsServiceAccount)
+            .into_tuple::<bool>()
+            .one(&self.sql_pool)
+            .await?
+            .unwrap_or(false))
+    }
+
+    /// Service accounts can only bind over LDAP or from the non-interactive HTTP clients, from
+    /// their allowed networks. People can log in from anywhere.
+    #[instrument(skip(self), level = "debug", err, ret)]
+    async fn is_bind_source_allowed(&self, user_id: &UserId, source: BindSource) -> Result<bool> {
+        let Some((true, allowed_sources)) = model::User::find_by_id(user_id.clone())
+            .select_only()
+            .column(UserColumn::IsServiceAccount)
+            .column(UserColumn::AllowedSources)
+            .into_tuple::<(bool, Option<String>)>()
+            .one(&self.sql_pool)
+            .await?
+        else {
+            return Ok(true);
+        };
+        Ok(match source {
+            BindSource::Http => false,
+            BindSource::Ldap(client_address) | BindSource::HttpAppPassword(client_address) => {
+                let allowed_sources = allowed_sources
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>();
+                is_source_allowed(&allowed_sources, client_address)
+            }
+        })
+    }
+
+    #[instrument(skip(self), level = "debug", err, ret)]
+    async fn compute_password_state(&self, user_id: &UserId) -> Result<PasswordState> {
+        let (must_change_password, password_modified_date) =
+            model::User::find_by_id(user_id.clone())
+                .select_only()
+                .column(UserColumn::MustChangePassword)
+                .column(UserColumn::PasswordModifiedDate)
+                .into_tuple::<(bool, Option<chrono::NaiveDateTime>)>()
+                .one(&self.sql_pool)
+                .await?
+                .ok_or_else(|| DomainError::EntityNotFound(user_id.to_string()))?;
+        if must_change_password {
+            return Ok(PasswordState::MustChange);
+        }
+        let is_expired = self
+            .password_max_age
+            .zip(password_modified_date)
+            .is_some_and(|(max_age, modified_date)| {
+                modified_date + max_age <= chrono::Utc::now().naive_utc()
+            });
+        Ok(if is_expired {
+            PasswordState::Expired
+        } else {
+            PasswordState::Valid
+        })
+    }
+
+    async fn get_bind_result(&self, user_id: &UserId, app_password: bool) -> Result<BindResult> {
+        Ok(BindResult {
+            password_state: self.compute_password_state(user_id).await?,
+            app_password,
+        })
+    }
+
+    /// Checks the password against the hash imported from another directory, and if it matches,
+    /// registers it with OPAQUE, which discards the imported hash.
+    #[instrument(skip_all, level = "debug", err)]
+    async fn bind_with_legacy_password_hash(
+        &self,
+        request: &BindRequest,
+        hash: LegacyPasswordHash,
+    ) -> Result<bool> {
+        let is_match = self
+            .run_password_hashing({
+                let password = request.password.clone();
+                move || legacy_password_matches(&hash, &password)
+            })
+            .await?;
+        if is_match {
+            info!(
+                r#"Upgrading the imported password hash of "{}" to OPAQUE"#,
+                &request.name
+            );
+            let policy_check = self
+                .check_password_policy(&request.name, &request.password)
+                .await;
+            match register_password(
+                self,
+                request.name.clone(),
+                &SecUtf8::from(request.password.as_str()),
+            )
+            .await
+            {
+                Ok(()) => {
+                    // The imported password was never checked against the policy: let the user
+                    // in, but only to change it.
+                    if let Err(e) = policy_check {
+                        info!(
+                            r#"The imported password of "{}" has to be changed: {e:#}"#,
+                            &request.name
+                        );
+                        self.require_password_change(&request.name).await?;
+                    }
+                }
+                // The login itself was successful, the upgrade will be retried on the next one.
+                Err(e) => warn!(
+                    r#"Could not upgrade the password hash of "{}": {e:#}"#,
+                    &request.name
+                ),
+            }
+        }
+        Ok(is_match)
+    }
+
+    /// Runs a password hashing operation on the blocking thread pool, to keep the async runtime
+    /// responsive. If too many are already running, waits for one of them to finish.
+    pub(crate) async fn run_password_hashing<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let permit = self
+            .password_hashing_permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| {
+                DomainError::InternalError(format!("Password hashing pool closed: {e}"))
+            })?;
+        let span = tracing::Span::current();
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            span.in_scope(f)
+        })
+        .await
+        .map_err(|e| DomainError::InternalError(format!("Password hashing task failed: {e}")))
+    }
 }
 
 #[async_trait]
 impl LoginHandler for SqlBackendHandler {
     #[instrument(skip_all, level = "debug", err)]
-    async fn bind(&self, request: BindRequest) -> Result<()> {
+    async fn bind(&self, request: BindRequest) -> Result<BindResult> {
+        if self.is_user_locked_out(&request.name).await? {
+            info!(
+                r#"Login attempt for disabled or expired user "{}""#,
+                &request.name
+            );
+            return Err(DomainError::AuthenticationError(format!(
+                r#"for user "{}""#,
+                request.name
+            )));
+        }
+        if !self
+            .is_bind_source_allowed(&request.name, request.source)
+            .await?
+        {
+            info!(
+                r#"Login attempt for service account "{}" from a source that is not allowed: {:?}"#,
+                &request.name, request.source
+            );
+            return Err(DomainError::AuthenticationError(format!(
+                r#"for user "{}""#,
+                request.name
+            )));
+        }
+        // Checked first: it's much cheaper than the password hashing.
+        if self.bind_with_app_password(&request).await? {
+            info!(r#"Login with an app password for "{}""#, &request.name);
+            return self.get_bind_result(&request.name, true).await;
+        }
+        // Service accounts have no second factor: they use their own password.
+        if matches!(request.source, BindSource::HttpAppPassword(_))
+            && !self.is_service_account(&request.name).await?
+        {
+            info!(
+                r#"Login attempt for "{}" without an app password"#,
+                &request.name
+            );
+            return Err(DomainError::AuthenticationError(format!(
+                r#"for user "{}""#,
+                request.name
+            )));
+        }
         if let Some(password_hash) = self
             .get_password_file_for_user(request.name.clone())
             .await?
         {
             info!(r#"Login attempt for "{}""#, &request.name);
-            if passwords_match(
-                &password_hash,
-                &request.password,
-                &self.opaque_setup,
-                &request.name,
-            )
-            .is_ok()
-            {
-                return Ok(());
+            if self.bind_cache.as_ref().is_some_and(|cache| {
+                cache.contains(&request.name, &password_hash, &request.password)
+            }) {
+                debug!(
+                    r#"Credentials of "{}" found in the bind cache"#,
+                    &request.name
+                );
+                return self.get_bind_result(&request.name, false).await;
+            }
+            let is_match = self
+                .run_password_hashing({
+                    let password_hash = password_hash.clone();
+                    let password = request.password.clone();
+                    let opaque_setup = self.opaque_setup.clone();
+                    let name = request.name.clone();
+                    move || passwords_match(&password_hash, &password, &opaque_setup, &name).is_ok()
+                })
+                .await?;
+            if is_match {
+                if let Some(cache) = &self.bind_cache {
+                    cache.insert(&request.name, &password_hash, &request.password);
+                }
+                return self.get_bind_result(&request.name, false).await;
+            }
+        } else if let Some(hash) = self
+            .get_legacy_password_hash_for_user(request.name.clone())
+            .await?
+        {
+            info!(
+                r#"Login attempt for "{}" with an imported hash"#,
+                &request.name
+            );
+            if self.bind_with_legacy_password_hash(&request, hash).await? {
+                return self.get_bind_result(&request.name, false).await;
             }
         } else {
             debug!(
@@ -92,6 +400,77 @@ impl LoginHandler for SqlBackendHandler {
             request.name
         )))
     }
+
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str()))]
+    async fn get_password_state(&self, user_id: &UserId) -> Result<PasswordState> {
+        self.compute_password_state(user_id).await
+    }
+
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str()))]
+    async fn require_password_change(&self, user_id: &UserId) -> Result<()> {
+        let res = model::User::update_many()
+            .col_expr(UserColumn::MustChangePassword, Expr::value(true))
+            .filter(UserColumn::UserId.eq(user_id))
+            .exec(&self.sql_pool)
+            .await?;
+        if res.rows_affected == 0 {
+            return Err(DomainError::EntityNotFound(format!(
+                "No such user: '{user_id}'"
+            )));
+        }
+        if let Some(cache) = &self.bind_cache {
+            cache.invalidate(user_id);
+        }
+        info!(r#"User "{}" has to change their password"#, user_id);
+        Ok(())
+    }
+
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str()))]
+    async fn check_password_policy(&self, user_id: &UserId, password: &str) -> Result<()> {
+        let (lowercase_email, display_name) = model::User::find_by_id(user_id.clone())
+            .select_only()
+            .column(UserColumn::LowercaseEmail)
+            .column(UserColumn::DisplayName)
+            .into_tuple::<(Option<String>, Option<String>)>()
+            .one(&self.sql_pool)
+            .await?
+            .ok_or_else(|| DomainError::EntityNotFound(format!("No such user: '{user_id}'")))?;
+        let lowercase_email = lowercase_email.unwrap_or_default();
+        let email_local_part = lowercase_email.split('@').next().unwrap_or_default();
+        let mut errors = validate_password(
+            &self.password_policy,
+            password,
+            &[
+                user_id.as_str(),
+                &lowercase_email,
+                email_local_part,
+                display_name.as_deref().unwrap_or_default(),
+            ],
+        )
+        .err()
+        .unwrap_or_default();
+        if let Some(breached_passwords) = self.breached_passwords.clone() {
+            let password = password.to_owned();
+            let is_breached = tokio::task::spawn_blocking(move || {
+                breached_passwords.contains_password(&password)
+            })
+            .await
+            .map_err(|e| {
+                DomainError::InternalError(format!("Breached password check failed: {e}"))
+            })?
+            .map_err(|e| {
+                DomainError::InternalError(format!("Could not read the breached passwords: {e}"))
+            })?;
+            if is_breached {
+                errors.push(BREACHED_PASSWORD_ERROR.to_string());
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(DomainError::PasswordPolicyError(errors.join(", ")))
+        }
+    }
 }
 
 #[async_trait]
@@ -157,6 +536,29 @@ impl OpaqueHandler for SqlOpaqueHandler {
                 return Err(e.into());
             }
         };
+        if self.is_user_locked_out(&username).await? {
+            info!(
+                r#"OPAQUE login attempt for disabled or expired user "{}""#,
+                &username
+            );
+            return Err(DomainError::AuthenticationError(format!(
+                r#"for user "{}""#,
+                username
+            )));
+        }
+        if !self
+            .is_bind_source_allowed(&username, BindSource::Http)
+            .await?
+        {
+            info!(
+                r#"OPAQUE login attempt for service account "{}""#,
+                &username
+            );
+            return Err(DomainError::AuthenticationError(format!(
+                r#"for user "{}""#,
+                username
+            )));
+        }
 
         Ok(username)
     }
@@ -176,10 +578,18 @@ impl OpaqueHandler for SqlOpaqueHandler {
         let server_data = registration::ServerData {
             username: request.username,
         };
+        let password_history_salt = if self.password_policy.history_size > 0 {
+            Some(self.keyed_hash(
+                format!("lldap_password_history:{}", server_data.username.as_str()).as_bytes(),
+            )?)
+        } else {
+            None
+        };
         let encrypted_state = orion::aead::seal(&secret_key, &bincode::serialize(&server_data)?)?;
         Ok(registration::ServerRegistrationStartResponse {
             server_data: base64::engine::general_purpose::STANDARD.encode(encrypted_state),
             registration_response: start_response.message,
+            password_history_salt,
         })
     }
 
@@ -187,24 +597,54 @@ impl OpaqueHandler for SqlOpaqueHandler {
     async fn registration_finish(
         &self,
         request: registration::ClientRegistrationFinishRequest,
-    ) -> Result<()> {
+    ) -> Result<UserId> {
         let secret_key = self.get_orion_secret_key()?;
         let registration::ServerData { username } = bincode::deserialize(&orion::aead::open(
             &secret_key,
             &base64::engine::general_purpose::STANDARD.decode(&request.server_data)?,
         )?)?;
 
+        let fingerprint_hash = if self.password_policy.history_size > 0 {
+            let Some(fingerprint) = request.password_fingerprint else {
+                return Err(DomainError::PasswordPolicyError(
+                    "The password history is enabled, the password fingerprint is required"
+                        .to_string(),
+                ));
+            };
+            Some(self.keyed_hash(&fingerprint)?)
+        } else {
+            None
+        };
+        let is_reused = match &fingerprint_hash {
+            Some(hash) => self.is_password_in_history(&username, hash).await?,
+            None => false,
+        };
+        if is_reused {
+            return Err(DomainError::PasswordPolicyError(format!(
+                "The password must not be one of the last {} passwords",
+                self.password_policy.history_size
+            )));
+        }
         let password_file =
             opaque::server::registration::get_password_file(request.registration_upload);
         // Set the user password to the new password.
         let user_update = model::users::ActiveModel {
             user_id: ActiveValue::Set(username.clone()),
             password_hash: ActiveValue::Set(Some(password_file.serialize())),
+            legacy_password_hash: ActiveValue::Set(None),
+            password_modified_date: ActiveValue::Set(Some(chrono::Utc::now().naive_utc())),
+            must_change_password: ActiveValue::Set(false),
             ..Default::default()
         };
         user_update.update(&self.sql_pool).await?;
+        if let Some(hash) = fingerprint_hash {
+            self.add_password_to_history(&username, hash).await?;
+        }
+        if let Some(cache) = &self.bind_cache {
+            cache.invalidate(&username);
+        }
         info!(r#"Successfully (re)set password for "{}""#, &username);
-        Ok(())
+        Ok(username)
     }
 }
 
@@ -221,21 +661,34 @@ pub async fn register_password(
         opaque::client::registration::start_registration(password.unsecure().as_bytes(), &mut rng)?;
     let start_response = opaque_handler
         .registration_start(ClientRegistrationStartRequest {
-            username,
+            username: username.clone(),
             registration_start_request: registration_start.message,
         })
         .await?;
-    let registration_finish = opaque::client::registration::finish_registration(
-        registration_start.state,
-        start_response.registration_response,
-        &mut rng,
-    )?;
+    // Hashing the password is expensive.
+    let password = password.clone();
+    let (registration_finish, password_fingerprint) = opaque_handler
+        .run_password_hashing(move || -> Result<_> {
+            let registration_finish = opaque::client::registration::finish_registration(
+                registration_start.state,
+                start_response.registration_response,
+                &mut rand::rngs::OsRng,
+            )?;
+            let password_fingerprint = start_response
+                .password_history_salt
+                .map(|salt| opaque::password_fingerprint(&salt, password.unsecure().as_bytes()))
+                .transpose()?;
+            Ok((registration_finish, password_fingerprint))
+        })
+        .await??;
     opaque_handler
         .registration_finish(ClientRegistrationFinishRequest {
             server_data: start_response.server_data,
             registration_upload: registration_finish.message,
+            password_fingerprint,
         })
-        .await
+        .await?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -246,6 +699,9 @@ mod tests {
     use crate::sql_backend_handler::tests::{
         get_initialized_db, insert_user, insert_user_no_password,
     };
+    use lldap_domain::requests::CreateUserRequest;
+    use lldap_domain_handlers::handler::UserBackendHandler;
+    use lldap_validation::{breached_passwords::BreachedPasswordList, password::PasswordPolicy};
 
     async fn attempt_login(
         opaque_handler: &SqlOpaqueHandler,
@@ -307,6 +763,7 @@ mod tests {
             .bind(BindRequest {
                 name: UserId::new("bob"),
                 password: "bob00".to_string(),
+                source: BindSource::Http,
             })
             .await
             .unwrap();
@@ -314,6 +771,7 @@ mod tests {
             .bind(BindRequest {
                 name: UserId::new("andrew"),
                 password: "bob00".to_string(),
+                source: BindSource::Http,
             })
             .await
             .unwrap_err();
@@ -321,11 +779,454 @@ mod tests {
             .bind(BindRequest {
                 name: UserId::new("bob"),
                 password: "wrong_password".to_string(),
+                source: BindSource::Http,
             })
             .await
             .unwrap_err();
     }
 
+    #[tokio::test]
+    async fn test_bind_service_account() {
+        let sql_pool = get_initialized_db().await;
+        let handler = SqlOpaqueHandler::new(generate_random_private_key(), sql_pool.clone());
+        handler
+            .create_user(CreateUserRequest {
+                user_id: UserId::new("backup"),
+                is_service_account: true,
+                allowed_sources: vec!["10.0.0.0/8".to_owned()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        register_password(
+            &handler,
+            UserId::new("backup"),
+            &secstr::SecUtf8::from("backup00"),
+        )
+        .await
+        .unwrap();
+        let bind = |source: BindSource| {
+            handler.bind(BindRequest {
+                name: UserId::new("backup"),
+                password: "backup00".to_string(),
+                source,
+            })
+        };
+
+        bind(BindSource::Ldap(Some("10.1.2.3".parse().unwrap())))
+            .await
+            .unwrap();
+        bind(BindSource::Ldap(Some("192.168.1.2".parse().unwrap())))
+            .await
+            .unwrap_err();
+        bind(BindSource::Ldap(None)).await.unwrap_err();
+        // The non-interactive HTTP clients, like sshd fetching the authorized keys, take the
+        // service account's own password.
+        bind(BindSource::HttpAppPassword(Some(
+            "10.1.2.3".parse().unwrap(),
+        )))
+        .await
+        .unwrap();
+        bind(BindSource::HttpAppPassword(Some(
+            "192.168.1.2".parse().unwrap(),
+        )))
+        .await
+        .unwrap_err();
+        // No web login.
+        bind(BindSource::Http).await.unwrap_err();
+        attempt_login(&handler, "backup", "backup00")
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_bind_cache() {
+        let sql_pool = get_initialized_db().await;
+        let handler = SqlOpaqueHandler::new(generate_random_private_key(), sql_pool.clone())
+            .with_bind_cache(std::time::Duration::from_secs(60));
+        insert_user(&handler, "bob", "bob00").await;
+        let bind = |password: &str| {
+            handler.bind(BindRequest {
+                name: UserId::new("bob"),
+                password: password.to_string(),
+                source: BindSource::Http,
+            })
+        };
+
+        bind("bob00").await.unwrap();
+        // Served from the cache.
+        bind("bob00").await.unwrap();
+        bind("wrong_password").await.unwrap_err();
+        register_password(
+            &handler,
+            UserId::new("bob"),
+            &secstr::SecUtf8::from("bob01"),
+        )
+        .await
+        .unwrap();
+        bind("bob00").await.unwrap_err();
+        bind("bob01").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bind_cache_invalidated_on_account_changes() {
+        let sql_pool = get_initialized_db().await;
+        let handler = SqlOpaqueHandler::new(generate_random_private_key(), sql_pool.clone())
+            .with_bind_cache(std::time::Duration::from_secs(60));
+        insert_user(&handler, "bob", "bob00").await;
+        let bob = UserId::new("bob");
+        let bind = || {
+            handler.bind(BindRequest {
+                name: bob.clone(),
+                password: "bob00".to_string(),
+                source: BindSource::Http,
+            })
+        };
+        let password_file = handler
+            .get_password_file_for_user(bob.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        let is_cached = || {
+            handler
+                .bind_cache
+                .as_ref()
+                .unwrap()
+                .contains(&bob, &password_file, "bob00")
+        };
+
+        bind().await.unwrap();
+        assert!(is_cached());
+        handler.set_user_disabled(&bob, true).await.unwrap();
+        assert!(!is_cached());
+        bind().await.unwrap_err();
+
+        handler.set_user_disabled(&bob, false).await.unwrap();
+        bind().await.unwrap();
+        assert!(is_cached());
+        let yesterday = chrono::Utc::now().naive_utc() - chrono::Duration::days(1);
+        handler
+            .set_user_expiration_date(&bob, Some(yesterday))
+            .await
+            .unwrap();
+        assert!(!is_cached());
+        bind().await.unwrap_err();
+
+        handler.set_user_expiration_date(&bob, None).await.unwrap();
+        bind().await.unwrap();
+        assert!(is_cached());
+        handler.require_password_change(&bob).await.unwrap();
+        assert!(!is_cached());
+
+        bind().await.unwrap();
+        assert!(is_cached());
+        handler.delete_user(&bob).await.unwrap();
+        assert!(!is_cached());
+    }
+
+    #[tokio::test]
+    async fn test_bind_upgrades_legacy_password_hash() {
+        let sql_pool = get_initialized_db().await;
+        let handler = SqlOpaqueHandler::new(generate_random_private_key(), sql_pool.clone());
+        handler
+            .create_user(CreateUserRequest {
+                user_id: UserId::new("bob"),
+                email: "bob@bob.bob".into(),
+                legacy_password_hash: Some(
+                    LegacyPasswordHash::try_from("{SSHA}UC5tunSkhtN5xi9e7qcCsXKJNjBzYWx0").unwrap(),
+                ),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let bind = |password: &str| {
+            handler.bind(BindRequest {
+                name: UserId::new("bob"),
+                password: password.to_string(),
+                source: BindSource::Http,
+            })
+        };
+
+        bind("wrong_password").await.unwrap_err();
+        // OPAQUE logins need a registered password.
+        attempt_login(&handler, "bob", "bob00").await.unwrap_err();
+        bind("bob00").await.unwrap();
+        assert!(
+            handler
+                .get_legacy_password_hash_for_user(UserId::new("bob"))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        attempt_login(&handler, "bob", "bob00").await.unwrap();
+        bind("bob00").await.unwrap();
+        bind("wrong_password").await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_disabled_user_cannot_log_in() {
+        let sql_pool = get_initialized_db().await;
+        let handler = SqlOpaqueHandler::new(generate_random_private_key(), sql_pool.clone())
+            .with_bind_cache(std::time::Duration::from_secs(60));
+        insert_user(&handler, "bob", "bob00").await;
+        let bind = || {
+            handler.bind(BindRequest {
+                name: UserId::new("bob"),
+                password: "bob00".to_string(),
+                source: BindSource::Http,
+            })
+        };
+        bind().await.unwrap();
+        attempt_login(&handler, "bob", "bob00").await.unwrap();
+
+        handler
+            .set_user_disabled(&UserId::new("bob"), true)
+            .await
+            .unwrap();
+        bind().await.unwrap_err();
+        attempt_login(&handler, "bob", "bob00").await.unwrap_err();
+
+        handler
+            .set_user_disabled(&UserId::new("bob"), false)
+            .await
+            .unwrap();
+        bind().await.unwrap();
+        attempt_login(&handler, "bob", "bob00").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_expired_user_cannot_log_in() {
+        let sql_pool = get_initialized_db().await;
+        let handler = SqlOpaqueHandler::new(generate_random_private_key(), sql_pool.clone())
+            .with_bind_cache(std::time::Duration::from_secs(60));
+        insert_user(&handler, "bob", "bob00").await;
+        let bind = || {
+            handler.bind(BindRequest {
+                name: UserId::new("bob"),
+                password: "bob00".to_string(),
+                source: BindSource::Http,
+            })
+        };
+        let now = chrono::Utc::now().naive_utc();
+        handler
+            .set_user_expiration_date(&UserId::new("bob"), Some(now + chrono::Duration::days(1)))
+            .await
+            .unwrap();
+        bind().await.unwrap();
+        attempt_login(&handler, "bob", "bob00").await.unwrap();
+
+        handler
+            .set_user_expiration_date(&UserId::new("bob"), Some(now - chrono::Duration::days(1)))
+            .await
+            .unwrap();
+        bind().await.unwrap_err();
+        attempt_login(&handler, "bob", "bob00").await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_password_must_change_after_reset() {
+        let sql_pool = get_initialized_db().await;
+        let handler = SqlOpaqueHandler::new(generate_random_private_key(), sql_pool.clone())
+            .with_bind_cache(std::time::Duration::from_secs(60));
+        insert_user(&handler, "bob", "bob00").await;
+        let bob = UserId::new("bob");
+        let bind = || {
+            handler.bind(BindRequest {
+                name: UserId::new("bob"),
+                password: "bob00".to_string(),
+                source: BindSource::Http,
+            })
+        };
+        assert_eq!(bind().await.unwrap().password_state, PasswordState::Valid);
+
+        handler.require_password_change(&bob).await.unwrap();
+        // Still reported when the credentials are in the bind cache.
+        assert_eq!(
+            bind().await.unwrap().password_state,
+            PasswordState::MustChange
+        );
+        assert_eq!(
+            handler.get_password_state(&bob).await.unwrap(),
+            PasswordState::MustChange
+        );
+
+        register_password(&handler, bob.clone(), &secstr::SecUtf8::from("bob00"))
+            .await
+            .unwrap();
+        assert_eq!(bind().await.unwrap().password_state, PasswordState::Valid);
+        handler
+            .require_password_change(&UserId::new("andrew"))
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_password_max_age() {
+        let sql_pool = get_initialized_db().await;
+        let handler = SqlOpaqueHandler::new(generate_random_private_key(), sql_pool.clone())
+            .with_password_max_age(chrono::Duration::days(30));
+        insert_user(&handler, "bob", "bob00").await;
+        let bob = UserId::new("bob");
+        let bind = || {
+            handler.bind(BindRequest {
+                name: UserId::new("bob"),
+                password: "bob00".to_string(),
+                source: BindSource::Http,
+            })
+        };
+        assert_eq!(bind().await.unwrap().password_state, PasswordState::Valid);
+
+        model::User::update_many()
+            .col_expr(
+                UserColumn::PasswordModifiedDate,
+                Expr::value(chrono::Utc::now().naive_utc() - chrono::Duration::days(31)),
+            )
+            .filter(UserColumn::UserId.eq(&bob))
+            .exec(&sql_pool)
+            .await
+            .unwrap();
+        assert_eq!(bind().await.unwrap().password_state, PasswordState::Expired);
+        assert_eq!(
+            handler.get_password_state(&bob).await.unwrap(),
+            PasswordState::Expired
+        );
+
+        register_password(&handler, bob.clone(), &secstr::SecUtf8::from("bob00"))
+            .await
+            .unwrap();
+        assert_eq!(bind().await.unwrap().password_state, PasswordState::Valid);
+    }
+
+    #[tokio::test]
+    async fn test_password_history() {
+        let sql_pool = get_initialized_db().await;
+        let handler = SqlOpaqueHandler::new(generate_random_private_key(), sql_pool.clone())
+            .with_password_policy(PasswordPolicy {
+                history_size: 2,
+                ..Default::default()
+            });
+        insert_user_no_password(&handler, "bob").await;
+        let (handler, bob) = (&handler, &UserId::new("bob"));
+        let set_password = |password: &str| {
+            let password = SecUtf8::from(password);
+            async move { register_password(handler, bob.clone(), &password).await }
+        };
+        set_password("password1").await.unwrap();
+        set_password("password2").await.unwrap();
+        assert!(matches!(
+            set_password("password1").await,
+            Err(DomainError::PasswordPolicyError(_))
+        ));
+        set_password("password3").await.unwrap();
+        // Only the last 2 passwords are remembered.
+        set_password("password1").await.unwrap();
+        assert_eq!(
+            model::PasswordHistory::find()
+                .all(&sql_pool)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_password_history_requires_fingerprint() {
+        let sql_pool = get_initialized_db().await;
+        let handler = SqlOpaqueHandler::new(generate_random_private_key(), sql_pool.clone())
+            .with_password_policy(PasswordPolicy {
+                history_size: 2,
+                ..Default::default()
+            });
+        insert_user_no_password(&handler, "bob").await;
+        let mut rng = rand::rngs::OsRng;
+        let registration_start =
+            opaque::client::registration::start_registration(b"password1", &mut rng).unwrap();
+        let start_response = handler
+            .registration_start(registration::ClientRegistrationStartRequest {
+                username: UserId::new("bob"),
+                registration_start_request: registration_start.message,
+            })
+            .await
+            .unwrap();
+        assert!(start_response.password_history_salt.is_some());
+        let registration_finish = opaque::client::registration::finish_registration(
+            registration_start.state,
+            start_response.registration_response,
+            &mut rng,
+        )
+        .unwrap();
+        assert!(matches!(
+            handler
+                .registration_finish(registration::ClientRegistrationFinishRequest {
+                    server_data: start_response.server_data,
+                    registration_upload: registration_finish.message,
+                    password_fingerprint: None,
+                })
+                .await,
+            Err(DomainError::PasswordPolicyError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_check_password_policy() {
+        let sql_pool = get_initialized_db().await;
+        let handler = SqlOpaqueHandler::new(generate_random_private_key(), sql_pool.clone())
+            .with_password_policy(PasswordPolicy {
+                require_digit: true,
+                forbid_user_attributes: true,
+                ..Default::default()
+            });
+        insert_user_no_password(&handler, "bob").await;
+        let bob = UserId::new("bob");
+        handler
+            .check_password_policy(&bob, "correct horse 1")
+            .await
+            .unwrap();
+        assert!(matches!(
+            handler.check_password_policy(&bob, "correct horse").await,
+            Err(DomainError::PasswordPolicyError(_))
+        ));
+        assert!(matches!(
+            handler.check_password_policy(&bob, "i am bob 1").await,
+            Err(DomainError::PasswordPolicyError(_))
+        ));
+        assert!(matches!(
+            handler
+                .check_password_policy(&UserId::new("john"), "correct horse 1")
+                .await,
+            Err(DomainError::EntityNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_check_breached_password() {
+        let sql_pool = get_initialized_db().await;
+        let breached_passwords_file = std::env::temp_dir().join(format!(
+            "lldap_test_check_breached_password_{}.txt",
+            std::process::id()
+        ));
+        // SHA-1 of "password".
+        std::fs::write(
+            &breached_passwords_file,
+            "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\n",
+        )
+        .unwrap();
+        let handler = SqlOpaqueHandler::new(generate_random_private_key(), sql_pool.clone())
+            .with_breached_passwords(BreachedPasswordList::open(&breached_passwords_file).unwrap());
+        insert_user_no_password(&handler, "bob").await;
+        let bob = UserId::new("bob");
+        handler
+            .check_password_policy(&bob, "correct horse")
+            .await
+            .unwrap();
+        assert!(matches!(
+            handler.check_password_policy(&bob, "password").await,
+            Err(DomainError::PasswordPolicyError(e)) if e == BREACHED_PASSWORD_ERROR
+        ));
+        std::fs::remove_file(breached_passwords_file).unwrap();
+    }
+
     #[tokio::test]
     async fn test_user_no_password() {
         let sql_pool = get_initialized_db().await;
@@ -336,6 +1237,7 @@ mod tests {
             .bind(BindRequest {
                 name: UserId::new("bob"),
                 password: "bob00".to_string(),
+                source: BindSource::Http,
             })
             .await
             .unwrap_err();
@@ -3,7 +3,7 @@ use async_trait::async_trait;
 use lldap_domain::{
     requests::CreateAttributeRequest,
     schema::{AttributeList, AttributeSchema, Schema},
-    types::{AttributeName, LdapObjectClass},
+    types::{AttributeName, LdapObjectClass, MatchingRule},
 };
 use lldap_domain_handlers::handler::{ReadSchemaBackendHandler, SchemaBackendHandler};
 use lldap_domain_model::{
@@ -14,6 +14,19 @@ use sea_orm::{
     ActiveModelTrait, DatabaseTransaction, EntityTrait, QueryOrder, Set, TransactionTrait,
 };
 
+fn get_matching_rule(request: &CreateAttributeRequest) -> Result<MatchingRule> {
+    match request.matching_rule {
+        None => Ok(MatchingRule::default_for(request.attribute_type)),
+        Some(rule) if rule.is_compatible_with(request.attribute_type) => Ok(rule),
+        Some(rule) => Err(DomainError::InternalError(format!(
+            "The matching rule {} cannot be used for the {:?} attribute {}",
+            rule.ldap_name(),
+            request.attribute_type,
+            request.name
+        ))),
+    }
+}
+
 #[async_trait]
 impl ReadSchemaBackendHandler for SqlBackendHandler {
     async fn get_schema(&self) -> Result<Schema> {
@@ -29,6 +42,7 @@ impl ReadSchemaBackendHandler for SqlBackendHandler {
 #[async_trait]
 impl SchemaBackendHandler for SqlBackendHandler {
     async fn add_user_attribute(&self, request: CreateAttributeRequest) -> Result<()> {
+        let matching_rule = get_matching_rule(&request)?;
         let new_attribute = model::user_attribute_schema::ActiveModel {
             attribute_name: Set(request.name),
             attribute_type: Set(request.attribute_type),
@@ -36,12 +50,14 @@ impl SchemaBackendHandler for SqlBackendHandler {
             is_user_visible: Set(request.is_visible),
             is_user_editable: Set(request.is_editable),
             is_hardcoded: Set(false),
+            matching_rule: Set(matching_rule),
         };
         new_attribute.insert(&self.sql_pool).await?;
         Ok(())
     }
 
     async fn add_group_attribute(&self, request: CreateAttributeRequest) -> Result<()> {
+        let matching_rule = get_matching_rule(&request)?;
         let new_attribute = model::group_attribute_schema::ActiveModel {
             attribute_name: Set(request.name),
             attribute_type: Set(request.attribute_type),
@@ -49,6 +65,7 @@ impl SchemaBackendHandler for SqlBackendHandler {
             is_group_visible: Set(request.is_visible),
             is_group_editable: Set(request.is_editable),
             is_hardcoded: Set(false),
+            matching_rule: Set(matching_rule),
         };
         new_attribute.insert(&self.sql_pool).await?;
         Ok(())
@@ -178,7 +195,7 @@ mod tests {
     use crate::sql_backend_handler::tests::*;
     use lldap_domain::requests::UpdateUserRequest;
     use lldap_domain::schema::AttributeList;
-    use lldap_domain::types::{Attribute, AttributeType};
+    use lldap_domain::types::{Attribute, AttributeType, MatchingRule};
     use lldap_domain_handlers::handler::{UserBackendHandler, UserRequestFilter};
     use pretty_assertions::assert_eq;
 
@@ -198,6 +215,7 @@ mod tests {
                             is_editable: true,
                             is_hardcoded: true,
                             is_readonly: false,
+                            matching_rule: MatchingRule::OctetStringMatch,
                         },
                         AttributeSchema {
                             name: "first_name".into(),
@@ -207,6 +225,7 @@ mod tests {
                             is_editable: true,
                             is_hardcoded: true,
                             is_readonly: false,
+                            matching_rule: MatchingRule::CaseIgnoreMatch,
                         },
                         AttributeSchema {
                             name: "last_name".into(),
@@ -216,6 +235,7 @@ mod tests {
                             is_editable: true,
                             is_hardcoded: true,
                             is_readonly: false,
+                            matching_rule: MatchingRule::CaseIgnoreMatch,
                         }
                     ]
                 },
@@ -237,6 +257,7 @@ mod tests {
             is_list: true,
             is_visible: false,
             is_editable: false,
+            matching_rule: None,
         };
         fixture
             .handler
@@ -251,6 +272,7 @@ mod tests {
             is_editable: false,
             is_hardcoded: false,
             is_readonly: false,
+            matching_rule: MatchingRule::IntegerMatch,
         };
         assert!(
             fixture
@@ -279,6 +301,45 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_user_attribute_add_with_matching_rule() {
+        let fixture = TestFixture::new().await;
+        fixture
+            .handler
+            .add_user_attribute(CreateAttributeRequest {
+                name: "login".into(),
+                attribute_type: AttributeType::String,
+                is_list: false,
+                is_visible: true,
+                is_editable: false,
+                matching_rule: Some(MatchingRule::CaseExactMatch),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            fixture
+                .handler
+                .get_schema()
+                .await
+                .unwrap()
+                .user_attributes
+                .get_matching_rule(&"login".into()),
+            Some(MatchingRule::CaseExactMatch)
+        );
+        fixture
+            .handler
+            .add_user_attribute(CreateAttributeRequest {
+                name: "shoe_size".into(),
+                attribute_type: AttributeType::Integer,
+                is_list: false,
+                is_visible: true,
+                is_editable: false,
+                matching_rule: Some(MatchingRule::CaseExactMatch),
+            })
+            .await
+            .unwrap_err();
+    }
+
     #[tokio::test]
     async fn test_user_attribute_present_filter() {
         let fixture = TestFixture::new().await;
@@ -288,6 +349,7 @@ mod tests {
             is_list: true,
             is_visible: false,
             is_editable: false,
+            matching_rule: None,
         };
         fixture
             .handler
@@ -325,6 +387,7 @@ mod tests {
             is_list: false,
             is_visible: true,
             is_editable: false,
+            matching_rule: None,
         };
         fixture
             .handler
@@ -339,6 +402,7 @@ mod tests {
             is_editable: false,
             is_hardcoded: false,
             is_readonly: false,
+            matching_rule: MatchingRule::OctetStringMatch,
         };
         assert!(
             fixture
@@ -0,0 +1,351 @@
+use crate::sql_backend_handler::SqlBackendHandler;
+use async_trait::async_trait;
+use lldap_domain::{
+    requests::{CreateSudoRoleRequest, UpdateSudoRoleRequest},
+    types::{SudoRole, SudoRoleId, Uuid},
+};
+use lldap_domain_handlers::handler::{SudoRoleBackendHandler, SudoRoleListerBackendHandler};
+use lldap_domain_model::{
+    error::{DomainError, Result},
+    model::{self, SudoRoleColumn, SudoRoleValuesColumn},
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseTransaction, EntityTrait, QueryFilter, QueryOrder, Set,
+    TransactionTrait,
+};
+use std::collections::HashMap;
+use tracing::instrument;
+
+const SUDO_USER: &str = "sudoUser";
+const SUDO_HOST: &str = "sudoHost";
+const SUDO_COMMAND: &str = "sudoCommand";
+const SUDO_RUN_AS_USER: &str = "sudoRunAsUser";
+const SUDO_RUN_AS_GROUP: &str = "sudoRunAsGroup";
+const SUDO_OPTION: &str = "sudoOption";
+
+fn get_values_mut<'a>(role: &'a mut SudoRole, attribute_name: &str) -> Option<&'a mut Vec<String>> {
+    match attribute_name {
+        SUDO_USER => Some(&mut role.users),
+        SUDO_HOST => Some(&mut role.hosts),
+        SUDO_COMMAND => Some(&mut role.commands),
+        SUDO_RUN_AS_USER => Some(&mut role.run_as_users),
+        SUDO_RUN_AS_GROUP => Some(&mut role.run_as_groups),
+        SUDO_OPTION => Some(&mut role.options),
+        _ => None,
+    }
+}
+
+/// Fills the multi-valued attributes of the roles. The values must be sorted by position.
+fn add_values(roles: &mut [SudoRole], values: Vec<model::sudo_role_values::Model>) {
+    let index_by_id: HashMap<SudoRoleId, usize> = roles
+        .iter()
+        .enumerate()
+        .map(|(index, role)| (role.id, index))
+        .collect();
+    for value in values {
+        let Some(&index) = index_by_id.get(&value.sudo_role_id) else {
+            continue;
+        };
+        match get_values_mut(&mut roles[index], &value.attribute_name) {
+            Some(role_values) => role_values.push(value.value),
+            None => tracing::warn!(
+                "Ignoring unknown sudo role attribute {}",
+                &value.attribute_name
+            ),
+        }
+    }
+}
+
+fn sudo_role_not_found(id: SudoRoleId) -> DomainError {
+    DomainError::EntityNotFound(format!("No such sudo role: '{id:?}'"))
+}
+
+impl SqlBackendHandler {
+    async fn check_sudo_role_name_is_free(
+        transaction: &DatabaseTransaction,
+        lowercase_name: &str,
+        id: Option<SudoRoleId>,
+    ) -> Result<()> {
+        let existing = model::SudoRole::find()
+            .filter(SudoRoleColumn::LowercaseName.eq(lowercase_name))
+            .one(transaction)
+            .await?;
+        if existing.is_some_and(|role| Some(role.sudo_role_id) != id) {
+            return Err(DomainError::InternalError(format!(
+                "A sudo role named '{lowercase_name}' already exists"
+            )));
+        }
+        Ok(())
+    }
+
+    async fn set_sudo_role_values(
+        transaction: &DatabaseTransaction,
+        id: SudoRoleId,
+        attribute_name: &str,
+        values: Vec<String>,
+    ) -> Result<()> {
+        model::SudoRoleValues::delete_many()
+            .filter(SudoRoleValuesColumn::SudoRoleId.eq(id))
+            .filter(SudoRoleValuesColumn::AttributeName.eq(attribute_name))
+            .exec(transaction)
+            .await?;
+        if values.is_empty() {
+            return Ok(());
+        }
+        model::SudoRoleValues::insert_many(values.into_iter().enumerate().map(
+            |(position, value)| model::sudo_role_values::ActiveModel {
+                sudo_role_id: Set(id),
+                attribute_name: Set(attribute_name.to_owned()),
+                position: Set(position as i32),
+                value: Set(value),
+            },
+        ))
+        .exec(transaction)
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SudoRoleListerBackendHandler for SqlBackendHandler {
+    #[instrument(skip(self), level = "debug", err)]
+    async fn list_sudo_roles(&self) -> Result<Vec<SudoRole>> {
+        let mut roles: Vec<SudoRole> = model::SudoRole::find()
+            .order_by_asc(SudoRoleColumn::LowercaseName)
+            .all(&self.sql_pool)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        let values = model::SudoRoleValues::find()
+            .order_by_asc(SudoRoleValuesColumn::SudoRoleId)
+            .order_by_asc(SudoRoleValuesColumn::AttributeName)
+            .order_by_asc(SudoRoleValuesColumn::Position)
+            .all(&self.sql_pool)
+            .await?;
+        add_values(&mut roles, values);
+        Ok(roles)
+    }
+}
+
+#[async_trait]
+impl SudoRoleBackendHandler for SqlBackendHandler {
+    #[instrument(skip(self), level = "debug", ret, err)]
+    async fn get_sudo_role(&self, id: SudoRoleId) -> Result<SudoRole> {
+        let role: SudoRole = model::SudoRole::find_by_id(id)
+            .one(&self.sql_pool)
+            .await?
+            .ok_or_else(|| sudo_role_not_found(id))?
+            .into();
+        let values = model::SudoRoleValues::find()
+            .filter(SudoRoleValuesColumn::SudoRoleId.eq(id))
+            .order_by_asc(SudoRoleValuesColumn::AttributeName)
+            .order_by_asc(SudoRoleValuesColumn::Position)
+            .all(&self.sql_pool)
+            .await?;
+        let mut roles = [role];
+        add_values(&mut roles, values);
+        let [role] = roles;
+        Ok(role)
+    }
+
+    #[instrument(skip(self), level = "debug", ret, err)]
+    async fn create_sudo_role(&self, request: CreateSudoRoleRequest) -> Result<SudoRoleId> {
+        let now = chrono::Utc::now().naive_utc();
+        let uuid = Uuid::from_name_and_date(&request.name, &now);
+        let lowercase_name = request.name.to_lowercase();
+        let new_role = model::sudo_roles::ActiveModel {
+            name: Set(request.name),
+            lowercase_name: Set(lowercase_name.clone()),
+            description: Set(request.description.filter(|d| !d.is_empty())),
+            sudo_order: Set(request.order),
+            creation_date: Set(now),
+            uuid: Set(uuid),
+            ..Default::default()
+        };
+        Ok(self
+            .sql_pool
+            .transaction::<_, SudoRoleId, DomainError>(|transaction| {
+                Box::pin(async move {
+                    Self::check_sudo_role_name_is_free(transaction, &lowercase_name, None).await?;
+                    let id = new_role.insert(transaction).await?.sudo_role_id;
+                    for (attribute_name, values) in [
+                        (SUDO_USER, request.users),
+                        (SUDO_HOST, request.hosts),
+                        (SUDO_COMMAND, request.commands),
+                        (SUDO_RUN_AS_USER, request.run_as_users),
+                        (SUDO_RUN_AS_GROUP, request.run_as_groups),
+                        (SUDO_OPTION, request.options),
+                    ] {
+                        Self::set_sudo_role_values(transaction, id, attribute_name, values).await?;
+                    }
+                    Ok(id)
+                })
+            })
+            .await?)
+    }
+
+    #[instrument(skip(self), level = "debug", err, fields(id = ?request.id))]
+    async fn update_sudo_role(&self, request: UpdateSudoRoleRequest) -> Result<()> {
+        Ok(self
+            .sql_pool
+            .transaction::<_, (), DomainError>(|transaction| {
+                Box::pin(async move {
+                    let id = request.id;
+                    if model::SudoRole::find_by_id(id)
+                        .one(transaction)
+                        .await?
+                        .is_none()
+                    {
+                        return Err(sudo_role_not_found(id));
+                    }
+                    let mut role = model::sudo_roles::ActiveModel {
+                        sudo_role_id: Set(id),
+                        ..Default::default()
+                    };
+                    if let Some(name) = request.name {
+                        let lowercase_name = name.to_lowercase();
+                        Self::check_sudo_role_name_is_free(transaction, &lowercase_name, Some(id))
+                            .await?;
+                        role.name = Set(name);
+                        role.lowercase_name = Set(lowercase_name);
+                    }
+                    if let Some(description) = request.description {
+                        role.description = Set(Some(description).filter(|d| !d.is_empty()));
+                    }
+                    if let Some(order) = request.order {
+                        role.sudo_order = Set(order);
+                    }
+                    if role.is_changed() {
+                        role.update(transaction).await?;
+                    }
+                    for (attribute_name, values) in [
+                        (SUDO_USER, request.users),
+                        (SUDO_HOST, request.hosts),
+                        (SUDO_COMMAND, request.commands),
+                        (SUDO_RUN_AS_USER, request.run_as_users),
+                        (SUDO_RUN_AS_GROUP, request.run_as_groups),
+                        (SUDO_OPTION, request.options),
+                    ] {
+                        if let Some(values) = values {
+                            Self::set_sudo_role_values(transaction, id, attribute_name, values)
+                                .await?;
+                        }
+                    }
+                    Ok(())
+                })
+            })
+            .await?)
+    }
+
+    #[instrument(skip(self), level = "debug", err)]
+    async fn delete_sudo_role(&self, id: SudoRoleId) -> Result<()> {
+        let res = model::SudoRole::delete_by_id(id)
+            .exec(&self.sql_pool)
+            .await?;
+        if res.rows_affected == 0 {
+            return Err(sudo_role_not_found(id));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql_backend_handler::tests::*;
+    use pretty_assertions::assert_eq;
+
+    async fn insert_sudo_role(handler: &SqlBackendHandler, name: &str) -> SudoRoleId {
+        handler
+            .create_sudo_role(CreateSudoRoleRequest {
+                name: name.to_owned(),
+                users: vec!["%admins".to_owned(), "bob".to_owned()],
+                hosts: vec!["ALL".to_owned()],
+                commands: vec!["ALL".to_owned()],
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_create_and_list_sudo_roles() {
+        let fixture = TestFixture::new().await;
+        let id = insert_sudo_role(&fixture.handler, "Admins").await;
+        insert_sudo_role(&fixture.handler, "backup").await;
+        let roles = fixture.handler.list_sudo_roles().await.unwrap();
+        assert_eq!(
+            roles.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(),
+            vec!["Admins", "backup"]
+        );
+        let role = fixture.handler.get_sudo_role(id).await.unwrap();
+        assert_eq!(role, roles[0]);
+        assert_eq!(role.users, vec!["%admins", "bob"]);
+        assert_eq!(role.hosts, vec!["ALL"]);
+        assert_eq!(role.commands, vec!["ALL"]);
+        assert!(role.options.is_empty());
+        assert_eq!(role.description, None);
+    }
+
+    #[tokio::test]
+    async fn test_create_sudo_role_duplicate_name() {
+        let fixture = TestFixture::new().await;
+        insert_sudo_role(&fixture.handler, "Admins").await;
+        fixture
+            .handler
+            .create_sudo_role(CreateSudoRoleRequest {
+                name: "admins".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_update_sudo_role() {
+        let fixture = TestFixture::new().await;
+        let id = insert_sudo_role(&fixture.handler, "Admins").await;
+        fixture
+            .handler
+            .update_sudo_role(UpdateSudoRoleRequest {
+                id,
+                name: Some("admins".to_owned()),
+                description: Some("Administrators".to_owned()),
+                users: Some(vec!["%wheel".to_owned()]),
+                hosts: None,
+                commands: Some(Vec::new()),
+                run_as_users: None,
+                run_as_groups: None,
+                options: Some(vec!["!authenticate".to_owned()]),
+                order: Some(3),
+            })
+            .await
+            .unwrap();
+        let role = fixture.handler.get_sudo_role(id).await.unwrap();
+        assert_eq!(role.name, "admins");
+        assert_eq!(role.description.as_deref(), Some("Administrators"));
+        assert_eq!(role.users, vec!["%wheel"]);
+        assert_eq!(role.hosts, vec!["ALL"]);
+        assert!(role.commands.is_empty());
+        assert_eq!(role.options, vec!["!authenticate"]);
+        assert_eq!(role.order, 3);
+    }
+
+    #[tokio::test]
+    async fn test_delete_sudo_role() {
+        let fixture = TestFixture::new().await;
+        let id = insert_sudo_role(&fixture.handler, "Admins").await;
+        fixture.handler.delete_sudo_role(id).await.unwrap();
+        assert!(fixture.handler.list_sudo_roles().await.unwrap().is_empty());
+        assert!(
+            model::SudoRoleValues::find()
+                .all(&fixture.handler.sql_pool)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        fixture.handler.delete_sudo_role(id).await.unwrap_err();
+        fixture.handler.get_sudo_role(id).await.unwrap_err();
+    }
+}
@@ -9,7 +9,7 @@ pub type DbConnection = sea_orm::DatabaseConnection;
 #[derive(Copy, PartialEq, Eq, Debug, Clone, PartialOrd, Ord, DeriveValueType)]
 pub struct SchemaVersion(pub i16);
 
-pub const LAST_SCHEMA_VERSION: SchemaVersion = SchemaVersion(10);
+pub const LAST_SCHEMA_VERSION: SchemaVersion = SchemaVersion(26);
 
 #[derive(Copy, PartialEq, Eq, Debug, Clone, PartialOrd, Ord)]
 pub struct PrivateKeyHash(pub [u8; 32]);
@@ -545,6 +545,703 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_migration_to_v12() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(11))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO users (user_id, email, lowercase_email, creation_date, uuid)
+                       VALUES ("bob", "bob@bob.com", "bob@bob.com", "1970-01-01 00:00:00", "a02eaf13-48a7-30f6-a3d4-040ff7c52b04")"#,
+            ))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(sea_orm::Statement::from_sql_and_values(DbBackend::Sqlite,
+                r#"INSERT INTO user_attributes (user_attribute_user_id, user_attribute_name, user_attribute_value)
+                       VALUES ("bob", "first_name", $1), ("bob", "avatar", $2)"#,
+                [Serialized::from("Bob").into(), Serialized::from(&JpegPhoto::for_tests()).into()]),
+            )
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO groups (display_name, lowercase_display_name, creation_date, uuid)
+                       VALUES ("BestGroup", "bestgroup", "1970-01-01 00:00:00", "986765a5-3f03-389e-b47b-536b2d6e1bec")"#,
+            ))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO group_attribute_schema (group_attribute_schema_name, group_attribute_schema_type, group_attribute_schema_is_list, group_attribute_schema_is_group_visible, group_attribute_schema_is_group_editable, group_attribute_schema_is_hardcoded)
+                       VALUES ("aliases", "String", true, true, true, false)"#,
+            ))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(sea_orm::Statement::from_sql_and_values(DbBackend::Sqlite,
+                r#"INSERT INTO group_attributes (group_attribute_group_id, group_attribute_name, group_attribute_value)
+                       VALUES ((SELECT group_id FROM groups WHERE display_name = "BestGroup"), "aliases", $1)"#,
+                [Serialized::from(&vec!["Best", "TopGroup"]).into()]),
+            )
+            .await
+            .unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(11), SchemaVersion(12))
+            .await
+            .unwrap();
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct LowercaseValue {
+            name: String,
+            lowercase_value: Option<String>,
+        }
+        assert_eq!(
+            LowercaseValue::find_by_statement(raw_statement(
+                r#"SELECT user_attribute_name AS name, user_attribute_lowercase_value AS lowercase_value FROM user_attributes ORDER BY user_attribute_name"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap(),
+            vec![
+                LowercaseValue {
+                    name: "avatar".to_owned(),
+                    lowercase_value: None,
+                },
+                LowercaseValue {
+                    name: "first_name".to_owned(),
+                    lowercase_value: Some("\nbob\n".to_owned()),
+                },
+            ]
+        );
+        assert_eq!(
+            LowercaseValue::find_by_statement(raw_statement(
+                r#"SELECT group_attribute_name AS name, group_attribute_lowercase_value AS lowercase_value FROM group_attributes"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap(),
+            vec![LowercaseValue {
+                name: "aliases".to_owned(),
+                lowercase_value: Some("\nbest\ntopgroup\n".to_owned()),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_v13() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(12))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO group_attribute_schema (group_attribute_schema_name, group_attribute_schema_type, group_attribute_schema_is_list, group_attribute_schema_is_group_visible, group_attribute_schema_is_group_editable, group_attribute_schema_is_hardcoded)
+                       VALUES ("gid", "Integer", false, true, true, false)"#,
+            ))
+            .await
+            .unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(12), SchemaVersion(13))
+            .await
+            .unwrap();
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct AttributeMatchingRule {
+            name: String,
+            matching_rule: String,
+        }
+        assert_eq!(
+            AttributeMatchingRule::find_by_statement(raw_statement(
+                r#"SELECT user_attribute_schema_name AS name, user_attribute_schema_matching_rule AS matching_rule FROM user_attribute_schema ORDER BY user_attribute_schema_name"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap(),
+            vec![
+                AttributeMatchingRule {
+                    name: "avatar".to_owned(),
+                    matching_rule: "OctetStringMatch".to_owned(),
+                },
+                AttributeMatchingRule {
+                    name: "first_name".to_owned(),
+                    matching_rule: "CaseIgnoreMatch".to_owned(),
+                },
+                AttributeMatchingRule {
+                    name: "last_name".to_owned(),
+                    matching_rule: "CaseIgnoreMatch".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(
+            AttributeMatchingRule::find_by_statement(raw_statement(
+                r#"SELECT group_attribute_schema_name AS name, group_attribute_schema_matching_rule AS matching_rule FROM group_attribute_schema"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap(),
+            vec![AttributeMatchingRule {
+                name: "gid".to_owned(),
+                matching_rule: "IntegerMatch".to_owned(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_v14() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(14))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO sudo_roles (name, lowercase_name, description, sudo_order, creation_date, uuid)
+                       VALUES ("Admins", "admins", NULL, 1, "1970-01-01 00:00:00", "a02eaf13-48a7-30f6-a3d4-040ff7c52b04")"#,
+            ))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO sudo_role_values (sudo_role_id, attribute_name, position, value)
+                       VALUES (1, "sudoUser", 0, "%admins")"#,
+            ))
+            .await
+            .unwrap();
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct SudoRoleValue {
+            name: String,
+            value: String,
+        }
+        assert_eq!(
+            SudoRoleValue::find_by_statement(raw_statement(
+                r#"SELECT sudo_roles.name AS name, sudo_role_values.value AS value FROM sudo_roles JOIN sudo_role_values ON sudo_roles.sudo_role_id = sudo_role_values.sudo_role_id"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap(),
+            vec![SudoRoleValue {
+                name: "Admins".to_owned(),
+                value: "%admins".to_owned(),
+            }]
+        );
+        sql_pool
+            .execute(raw_statement(r#"DELETE FROM sudo_roles"#))
+            .await
+            .unwrap();
+        assert!(
+            SudoRoleValue::find_by_statement(raw_statement(
+                r#"SELECT "" AS name, value FROM sudo_role_values"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap()
+            .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_v15() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(14))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO users (user_id, email, lowercase_email, display_name, creation_date, uuid)
+                       VALUES ("bob", "bob@bob.com", "bob@bob.com", "", "1970-01-01 00:00:00", "a02eaf13-48a7-30f6-a3d4-040ff7c52b04")"#,
+            ))
+            .await
+            .unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(14), SchemaVersion(15))
+            .await
+            .unwrap();
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct LegacyPasswordHash {
+            legacy_password_hash: Option<String>,
+        }
+        assert_eq!(
+            LegacyPasswordHash::find_by_statement(raw_statement(
+                r#"SELECT legacy_password_hash FROM users"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap(),
+            vec![LegacyPasswordHash {
+                legacy_password_hash: None,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_v16() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(15))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO users (user_id, email, lowercase_email, display_name, creation_date, uuid)
+                       VALUES ("bob", "bob@bob.com", "bob@bob.com", "", "1970-01-01 00:00:00", "a02eaf13-48a7-30f6-a3d4-040ff7c52b04")"#,
+            ))
+            .await
+            .unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(15), SchemaVersion(16))
+            .await
+            .unwrap();
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct Disabled {
+            disabled: bool,
+        }
+        assert_eq!(
+            Disabled::find_by_statement(raw_statement(r#"SELECT disabled FROM users"#,))
+                .all(&sql_pool)
+                .await
+                .unwrap(),
+            vec![Disabled { disabled: false }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_v17() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(16))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO users (user_id, email, lowercase_email, display_name, creation_date, uuid)
+                       VALUES ("bob", "bob@bob.com", "bob@bob.com", "", "1970-01-01 00:00:00", "a02eaf13-48a7-30f6-a3d4-040ff7c52b04")"#,
+            ))
+            .await
+            .unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(16), SchemaVersion(17))
+            .await
+            .unwrap();
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct Expiration {
+            expiration_date: Option<chrono::NaiveDateTime>,
+            expiration_warning_sent: bool,
+        }
+        assert_eq!(
+            Expiration::find_by_statement(raw_statement(
+                r#"SELECT expiration_date, expiration_warning_sent FROM users"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap(),
+            vec![Expiration {
+                expiration_date: None,
+                expiration_warning_sent: false,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_v18() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(17))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO users (user_id, email, lowercase_email, display_name, creation_date, uuid, password_hash)
+                       VALUES ("bob", "bob@bob.com", "bob@bob.com", "", "1970-01-01 00:00:00", "a02eaf13-48a7-30f6-a3d4-040ff7c52b04", X'01'),
+                              ("john", "john@bob.com", "john@bob.com", "", "1970-01-01 00:00:00", "986765a5-3f03-389e-b47b-536b2d6e1bec", NULL)"#,
+            ))
+            .await
+            .unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(17), SchemaVersion(18))
+            .await
+            .unwrap();
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct PasswordState {
+            user_id: String,
+            has_modified_date: bool,
+            must_change_password: bool,
+        }
+        assert_eq!(
+            PasswordState::find_by_statement(raw_statement(
+                r#"SELECT user_id, password_modified_date IS NOT NULL AS has_modified_date, must_change_password FROM users ORDER BY user_id"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap(),
+            vec![
+                PasswordState {
+                    user_id: "bob".to_owned(),
+                    has_modified_date: true,
+                    must_change_password: false,
+                },
+                PasswordState {
+                    user_id: "john".to_owned(),
+                    has_modified_date: false,
+                    must_change_password: false,
+                }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_v19() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(18))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO users (user_id, email, lowercase_email, display_name, creation_date, uuid)
+                       VALUES ("bob", "bob@bob.com", "bob@bob.com", "", "1970-01-01 00:00:00", "a02eaf13-48a7-30f6-a3d4-040ff7c52b04")"#,
+            ))
+            .await
+            .unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(18), SchemaVersion(19))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO password_history (user_id, fingerprint_hash, creation_date)
+                       VALUES ("bob", X'01', "1970-01-01 00:00:00")"#,
+            ))
+            .await
+            .unwrap();
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct Count {
+            count: i64,
+        }
+        assert_eq!(
+            Count::find_by_statement(raw_statement(
+                r#"SELECT COUNT(*) AS count FROM password_history"#,
+            ))
+            .one(&sql_pool)
+            .await
+            .unwrap(),
+            Some(Count { count: 1 })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_v20() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(19))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO users (user_id, email, lowercase_email, display_name, creation_date, uuid)
+                       VALUES ("bob", "bob@bob.com", "bob@bob.com", "", "1970-01-01 00:00:00", "a02eaf13-48a7-30f6-a3d4-040ff7c52b04")"#,
+            ))
+            .await
+            .unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(19), SchemaVersion(20))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO mfa_recovery_codes (user_id, code_hash) VALUES ("bob", X'01')"#,
+            ))
+            .await
+            .unwrap();
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct Count {
+            count: i64,
+        }
+        assert_eq!(
+            Count::find_by_statement(raw_statement(
+                r#"SELECT COUNT(*) AS count FROM mfa_recovery_codes"#,
+            ))
+            .one(&sql_pool)
+            .await
+            .unwrap(),
+            Some(Count { count: 1 })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_v21() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(20))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO users (user_id, email, lowercase_email, display_name, creation_date, uuid)
+                       VALUES ("bob", "bob@bob.com", "bob@bob.com", "", "1970-01-01 00:00:00", "a02eaf13-48a7-30f6-a3d4-040ff7c52b04")"#,
+            ))
+            .await
+            .unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(20), SchemaVersion(21))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO webauthn_credentials (user_id, credential_id, name, passkey, creation_date)
+                       VALUES ("bob", X'01', "YubiKey", "{}", "1970-01-01 00:00:00")"#,
+            ))
+            .await
+            .unwrap();
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct Credential {
+            name: String,
+            last_used_date: Option<chrono::NaiveDateTime>,
+        }
+        assert_eq!(
+            Credential::find_by_statement(raw_statement(
+                r#"SELECT name, last_used_date FROM webauthn_credentials"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap(),
+            vec![Credential {
+                name: "YubiKey".to_owned(),
+                last_used_date: None,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_v22() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(21))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO users (user_id, email, lowercase_email, display_name, creation_date, uuid)
+                       VALUES ("bob", "bob@bob.com", "bob@bob.com", "", "1970-01-01 00:00:00", "a02eaf13-48a7-30f6-a3d4-040ff7c52b04")"#,
+            ))
+            .await
+            .unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(21), SchemaVersion(22))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO app_passwords (user_id, name, password_hash, allowed_sources, creation_date)
+                       VALUES ("bob", "Thunderbird", X'01', "", "1970-01-01 00:00:00")"#,
+            ))
+            .await
+            .unwrap();
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct AppPassword {
+            name: String,
+            required_group_id: Option<i32>,
+            last_used_date: Option<chrono::NaiveDateTime>,
+        }
+        assert_eq!(
+            AppPassword::find_by_statement(raw_statement(
+                r#"SELECT name, required_group_id, last_used_date FROM app_passwords"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap(),
+            vec![AppPassword {
+                name: "Thunderbird".to_owned(),
+                required_group_id: None,
+                last_used_date: None,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_v23() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(22))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO users (user_id, email, lowercase_email, display_name, creation_date, uuid)
+                       VALUES ("bob", "bob@bob.com", "bob@bob.com", "", "1970-01-01 00:00:00", "a02eaf13-48a7-30f6-a3d4-040ff7c52b04")"#,
+            ))
+            .await
+            .unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(22), SchemaVersion(23))
+            .await
+            .unwrap();
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct ServiceAccount {
+            is_service_account: bool,
+            allowed_sources: Option<String>,
+        }
+        assert_eq!(
+            ServiceAccount::find_by_statement(raw_statement(
+                r#"SELECT is_service_account, allowed_sources FROM users"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap(),
+            vec![ServiceAccount {
+                is_service_account: false,
+                allowed_sources: None,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_v24() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(23))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO users (user_id, email, lowercase_email, display_name, creation_date, uuid)
+                       VALUES ("bob", "bob@bob.com", "bob@bob.com", "", "1970-01-01 00:00:00", "a02eaf13-48a7-30f6-a3d4-040ff7c52b04")"#,
+            ))
+            .await
+            .unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(23), SchemaVersion(24))
+            .await
+            .unwrap();
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct MfaState {
+            totp_last_step: Option<i64>,
+            mfa_failed_attempts: i32,
+            mfa_locked_until: Option<chrono::NaiveDateTime>,
+        }
+        assert_eq!(
+            MfaState::find_by_statement(raw_statement(
+                r#"SELECT totp_last_step, mfa_failed_attempts, mfa_locked_until FROM users"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap(),
+            vec![MfaState {
+                totp_last_step: None,
+                mfa_failed_attempts: 0,
+                mfa_locked_until: None,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_v25() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(24))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO users (user_id, email, lowercase_email, display_name, creation_date, uuid, is_service_account)
+                       VALUES ("bob", "Bob@bob.com", "bob@bob.com", "", "1970-01-01 00:00:00", "a02eaf13-48a7-30f6-a3d4-040ff7c52b04", false),
+                              ("backup", "", "backup@service.invalid", "", "1970-01-01 00:00:00", "b02eaf13-48a7-30f6-a3d4-040ff7c52b04", true)"#,
+            ))
+            .await
+            .unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(24), SchemaVersion(25))
+            .await
+            .unwrap();
+        // Several users can go without an email.
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO users (user_id, email, lowercase_email, display_name, creation_date, uuid, is_service_account)
+                       VALUES ("nextcloud", "", NULL, "", "1970-01-01 00:00:00", "c02eaf13-48a7-30f6-a3d4-040ff7c52b04", true)"#,
+            ))
+            .await
+            .unwrap();
+        // The emails are still unique.
+        assert!(
+            sql_pool
+                .execute(raw_statement(
+                    r#"INSERT INTO users (user_id, email, lowercase_email, display_name, creation_date, uuid)
+                       VALUES ("john", "BOB@bob.com", "bob@bob.com", "", "1970-01-01 00:00:00", "d02eaf13-48a7-30f6-a3d4-040ff7c52b04")"#,
+                ))
+                .await
+                .is_err()
+        );
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct Email {
+            user_id: String,
+            lowercase_email: Option<String>,
+        }
+        assert_eq!(
+            Email::find_by_statement(raw_statement(
+                r#"SELECT user_id, lowercase_email FROM users ORDER BY user_id"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap(),
+            vec![
+                Email {
+                    user_id: "backup".to_owned(),
+                    lowercase_email: None,
+                },
+                Email {
+                    user_id: "bob".to_owned(),
+                    lowercase_email: Some("bob@bob.com".to_owned()),
+                },
+                Email {
+                    user_id: "nextcloud".to_owned(),
+                    lowercase_email: None,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration_to_v26() {
+        crate::logging::init_for_tests();
+        let sql_pool = get_in_memory_db().await;
+        upgrade_to_v1(&sql_pool).await.unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(1), SchemaVersion(25))
+            .await
+            .unwrap();
+        sql_pool
+            .execute(raw_statement(
+                r#"INSERT INTO users (user_id, email, lowercase_email, display_name, creation_date, uuid, totp_secret, mfa_type)
+                       VALUES ("bob", "bob@bob.com", "bob@bob.com", "", "1970-01-01 00:00:00", "a02eaf13-48a7-30f6-a3d4-040ff7c52b04", "JBSWY3DPEHPK3PXP", "totp")"#,
+            ))
+            .await
+            .unwrap();
+        migrate_from_version(&sql_pool, SchemaVersion(25), SchemaVersion(26))
+            .await
+            .unwrap();
+        #[derive(FromQueryResult, PartialEq, Eq, Debug)]
+        struct TotpSecrets {
+            totp_secret: Option<String>,
+            encrypted_totp_secret: Option<Vec<u8>>,
+        }
+        // The secret is only encrypted on startup.
+        assert_eq!(
+            TotpSecrets::find_by_statement(raw_statement(
+                r#"SELECT totp_secret, encrypted_totp_secret FROM users"#,
+            ))
+            .all(&sql_pool)
+            .await
+            .unwrap(),
+            vec![TotpSecrets {
+                totp_secret: Some("JBSWY3DPEHPK3PXP".to_owned()),
+                encrypted_totp_secret: None,
+            }]
+        );
+    }
+
     #[tokio::test]
     async fn test_too_high_version() {
         let sql_pool = get_in_memory_db().await;
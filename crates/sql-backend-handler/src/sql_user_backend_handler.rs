@@ -1,11 +1,25 @@
-use crate::sql_backend_handler::SqlBackendHandler;
+use crate::{
+    allowed_sources::normalize_allowed_sources,
+    sql_backend_handler::{
+        STREAM_BATCH_SIZE, SqlBackendHandler, get_attribute_ignore_case_pattern,
+        get_attribute_substring_pattern, get_lowercase_attribute_value,
+    },
+    sql_group_backend_handler::DynamicGroup,
+};
 use async_trait::async_trait;
+use futures_util::{
+    StreamExt, TryFutureExt, TryStreamExt,
+    stream::{self, BoxStream},
+};
 use lldap_domain::{
     requests::{CreateUserRequest, UpdateUserRequest},
-    types::{AttributeName, GroupDetails, GroupId, Serialized, User, UserAndGroups, UserId, Uuid},
+    types::{
+        AttributeName, Email, GroupDetails, GroupId, Serialized, User, UserAndGroups, UserId, Uuid,
+    },
 };
 use lldap_domain_handlers::handler::{
     ReadSchemaBackendHandler, UserBackendHandler, UserListerBackendHandler, UserRequestFilter,
+    UserSortOrder,
 };
 use lldap_domain_model::{
     error::{DomainError, Result},
@@ -13,26 +27,83 @@ use lldap_domain_model::{
 };
 use sea_orm::{
     ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseTransaction, EntityTrait, ModelTrait,
-    QueryFilter, QueryOrder, QuerySelect, QueryTrait, Set, TransactionTrait,
+    Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, Set, TransactionTrait,
     sea_query::{
         Alias, Cond, Expr, Func, IntoColumnRef, IntoCondition, SimpleExpr, query::OnConflict,
     },
 };
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 use tracing::instrument;
 
-fn attribute_condition(name: AttributeName, value: Option<Serialized>) -> Cond {
+/// Matches the users that are allowed to log in: neither disabled nor expired.
+pub fn active_user_condition() -> Cond {
+    UserColumn::Disabled
+        .eq(false)
+        .and(
+            UserColumn::ExpirationDate
+                .is_null()
+                .or(UserColumn::ExpirationDate.gt(chrono::Utc::now().naive_utc())),
+        )
+        .into_condition()
+}
+
+/// The value of the unique email index. It's NULL for the users without an email, like the
+/// service accounts, so that they don't collide with each other.
+fn get_lowercase_email(email: &Email) -> Option<String> {
+    (!email.as_str().is_empty()).then(|| email.as_str().to_lowercase())
+}
+
+/// The allowed sources as stored in the database, `None` for no restriction.
+fn to_allowed_sources_value(allowed_sources: &[String]) -> Result<Option<String>> {
+    let allowed_sources = normalize_allowed_sources(allowed_sources)?;
+    Ok((!allowed_sources.is_empty()).then(|| allowed_sources.join(" ")))
+}
+
+/// Revokes all the sessions of the user: refresh tokens, JWTs and password reset tokens. Returns
+/// the hashes of the newly revoked JWTs.
+async fn revoke_user_sessions(
+    transaction: &DatabaseTransaction,
+    user_id: &UserId,
+) -> Result<HashSet<u64>> {
+    model::JwtRefreshStorage::delete_many()
+        .filter(model::JwtRefreshStorageColumn::UserId.eq(user_id))
+        .exec(transaction)
+        .await?;
+    model::PasswordResetTokens::delete_many()
+        .filter(model::PasswordResetTokensColumn::UserId.eq(user_id))
+        .exec(transaction)
+        .await?;
+    let revoked_jwts = model::JwtStorage::find()
+        .select_only()
+        .column(model::JwtStorageColumn::JwtHash)
+        .filter(model::JwtStorageColumn::UserId.eq(user_id))
+        .filter(model::JwtStorageColumn::Blacklisted.eq(false))
+        .into_tuple::<i64>()
+        .all(transaction)
+        .await?
+        .into_iter()
+        .map(|jwt_hash| jwt_hash as u64)
+        .collect();
+    model::JwtStorage::update_many()
+        .col_expr(model::JwtStorageColumn::Blacklisted, Expr::value(true))
+        .filter(model::JwtStorageColumn::UserId.eq(user_id))
+        .exec(transaction)
+        .await?;
+    Ok(revoked_jwts)
+}
+
+/// Matches the entities having the attribute, with a value satisfying `value_condition`.
+fn attribute_condition(name: AttributeName, value_condition: SimpleExpr) -> Cond {
     Expr::in_subquery(
         Expr::col(UserColumn::UserId.as_column_ref()),
         model::UserAttributes::find()
             .select_only()
             .column(model::UserAttributesColumn::UserId)
             .filter(model::UserAttributesColumn::AttributeName.eq(name))
-            .filter(
-                value
-                    .map(|value| model::UserAttributesColumn::Value.eq(value))
-                    .unwrap_or_else(|| SimpleExpr::Constant(true.into())),
-            )
+            .filter(value_condition)
             .into_query(),
     )
     .into_condition()
@@ -51,7 +122,20 @@ fn user_id_subcondition(filter: Cond) -> Cond {
     .into_condition()
 }
 
-fn get_user_filter_expr(filter: UserRequestFilter) -> Cond {
+/// The expression used to sort users by the given column, case-insensitively.
+fn user_sort_expr(column: UserColumn) -> SimpleExpr {
+    let column_expr = |c: UserColumn| SimpleExpr::Column(c.as_column_ref().into_column_ref());
+    match column {
+        UserColumn::Email => column_expr(UserColumn::LowercaseEmail),
+        UserColumn::DisplayName => SimpleExpr::FunctionCall(Func::lower(Func::coalesce([
+            column_expr(UserColumn::DisplayName),
+            SimpleExpr::Value("".into()),
+        ]))),
+        column => column_expr(column),
+    }
+}
+
+pub(crate) fn get_user_filter_expr(filter: UserRequestFilter) -> Cond {
     use UserRequestFilter::*;
     let group_table = Alias::new("r1");
     fn bool_to_expr(b: bool) -> Cond {
@@ -87,7 +171,20 @@ fn get_user_filter_expr(filter: UserRequestFilter) -> Cond {
                 ColumnTrait::eq(&column, value).into_condition()
             }
         }
-        AttributeEquality(column, value) => attribute_condition(column, Some(value.into())),
+        AttributeEquality(column, value) => attribute_condition(
+            column,
+            model::UserAttributesColumn::Value.eq(Serialized::from(value)),
+        ),
+        AttributeEqualityIgnoreCase(name, value) => attribute_condition(
+            name,
+            model::UserAttributesColumn::LowercaseValue
+                .like(get_attribute_ignore_case_pattern(&value)),
+        ),
+        AttributeSubString(name, filter) => attribute_condition(
+            name,
+            model::UserAttributesColumn::LowercaseValue
+                .like(get_attribute_substring_pattern(&filter)),
+        ),
         MemberOf(group) => user_id_subcondition(
             Expr::col((group_table, GroupColumn::LowercaseDisplayName))
                 .eq(group.as_str().to_lowercase())
@@ -106,10 +203,68 @@ fn get_user_filter_expr(filter: UserRequestFilter) -> Cond {
                 .like(filter.to_sql_filter())
                 .into_condition()
         }
-        CustomAttributePresent(name) => attribute_condition(name, None),
+        GreaterOrEqual(column, value) => Expr::expr(user_sort_expr(column))
+            .gte(value.to_lowercase())
+            .into_condition(),
+        LessOrEqual(column, value) => Expr::expr(user_sort_expr(column))
+            .lte(value.to_lowercase())
+            .into_condition(),
+        CustomAttributePresent(name) => {
+            attribute_condition(name, SimpleExpr::Constant(true.into()))
+        }
+        Disabled(disabled) => UserColumn::Disabled.eq(disabled).into_condition(),
+        Expires(true) => UserColumn::ExpirationDate.is_not_null().into_condition(),
+        Expires(false) => UserColumn::ExpirationDate.is_null().into_condition(),
+        ServiceAccount(is_service_account) => UserColumn::IsServiceAccount
+            .eq(is_service_account)
+            .into_condition(),
+    }
+}
+
+/// Replaces the membership conditions on dynamic groups with the filters defining them.
+///
+/// The filters of the dynamic groups are not expanded themselves: a dynamic group can only refer
+/// to the members of regular groups.
+fn expand_dynamic_groups(
+    filter: UserRequestFilter,
+    dynamic_groups: &[DynamicGroup],
+) -> UserRequestFilter {
+    use UserRequestFilter::*;
+    let expand_all = |fs: Vec<UserRequestFilter>| {
+        fs.into_iter()
+            .map(|f| expand_dynamic_groups(f, dynamic_groups))
+            .collect()
+    };
+    let find_group = |predicate: &dyn Fn(&GroupDetails) -> bool| {
+        dynamic_groups
+            .iter()
+            .find(|g| predicate(&g.details))
+            .map(|g| g.filter.clone())
+    };
+    match filter {
+        And(fs) => And(expand_all(fs)),
+        Or(fs) => Or(expand_all(fs)),
+        Not(f) => Not(Box::new(expand_dynamic_groups(*f, dynamic_groups))),
+        MemberOf(group) => {
+            let dynamic_filter = find_group(&|g| g.display_name == group);
+            dynamic_filter.unwrap_or(MemberOf(group))
+        }
+        MemberOfId(group_id) => {
+            find_group(&|g| g.group_id == group_id).unwrap_or(MemberOfId(group_id))
+        }
+        filter => filter,
     }
 }
 
+fn get_expanded_filter_expr(
+    filters: Option<UserRequestFilter>,
+    dynamic_groups: &[DynamicGroup],
+) -> Cond {
+    filters
+        .map(|f| get_user_filter_expr(expand_dynamic_groups(f, dynamic_groups)))
+        .unwrap_or_else(|| SimpleExpr::Value(true.into()).into_condition())
+}
+
 fn to_value(opt_name: &Option<String>) -> ActiveValue<Option<String>> {
     match opt_name {
         None => ActiveValue::NotSet,
@@ -130,9 +285,98 @@ impl UserListerBackendHandler for SqlBackendHandler {
         // To simplify the query, we always fetch groups. TODO: cleanup.
         _get_groups: bool,
     ) -> Result<Vec<UserAndGroups>> {
-        let filters = filters
-            .map(get_user_filter_expr)
-            .unwrap_or_else(|| SimpleExpr::Value(true.into()).into_condition());
+        let dynamic_groups = self.get_dynamic_groups().await?;
+        self.list_users_with_condition(
+            get_expanded_filter_expr(filters, &dynamic_groups),
+            &dynamic_groups,
+        )
+        .await
+    }
+
+    fn stream_users(
+        &self,
+        filters: Option<UserRequestFilter>,
+        _get_groups: bool,
+    ) -> BoxStream<'_, Result<UserAndGroups>> {
+        async move {
+            // Select the ids up front, then fetch the users with their groups and attributes one
+            // batch at a time.
+            let dynamic_groups = Arc::new(self.get_dynamic_groups().await?);
+            let user_ids: Vec<UserId> = model::User::find()
+                .filter(get_expanded_filter_expr(filters, &dynamic_groups))
+                .select_only()
+                .column(UserColumn::UserId)
+                .order_by_asc(UserColumn::UserId)
+                .into_tuple()
+                .all(&self.sql_pool)
+                .await?;
+            let batches: Vec<Vec<UserId>> = user_ids
+                .chunks(STREAM_BATCH_SIZE)
+                .map(<[UserId]>::to_vec)
+                .collect();
+            Ok::<_, DomainError>(
+                stream::iter(batches)
+                    .then(move |batch| {
+                        let dynamic_groups = dynamic_groups.clone();
+                        async move { self.list_users_by_id(&batch, &dynamic_groups).await }
+                    })
+                    .map_ok(|users| stream::iter(users.into_iter().map(Ok::<_, DomainError>)))
+                    .try_flatten(),
+            )
+        }
+        .try_flatten_stream()
+        .boxed()
+    }
+
+    #[instrument(skip(self), level = "debug", ret, err)]
+    async fn count_users(&self, filters: Option<UserRequestFilter>) -> Result<u64> {
+        let filters = get_expanded_filter_expr(filters, &self.get_dynamic_groups().await?);
+        Ok(model::User::find()
+            .filter(filters)
+            .count(&self.sql_pool)
+            .await?)
+    }
+
+    #[instrument(skip(self), level = "debug", ret, err)]
+    async fn list_users_sorted(
+        &self,
+        filters: Option<UserRequestFilter>,
+        _get_groups: bool,
+        order: UserSortOrder,
+        offset: u64,
+        limit: Option<u64>,
+    ) -> Result<Vec<UserAndGroups>> {
+        let sort_order = if order.descending {
+            Order::Desc
+        } else {
+            Order::Asc
+        };
+        // Select the ids of the requested window first: the full listing joins the groups, which
+        // doesn't play well with LIMIT. The window is then fetched in batches.
+        let dynamic_groups = self.get_dynamic_groups().await?;
+        let page: Vec<UserId> = model::User::find()
+            .filter(get_expanded_filter_expr(filters, &dynamic_groups))
+            .select_only()
+            .column(UserColumn::UserId)
+            .order_by(user_sort_expr(order.column), sort_order.clone())
+            .order_by(UserColumn::UserId, sort_order)
+            .offset(offset)
+            .limit(limit)
+            .into_tuple()
+            .all(&self.sql_pool)
+            .await?;
+        self.list_users_by_id(&page, &dynamic_groups).await
+    }
+}
+
+impl SqlBackendHandler {
+    /// Lists the users matching the (already expanded) condition, with their groups and
+    /// attributes, ordered by user id.
+    async fn list_users_with_condition(
+        &self,
+        filters: Cond,
+        dynamic_groups: &[DynamicGroup],
+    ) -> Result<Vec<UserAndGroups>> {
         let mut users: Vec<_> = model::User::find()
             .filter(filters.clone())
             .order_by_asc(UserColumn::UserId)
@@ -148,6 +392,26 @@ impl UserListerBackendHandler for SqlBackendHandler {
                 groups: Some(groups.into_iter().map(Into::<GroupDetails>::into).collect()),
             })
             .collect();
+        for dynamic_group in dynamic_groups {
+            let members: HashSet<UserId> = model::User::find()
+                .select_only()
+                .column(UserColumn::UserId)
+                .filter(filters.clone())
+                .filter(get_user_filter_expr(dynamic_group.filter.clone()))
+                .into_tuple::<UserId>()
+                .all(&self.sql_pool)
+                .await?
+                .into_iter()
+                .collect();
+            for user in users
+                .iter_mut()
+                .filter(|u| members.contains(&u.user.user_id))
+            {
+                let groups = user.groups.get_or_insert_with(Vec::new);
+                groups.push(dynamic_group.details.clone());
+                groups.sort_by(|g1, g2| g1.display_name.cmp(&g2.display_name));
+            }
+        }
 
         // At this point, the users don't have attributes, we need to populate it with another query.
         let attributes = model::UserAttributes::find()
@@ -182,25 +446,66 @@ impl UserListerBackendHandler for SqlBackendHandler {
         }
         Ok(users)
     }
-}
 
-impl SqlBackendHandler {
+    /// Fetches the given users in batches, keeping the order of `user_ids`. Selecting them by id
+    /// keeps the queries small, whatever the original filter.
+    async fn list_users_by_id(
+        &self,
+        user_ids: &[UserId],
+        dynamic_groups: &[DynamicGroup],
+    ) -> Result<Vec<UserAndGroups>> {
+        let positions: HashMap<&UserId, usize> =
+            user_ids.iter().enumerate().map(|(i, id)| (id, i)).collect();
+        let mut users = Vec::with_capacity(user_ids.len());
+        for batch in user_ids.chunks(STREAM_BATCH_SIZE) {
+            users.extend(
+                self.list_users_with_condition(
+                    UserColumn::UserId
+                        .is_in(batch.iter().cloned())
+                        .into_condition(),
+                    dynamic_groups,
+                )
+                .await?,
+            );
+        }
+        users.sort_by_key(|u| positions.get(&u.user.user_id).copied());
+        Ok(users)
+    }
+
     async fn update_user_with_transaction(
         transaction: &DatabaseTransaction,
         request: UpdateUserRequest,
     ) -> Result<()> {
-        let lower_email = request.email.as_ref().map(|s| s.as_str().to_lowercase());
+        let lower_email = request.email.as_ref().map(get_lowercase_email);
+        let allowed_sources = match &request.allowed_sources {
+            Some(allowed_sources) => {
+                let is_service_account = model::User::find_by_id(request.user_id.clone())
+                    .one(transaction)
+                    .await?
+                    .is_some_and(|user| user.is_service_account);
+                if !is_service_account {
+                    return Err(DomainError::InternalError(
+                        "Only service accounts can be restricted to some networks".to_owned(),
+                    ));
+                }
+                ActiveValue::Set(to_allowed_sources_value(allowed_sources)?)
+            }
+            None => ActiveValue::NotSet,
+        };
         let update_user = model::users::ActiveModel {
             user_id: ActiveValue::Set(request.user_id.clone()),
             email: request.email.map(ActiveValue::Set).unwrap_or_default(),
             lowercase_email: lower_email.map(ActiveValue::Set).unwrap_or_default(),
             display_name: to_value(&request.display_name),
+            allowed_sources,
             ..Default::default()
         };
         let mut update_user_attributes = Vec::new();
         let mut remove_user_attributes = Vec::new();
         let mut process_serialized =
-            |value: ActiveValue<Serialized>, attribute_name: AttributeName| match &value {
+            |value: ActiveValue<Serialized>,
+             lowercase_value: Option<String>,
+             attribute_name: AttributeName| match &value {
                 ActiveValue::NotSet => {
                     remove_user_attributes.push(attribute_name);
                 }
@@ -209,6 +514,7 @@ impl SqlBackendHandler {
                         user_id: Set(request.user_id.clone()),
                         attribute_name: Set(attribute_name),
                         value,
+                        lowercase_value: Set(lowercase_value),
                     })
                 }
                 _ => unreachable!(),
@@ -220,8 +526,10 @@ impl SqlBackendHandler {
                 .get_attribute_type(&attribute.name)
                 .is_some()
             {
+                let lowercase_value = get_lowercase_attribute_value(&attribute.value);
                 process_serialized(
                     ActiveValue::Set(attribute.value.into()),
+                    lowercase_value,
                     attribute.name.clone(),
                 );
             } else {
@@ -259,7 +567,10 @@ impl SqlBackendHandler {
                         model::UserAttributesColumn::UserId,
                         model::UserAttributesColumn::AttributeName,
                     ])
-                    .update_column(model::UserAttributesColumn::Value)
+                    .update_columns([
+                        model::UserAttributesColumn::Value,
+                        model::UserAttributesColumn::LowercaseValue,
+                    ])
                     .to_owned(),
                 )
                 .exec(transaction)
@@ -269,6 +580,21 @@ impl SqlBackendHandler {
     }
 }
 
+impl SqlBackendHandler {
+    /// The members of a dynamic group are defined by its filter, they cannot be edited.
+    async fn check_not_dynamic_group(&self, group_id: GroupId) -> Result<()> {
+        let group = model::Group::find_by_id(group_id)
+            .one(&self.sql_pool)
+            .await?;
+        if group.is_some_and(|g| g.member_filter.is_some()) {
+            return Err(DomainError::InternalError(format!(
+                "Cannot edit the members of the dynamic group {group_id:?}"
+            )));
+        }
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl UserBackendHandler for SqlBackendHandler {
     #[instrument(skip_all, level = "debug", ret, fields(user_id = ?user_id.as_str()))]
@@ -304,20 +630,37 @@ impl UserBackendHandler for SqlBackendHandler {
             .one(&self.sql_pool)
             .await?
             .ok_or_else(|| DomainError::EntityNotFound(user_id.to_string()))?;
-        Ok(HashSet::from_iter(
+        let mut groups = HashSet::from_iter(
             user.find_linked(model::memberships::UserToGroup)
                 .all(&self.sql_pool)
                 .await?
                 .into_iter()
                 .map(Into::<GroupDetails>::into),
-        ))
+        );
+        for dynamic_group in self.get_dynamic_groups().await? {
+            let is_member = model::User::find()
+                .filter(UserColumn::UserId.eq(user_id))
+                .filter(get_user_filter_expr(dynamic_group.filter))
+                .count(&self.sql_pool)
+                .await?
+                > 0;
+            if is_member {
+                groups.insert(dynamic_group.details);
+            }
+        }
+        Ok(groups)
     }
 
     #[instrument(skip(self), level = "debug", err, fields(user_id = ?request.user_id.as_str()))]
     async fn create_user(&self, request: CreateUserRequest) -> Result<()> {
         let now = chrono::Utc::now().naive_utc();
         let uuid = Uuid::from_name_and_date(request.user_id.as_str(), &now);
-        let lower_email = request.email.as_str().to_lowercase();
+        let lower_email = get_lowercase_email(&request.email);
+        if !request.is_service_account && !request.allowed_sources.is_empty() {
+            return Err(DomainError::InternalError(
+                "Only service accounts can be restricted to some networks".to_owned(),
+            ));
+        }
         let new_user = model::users::ActiveModel {
             user_id: Set(request.user_id.clone()),
             email: Set(request.email),
@@ -325,6 +668,9 @@ impl UserBackendHandler for SqlBackendHandler {
             display_name: to_value(&request.display_name),
             creation_date: ActiveValue::Set(now),
             uuid: ActiveValue::Set(uuid),
+            legacy_password_hash: ActiveValue::Set(request.legacy_password_hash),
+            is_service_account: ActiveValue::Set(request.is_service_account),
+            allowed_sources: ActiveValue::Set(to_allowed_sources_value(&request.allowed_sources)?),
             ..Default::default()
         };
         let mut new_user_attributes = Vec::new();
@@ -341,6 +687,9 @@ impl UserBackendHandler for SqlBackendHandler {
                             new_user_attributes.push(model::user_attributes::ActiveModel {
                                 user_id: Set(request.user_id.clone()),
                                 attribute_name: Set(attribute.name),
+                                lowercase_value: Set(get_lowercase_attribute_value(
+                                    &attribute.value,
+                                )),
                                 value: Set(attribute.value.into()),
                             });
                         } else {
@@ -386,11 +735,15 @@ impl UserBackendHandler for SqlBackendHandler {
                 "No such user: '{user_id}'"
             )));
         }
+        if let Some(cache) = &self.bind_cache {
+            cache.invalidate(user_id);
+        }
         Ok(())
     }
 
     #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str(), group_id))]
     async fn add_user_to_group(&self, user_id: &UserId, group_id: GroupId) -> Result<()> {
+        self.check_not_dynamic_group(group_id).await?;
         let new_membership = model::memberships::ActiveModel {
             user_id: ActiveValue::Set(user_id.clone()),
             group_id: ActiveValue::Set(group_id),
@@ -401,6 +754,7 @@ impl UserBackendHandler for SqlBackendHandler {
 
     #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str(), group_id))]
     async fn remove_user_from_group(&self, user_id: &UserId, group_id: GroupId) -> Result<()> {
+        self.check_not_dynamic_group(group_id).await?;
         let res = model::Membership::delete_by_id((user_id.clone(), group_id))
             .exec(&self.sql_pool)
             .await?;
@@ -411,6 +765,75 @@ impl UserBackendHandler for SqlBackendHandler {
         }
         Ok(())
     }
+
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str(), disabled))]
+    async fn set_user_disabled(&self, user_id: &UserId, disabled: bool) -> Result<()> {
+        let revoked_jwts = self
+            .sql_pool
+            .transaction::<_, HashSet<u64>, DomainError>(|transaction| {
+                let user_id = user_id.clone();
+                Box::pin(async move {
+                    let res = model::User::update_many()
+                        .col_expr(UserColumn::Disabled, Expr::value(disabled))
+                        .filter(UserColumn::UserId.eq(&user_id))
+                        .exec(transaction)
+                        .await?;
+                    if res.rows_affected == 0 {
+                        return Err(DomainError::EntityNotFound(format!(
+                            "No such user: '{user_id}'"
+                        )));
+                    }
+                    if disabled {
+                        revoke_user_sessions(transaction, &user_id).await
+                    } else {
+                        Ok(HashSet::new())
+                    }
+                })
+            })
+            .await?;
+        self.jwt_blacklist.write().unwrap().extend(revoked_jwts);
+        if let Some(cache) = &self.bind_cache {
+            cache.invalidate(user_id);
+        }
+        Ok(())
+    }
+
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str(), expiration_date = ?expiration_date))]
+    async fn set_user_expiration_date(
+        &self,
+        user_id: &UserId,
+        expiration_date: Option<chrono::NaiveDateTime>,
+    ) -> Result<()> {
+        let revoked_jwts = self
+            .sql_pool
+            .transaction::<_, HashSet<u64>, DomainError>(|transaction| {
+                let user_id = user_id.clone();
+                Box::pin(async move {
+                    let res = model::User::update_many()
+                        .col_expr(UserColumn::ExpirationDate, Expr::value(expiration_date))
+                        .col_expr(UserColumn::ExpirationWarningSent, Expr::value(false))
+                        .filter(UserColumn::UserId.eq(&user_id))
+                        .exec(transaction)
+                        .await?;
+                    if res.rows_affected == 0 {
+                        return Err(DomainError::EntityNotFound(format!(
+                            "No such user: '{user_id}'"
+                        )));
+                    }
+                    if expiration_date.is_some_and(|d| d <= chrono::Utc::now().naive_utc()) {
+                        revoke_user_sessions(transaction, &user_id).await
+                    } else {
+                        Ok(HashSet::new())
+                    }
+                })
+            })
+            .await?;
+        self.jwt_blacklist.write().unwrap().extend(revoked_jwts);
+        if let Some(cache) = &self.bind_cache {
+            cache.invalidate(user_id);
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -430,6 +853,28 @@ mod tests {
         assert_eq!(users, vec!["bob", "john", "nogroup", "patrick"]);
     }
 
+    #[tokio::test]
+    async fn test_stream_users() {
+        let fixture = TestFixture::new().await;
+        let filter =
+            UserRequestFilter::Not(Box::new(UserRequestFilter::UserId(UserId::new("john"))));
+        let streamed: Vec<_> = fixture
+            .handler
+            .stream_users(Some(filter.clone()), true)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(
+            streamed,
+            fixture
+                .handler
+                .list_users(Some(filter), true)
+                .await
+                .unwrap()
+        );
+        assert_eq!(streamed.len(), 3);
+    }
+
     #[tokio::test]
     async fn test_list_users_user_id_filter() {
         let fixture = TestFixture::new().await;
@@ -469,6 +914,29 @@ mod tests {
         assert_eq!(users, vec!["bob"]);
     }
 
+    #[tokio::test]
+    async fn test_list_users_attribute_ignore_case_filter() {
+        let fixture = TestFixture::new().await;
+        let first_name_filter = |value: &str| {
+            Some(UserRequestFilter::AttributeEqualityIgnoreCase(
+                AttributeName::from("first_name"),
+                value.to_owned(),
+            ))
+        };
+        assert_eq!(
+            get_user_names(&fixture.handler, first_name_filter("First BOB")).await,
+            vec!["bob"]
+        );
+        assert_eq!(
+            get_user_names(&fixture.handler, first_name_filter("first bo")).await,
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            get_user_names(&fixture.handler, first_name_filter("first_bob")).await,
+            Vec::<String>::new()
+        );
+    }
+
     #[tokio::test]
     async fn test_list_users_email_filter_uppercase_email() {
         let fixture = TestFixture::new().await;
@@ -518,6 +986,49 @@ mod tests {
         assert_eq!(users, vec!["patrick"]);
     }
 
+    #[tokio::test]
+    async fn test_list_users_attribute_substring_filter() {
+        let fixture = TestFixture::new().await;
+        let first_name_filter = |initial: &str, any: &[&str], final_: Option<&str>| {
+            Some(UserRequestFilter::AttributeSubString(
+                AttributeName::from("first_name"),
+                SubStringFilter {
+                    initial: Some(initial.to_owned()),
+                    any: any.iter().map(|s| s.to_string()).collect(),
+                    final_: final_.map(str::to_owned),
+                },
+            ))
+        };
+        assert_eq!(
+            get_user_names(&fixture.handler, first_name_filter("FIRST", &["o"], None)).await,
+            vec!["bob", "john", "nogroup"]
+        );
+        assert_eq!(
+            get_user_names(
+                &fixture.handler,
+                first_name_filter("first ", &[], Some("N"))
+            )
+            .await,
+            vec!["john"]
+        );
+        fixture
+            .handler
+            .update_user(UpdateUserRequest {
+                user_id: UserId::new("bob"),
+                insert_attributes: vec![Attribute {
+                    name: "first_name".into(),
+                    value: "Bobby".to_string().into(),
+                }],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            get_user_names(&fixture.handler, first_name_filter("bob", &[], Some("BY"))).await,
+            vec!["bob"]
+        );
+    }
+
     #[tokio::test]
     async fn test_list_users_false_filter() {
         let fixture = TestFixture::new().await;
@@ -654,6 +1165,150 @@ mod tests {
         assert_eq!(users, vec!["john", "nogroup", "patrick"]);
     }
 
+    #[tokio::test]
+    async fn test_list_users_filter_greater_or_equal() {
+        let fixture = TestFixture::new().await;
+        let users = get_user_names(
+            &fixture.handler,
+            Some(UserRequestFilter::GreaterOrEqual(
+                UserColumn::DisplayName,
+                "Display N".to_string(),
+            )),
+        )
+        .await;
+        assert_eq!(users, vec!["nogroup", "patrick"]);
+        let users = get_user_names(
+            &fixture.handler,
+            Some(UserRequestFilter::LessOrEqual(
+                UserColumn::UserId,
+                "john".to_string(),
+            )),
+        )
+        .await;
+        assert_eq!(users, vec!["bob", "john"]);
+    }
+
+    #[tokio::test]
+    async fn test_count_users() {
+        let fixture = TestFixture::new().await;
+        assert_eq!(fixture.handler.count_users(None).await.unwrap(), 4);
+        assert_eq!(
+            fixture
+                .handler
+                .count_users(Some(UserRequestFilter::MemberOf("Best Group".into())))
+                .await
+                .unwrap(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_users_sorted() {
+        let fixture = TestFixture::new().await;
+        let list = |column, descending, offset, limit| {
+            let handler = &fixture.handler;
+            async move {
+                handler
+                    .list_users_sorted(
+                        None,
+                        false,
+                        UserSortOrder { column, descending },
+                        offset,
+                        limit,
+                    )
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|u| u.user.user_id.to_string())
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(
+            list(UserColumn::DisplayName, false, 0, None).await,
+            vec!["bob", "john", "nogroup", "patrick"]
+        );
+        assert_eq!(
+            list(UserColumn::DisplayName, true, 1, Some(2)).await,
+            vec!["nogroup", "john"]
+        );
+        assert_eq!(
+            list(UserColumn::Email, false, 3, Some(10)).await,
+            vec!["patrick"]
+        );
+        assert!(list(UserColumn::UserId, false, 4, None).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_users_sorted_several_batches() {
+        let handler =
+            SqlBackendHandler::new(generate_random_private_key(), get_initialized_db().await);
+        let mut user_ids: Vec<String> = (0..STREAM_BATCH_SIZE + 20)
+            .map(|i| format!("user{i:03}"))
+            .collect();
+        for user_id in &user_ids {
+            insert_user_no_password(&handler, user_id).await;
+        }
+        user_ids.reverse();
+        let users = handler
+            .list_users_sorted(
+                None,
+                false,
+                UserSortOrder {
+                    column: UserColumn::UserId,
+                    descending: true,
+                },
+                10,
+                Some(STREAM_BATCH_SIZE as u64),
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|u| u.user.user_id.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(users, user_ids[10..STREAM_BATCH_SIZE + 10]);
+    }
+
+    #[tokio::test]
+    async fn test_list_users_sorted_with_groups() {
+        let fixture = TestFixture::new().await;
+        let users = fixture
+            .handler
+            .list_users_sorted(
+                Some(UserRequestFilter::MemberOf("Worst Group".into())),
+                true,
+                UserSortOrder {
+                    column: UserColumn::UserId,
+                    descending: true,
+                },
+                0,
+                Some(10),
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|u| {
+                (
+                    u.user.user_id.to_string(),
+                    u.groups
+                        .unwrap()
+                        .into_iter()
+                        .map(|g| g.display_name.to_string())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            users,
+            vec![
+                (
+                    "patrick".to_string(),
+                    vec!["Best Group".to_string(), "Worst Group".to_string()]
+                ),
+                ("john".to_string(), vec!["Worst Group".to_string()]),
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn test_list_users_with_groups() {
         let fixture = TestFixture::new().await;
@@ -796,6 +1451,159 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_set_user_disabled() {
+        let fixture = TestFixture::new().await;
+        let bob = UserId::new("bob");
+        let expiry_date = chrono::Utc::now().naive_utc() + chrono::Duration::days(1);
+        model::jwt_refresh_storage::ActiveModel {
+            refresh_token_hash: Set(1),
+            user_id: Set(bob.clone()),
+            expiry_date: Set(expiry_date),
+        }
+        .insert(&fixture.handler.sql_pool)
+        .await
+        .unwrap();
+        model::jwt_storage::ActiveModel {
+            jwt_hash: Set(2),
+            user_id: Set(bob.clone()),
+            blacklisted: Set(false),
+            expiry_date: Set(expiry_date),
+        }
+        .insert(&fixture.handler.sql_pool)
+        .await
+        .unwrap();
+
+        fixture.handler.set_user_disabled(&bob, true).await.unwrap();
+
+        assert!(
+            fixture
+                .handler
+                .get_user_details(&bob)
+                .await
+                .unwrap()
+                .disabled
+        );
+        assert!(
+            model::JwtRefreshStorage::find_by_id(1)
+                .one(&fixture.handler.sql_pool)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            model::JwtStorage::find_by_id(2)
+                .one(&fixture.handler.sql_pool)
+                .await
+                .unwrap()
+                .unwrap()
+                .blacklisted
+        );
+        // The other clones of the handler see the revoked JWT without querying the DB.
+        assert!(
+            fixture
+                .handler
+                .clone()
+                .jwt_blacklist()
+                .read()
+                .unwrap()
+                .contains(&2)
+        );
+        assert_eq!(
+            get_user_names(&fixture.handler, Some(UserRequestFilter::Disabled(true))).await,
+            vec!["bob"]
+        );
+
+        fixture
+            .handler
+            .set_user_disabled(&bob, false)
+            .await
+            .unwrap();
+        assert!(
+            !fixture
+                .handler
+                .get_user_details(&bob)
+                .await
+                .unwrap()
+                .disabled
+        );
+        assert_eq!(
+            get_user_names(&fixture.handler, Some(UserRequestFilter::Disabled(true))).await,
+            Vec::<String>::new()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_user_disabled_not_found() {
+        let fixture = TestFixture::new().await;
+        assert!(matches!(
+            fixture
+                .handler
+                .set_user_disabled(&UserId::new("not_found"), true)
+                .await,
+            Err(DomainError::EntityNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_set_user_expiration_date() {
+        let fixture = TestFixture::new().await;
+        let bob = UserId::new("bob");
+        model::jwt_refresh_storage::ActiveModel {
+            refresh_token_hash: Set(1),
+            user_id: Set(bob.clone()),
+            expiry_date: Set(chrono::Utc::now().naive_utc() + chrono::Duration::days(1)),
+        }
+        .insert(&fixture.handler.sql_pool)
+        .await
+        .unwrap();
+        let get_expiration_date = async || {
+            fixture
+                .handler
+                .get_user_details(&bob)
+                .await
+                .unwrap()
+                .expiration_date
+        };
+        let get_refresh_token = async || {
+            model::JwtRefreshStorage::find_by_id(1)
+                .one(&fixture.handler.sql_pool)
+                .await
+                .unwrap()
+        };
+
+        let future_date = chrono::NaiveDate::from_ymd_opt(2100, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        fixture
+            .handler
+            .set_user_expiration_date(&bob, Some(future_date))
+            .await
+            .unwrap();
+        assert_eq!(get_expiration_date().await, Some(future_date));
+        assert!(get_refresh_token().await.is_some());
+
+        let past_date = chrono::NaiveDate::from_ymd_opt(2000, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        fixture
+            .handler
+            .set_user_expiration_date(&bob, Some(past_date))
+            .await
+            .unwrap();
+        assert_eq!(get_expiration_date().await, Some(past_date));
+        assert!(get_refresh_token().await.is_none());
+
+        fixture
+            .handler
+            .set_user_expiration_date(&bob, None)
+            .await
+            .unwrap();
+        assert_eq!(get_expiration_date().await, None);
+    }
+
     #[tokio::test]
     async fn test_get_user_groups() {
         let fixture = TestFixture::new().await;
@@ -819,6 +1627,43 @@ mod tests {
         assert_eq!(get_group_ids("nogroup").await, vec![]);
     }
 
+    #[tokio::test]
+    async fn test_get_user_groups_dynamic_group() {
+        use lldap_domain_handlers::handler::{GroupBackendHandler, GroupMemberFilter};
+        let fixture = TestFixture::new().await;
+        fixture
+            .handler
+            .set_group_member_filter(
+                fixture.groups[2],
+                Some(GroupMemberFilter {
+                    ldap_filter: "(memberOf=cn=Worst Group,ou=groups,dc=example,dc=com)".to_owned(),
+                    filter: UserRequestFilter::MemberOf("Worst Group".into()),
+                }),
+            )
+            .await
+            .unwrap();
+        let groups = fixture
+            .handler
+            .get_user_groups(&UserId::new("john"))
+            .await
+            .unwrap();
+        assert_eq!(
+            groups
+                .into_iter()
+                .map(|g| g.group_id)
+                .collect::<HashSet<_>>(),
+            HashSet::from([fixture.groups[1], fixture.groups[2]])
+        );
+        assert_eq!(
+            fixture
+                .handler
+                .count_users(Some(UserRequestFilter::MemberOf("Empty Group".into())))
+                .await
+                .unwrap(),
+            2
+        );
+    }
+
     #[tokio::test]
     async fn test_update_user_all_values() {
         let fixture = TestFixture::new().await;
@@ -844,6 +1689,7 @@ mod tests {
                         value: JpegPhoto::for_tests().into(),
                     },
                 ],
+                ..Default::default()
             })
             .await
             .unwrap();
@@ -1088,6 +1934,7 @@ mod tests {
                         value: JpegPhoto::for_tests().into(),
                     },
                 ],
+                ..Default::default()
             })
             .await
             .unwrap();
@@ -1190,4 +2037,116 @@ mod tests {
             .await
             .unwrap_err();
     }
+
+    #[tokio::test]
+    async fn test_create_service_accounts() {
+        let fixture = TestFixture::new().await;
+        let create_service_account = |name: &str, allowed_sources: Vec<String>| {
+            fixture.handler.create_user(CreateUserRequest {
+                user_id: UserId::new(name),
+                is_service_account: true,
+                allowed_sources,
+                ..Default::default()
+            })
+        };
+
+        // Several service accounts can go without an email.
+        create_service_account("backup", vec!["10.0.0.1".to_owned()])
+            .await
+            .unwrap();
+        create_service_account("nextcloud", Vec::new())
+            .await
+            .unwrap();
+        create_service_account("jellyfin", vec!["not a network".to_owned()])
+            .await
+            .unwrap_err();
+        // People cannot be restricted to some networks.
+        fixture
+            .handler
+            .create_user(CreateUserRequest {
+                user_id: UserId::new("james"),
+                email: "james@bob.bob".into(),
+                allowed_sources: vec!["10.0.0.1".to_owned()],
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            get_user_names(
+                &fixture.handler,
+                Some(UserRequestFilter::ServiceAccount(true))
+            )
+            .await,
+            vec!["backup", "nextcloud"]
+        );
+        assert!(
+            !get_user_names(
+                &fixture.handler,
+                Some(UserRequestFilter::ServiceAccount(false))
+            )
+            .await
+            .contains(&"backup".to_owned())
+        );
+        let backup = fixture
+            .handler
+            .get_user_details(&UserId::new("backup"))
+            .await
+            .unwrap();
+        assert!(backup.is_service_account);
+        assert_eq!(backup.email, "".into());
+        assert_eq!(backup.allowed_sources, vec!["10.0.0.1/32".to_owned()]);
+        // No made-up address in the unique email index.
+        assert_eq!(
+            model::User::find_by_id(UserId::new("backup"))
+                .one(&fixture.handler.sql_pool)
+                .await
+                .unwrap()
+                .unwrap()
+                .lowercase_email,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_service_account_allowed_sources() {
+        let fixture = TestFixture::new().await;
+        fixture
+            .handler
+            .create_user(CreateUserRequest {
+                user_id: UserId::new("backup"),
+                is_service_account: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        fixture
+            .handler
+            .update_user(UpdateUserRequest {
+                user_id: UserId::new("backup"),
+                allowed_sources: Some(vec!["192.168.1.0/24".to_owned()]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            fixture
+                .handler
+                .get_user_details(&UserId::new("backup"))
+                .await
+                .unwrap()
+                .allowed_sources,
+            vec!["192.168.1.0/24".to_owned()]
+        );
+        fixture
+            .handler
+            .update_user(UpdateUserRequest {
+                user_id: UserId::new("bob"),
+                allowed_sources: Some(vec!["192.168.1.0/24".to_owned()]),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+    }
 }
@@ -0,0 +1,267 @@
+use crate::sql_backend_handler::SqlBackendHandler;
+use async_trait::async_trait;
+use lldap_domain::{
+    requests::AddWebauthnCredentialRequest,
+    types::{UserId, WebauthnCredential, WebauthnCredentialId},
+};
+use lldap_domain_handlers::handler::WebauthnBackendHandler;
+use lldap_domain_model::{
+    error::{DomainError, Result},
+    model::{self, WebauthnCredentialsColumn},
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, sea_query::Expr,
+};
+use tracing::{info, instrument};
+
+#[async_trait]
+impl WebauthnBackendHandler for SqlBackendHandler {
+    #[instrument(skip_all, level = "debug", ret, err, fields(user_id = ?user_id.as_str()))]
+    async fn list_webauthn_credentials(&self, user_id: &UserId) -> Result<Vec<WebauthnCredential>> {
+        Ok(model::WebauthnCredentials::find()
+            .filter(WebauthnCredentialsColumn::UserId.eq(user_id))
+            .order_by_asc(WebauthnCredentialsColumn::WebauthnCredentialId)
+            .all(&self.sql_pool)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    #[instrument(skip_all, level = "debug", err)]
+    async fn get_webauthn_credential(&self, credential_id: &[u8]) -> Result<WebauthnCredential> {
+        model::WebauthnCredentials::find()
+            .filter(WebauthnCredentialsColumn::CredentialId.eq(credential_id))
+            .one(&self.sql_pool)
+            .await?
+            .map(Into::into)
+            .ok_or_else(|| DomainError::EntityNotFound("No such WebAuthn credential".to_owned()))
+    }
+
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?request.user_id.as_str(), name = ?request.name))]
+    async fn add_webauthn_credential(&self, request: AddWebauthnCredentialRequest) -> Result<()> {
+        let existing = model::WebauthnCredentials::find()
+            .filter(WebauthnCredentialsColumn::CredentialId.eq(request.credential_id.clone()))
+            .count(&self.sql_pool)
+            .await?;
+        if existing > 0 {
+            return Err(DomainError::InternalError(
+                "This credential is already registered".to_owned(),
+            ));
+        }
+        model::webauthn_credentials::ActiveModel {
+            user_id: ActiveValue::Set(request.user_id.clone()),
+            credential_id: ActiveValue::Set(request.credential_id),
+            name: ActiveValue::Set(request.name),
+            passkey: ActiveValue::Set(request.passkey),
+            creation_date: ActiveValue::Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .insert(&self.sql_pool)
+        .await?;
+        info!(
+            r#"User "{}" registered a WebAuthn credential"#,
+            request.user_id
+        );
+        Ok(())
+    }
+
+    #[instrument(skip_all, level = "debug", err)]
+    async fn use_webauthn_credential(
+        &self,
+        credential_id: &[u8],
+        updated_passkey: Option<String>,
+    ) -> Result<()> {
+        let credential = self.get_webauthn_credential(credential_id).await?;
+        if self.is_user_locked_out(&credential.user_id).await? {
+            info!(
+                r#"WebAuthn login attempt for disabled or expired user "{}""#,
+                &credential.user_id
+            );
+            return Err(DomainError::AuthenticationError(format!(
+                r#"User "{}" is disabled or expired"#,
+                &credential.user_id
+            )));
+        }
+        let mut update = model::WebauthnCredentials::update_many()
+            .col_expr(
+                WebauthnCredentialsColumn::LastUsedDate,
+                Expr::value(chrono::Utc::now().naive_utc()),
+            )
+            .filter(WebauthnCredentialsColumn::WebauthnCredentialId.eq(credential.id));
+        if let Some(passkey) = updated_passkey {
+            update = update.col_expr(WebauthnCredentialsColumn::Passkey, Expr::value(passkey));
+        }
+        update.exec(&self.sql_pool).await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str(), id = ?id))]
+    async fn rename_webauthn_credential(
+        &self,
+        user_id: &UserId,
+        id: WebauthnCredentialId,
+        name: &str,
+    ) -> Result<()> {
+        let res = model::WebauthnCredentials::update_many()
+            .col_expr(WebauthnCredentialsColumn::Name, Expr::value(name))
+            .filter(WebauthnCredentialsColumn::WebauthnCredentialId.eq(id))
+            .filter(WebauthnCredentialsColumn::UserId.eq(user_id))
+            .exec(&self.sql_pool)
+            .await?;
+        if res.rows_affected == 0 {
+            return Err(DomainError::EntityNotFound(format!(
+                "No such WebAuthn credential: {id:?}"
+            )));
+        }
+        Ok(())
+    }
+
+    #[instrument(skip_all, level = "debug", err, fields(user_id = ?user_id.as_str(), id = ?id))]
+    async fn delete_webauthn_credential(
+        &self,
+        user_id: &UserId,
+        id: WebauthnCredentialId,
+    ) -> Result<()> {
+        let res = model::WebauthnCredentials::delete_many()
+            .filter(WebauthnCredentialsColumn::WebauthnCredentialId.eq(id))
+            .filter(WebauthnCredentialsColumn::UserId.eq(user_id))
+            .exec(&self.sql_pool)
+            .await?;
+        if res.rows_affected == 0 {
+            return Err(DomainError::EntityNotFound(format!(
+                "No such WebAuthn credential: {id:?}"
+            )));
+        }
+        info!(
+            r#"WebAuthn credential {:?} of user "{}" revoked"#,
+            id, user_id
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql_backend_handler::tests::{get_initialized_db, insert_user_no_password};
+    use lldap_auth::opaque::server::generate_random_private_key;
+    use lldap_domain_handlers::handler::UserBackendHandler;
+
+    async fn setup_handler() -> SqlBackendHandler {
+        let sql_pool = get_initialized_db().await;
+        let handler = SqlBackendHandler::new(generate_random_private_key(), sql_pool);
+        insert_user_no_password(&handler, "bob").await;
+        insert_user_no_password(&handler, "patrick").await;
+        handler
+    }
+
+    fn add_request(user_id: &str, credential_id: &[u8]) -> AddWebauthnCredentialRequest {
+        AddWebauthnCredentialRequest {
+            user_id: UserId::new(user_id),
+            credential_id: credential_id.to_vec(),
+            name: "YubiKey".to_owned(),
+            passkey: "{}".to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_and_list_webauthn_credentials() {
+        let handler = setup_handler().await;
+        let bob = UserId::new("bob");
+        handler
+            .add_webauthn_credential(add_request("bob", b"key1"))
+            .await
+            .unwrap();
+        handler
+            .add_webauthn_credential(add_request("patrick", b"key2"))
+            .await
+            .unwrap();
+        // The same authenticator cannot be registered twice.
+        assert!(
+            handler
+                .add_webauthn_credential(add_request("patrick", b"key1"))
+                .await
+                .is_err()
+        );
+        let credentials = handler.list_webauthn_credentials(&bob).await.unwrap();
+        assert_eq!(credentials.len(), 1);
+        assert_eq!(credentials[0].credential_id, b"key1");
+        assert_eq!(credentials[0].last_used_date, None);
+        assert_eq!(
+            handler
+                .get_webauthn_credential(b"key2")
+                .await
+                .unwrap()
+                .user_id,
+            UserId::new("patrick")
+        );
+        assert!(handler.get_webauthn_credential(b"key3").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_use_webauthn_credential() {
+        let handler = setup_handler().await;
+        let bob = UserId::new("bob");
+        handler
+            .add_webauthn_credential(add_request("bob", b"key1"))
+            .await
+            .unwrap();
+        handler
+            .use_webauthn_credential(b"key1", Some("updated".to_owned()))
+            .await
+            .unwrap();
+        let credential = handler.get_webauthn_credential(b"key1").await.unwrap();
+        assert_eq!(credential.passkey, "updated");
+        assert!(credential.last_used_date.is_some());
+        handler.set_user_disabled(&bob, true).await.unwrap();
+        assert!(
+            handler
+                .use_webauthn_credential(b"key1", None)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rename_and_delete_webauthn_credential() {
+        let handler = setup_handler().await;
+        let bob = UserId::new("bob");
+        let patrick = UserId::new("patrick");
+        handler
+            .add_webauthn_credential(add_request("bob", b"key1"))
+            .await
+            .unwrap();
+        let id = handler.list_webauthn_credentials(&bob).await.unwrap()[0].id;
+        // Only the owner's credentials can be changed.
+        assert!(
+            handler
+                .rename_webauthn_credential(&patrick, id, "Stolen")
+                .await
+                .is_err()
+        );
+        assert!(
+            handler
+                .delete_webauthn_credential(&patrick, id)
+                .await
+                .is_err()
+        );
+        handler
+            .rename_webauthn_credential(&bob, id, "Laptop")
+            .await
+            .unwrap();
+        assert_eq!(
+            handler.list_webauthn_credentials(&bob).await.unwrap()[0].name,
+            "Laptop"
+        );
+        handler.delete_webauthn_credential(&bob, id).await.unwrap();
+        assert!(
+            handler
+                .list_webauthn_credentials(&bob)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
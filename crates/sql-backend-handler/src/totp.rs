@@ -0,0 +1,154 @@
+//! Time-based one-time passwords (RFC 6238), as generated by authenticator apps.
+//!
+//! Only the parameters that all the apps support are used: HMAC-SHA1, 6 digits and 30 seconds.
+
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+/// Length of the generated secrets, in bytes: the size of a SHA-1 hash, as recommended by
+/// RFC 4226.
+const SECRET_LENGTH: usize = 20;
+const PERIOD_SECONDS: u64 = 30;
+const DIGITS: u32 = 6;
+/// Number of periods before and after the current one during which a code is still accepted,
+/// to allow for clock drift.
+const ALLOWED_DRIFT: u64 = 1;
+
+/// Generates a new random secret, encoded in base32 like in the `otpauth://` URIs.
+pub(crate) fn generate_secret(rng: &mut impl RngCore) -> String {
+    let mut secret = [0u8; SECRET_LENGTH];
+    rng.fill_bytes(&mut secret);
+    BASE32_NOPAD.encode(&secret)
+}
+
+fn get_code(secret: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = (hash[hash.len() - 1] & 0xf) as usize;
+    let value = u32::from_be_bytes([
+        hash[offset],
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]) & 0x7fff_ffff;
+    value % 10u32.pow(DIGITS)
+}
+
+/// Checks the code entered by the user against the base32 secret, at the given Unix time.
+///
+/// Returns the time step of the code if it's valid. The codes of `last_step` and of the steps
+/// before it are rejected, so that a code can only be used once.
+pub(crate) fn check_code(
+    secret: &str,
+    code: &str,
+    unix_time: u64,
+    last_step: Option<u64>,
+) -> Option<u64> {
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let code = code.parse::<u32>().ok()?;
+    let counter = unix_time / PERIOD_SECONDS;
+    (counter.saturating_sub(ALLOWED_DRIFT)..=counter + ALLOWED_DRIFT)
+        .filter(|&c| last_step.is_none_or(|last| c > last))
+        .find(|&c| get_code(&secret, c) == code)
+}
+
+/// The code that the authenticator apps display at the given Unix time.
+#[cfg(test)]
+pub(crate) fn generate_code(secret: &str, unix_time: u64) -> String {
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+    format!(
+        "{:0width$}",
+        get_code(&secret, unix_time / PERIOD_SECONDS),
+        width = DIGITS as usize
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect()
+}
+
+/// The URI to put in the QR code scanned by the authenticator apps.
+pub(crate) fn get_otpauth_uri(secret: &str, account: &str, issuer: &str) -> String {
+    let issuer = percent_encode(issuer);
+    format!(
+        "otpauth://totp/{issuer}:{}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={PERIOD_SECONDS}",
+        percent_encode(account),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The SHA-1 test vectors from RFC 6238, truncated to 6 digits.
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_rfc_vectors() {
+        let secret = BASE32_NOPAD.encode(RFC_SECRET);
+        assert_eq!(check_code(&secret, "287082", 59, None), Some(1));
+        assert_eq!(
+            check_code(&secret, "081804", 1111111109, None),
+            Some(37037036)
+        );
+        assert!(check_code(&secret, "005924", 1234567890, None).is_some());
+        assert_eq!(check_code(&secret, "005925", 1234567890, None), None);
+    }
+
+    #[test]
+    fn test_clock_drift() {
+        let secret = BASE32_NOPAD.encode(RFC_SECRET);
+        assert!(check_code(&secret, "081804", 1111111109 + 30, None).is_some());
+        assert!(check_code(&secret, "081804", 1111111109 - 30, None).is_some());
+        assert_eq!(check_code(&secret, "081804", 1111111109 + 90, None), None);
+    }
+
+    #[test]
+    fn test_replayed_codes() {
+        let secret = BASE32_NOPAD.encode(RFC_SECRET);
+        let step = check_code(&secret, "081804", 1111111109, None).unwrap();
+        assert_eq!(check_code(&secret, "081804", 1111111109, Some(step)), None);
+        assert_eq!(
+            check_code(&secret, "081804", 1111111109, Some(step + 1)),
+            None
+        );
+        assert_eq!(
+            check_code(&secret, "081804", 1111111109, Some(step - 1)),
+            Some(step)
+        );
+    }
+
+    #[test]
+    fn test_invalid_codes() {
+        let secret = generate_secret(&mut rand::rngs::OsRng);
+        assert_eq!(secret.len(), 32);
+        assert_eq!(check_code(&secret, "", 0, None), None);
+        assert_eq!(check_code(&secret, "12345", 0, None), None);
+        assert_eq!(check_code(&secret, "+12345", 0, None), None);
+        assert_eq!(check_code("not base32!", "123456", 0, None), None);
+    }
+
+    #[test]
+    fn test_otpauth_uri() {
+        assert_eq!(
+            get_otpauth_uri("ABCD", "bob smith", "LLDAP"),
+            "otpauth://totp/LLDAP:bob%20smith?secret=ABCD&issuer=LLDAP&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
@@ -1,19 +1,24 @@
 use async_trait::async_trait;
+use chrono::NaiveDateTime;
 use lldap_domain::{
     requests::{
-        CreateAttributeRequest, CreateGroupRequest, CreateUserRequest, UpdateGroupRequest,
-        UpdateUserRequest,
+        AddWebauthnCredentialRequest, CreateAppPasswordRequest, CreateAttributeRequest,
+        CreateGroupRequest, CreateSudoRoleRequest, CreateUserRequest, UpdateGroupRequest,
+        UpdateSudoRoleRequest, UpdateUserRequest,
     },
     schema::{AttributeList, AttributeSchema, Schema},
     types::{
-        AttributeName, AttributeType, Group, GroupDetails, GroupId, LdapObjectClass, User,
-        UserAndGroups, UserId,
+        AppPassword, AppPasswordId, AttributeName, AttributeType, Group, GroupDetails, GroupId,
+        LdapObjectClass, MatchingRule, SudoRole, SudoRoleId, User, UserAndGroups, UserId,
+        WebauthnCredential, WebauthnCredentialId,
     },
 };
 use lldap_domain_handlers::handler::{
-    BackendHandler, BindRequest, GroupBackendHandler, GroupListerBackendHandler,
-    GroupRequestFilter, LoginHandler, ReadSchemaBackendHandler, SchemaBackendHandler,
-    UserBackendHandler, UserListerBackendHandler, UserRequestFilter,
+    AppPasswordBackendHandler, BackendHandler, BindRequest, BindResult, GroupBackendHandler,
+    GroupListerBackendHandler, GroupMemberFilter, GroupRequestFilter, LoginHandler,
+    MfaBackendHandler, PasswordState, ReadSchemaBackendHandler, SchemaBackendHandler,
+    SudoRoleBackendHandler, SudoRoleListerBackendHandler, TotpEnrollment, UserBackendHandler,
+    UserListerBackendHandler, UserRequestFilter, UserSortOrder, WebauthnBackendHandler,
 };
 use lldap_domain_model::error::Result;
 use lldap_opaque_handler::{OpaqueHandler, login, registration};
@@ -26,7 +31,10 @@ mockall::mock! {
     }
     #[async_trait]
     impl LoginHandler for TestBackendHandler {
-        async fn bind(&self, request: BindRequest) -> Result<()>;
+        async fn bind(&self, request: BindRequest) -> Result<BindResult>;
+        async fn get_password_state(&self, user_id: &UserId) -> Result<PasswordState>;
+        async fn require_password_change(&self, user_id: &UserId) -> Result<()>;
+        async fn check_password_policy(&self, user_id: &UserId, password: &str) -> Result<()>;
     }
     #[async_trait]
     impl GroupListerBackendHandler for TestBackendHandler {
@@ -38,10 +46,24 @@ mockall::mock! {
         async fn update_group(&self, request: UpdateGroupRequest) -> Result<()>;
         async fn create_group(&self, request: CreateGroupRequest) -> Result<GroupId>;
         async fn delete_group(&self, group_id: GroupId) -> Result<()>;
+        async fn set_group_member_filter(
+            &self,
+            group_id: GroupId,
+            member_filter: Option<GroupMemberFilter>,
+        ) -> Result<()>;
     }
     #[async_trait]
     impl UserListerBackendHandler for TestBackendHandler {
         async fn list_users(&self, filters: Option<UserRequestFilter>, get_groups: bool) -> Result<Vec<UserAndGroups>>;
+        async fn count_users(&self, filters: Option<UserRequestFilter>) -> Result<u64>;
+        async fn list_users_sorted(
+            &self,
+            filters: Option<UserRequestFilter>,
+            get_groups: bool,
+            order: UserSortOrder,
+            offset: u64,
+            limit: Option<u64>,
+        ) -> Result<Vec<UserAndGroups>>;
     }
     #[async_trait]
     impl UserBackendHandler for TestBackendHandler {
@@ -52,6 +74,12 @@ mockall::mock! {
         async fn get_user_groups(&self, user_id: &UserId) -> Result<HashSet<GroupDetails>>;
         async fn add_user_to_group(&self, user_id: &UserId, group_id: GroupId) -> Result<()>;
         async fn remove_user_from_group(&self, user_id: &UserId, group_id: GroupId) -> Result<()>;
+        async fn set_user_disabled(&self, user_id: &UserId, disabled: bool) -> Result<()>;
+        async fn set_user_expiration_date(
+            &self,
+            user_id: &UserId,
+            expiration_date: Option<NaiveDateTime>,
+        ) -> Result<()>;
     }
     #[async_trait]
     impl ReadSchemaBackendHandler for TestBackendHandler {
@@ -69,6 +97,39 @@ mockall::mock! {
         async fn delete_group_object_class(&self, name: &LdapObjectClass) -> Result<()>;
     }
     #[async_trait]
+    impl SudoRoleListerBackendHandler for TestBackendHandler {
+        async fn list_sudo_roles(&self) -> Result<Vec<SudoRole>>;
+    }
+    #[async_trait]
+    impl SudoRoleBackendHandler for TestBackendHandler {
+        async fn get_sudo_role(&self, id: SudoRoleId) -> Result<SudoRole>;
+        async fn create_sudo_role(&self, request: CreateSudoRoleRequest) -> Result<SudoRoleId>;
+        async fn update_sudo_role(&self, request: UpdateSudoRoleRequest) -> Result<()>;
+        async fn delete_sudo_role(&self, id: SudoRoleId) -> Result<()>;
+    }
+    #[async_trait]
+    impl MfaBackendHandler for TestBackendHandler {
+        async fn start_totp_enrollment(&self, user_id: &UserId) -> Result<TotpEnrollment>;
+        async fn confirm_totp_enrollment(&self, user_id: &UserId, code: &str) -> Result<Vec<String>>;
+        async fn verify_mfa_code(&self, user_id: &UserId, code: &str) -> Result<()>;
+        async fn reset_mfa(&self, user_id: &UserId) -> Result<()>;
+    }
+    #[async_trait]
+    impl WebauthnBackendHandler for TestBackendHandler {
+        async fn list_webauthn_credentials(&self, user_id: &UserId) -> Result<Vec<WebauthnCredential>>;
+        async fn get_webauthn_credential(&self, credential_id: &[u8]) -> Result<WebauthnCredential>;
+        async fn add_webauthn_credential(&self, request: AddWebauthnCredentialRequest) -> Result<()>;
+        async fn use_webauthn_credential(&self, credential_id: &[u8], updated_passkey: Option<String>) -> Result<()>;
+        async fn rename_webauthn_credential(&self, user_id: &UserId, id: WebauthnCredentialId, name: &str) -> Result<()>;
+        async fn delete_webauthn_credential(&self, user_id: &UserId, id: WebauthnCredentialId) -> Result<()>;
+    }
+    #[async_trait]
+    impl AppPasswordBackendHandler for TestBackendHandler {
+        async fn list_app_passwords(&self, user_id: &UserId) -> Result<Vec<AppPassword>>;
+        async fn create_app_password(&self, request: CreateAppPasswordRequest) -> Result<String>;
+        async fn delete_app_password(&self, user_id: &UserId, id: AppPasswordId) -> Result<()>;
+    }
+    #[async_trait]
     impl BackendHandler for TestBackendHandler {}
     #[async_trait]
     impl OpaqueHandler for TestBackendHandler {
@@ -84,7 +145,7 @@ mockall::mock! {
         async fn registration_finish(
             &self,
             request: registration::ClientRegistrationFinishRequest
-        ) -> Result<()>;
+        ) -> Result<UserId>;
     }
 }
 
@@ -101,6 +162,7 @@ pub fn setup_default_schema(mock: &mut MockTestBackendHandler) {
                         is_editable: true,
                         is_hardcoded: true,
                         is_readonly: false,
+                        matching_rule: MatchingRule::OctetStringMatch,
                     },
                     AttributeSchema {
                         name: "first_name".into(),
@@ -110,6 +172,7 @@ pub fn setup_default_schema(mock: &mut MockTestBackendHandler) {
                         is_editable: true,
                         is_hardcoded: true,
                         is_readonly: false,
+                        matching_rule: MatchingRule::CaseIgnoreMatch,
                     },
                     AttributeSchema {
                         name: "last_name".into(),
@@ -119,6 +182,7 @@ pub fn setup_default_schema(mock: &mut MockTestBackendHandler) {
                         is_editable: true,
                         is_hardcoded: true,
                         is_readonly: false,
+                        matching_rule: MatchingRule::CaseIgnoreMatch,
                     },
                 ],
             },
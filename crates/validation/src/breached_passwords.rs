@@ -0,0 +1,405 @@
+//! Offline check of the passwords against a list of breached passwords, such as the one from
+//! [Have I Been Pwned](https://haveibeenpwned.com/Passwords).
+//!
+//! The list is identified by the SHA-1 of the passwords. Like the HIBP API, the clients only send
+//! the first 5 hex characters of the hash to the server (k-anonymity), and check the password
+//! against the range of hashes starting with this prefix.
+//!
+//! The server can read two formats:
+//!  - The HIBP text file, one `HASH:COUNT` line per password, sorted by hash. It's searched in
+//!    place, without loading it in memory.
+//!  - A bloom filter generated from it by [`write_bloom_filter`], much smaller but with a few
+//!    false positives. It's split in one fixed-size filter per prefix, so that the clients can
+//!    get the part that concerns them.
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+pub const BREACHED_PASSWORD_ERROR: &str =
+    "The password appears in a list of breached passwords, choose another one";
+
+/// Number of hex characters of the hash sent to the server.
+pub const PREFIX_LENGTH: usize = 5;
+/// Number of different prefixes: each hex character is 4 bits.
+const PREFIX_COUNT: usize = 1 << (4 * PREFIX_LENGTH);
+const HASH_HEX_LENGTH: usize = 40;
+const BLOOM_FILTER_MAGIC: &[u8; 8] = b"LLDAPBF1";
+/// Magic, bytes per prefix (u32 LE), number of hash functions (u8).
+const BLOOM_FILTER_HEADER_LENGTH: u64 = 8 + 4 + 1;
+
+pub type PasswordHash = [u8; 20];
+
+pub fn get_password_hash(password: &str) -> PasswordHash {
+    Sha1::digest(password.as_bytes()).into()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+/// The prefix of the hash to send to the server.
+pub fn get_hash_prefix(hash: &PasswordHash) -> String {
+    to_hex(hash)[..PREFIX_LENGTH].to_string()
+}
+
+pub fn is_valid_prefix(prefix: &str) -> bool {
+    prefix.len() == PREFIX_LENGTH && prefix.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn get_prefix_index(hash: &PasswordHash) -> usize {
+    (usize::from(hash[0]) << 12) | (usize::from(hash[1]) << 4) | (usize::from(hash[2]) >> 4)
+}
+
+/// The bits of the bloom filter set by a hash, using double hashing. The hash is already uniformly
+/// distributed, its bytes after the prefix are used directly.
+fn get_bloom_filter_bits(
+    hash: &PasswordHash,
+    hash_count: u8,
+    bit_count: u64,
+) -> impl Iterator<Item = u64> {
+    let h1 = u64::from_le_bytes(hash[4..12].try_into().unwrap());
+    let h2 = u64::from_le_bytes(hash[12..20].try_into().unwrap()) | 1;
+    (0..u64::from(hash_count)).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % bit_count)
+}
+
+/// The breached passwords whose hash starts with a given prefix.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum BreachedPasswordRange {
+    /// The rest of the hashes, in uppercase hex.
+    Suffixes(Vec<String>),
+    /// The part of the bloom filter for the prefix.
+    BloomFilter { hash_count: u8, bits: Vec<u8> },
+}
+
+impl BreachedPasswordRange {
+    /// Whether the password is in the range. The hash must start with the prefix of the range.
+    pub fn contains(&self, hash: &PasswordHash) -> bool {
+        match self {
+            BreachedPasswordRange::Suffixes(suffixes) => {
+                let suffix = &to_hex(hash)[PREFIX_LENGTH..];
+                suffixes.iter().any(|s| s.eq_ignore_ascii_case(suffix))
+            }
+            BreachedPasswordRange::BloomFilter { hash_count, bits } => {
+                !bits.is_empty()
+                    && get_bloom_filter_bits(hash, *hash_count, bits.len() as u64 * 8)
+                        .all(|bit| bits[(bit / 8) as usize] & (1 << (bit % 8)) != 0)
+            }
+        }
+    }
+}
+
+/// Parses a line of the HIBP file: the hash, optionally followed by `:COUNT`.
+fn parse_hash_line(line: &str) -> Option<&str> {
+    let hash = line.trim().split(':').next().unwrap_or_default();
+    (hash.len() == HASH_HEX_LENGTH && hash.chars().all(|c| c.is_ascii_hexdigit())).then_some(hash)
+}
+
+fn parse_hash(hex: &str) -> Option<PasswordHash> {
+    let mut hash = PasswordHash::default();
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(hash)
+}
+
+#[derive(Clone, Debug)]
+enum ListFormat {
+    SortedHashes {
+        file_length: u64,
+    },
+    BloomFilter {
+        bytes_per_prefix: u32,
+        hash_count: u8,
+    },
+}
+
+/// A file of breached passwords, read on demand.
+#[derive(Clone, Debug)]
+pub struct BreachedPasswordList {
+    path: PathBuf,
+    format: ListFormat,
+}
+
+impl BreachedPasswordList {
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let mut file = File::open(path)?;
+        let file_length = file.metadata()?.len();
+        let mut header = [0u8; BLOOM_FILTER_HEADER_LENGTH as usize];
+        let is_bloom_filter = file_length >= BLOOM_FILTER_HEADER_LENGTH
+            && file.read_exact(&mut header).is_ok()
+            && header.starts_with(BLOOM_FILTER_MAGIC);
+        let format = if is_bloom_filter {
+            let bytes_per_prefix = u32::from_le_bytes(header[8..12].try_into().unwrap());
+            let format = ListFormat::BloomFilter {
+                bytes_per_prefix,
+                hash_count: header[12],
+            };
+            let expected_length =
+                BLOOM_FILTER_HEADER_LENGTH + PREFIX_COUNT as u64 * u64::from(bytes_per_prefix);
+            if file_length != expected_length {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "Truncated bloom filter: expected {expected_length} bytes, got {file_length}"
+                    ),
+                ));
+            }
+            format
+        } else {
+            ListFormat::SortedHashes { file_length }
+        };
+        Ok(Self {
+            path: path.to_owned(),
+            format,
+        })
+    }
+
+    /// Returns the breached passwords whose hash starts with the prefix, which must be valid.
+    pub fn get_range(&self, prefix: &str) -> std::io::Result<BreachedPasswordRange> {
+        assert!(is_valid_prefix(prefix));
+        let mut file = BufReader::new(File::open(&self.path)?);
+        match self.format {
+            ListFormat::SortedHashes { file_length } => Ok(BreachedPasswordRange::Suffixes(
+                find_sorted_hashes(&mut file, file_length, &prefix.to_ascii_uppercase())?,
+            )),
+            ListFormat::BloomFilter {
+                bytes_per_prefix,
+                hash_count,
+            } => {
+                let prefix_index = usize::from_str_radix(prefix, 16).unwrap() as u64;
+                file.seek(SeekFrom::Start(
+                    BLOOM_FILTER_HEADER_LENGTH + prefix_index * u64::from(bytes_per_prefix),
+                ))?;
+                let mut bits = vec![0u8; bytes_per_prefix as usize];
+                file.read_exact(&mut bits)?;
+                Ok(BreachedPasswordRange::BloomFilter { hash_count, bits })
+            }
+        }
+    }
+
+    pub fn contains_password(&self, password: &str) -> std::io::Result<bool> {
+        let hash = get_password_hash(password);
+        Ok(self.get_range(&get_hash_prefix(&hash))?.contains(&hash))
+    }
+}
+
+/// Reads the first full line starting at or after `offset`.
+fn read_line_from<R: BufRead + Seek>(file: &mut R, offset: u64) -> std::io::Result<String> {
+    let mut line = String::new();
+    if offset > 0 {
+        // Skip the end of the previous line.
+        file.seek(SeekFrom::Start(offset - 1))?;
+        file.read_line(&mut line)?;
+        line.clear();
+    } else {
+        file.seek(SeekFrom::Start(0))?;
+    }
+    file.read_line(&mut line)?;
+    Ok(line)
+}
+
+/// Binary search in the file, sorted by hash, for the lines starting with the prefix.
+fn find_sorted_hashes<R: BufRead + Seek>(
+    file: &mut R,
+    file_length: u64,
+    prefix: &str,
+) -> std::io::Result<Vec<String>> {
+    let is_before_prefix =
+        |line: &str| !line.is_empty() && line.to_ascii_uppercase().as_str() < prefix;
+    let (mut low, mut high) = (0, file_length);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if is_before_prefix(&read_line_from(file, middle)?) {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    let mut line = read_line_from(file, low)?;
+    let mut suffixes = Vec::new();
+    while let Some(hash) = parse_hash_line(&line) {
+        let hash = hash.to_ascii_uppercase();
+        if !hash.starts_with(prefix) {
+            break;
+        }
+        suffixes.push(hash[PREFIX_LENGTH..].to_string());
+        line.clear();
+        file.read_line(&mut line)?;
+    }
+    Ok(suffixes)
+}
+
+/// Generates a bloom filter from a HIBP-style file of SHA-1 hashes, sorted by hash, with the
+/// given rate of false positives. Returns the number of hashes.
+pub fn write_bloom_filter(
+    input: &Path,
+    output: impl Write,
+    false_positive_rate: f64,
+) -> std::io::Result<u64> {
+    let invalid_data =
+        |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+    let read_hashes = || -> std::io::Result<_> {
+        Ok(BufReader::new(File::open(input)?)
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.as_ref().is_ok_and(|l| l.trim().is_empty()))
+            .map(move |(line_number, line)| {
+                let line = line?;
+                parse_hash_line(&line).and_then(parse_hash).ok_or_else(|| {
+                    invalid_data(format!("Invalid hash on line {}", line_number + 1))
+                })
+            }))
+    };
+    // First pass: the size of the filters is based on the most populated prefix.
+    let mut counts = vec![0u64; PREFIX_COUNT];
+    let mut previous_hash = None;
+    for hash in read_hashes()? {
+        let hash = hash?;
+        if previous_hash.is_some_and(|previous| previous > hash) {
+            return Err(invalid_data(
+                "The hashes must be sorted, like in the files from Have I Been Pwned".to_string(),
+            ));
+        }
+        previous_hash = Some(hash);
+        counts[get_prefix_index(&hash)] += 1;
+    }
+    let max_count = counts.iter().copied().max().unwrap_or_default().max(1) as f64;
+    let ln2 = std::f64::consts::LN_2;
+    let bit_count = (-max_count * false_positive_rate.ln() / (ln2 * ln2)).ceil();
+    let bytes_per_prefix = ((bit_count / 8.0).ceil() as u32).max(1);
+    let hash_count = ((f64::from(bytes_per_prefix) * 8.0 / max_count) * ln2)
+        .round()
+        .clamp(1.0, 32.0) as u8;
+
+    // Second pass: the hashes are sorted, so the filters can be written one prefix at a time.
+    let mut output = std::io::BufWriter::new(output);
+    output.write_all(BLOOM_FILTER_MAGIC)?;
+    output.write_all(&bytes_per_prefix.to_le_bytes())?;
+    output.write_all(&[hash_count])?;
+    let bit_count = u64::from(bytes_per_prefix) * 8;
+    let mut bits = vec![0u8; bytes_per_prefix as usize];
+    let mut current_prefix = 0;
+    let mut total = 0;
+    for hash in read_hashes()? {
+        let hash = hash?;
+        let prefix_index = get_prefix_index(&hash);
+        while current_prefix < prefix_index {
+            output.write_all(&bits)?;
+            bits.fill(0);
+            current_prefix += 1;
+        }
+        for bit in get_bloom_filter_bits(&hash, hash_count, bit_count) {
+            bits[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+        total += 1;
+    }
+    while current_prefix < PREFIX_COUNT {
+        output.write_all(&bits)?;
+        bits.fill(0);
+        current_prefix += 1;
+    }
+    output.flush()?;
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWORDS: [&str; 4] = ["password", "123456", "hunter2", "correct horse"];
+
+    fn write_hibp_file(dir: &Path) -> PathBuf {
+        let mut hashes: Vec<String> = PASSWORDS
+            .iter()
+            .map(|p| format!("{}:42", to_hex(&get_password_hash(p))))
+            .collect();
+        hashes.sort();
+        let path = dir.join("hibp.txt");
+        std::fs::write(&path, hashes.join("\r\n")).unwrap();
+        path
+    }
+
+    fn get_test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "lldap_breached_passwords_{name}_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_password_hash() {
+        let hash = get_password_hash("password");
+        assert_eq!(to_hex(&hash), "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8");
+        assert_eq!(get_hash_prefix(&hash), "5BAA6");
+        assert_eq!(get_prefix_index(&hash), 0x5BAA6);
+        assert!(is_valid_prefix("5baa6"));
+        assert!(!is_valid_prefix("5BAA"));
+        assert!(!is_valid_prefix("5BAAG"));
+    }
+
+    #[test]
+    fn test_sorted_hashes() {
+        let dir = get_test_dir("sorted");
+        let list = BreachedPasswordList::open(&write_hibp_file(&dir)).unwrap();
+        for password in PASSWORDS {
+            assert!(list.contains_password(password).unwrap(), "{password}");
+        }
+        assert!(!list.contains_password("not breached").unwrap());
+        assert_eq!(
+            list.get_range("5BAA6").unwrap(),
+            BreachedPasswordRange::Suffixes(vec![
+                "1E4C9B93F3F0682250B6CF8331B7EE68FD8".to_string()
+            ])
+        );
+        assert_eq!(
+            list.get_range("00000").unwrap(),
+            BreachedPasswordRange::Suffixes(vec![])
+        );
+        assert_eq!(
+            list.get_range("FFFFF").unwrap(),
+            BreachedPasswordRange::Suffixes(vec![])
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_bloom_filter() {
+        let dir = get_test_dir("bloom");
+        let filter_path = dir.join("filter.bin");
+        assert_eq!(
+            write_bloom_filter(
+                &write_hibp_file(&dir),
+                File::create(&filter_path).unwrap(),
+                0.001
+            )
+            .unwrap(),
+            PASSWORDS.len() as u64
+        );
+        let list = BreachedPasswordList::open(&filter_path).unwrap();
+        for password in PASSWORDS {
+            assert!(list.contains_password(password).unwrap(), "{password}");
+        }
+        assert!(!list.contains_password("not breached").unwrap());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_bloom_filter_unsorted_input() {
+        let dir = get_test_dir("unsorted");
+        let input = dir.join("unsorted.txt");
+        std::fs::write(
+            &input,
+            "FFFFF61E4C9B93F3F0682250B6CF8331B7EE68FD8\n5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8\n",
+        )
+        .unwrap();
+        assert!(write_bloom_filter(&input, std::io::sink(), 0.01).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
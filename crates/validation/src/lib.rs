@@ -1,3 +1,5 @@
 #![forbid(non_ascii_idents)]
 
 pub mod attributes;
+pub mod breached_passwords;
+pub mod password;
@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+
+/// The rules that new passwords have to follow.
+///
+/// It is shared between the server, which enforces it whenever it sees the password, and the
+/// clients (web app, `lldap_set_password`), which get it from the `/settings` endpoint.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_special: bool,
+    /// Refuse passwords that contain the user ID, email or display name.
+    pub forbid_user_attributes: bool,
+    /// Number of previous passwords that cannot be reused. 0 disables the password history.
+    pub history_size: usize,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            require_uppercase: false,
+            require_lowercase: false,
+            require_digit: false,
+            require_special: false,
+            forbid_user_attributes: false,
+            history_size: 0,
+        }
+    }
+}
+
+/// Values shorter than this are not checked against the password, to avoid refusing a password
+/// because the user ID is, e.g., "bo".
+const MIN_FORBIDDEN_VALUE_LENGTH: usize = 3;
+
+/// Checks the password against the policy, and returns the list of the rules it breaks.
+///
+/// `user_values` are the user ID, email, display name, etc. They are only checked when
+/// `forbid_user_attributes` is set.
+pub fn validate_password(
+    policy: &PasswordPolicy,
+    password: &str,
+    user_values: &[&str],
+) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    if password.chars().count() < policy.min_length {
+        errors.push(format!(
+            "The password must be at least {} characters long",
+            policy.min_length
+        ));
+    }
+    let mut check_class = |required: bool, description: &str, predicate: fn(&char) -> bool| {
+        if required && !password.chars().any(|c| predicate(&c)) {
+            errors.push(format!(
+                "The password must contain at least one {description}"
+            ));
+        }
+    };
+    check_class(policy.require_uppercase, "uppercase letter", |c| {
+        c.is_uppercase()
+    });
+    check_class(policy.require_lowercase, "lowercase letter", |c| {
+        c.is_lowercase()
+    });
+    check_class(policy.require_digit, "digit", |c| c.is_numeric());
+    check_class(policy.require_special, "special character", |c| {
+        !c.is_alphanumeric()
+    });
+    if policy.forbid_user_attributes {
+        let lowercase_password = password.to_lowercase();
+        if user_values
+            .iter()
+            .map(|v| v.trim().to_lowercase())
+            .filter(|v| v.chars().count() >= MIN_FORBIDDEN_VALUE_LENGTH)
+            .any(|v| lowercase_password.contains(&v))
+        {
+            errors.push(
+                "The password must not contain the user ID, email or display name".to_string(),
+            );
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy() {
+        let policy = PasswordPolicy::default();
+        assert_eq!(validate_password(&policy, "password", &["bob"]), Ok(()));
+        assert_eq!(
+            validate_password(&policy, "pass", &[]),
+            Err(vec![
+                "The password must be at least 8 characters long".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_character_classes() {
+        let policy = PasswordPolicy {
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_special: true,
+            ..Default::default()
+        };
+        assert_eq!(validate_password(&policy, "Passw0rd!", &[]), Ok(()));
+        assert_eq!(
+            validate_password(&policy, "password", &[]).unwrap_err(),
+            vec![
+                "The password must contain at least one uppercase letter".to_string(),
+                "The password must contain at least one digit".to_string(),
+                "The password must contain at least one special character".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_user_attributes() {
+        let policy = PasswordPolicy {
+            forbid_user_attributes: true,
+            ..Default::default()
+        };
+        let user_values = ["bob", "Bob@example.com", "Bob Smith", ""];
+        assert_eq!(
+            validate_password(&policy, "correct horse", &user_values),
+            Ok(())
+        );
+        assert!(validate_password(&policy, "myBOB@example.com", &user_values).is_err());
+        assert!(validate_password(&policy, "hello bob!", &user_values).is_err());
+        let short_values = ["bo"];
+        assert_eq!(
+            validate_password(&policy, "bonjour bo", &short_values),
+            Ok(())
+        );
+    }
+}
@@ -140,6 +140,28 @@ fn bind_ldap(
     }
 }
 
+/// The prefixes of the password hashes that LLDAP can import. The other passwords (e.g. clear
+/// text or MD5) are not migrated.
+const SUPPORTED_PASSWORD_HASH_PREFIXES: &[&str] = &[
+    "{ssha}",
+    "{ssha512}",
+    "{crypt}$6$",
+    "{crypt}$2",
+    "{argon2}",
+    "$6$",
+    "$2a$",
+    "$2b$",
+    "$2y$",
+    "$argon2",
+];
+
+fn is_supported_password_hash(password: &str) -> bool {
+    let password = password.to_ascii_lowercase();
+    SUPPORTED_PASSWORD_HASH_PREFIXES
+        .iter()
+        .any(|prefix| password.starts_with(prefix))
+}
+
 impl TryFrom<ResultEntry> for User {
     type Error = anyhow::Error;
 
@@ -195,6 +217,7 @@ impl TryFrom<ResultEntry> for User {
                 last_name,
                 avatar: avatar.map(base64::encode),
                 attributes: None,
+                legacy_password_hash: password.clone().filter(|p| is_supported_password_hash(p)),
             },
             password,
             entry.dn,
@@ -37,8 +37,10 @@ fn get_users_to_add(users: &[User], existing_users: &[String]) -> Result<Option<
             .map(|u| format!(
                 "\"{}\" ({})",
                 &u.user_input.id,
-                if u.password.is_some() {
-                    "with password"
+                if u.user_input.legacy_password_hash.is_some() {
+                    "with password hash"
+                } else if u.password.is_some() {
+                    "unsupported password hash, not migrated"
                 } else {
                     "no password"
                 }
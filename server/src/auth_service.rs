@@ -1,6 +1,7 @@
 use crate::{
     tcp_backend_handler::*,
     tcp_server::{AppState, TcpError, TcpResult, error_to_http_response},
+    webauthn::WebauthnCeremony,
 };
 use actix_web::{
     HttpRequest, HttpResponse,
@@ -18,14 +19,19 @@ use hmac::Hmac;
 use jwt::{SignWithKey, VerifyWithKey};
 use lldap_access_control::{ReadonlyBackendHandler, UserReadableBackendHandler};
 use lldap_auth::{
-    JWTClaims, access_control::ValidationResults, login, password_reset, registration,
+    JWTClaims, access_control::ValidationResults, login, password_reset, registration, webauthn,
+};
+use lldap_domain::{
+    requests::AddWebauthnCredentialRequest,
+    types::{GroupDetails, GroupName, UserId, WebauthnCredential},
 };
-use lldap_domain::types::{GroupDetails, GroupName, UserId};
 use lldap_domain_handlers::handler::{
-    BackendHandler, BindRequest, LoginHandler, UserRequestFilter,
+    BackendHandler, BindRequest, BindSource, LoginHandler, MfaBackendHandler, PasswordState,
+    UserRequestFilter, WebauthnBackendHandler,
 };
 use lldap_domain_model::{error::DomainError, model::UserColumn};
 use lldap_opaque_handler::OpaqueHandler;
+use lldap_validation::breached_passwords::{BreachedPasswordRange, PREFIX_LENGTH, is_valid_prefix};
 use sha2::Sha512;
 use std::{
     collections::HashSet,
@@ -35,6 +41,10 @@ use std::{
 };
 use time::ext::NumericalDuration;
 use tracing::{debug, info, instrument, warn};
+use webauthn_rs::{
+    Webauthn,
+    prelude::{AuthenticationResult, CredentialID, DiscoverableKey, Passkey, Uuid, WebauthnError},
+};
 
 type Token<S> = jwt::Token<jwt::Header, JWTClaims, S>;
 type SignedToken = Token<jwt::token::Signed>;
@@ -101,7 +111,7 @@ async fn get_refresh<Backend>(
     request: HttpRequest,
 ) -> TcpResult<HttpResponse>
 where
-    Backend: TcpBackendHandler + BackendHandler + 'static,
+    Backend: TcpBackendHandler + BackendHandler + LoginHandler + 'static,
 {
     let jwt_key = &data.jwt_key;
     let (refresh_token_hash, user) = get_refresh_token(request)?;
@@ -118,7 +128,13 @@ where
     if !path.ends_with('/') {
         path.push('/');
     };
-    let groups = data.get_readonly_handler().get_user_groups(&user).await?;
+    let mut groups = data.get_readonly_handler().get_user_groups(&user).await?;
+    let password_state = data.get_login_handler().get_password_state(&user).await?;
+    let (totp, webauthn) = get_second_factors(&data, &user).await?;
+    let must_enroll_mfa = must_enroll_mfa(&data, &groups, totp || webauthn);
+    if must_enroll_mfa {
+        groups.clear();
+    }
     let token = create_jwt(data.get_tcp_handler(), jwt_key, &user, groups).await;
     Ok(HttpResponse::Ok()
         .cookie(
@@ -132,6 +148,8 @@ where
         .json(&login::ServerLoginResponse {
             token: token.as_str().to_owned(),
             refresh_token: None,
+            must_change_password: password_state != PasswordState::Valid,
+            must_enroll_mfa,
         }))
 }
 
@@ -140,7 +158,7 @@ async fn get_refresh_handler<Backend>(
     request: HttpRequest,
 ) -> HttpResponse
 where
-    Backend: TcpBackendHandler + BackendHandler + 'static,
+    Backend: TcpBackendHandler + BackendHandler + LoginHandler + 'static,
 {
     get_refresh(data, request)
         .await
@@ -162,9 +180,13 @@ where
     let user_results = data
         .get_readonly_handler()
         .list_users(
-            Some(UserRequestFilter::Or(vec![
-                UserRequestFilter::UserId(UserId::new(user_string)),
-                UserRequestFilter::Equality(UserColumn::Email, user_string.to_owned()),
+            // Service accounts have no email, and cannot use the web UI anyway.
+            Some(UserRequestFilter::And(vec![
+                UserRequestFilter::Or(vec![
+                    UserRequestFilter::UserId(UserId::new(user_string)),
+                    UserRequestFilter::Equality(UserColumn::Email, user_string.to_owned()),
+                ]),
+                UserRequestFilter::ServiceAccount(false),
             ])),
             false,
         )
@@ -288,11 +310,7 @@ where
     data.get_tcp_handler()
         .delete_refresh_token(refresh_token_hash)
         .await?;
-    let new_blacklisted_jwt_hashes = data.get_tcp_handler().blacklist_jwts(&user).await?;
-    let mut jwt_blacklist = data.jwt_blacklist.write().unwrap();
-    for jwt_hash in new_blacklisted_jwt_hashes {
-        jwt_blacklist.insert(jwt_hash);
-    }
+    data.get_tcp_handler().blacklist_jwts(&user).await?;
     let mut path = data.server_url.path().to_string();
     if !path.ends_with('/') {
         path.push('/');
@@ -350,17 +368,40 @@ where
         .unwrap_or_else(error_to_api_response)
 }
 
+/// Members of the groups requiring a second factor only get a token without groups until they
+/// enroll one: it is enough to set it up, but not to use their permissions.
+fn must_enroll_mfa<Backend>(
+    data: &AppState<Backend>,
+    groups: &HashSet<GroupDetails>,
+    mfa_enabled: bool,
+) -> bool {
+    !mfa_enabled
+        && groups
+            .iter()
+            .any(|g| data.mfa_required_groups.contains(&g.display_name))
+}
+
 #[instrument(skip_all, level = "debug")]
 async fn get_login_successful_response<Backend>(
     data: &web::Data<AppState<Backend>>,
     name: &UserId,
+    password_state: PasswordState,
+    mfa_enabled: bool,
 ) -> TcpResult<HttpResponse>
 where
     Backend: TcpBackendHandler + BackendHandler,
 {
     // The authentication was successful, we need to fetch the groups to create the JWT
     // token.
-    let groups = data.get_readonly_handler().get_user_groups(name).await?;
+    let mut groups = data.get_readonly_handler().get_user_groups(name).await?;
+    let must_enroll_mfa = must_enroll_mfa(data, &groups, mfa_enabled);
+    if must_enroll_mfa {
+        info!(
+            r#"User "{}" has to enroll a second factor before getting their permissions"#,
+            name
+        );
+        groups.clear();
+    }
     let (refresh_token, max_age) = data.get_tcp_handler().create_refresh_token(name).await?;
     let token = create_jwt(data.get_tcp_handler(), &data.jwt_key, name, groups).await;
     let refresh_token_plus_name = refresh_token + "+" + name.as_str();
@@ -388,25 +429,101 @@ where
         .json(&login::ServerLoginResponse {
             token: token.as_str().to_owned(),
             refresh_token: Some(refresh_token_plus_name),
+            must_change_password: password_state != PasswordState::Valid,
+            must_enroll_mfa,
         }))
 }
 
+/// Whether the user can log in with a TOTP code and with a security key. The security keys are
+/// ignored if WebAuthn is not available.
+async fn get_second_factors<Backend>(
+    data: &AppState<Backend>,
+    name: &UserId,
+) -> TcpResult<(bool, bool)>
+where
+    Backend: BackendHandler,
+{
+    let totp = data
+        .get_readonly_handler()
+        .get_user_details(name)
+        .await?
+        .mfa_enabled;
+    let webauthn = data.webauthn.is_some()
+        && !data
+            .get_webauthn_handler()
+            .list_webauthn_credentials(name)
+            .await?
+            .is_empty();
+    Ok((totp, webauthn))
+}
+
+/// Called once the password is checked: the tokens are only issued after the second factor, if
+/// the user has one.
+async fn get_password_login_response<Backend>(
+    data: &web::Data<AppState<Backend>>,
+    name: &UserId,
+    password_state: PasswordState,
+) -> TcpResult<HttpResponse>
+where
+    Backend: TcpBackendHandler + BackendHandler,
+{
+    let (totp, webauthn) = get_second_factors(data, name).await?;
+    if !totp && !webauthn {
+        return get_login_successful_response(data, name, password_state, false).await;
+    }
+    let mfa_token = data.pending_mfa_logins.start(name.clone(), password_state);
+    Ok(HttpResponse::Ok().json(&login::ServerMfaRequiredResponse {
+        mfa_token,
+        totp,
+        webauthn,
+    }))
+}
+
+#[instrument(skip_all, level = "debug")]
+async fn mfa_verify<Backend>(
+    data: web::Data<AppState<Backend>>,
+    request: web::Json<login::ClientMfaVerifyRequest>,
+) -> TcpResult<HttpResponse>
+where
+    Backend: TcpBackendHandler + BackendHandler + 'static,
+{
+    let login::ClientMfaVerifyRequest { mfa_token, code } = request.into_inner();
+    let (name, password_state) = data.pending_mfa_logins.attempt(&mfa_token).ok_or_else(|| {
+        TcpError::UnauthorizedError(
+            "Invalid or expired login, please enter the password again".to_string(),
+        )
+    })?;
+    data.get_mfa_handler().verify_mfa_code(&name, &code).await?;
+    data.pending_mfa_logins.finish(&mfa_token);
+    get_login_successful_response(&data, &name, password_state, true).await
+}
+
+async fn mfa_verify_handler<Backend>(
+    data: web::Data<AppState<Backend>>,
+    request: web::Json<login::ClientMfaVerifyRequest>,
+) -> HttpResponse
+where
+    Backend: TcpBackendHandler + BackendHandler + 'static,
+{
+    mfa_verify(data, request)
+        .await
+        .unwrap_or_else(error_to_http_response)
+}
+
 #[instrument(skip_all, level = "debug")]
 async fn opaque_login_finish<Backend>(
     data: web::Data<AppState<Backend>>,
     request: web::Json<login::ClientLoginFinishRequest>,
 ) -> TcpResult<HttpResponse>
 where
-    Backend: TcpBackendHandler + BackendHandler + OpaqueHandler + 'static,
+    Backend: TcpBackendHandler + BackendHandler + OpaqueHandler + LoginHandler + 'static,
 {
-    match data
+    let name = data
         .get_opaque_handler()
         .login_finish(request.into_inner())
-        .await
-    {
-        Ok(name) => get_login_successful_response(&data, &name).await,
-        Err(e) => Err(e.into()),
-    }
+        .await?;
+    let password_state = data.get_login_handler().get_password_state(&name).await?;
+    get_password_login_response(&data, &name, password_state).await
 }
 
 async fn opaque_login_finish_handler<Backend>(
@@ -414,7 +531,7 @@ async fn opaque_login_finish_handler<Backend>(
     request: web::Json<login::ClientLoginFinishRequest>,
 ) -> HttpResponse
 where
-    Backend: TcpBackendHandler + BackendHandler + OpaqueHandler + 'static,
+    Backend: TcpBackendHandler + BackendHandler + OpaqueHandler + LoginHandler + 'static,
 {
     opaque_login_finish(data, request)
         .await
@@ -432,10 +549,40 @@ where
     let login::ClientSimpleLoginRequest { username, password } = request.into_inner();
     let bind_request = BindRequest {
         name: username.clone(),
-        password,
+        password: password.clone(),
+        source: BindSource::Http,
     };
-    data.get_login_handler().bind(bind_request).await?;
-    get_login_successful_response(&data, &username).await
+    let mut password_state = data
+        .get_login_handler()
+        .bind(bind_request)
+        .await?
+        .password_state;
+    if password_state == PasswordState::Valid && is_password_breached(&data, password).await? {
+        info!(
+            r#"Password of user "{}" is breached, requiring a change"#,
+            &username
+        );
+        data.get_login_handler()
+            .require_password_change(&username)
+            .await?;
+        password_state = PasswordState::MustChange;
+    }
+    get_password_login_response(&data, &username, password_state).await
+}
+
+async fn is_password_breached<Backend>(
+    data: &web::Data<AppState<Backend>>,
+    password: String,
+) -> TcpResult<bool> {
+    let Some(breached_passwords) = data.breached_passwords.clone() else {
+        return Ok(false);
+    };
+    web::block(move || breached_passwords.contains_password(&password))
+        .await
+        .map_err(|e| TcpError::InternalServerError(e.to_string()))?
+        .map_err(|e| {
+            TcpError::InternalServerError(format!("Could not read the breached passwords: {e}"))
+        })
 }
 
 async fn simple_login_handler<Backend>(
@@ -450,6 +597,42 @@ where
         .unwrap_or_else(error_to_http_response)
 }
 
+#[instrument(skip_all, level = "debug")]
+async fn get_breached_password_range<Backend>(
+    data: web::Data<AppState<Backend>>,
+    request: HttpRequest,
+) -> TcpResult<BreachedPasswordRange> {
+    let prefix = request
+        .match_info()
+        .get("prefix")
+        .ok_or_else(|| TcpError::BadRequest("Missing hash prefix".to_owned()))?
+        .to_ascii_uppercase();
+    if !is_valid_prefix(&prefix) {
+        return Err(TcpError::BadRequest(format!(
+            "Invalid hash prefix, expected {PREFIX_LENGTH} hexadecimal characters"
+        )));
+    }
+    let breached_passwords = data.breached_passwords.clone().ok_or_else(|| {
+        TcpError::NotFoundError("No list of breached passwords configured".to_owned())
+    })?;
+    web::block(move || breached_passwords.get_range(&prefix))
+        .await
+        .map_err(|e| TcpError::InternalServerError(e.to_string()))?
+        .map_err(|e| {
+            TcpError::InternalServerError(format!("Could not read the breached passwords: {e}"))
+        })
+}
+
+async fn get_breached_password_range_handler<Backend>(
+    data: web::Data<AppState<Backend>>,
+    request: HttpRequest,
+) -> HttpResponse {
+    get_breached_password_range(data, request)
+        .await
+        .map(|range| HttpResponse::Ok().json(range))
+        .unwrap_or_else(error_to_http_response)
+}
+
 #[instrument(skip_all, level = "debug")]
 async fn opaque_register_start<Backend>(
     request: actix_web::HttpRequest,
@@ -457,17 +640,17 @@ async fn opaque_register_start<Backend>(
     data: web::Data<AppState<Backend>>,
 ) -> TcpResult<registration::ServerRegistrationStartResponse>
 where
-    Backend: BackendHandler + OpaqueHandler + 'static,
+    Backend: TcpBackendHandler + BackendHandler + OpaqueHandler + 'static,
 {
     use actix_web::FromRequest;
     let inner_payload = &mut payload.into_inner();
-    let validation_result = BearerAuth::from_request(&request, inner_payload)
-        .await
-        .ok()
-        .and_then(|bearer| check_if_token_is_valid(&data, bearer.token()).ok())
-        .ok_or_else(|| {
-            TcpError::UnauthorizedError("Not authorized to change the user's password".to_string())
-        })?;
+    let validation_result = match BearerAuth::from_request(&request, inner_payload).await {
+        Ok(bearer) => check_if_token_is_valid(&data, bearer.token()).ok(),
+        Err(_) => None,
+    }
+    .ok_or_else(|| {
+        TcpError::UnauthorizedError("Not authorized to change the user's password".to_string())
+    })?;
     let registration_start_request =
         web::Json::<registration::ClientRegistrationStartRequest>::from_request(
             &request,
@@ -500,7 +683,7 @@ async fn opaque_register_start_handler<Backend>(
     data: web::Data<AppState<Backend>>,
 ) -> ApiResult<registration::ServerRegistrationStartResponse>
 where
-    Backend: BackendHandler + OpaqueHandler + 'static,
+    Backend: TcpBackendHandler + BackendHandler + OpaqueHandler + 'static,
 {
     opaque_register_start(request, payload, data)
         .await
@@ -511,25 +694,351 @@ where
 #[instrument(skip_all, level = "debug")]
 async fn opaque_register_finish<Backend>(
     data: web::Data<AppState<Backend>>,
+    bearer: Option<BearerAuth>,
     request: web::Json<registration::ClientRegistrationFinishRequest>,
 ) -> TcpResult<HttpResponse>
 where
-    Backend: TcpBackendHandler + BackendHandler + OpaqueHandler + 'static,
+    Backend: TcpBackendHandler + BackendHandler + OpaqueHandler + LoginHandler + 'static,
 {
-    data.get_opaque_handler()
+    let user_id = data
+        .get_opaque_handler()
         .registration_finish(request.into_inner())
         .await?;
+    // A password set by someone else, e.g. an admin with `set-password`, is only temporary.
+    let set_by_other_user = match bearer {
+        Some(bearer) => check_if_token_is_valid(&data, bearer.token())
+            .is_ok_and(|validation_result| validation_result.user != user_id),
+        None => false,
+    };
+    if set_by_other_user {
+        data.get_login_handler()
+            .require_password_change(&user_id)
+            .await?;
+    }
     Ok(HttpResponse::Ok().finish())
 }
 
 async fn opaque_register_finish_handler<Backend>(
     data: web::Data<AppState<Backend>>,
+    bearer: Option<BearerAuth>,
     request: web::Json<registration::ClientRegistrationFinishRequest>,
 ) -> HttpResponse
 where
-    Backend: TcpBackendHandler + BackendHandler + OpaqueHandler + 'static,
+    Backend: TcpBackendHandler + BackendHandler + OpaqueHandler + LoginHandler + 'static,
 {
-    opaque_register_finish(data, request)
+    opaque_register_finish(data, bearer, request)
+        .await
+        .unwrap_or_else(error_to_http_response)
+}
+
+fn get_webauthn<Backend>(data: &AppState<Backend>) -> TcpResult<&Webauthn> {
+    data.webauthn.as_deref().ok_or_else(|| {
+        TcpError::NotFoundError("WebAuthn is not available on this server".to_string())
+    })
+}
+
+fn webauthn_error(e: WebauthnError) -> TcpError {
+    TcpError::UnauthorizedError(format!("WebAuthn error: {e}"))
+}
+
+fn parse_passkey(credential: &WebauthnCredential) -> TcpResult<Passkey> {
+    serde_json::from_str(&credential.passkey).map_err(|e| {
+        TcpError::InternalServerError(format!("Invalid stored WebAuthn credential: {e}"))
+    })
+}
+
+async fn get_webauthn_user<Backend>(
+    data: &AppState<Backend>,
+    bearer: &BearerAuth,
+) -> TcpResult<UserId>
+where
+    Backend: TcpBackendHandler + BackendHandler,
+{
+    check_if_token_is_valid(data, bearer.token())
+        .map(|validation_result| validation_result.user)
+        .map_err(|_| {
+            TcpError::UnauthorizedError("Not authorized to add a security key".to_string())
+        })
+}
+
+#[instrument(skip_all, level = "debug")]
+async fn webauthn_register_start<Backend>(
+    data: web::Data<AppState<Backend>>,
+    bearer: BearerAuth,
+) -> TcpResult<webauthn::ServerRegistrationStartResponse>
+where
+    Backend: TcpBackendHandler + BackendHandler + 'static,
+{
+    let webauthn = get_webauthn(&data)?;
+    let user_id = get_webauthn_user(&data, &bearer).await?;
+    let user = data
+        .get_readonly_handler()
+        .get_user_details(&user_id)
+        .await?;
+    let unique_id = Uuid::parse_str(user.uuid.as_str())
+        .map_err(|e| TcpError::InternalServerError(format!("Invalid user UUID: {e}")))?;
+    // Don't let the browser register the same authenticator twice.
+    let existing_credentials = data
+        .get_webauthn_handler()
+        .list_webauthn_credentials(&user_id)
+        .await?
+        .into_iter()
+        .map(|credential| CredentialID::from(credential.credential_id))
+        .collect();
+    let (options, state) = webauthn
+        .start_passkey_registration(
+            unique_id,
+            user_id.as_str(),
+            user.display_name.as_deref().unwrap_or(user_id.as_str()),
+            Some(existing_credentials),
+        )
+        .map_err(webauthn_error)?;
+    let state_token = data
+        .pending_webauthn_ceremonies
+        .start(WebauthnCeremony::Registration { user_id, state });
+    Ok(webauthn::ServerRegistrationStartResponse {
+        state_token,
+        options,
+    })
+}
+
+async fn webauthn_register_start_handler<Backend>(
+    data: web::Data<AppState<Backend>>,
+    bearer: BearerAuth,
+) -> ApiResult<webauthn::ServerRegistrationStartResponse>
+where
+    Backend: TcpBackendHandler + BackendHandler + 'static,
+{
+    webauthn_register_start(data, bearer)
+        .await
+        .map(|res| ApiResult::Left(web::Json(res)))
+        .unwrap_or_else(error_to_api_response)
+}
+
+#[instrument(skip_all, level = "debug")]
+async fn webauthn_register_finish<Backend>(
+    data: web::Data<AppState<Backend>>,
+    bearer: BearerAuth,
+    request: web::Json<webauthn::ClientRegistrationFinishRequest>,
+) -> TcpResult<HttpResponse>
+where
+    Backend: TcpBackendHandler + BackendHandler + 'static,
+{
+    let webauthn = get_webauthn(&data)?;
+    let user_id = get_webauthn_user(&data, &bearer).await?;
+    let webauthn::ClientRegistrationFinishRequest {
+        state_token,
+        name,
+        credential,
+    } = request.into_inner();
+    let state = match data.pending_webauthn_ceremonies.take(&state_token) {
+        Some(WebauthnCeremony::Registration {
+            user_id: registering_user,
+            state,
+        }) if registering_user == user_id => state,
+        _ => {
+            return Err(TcpError::BadRequest(
+                "Invalid or expired registration, please try again".to_string(),
+            ));
+        }
+    };
+    let passkey = webauthn
+        .finish_passkey_registration(&credential, &state)
+        .map_err(webauthn_error)?;
+    let credential_id: &[u8] = passkey.cred_id().as_ref();
+    let name = name.trim();
+    data.get_webauthn_handler()
+        .add_webauthn_credential(AddWebauthnCredentialRequest {
+            user_id,
+            credential_id: credential_id.to_vec(),
+            name: if name.is_empty() {
+                "Security key".to_owned()
+            } else {
+                name.to_owned()
+            },
+            passkey: serde_json::to_string(&passkey)
+                .map_err(|e| TcpError::InternalServerError(e.to_string()))?,
+        })
+        .await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn webauthn_register_finish_handler<Backend>(
+    data: web::Data<AppState<Backend>>,
+    bearer: BearerAuth,
+    request: web::Json<webauthn::ClientRegistrationFinishRequest>,
+) -> HttpResponse
+where
+    Backend: TcpBackendHandler + BackendHandler + 'static,
+{
+    webauthn_register_finish(data, bearer, request)
+        .await
+        .unwrap_or_else(error_to_http_response)
+}
+
+#[instrument(skip_all, level = "debug")]
+async fn webauthn_login_start<Backend>(
+    data: web::Data<AppState<Backend>>,
+    request: web::Json<webauthn::ClientLoginStartRequest>,
+) -> TcpResult<webauthn::ServerLoginStartResponse>
+where
+    Backend: TcpBackendHandler + BackendHandler + 'static,
+{
+    let webauthn = get_webauthn(&data)?;
+    let (options, ceremony) = match request.into_inner().mfa_token {
+        Some(mfa_token) => {
+            let (user_id, password_state) =
+                data.pending_mfa_logins.attempt(&mfa_token).ok_or_else(|| {
+                    TcpError::UnauthorizedError(
+                        "Invalid or expired login, please enter the password again".to_string(),
+                    )
+                })?;
+            let passkeys = data
+                .get_webauthn_handler()
+                .list_webauthn_credentials(&user_id)
+                .await?
+                .iter()
+                .map(parse_passkey)
+                .collect::<TcpResult<Vec<_>>>()?;
+            let (options, state) = webauthn
+                .start_passkey_authentication(&passkeys)
+                .map_err(webauthn_error)?;
+            (
+                options,
+                WebauthnCeremony::SecondFactor {
+                    mfa_token,
+                    user_id,
+                    password_state,
+                    state,
+                },
+            )
+        }
+        None => {
+            let (options, state) = webauthn
+                .start_discoverable_authentication()
+                .map_err(webauthn_error)?;
+            (options, WebauthnCeremony::Passwordless { state })
+        }
+    };
+    let state_token = data.pending_webauthn_ceremonies.start(ceremony);
+    Ok(webauthn::ServerLoginStartResponse {
+        state_token,
+        options,
+    })
+}
+
+async fn webauthn_login_start_handler<Backend>(
+    data: web::Data<AppState<Backend>>,
+    request: web::Json<webauthn::ClientLoginStartRequest>,
+) -> ApiResult<webauthn::ServerLoginStartResponse>
+where
+    Backend: TcpBackendHandler + BackendHandler + 'static,
+{
+    webauthn_login_start(data, request)
+        .await
+        .map(|res| ApiResult::Left(web::Json(res)))
+        .unwrap_or_else(error_to_api_response)
+}
+
+/// Records the use of the credential, saving its new signature counter. Fails if the user is
+/// disabled.
+async fn record_webauthn_use<Backend>(
+    data: &AppState<Backend>,
+    credential: &WebauthnCredential,
+    mut passkey: Passkey,
+    result: &AuthenticationResult,
+) -> TcpResult<()>
+where
+    Backend: BackendHandler,
+{
+    let updated_passkey = match passkey.update_credential(result) {
+        Some(true) => Some(
+            serde_json::to_string(&passkey)
+                .map_err(|e| TcpError::InternalServerError(e.to_string()))?,
+        ),
+        _ => None,
+    };
+    data.get_webauthn_handler()
+        .use_webauthn_credential(&credential.credential_id, updated_passkey)
+        .await?;
+    Ok(())
+}
+
+#[instrument(skip_all, level = "debug")]
+async fn webauthn_login_finish<Backend>(
+    data: web::Data<AppState<Backend>>,
+    request: web::Json<webauthn::ClientLoginFinishRequest>,
+) -> TcpResult<HttpResponse>
+where
+    Backend: TcpBackendHandler + BackendHandler + LoginHandler + 'static,
+{
+    let webauthn = get_webauthn(&data)?;
+    let webauthn::ClientLoginFinishRequest {
+        state_token,
+        credential,
+    } = request.into_inner();
+    let unknown_credential = || TcpError::UnauthorizedError("Unknown security key".to_string());
+    match data.pending_webauthn_ceremonies.take(&state_token) {
+        Some(WebauthnCeremony::SecondFactor {
+            mfa_token,
+            user_id,
+            password_state,
+            state,
+        }) => {
+            let result = webauthn
+                .finish_passkey_authentication(&credential, &state)
+                .map_err(webauthn_error)?;
+            let stored = data
+                .get_webauthn_handler()
+                .get_webauthn_credential(result.cred_id().as_ref())
+                .await
+                .map_err(|_| unknown_credential())?;
+            if stored.user_id != user_id {
+                return Err(unknown_credential());
+            }
+            record_webauthn_use(&data, &stored, parse_passkey(&stored)?, &result).await?;
+            data.pending_mfa_logins.finish(&mfa_token);
+            get_login_successful_response(&data, &user_id, password_state, true).await
+        }
+        Some(WebauthnCeremony::Passwordless { state }) => {
+            let (_, credential_id) = webauthn
+                .identify_discoverable_authentication(&credential)
+                .map_err(webauthn_error)?;
+            let stored = data
+                .get_webauthn_handler()
+                .get_webauthn_credential(credential_id)
+                .await
+                .map_err(|_| unknown_credential())?;
+            let passkey = parse_passkey(&stored)?;
+            let result = webauthn
+                .finish_discoverable_authentication(
+                    &credential,
+                    state,
+                    &[DiscoverableKey::from(&passkey)],
+                )
+                .map_err(webauthn_error)?;
+            record_webauthn_use(&data, &stored, passkey, &result).await?;
+            let password_state = data
+                .get_login_handler()
+                .get_password_state(&stored.user_id)
+                .await?;
+            info!(r#"User "{}" logged in with a passkey"#, &stored.user_id);
+            get_login_successful_response(&data, &stored.user_id, password_state, true).await
+        }
+        _ => Err(TcpError::UnauthorizedError(
+            "Invalid or expired login, please try again".to_string(),
+        )),
+    }
+}
+
+async fn webauthn_login_finish_handler<Backend>(
+    data: web::Data<AppState<Backend>>,
+    request: web::Json<webauthn::ClientLoginFinishRequest>,
+) -> HttpResponse
+where
+    Backend: TcpBackendHandler + BackendHandler + LoginHandler + 'static,
+{
+    webauthn_login_finish(data, request)
         .await
         .unwrap_or_else(error_to_http_response)
 }
@@ -591,7 +1100,7 @@ where
 }
 
 #[instrument(skip_all, level = "debug", err, ret)]
-pub(crate) fn check_if_token_is_valid<Backend: BackendHandler>(
+pub(crate) fn check_if_token_is_valid<Backend: TcpBackendHandler + BackendHandler>(
     state: &AppState<Backend>,
     token_str: &str,
 ) -> Result<ValidationResults, actix_web::Error> {
@@ -607,7 +1116,7 @@ pub(crate) fn check_if_token_is_valid<Backend: BackendHandler>(
         )));
     }
     let jwt_hash = default_hash(token_str);
-    if state.jwt_blacklist.read().unwrap().contains(&jwt_hash) {
+    if state.get_tcp_handler().is_jwt_blacklisted(jwt_hash) {
         return Err(ErrorUnauthorized("JWT was logged out"));
     }
     Ok(state.backend_handler.get_permissions_from_groups(
@@ -632,8 +1141,13 @@ where
             .route(web::post().to(opaque_login_finish_handler::<Backend>)),
     )
     .service(web::resource("/simple/login").route(web::post().to(simple_login_handler::<Backend>)))
+    .service(web::resource("/mfa/verify").route(web::post().to(mfa_verify_handler::<Backend>)))
     .service(web::resource("/refresh").route(web::get().to(get_refresh_handler::<Backend>)))
     .service(web::resource("/logout").route(web::get().to(get_logout_handler::<Backend>)))
+    .service(
+        web::resource("/breached_passwords/{prefix}")
+            .route(web::get().to(get_breached_password_range_handler::<Backend>)),
+    )
     .service(
         web::scope("/opaque/register")
             .wrap(CookieToHeaderTranslatorFactory)
@@ -645,6 +1159,26 @@ where
                 web::resource("/finish")
                     .route(web::post().to(opaque_register_finish_handler::<Backend>)),
             ),
+    )
+    .service(
+        web::scope("/webauthn/register")
+            .wrap(CookieToHeaderTranslatorFactory)
+            .service(
+                web::resource("/start")
+                    .route(web::post().to(webauthn_register_start_handler::<Backend>)),
+            )
+            .service(
+                web::resource("/finish")
+                    .route(web::post().to(webauthn_register_finish_handler::<Backend>)),
+            ),
+    )
+    .service(
+        web::resource("/webauthn/login/start")
+            .route(web::post().to(webauthn_login_start_handler::<Backend>)),
+    )
+    .service(
+        web::resource("/webauthn/login/finish")
+            .route(web::post().to(webauthn_login_finish_handler::<Backend>)),
     );
     if enable_password_reset {
         cfg.service(
@@ -0,0 +1,143 @@
+//! Serves the SSH public keys of a user in the authorized_keys format, to be used as sshd's
+//! `AuthorizedKeysCommand`.
+use crate::{
+    auth_service::check_if_token_is_valid,
+    tcp_backend_handler::TcpBackendHandler,
+    tcp_server::{AppState, TcpError, TcpResult, error_to_http_response},
+};
+use actix_web::{
+    FromRequest, HttpRequest, HttpResponse,
+    web::{self, ServiceConfig},
+};
+use actix_web_httpauth::extractors::{basic::BasicAuth, bearer::BearerAuth};
+use lldap_access_control::UserReadableBackendHandler;
+use lldap_auth::access_control::ValidationResults;
+use lldap_domain::types::{GroupName, UserId};
+use lldap_domain_handlers::handler::{
+    BackendHandler, BindRequest, BindSource, LoginHandler, PasswordState,
+};
+use lldap_domain_model::error::DomainError;
+use serde::Deserialize;
+use tracing::{debug, instrument};
+
+#[derive(Deserialize, Debug)]
+struct AuthorizedKeysQuery {
+    /// If set, only return the keys if the user is a member of this group.
+    group: Option<String>,
+}
+
+/// Authenticates the request, either with a JWT like the rest of the API, or with basic
+/// credentials since sshd has no way to refresh a token nor to enter a second factor: the
+/// password of a service account (from its allowed networks), or an app password of a user. The
+/// account is typically a member of lldap_strict_readonly.
+async fn get_validation_result<Backend>(
+    data: &AppState<Backend>,
+    request: &HttpRequest,
+) -> TcpResult<ValidationResults>
+where
+    Backend: TcpBackendHandler + BackendHandler + LoginHandler,
+{
+    if let Ok(bearer) = BearerAuth::extract(request).await {
+        return check_if_token_is_valid(data, bearer.token())
+            .map_err(|e| TcpError::UnauthorizedError(e.to_string()));
+    }
+    let credentials = BasicAuth::extract(request).await.map_err(|_| {
+        TcpError::UnauthorizedError("Missing bearer token or basic credentials".to_string())
+    })?;
+    let user_id = UserId::new(credentials.user_id());
+    let bind_result = data
+        .get_login_handler()
+        .bind(BindRequest {
+            name: user_id.clone(),
+            password: credentials.password().unwrap_or_default().to_string(),
+            source: BindSource::HttpAppPassword(request.peer_addr().map(|addr| addr.ip())),
+        })
+        .await?;
+    if bind_result.password_state != PasswordState::Valid {
+        return Err(TcpError::UnauthorizedError(format!(
+            "The password of user {user_id} has to be changed"
+        )));
+    }
+    Ok(data
+        .backend_handler
+        .get_permissions_for_user(user_id)
+        .await?)
+}
+
+#[instrument(skip_all, level = "debug")]
+async fn get_authorized_keys<Backend>(
+    data: web::Data<AppState<Backend>>,
+    request: HttpRequest,
+    user_id: web::Path<String>,
+    query: web::Query<AuthorizedKeysQuery>,
+) -> TcpResult<HttpResponse>
+where
+    Backend: TcpBackendHandler + BackendHandler + LoginHandler + 'static,
+{
+    let validation_result = get_validation_result(&data, &request).await?;
+    let user_id = UserId::new(&user_id);
+    debug!(?user_id, ?query);
+    let handler = data
+        .backend_handler
+        .get_readable_handler(&validation_result, &user_id)
+        .ok_or_else(|| {
+            TcpError::UnauthorizedError(format!(
+                "Not allowed to read the SSH keys of user {user_id}"
+            ))
+        })?;
+    let user = match handler.get_user_details(&user_id).await {
+        Err(DomainError::EntityNotFound(e)) => return Err(TcpError::NotFoundError(e)),
+        result => result?,
+    };
+    if let Some(group) = &query.group {
+        let group = GroupName::from(group.as_str());
+        if !handler
+            .get_user_groups(&user_id)
+            .await?
+            .iter()
+            .any(|g| g.display_name == group)
+        {
+            debug!("User is not a member of {group}, returning no keys");
+            return Ok(HttpResponse::Ok().content_type("text/plain").finish());
+        }
+    }
+    let schema = handler.get_schema().await?;
+    let user_attributes = &schema.get_schema().user_attributes;
+    let keys: String = user
+        .attributes
+        .iter()
+        .filter(|a| {
+            validation_result.can_read_all()
+                || user_attributes
+                    .get_attribute_schema(&a.name)
+                    .is_some_and(|s| s.is_visible)
+        })
+        .flat_map(|a| a.value.as_ssh_public_keys())
+        .map(|key| format!("{key}\n"))
+        .collect();
+    Ok(HttpResponse::Ok().content_type("text/plain").body(keys))
+}
+
+async fn get_authorized_keys_handler<Backend>(
+    data: web::Data<AppState<Backend>>,
+    request: HttpRequest,
+    user_id: web::Path<String>,
+    query: web::Query<AuthorizedKeysQuery>,
+) -> HttpResponse
+where
+    Backend: TcpBackendHandler + BackendHandler + LoginHandler + 'static,
+{
+    get_authorized_keys(data, request, user_id, query)
+        .await
+        .unwrap_or_else(error_to_http_response)
+}
+
+pub fn configure_endpoint<Backend>(cfg: &mut ServiceConfig)
+where
+    Backend: TcpBackendHandler + BackendHandler + LoginHandler + 'static,
+{
+    cfg.service(
+        web::resource("/ssh/authorized_keys/{user_id}")
+            .route(web::get().to(get_authorized_keys_handler::<Backend>)),
+    );
+}
@@ -100,6 +100,16 @@ pub enum Command {
     /// Create database schema.
     #[clap(name = "create_schema")]
     CreateSchema(RunOpts),
+    /// Export the users, groups and memberships to an LDIF file. Passwords are not exported.
+    #[clap(name = "export_ldif", alias = "export-ldif")]
+    ExportLdif(ExportLdifOpts),
+    /// Import users, groups and memberships from an LDIF file.
+    #[clap(name = "import_ldif", alias = "import-ldif")]
+    ImportLdif(ImportLdifOpts),
+    /// Create a compact bloom filter from a file of breached password hashes, to use as
+    /// `breached_passwords_file`.
+    #[clap(name = "create_breached_password_filter")]
+    CreateBreachedPasswordFilter(CreateBreachedPasswordFilterOpts),
 }
 
 #[derive(Debug, Parser, Clone)]
@@ -264,6 +274,46 @@ pub struct ExportGraphQLSchemaOpts {
     pub output_file: Option<String>,
 }
 
+#[derive(Debug, Parser, Clone)]
+pub struct ExportLdifOpts {
+    #[clap(flatten)]
+    pub run_opts: RunOpts,
+
+    /// File to write the LDIF to.
+    #[clap(short, long)]
+    pub output_file: String,
+}
+
+#[derive(Debug, Parser, Clone)]
+pub struct ImportLdifOpts {
+    #[clap(flatten)]
+    pub run_opts: RunOpts,
+
+    /// LDIF file to import. Records without a "changetype" are added.
+    #[clap(short, long)]
+    pub input_file: String,
+
+    /// Only check the file and print the changes, without applying them.
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Parser, Clone)]
+pub struct CreateBreachedPasswordFilterOpts {
+    /// Sorted file of SHA-1 password hashes, one "HASH:COUNT" per line, as downloaded from Have I
+    /// Been Pwned.
+    #[clap(short, long)]
+    pub input_file: String,
+
+    /// File to write the bloom filter to.
+    #[clap(short, long)]
+    pub output_file: String,
+
+    /// Probability that a password that wasn't breached is refused.
+    #[clap(long, default_value = "0.001")]
+    pub false_positive_rate: f64,
+}
+
 pub fn init() -> CLIOpts {
     CLIOpts::parse()
 }
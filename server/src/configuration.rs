@@ -1,7 +1,7 @@
 use crate::{
     cli::{
-        GeneralConfigOpts, LdapsOpts, RunOpts, SmtpEncryption, SmtpOpts, TestEmailOpts,
-        TrueFalseAlways,
+        ExportLdifOpts, GeneralConfigOpts, ImportLdifOpts, LdapsOpts, RunOpts, SmtpEncryption,
+        SmtpOpts, TestEmailOpts, TrueFalseAlways,
     },
     database_string::DatabaseUrl,
 };
@@ -15,10 +15,12 @@ use lldap_auth::opaque::{
     KeyPair,
     server::{ServerSetup, generate_random_private_key},
 };
-use lldap_domain::types::{AttributeName, UserId};
+use lldap_domain::types::{AttributeName, GroupName, UserId};
+use lldap_ldap::BindMfaPolicy;
 use lldap_sql_backend_handler::sql_tables::{
     ConfigLocation, PrivateKeyHash, PrivateKeyInfo, PrivateKeyLocation,
 };
+use lldap_validation::{breached_passwords::BreachedPasswordList, password::PasswordPolicy};
 use secstr::SecUtf8;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
@@ -83,6 +85,123 @@ impl std::default::Default for LdapsOptions {
     }
 }
 
+/// What happens to the accounts past their expiration date.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ExpiredAccountPolicy {
+    /// The account is kept as is: the user cannot log in, but it can be extended.
+    #[default]
+    Keep,
+    Disable,
+    Delete,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, derive_builder::Builder)]
+#[builder(pattern = "owned")]
+pub struct AccountExpirationOptions {
+    /// How many days before the expiration to send a warning email. 0 disables the warning.
+    #[builder(default = "0")]
+    pub warning_days: u32,
+    #[builder(default)]
+    pub expired_accounts: ExpiredAccountPolicy,
+}
+
+impl std::default::Default for AccountExpirationOptions {
+    fn default() -> Self {
+        AccountExpirationOptionsBuilder::default().build().unwrap()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, derive_builder::Builder)]
+#[builder(pattern = "owned")]
+pub struct PasswordPolicyOptions {
+    /// After how many days the passwords expire and have to be changed. 0 means never.
+    #[builder(default = "0")]
+    pub max_age_days: u32,
+    #[builder(default = "8")]
+    pub min_length: usize,
+    #[builder(default = "false")]
+    pub require_uppercase: bool,
+    #[builder(default = "false")]
+    pub require_lowercase: bool,
+    #[builder(default = "false")]
+    pub require_digit: bool,
+    #[builder(default = "false")]
+    pub require_special: bool,
+    /// Refuse the passwords containing the user ID, email or display name.
+    #[builder(default = "false")]
+    pub forbid_user_attributes: bool,
+    /// How many previous passwords cannot be reused. 0 disables the history.
+    #[builder(default = "0")]
+    pub history_size: usize,
+    /// File of breached passwords: the SHA-1 hashes from Have I Been Pwned, or a bloom filter
+    /// generated from them with `create_breached_password_filter`.
+    #[builder(default)]
+    pub breached_passwords_file: Option<PathBuf>,
+}
+
+impl std::default::Default for PasswordPolicyOptions {
+    fn default() -> Self {
+        PasswordPolicyOptionsBuilder::default().build().unwrap()
+    }
+}
+
+impl PasswordPolicyOptions {
+    pub fn get_password_policy(&self) -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: self.min_length,
+            require_uppercase: self.require_uppercase,
+            require_lowercase: self.require_lowercase,
+            require_digit: self.require_digit,
+            require_special: self.require_special,
+            forbid_user_attributes: self.forbid_user_attributes,
+            history_size: self.history_size,
+        }
+    }
+
+    pub fn get_breached_passwords(&self) -> Result<Option<BreachedPasswordList>> {
+        self.breached_passwords_file
+            .as_ref()
+            .map(|path| {
+                BreachedPasswordList::open(path)
+                    .with_context(|| format!("while opening {}", path.display()))
+            })
+            .transpose()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, derive_builder::Builder)]
+#[builder(pattern = "owned")]
+pub struct MfaOptions {
+    /// The members of these groups have to enroll a second factor before using their
+    /// permissions, e.g. `lldap_admin`.
+    #[builder(default)]
+    pub required_groups: Vec<GroupName>,
+    /// The members of these groups have to append their TOTP code to their password when binding
+    /// over LDAP, e.g. for a VPN.
+    #[builder(default)]
+    pub ldap_required_groups: Vec<GroupName>,
+    /// The members of these groups bind with only their password, even if they are members of
+    /// one of the `ldap_required_groups`, e.g. the service accounts.
+    #[builder(default)]
+    pub ldap_exempt_groups: Vec<GroupName>,
+}
+
+impl MfaOptions {
+    pub fn get_bind_mfa_policy(&self) -> BindMfaPolicy {
+        BindMfaPolicy {
+            required_groups: self.ldap_required_groups.iter().cloned().collect(),
+            exempt_groups: self.ldap_exempt_groups.iter().cloned().collect(),
+        }
+    }
+}
+
+impl std::default::Default for MfaOptions {
+    fn default() -> Self {
+        MfaOptionsBuilder::default().build().unwrap()
+    }
+}
+
 #[derive(Clone, Deserialize, Serialize, derive_more::Debug)]
 #[debug(r#""{_0}""#)]
 pub struct HttpUrl(pub Url);
@@ -119,6 +238,10 @@ pub struct Configuration {
     #[builder(default)]
     pub ignored_group_attributes: Vec<AttributeName>,
     #[builder(default = "false")]
+    pub ldap_ad_compatibility: bool,
+    #[builder(default = "0")]
+    pub ldap_bind_cache_ttl_seconds: u64,
+    #[builder(default = "false")]
     pub verbose: bool,
     #[builder(default = r#"String::from("server_key")"#)]
     pub key_file: String,
@@ -132,6 +255,12 @@ pub struct Configuration {
     pub smtp_options: MailOptions,
     #[builder(default)]
     pub ldaps_options: LdapsOptions,
+    #[builder(default)]
+    pub account_expiration_options: AccountExpirationOptions,
+    #[builder(default)]
+    pub password_policy_options: PasswordPolicyOptions,
+    #[builder(default)]
+    pub mfa_options: MfaOptions,
     #[builder(default = r#"HttpUrl(Url::parse("http://localhost").unwrap())"#)]
     pub http_url: HttpUrl,
     #[debug(skip)]
@@ -412,6 +541,18 @@ impl TopLevelCommandOpts for TestEmailOpts {
     }
 }
 
+impl TopLevelCommandOpts for ExportLdifOpts {
+    fn general_config(&self) -> &GeneralConfigOpts {
+        &self.run_opts.general_config
+    }
+}
+
+impl TopLevelCommandOpts for ImportLdifOpts {
+    fn general_config(&self) -> &GeneralConfigOpts {
+        &self.run_opts.general_config
+    }
+}
+
 impl ConfigOverrider for RunOpts {
     fn override_config(&self, config: &mut Configuration) {
         self.general_config.override_config(config);
@@ -452,6 +593,18 @@ impl ConfigOverrider for RunOpts {
     }
 }
 
+impl ConfigOverrider for ExportLdifOpts {
+    fn override_config(&self, config: &mut Configuration) {
+        self.run_opts.override_config(config);
+    }
+}
+
+impl ConfigOverrider for ImportLdifOpts {
+    fn override_config(&self, config: &mut Configuration) {
+        self.run_opts.override_config(config);
+    }
+}
+
 impl ConfigOverrider for TestEmailOpts {
     fn override_config(&self, config: &mut Configuration) {
         self.general_config.override_config(config);
@@ -1,17 +1,27 @@
-use crate::sql_tables::DbConnection;
+use crate::{
+    configuration::{AccountExpirationOptions, Configuration, ExpiredAccountPolicy, MailOptions},
+    mail::send_account_expiration_warning_email,
+    sql_tables::DbConnection,
+};
 use actix::prelude::{Actor, AsyncContext, Context};
 use cron::Schedule;
+use lldap_domain::types::UserId;
+use lldap_domain_handlers::handler::UserBackendHandler;
 use lldap_domain_model::model::{
-    self, JwtRefreshStorageColumn, JwtStorageColumn, PasswordResetTokensColumn,
+    self, JwtRefreshStorageColumn, JwtStorageColumn, PasswordResetTokensColumn, UserColumn,
 };
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use lldap_sql_backend_handler::SqlBackendHandler;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QuerySelect, QueryTrait, sea_query::Expr};
 use std::{str::FromStr, time::Duration};
-use tracing::{error, info, instrument};
+use tracing::{error, info, instrument, warn};
 
 // Define actor
 pub struct Scheduler {
     schedule: Schedule,
-    sql_pool: DbConnection,
+    backend_handler: SqlBackendHandler,
+    account_expiration_options: AccountExpirationOptions,
+    smtp_options: MailOptions,
+    server_url: url::Url,
 }
 
 // Provide Actor implementation for our actor
@@ -32,13 +42,36 @@ impl Actor for Scheduler {
 }
 
 impl Scheduler {
-    pub fn new(cron_expression: &str, sql_pool: DbConnection) -> Self {
+    pub fn new(
+        cron_expression: &str,
+        backend_handler: SqlBackendHandler,
+        config: &Configuration,
+    ) -> Self {
         let schedule = Schedule::from_str(cron_expression).unwrap();
-        Self { schedule, sql_pool }
+        Self {
+            schedule,
+            backend_handler,
+            account_expiration_options: config.account_expiration_options.clone(),
+            smtp_options: config.smtp_options.clone(),
+            server_url: config.http_url.0.clone(),
+        }
     }
 
     fn schedule_task(&self, ctx: &mut Context<Self>) {
-        let future = actix::fut::wrap_future::<_, Self>(Self::cleanup_db(self.sql_pool.clone()));
+        let backend_handler = self.backend_handler.clone();
+        let account_expiration_options = self.account_expiration_options.clone();
+        let smtp_options = self.smtp_options.clone();
+        let server_url = self.server_url.clone();
+        let future = actix::fut::wrap_future::<_, Self>(async move {
+            Self::cleanup_db(backend_handler.pool().clone()).await;
+            Self::handle_account_expiration(
+                &backend_handler,
+                &account_expiration_options,
+                &smtp_options,
+                &server_url,
+            )
+            .await;
+        });
         ctx.spawn(future);
 
         ctx.run_later(self.duration_until_next(), move |this, ctx| {
@@ -71,6 +104,123 @@ impl Scheduler {
         };
     }
 
+    #[instrument(skip_all)]
+    async fn handle_account_expiration(
+        backend_handler: &SqlBackendHandler,
+        options: &AccountExpirationOptions,
+        smtp_options: &MailOptions,
+        server_url: &url::Url,
+    ) {
+        if options.warning_days > 0 {
+            Self::send_expiration_warnings(backend_handler, options, smtp_options, server_url)
+                .await;
+        }
+        let now = chrono::Utc::now().naive_utc();
+        // The JWTs are short-lived, but they shouldn't outlive the account.
+        if let Err(e) = model::JwtStorage::update_many()
+            .col_expr(JwtStorageColumn::Blacklisted, Expr::value(true))
+            .filter(
+                JwtStorageColumn::UserId.in_subquery(
+                    model::User::find()
+                        .select_only()
+                        .column(UserColumn::UserId)
+                        .filter(UserColumn::ExpirationDate.lte(now))
+                        .into_query(),
+                ),
+            )
+            .exec(backend_handler.pool())
+            .await
+        {
+            error!("DB error while revoking the JWTs of expired users: {}", e);
+        }
+        let delete = match options.expired_accounts {
+            ExpiredAccountPolicy::Keep => return,
+            ExpiredAccountPolicy::Disable => false,
+            ExpiredAccountPolicy::Delete => true,
+        };
+        let expired_users = match model::User::find()
+            .select_only()
+            .column(UserColumn::UserId)
+            .filter(UserColumn::ExpirationDate.lte(now))
+            .filter(UserColumn::Disabled.eq(false))
+            .into_tuple::<(UserId,)>()
+            .all(backend_handler.pool())
+            .await
+        {
+            Ok(users) => users,
+            Err(e) => {
+                error!("DB error while listing the expired users: {}", e);
+                return;
+            }
+        };
+        for (user_id,) in expired_users {
+            let result = if delete {
+                info!(r#"Deleting expired user "{}""#, &user_id);
+                backend_handler.delete_user(&user_id).await
+            } else {
+                info!(r#"Disabling expired user "{}""#, &user_id);
+                backend_handler.set_user_disabled(&user_id, true).await
+            };
+            if let Err(e) = result {
+                error!(r#"Error while handling expired user "{}": {}"#, &user_id, e);
+            }
+        }
+    }
+
+    async fn send_expiration_warnings(
+        backend_handler: &SqlBackendHandler,
+        options: &AccountExpirationOptions,
+        smtp_options: &MailOptions,
+        server_url: &url::Url,
+    ) {
+        let now = chrono::Utc::now().naive_utc();
+        let users = match model::User::find()
+            .filter(UserColumn::ExpirationWarningSent.eq(false))
+            .filter(UserColumn::Disabled.eq(false))
+            .filter(UserColumn::ExpirationDate.gt(now))
+            .filter(
+                UserColumn::ExpirationDate
+                    .lte(now + chrono::Duration::days(i64::from(options.warning_days))),
+            )
+            .all(backend_handler.pool())
+            .await
+        {
+            Ok(users) => users,
+            Err(e) => {
+                error!("DB error while listing the users about to expire: {}", e);
+                return;
+            }
+        };
+        for user in users {
+            let Some(expiration_date) = user.expiration_date else {
+                continue;
+            };
+            if let Err(e) = send_account_expiration_warning_email(
+                user.user_id.as_str(),
+                user.email.as_str(),
+                &expiration_date,
+                server_url,
+                smtp_options,
+            )
+            .await
+            {
+                warn!(
+                    r#"Could not send the expiration warning to "{}": {:#}"#,
+                    &user.user_id, e
+                );
+                continue;
+            }
+            if let Err(e) = model::User::update_many()
+                .col_expr(UserColumn::ExpirationWarningSent, Expr::value(true))
+                .filter(UserColumn::UserId.eq(&user.user_id))
+                .exec(backend_handler.pool())
+                .await
+            {
+                error!("DB error while recording the expiration warning: {}", e);
+            }
+        }
+    }
+
     fn duration_until_next(&self) -> Duration {
         let now = chrono::Utc::now();
         let next = self.schedule.upcoming(chrono::Utc).next().unwrap();
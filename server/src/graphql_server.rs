@@ -1,4 +1,7 @@
-use crate::{auth_service::check_if_token_is_valid, tcp_server::AppState};
+use crate::{
+    auth_service::check_if_token_is_valid, tcp_backend_handler::TcpBackendHandler,
+    tcp_server::AppState,
+};
 use actix_web::FromRequest;
 use actix_web::HttpMessage;
 use actix_web::{Error, HttpRequest, HttpResponse, error::JsonPayloadError, web};
@@ -119,7 +122,7 @@ where
     Ok(response.content_type("application/json").body(gql_response))
 }
 
-async fn graphql_route<Handler: BackendHandler + Clone>(
+async fn graphql_route<Handler: TcpBackendHandler + BackendHandler + Clone>(
     req: actix_web::HttpRequest,
     payload: actix_web::web::Payload,
     data: web::Data<AppState<Handler>>,
@@ -142,7 +145,7 @@ async fn graphql_route<Handler: BackendHandler + Clone>(
 
 pub fn configure_endpoint<Backend>(cfg: &mut web::ServiceConfig)
 where
-    Backend: BackendHandler + Clone + 'static,
+    Backend: TcpBackendHandler + BackendHandler + Clone + 'static,
 {
     let json_config = web::JsonConfig::default()
         .limit(4096)
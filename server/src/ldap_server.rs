@@ -7,9 +7,10 @@ use ldap3_proto::{LdapCodec, control::LdapControl, proto::LdapMsg, proto::LdapOp
 use lldap_access_control::AccessControlledBackendHandler;
 use lldap_domain::types::AttributeName;
 use lldap_domain_handlers::handler::{BackendHandler, LoginHandler};
-use lldap_ldap::LdapHandler;
+use lldap_ldap::{BindMfaPolicy, LdapHandler, SUPPORTED_CONTROLS};
 use lldap_opaque_handler::OpaqueHandler;
 use rustls::PrivateKey;
+use std::net::IpAddr;
 use tokio_rustls::TlsAcceptor as RustlsTlsAcceptor;
 use tokio_util::codec::{FramedRead, FramedWrite};
 use tracing::{debug, error, info, instrument};
@@ -30,50 +31,58 @@ where
     let msg = msg.context("while receiving LDAP op")?;
     for control in msg.ctrl.iter() {
         if let LdapControl::Unknown { oid, .. } = control {
+            if SUPPORTED_CONTROLS.contains(&oid.as_str()) {
+                continue;
+            }
             info!("Received unknown control: {}, ignoring", oid);
         }
     }
     debug!(?msg);
-    match session.handle_ldap_message(msg.op).await {
-        None => return Ok(false),
-        Some(result) => {
-            if result.is_empty() {
-                debug!("No response");
-            }
-            let results: i64 = result.len().try_into().unwrap();
-            for response in result.into_iter() {
-                debug!(?response);
-                let controls = if matches!(response, LdapOp::SearchResultDone(_)) {
-                    vec![LdapControl::SimplePagedResults {
-                        size: results - 1, // Avoid counting SearchResultDone as a result
-                        cookie: vec![],
-                    }]
-                } else {
-                    vec![]
-                };
-                resp.send(LdapMsg {
-                    msgid: msg.msgid,
-                    op: response,
-                    ctrl: controls,
-                })
-                .await
-                .context("while sending a response: {:#}")?
-            }
-
-            resp.flush()
-                .await
-                .context("while flushing responses: {:#}")?
+    let msgid = msg.msgid;
+    let mut results: i64 = 0;
+    let mut sink = (&mut *resp).with(|(response, mut controls): (LdapOp, Vec<LdapControl>)| {
+        debug!(?response);
+        if matches!(response, LdapOp::SearchResultDone(_)) {
+            controls.push(LdapControl::SimplePagedResults {
+                size: results,
+                cookie: vec![],
+            });
+        } else {
+            results += 1;
         }
+        futures_util::future::ready(Ok::<_, <Writer as futures_util::Sink<LdapMsg>>::Error>(
+            LdapMsg {
+                msgid,
+                op: response,
+                ctrl: controls,
+            },
+        ))
+    });
+    let keep_going = session
+        .handle_ldap_message_into(msg.op, &msg.ctrl, &mut sink)
+        .await
+        .context("while sending a response: {:#}")?;
+    // Release the borrow on `resp` before flushing.
+    drop(sink);
+    if !keep_going {
+        return Ok(false);
     }
+    resp.flush()
+        .await
+        .context("while flushing responses: {:#}")?;
     Ok(true)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_ldap_stream<Stream, Backend>(
     stream: Stream,
+    client_address: Option<IpAddr>,
     backend_handler: Backend,
     ldap_base_dn: String,
     ignored_user_attributes: Vec<AttributeName>,
     ignored_group_attributes: Vec<AttributeName>,
+    ad_compatibility: bool,
+    bind_mfa_policy: BindMfaPolicy,
 ) -> Result<Stream>
 where
     Backend: BackendHandler + LoginHandler + OpaqueHandler + 'static,
@@ -91,8 +100,11 @@ where
         ldap_base_dn,
         ignored_user_attributes,
         ignored_group_attributes,
+        ad_compatibility,
+        bind_mfa_policy,
         session_uuid,
-    );
+    )
+    .with_client_address(client_address);
 
     info!("LDAP session start: {}", session_uuid);
     while let Some(msg) = requests.next().await {
@@ -173,6 +185,8 @@ where
         config.ldap_base_dn.clone(),
         config.ignored_user_attributes.clone(),
         config.ignored_group_attributes.clone(),
+        config.ldap_ad_compatibility,
+        config.mfa_options.get_bind_mfa_policy(),
     );
 
     let context_for_tls = context.clone();
@@ -182,13 +196,24 @@ where
         fn_service(move |stream: TcpStream| {
             let context = context.clone();
             async move {
-                let (handler, base_dn, ignored_user_attributes, ignored_group_attributes) = context;
+                let (
+                    handler,
+                    base_dn,
+                    ignored_user_attributes,
+                    ignored_group_attributes,
+                    ad_compatibility,
+                    bind_mfa_policy,
+                ) = context;
+                let client_address = stream.peer_addr().ok().map(|addr| addr.ip());
                 handle_ldap_stream(
                     stream,
+                    client_address,
                     handler,
                     base_dn,
                     ignored_user_attributes,
                     ignored_group_attributes,
+                    ad_compatibility,
+                    bind_mfa_policy,
                 )
                 .await
             }
@@ -212,16 +237,27 @@ where
                 let tls_context = tls_context.clone();
                 async move {
                     let (
-                        (handler, base_dn, ignored_user_attributes, ignored_group_attributes),
+                        (
+                            handler,
+                            base_dn,
+                            ignored_user_attributes,
+                            ignored_group_attributes,
+                            ad_compatibility,
+                            bind_mfa_policy,
+                        ),
                         tls_acceptor,
                     ) = tls_context;
+                    let client_address = stream.peer_addr().ok().map(|addr| addr.ip());
                     let tls_stream = tls_acceptor.accept(stream).await?;
                     handle_ldap_stream(
                         tls_stream,
+                        client_address,
                         handler,
                         base_dn,
                         ignored_user_attributes,
                         ignored_group_attributes,
+                        ad_compatibility,
+                        bind_mfa_policy,
                     )
                     .await
                 }
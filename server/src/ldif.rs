@@ -0,0 +1,1413 @@
+//! Import and export of the directory in the LDIF format (RFC 2849).
+
+use anyhow::{Context, Result, anyhow, bail};
+use base64::Engine;
+use lldap_domain::{
+    deserialize::deserialize_attribute_value,
+    public_schema::PublicSchema,
+    requests::{CreateGroupRequest, CreateUserRequest, UpdateGroupRequest, UpdateUserRequest},
+    types::{
+        Attribute, AttributeName, AttributeType, Group, GroupName, JpegPhoto, LegacyPasswordHash,
+        MatchingRule, User, UserId,
+    },
+};
+use lldap_domain_handlers::handler::{BackendHandler, GroupRequestFilter};
+use lldap_domain_model::model::UserColumn;
+use lldap_ldap::{
+    GroupFieldType, Rdn, SERVICES_OU, UserFieldType, escape_dn_value, get_custom_attribute,
+    get_default_group_object_classes, get_default_user_object_classes, get_user_dn,
+    map_group_field, map_user_field, parse_dn,
+};
+use std::collections::HashSet;
+use tracing::{info, warn};
+
+const MAX_LINE_LENGTH: usize = 76;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModifyOperation {
+    Add,
+    Delete,
+    Replace,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LdifModification {
+    pub operation: ModifyOperation,
+    pub attribute: String,
+    pub values: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LdifChange {
+    /// Also used for content records, without a "changetype".
+    Add(Vec<(String, Vec<u8>)>),
+    Delete,
+    Modify(Vec<LdifModification>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LdifRecord {
+    pub dn: String,
+    pub change: LdifChange,
+}
+
+/// Whether the value can be written as is, as a SAFE-STRING from RFC 2849.
+fn is_safe_string(value: &[u8]) -> bool {
+    match value.first() {
+        None => true,
+        Some(b' ' | b':' | b'<') => false,
+        Some(_) => {
+            value.last() != Some(&b' ')
+                && value
+                    .iter()
+                    .all(|&c| c != 0 && c != b'\n' && c != b'\r' && c.is_ascii())
+        }
+    }
+}
+
+fn write_folded_line(output: &mut String, line: &str) {
+    let mut remaining = line;
+    let mut limit = MAX_LINE_LENGTH;
+    while remaining.len() > limit {
+        let mut end = limit;
+        while !remaining.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (head, tail) = remaining.split_at(end);
+        output.push_str(head);
+        output.push_str("\n ");
+        remaining = tail;
+        // The leading space of continuation lines counts towards the limit.
+        limit = MAX_LINE_LENGTH - 1;
+    }
+    output.push_str(remaining);
+    output.push('\n');
+}
+
+fn write_attribute(output: &mut String, name: &str, value: &[u8]) {
+    if is_safe_string(value) {
+        write_folded_line(
+            output,
+            &format!("{}: {}", name, std::str::from_utf8(value).unwrap()),
+        );
+    } else {
+        write_folded_line(
+            output,
+            &format!(
+                "{}:: {}",
+                name,
+                base64::engine::general_purpose::STANDARD.encode(value)
+            ),
+        );
+    }
+}
+
+pub fn write_entry(output: &mut String, dn: &str, attributes: &[(String, Vec<u8>)]) {
+    write_attribute(output, "dn", dn.as_bytes());
+    for (name, value) in attributes {
+        write_attribute(output, name, value);
+    }
+}
+
+/// Joins the folded lines, and drops the comments.
+fn unfold_lines(input: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in input.lines() {
+        let is_continuation =
+            line.starts_with(' ') && lines.last().is_some_and(|last| !last.is_empty());
+        if is_continuation {
+            lines.last_mut().unwrap().push_str(&line[1..]);
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    lines.retain(|l| !l.starts_with('#'));
+    lines
+}
+
+fn parse_attribute_line(line: &str) -> Result<(String, Vec<u8>)> {
+    let (name, value) = line
+        .split_once(':')
+        .ok_or_else(|| anyhow!(r#"Invalid LDIF line: "{line}""#))?;
+    // Drop the attribute options, like ";binary".
+    let name = name.split(';').next().unwrap().trim().to_string();
+    let value = if let Some(encoded) = value.strip_prefix(':') {
+        base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .with_context(|| format!("Invalid base64 value for {name}"))?
+    } else if value.starts_with('<') {
+        bail!(r#"URL values are not supported: "{line}""#)
+    } else {
+        value.trim_start_matches(' ').as_bytes().to_vec()
+    };
+    Ok((name, value))
+}
+
+fn parse_string_value(name: &str, value: Vec<u8>) -> Result<String> {
+    String::from_utf8(value).with_context(|| format!("Invalid UTF-8 value for {name}"))
+}
+
+fn parse_modifications(lines: &[String]) -> Result<Vec<LdifModification>> {
+    let mut modifications = Vec::new();
+    let mut lines = lines.iter();
+    while let Some(line) = lines.next() {
+        let (operation, attribute) = parse_attribute_line(line)?;
+        let operation = match operation.to_ascii_lowercase().as_str() {
+            "add" => ModifyOperation::Add,
+            "delete" => ModifyOperation::Delete,
+            "replace" => ModifyOperation::Replace,
+            _ => bail!(r#"Unknown modification "{operation}""#),
+        };
+        let attribute = parse_string_value("the modified attribute", attribute)?;
+        let mut values = Vec::new();
+        for line in lines.by_ref() {
+            if line.trim_end() == "-" {
+                break;
+            }
+            let (name, value) = parse_attribute_line(line)?;
+            if !name.eq_ignore_ascii_case(&attribute) {
+                bail!("Expected a value for {attribute}, got {name}");
+            }
+            values.push(value);
+        }
+        modifications.push(LdifModification {
+            operation,
+            attribute,
+            values,
+        });
+    }
+    Ok(modifications)
+}
+
+fn parse_record(lines: &[String]) -> Result<LdifRecord> {
+    let (name, dn) = parse_attribute_line(&lines[0])?;
+    if !name.eq_ignore_ascii_case("dn") {
+        bail!(
+            r#"Expected a record to start with "dn", got "{}""#,
+            lines[0]
+        );
+    }
+    let dn = parse_string_value("the DN", dn)?;
+    let mut rest = &lines[1..];
+    let mut change_type = "add".to_string();
+    if let Some(line) = rest.first() {
+        let (name, value) = parse_attribute_line(line)?;
+        if name.eq_ignore_ascii_case("control") {
+            bail!("Controls are not supported: {dn}");
+        }
+        if name.eq_ignore_ascii_case("changetype") {
+            change_type = parse_string_value("changetype", value)?.to_ascii_lowercase();
+            rest = &rest[1..];
+        }
+    }
+    let change = match change_type.as_str() {
+        "add" => LdifChange::Add(
+            rest.iter()
+                .map(|line| parse_attribute_line(line))
+                .collect::<Result<_>>()?,
+        ),
+        "delete" => {
+            if !rest.is_empty() {
+                bail!(r#"Unexpected attributes after "changetype: delete" for {dn}"#);
+            }
+            LdifChange::Delete
+        }
+        "modify" => LdifChange::Modify(
+            parse_modifications(rest)
+                .with_context(|| format!("while parsing the modifications of {dn}"))?,
+        ),
+        "modrdn" | "moddn" => bail!("Renaming entries is not supported: {dn}"),
+        other => bail!(r#"Unknown changetype "{other}" for {dn}"#),
+    };
+    Ok(LdifRecord { dn, change })
+}
+
+pub fn parse_ldif(input: &str) -> Result<Vec<LdifRecord>> {
+    let mut records = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut is_first_line = true;
+    for line in unfold_lines(input)
+        .into_iter()
+        .chain(std::iter::once(String::new()))
+    {
+        if line.is_empty() {
+            if !current.is_empty() {
+                records.push(parse_record(&current)?);
+                current.clear();
+            }
+            continue;
+        }
+        if is_first_line {
+            is_first_line = false;
+            if let Some(version) = line.strip_prefix("version:") {
+                if version.trim() != "1" {
+                    bail!("Unsupported LDIF version: {}", version.trim());
+                }
+                continue;
+            }
+        }
+        current.push(line);
+    }
+    Ok(records)
+}
+
+fn push_attribute(attributes: &mut Vec<(String, Vec<u8>)>, name: &str, value: &str) {
+    attributes.push((name.to_string(), value.as_bytes().to_vec()));
+}
+
+fn make_user_entry(
+    user: &User,
+    base_dn: &str,
+    schema: &PublicSchema,
+) -> (String, Vec<(String, Vec<u8>)>) {
+    let mut attributes = Vec::new();
+    for class in get_default_user_object_classes()
+        .iter()
+        .chain(&schema.get_schema().extra_user_object_classes)
+    {
+        push_attribute(&mut attributes, "objectClass", class.as_str());
+    }
+    push_attribute(&mut attributes, "uid", user.user_id.as_str());
+    if !user.is_service_account {
+        push_attribute(&mut attributes, "mail", user.email.as_str());
+    }
+    if let Some(display_name) = user.display_name.as_ref().filter(|n| !n.is_empty()) {
+        push_attribute(&mut attributes, "cn", display_name);
+    }
+    for attribute in &user.attributes {
+        let name = match attribute.name.as_str() {
+            "avatar" => "jpegPhoto",
+            "first_name" => "givenName",
+            "last_name" => "sn",
+            name => name,
+        };
+        for value in get_custom_attribute(&user.attributes, &attribute.name).unwrap_or_default() {
+            attributes.push((name.to_string(), value));
+        }
+    }
+    (
+        get_user_dn(&user.user_id, user.is_service_account, base_dn),
+        attributes,
+    )
+}
+
+fn make_group_entry(
+    group: &Group,
+    service_accounts: &HashSet<UserId>,
+    base_dn: &str,
+    schema: &PublicSchema,
+) -> (String, Vec<(String, Vec<u8>)>) {
+    let mut attributes = Vec::new();
+    for class in get_default_group_object_classes()
+        .iter()
+        .chain(&schema.get_schema().extra_group_object_classes)
+    {
+        push_attribute(&mut attributes, "objectClass", class.as_str());
+    }
+    push_attribute(&mut attributes, "cn", group.display_name.as_str());
+    for attribute in &group.attributes {
+        for value in get_custom_attribute(&group.attributes, &attribute.name).unwrap_or_default() {
+            attributes.push((attribute.name.to_string(), value));
+        }
+    }
+    for user_id in &group.users {
+        push_attribute(
+            &mut attributes,
+            "member",
+            &get_user_dn(user_id, service_accounts.contains(user_id), base_dn),
+        );
+    }
+    (
+        format!(
+            "cn={},ou=groups,{}",
+            escape_dn_value(group.display_name.as_str()),
+            base_dn
+        ),
+        attributes,
+    )
+}
+
+/// Exports all the users, then all the groups with their members. Passwords are not exported.
+pub async fn export_ldif(handler: &impl BackendHandler, base_dn: &str) -> Result<String> {
+    let base_dn = base_dn.to_ascii_lowercase();
+    let schema = PublicSchema::from(handler.get_schema().await?);
+    let mut output = "version: 1\n".to_string();
+    let mut service_accounts = HashSet::new();
+    for user in handler.list_users(None, false).await? {
+        if user.user.is_service_account {
+            service_accounts.insert(user.user.user_id.clone());
+        }
+        let (dn, attributes) = make_user_entry(&user.user, &base_dn, &schema);
+        output.push('\n');
+        write_entry(&mut output, &dn, &attributes);
+    }
+    for group in handler.list_groups(None).await? {
+        let (dn, attributes) = make_group_entry(&group, &service_accounts, &base_dn, &schema);
+        output.push('\n');
+        write_entry(&mut output, &dn, &attributes);
+    }
+    Ok(output)
+}
+
+enum EntryName {
+    /// The base DN, ou=people, ou=services or ou=groups.
+    Container,
+    User(UserId),
+    ServiceAccount(UserId),
+    Group(GroupName),
+}
+
+/// Groups the values of each attribute together, keeping the order of the attributes.
+fn group_attribute_values(
+    attributes: Vec<(String, Vec<u8>)>,
+) -> Vec<(AttributeName, Vec<Vec<u8>>)> {
+    let mut grouped: Vec<(AttributeName, Vec<Vec<u8>>)> = Vec::new();
+    for (name, value) in attributes {
+        let name = AttributeName::from(name.as_str());
+        match grouped.iter_mut().find(|(n, _)| n == &name) {
+            Some((_, values)) => values.push(value),
+            None => grouped.push((name, vec![value])),
+        }
+    }
+    grouped
+}
+
+fn get_single_string_value(name: &AttributeName, mut values: Vec<Vec<u8>>) -> Result<String> {
+    if values.len() != 1 {
+        bail!("Expected a single value for {name}, got {}", values.len());
+    }
+    parse_string_value(name.as_str(), values.pop().unwrap())
+}
+
+fn make_attribute(
+    name: AttributeName,
+    attribute_type: AttributeType,
+    is_list: bool,
+    values: Vec<Vec<u8>>,
+) -> Result<Attribute> {
+    let values = values
+        .into_iter()
+        .map(|value| {
+            if attribute_type == AttributeType::JpegPhoto {
+                JpegPhoto::try_from(value)
+                    .map(|photo| String::from(&photo))
+                    .with_context(|| format!("Invalid JPEG value for {name}"))
+            } else {
+                parse_string_value(name.as_str(), value)
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let value = deserialize_attribute_value(&values, attribute_type, is_list)
+        .with_context(|| format!("Invalid value for {name}"))?;
+    Ok(Attribute { name, value })
+}
+
+fn check_unknown_attribute(
+    name: &AttributeName,
+    ignored_attributes: &[AttributeName],
+    kind: &str,
+) -> Result<()> {
+    if name.as_str() == "userpassword" {
+        warn!("Ignoring userPassword: password hashes can only be imported when adding a user");
+        Ok(())
+    } else if ignored_attributes.contains(name) {
+        Ok(())
+    } else {
+        bail!(
+            r#"Unknown attribute "{name}". To skip it, add it to "ignored_{kind}_attributes" in the config"#
+        )
+    }
+}
+
+/// Applies the modification to the values, considering two values the same if `matches` says so.
+fn apply_modification<T>(
+    values: &mut Vec<T>,
+    operation: ModifyOperation,
+    new_values: Vec<T>,
+    matches: impl Fn(&T, &T) -> bool,
+) {
+    match operation {
+        ModifyOperation::Add => {
+            for value in new_values {
+                if !values.iter().any(|v| matches(v, &value)) {
+                    values.push(value);
+                }
+            }
+        }
+        ModifyOperation::Replace => *values = new_values,
+        ModifyOperation::Delete if new_values.is_empty() => values.clear(),
+        ModifyOperation::Delete => {
+            values.retain(|v| !new_values.iter().any(|value| matches(v, value)))
+        }
+    }
+}
+
+struct ModifiedAttribute {
+    name: AttributeName,
+    attribute_type: AttributeType,
+    is_list: bool,
+    values: Vec<Vec<u8>>,
+}
+
+/// Tracks the values of the custom attributes modified in a record.
+struct ModifiedAttributes {
+    current_attributes: Vec<Attribute>,
+    modified: Vec<ModifiedAttribute>,
+}
+
+impl ModifiedAttributes {
+    fn new(current_attributes: Vec<Attribute>) -> Self {
+        Self {
+            current_attributes,
+            modified: Vec::new(),
+        }
+    }
+
+    fn apply(
+        &mut self,
+        name: AttributeName,
+        attribute_type: AttributeType,
+        is_list: bool,
+        matching_rule: MatchingRule,
+        modification: LdifModification,
+    ) {
+        let index = match self.modified.iter().position(|m| m.name == name) {
+            Some(index) => index,
+            None => {
+                let values =
+                    get_custom_attribute(&self.current_attributes, &name).unwrap_or_default();
+                self.modified.push(ModifiedAttribute {
+                    name,
+                    attribute_type,
+                    is_list,
+                    values,
+                });
+                self.modified.len() - 1
+            }
+        };
+        apply_modification(
+            &mut self.modified[index].values,
+            modification.operation,
+            modification.values,
+            |v1, v2| matching_rule.values_match(v1, v2),
+        );
+    }
+
+    /// Returns the attributes to delete and the ones to insert.
+    fn into_changes(self) -> Result<(Vec<AttributeName>, Vec<Attribute>)> {
+        let mut delete_attributes = Vec::new();
+        let mut insert_attributes = Vec::new();
+        for attribute in self.modified {
+            if attribute.values.is_empty() {
+                delete_attributes.push(attribute.name);
+            } else {
+                insert_attributes.push(make_attribute(
+                    attribute.name,
+                    attribute.attribute_type,
+                    attribute.is_list,
+                    attribute.values,
+                )?);
+            }
+        }
+        Ok((delete_attributes, insert_attributes))
+    }
+}
+
+pub struct LdifImporter<'a, Handler> {
+    handler: &'a Handler,
+    base_dn: Vec<Rdn>,
+    base_dn_str: String,
+    schema: PublicSchema,
+    ignored_user_attributes: Vec<AttributeName>,
+    ignored_group_attributes: Vec<AttributeName>,
+    dry_run: bool,
+}
+
+impl<'a, Handler: BackendHandler> LdifImporter<'a, Handler> {
+    pub async fn new(
+        handler: &'a Handler,
+        base_dn: &str,
+        ignored_user_attributes: Vec<AttributeName>,
+        ignored_group_attributes: Vec<AttributeName>,
+        dry_run: bool,
+    ) -> Result<Self> {
+        let base_dn_str = base_dn.to_ascii_lowercase();
+        Ok(Self {
+            handler,
+            base_dn: parse_dn(&base_dn_str)
+                .map_err(|e| anyhow!(r#"Invalid base DN "{base_dn}": {e}"#))?,
+            base_dn_str,
+            schema: PublicSchema::from(handler.get_schema().await?),
+            ignored_user_attributes,
+            ignored_group_attributes,
+            dry_run,
+        })
+    }
+
+    /// Applies the records in order, stopping at the first error.
+    pub async fn import(&self, records: Vec<LdifRecord>) -> Result<()> {
+        for record in records {
+            let dn = record.dn.clone();
+            self.apply_record(record)
+                .await
+                .with_context(|| format!("while importing {dn}"))?;
+        }
+        Ok(())
+    }
+
+    fn dry_run_prefix(&self) -> &'static str {
+        if self.dry_run { "[dry run] " } else { "" }
+    }
+
+    fn parse_entry_name(&self, dn: &str) -> Result<EntryName> {
+        let parts = parse_dn(dn).map_err(|e| anyhow!(r#"Invalid DN "{dn}": {e}"#))?;
+        let unexpected_dn = || {
+            anyhow!(
+                r#"Unexpected DN "{dn}", expected "uid=id,ou=people,{base}" or "cn=name,ou=groups,{base}""#,
+                base = self.base_dn_str
+            )
+        };
+        let base_len = self.base_dn.len();
+        if parts.len() < base_len
+            || !parts[parts.len() - base_len..]
+                .iter()
+                .zip(&self.base_dn)
+                .all(|(rdn, base_rdn)| rdn.to_ascii_lowercase() == *base_rdn)
+        {
+            return Err(unexpected_dn());
+        }
+        let container = |rdn: &Rdn| match rdn.single() {
+            Some(("ou", value)) => Some(value.to_ascii_lowercase()),
+            _ => None,
+        };
+        match parts.len() - base_len {
+            0 => Ok(EntryName::Container),
+            1 if matches!(
+                container(&parts[0]).as_deref(),
+                Some("people" | SERVICES_OU | "groups")
+            ) =>
+            {
+                Ok(EntryName::Container)
+            }
+            2 => match (parts[0].single(), container(&parts[1]).as_deref()) {
+                (Some(("uid" | "cn", name)), Some("people")) => {
+                    Ok(EntryName::User(UserId::new(name)))
+                }
+                (Some(("uid" | "cn", name)), Some(SERVICES_OU)) => {
+                    Ok(EntryName::ServiceAccount(UserId::new(name)))
+                }
+                (Some(("uid" | "cn", name)), Some("groups")) => {
+                    Ok(EntryName::Group(GroupName::new(name)))
+                }
+                _ => Err(unexpected_dn()),
+            },
+            _ => Err(unexpected_dn()),
+        }
+    }
+
+    fn parse_member(&self, value: Vec<u8>) -> Result<UserId> {
+        let member = parse_string_value("member", value)?;
+        if !member.contains('=') && !member.contains(',') {
+            return Ok(UserId::new(&member));
+        }
+        match self.parse_entry_name(&member)? {
+            EntryName::User(user_id) | EntryName::ServiceAccount(user_id) => Ok(user_id),
+            _ => bail!(r#"Invalid member "{member}", expected a user"#),
+        }
+    }
+
+    async fn apply_record(&self, record: LdifRecord) -> Result<()> {
+        match (self.parse_entry_name(&record.dn)?, record.change) {
+            (EntryName::Container, _) => {
+                info!("Skipping container entry {}", record.dn);
+                Ok(())
+            }
+            (EntryName::User(user_id), LdifChange::Add(attributes)) => {
+                self.add_user(user_id, false, attributes).await
+            }
+            (EntryName::ServiceAccount(user_id), LdifChange::Add(attributes)) => {
+                self.add_user(user_id, true, attributes).await
+            }
+            (EntryName::User(user_id) | EntryName::ServiceAccount(user_id), LdifChange::Delete) => {
+                info!("{}Deleting user {}", self.dry_run_prefix(), user_id);
+                if !self.dry_run {
+                    self.handler.delete_user(&user_id).await?;
+                }
+                Ok(())
+            }
+            (
+                EntryName::User(user_id) | EntryName::ServiceAccount(user_id),
+                LdifChange::Modify(modifications),
+            ) => self.modify_user(user_id, modifications).await,
+            (EntryName::Group(group_name), LdifChange::Add(attributes)) => {
+                self.add_group(group_name, attributes).await
+            }
+            (EntryName::Group(group_name), LdifChange::Delete) => {
+                let group = self.find_group(&group_name).await?;
+                info!("{}Deleting group {}", self.dry_run_prefix(), group_name);
+                match group {
+                    Some(group) if !self.dry_run => Ok(self.handler.delete_group(group.id).await?),
+                    _ => Ok(()),
+                }
+            }
+            (EntryName::Group(group_name), LdifChange::Modify(modifications)) => {
+                self.modify_group(group_name, modifications).await
+            }
+        }
+    }
+
+    /// In a dry run, the group might be created by an earlier record of the import.
+    async fn find_group(&self, group_name: &GroupName) -> Result<Option<Group>> {
+        let group = self
+            .handler
+            .list_groups(Some(GroupRequestFilter::DisplayName(group_name.clone())))
+            .await?
+            .into_iter()
+            .next();
+        match group {
+            Some(group) => Ok(Some(group)),
+            None if self.dry_run => {
+                warn!("Group {group_name} does not exist, assuming it is created by the import");
+                Ok(None)
+            }
+            None => bail!("No such group: {group_name}"),
+        }
+    }
+
+    async fn add_user(
+        &self,
+        user_id: UserId,
+        is_service_account: bool,
+        attributes: Vec<(String, Vec<u8>)>,
+    ) -> Result<()> {
+        let mut email = None;
+        let mut display_name = None;
+        let mut legacy_password_hash = None;
+        let mut user_attributes = Vec::new();
+        for (name, values) in group_attribute_values(attributes) {
+            if name.as_str() == "userpassword" {
+                let password = get_single_string_value(&name, values)?;
+                match LegacyPasswordHash::try_from(password.as_str()) {
+                    Ok(hash) => legacy_password_hash = Some(hash),
+                    Err(e) => warn!("Ignoring userPassword of {user_id}: {e:#}"),
+                }
+                continue;
+            }
+            match map_user_field(&name, &self.schema, false) {
+                UserFieldType::PrimaryField(UserColumn::Email) => {
+                    email = Some(get_single_string_value(&name, values)?)
+                }
+                UserFieldType::PrimaryField(UserColumn::DisplayName) => {
+                    display_name = Some(get_single_string_value(&name, values)?)
+                }
+                UserFieldType::Attribute(name, attribute_type, is_list) => {
+                    user_attributes.push(make_attribute(name, attribute_type, is_list, values)?)
+                }
+                UserFieldType::NoMatch => {
+                    check_unknown_attribute(&name, &self.ignored_user_attributes, "user")?
+                }
+                // The user id comes from the DN, the rest is generated by the server.
+                _ => {}
+            }
+        }
+        // Service accounts don't need an email.
+        let email = match email {
+            Some(email) => email,
+            None if is_service_account => String::new(),
+            None => bail!("Missing mail attribute for user {user_id}"),
+        };
+        info!("{}Creating user {}", self.dry_run_prefix(), user_id);
+        if !self.dry_run {
+            self.handler
+                .create_user(CreateUserRequest {
+                    user_id,
+                    email: email.into(),
+                    display_name,
+                    attributes: user_attributes,
+                    legacy_password_hash,
+                    is_service_account,
+                    allowed_sources: Vec::new(),
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn modify_user(
+        &self,
+        user_id: UserId,
+        modifications: Vec<LdifModification>,
+    ) -> Result<()> {
+        let current_attributes = match self.handler.get_user_details(&user_id).await {
+            Ok(user) => user.attributes,
+            Err(e) if self.dry_run => {
+                warn!(
+                    "Could not read user {user_id} ({e:#}), assuming it is created by the import"
+                );
+                Vec::new()
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut request = UpdateUserRequest {
+            user_id: user_id.clone(),
+            ..Default::default()
+        };
+        let mut modified_attributes = ModifiedAttributes::new(current_attributes);
+        for modification in modifications {
+            let name = AttributeName::from(modification.attribute.as_str());
+            match map_user_field(&name, &self.schema, false) {
+                UserFieldType::PrimaryField(UserColumn::Email) => {
+                    if modification.operation == ModifyOperation::Delete {
+                        bail!("The mail attribute cannot be deleted");
+                    }
+                    request.email =
+                        Some(get_single_string_value(&name, modification.values)?.into());
+                }
+                UserFieldType::PrimaryField(UserColumn::DisplayName) => {
+                    request.display_name =
+                        Some(if modification.operation == ModifyOperation::Delete {
+                            String::new()
+                        } else {
+                            get_single_string_value(&name, modification.values)?
+                        });
+                }
+                UserFieldType::Attribute(name, attribute_type, is_list) => {
+                    let matching_rule = self
+                        .schema
+                        .get_schema()
+                        .user_attributes
+                        .get_matching_rule(&name)
+                        .unwrap_or_else(|| MatchingRule::default_for(attribute_type));
+                    modified_attributes.apply(
+                        name,
+                        attribute_type,
+                        is_list,
+                        matching_rule,
+                        modification,
+                    )
+                }
+                UserFieldType::NoMatch => {
+                    check_unknown_attribute(&name, &self.ignored_user_attributes, "user")?
+                }
+                _ => bail!("Attribute {name} cannot be modified"),
+            }
+        }
+        (request.delete_attributes, request.insert_attributes) =
+            modified_attributes.into_changes()?;
+        info!("{}Updating user {}", self.dry_run_prefix(), user_id);
+        if !self.dry_run {
+            self.handler.update_user(request).await?;
+        }
+        Ok(())
+    }
+
+    async fn add_group(
+        &self,
+        group_name: GroupName,
+        attributes: Vec<(String, Vec<u8>)>,
+    ) -> Result<()> {
+        let mut members = Vec::new();
+        let mut group_attributes = Vec::new();
+        for (name, values) in group_attribute_values(attributes) {
+            match map_group_field(&name, &self.schema, false) {
+                GroupFieldType::Member => {
+                    for value in values {
+                        members.push(self.parse_member(value)?);
+                    }
+                }
+                GroupFieldType::Attribute(name, attribute_type, is_list) => {
+                    group_attributes.push(make_attribute(name, attribute_type, is_list, values)?)
+                }
+                GroupFieldType::NoMatch => {
+                    check_unknown_attribute(&name, &self.ignored_group_attributes, "group")?
+                }
+                // The name comes from the DN, the rest is generated by the server.
+                _ => {}
+            }
+        }
+        info!("{}Creating group {}", self.dry_run_prefix(), group_name);
+        let group_id = if self.dry_run {
+            None
+        } else {
+            Some(
+                self.handler
+                    .create_group(CreateGroupRequest {
+                        display_name: group_name.clone(),
+                        attributes: group_attributes,
+                    })
+                    .await?,
+            )
+        };
+        for member in members {
+            info!(
+                "{}Adding {} to group {}",
+                self.dry_run_prefix(),
+                member,
+                group_name
+            );
+            if let Some(group_id) = group_id {
+                self.handler.add_user_to_group(&member, group_id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn modify_group(
+        &self,
+        group_name: GroupName,
+        modifications: Vec<LdifModification>,
+    ) -> Result<()> {
+        let group = self.find_group(&group_name).await?;
+        let current_members = group.as_ref().map(|g| g.users.clone()).unwrap_or_default();
+        let mut members = current_members.clone();
+        let mut modified_attributes = ModifiedAttributes::new(
+            group
+                .as_ref()
+                .map(|g| g.attributes.clone())
+                .unwrap_or_default(),
+        );
+        for modification in modifications {
+            let name = AttributeName::from(modification.attribute.as_str());
+            match map_group_field(&name, &self.schema, false) {
+                GroupFieldType::Member => {
+                    let new_members = modification
+                        .values
+                        .into_iter()
+                        .map(|value| self.parse_member(value))
+                        .collect::<Result<Vec<_>>>()?;
+                    apply_modification(
+                        &mut members,
+                        modification.operation,
+                        new_members,
+                        PartialEq::eq,
+                    );
+                }
+                GroupFieldType::Attribute(name, attribute_type, is_list) => {
+                    let matching_rule = self
+                        .schema
+                        .get_schema()
+                        .group_attributes
+                        .get_matching_rule(&name)
+                        .unwrap_or_else(|| MatchingRule::default_for(attribute_type));
+                    modified_attributes.apply(
+                        name,
+                        attribute_type,
+                        is_list,
+                        matching_rule,
+                        modification,
+                    )
+                }
+                GroupFieldType::NoMatch => {
+                    check_unknown_attribute(&name, &self.ignored_group_attributes, "group")?
+                }
+                _ => bail!("Attribute {name} cannot be modified"),
+            }
+        }
+        let (delete_attributes, insert_attributes) = modified_attributes.into_changes()?;
+        let group_id = group.filter(|_| !self.dry_run).map(|g| g.id);
+        if !delete_attributes.is_empty() || !insert_attributes.is_empty() {
+            info!("{}Updating group {}", self.dry_run_prefix(), group_name);
+            if let Some(group_id) = group_id {
+                self.handler
+                    .update_group(UpdateGroupRequest {
+                        group_id,
+                        display_name: None,
+                        delete_attributes,
+                        insert_attributes,
+                    })
+                    .await?;
+            }
+        }
+        for member in members.iter().filter(|m| !current_members.contains(m)) {
+            info!(
+                "{}Adding {} to group {}",
+                self.dry_run_prefix(),
+                member,
+                group_name
+            );
+            if let Some(group_id) = group_id {
+                self.handler.add_user_to_group(member, group_id).await?;
+            }
+        }
+        for member in current_members.iter().filter(|m| !members.contains(m)) {
+            info!(
+                "{}Removing {} from group {}",
+                self.dry_run_prefix(),
+                member,
+                group_name
+            );
+            if let Some(group_id) = group_id {
+                self.handler
+                    .remove_user_from_group(member, group_id)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use lldap_domain::types::{GroupId, UserAndGroups};
+    use lldap_test_utils::{MockTestBackendHandler, setup_default_schema};
+    use mockall::predicate::eq;
+    use pretty_assertions::assert_eq;
+
+    fn make_group(name: &str, users: Vec<UserId>) -> Group {
+        Group {
+            id: GroupId(3),
+            display_name: name.into(),
+            creation_date: chrono::Utc.timestamp_opt(42, 0).unwrap().naive_utc(),
+            uuid: lldap_domain::uuid!("a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d8"),
+            users,
+            attributes: Vec::new(),
+            member_filter: None,
+        }
+    }
+
+    #[test]
+    fn test_write_entry_encodes_unsafe_values() {
+        let mut output = String::new();
+        write_entry(
+            &mut output,
+            "uid=bob,ou=people,dc=example,dc=com",
+            &[
+                ("cn".to_string(), b"Bob".to_vec()),
+                ("sn".to_string(), " leading space".as_bytes().to_vec()),
+                ("givenName".to_string(), "Jérôme".as_bytes().to_vec()),
+                ("description".to_string(), Vec::new()),
+            ],
+        );
+        assert_eq!(
+            output,
+            "dn: uid=bob,ou=people,dc=example,dc=com\n\
+             cn: Bob\n\
+             sn:: IGxlYWRpbmcgc3BhY2U=\n\
+             givenName:: SsOpcsO0bWU=\n\
+             description: \n"
+        );
+    }
+
+    #[test]
+    fn test_write_entry_folds_long_lines() {
+        let mut output = String::new();
+        let value = "a".repeat(100);
+        write_entry(
+            &mut output,
+            "dc=example",
+            &[("cn".to_string(), value.clone().into_bytes())],
+        );
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1].len(), MAX_LINE_LENGTH);
+        assert!(lines[2].starts_with(' '));
+        assert_eq!(
+            parse_ldif(&output).unwrap()[0].change,
+            LdifChange::Add(vec![("cn".to_string(), value.into_bytes())])
+        );
+    }
+
+    #[test]
+    fn test_parse_ldif() {
+        let input = "version: 1\n\
+                     # A comment\n\
+                     dn: uid=bob,ou=people,dc=example,dc=com\n\
+                     objectClass: person\n\
+                     cn: Bob\n \
+                     by\n\
+                     jpegPhoto;binary:: /9j/\n\
+                     \n\
+                     dn: uid=john,ou=people,dc=example,dc=com\n\
+                     changetype: delete\n\
+                     \n\
+                     dn: cn=group,ou=groups,dc=example,dc=com\n\
+                     changetype: modify\n\
+                     add: member\n\
+                     member: uid=bob,ou=people,dc=example,dc=com\n\
+                     -\n\
+                     delete: description\n\
+                     -\n";
+        assert_eq!(
+            parse_ldif(input).unwrap(),
+            vec![
+                LdifRecord {
+                    dn: "uid=bob,ou=people,dc=example,dc=com".to_string(),
+                    change: LdifChange::Add(vec![
+                        ("objectClass".to_string(), b"person".to_vec()),
+                        ("cn".to_string(), b"Bobby".to_vec()),
+                        ("jpegPhoto".to_string(), vec![0xff, 0xd8, 0xff]),
+                    ]),
+                },
+                LdifRecord {
+                    dn: "uid=john,ou=people,dc=example,dc=com".to_string(),
+                    change: LdifChange::Delete,
+                },
+                LdifRecord {
+                    dn: "cn=group,ou=groups,dc=example,dc=com".to_string(),
+                    change: LdifChange::Modify(vec![
+                        LdifModification {
+                            operation: ModifyOperation::Add,
+                            attribute: "member".to_string(),
+                            values: vec![b"uid=bob,ou=people,dc=example,dc=com".to_vec()],
+                        },
+                        LdifModification {
+                            operation: ModifyOperation::Delete,
+                            attribute: "description".to_string(),
+                            values: Vec::new(),
+                        },
+                    ]),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_ldif_errors() {
+        assert!(parse_ldif("cn: bob\n").is_err());
+        assert!(parse_ldif("dn: cn=bob\nchangetype: modrdn\nnewrdn: cn=john\n").is_err());
+        assert!(parse_ldif("dn: cn=bob\njpegPhoto:< file:///tmp/photo.jpg\n").is_err());
+        assert!(parse_ldif("dn: cn=bob\nchangetype: modify\nadd: mail\ncn: bob\n-\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export_ldif() {
+        let mut mock = MockTestBackendHandler::new();
+        setup_default_schema(&mut mock);
+        mock.expect_list_users().times(1).return_once(|_, _| {
+            Ok(vec![UserAndGroups {
+                user: User {
+                    user_id: UserId::new("bob"),
+                    email: "bob@bobmail.bob".into(),
+                    display_name: Some("Bôb".to_string()),
+                    attributes: vec![Attribute {
+                        name: "first_name".into(),
+                        value: "Bob".to_string().into(),
+                    }],
+                    ..Default::default()
+                },
+                groups: None,
+            }])
+        });
+        mock.expect_list_groups()
+            .times(1)
+            .return_once(|_| Ok(vec![make_group("Best Group", vec![UserId::new("bob")])]));
+        assert_eq!(
+            export_ldif(&mock, "dc=Example,dc=com").await.unwrap(),
+            "version: 1\n\
+             \n\
+             dn: uid=bob,ou=people,dc=example,dc=com\n\
+             objectClass: inetOrgPerson\n\
+             objectClass: posixAccount\n\
+             objectClass: mailAccount\n\
+             objectClass: person\n\
+             objectClass: customUserClass\n\
+             uid: bob\n\
+             mail: bob@bobmail.bob\n\
+             cn:: QsO0Yg==\n\
+             givenName: Bob\n\
+             \n\
+             dn: cn=Best Group,ou=groups,dc=example,dc=com\n\
+             objectClass: groupOfUniqueNames\n\
+             objectClass: groupOfNames\n\
+             cn: Best Group\n\
+             member: uid=bob,ou=people,dc=example,dc=com\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_service_account() {
+        let mut mock = MockTestBackendHandler::new();
+        setup_default_schema(&mut mock);
+        mock.expect_list_users().times(1).return_once(|_, _| {
+            Ok(vec![UserAndGroups {
+                user: User {
+                    user_id: UserId::new("backup"),
+                    is_service_account: true,
+                    ..Default::default()
+                },
+                groups: None,
+            }])
+        });
+        mock.expect_list_groups()
+            .times(1)
+            .return_once(|_| Ok(Vec::new()));
+        assert_eq!(
+            export_ldif(&mock, "dc=example,dc=com").await.unwrap(),
+            "version: 1\n\
+             \n\
+             dn: uid=backup,ou=services,dc=example,dc=com\n\
+             objectClass: inetOrgPerson\n\
+             objectClass: posixAccount\n\
+             objectClass: mailAccount\n\
+             objectClass: person\n\
+             objectClass: customUserClass\n\
+             uid: backup\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_add_user_and_group() {
+        let mut mock = MockTestBackendHandler::new();
+        setup_default_schema(&mut mock);
+        mock.expect_create_user()
+            .with(eq(CreateUserRequest {
+                user_id: UserId::new("bob"),
+                email: "bob@bobmail.bob".into(),
+                display_name: Some("Bob".to_string()),
+                attributes: vec![
+                    Attribute {
+                        name: "last_name".into(),
+                        value: "Bobberson".to_string().into(),
+                    },
+                    Attribute {
+                        name: "avatar".into(),
+                        value: JpegPhoto::for_tests().into(),
+                    },
+                ],
+                legacy_password_hash: Some(
+                    LegacyPasswordHash::try_from("{SSHA}AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA").unwrap(),
+                ),
+                is_service_account: false,
+                allowed_sources: Vec::new(),
+            }))
+            .times(1)
+            .return_once(|_| Ok(()));
+        mock.expect_create_group()
+            .with(eq(CreateGroupRequest {
+                display_name: "Best Group".into(),
+                attributes: Vec::new(),
+            }))
+            .times(1)
+            .return_once(|_| Ok(GroupId(3)));
+        mock.expect_add_user_to_group()
+            .with(eq(UserId::new("bob")), eq(GroupId(3)))
+            .times(1)
+            .return_once(|_, _| Ok(()));
+        let mut input = "dn: dc=example,dc=com\nobjectClass: domain\n\n\
+                         dn: uid=bob,ou=people,dc=Example,dc=com\n\
+                         objectClass: inetOrgPerson\n\
+                         uid: bob\n\
+                         mail: bob@bobmail.bob\n\
+                         cn: Bob\n\
+                         sn: Bobberson\n\
+                         userPassword: {SSHA}AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA\n"
+            .to_string();
+        write_attribute(
+            &mut input,
+            "jpegPhoto",
+            &JpegPhoto::for_tests().into_bytes(),
+        );
+        input.push_str(
+            "\ndn: cn=Best Group,ou=groups,dc=example,dc=com\n\
+             objectClass: groupOfUniqueNames\n\
+             cn: Best Group\n\
+             member: uid=bob,ou=people,dc=example,dc=com\n",
+        );
+        let importer = LdifImporter::new(&mock, "dc=example,dc=com", vec![], vec![], false)
+            .await
+            .unwrap();
+        importer.import(parse_ldif(&input).unwrap()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_import_unknown_attribute() {
+        let mut mock = MockTestBackendHandler::new();
+        setup_default_schema(&mut mock);
+        let input = "dn: uid=bob,ou=people,dc=example,dc=com\n\
+                     mail: bob@bobmail.bob\n\
+                     homeDirectory: /home/bob\n";
+        let importer = LdifImporter::new(&mock, "dc=example,dc=com", vec![], vec![], false)
+            .await
+            .unwrap();
+        assert!(importer.import(parse_ldif(input).unwrap()).await.is_err());
+        let importer = LdifImporter::new(
+            &mock,
+            "dc=example,dc=com",
+            vec!["homedirectory".into()],
+            vec![],
+            true,
+        )
+        .await
+        .unwrap();
+        importer.import(parse_ldif(input).unwrap()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_import_modify_and_delete() {
+        let mut mock = MockTestBackendHandler::new();
+        setup_default_schema(&mut mock);
+        mock.expect_get_user_details()
+            .with(eq(UserId::new("bob")))
+            .times(1)
+            .return_once(|_| {
+                Ok(User {
+                    user_id: UserId::new("bob"),
+                    attributes: vec![Attribute {
+                        name: "first_name".into(),
+                        value: "Bob".to_string().into(),
+                    }],
+                    ..Default::default()
+                })
+            });
+        mock.expect_update_user()
+            .with(eq(UpdateUserRequest {
+                user_id: UserId::new("bob"),
+                email: Some("new@bobmail.bob".into()),
+                display_name: None,
+                delete_attributes: vec!["first_name".into()],
+                insert_attributes: Vec::new(),
+                allowed_sources: None,
+            }))
+            .times(1)
+            .return_once(|_| Ok(()));
+        mock.expect_list_groups()
+            .with(eq(Some(GroupRequestFilter::DisplayName(
+                "Best Group".into(),
+            ))))
+            .times(2)
+            .returning(|_| {
+                Ok(vec![make_group(
+                    "Best Group",
+                    vec![UserId::new("bob"), UserId::new("john")],
+                )])
+            });
+        mock.expect_add_user_to_group()
+            .with(eq(UserId::new("patrick")), eq(GroupId(3)))
+            .times(1)
+            .return_once(|_, _| Ok(()));
+        mock.expect_remove_user_from_group()
+            .with(eq(UserId::new("john")), eq(GroupId(3)))
+            .times(1)
+            .return_once(|_, _| Ok(()));
+        mock.expect_delete_group()
+            .with(eq(GroupId(3)))
+            .times(1)
+            .return_once(|_| Ok(()));
+        mock.expect_delete_user()
+            .with(eq(UserId::new("john")))
+            .times(1)
+            .return_once(|_| Ok(()));
+        let input = "dn: uid=bob,ou=people,dc=example,dc=com\n\
+                     changetype: modify\n\
+                     replace: mail\n\
+                     mail: new@bobmail.bob\n\
+                     -\n\
+                     delete: givenName\n\
+                     -\n\
+                     \n\
+                     dn: cn=Best Group,ou=groups,dc=example,dc=com\n\
+                     changetype: modify\n\
+                     add: member\n\
+                     member: patrick\n\
+                     -\n\
+                     delete: member\n\
+                     member: uid=john,ou=people,dc=example,dc=com\n\
+                     -\n\
+                     \n\
+                     dn: cn=Best Group,ou=groups,dc=example,dc=com\n\
+                     changetype: delete\n\
+                     \n\
+                     dn: uid=john,ou=people,dc=example,dc=com\n\
+                     changetype: delete\n";
+        let importer = LdifImporter::new(&mock, "dc=example,dc=com", vec![], vec![], false)
+            .await
+            .unwrap();
+        importer.import(parse_ldif(input).unwrap()).await.unwrap();
+    }
+
+    #[test]
+    fn test_apply_modification_matching_rule() {
+        let mut values = vec![b"Alice".to_vec(), b"Bob".to_vec()];
+        let case_ignore =
+            |v1: &Vec<u8>, v2: &Vec<u8>| MatchingRule::CaseIgnoreMatch.values_match(v1, v2);
+        apply_modification(
+            &mut values,
+            ModifyOperation::Add,
+            vec![b"ALICE".to_vec(), b"Carol".to_vec()],
+            case_ignore,
+        );
+        assert_eq!(
+            values,
+            vec![b"Alice".to_vec(), b"Bob".to_vec(), b"Carol".to_vec()]
+        );
+        apply_modification(
+            &mut values,
+            ModifyOperation::Delete,
+            vec![b"bob".to_vec()],
+            case_ignore,
+        );
+        assert_eq!(values, vec![b"Alice".to_vec(), b"Carol".to_vec()]);
+        apply_modification(
+            &mut values,
+            ModifyOperation::Delete,
+            vec![b"carol".to_vec()],
+            |v1, v2| MatchingRule::CaseExactMatch.values_match(v1, v2),
+        );
+        assert_eq!(values, vec![b"Alice".to_vec(), b"Carol".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_import_dry_run() {
+        let mut mock = MockTestBackendHandler::new();
+        setup_default_schema(&mut mock);
+        mock.expect_list_groups().returning(|_| Ok(Vec::new()));
+        let input = "dn: uid=bob,ou=people,dc=example,dc=com\n\
+                     mail: bob@bobmail.bob\n\
+                     \n\
+                     dn: cn=Best Group,ou=groups,dc=example,dc=com\n\
+                     member: uid=bob,ou=people,dc=example,dc=com\n\
+                     \n\
+                     dn: cn=Best Group,ou=groups,dc=example,dc=com\n\
+                     changetype: delete\n";
+        let importer = LdifImporter::new(&mock, "dc=example,dc=com", vec![], vec![], true)
+            .await
+            .unwrap();
+        importer.import(parse_ldif(input).unwrap()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_import_outside_of_base_dn() {
+        let mut mock = MockTestBackendHandler::new();
+        setup_default_schema(&mut mock);
+        let input = "dn: uid=bob,ou=people,dc=other,dc=com\nmail: bob@bobmail.bob\n";
+        let importer = LdifImporter::new(&mock, "dc=example,dc=com", vec![], vec![], false)
+            .await
+            .unwrap();
+        assert!(importer.import(parse_ldif(input).unwrap()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parse_escaped_entry_name() {
+        let mut mock = MockTestBackendHandler::new();
+        setup_default_schema(&mut mock);
+        let importer = LdifImporter::new(&mock, "dc=example,dc=com", vec![], vec![], true)
+            .await
+            .unwrap();
+        assert!(matches!(
+            importer.parse_entry_name(r"cn=Doe\, John\+Co,OU=Groups,DC=Example,dc=com"),
+            Ok(EntryName::Group(name)) if name.as_str() == "Doe, John+Co"
+        ));
+        assert!(matches!(
+            importer.parse_entry_name(
+                "0.9.2342.19200300.100.1.1=#0C03626F62,ou=people,dc=example,dc=com"
+            ),
+            Ok(EntryName::User(user_id)) if user_id.as_str() == "bob"
+        ));
+        assert!(matches!(
+            importer.parse_entry_name("uid=backup,ou=Services,dc=example,dc=com"),
+            Ok(EntryName::ServiceAccount(user_id)) if user_id.as_str() == "backup"
+        ));
+        assert!(
+            importer
+                .parse_entry_name("uid=bob+cn=bob,ou=people,dc=example,dc=com")
+                .is_err()
+        );
+        assert!(
+            importer
+                .parse_entry_name("uid=bob,ou=people;dc=example,dc=com")
+                .is_err()
+        );
+    }
+}
@@ -116,6 +116,32 @@ Please contact an administrator if you did not initiate the process."
     res
 }
 
+pub async fn send_account_expiration_warning_email(
+    username: &str,
+    to: &str,
+    expiration_date: &chrono::NaiveDateTime,
+    server_url: &url::Url,
+    options: &MailOptions,
+) -> Result<()> {
+    let to = to.parse()?;
+    let expiration_date = expiration_date.format("%Y-%m-%d %H:%M UTC");
+    let body = format!(
+        "Hello {username},
+Your account on {server_url} expires on {expiration_date}.
+After that date, you will not be able to log in anymore.
+
+Please contact an administrator if you need to keep access."
+    );
+    send_email(
+        to,
+        "[LLDAP] Your account is about to expire",
+        body,
+        options,
+        server_url,
+    )
+    .await
+}
+
 pub async fn send_test_email(to: Mailbox, options: &MailOptions) -> Result<()> {
     send_email(
         to,
@@ -4,6 +4,7 @@
 #![allow(clippy::blocks_in_conditions)]
 
 mod auth_service;
+mod authorized_keys;
 mod cli;
 mod configuration;
 mod database_string;
@@ -12,14 +13,20 @@ mod graphql_server;
 mod healthcheck;
 mod jwt_sql_tables;
 mod ldap_server;
+mod ldif;
 mod logging;
 mod mail;
+mod mfa_login;
 mod sql_tcp_backend_handler;
 mod tcp_backend_handler;
 mod tcp_server;
+mod webauthn;
 
 use crate::{
-    cli::{Command, RunOpts, TestEmailOpts},
+    cli::{
+        Command, CreateBreachedPasswordFilterOpts, ExportLdifOpts, ImportLdifOpts, RunOpts,
+        TestEmailOpts,
+    },
     configuration::{Configuration, compare_private_key_hashes},
     database_string::DatabaseUrl,
     db_cleaner::Scheduler,
@@ -32,8 +39,9 @@ use lldap_sql_backend_handler::{
     SqlBackendHandler, register_password,
     sql_tables::{self, get_private_key_info, set_private_key_info},
 };
+use lldap_validation::breached_passwords::write_bloom_filter;
 use sea_orm::{Database, DatabaseConnection};
-use std::time::Duration;
+use std::{path::Path, time::Duration};
 use tracing::{Instrument, Level, debug, error, info, instrument, span, warn};
 
 use lldap_domain::requests::{CreateGroupRequest, CreateUserRequest};
@@ -151,8 +159,25 @@ async fn set_up_server(config: Configuration) -> Result<ServerBuilder> {
             return Err(anyhow!("The private key encoding the passwords has changed since last successful startup. Changing the private key will invalidate all existing passwords. If you want to proceed, restart the server with the CLI arg --force-update-private-key=true or the env variable LLDAP_FORCE_UPDATE_PRIVATE_KEY=true. You probably also want --force-ldap-user-pass-reset / LLDAP_FORCE_LDAP_USER_PASS_RESET=true to reset the admin password to the value in the configuration.").context(e));
         }
     }
-    let backend_handler =
-        SqlBackendHandler::new(config.get_server_setup().clone(), sql_pool.clone());
+    let mut backend_handler = SqlBackendHandler::new(config.get_server_setup().clone(), sql_pool);
+    if config.ldap_bind_cache_ttl_seconds > 0 {
+        backend_handler = backend_handler
+            .with_bind_cache(Duration::from_secs(config.ldap_bind_cache_ttl_seconds));
+    }
+    if config.password_policy_options.max_age_days > 0 {
+        backend_handler = backend_handler.with_password_max_age(chrono::Duration::days(
+            config.password_policy_options.max_age_days.into(),
+        ));
+    }
+    backend_handler =
+        backend_handler.with_password_policy(config.password_policy_options.get_password_policy());
+    if let Some(breached_passwords) = config.password_policy_options.get_breached_passwords()? {
+        backend_handler = backend_handler.with_breached_passwords(breached_passwords);
+    }
+    backend_handler
+        .encrypt_legacy_totp_secrets()
+        .await
+        .context("while encrypting the TOTP secrets")?;
     ensure_group_exists(&backend_handler, "lldap_admin").await?;
     ensure_group_exists(&backend_handler, "lldap_password_manager").await?;
     ensure_group_exists(&backend_handler, "lldap_strict_readonly").await?;
@@ -210,11 +235,12 @@ async fn set_up_server(config: Configuration) -> Result<ServerBuilder> {
         actix_server::Server::build(),
     )
     .context("while binding the LDAP server")?;
-    let server_builder = tcp_server::build_tcp_server(&config, backend_handler, server_builder)
-        .await
-        .context("while binding the TCP server")?;
+    let server_builder =
+        tcp_server::build_tcp_server(&config, backend_handler.clone(), server_builder)
+            .await
+            .context("while binding the TCP server")?;
     // Run every hour.
-    let scheduler = Scheduler::new("0 0 * * * * *", sql_pool);
+    let scheduler = Scheduler::new("0 0 * * * * *", backend_handler, &config);
     scheduler.start();
     Ok(server_builder)
 }
@@ -280,6 +306,76 @@ async fn create_schema_command(opts: RunOpts) -> Result<()> {
     Ok(())
 }
 
+async fn export_ldif_command(opts: ExportLdifOpts) -> Result<()> {
+    debug!("CLI: {:#?}", &opts);
+    let output_file = opts.output_file.clone();
+    let config = configuration::init(opts)?;
+    logging::init(&config)?;
+    let sql_pool = setup_sql_tables(&config.database_url).await?;
+    let backend_handler = SqlBackendHandler::new(config.get_server_setup().clone(), sql_pool);
+    let ldif = ldif::export_ldif(&backend_handler, &config.ldap_base_dn)
+        .await
+        .context("while exporting the directory")?;
+    std::fs::write(&output_file, ldif).context(format!("while writing {output_file}"))?;
+    info!("Directory exported to {}", output_file);
+    Ok(())
+}
+
+async fn import_ldif_command(opts: ImportLdifOpts) -> Result<()> {
+    debug!("CLI: {:#?}", &opts);
+    let input_file = opts.input_file.clone();
+    let dry_run = opts.dry_run;
+    let config = configuration::init(opts)?;
+    logging::init(&config)?;
+    let input =
+        std::fs::read_to_string(&input_file).context(format!("while reading {input_file}"))?;
+    let records = ldif::parse_ldif(&input).context(format!("while parsing {input_file}"))?;
+    let sql_pool = setup_sql_tables(&config.database_url).await?;
+    let backend_handler = SqlBackendHandler::new(config.get_server_setup().clone(), sql_pool);
+    let num_records = records.len();
+    ldif::LdifImporter::new(
+        &backend_handler,
+        &config.ldap_base_dn,
+        config.ignored_user_attributes.clone(),
+        config.ignored_group_attributes.clone(),
+        dry_run,
+    )
+    .await?
+    .import(records)
+    .await?;
+    if dry_run {
+        info!(
+            "Dry run: {} records checked, nothing was changed",
+            num_records
+        );
+    } else {
+        info!("{} records imported", num_records);
+    }
+    Ok(())
+}
+
+fn create_breached_password_filter_command(opts: CreateBreachedPasswordFilterOpts) -> Result<()> {
+    if !(opts.false_positive_rate > 0.0 && opts.false_positive_rate < 1.0) {
+        bail!("The false positive rate must be between 0 and 1");
+    }
+    let output = std::fs::File::create(&opts.output_file)
+        .context(format!("while creating {}", opts.output_file))?;
+    let num_hashes = write_bloom_filter(
+        Path::new(&opts.input_file),
+        std::io::BufWriter::new(output),
+        opts.false_positive_rate,
+    )
+    .context(format!(
+        "while creating the filter from {}",
+        opts.input_file
+    ))?;
+    println!(
+        "{} password hashes written to {}",
+        num_hashes, opts.output_file
+    );
+    Ok(())
+}
+
 #[actix::main]
 async fn main() -> Result<()> {
     let cli_opts = cli::init();
@@ -291,5 +387,10 @@ async fn main() -> Result<()> {
         Command::HealthCheck(opts) => run_healthcheck(opts).await,
         Command::SendTestEmail(opts) => send_test_email_command(opts).await,
         Command::CreateSchema(opts) => create_schema_command(opts).await,
+        Command::ExportLdif(opts) => export_ldif_command(opts).await,
+        Command::ImportLdif(opts) => import_ldif_command(opts).await,
+        Command::CreateBreachedPasswordFilter(opts) => {
+            create_breached_password_filter_command(opts)
+        }
     }
 }
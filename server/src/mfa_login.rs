@@ -0,0 +1,107 @@
+//! The logins waiting for the second factor: the password was checked, but the JWT and refresh
+//! token are only issued once the TOTP or recovery code is verified.
+
+use chrono::{DateTime, Duration, Utc};
+use lldap_domain::types::UserId;
+use lldap_domain_handlers::handler::PasswordState;
+use rand::{Rng, distributions::Alphanumeric, rngs::OsRng};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+const MFA_TOKEN_LENGTH: usize = 32;
+/// How long the user has to enter the code after the password.
+const MFA_LOGIN_TIMEOUT_MINUTES: i64 = 5;
+/// How many wrong codes can be tried before having to enter the password again. The backend
+/// also locks the second factor of the user after too many wrong codes in a row, across logins.
+const MAX_MFA_ATTEMPTS: u8 = 5;
+
+struct PendingMfaLogin {
+    user_id: UserId,
+    password_state: PasswordState,
+    expiry: DateTime<Utc>,
+    attempts: u8,
+}
+
+/// A random token identifying a login in progress, sent to the client between two steps.
+pub(crate) fn generate_login_token() -> String {
+    OsRng
+        .sample_iter(Alphanumeric)
+        .map(char::from)
+        .take(MFA_TOKEN_LENGTH)
+        .collect()
+}
+
+/// Shared between all the HTTP workers, since the code can be checked by another worker than
+/// the password.
+#[derive(Clone, Default)]
+pub(crate) struct PendingMfaLogins(Arc<Mutex<HashMap<String, PendingMfaLogin>>>);
+
+impl PendingMfaLogins {
+    /// Records that the user entered the right password, and returns the token to send back with
+    /// the code.
+    pub fn start(&self, user_id: UserId, password_state: PasswordState) -> String {
+        let token = generate_login_token();
+        let now = Utc::now();
+        let mut logins = self.0.lock().unwrap();
+        logins.retain(|_, login| login.expiry > now);
+        logins.insert(
+            token.clone(),
+            PendingMfaLogin {
+                user_id,
+                password_state,
+                expiry: now + Duration::minutes(MFA_LOGIN_TIMEOUT_MINUTES),
+                attempts: 0,
+            },
+        );
+        token
+    }
+
+    /// Counts an attempt at entering the code, and returns the user it is for. `None` if the
+    /// token is unknown, expired or has no attempts left.
+    pub fn attempt(&self, token: &str) -> Option<(UserId, PasswordState)> {
+        let mut logins = self.0.lock().unwrap();
+        let login = logins.get_mut(token)?;
+        if login.expiry <= Utc::now() || login.attempts >= MAX_MFA_ATTEMPTS {
+            logins.remove(token);
+            return None;
+        }
+        login.attempts += 1;
+        Some((login.user_id.clone(), login.password_state))
+    }
+
+    /// The code was right: the token cannot be used again.
+    pub fn finish(&self, token: &str) {
+        self.0.lock().unwrap().remove(token);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_mfa_login() {
+        let logins = PendingMfaLogins::default();
+        let token = logins.start(UserId::new("bob"), PasswordState::MustChange);
+        assert_eq!(token.len(), MFA_TOKEN_LENGTH);
+        assert_eq!(
+            logins.attempt(&token),
+            Some((UserId::new("bob"), PasswordState::MustChange))
+        );
+        logins.finish(&token);
+        assert_eq!(logins.attempt(&token), None);
+        assert_eq!(logins.attempt("unknown"), None);
+    }
+
+    #[test]
+    fn test_pending_mfa_login_max_attempts() {
+        let logins = PendingMfaLogins::default();
+        let token = logins.start(UserId::new("bob"), PasswordState::Valid);
+        for _ in 0..MAX_MFA_ATTEMPTS {
+            assert!(logins.attempt(&token).is_some());
+        }
+        assert_eq!(logins.attempt(&token), None);
+    }
+}
@@ -1,4 +1,5 @@
 pub mod auth_service;
+pub mod authorized_keys;
 pub mod cli;
 pub mod configuration;
 pub mod database_string;
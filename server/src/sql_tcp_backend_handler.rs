@@ -6,7 +6,7 @@ use lldap_domain_model::{
     error::*,
     model::{self, JwtRefreshStorageColumn, JwtStorageColumn, PasswordResetTokensColumn},
 };
-use lldap_sql_backend_handler::SqlBackendHandler;
+use lldap_sql_backend_handler::{SqlBackendHandler, active_user_condition};
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, QuerySelect,
     sea_query::{Cond, Expr},
@@ -27,8 +27,8 @@ fn gen_random_string(len: usize) -> String {
 #[async_trait]
 impl TcpBackendHandler for SqlBackendHandler {
     #[instrument(skip_all, level = "debug")]
-    async fn get_jwt_blacklist(&self) -> anyhow::Result<HashSet<u64>> {
-        Ok(model::JwtStorage::find()
+    async fn load_jwt_blacklist(&self) -> anyhow::Result<()> {
+        let jwt_blacklist = model::JwtStorage::find()
             .select_only()
             .column(JwtStorageColumn::JwtHash)
             .filter(JwtStorageColumn::Blacklisted.eq(true))
@@ -37,7 +37,9 @@ impl TcpBackendHandler for SqlBackendHandler {
             .await?
             .into_iter()
             .map(|m| m.0 as u64)
-            .collect::<HashSet<u64>>())
+            .collect::<HashSet<u64>>();
+        *self.jwt_blacklist().write().unwrap() = jwt_blacklist;
+        Ok(())
     }
 
     #[instrument(skip_all, level = "debug")]
@@ -88,6 +90,8 @@ impl TcpBackendHandler for SqlBackendHandler {
         Ok(
             model::JwtRefreshStorage::find_by_id(refresh_token_hash as i64)
                 .filter(JwtRefreshStorageColumn::UserId.eq(user))
+                .inner_join(model::User)
+                .filter(active_user_condition())
                 .one(self.pool())
                 .await?
                 .is_some(),
@@ -95,7 +99,7 @@ impl TcpBackendHandler for SqlBackendHandler {
     }
 
     #[instrument(skip_all, level = "debug")]
-    async fn blacklist_jwts(&self, user: &UserId) -> Result<HashSet<u64>> {
+    async fn blacklist_jwts(&self, user: &UserId) -> Result<()> {
         debug!(?user);
         let valid_tokens = model::JwtStorage::find()
             .select_only()
@@ -116,7 +120,12 @@ impl TcpBackendHandler for SqlBackendHandler {
             .filter(JwtStorageColumn::UserId.eq(user))
             .exec(self.pool())
             .await?;
-        Ok(valid_tokens)
+        self.jwt_blacklist().write().unwrap().extend(valid_tokens);
+        Ok(())
+    }
+
+    fn is_jwt_blacklisted(&self, jwt_hash: u64) -> bool {
+        self.jwt_blacklist().read().unwrap().contains(&jwt_hash)
     }
 
     #[instrument(skip_all, level = "debug")]
@@ -131,11 +140,12 @@ impl TcpBackendHandler for SqlBackendHandler {
     async fn start_password_reset(&self, user: &UserId) -> Result<Option<String>> {
         debug!(?user);
         if model::User::find_by_id(user.clone())
+            .filter(active_user_condition())
             .one(self.pool())
             .await?
             .is_none()
         {
-            debug!("User not found");
+            debug!("User not found, disabled or expired");
             return Ok(None);
         }
 
@@ -156,6 +166,8 @@ impl TcpBackendHandler for SqlBackendHandler {
     async fn get_user_id_for_password_reset_token(&self, token: &str) -> Result<UserId> {
         Ok(model::PasswordResetTokens::find_by_id(token.to_owned())
             .filter(PasswordResetTokensColumn::ExpiryDate.gt(chrono::Utc::now().naive_utc()))
+            .inner_join(model::User)
+            .filter(active_user_condition())
             .one(self.pool())
             .await?
             .ok_or_else(|| DomainError::EntityNotFound("Invalid reset token".to_owned()))?
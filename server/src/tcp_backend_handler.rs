@@ -2,11 +2,11 @@ use async_trait::async_trait;
 use chrono::NaiveDateTime;
 use lldap_domain::types::UserId;
 use lldap_domain_model::error::Result;
-use std::collections::HashSet;
 
 #[async_trait]
 pub trait TcpBackendHandler: Sync {
-    async fn get_jwt_blacklist(&self) -> anyhow::Result<HashSet<u64>>;
+    /// Fills the in-memory set of revoked JWTs from the DB.
+    async fn load_jwt_blacklist(&self) -> anyhow::Result<()>;
     async fn create_refresh_token(&self, user: &UserId) -> Result<(String, chrono::Duration)>;
     async fn register_jwt(
         &self,
@@ -15,11 +15,14 @@ pub trait TcpBackendHandler: Sync {
         expiry_date: NaiveDateTime,
     ) -> Result<()>;
     async fn check_token(&self, refresh_token_hash: u64, user: &UserId) -> Result<bool>;
-    async fn blacklist_jwts(&self, user: &UserId) -> Result<HashSet<u64>>;
+    async fn blacklist_jwts(&self, user: &UserId) -> Result<()>;
+    /// Whether the JWT was revoked, from the in-memory set: it is updated on each revocation,
+    /// whichever server worker handles it.
+    fn is_jwt_blacklisted(&self, jwt_hash: u64) -> bool;
     async fn delete_refresh_token(&self, refresh_token_hash: u64) -> Result<()>;
 
     /// Request a token to reset a user's password.
-    /// If the user doesn't exist, returns `Ok(None)`, otherwise `Ok(Some(token))`.
+    /// If the user doesn't exist or is disabled, returns `Ok(None)`, otherwise `Ok(Some(token))`.
     async fn start_password_reset(&self, user: &UserId) -> Result<Option<String>>;
 
     /// Get the user ID associated with a password reset token.
@@ -2,7 +2,9 @@ use crate::{
     auth_service,
     configuration::{Configuration, MailOptions},
     logging::CustomRootSpanBuilder,
+    mfa_login::PendingMfaLogins,
     tcp_backend_handler::*,
+    webauthn::{PendingWebauthnCeremonies, build_webauthn},
 };
 use actix_files::Files;
 use actix_http::{HttpServiceBuilder, header};
@@ -12,14 +14,19 @@ use actix_web::{App, HttpResponse, Responder, dev::AppConfig, guard, web};
 use anyhow::{Context, Result};
 use hmac::Hmac;
 use lldap_access_control::{AccessControlledBackendHandler, ReadonlyBackendHandler};
-use lldap_domain_handlers::handler::{BackendHandler, LoginHandler};
+use lldap_domain::types::GroupName;
+use lldap_domain_handlers::handler::{
+    BackendHandler, LoginHandler, MfaBackendHandler, WebauthnBackendHandler,
+};
 use lldap_domain_model::error::DomainError;
 use lldap_opaque_handler::OpaqueHandler;
+use lldap_validation::{breached_passwords::BreachedPasswordList, password::PasswordPolicy};
 use sha2::Sha512;
 use std::collections::HashSet;
 use std::path::PathBuf;
-use std::sync::RwLock;
+use std::sync::Arc;
 use tracing::{info, warn};
+use webauthn_rs::Webauthn;
 
 async fn index<Backend>(data: web::Data<AppState<Backend>>) -> actix_web::Result<impl Responder> {
     let mut file = std::fs::read_to_string(data.assets_path.join("index.html"))?;
@@ -64,7 +71,8 @@ pub(crate) fn error_to_http_response(error: TcpError) -> HttpResponse {
             | DomainError::UnknownCryptoError(_) => HttpResponse::InternalServerError(),
             DomainError::Base64DecodeError(_)
             | DomainError::BinarySerializationError(_)
-            | DomainError::EntityNotFound(_) => HttpResponse::BadRequest(),
+            | DomainError::EntityNotFound(_)
+            | DomainError::PasswordPolicyError(_) => HttpResponse::BadRequest(),
         },
         TcpError::BadRequest(_) => HttpResponse::BadRequest(),
         TcpError::NotFoundError(_) => HttpResponse::NotFound(),
@@ -109,17 +117,26 @@ async fn wasm_handler_compressed<Backend>(
 async fn get_settings<Backend>(data: web::Data<AppState<Backend>>) -> HttpResponse {
     HttpResponse::Ok().json(lldap_frontend_options::Options {
         password_reset_enabled: data.mail_options.enable_password_reset,
+        password_policy: data.password_policy.clone(),
+        breached_password_check_enabled: data.breached_passwords.is_some(),
+        webauthn_enabled: data.webauthn.is_some(),
     })
 }
 
+#[allow(clippy::too_many_arguments)]
 fn http_config<Backend>(
     cfg: &mut web::ServiceConfig,
     backend_handler: Backend,
     jwt_secret: secstr::SecUtf8,
-    jwt_blacklist: HashSet<u64>,
     server_url: url::Url,
     assets_path: PathBuf,
     mail_options: MailOptions,
+    password_policy: PasswordPolicy,
+    breached_passwords: Option<BreachedPasswordList>,
+    mfa_required_groups: HashSet<GroupName>,
+    pending_mfa_logins: PendingMfaLogins,
+    webauthn: Option<Arc<Webauthn>>,
+    pending_webauthn_ceremonies: PendingWebauthnCeremonies,
 ) where
     Backend: TcpBackendHandler + BackendHandler + LoginHandler + OpaqueHandler + Clone + 'static,
 {
@@ -127,10 +144,15 @@ fn http_config<Backend>(
     cfg.app_data(web::Data::new(AppState::<Backend> {
         backend_handler: AccessControlledBackendHandler::new(backend_handler),
         jwt_key: hmac::Mac::new_from_slice(jwt_secret.unsecure().as_bytes()).unwrap(),
-        jwt_blacklist: RwLock::new(jwt_blacklist),
         server_url,
         assets_path: assets_path.clone(),
         mail_options,
+        password_policy,
+        breached_passwords,
+        mfa_required_groups,
+        pending_mfa_logins,
+        webauthn,
+        pending_webauthn_ceremonies,
     }))
     .route(
         "/health",
@@ -145,7 +167,8 @@ fn http_config<Backend>(
     .service(
         web::scope("/api")
             .wrap(auth_service::CookieToHeaderTranslatorFactory)
-            .configure(crate::graphql_server::configure_endpoint::<Backend>),
+            .configure(crate::graphql_server::configure_endpoint::<Backend>)
+            .configure(crate::authorized_keys::configure_endpoint::<Backend>),
     )
     .service(
         web::resource("/pkg/lldap_app_bg.wasm.gz")
@@ -171,10 +194,16 @@ fn http_config<Backend>(
 pub(crate) struct AppState<Backend> {
     pub backend_handler: AccessControlledBackendHandler<Backend>,
     pub jwt_key: Hmac<Sha512>,
-    pub jwt_blacklist: RwLock<HashSet<u64>>,
     pub server_url: url::Url,
     pub assets_path: PathBuf,
     pub mail_options: MailOptions,
+    pub password_policy: PasswordPolicy,
+    pub breached_passwords: Option<BreachedPasswordList>,
+    pub mfa_required_groups: HashSet<GroupName>,
+    pub pending_mfa_logins: PendingMfaLogins,
+    /// `None` if the server URL cannot be used as a WebAuthn relying party.
+    pub webauthn: Option<Arc<Webauthn>>,
+    pub pending_webauthn_ceremonies: PendingWebauthnCeremonies,
 }
 
 impl<Backend: BackendHandler> AppState<Backend> {
@@ -197,6 +226,16 @@ impl<Backend: LoginHandler> AppState<Backend> {
         self.backend_handler.unsafe_get_handler()
     }
 }
+impl<Backend: MfaBackendHandler> AppState<Backend> {
+    pub fn get_mfa_handler(&self) -> &(impl MfaBackendHandler + use<Backend>) {
+        self.backend_handler.unsafe_get_handler()
+    }
+}
+impl<Backend: WebauthnBackendHandler> AppState<Backend> {
+    pub fn get_webauthn_handler(&self) -> &(impl WebauthnBackendHandler + use<Backend>) {
+        self.backend_handler.unsafe_get_handler()
+    }
+}
 
 pub async fn build_tcp_server<Backend>(
     config: &Configuration,
@@ -207,13 +246,25 @@ where
     Backend: TcpBackendHandler + BackendHandler + LoginHandler + OpaqueHandler + Clone + 'static,
 {
     let jwt_secret = config.jwt_secret.clone().unwrap();
-    let jwt_blacklist = backend_handler
-        .get_jwt_blacklist()
+    backend_handler
+        .load_jwt_blacklist()
         .await
-        .context("while getting the jwt blacklist")?;
+        .context("while loading the jwt blacklist")?;
     let server_url = config.http_url.0.clone();
     let assets_path = config.assets_path.clone();
     let mail_options = config.smtp_options.clone();
+    let password_policy = config.password_policy_options.get_password_policy();
+    let breached_passwords = config.password_policy_options.get_breached_passwords()?;
+    let mfa_required_groups = config
+        .mfa_options
+        .required_groups
+        .iter()
+        .cloned()
+        .collect::<HashSet<_>>();
+    // Created outside of the workers, so that the code can be checked by any of them.
+    let pending_mfa_logins = PendingMfaLogins::default();
+    let webauthn = build_webauthn(&server_url);
+    let pending_webauthn_ceremonies = PendingWebauthnCeremonies::default();
     let verbose = config.verbose;
     if !assets_path.join("index.html").exists() {
         warn!(
@@ -229,10 +280,15 @@ where
             move || {
                 let backend_handler = backend_handler.clone();
                 let jwt_secret = jwt_secret.clone();
-                let jwt_blacklist = jwt_blacklist.clone();
                 let server_url = server_url.clone();
                 let assets_path = assets_path.clone();
                 let mail_options = mail_options.clone();
+                let password_policy = password_policy.clone();
+                let breached_passwords = breached_passwords.clone();
+                let mfa_required_groups = mfa_required_groups.clone();
+                let pending_mfa_logins = pending_mfa_logins.clone();
+                let webauthn = webauthn.clone();
+                let pending_webauthn_ceremonies = pending_webauthn_ceremonies.clone();
                 HttpServiceBuilder::default()
                     .finish(map_config(
                         App::new()
@@ -245,10 +301,15 @@ where
                                     cfg,
                                     backend_handler,
                                     jwt_secret,
-                                    jwt_blacklist,
                                     server_url,
                                     assets_path,
                                     mail_options,
+                                    password_policy,
+                                    breached_passwords,
+                                    mfa_required_groups,
+                                    pending_mfa_logins,
+                                    webauthn,
+                                    pending_webauthn_ceremonies,
                                 )
                             }),
                         |_| AppConfig::default(),
@@ -0,0 +1,121 @@
+//! The WebAuthn relying party, and the registration and login ceremonies in progress: the
+//! challenge sent to the browser is kept here until it answers with the signed credential.
+
+use crate::mfa_login::generate_login_token;
+use chrono::{DateTime, Duration, Utc};
+use lldap_domain::types::UserId;
+use lldap_domain_handlers::handler::PasswordState;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tracing::warn;
+use webauthn_rs::{
+    Webauthn, WebauthnBuilder,
+    prelude::{DiscoverableAuthentication, PasskeyAuthentication, PasskeyRegistration},
+};
+
+/// How long the user has to touch their security key.
+const CEREMONY_TIMEOUT_MINUTES: i64 = 5;
+
+/// The relying party is the domain of `http_url`: the credentials registered with it cannot be
+/// used with another URL. Returns `None`, disabling WebAuthn, if the URL has no domain.
+pub(crate) fn build_webauthn(http_url: &url::Url) -> Option<Arc<Webauthn>> {
+    let rp_id = http_url.host_str()?;
+    if http_url.scheme() != "https" && rp_id != "localhost" {
+        warn!(
+            "The http_url is not using https: browsers will refuse to use passkeys and security keys"
+        );
+    }
+    match WebauthnBuilder::new(rp_id, http_url).and_then(|builder| builder.rp_name("LLDAP").build())
+    {
+        Ok(webauthn) => Some(Arc::new(webauthn)),
+        Err(e) => {
+            warn!(
+                "Could not set up WebAuthn for {}, disabling it: {}",
+                http_url, e
+            );
+            None
+        }
+    }
+}
+
+pub(crate) enum WebauthnCeremony {
+    /// A logged-in user adding a security key.
+    Registration {
+        user_id: UserId,
+        state: PasskeyRegistration,
+    },
+    /// The password was checked, the security key is the second factor.
+    SecondFactor {
+        mfa_token: String,
+        user_id: UserId,
+        password_state: PasswordState,
+        state: PasskeyAuthentication,
+    },
+    /// No password: the passkey identifies the user.
+    Passwordless { state: DiscoverableAuthentication },
+}
+
+struct PendingCeremony {
+    ceremony: WebauthnCeremony,
+    expiry: DateTime<Utc>,
+}
+
+/// Shared between all the HTTP workers, like the [`crate::mfa_login::PendingMfaLogins`].
+#[derive(Clone, Default)]
+pub(crate) struct PendingWebauthnCeremonies(Arc<Mutex<HashMap<String, PendingCeremony>>>);
+
+impl PendingWebauthnCeremonies {
+    /// Returns the token to send back with the credential.
+    pub fn start(&self, ceremony: WebauthnCeremony) -> String {
+        let token = generate_login_token();
+        let now = Utc::now();
+        let mut ceremonies = self.0.lock().unwrap();
+        ceremonies.retain(|_, pending| pending.expiry > now);
+        ceremonies.insert(
+            token.clone(),
+            PendingCeremony {
+                ceremony,
+                expiry: now + Duration::minutes(CEREMONY_TIMEOUT_MINUTES),
+            },
+        );
+        token
+    }
+
+    /// Each challenge can only be answered once. `None` if the token is unknown or expired.
+    pub fn take(&self, token: &str) -> Option<WebauthnCeremony> {
+        self.0
+            .lock()
+            .unwrap()
+            .remove(token)
+            .filter(|pending| pending.expiry > Utc::now())
+            .map(|pending| pending.ceremony)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_webauthn() {
+        assert!(build_webauthn(&url::Url::parse("https://ldap.example.com").unwrap()).is_some());
+        assert!(build_webauthn(&url::Url::parse("http://localhost:17170").unwrap()).is_some());
+    }
+
+    #[test]
+    fn test_pending_webauthn_ceremony() {
+        let webauthn =
+            build_webauthn(&url::Url::parse("https://ldap.example.com").unwrap()).unwrap();
+        let (_, state) = webauthn.start_discoverable_authentication().unwrap();
+        let ceremonies = PendingWebauthnCeremonies::default();
+        let token = ceremonies.start(WebauthnCeremony::Passwordless { state });
+        assert!(matches!(
+            ceremonies.take(&token),
+            Some(WebauthnCeremony::Passwordless { .. })
+        ));
+        assert!(ceremonies.take(&token).is_none());
+        assert!(ceremonies.take("unknown").is_none());
+    }
+}
@@ -108,6 +108,7 @@ impl LLDAPFixture {
                     first_name: None,
                     last_name: None,
                     attributes: None,
+                    legacy_password_hash: None,
                 },
             },
         )
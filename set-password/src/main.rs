@@ -1,8 +1,15 @@
 use std::env;
 
-use anyhow::{Context, Result, bail, ensure};
+use anyhow::{Context, Result, anyhow, bail, ensure};
 use clap::Parser;
 use lldap_auth::{opaque, registration};
+use lldap_frontend_options::Options;
+use lldap_validation::{
+    breached_passwords::{
+        BREACHED_PASSWORD_ERROR, BreachedPasswordRange, get_hash_prefix, get_password_hash,
+    },
+    password::validate_password,
+};
 use reqwest::Url;
 use serde::Serialize;
 
@@ -33,7 +40,8 @@ pub struct CliOpts {
     #[clap(short, long)]
     pub password: Option<String>,
 
-    /// Bypass password requirements such as minimum length. Unsafe.
+    /// Bypass the password policy of the server, such as the minimum length or the breached
+    /// passwords. Unsafe. The password history is still checked.
     #[clap(long)]
     pub bypass_password_policy: bool,
 }
@@ -58,7 +66,30 @@ fn get_token(base_url: &Url, username: &str, password: &str) -> Result<String> {
         )
         .send()?
         .error_for_status()?;
-    Ok(serde_json::from_str::<lldap_auth::login::ServerLoginResponse>(&response.text()?)?.token)
+    match serde_json::from_str::<lldap_auth::login::ServerLoginResult>(&response.text()?)? {
+        lldap_auth::login::ServerLoginResult::Success(response) => Ok(response.token),
+        lldap_auth::login::ServerLoginResult::MfaRequired(_) => {
+            bail!("The admin user has a second factor enabled, pass a token instead")
+        }
+    }
+}
+
+fn get_settings(base_url: &Url) -> Result<Options> {
+    let response =
+        reqwest::blocking::get(append_to_url(base_url, "settings"))?.error_for_status()?;
+    Ok(serde_json::from_str::<Options>(&response.text()?)?)
+}
+
+/// Only the first characters of the hash are sent to the server, which returns all the breached
+/// passwords starting with them.
+fn is_password_breached(base_url: &Url, password: &str) -> Result<bool> {
+    let hash = get_password_hash(password);
+    let response = reqwest::blocking::get(append_to_url(
+        base_url,
+        &format!("auth/breached_passwords/{}", get_hash_prefix(&hash)),
+    ))?
+    .error_for_status()?;
+    Ok(serde_json::from_str::<BreachedPasswordRange>(&response.text()?)?.contains(&hash))
 }
 
 fn call_server(url: Url, token: &str, body: impl Serialize) -> Result<String> {
@@ -108,14 +139,22 @@ fn main() -> Result<()> {
         None => env::var("LLDAP_USER_PASSWORD").unwrap_or_default(),
     };
 
-    ensure!(
-        opts.bypass_password_policy || password.len() >= 8,
-        "New password is too short, expected at least 8 characters"
-    );
     ensure!(
         opts.base_url.scheme() == "http" || opts.base_url.scheme() == "https",
         "Base URL should start with `http://` or `https://`"
     );
+    let settings = get_settings(&opts.base_url).context("While fetching the password policy")?;
+    let password_policy = settings.password_policy;
+    if !opts.bypass_password_policy {
+        validate_password(&password_policy, &password, &[&opts.username])
+            .map_err(|errors| anyhow!("Invalid password: {}", errors.join(", ")))?;
+        ensure!(
+            !settings.breached_password_check_enabled
+                || !is_password_breached(&opts.base_url, &password)
+                    .context("While checking the breached passwords")?,
+            BREACHED_PASSWORD_ERROR
+        );
+    }
     let token = match (opts.token.as_ref(), opts.admin_password.as_ref()) {
         (Some(token), _) => token.clone(),
         (None, Some(password)) => {
@@ -140,9 +179,15 @@ fn main() -> Result<()> {
         &mut rng,
     )
     .context("Error during password change")?;
+    let password_fingerprint = res
+        .password_history_salt
+        .map(|salt| opaque::password_fingerprint(&salt, password.as_bytes()))
+        .transpose()
+        .context("Error during password change")?;
     let req = registration::ClientRegistrationFinishRequest {
         server_data: res.server_data,
         registration_upload: registration_finish.message,
+        password_fingerprint,
     };
 
     register_finish(&opts.base_url, &token, req)?;